@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE institutions DROP COLUMN IF EXISTS status;
+ALTER TABLE organizations DROP COLUMN IF EXISTS status;
+ALTER TABLE customers DROP COLUMN IF EXISTS status;
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS trigger_customer_features_update ON customer_features;
+DROP FUNCTION IF EXISTS customer_features_update;
+DROP TABLE IF EXISTS customer_features;
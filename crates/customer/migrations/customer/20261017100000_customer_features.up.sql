@@ -0,0 +1,41 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS customer_features
+(
+    customer_id    BIGINT NOT NULL,
+    name           VARCHAR(255) NOT NULL,
+    enabled        BOOLEAN NOT NULL,
+    updated_by     uuid,
+    updated_at     TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY(customer_id, name),
+    FOREIGN KEY(customer_id)
+       REFERENCES customers(id)
+       ON DELETE CASCADE
+);
+
+CREATE OR REPLACE FUNCTION customer_features_update() RETURNS TRIGGER AS $$
+    DECLARE
+    output TEXT;
+
+    BEGIN
+    IF (TG_OP = 'DELETE') THEN
+      output = '{ "op": "' || TG_OP || '", "old": ' || ROW_TO_JSON(OLD)::text || '}';
+    ELSE
+      IF (TG_OP = 'UPDATE') THEN
+        output = '{ "op": "' || TG_OP || '", "new": ' || ROW_TO_JSON(NEW)::text || ', "old": ' || ROW_TO_JSON(OLD)::text || '}';
+      ELSE
+        output = '{ "op": "' || TG_OP || '", "new": ' || ROW_TO_JSON(NEW)::text || '}';
+      END IF;
+    END IF;
+
+    PERFORM pg_notify('customer_features_update', output);
+
+    -- Returning null because it is an after trigger.
+    RETURN NULL;
+    END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE TRIGGER trigger_customer_features_update
+  AFTER INSERT OR UPDATE OR DELETE
+  ON customer_features
+  FOR EACH ROW
+  EXECUTE PROCEDURE customer_features_update();
@@ -0,0 +1,259 @@
+//! Audit log of customer, organization, institution and user mutations.
+//!
+//! Entries are stored in the [`AUDIT_LOG_COLLECTION`] collection of the
+//! object database and can be queried by admins with `qmAuditLog`.
+
+use std::collections::BTreeSet;
+
+use async_graphql::{Context, Enum, Json, Object, ResultExt, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use qm_entity::err;
+use qm_entity::ids::InfraContext;
+use qm_entity::model::ListFilter;
+use qm_mongodb::bson::{doc, Document};
+use qm_mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::schema::auth::AuthCtx;
+
+pub const AUDIT_LOG_COLLECTION: &str = "qm_audit_log";
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmAuditEntity")]
+pub enum AuditEntity {
+    Customer,
+    Organization,
+    Institution,
+    User,
+    Group,
+}
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmAuditAction")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Remove,
+}
+
+/// A top level field which differs between the state before and after a
+/// mutation.
+#[derive(Debug, Clone, PartialEq, SimpleObject, Serialize, Deserialize)]
+pub struct QmAuditChange {
+    pub field: String,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QmAuditEntry {
+    pub entity: AuditEntity,
+    pub entity_id: String,
+    pub action: AuditAction,
+    pub actor: Option<String>,
+    pub context: Option<String>,
+    pub changes: Vec<QmAuditChange>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmAuditLog {
+    pub items: Vec<QmAuditEntry>,
+    pub limit: Option<i64>,
+    pub total: Option<i64>,
+    pub page: Option<i64>,
+}
+
+impl QmAuditEntry {
+    pub fn new(entity: AuditEntity, action: AuditAction, entity_id: impl ToString) -> Self {
+        Self {
+            entity,
+            entity_id: entity_id.to_string(),
+            action,
+            actor: None,
+            context: None,
+            changes: vec![],
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn with_actor(mut self, actor: Option<&impl ToString>) -> Self {
+        self.actor = actor.map(ToString::to_string);
+        self
+    }
+
+    pub fn with_context(mut self, context: Option<InfraContext>) -> Self {
+        self.context = context.map(|context| context.to_string());
+        self
+    }
+
+    /// Records the fields which differ between `before` and `after`.
+    pub fn with_diff<B, A>(mut self, before: Option<&B>, after: Option<&A>) -> Self
+    where
+        B: Serialize,
+        A: Serialize,
+    {
+        let before = before.and_then(|v| serde_json::to_value(v).ok());
+        let after = after.and_then(|v| serde_json::to_value(v).ok());
+        self.changes = diff(before.as_ref(), after.as_ref());
+        self
+    }
+
+    /// Stores the entry, failures are logged and don't fail the mutation.
+    pub async fn record(self, db: &qm_mongodb::DB) {
+        if let Err(err) = db
+            .get()
+            .collection::<Self>(AUDIT_LOG_COLLECTION)
+            .insert_one(&self)
+            .await
+        {
+            tracing::error!(
+                "unable to record audit entry for {:?} {}: {err:#}",
+                self.entity,
+                self.entity_id
+            );
+        }
+    }
+}
+
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Vec<QmAuditChange> {
+    let field =
+        |v: Option<&Value>, key: &str| v.and_then(|v| v.get(key)).filter(|v| !v.is_null()).cloned();
+    let keys: BTreeSet<&String> = before
+        .and_then(Value::as_object)
+        .into_iter()
+        .chain(after.and_then(Value::as_object))
+        .flat_map(|v| v.keys())
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let before = field(before, key);
+            let after = field(after, key);
+            (before != after).then(|| QmAuditChange {
+                field: key.clone(),
+                before: before.map(Json),
+                after: after.map(Json),
+            })
+        })
+        .collect()
+}
+
+pub async fn list(
+    db: &qm_mongodb::DB,
+    query: Document,
+    filter: Option<ListFilter>,
+) -> qm_mongodb::error::Result<QmAuditLog> {
+    let collection = db.get().collection::<QmAuditEntry>(AUDIT_LOG_COLLECTION);
+    let limit = filter.as_ref().and_then(|f| f.limit).unwrap_or(100) as i64;
+    let page = filter.as_ref().and_then(|f| f.page).unwrap_or(0) as i64;
+    let total = collection.count_documents(query.clone()).await?;
+    let options = FindOptions::builder()
+        .sort(doc! { "createdAt": -1 })
+        .limit(limit)
+        .skip((page * limit) as u64)
+        .build();
+    let items = collection
+        .find(query)
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(QmAuditLog {
+        items,
+        limit: Some(limit),
+        total: Some(total as i64),
+        page: Some(page),
+    })
+}
+
+pub struct AuditQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for AuditQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    AuditQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    async fn qm_audit_log(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ListFilter>,
+        entity: Option<AuditEntity>,
+        entity_id: Option<String>,
+        context: Option<InfraContext>,
+    ) -> async_graphql::FieldResult<QmAuditLog> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        if !auth_ctx.is_admin {
+            return err!(unauthorized(&auth_ctx.auth)).extend();
+        }
+        let mut query = Document::new();
+        if let Some(entity) = entity {
+            query.insert("entity", qm_mongodb::bson::to_bson(&entity)?);
+        }
+        if let Some(entity_id) = entity_id {
+            query.insert("entityId", entity_id);
+        }
+        if let Some(context) = context {
+            query.insert("context", context.to_string());
+        }
+        Ok(list(
+            AsRef::<qm_mongodb::DB>::as_ref(auth_ctx.store),
+            query,
+            filter,
+        )
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff() {
+        let before = json!({ "id": 1, "name": "a", "updatedBy": null });
+        let after = json!({ "id": 1, "name": "b", "updatedBy": "u" });
+        assert_eq!(
+            diff(Some(&before), Some(&after)),
+            vec![
+                QmAuditChange {
+                    field: "name".to_string(),
+                    before: Some(Json(json!("a"))),
+                    after: Some(Json(json!("b"))),
+                },
+                QmAuditChange {
+                    field: "updatedBy".to_string(),
+                    before: None,
+                    after: Some(Json(json!("u"))),
+                },
+            ]
+        );
+        let removed = diff(Some(&before), None);
+        assert_eq!(removed.len(), 2);
+        assert!(removed.iter().all(|change| change.after.is_none()));
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+
+use async_graphql::Enum;
+use futures::Stream;
+use qm_entity::ids::{InfraContext, InfraId};
+use serde::de::DeserializeOwned;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::update::{Op, Payload};
+
+pub const EVENT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq)]
+#[graphql(name = "QmChangeOp")]
+pub enum ChangeOp {
+    Created,
+    Updated,
+    Removed,
+}
+
+impl From<Op> for ChangeOp {
+    fn from(value: Op) -> Self {
+        match value {
+            Op::Insert => Self::Created,
+            Op::Update => Self::Updated,
+            Op::Delete => Self::Removed,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum CacheEvent {
+    Customer {
+        op: ChangeOp,
+        id: InfraId,
+    },
+    User {
+        op: ChangeOp,
+        id: Arc<str>,
+        context: Option<InfraContext>,
+    },
+    Group {
+        op: ChangeOp,
+        id: Arc<str>,
+        context: Option<InfraContext>,
+    },
+}
+
+#[derive(serde::Deserialize)]
+pub struct IdRow<T> {
+    pub id: T,
+}
+
+/// Reads the operation and the id of the changed row from a notification payload.
+pub fn changed_id<T: DeserializeOwned>(payload: &str) -> Option<(ChangeOp, T)> {
+    let payload: Payload<IdRow<T>> = serde_json::from_str(payload).ok()?;
+    let row = payload.new.or(payload.old)?;
+    Some((payload.op.into(), row.id))
+}
+
+/// Turns a receiver into a stream, events missed by a lagging receiver are skipped.
+pub fn stream(rx: broadcast::Receiver<CacheEvent>) -> impl Stream<Item = CacheEvent> {
+    futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some((event, rx)),
+                Err(RecvError::Lagged(n)) => {
+                    tracing::warn!("cache event subscriber lagged behind, skipped {n} events");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+/// Returns true if an entity in `entity` context is visible to a subscriber
+/// restricted to `subscriber`, entities without context are only visible to
+/// unrestricted subscribers.
+pub fn visible(subscriber: Option<&InfraContext>, entity: Option<&InfraContext>) -> bool {
+    match (subscriber, entity) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(subscriber), Some(entity)) => match subscriber {
+            InfraContext::Customer(v) => entity.has_customer(v),
+            InfraContext::Organization(v) => entity.has_organization(v),
+            InfraContext::Institution(v) => entity.has_institution(v),
+            InfraContext::OrganizationUnit(v) => entity.has_organization_unit(v),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qm_entity::ids::{CustomerId, InstitutionId, OrganizationId};
+
+    #[test]
+    fn test_changed_id() {
+        let payload = r#"{"op":"DELETE","old":{"id":"u1","username":"a"},"new":null}"#;
+        assert_eq!(
+            changed_id::<Arc<str>>(payload),
+            Some((ChangeOp::Removed, Arc::from("u1")))
+        );
+        assert_eq!(changed_id::<Arc<str>>("{}"), None);
+    }
+
+    #[test]
+    fn test_visible() {
+        let customer = InfraContext::Customer(CustomerId::from(1i64));
+        let other_customer = InfraContext::Customer(CustomerId::from(2i64));
+        let organization = InfraContext::Organization(OrganizationId::from((1i64, 10i64)));
+        let institution = InfraContext::Institution(InstitutionId::from((1i64, 10i64, 100i64)));
+
+        assert!(visible(None, None));
+        assert!(visible(None, Some(&institution)));
+        assert!(!visible(Some(&customer), None));
+        assert!(visible(Some(&customer), Some(&customer)));
+        assert!(visible(Some(&customer), Some(&institution)));
+        assert!(!visible(Some(&other_customer), Some(&institution)));
+        assert!(visible(Some(&organization), Some(&institution)));
+        assert!(!visible(Some(&organization), Some(&customer)));
+        assert!(!visible(Some(&institution), Some(&organization)));
+    }
+}
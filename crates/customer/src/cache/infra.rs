@@ -1,18 +1,24 @@
+use crate::features::FeatureDB;
 use crate::model::*;
 use crate::query::fetch_customers;
 use crate::query::fetch_institutions;
 use crate::query::fetch_organizations;
+use futures::StreamExt;
 use prometheus_client::metrics::gauge::Gauge;
 use qm_entity::ids::InfraId;
 use qm_pg::DB;
-use sqlx::postgres::PgListener;
+use sqlx::postgres::PgNotification;
 use std::collections::HashMap;
 use std::sync::atomic::AtomicI64;
 use std::sync::Arc;
 use time::macros::format_description;
 use time::PrimitiveDateTime;
+use tokio::sync::broadcast;
+use tokio::sync::Notify;
 use tokio::sync::RwLock;
 
+use super::events::{changed_id, CacheEvent, EVENT_CAPACITY};
+use super::sorted::SortIndex;
 use super::update::Op;
 use super::update::Payload;
 
@@ -31,40 +37,47 @@ fn parse_date_time(s: &str) -> Option<PrimitiveDateTime> {
 pub struct InfraDB {
     pub customers: RwLock<CustomerMap>,
     pub customer_id_map: RwLock<CustomerIdMap>,
+    pub customers_sorted: RwLock<SortIndex<QmCustomer>>,
     pub customers_total: Gauge<i64, AtomicI64>,
     pub organizations: RwLock<OrganizationMap>,
     pub organization_id_map: RwLock<OrganizationIdMap>,
+    pub organizations_sorted: RwLock<SortIndex<QmOrganization>>,
     pub organizations_total: Gauge<i64, AtomicI64>,
     pub institutions: RwLock<InstitutionMap>,
     pub institution_id_map: RwLock<InstitutionIdMap>,
+    pub institutions_sorted: RwLock<SortIndex<QmInstitution>>,
     pub institutions_total: Gauge<i64, AtomicI64>,
+    pub events: broadcast::Sender<CacheEvent>,
+    pub features: FeatureDB,
 }
 
 impl InfraDB {
     pub async fn cleanup(db: &DB) -> anyhow::Result<()> {
-        let mut migrator = sqlx::migrate!("./migrations/customer");
-        migrator.set_ignore_missing(true);
-        migrator.undo(db.pool(), 0).await?;
-        Ok(())
+        db.undo_migrations(sqlx::migrate!("./migrations/customer"))
+            .await
     }
 
     pub async fn new(db: &DB) -> anyhow::Result<Self> {
         let customers_total = Gauge::default();
         let organizations_total = Gauge::default();
         let institutions_total = Gauge::default();
-        let mut migrator = sqlx::migrate!("./migrations/customer");
-        migrator.set_ignore_missing(true);
-        migrator.run(db.pool()).await?;
+        db.run_migrations(sqlx::migrate!("./migrations/customer"))
+            .await?;
         let result = Self {
             customers: Default::default(),
             customer_id_map: Default::default(),
+            customers_sorted: Default::default(),
             customers_total,
             organizations: Default::default(),
             organization_id_map: Default::default(),
+            organizations_sorted: Default::default(),
             organizations_total,
             institutions: Default::default(),
             institution_id_map: Default::default(),
+            institutions_sorted: Default::default(),
             institutions_total,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            features: Default::default(),
         };
         Ok(result)
     }
@@ -94,6 +107,7 @@ impl InfraDB {
         self.load_customers(db).await?;
         self.load_organizations(db).await?;
         self.load_institutions(db).await?;
+        self.features.load(db).await?;
         Ok(())
     }
 
@@ -101,6 +115,7 @@ impl InfraDB {
         let customers_total = {
             let mut customers = self.customers.write().await;
             customers.insert(customer.name.clone(), customer.clone());
+            self.customers_sorted.write().await.insert(customer.clone());
             self.customer_id_map
                 .write()
                 .await
@@ -117,6 +132,10 @@ impl InfraDB {
                 (organization.name.clone(), organization.customer_id),
                 organization.clone(),
             );
+            self.organizations_sorted
+                .write()
+                .await
+                .insert(organization.clone());
             self.organization_id_map
                 .write()
                 .await
@@ -137,6 +156,10 @@ impl InfraDB {
                 ),
                 institution.clone(),
             );
+            self.institutions_sorted
+                .write()
+                .await
+                .insert(institution.clone());
             self.institution_id_map
                 .write()
                 .await
@@ -150,7 +173,9 @@ impl InfraDB {
         let customers_total = {
             let mut customers = self.customers.write().await;
             customers.remove(&v.name);
-            self.customer_id_map.write().await.remove(&v.id);
+            if let Some(old) = self.customer_id_map.write().await.remove(&v.id) {
+                self.customers_sorted.write().await.remove(&old);
+            }
             customers.len()
         };
         self.customers_total.set(customers_total as i64);
@@ -161,7 +186,11 @@ impl InfraDB {
             let mut customers = self.customers.write().await;
             let mut customer_id_map = self.customer_id_map.write().await;
             customers.remove(&old.name);
-            customer_id_map.remove(&old.id);
+            let mut customers_sorted = self.customers_sorted.write().await;
+            if let Some(old) = customer_id_map.remove(&old.id) {
+                customers_sorted.remove(&old);
+            }
+            customers_sorted.insert(new.clone());
             customers.insert(new.name.clone(), new.clone());
             customer_id_map.insert(new.id, new);
             customers.len()
@@ -178,7 +207,11 @@ impl InfraDB {
             let mut organizations = self.organizations.write().await;
             let mut organization_id_map = self.organization_id_map.write().await;
             organizations.remove(&(old.name.clone(), old.customer_id));
-            organization_id_map.remove(&old.id);
+            let mut organizations_sorted = self.organizations_sorted.write().await;
+            if let Some(old) = organization_id_map.remove(&old.id) {
+                organizations_sorted.remove(&old);
+            }
+            organizations_sorted.insert(new.clone());
             organizations.insert((new.name.clone(), new.customer_id), new.clone());
             organization_id_map.insert(new.id, new);
             organizations.len()
@@ -191,7 +224,11 @@ impl InfraDB {
             let mut institutions = self.institutions.write().await;
             let mut institution_id_map = self.institution_id_map.write().await;
             institutions.remove(&(old.name.clone(), old.customer_id, old.organization_id));
-            institution_id_map.remove(&old.id);
+            let mut institutions_sorted = self.institutions_sorted.write().await;
+            if let Some(old) = institution_id_map.remove(&old.id) {
+                institutions_sorted.remove(&old);
+            }
+            institutions_sorted.insert(new.clone());
             institutions.insert(
                 (new.name.clone(), new.customer_id, new.organization_id),
                 new.clone(),
@@ -206,7 +243,9 @@ impl InfraDB {
         let organizations_total = {
             let mut organizations = self.organizations.write().await;
             organizations.remove(&(v.name.clone(), v.customer_id));
-            self.organization_id_map.write().await.remove(&v.id);
+            if let Some(old) = self.organization_id_map.write().await.remove(&v.id) {
+                self.organizations_sorted.write().await.remove(&old);
+            }
             organizations.len()
         };
         self.organizations_total.set(organizations_total as i64);
@@ -216,38 +255,97 @@ impl InfraDB {
         let institutions_total = {
             let mut institutions = self.institutions.write().await;
             institutions.remove(&(v.name.clone(), v.customer_id, v.organization_id));
-            self.institution_id_map.write().await.remove(&v.id);
+            if let Some(old) = self.institution_id_map.write().await.remove(&v.id) {
+                self.institutions_sorted.write().await.remove(&old);
+            }
             institutions.len()
         };
         self.institutions_total.set(institutions_total as i64);
     }
 
+    async fn customer_status_update(&self, id: InfraId, status: QmEntityStatus) {
+        let old = self.customer_id_map.read().await.get(&id).cloned();
+        if let Some(old) = old.filter(|old| old.status != status) {
+            let new = Arc::new(QmCustomer {
+                status,
+                ..old.as_ref().clone()
+            });
+            self.update_customer(new, old.as_ref().into()).await;
+        }
+    }
+
+    async fn organization_status_update(&self, id: InfraId, status: QmEntityStatus) {
+        let old = self.organization_id_map.read().await.get(&id).cloned();
+        if let Some(old) = old.filter(|old| old.status != status) {
+            let new = Arc::new(QmOrganization {
+                status,
+                ..old.as_ref().clone()
+            });
+            self.update_organization(new, old.as_ref().into()).await;
+        }
+    }
+
+    async fn institution_status_update(&self, id: InfraId, status: QmEntityStatus) {
+        let old = self.institution_id_map.read().await.get(&id).cloned();
+        if let Some(old) = old.filter(|old| old.status != status) {
+            let new = Arc::new(QmInstitution {
+                status,
+                ..old.as_ref().clone()
+            });
+            self.update_institution(new, old.as_ref().into()).await;
+        }
+    }
+
+    /// Applies the notifications of the customer database, the cache is
+    /// reloaded after notifications were missed during a reconnect.
     pub async fn listen(&self, db: &DB) -> anyhow::Result<()> {
-        let mut listener = PgListener::connect_with(db.pool()).await?;
-        listener
-            .listen_all([
+        let gap = Arc::new(Notify::new());
+        let notifications = db.listen(
+            [
                 "customers_update",
                 "organizations_update",
                 "institutions_update",
-            ])
-            .await?;
-
-        while let Some(notification) = listener.try_recv().await? {
-            match notification.channel() {
-                "customers_update" => {
-                    self.customers_update(notification.payload()).await?;
+                "customer_features_update",
+            ],
+            {
+                let gap = gap.clone();
+                move || gap.notify_one()
+            },
+        );
+        tokio::pin!(notifications);
+        loop {
+            tokio::select! {
+                _ = gap.notified() => {
+                    self.reload(db).await?;
+                    tracing::info!("reloaded customers after missed notifications");
                 }
-                "organizations_update" => {
-                    self.organizations_update(notification.payload()).await?;
+                Some(notification) = notifications.next() => {
+                    self.notification(&notification).await?;
                 }
-                "institutions_update" => {
-                    self.institutions_update(notification.payload()).await?;
+            }
+        }
+    }
+
+    async fn notification(&self, notification: &PgNotification) -> anyhow::Result<()> {
+        match notification.channel() {
+            "customers_update" => {
+                self.customers_update(notification.payload()).await?;
+                if let Some((op, id)) = changed_id(notification.payload()) {
+                    self.events.send(CacheEvent::Customer { op, id }).ok();
                 }
-                _ => {}
             }
+            "organizations_update" => {
+                self.organizations_update(notification.payload()).await?;
+            }
+            "institutions_update" => {
+                self.institutions_update(notification.payload()).await?;
+            }
+            "customer_features_update" => {
+                self.features.update(notification.payload()).await?;
+            }
+            _ => {}
         }
-        tracing::error!("postgresql listener disconnected");
-        std::process::exit(1);
+        Ok(())
     }
 
     async fn customers_update(&self, payload: &str) -> anyhow::Result<()> {
@@ -259,6 +357,7 @@ impl InfraDB {
                         id: new.id,
                         name: new.name,
                         ty: new.ty,
+                        status: new.status,
                         created_at,
                         created_by: new.created_by,
                         updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
@@ -267,6 +366,9 @@ impl InfraDB {
                     self.new_customer(customer).await;
                 }
             }
+            (Op::Update, Some(new), Some(_)) => {
+                self.customer_status_update(new.id, new.status).await;
+            }
             (Op::Delete, None, Some(old)) => {
                 self.remove_customer(old).await;
             }
@@ -285,6 +387,7 @@ impl InfraDB {
                         customer_id: new.customer_id,
                         name: new.name,
                         ty: new.ty,
+                        status: new.status,
                         created_at,
                         created_by: new.created_by,
                         updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
@@ -293,6 +396,9 @@ impl InfraDB {
                     self.new_organization(organization).await;
                 }
             }
+            (Op::Update, Some(new), Some(_)) => {
+                self.organization_status_update(new.id, new.status).await;
+            }
             (Op::Delete, None, Some(old)) => {
                 self.remove_organization(old).await;
             }
@@ -312,6 +418,7 @@ impl InfraDB {
                         organization_id: new.organization_id,
                         name: new.name,
                         ty: new.ty,
+                        status: new.status,
                         created_at,
                         created_by: new.created_by,
                         updated_at: new.updated_at.and_then(|s| parse_date_time(&s)),
@@ -320,6 +427,9 @@ impl InfraDB {
                     self.new_institution(organization).await;
                 }
             }
+            (Op::Update, Some(new), Some(_)) => {
+                self.institution_status_update(new.id, new.status).await;
+            }
             (Op::Delete, None, Some(old)) => {
                 self.remove_institution(old).await;
             }
@@ -1,25 +1,41 @@
 use prometheus_client::metrics::gauge::Gauge;
 
 use qm_entity::ids::PartialEqual;
-use qm_entity::ids::{CustomerId, CustomerOrOrganization, InfraContext, InfraId};
+use qm_entity::ids::{
+    CustomerId, CustomerOrOrganization, InfraContext, InfraId, InstitutionId, OrganizationId,
+};
 use qm_entity::model::ListFilter;
+use qm_entity::IsSuspended;
 
+use qm_role::AccessLevel;
+
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::sync::atomic::AtomicI64;
 use std::sync::Arc;
 use tokio::{runtime::Builder, task::LocalSet};
 
+pub mod events;
 pub mod infra;
+pub mod search;
+pub mod sorted;
 pub mod update;
 pub mod user;
 
 use crate::cache::infra::InfraDB;
+use crate::cache::search::{SearchIndex, SearchKey};
+use crate::cache::user::users::Users;
 use crate::cache::user::UserDB;
+use crate::config::{CacheMode, Config};
+use crate::features::FeatureDB;
 use crate::model::*;
+use crate::query::UserField;
 
 struct Inner {
     infra: InfraDB,
     user: UserDB,
+    search: tokio::sync::RwLock<Option<SearchIndex>>,
+    search_rebuild: tokio::sync::Mutex<()>,
 }
 
 #[derive(Clone)]
@@ -37,10 +53,59 @@ impl CacheDB {
         let infra = InfraDB::new(customer_db).await?;
         let user = UserDB::new(keycloak_db, realm, realm_admin_username).await?;
         Ok(Self {
-            inner: Arc::new(Inner { infra, user }),
+            inner: Arc::new(Inner {
+                infra,
+                user,
+                search: Default::default(),
+                search_rebuild: Default::default(),
+            }),
         })
     }
 
+    /// Creates the cache in the mode configured by `config`, in lazy mode
+    /// groups, roles and users are prefetched in the background.
+    pub async fn with_config(
+        customer_db: &qm_pg::DB,
+        keycloak_db: &qm_pg::DB,
+        realm: &str,
+        realm_admin_username: &str,
+        config: &Config,
+    ) -> anyhow::Result<Self> {
+        let user = match config.cache_mode() {
+            CacheMode::Eager => UserDB::new(keycloak_db, realm, realm_admin_username).await?,
+            CacheMode::Lazy => {
+                UserDB::new_lazy(
+                    keycloak_db,
+                    realm,
+                    realm_admin_username,
+                    config.cache_capacity(),
+                    config.cache_prefetch(),
+                    std::time::Duration::from_secs(config.cache_miss_ttl()),
+                    std::time::Duration::from_secs(config.cache_list_ttl()),
+                )
+                .await?
+            }
+        };
+        let infra = InfraDB::new(customer_db).await?;
+        let result = Self {
+            inner: Arc::new(Inner {
+                infra,
+                user,
+                search: Default::default(),
+                search_rebuild: Default::default(),
+            }),
+        };
+        if result.user().is_lazy() {
+            let cache = result.clone();
+            tokio::spawn(async move {
+                if let Err(err) = cache.user().prefetch().await {
+                    tracing::error!("user prefetch failed: {err:#}");
+                }
+            });
+        }
+        Ok(result)
+    }
+
     pub fn user(&self) -> &UserDB {
         &self.inner.user
     }
@@ -49,6 +114,10 @@ impl CacheDB {
         &self.inner.infra
     }
 
+    pub fn features(&self) -> &FeatureDB {
+        &self.inner.infra.features
+    }
+
     pub fn customers_total(&self) -> &Gauge<i64, AtomicI64> {
         &self.inner.infra.customers_total
     }
@@ -64,33 +133,20 @@ impl CacheDB {
     pub async fn customer_list(
         &self,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> QmCustomerList {
-        let customers = self.inner.infra.customers.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(customers.values().filter(|c| c.ty.as_ref() == ty.as_str()))
-        } else {
-            itertools::Either::Left(customers.values())
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<QmCustomer>]> = iter.skip(offset).take(limit).cloned().collect();
-            QmCustomerList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.customers_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<QmCustomer>]> = iter.cloned().collect();
-            QmCustomerList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.customers_total.get()),
-                page: Some(0),
-            }
+        let customers = self.inner.infra.customers_sorted.read().await;
+        let iter = customers
+            .iter(sort.unwrap_or_default())
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .cloned();
+        let page = paginate(iter, filter);
+        QmCustomerList {
+            items: page.items,
+            limit: page.limit,
+            total: Some(page.total),
+            page: Some(page.page),
         }
     }
 
@@ -98,44 +154,25 @@ impl CacheDB {
         &self,
         customer_id: Option<CustomerId>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> QmOrganizationList {
-        let organizations = self.inner.infra.organizations.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(
-                organizations
-                    .values()
-                    .filter(|c| c.ty.as_ref() == ty.as_str()),
-            )
-        } else {
-            itertools::Either::Left(organizations.values())
-        };
-        let iter = if let Some(customer_id) = customer_id.as_ref() {
-            itertools::Either::Right(iter.filter(|v| v.as_ref().partial_equal(customer_id)))
-        } else {
-            itertools::Either::Left(iter)
-        };
-        let total = iter.clone().count();
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<QmOrganization>]> =
-                iter.skip(offset).take(limit).cloned().collect();
-            QmOrganizationList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(total as i64),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<QmOrganization>]> = iter.cloned().collect();
-            QmOrganizationList {
-                items,
-                limit: None,
-                total: Some(total as i64),
-                page: Some(0),
-            }
+        let organizations = self.inner.infra.organizations_sorted.read().await;
+        let iter = organizations
+            .iter(sort.unwrap_or_default())
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|v| {
+                customer_id
+                    .as_ref()
+                    .map_or(true, |customer_id| v.as_ref().partial_equal(customer_id))
+            })
+            .cloned();
+        let page = paginate(iter, filter);
+        QmOrganizationList {
+            items: page.items,
+            limit: page.limit,
+            total: Some(page.total),
+            page: Some(page.page),
         }
     }
 
@@ -143,48 +180,29 @@ impl CacheDB {
         &self,
         customer_or_organization: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> QmInstitutionList {
-        let institutions = self.inner.infra.institutions.read().await;
-        let iter = if let Some(ty) = ty.as_ref() {
-            itertools::Either::Right(
-                institutions
-                    .values()
-                    .filter(|c| c.ty.as_ref() == ty.as_str()),
-            )
-        } else {
-            itertools::Either::Left(institutions.values())
-        };
-        let iter = match &customer_or_organization {
-            Some(CustomerOrOrganization::Customer(customer_id)) => itertools::Either::Left(
-                itertools::Either::Left(iter.filter(|v| v.as_ref().partial_equal(customer_id))),
-            ),
-            Some(CustomerOrOrganization::Organization(organization_id)) => {
-                itertools::Either::Left(itertools::Either::Right(
-                    iter.filter(|v| v.as_ref().partial_equal(organization_id)),
-                ))
-            }
-            _ => itertools::Either::Right(iter),
-        };
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Arc<[Arc<QmInstitution>]> = iter.skip(offset).take(limit).cloned().collect();
-            QmInstitutionList {
-                items,
-                limit: Some(limit as i64),
-                total: Some(self.inner.infra.institutions_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Arc<[Arc<QmInstitution>]> = iter.cloned().collect();
-            QmInstitutionList {
-                items,
-                limit: None,
-                total: Some(self.inner.infra.institutions_total.get()),
-                page: Some(0),
-            }
+        let institutions = self.inner.infra.institutions_sorted.read().await;
+        let iter = institutions
+            .iter(sort.unwrap_or_default())
+            .filter(|c| ty.as_ref().map_or(true, |ty| c.ty.as_ref() == ty.as_str()))
+            .filter(|v| match &customer_or_organization {
+                Some(CustomerOrOrganization::Customer(customer_id)) => {
+                    v.as_ref().partial_equal(customer_id)
+                }
+                Some(CustomerOrOrganization::Organization(organization_id)) => {
+                    v.as_ref().partial_equal(organization_id)
+                }
+                _ => true,
+            })
+            .cloned();
+        let page = paginate(iter, filter);
+        QmInstitutionList {
+            items: page.items,
+            limit: page.limit,
+            total: Some(page.total),
+            page: Some(page.page),
         }
     }
 
@@ -192,72 +210,62 @@ impl CacheDB {
         &self,
         context: Option<InfraContext>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
     ) -> QmUserList {
-        let users = self.inner.user.users.read().await;
-        let user_roles = self.inner.user.user_roles.read().await;
-        let roles = self.inner.user.roles.read().await;
-        let user_groups = self.inner.user.user_groups.read().await;
-        let groups = self.inner.user.groups.read().await;
-        let group_attributes = self.inner.user.group_attributes.read().await;
-        let user_list = users.list();
-        let iter = user_list.iter().map(|u| {
-            let context = user_roles
-                .by_user_id(&u.id)
-                .and_then(|r| r.iter().find_map(|r| roles.get(r).and_then(|r| r.context)));
-            let access = user_roles.by_user_id(&u.id).and_then(|r| {
-                r.iter().find_map(|r| {
-                    roles
-                        .get(r)
-                        .and_then(|r| qm_role::Access::from_str(r.name.as_ref()).ok())
-                })
-            });
-            let group = user_groups.by_user_id(&u.id).and_then(|g| {
-                g.iter().find_map(|g| {
-                    groups
-                        .get(g)
-                        .and_then(|r| group_attributes.get(&r.id).cloned())
-                })
-            });
-            QmUserDetails {
-                user: u.clone(),
-                context,
-                access,
-                group,
+        let fetched;
+        let cached;
+        let users: &Users = match self.fetch_all_users().await {
+            Some(users) => {
+                fetched = users;
+                &fetched
             }
-        });
-        if let Some(filter) = filter {
-            let page = filter.page.unwrap_or(0);
-            let limit = filter.limit.unwrap_or(100);
-            let offset = page * limit;
-            let items: Vec<QmUserDetails> = if let Some(context) = context {
-                iter.filter(|v| v.partial_equal(&context))
-                    .skip(offset)
-                    .take(limit)
-                    .collect::<Vec<QmUserDetails>>()
-            } else {
-                iter.skip(offset)
-                    .take(limit)
-                    .collect::<Vec<QmUserDetails>>()
-            };
-            QmUserList {
-                items: Arc::from(items),
-                limit: Some(limit as i64),
-                total: Some(self.inner.user.users_total.get()),
-                page: Some(page as i64),
-            }
-        } else {
-            let items: Vec<QmUserDetails> = if let Some(context) = context {
-                iter.filter(|v| v.partial_equal(&context))
-                    .collect::<Vec<QmUserDetails>>()
-            } else {
-                iter.collect::<Vec<QmUserDetails>>()
-            };
-            QmUserList {
-                items: Arc::from(items),
-                limit: None,
-                total: Some(self.inner.user.users_total.get()),
-                page: Some(0),
+            None => {
+                cached = self.inner.user.users.read().await;
+                &cached
             }
+        };
+        let user_roles = self.inner.user.loaded().await.user_roles.read().await;
+        let roles = self.inner.user.loaded().await.roles.read().await;
+        let user_groups = self.inner.user.loaded().await.user_groups.read().await;
+        let groups = self.inner.user.loaded().await.groups.read().await;
+        let group_attributes = self.inner.user.loaded().await.group_attributes.read().await;
+        let iter = users
+            .sorted(sort.unwrap_or_default())
+            .map(|u| {
+                let context = user_roles
+                    .by_user_id(&u.id)
+                    .and_then(|r| r.iter().find_map(|r| roles.get(r).and_then(|r| r.context)));
+                let access = user_roles.by_user_id(&u.id).and_then(|r| {
+                    r.iter().find_map(|r| {
+                        roles
+                            .get(r)
+                            .and_then(|r| qm_role::Access::from_str(r.name.as_ref()).ok())
+                    })
+                });
+                let group =
+                    user_groups.by_user_id(&u.id).and_then(|g| {
+                        primary_group(g.iter().filter_map(|g| {
+                            groups.get(g).and_then(|r| group_attributes.get(&r.id))
+                        }))
+                    });
+                QmUserDetails {
+                    user: u.clone(),
+                    context,
+                    access,
+                    group,
+                }
+            })
+            .filter(|v| {
+                context
+                    .as_ref()
+                    .map_or(true, |context| v.partial_equal(context))
+            });
+        let page = paginate(iter, filter);
+        QmUserList {
+            items: page.items,
+            limit: page.limit,
+            total: Some(page.total),
+            page: Some(page.page),
         }
     }
 
@@ -324,6 +332,34 @@ impl CacheDB {
             .cloned()
     }
 
+    /// `true` if the customer, organization or institution of `context` is
+    /// suspended or archived.
+    pub async fn is_suspended(&self, context: &InfraContext) -> bool {
+        if self
+            .customer_by_id(&context.customer_id())
+            .await
+            .is_some_and(|v| v.is_suspended())
+        {
+            return true;
+        }
+        if let Some(id) = context.organization_id() {
+            if self
+                .organization_by_id(&id)
+                .await
+                .is_some_and(|v| v.is_suspended())
+            {
+                return true;
+            }
+        }
+        if let Some(id) = context.institution_id() {
+            return self
+                .institution_by_id(&id)
+                .await
+                .is_some_and(|v| v.is_suspended());
+        }
+        false
+    }
+
     pub fn users_total(&self) -> &Gauge<i64, AtomicI64> {
         &self.inner.user.users_total
     }
@@ -339,6 +375,8 @@ impl CacheDB {
     pub async fn group_detail_by_id(&self, id: &str) -> Option<Arc<GroupDetail>> {
         self.inner
             .user
+            .loaded()
+            .await
             .group_attributes
             .read()
             .await
@@ -347,16 +385,23 @@ impl CacheDB {
     }
 
     pub async fn user_by_id(&self, id: &str) -> Option<Arc<QmUser>> {
-        self.inner.user.users.read().await.get(id).cloned()
+        let user = self.inner.user.users.read().await.get(id).cloned();
+        match user {
+            Some(user) => Some(user),
+            None => self.inner.user.load_user(UserField::Id, id).await,
+        }
     }
 
     pub async fn user_details_by_id(&self, id: &str) -> Option<QmUserDetails> {
+        if self.inner.user.is_lazy() {
+            self.user_by_id(id).await?;
+        }
         let users = self.inner.user.users.read().await;
-        let user_roles = self.inner.user.user_roles.read().await;
-        let roles = self.inner.user.roles.read().await;
-        let user_groups = self.inner.user.user_groups.read().await;
-        let groups = self.inner.user.groups.read().await;
-        let group_attributes = self.inner.user.group_attributes.read().await;
+        let user_roles = self.inner.user.loaded().await.user_roles.read().await;
+        let roles = self.inner.user.loaded().await.roles.read().await;
+        let user_groups = self.inner.user.loaded().await.user_groups.read().await;
+        let groups = self.inner.user.loaded().await.groups.read().await;
+        let group_attributes = self.inner.user.loaded().await.group_attributes.read().await;
         users.get(id).map(|u| {
             let context = user_roles
                 .by_user_id(&u.id)
@@ -369,11 +414,10 @@ impl CacheDB {
                 })
             });
             let group = user_groups.by_user_id(&u.id).and_then(|g| {
-                g.iter().find_map(|g| {
-                    groups
-                        .get(g)
-                        .and_then(|r| group_attributes.get(&r.id).cloned())
-                })
+                primary_group(
+                    g.iter()
+                        .filter_map(|g| groups.get(g).and_then(|r| group_attributes.get(&r.id))),
+                )
             });
             QmUserDetails {
                 user: u.clone(),
@@ -385,29 +429,67 @@ impl CacheDB {
     }
 
     pub async fn user_by_username(&self, username: &str) -> Option<Arc<QmUser>> {
-        self.inner
+        let user = self
+            .inner
             .user
             .users
             .read()
             .await
             .by_username(username)
-            .cloned()
+            .cloned();
+        match user {
+            Some(user) => Some(user),
+            None => {
+                self.inner
+                    .user
+                    .load_user(UserField::Username, username)
+                    .await
+            }
+        }
     }
 
     pub async fn user_by_email(&self, email: &str) -> Option<Arc<QmUser>> {
-        self.inner.user.users.read().await.by_email(email).cloned()
+        let user = self.inner.user.users.read().await.by_email(email).cloned();
+        match user {
+            Some(user) => Some(user),
+            None => self.inner.user.load_user(UserField::Email, email).await,
+        }
     }
 
+    /// All users of the realm, read from the database in lazy mode.
     pub async fn users(&self) -> Arc<[Arc<QmUser>]> {
-        self.inner.user.users.read().await.list()
+        match self.fetch_all_users().await {
+            Some(users) => users.list(),
+            None => self.inner.user.users.read().await.list(),
+        }
+    }
+
+    /// Users for lists in lazy mode where the cache doesn't hold all users,
+    /// the cached users are used if they can't be read.
+    async fn fetch_all_users(&self) -> Option<Arc<Users>> {
+        match self.inner.user.fetch_all().await? {
+            Ok(users) => Some(users),
+            Err(err) => {
+                tracing::error!("unable to fetch users: {err:#}");
+                None
+            }
+        }
     }
 
     pub async fn roles(&self) -> Arc<[Arc<Role>]> {
-        self.inner.user.roles.read().await.list()
+        self.inner.user.loaded().await.roles.read().await.list()
     }
 
     pub async fn group_by_id(&self, group_id: &str) -> Option<Arc<Group>> {
-        self.inner.user.groups.read().await.get(group_id).cloned()
+        self.inner
+            .user
+            .loaded()
+            .await
+            .groups
+            .read()
+            .await
+            .get(group_id)
+            .cloned()
     }
 
     pub async fn group_id_by_path(&self, path: &str) -> Option<String> {
@@ -416,7 +498,7 @@ impl CacheDB {
         }
         let mut s = path[1..].split('/');
         if let Some((parent, name)) = s.next().zip(s.next()) {
-            let m = self.inner.user.groups.read().await;
+            let m = self.inner.user.loaded().await.groups.read().await;
             m.by_parent(parent)
                 .and_then(|v| v.get(name))
                 .map(|g| g.id.to_string())
@@ -428,6 +510,8 @@ impl CacheDB {
     pub async fn groups_by_parent(&self, parent_name: &str) -> Vec<Arc<Group>> {
         self.inner
             .user
+            .loaded()
+            .await
             .groups
             .read()
             .await
@@ -437,12 +521,20 @@ impl CacheDB {
     }
 
     pub async fn role_by_name(&self, name: &str) -> Option<Arc<Role>> {
-        self.inner.user.roles.read().await.by_name(name).cloned()
+        self.inner
+            .user
+            .loaded()
+            .await
+            .roles
+            .read()
+            .await
+            .by_name(name)
+            .cloned()
     }
 
     pub async fn roles_by_user_id(&self, user_id: &str) -> Option<Arc<[Arc<Role>]>> {
-        let roles = self.inner.user.roles.read().await;
-        let user_roles = self.inner.user.user_roles.read().await;
+        let roles = self.inner.user.loaded().await.roles.read().await;
+        let user_roles = self.inner.user.loaded().await.user_roles.read().await;
         user_roles.by_user_id(user_id).map(|v| {
             v.iter()
                 .filter_map(|role_id| roles.get(role_id).cloned())
@@ -451,8 +543,8 @@ impl CacheDB {
     }
 
     pub async fn roles_by_group_id(&self, group_id: &str) -> Option<Arc<[Arc<Role>]>> {
-        let roles = self.inner.user.roles.read().await;
-        let group_roles = self.inner.user.group_roles.read().await;
+        let roles = self.inner.user.loaded().await.roles.read().await;
+        let group_roles = self.inner.user.loaded().await.group_roles.read().await;
         group_roles.by_group_id(group_id).map(|v| {
             v.iter()
                 .filter_map(|role_id| roles.get(role_id).cloned())
@@ -460,9 +552,115 @@ impl CacheDB {
         })
     }
 
+    /// Names of the roles granted by built-in groups allowing one of
+    /// `access_levels`, custom groups may only bundle these roles.
+    pub async fn built_in_role_names(
+        &self,
+        access_levels: &HashSet<AccessLevel>,
+    ) -> HashSet<Arc<str>> {
+        let group_attributes = self.inner.user.loaded().await.group_attributes.read().await;
+        let group_roles = self.inner.user.loaded().await.group_roles.read().await;
+        let roles = self.inner.user.loaded().await.roles.read().await;
+        group_attributes
+            .iter()
+            .filter(|(_, detail)| {
+                detail.built_in
+                    && detail
+                        .allowed_access_levels
+                        .as_ref()
+                        .is_some_and(|levels| levels.iter().any(|l| access_levels.contains(l)))
+            })
+            .filter_map(|(group_id, _)| group_roles.by_group_id(group_id))
+            .flatten()
+            .filter_map(|role_id| roles.get(role_id).map(|r| r.name.clone()))
+            .collect()
+    }
+
+    async fn build_search_index(&self) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for customer in self.inner.infra.customer_id_map.read().await.values() {
+            let context = InfraContext::from(CustomerId::from(customer.as_ref()));
+            index.insert(
+                SearchKey::Infra(context),
+                Some(context),
+                [customer.name.as_ref()],
+            );
+        }
+        for organization in self.inner.infra.organization_id_map.read().await.values() {
+            let context = InfraContext::from(OrganizationId::from(organization.as_ref()));
+            index.insert(
+                SearchKey::Infra(context),
+                Some(context),
+                [organization.name.as_ref()],
+            );
+        }
+        for institution in self.inner.infra.institution_id_map.read().await.values() {
+            let context = InfraContext::from(InstitutionId::from(institution.as_ref()));
+            index.insert(
+                SearchKey::Infra(context),
+                Some(context),
+                [institution.name.as_ref()],
+            );
+        }
+        let users = self.inner.user.users.read().await.list();
+        for user in users.iter() {
+            let context = self.inner.user.user_context(&user.id).await;
+            let name = format!("{} {}", user.firstname, user.lastname);
+            index.insert(
+                SearchKey::User(user.id.clone()),
+                context,
+                [user.username.as_ref(), user.email.as_ref(), name.as_str()],
+            );
+        }
+        index
+    }
+
+    /// Rebuilds the search index unless another rebuild finished while
+    /// waiting for the lock, only one rebuild runs at a time.
+    async fn rebuild_search_index(&self) {
+        let _guard = self.inner.search_rebuild.lock().await;
+        if let Some(index) = self.inner.search.read().await.as_ref() {
+            if !index.is_stale() {
+                return;
+            }
+        }
+        let index = self.build_search_index().await;
+        self.inner.search.write().await.replace(index);
+    }
+
+    /// Searches the names of cached entities and users, in lazy mode only
+    /// users already loaded are found. A stale index answers the query while
+    /// it is rebuilt in the background.
+    pub async fn search(
+        &self,
+        query: &str,
+        context: Option<&InfraContext>,
+        limit: usize,
+        filter: impl Fn(&SearchKey) -> bool,
+    ) -> Vec<(SearchKey, f64)> {
+        {
+            let index = self.inner.search.read().await;
+            if let Some(index) = index.as_ref() {
+                if index.is_stale() && self.inner.search_rebuild.try_lock().is_ok() {
+                    let cache = self.clone();
+                    tokio::spawn(async move { cache.rebuild_search_index().await });
+                }
+                return index.search(query, context, limit, filter);
+            }
+        }
+        self.rebuild_search_index().await;
+        self.inner
+            .search
+            .read()
+            .await
+            .as_ref()
+            .map(|index| index.search(query, context, limit, filter))
+            .unwrap_or_default()
+    }
+
     pub async fn groups_by_user_id(&self, user_id: &str) -> Option<Arc<[UserGroup]>> {
-        let group_attributes = self.inner.user.group_attributes.read().await;
-        let user_groups = self.inner.user.user_groups.read().await;
+        let group_attributes = self.inner.user.loaded().await.group_attributes.read().await;
+        let user_groups = self.inner.user.loaded().await.user_groups.read().await;
         user_groups.by_user_id(user_id).map(|v| {
             v.iter()
                 .filter_map(|group_id| {
@@ -476,6 +674,58 @@ impl CacheDB {
     }
 }
 
+const DEFAULT_PAGE_LIMIT: usize = 100;
+/// Larger limits requested by clients are capped.
+const MAX_PAGE_LIMIT: usize = 1000;
+
+struct Page<T> {
+    items: Arc<[T]>,
+    limit: Option<i64>,
+    total: i64,
+    page: i64,
+}
+
+/// Selects the page of `filter` from `iter` and counts all items of `iter`,
+/// the whole list is returned without filter.
+fn paginate<T>(mut iter: impl Iterator<Item = T>, filter: Option<ListFilter>) -> Page<T> {
+    let Some(filter) = filter else {
+        let items: Arc<[T]> = iter.collect();
+        return Page {
+            total: items.len() as i64,
+            items,
+            limit: None,
+            page: 0,
+        };
+    };
+    let page = filter.page.unwrap_or(0);
+    let limit = filter
+        .limit
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+    let offset = page.saturating_mul(limit);
+    let skipped = iter.by_ref().take(offset).count();
+    let items: Arc<[T]> = iter.by_ref().take(limit).collect();
+    let total = skipped
+        .saturating_add(items.len())
+        .saturating_add(iter.count());
+    Page {
+        items,
+        limit: Some(limit as i64),
+        total: total as i64,
+        page: page as i64,
+    }
+}
+
+/// Group shown for users in multiple groups, custom groups take precedence
+/// over built-in groups and ties are resolved by the display name.
+fn primary_group<'a>(
+    groups: impl Iterator<Item = &'a Arc<GroupDetail>>,
+) -> Option<Arc<GroupDetail>> {
+    groups
+        .min_by_key(|g| (g.built_in, g.display_name.clone()))
+        .cloned()
+}
+
 pub fn subscribe(keycloak_db: qm_pg::DB, customer_db: qm_pg::DB, listener_instance: CacheDB) {
     let keycloak_listener_instance = listener_instance.clone();
     std::thread::spawn(move || {
@@ -516,3 +766,43 @@ pub fn subscribe(keycloak_db: qm_pg::DB, customer_db: qm_pg::DB, listener_instan
         rt.block_on(local);
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate() {
+        let page = paginate((0..250).filter(|v| v % 2 == 0), None);
+        assert_eq!(page.total, 125);
+        assert_eq!(page.items.len(), 125);
+        assert_eq!(page.limit, None);
+        let filter = |page, limit| {
+            Some(ListFilter {
+                page: Some(page),
+                limit: Some(limit),
+            })
+        };
+        let page = paginate((0..250).filter(|v| v % 2 == 0), filter(1, 50));
+        assert_eq!(page.total, 125);
+        assert_eq!(page.items.first(), Some(&100));
+        assert_eq!(page.items.len(), 50);
+        assert_eq!(page.page, 1);
+        let page = paginate((0..250).filter(|v| v % 2 == 0), filter(2, 50));
+        assert_eq!(page.total, 125);
+        assert_eq!(page.items.len(), 25);
+        let page = paginate((0..250).filter(|v| v % 2 == 0), filter(5, 50));
+        assert_eq!(page.total, 125);
+        assert!(page.items.is_empty());
+        let page = paginate((0..10).filter(|v| v % 2 == 0), filter(0, 0));
+        assert_eq!(page.total, 5);
+        assert!(page.items.is_empty());
+        let page = paginate(0..2000, filter(0, usize::MAX));
+        assert_eq!(page.total, 2000);
+        assert_eq!(page.items.len(), MAX_PAGE_LIMIT);
+        assert_eq!(page.limit, Some(MAX_PAGE_LIMIT as i64));
+        let page = paginate(0..2000, filter(usize::MAX, usize::MAX));
+        assert_eq!(page.total, 2000);
+        assert!(page.items.is_empty());
+    }
+}
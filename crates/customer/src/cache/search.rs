@@ -0,0 +1,214 @@
+//! Trigram index over the names of customers, organizations, institutions
+//! and users.
+//!
+//! The index is rebuilt in the background from the cache once it is older
+//! than [`SEARCH_INDEX_MAX_AGE`], hits are resolved against the live cache so
+//! removed entities never show up.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use qm_entity::ids::InfraContext;
+
+pub const SEARCH_INDEX_MAX_AGE: Duration = Duration::from_secs(10);
+/// Hits scoring below are dropped.
+const MIN_SCORE: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SearchKey {
+    Infra(InfraContext),
+    User(Arc<str>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    key: SearchKey,
+    context: Option<InfraContext>,
+    terms: Vec<String>,
+}
+
+pub struct SearchIndex {
+    entries: Vec<Entry>,
+    trigrams: HashMap<[char; 3], Vec<u32>>,
+    built_at: Instant,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            entries: vec![],
+            trigrams: HashMap::default(),
+            built_at: Instant::now(),
+        }
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Trigrams of the words in `value`, words are padded at the start so short
+/// prefixes match and at the end if `complete` is set.
+fn trigrams(value: &str, complete: bool) -> HashSet<[char; 3]> {
+    let mut result = HashSet::new();
+    for word in value.split_whitespace() {
+        let mut chars = vec![' ', ' '];
+        chars.extend(word.chars());
+        if complete {
+            chars.push(' ');
+        }
+        for w in chars.windows(3) {
+            result.insert([w[0], w[1], w[2]]);
+        }
+    }
+    result
+}
+
+/// Ranks `term` for the normalized `query`, exact matches come before
+/// prefixes, word prefixes, substrings and similar spellings.
+fn score(query: &str, term: &str) -> f64 {
+    if term == query {
+        return 1.0;
+    }
+    if term.starts_with(query) {
+        return 0.9;
+    }
+    if term.split(' ').any(|word| word.starts_with(query)) {
+        return 0.8;
+    }
+    if term.contains(query) {
+        return 0.7;
+    }
+    let query = trigrams(query, false);
+    if query.is_empty() {
+        return 0.0;
+    }
+    let term = trigrams(term, true);
+    let shared = query.intersection(&term).count();
+    0.6 * shared as f64 / query.len() as f64
+}
+
+impl SearchIndex {
+    pub fn is_stale(&self) -> bool {
+        self.built_at.elapsed() > SEARCH_INDEX_MAX_AGE
+    }
+
+    pub fn insert<'a>(
+        &mut self,
+        key: SearchKey,
+        context: Option<InfraContext>,
+        terms: impl IntoIterator<Item = &'a str>,
+    ) {
+        let idx = self.entries.len() as u32;
+        let terms: Vec<String> = terms
+            .into_iter()
+            .map(normalize)
+            .filter(|t| !t.is_empty())
+            .collect();
+        let mut entry_trigrams = HashSet::new();
+        for term in terms.iter() {
+            entry_trigrams.extend(trigrams(term, true));
+        }
+        for trigram in entry_trigrams {
+            self.trigrams.entry(trigram).or_default().push(idx);
+        }
+        self.entries.push(Entry {
+            key,
+            context,
+            terms,
+        });
+    }
+
+    /// Keys accepted by `filter` matching `query` in `context` ordered by
+    /// descending score.
+    pub fn search(
+        &self,
+        query: &str,
+        context: Option<&InfraContext>,
+        limit: usize,
+        filter: impl Fn(&SearchKey) -> bool,
+    ) -> Vec<(SearchKey, f64)> {
+        let query = normalize(query);
+        if query.is_empty() {
+            return vec![];
+        }
+        let candidates: HashSet<u32> = trigrams(&query, false)
+            .iter()
+            .filter_map(|t| self.trigrams.get(t))
+            .flatten()
+            .copied()
+            .collect();
+        let mut result: Vec<(&Entry, f64)> = candidates
+            .into_iter()
+            .map(|idx| &self.entries[idx as usize])
+            .filter(|entry| {
+                super::events::visible(context, entry.context.as_ref()) && filter(&entry.key)
+            })
+            .map(|entry| {
+                let score = entry
+                    .terms
+                    .iter()
+                    .map(|term| score(&query, term))
+                    .fold(0.0, f64::max);
+                (entry, score)
+            })
+            .filter(|(_, score)| *score >= MIN_SCORE)
+            .collect();
+        result.sort_by(|a, b| {
+            b.1.total_cmp(&a.1)
+                .then_with(|| a.0.terms.first().cmp(&b.0.terms.first()))
+        });
+        result
+            .into_iter()
+            .take(limit)
+            .map(|(entry, score)| (entry.key.clone(), score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str) -> SearchKey {
+        SearchKey::User(Arc::from(id))
+    }
+
+    #[test]
+    fn test_search() {
+        let mut index = SearchIndex::default();
+        index.insert(user("1"), None, ["Health Care", "hc@example.com"]);
+        index.insert(user("2"), None, ["Healthy Food"]);
+        index.insert(user("3"), None, ["City  Hospital"]);
+        index.insert(user("4"), None, ["health"]);
+        let hits = index.search("health", None, 10, |_| true);
+        let keys: Vec<SearchKey> = hits.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!(keys, vec![user("4"), user("1"), user("2")]);
+        assert_eq!(hits[0].1, 1.0);
+        assert_eq!(index.search("hosp", None, 10, |_| true)[0].0, user("3"));
+        assert_eq!(index.search("city hospital", None, 10, |_| true)[0].1, 1.0);
+        assert_eq!(index.search("hospitl", None, 10, |_| true)[0].0, user("3"));
+        assert!(index.search("xyz", None, 10, |_| true).is_empty());
+        assert_eq!(index.search("health", None, 1, |_| true).len(), 1);
+    }
+
+    #[test]
+    fn test_search_context() {
+        use qm_entity::ids::CustomerId;
+        let c1 = InfraContext::Customer(CustomerId::from(1i64));
+        let c2 = InfraContext::Customer(CustomerId::from(2i64));
+        let mut index = SearchIndex::default();
+        index.insert(SearchKey::Infra(c1), Some(c1), ["Acme"]);
+        index.insert(SearchKey::Infra(c2), Some(c2), ["Acme Two"]);
+        assert_eq!(index.search("acme", None, 10, |_| true).len(), 2);
+        assert_eq!(
+            index.search("acme", Some(&c2), 10, |_| true),
+            vec![(SearchKey::Infra(c2), 0.9)]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Secondary indexes keeping cached entities in sort order, sorted lists are
+//! read from the index instead of sorting the whole map per request.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use qm_entity::ids::InfraId;
+use time::PrimitiveDateTime;
+
+use crate::model::{
+    ListSort, ListSortField, QmCustomer, QmInstitution, QmOrganization, SortDirection,
+};
+
+pub trait Sortable {
+    fn id(&self) -> InfraId;
+    fn name(&self) -> &Arc<str>;
+    fn created_at(&self) -> PrimitiveDateTime;
+}
+
+macro_rules! impl_sortable {
+    ($($ty:ty),*) => {
+        $(impl Sortable for $ty {
+            fn id(&self) -> InfraId {
+                self.id
+            }
+
+            fn name(&self) -> &Arc<str> {
+                &self.name
+            }
+
+            fn created_at(&self) -> PrimitiveDateTime {
+                self.created_at
+            }
+        })*
+    };
+}
+
+impl_sortable!(QmCustomer, QmOrganization, QmInstitution);
+
+/// Applies `direction` to an iterator over an index in ascending order.
+pub fn directed<'a, T: 'a>(
+    iter: impl DoubleEndedIterator<Item = T> + 'a,
+    direction: SortDirection,
+) -> Box<dyn Iterator<Item = T> + 'a> {
+    match direction {
+        SortDirection::Asc => Box::new(iter),
+        SortDirection::Desc => Box::new(iter.rev()),
+    }
+}
+
+pub struct SortIndex<T> {
+    by_name: BTreeMap<(Arc<str>, InfraId), Arc<T>>,
+    by_created_at: BTreeMap<(PrimitiveDateTime, InfraId), Arc<T>>,
+}
+
+impl<T> Default for SortIndex<T> {
+    fn default() -> Self {
+        Self {
+            by_name: BTreeMap::default(),
+            by_created_at: BTreeMap::default(),
+        }
+    }
+}
+
+impl<T: Sortable> SortIndex<T> {
+    pub fn insert(&mut self, value: Arc<T>) {
+        self.by_name
+            .insert((value.name().clone(), value.id()), value.clone());
+        self.by_created_at
+            .insert((value.created_at(), value.id()), value);
+    }
+
+    pub fn remove(&mut self, value: &T) {
+        self.by_name.remove(&(value.name().clone(), value.id()));
+        self.by_created_at.remove(&(value.created_at(), value.id()));
+    }
+
+    /// All values in the order of `sort`, fields only users have sort by name.
+    pub fn iter(&self, sort: ListSort) -> Box<dyn Iterator<Item = &Arc<T>> + '_> {
+        match sort.field {
+            ListSortField::CreatedAt => directed(self.by_created_at.values(), sort.direction),
+            ListSortField::Name
+            | ListSortField::Email
+            | ListSortField::Firstname
+            | ListSortField::Lastname => directed(self.by_name.values(), sort.direction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::QmEntityStatus;
+    use time::macros::datetime;
+
+    fn customer(id: i64, name: &str, created_at: PrimitiveDateTime) -> Arc<QmCustomer> {
+        Arc::new(QmCustomer {
+            id: id.into(),
+            name: Arc::from(name),
+            ty: Arc::from("none"),
+            status: QmEntityStatus::default(),
+            created_by: Default::default(),
+            created_at,
+            updated_by: None,
+            updated_at: None,
+        })
+    }
+
+    #[test]
+    fn test_sort_index() {
+        let mut index = SortIndex::default();
+        let b = customer(1, "b", datetime!(2024-01-01 0:00));
+        index.insert(b.clone());
+        index.insert(customer(2, "a", datetime!(2024-01-03 0:00)));
+        index.insert(customer(3, "c", datetime!(2024-01-02 0:00)));
+        let names = |index: &SortIndex<QmCustomer>, sort| -> Vec<String> {
+            index.iter(sort).map(|c| c.name.to_string()).collect()
+        };
+        assert_eq!(names(&index, ListSort::default()), ["a", "b", "c"]);
+        assert_eq!(
+            names(
+                &index,
+                ListSort {
+                    field: ListSortField::CreatedAt,
+                    direction: SortDirection::Desc,
+                }
+            ),
+            ["a", "c", "b"]
+        );
+        index.remove(&b);
+        assert_eq!(names(&index, ListSort::default()), ["a", "c"]);
+    }
+}
@@ -17,6 +17,7 @@ fn parse_type(s: &str) -> Arc<[Arc<str>]> {
     s.split(',').map(|s| s.trim().into()).collect()
 }
 
+#[derive(Default)]
 pub struct GroupAttributes {
     group_attribute_map: GroupDetailsMap,
 }
@@ -57,6 +58,10 @@ impl GroupAttributes {
         self.group_attribute_map.get(id)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&Arc<str>, &Arc<GroupDetail>)> {
+        self.group_attribute_map.iter()
+    }
+
     pub fn update(&mut self, groups: &Groups, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<GroupAttributeUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
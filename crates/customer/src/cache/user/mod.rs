@@ -1,18 +1,34 @@
-use std::sync::{atomic::AtomicI64, Arc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use futures::StreamExt;
 use prometheus_client::metrics::gauge::Gauge;
+use qm_entity::ids::InfraContext;
 use qm_keycloak::RoleRepresentation;
-use sqlx::postgres::PgListener;
-use tokio::sync::RwLock;
+use sqlx::postgres::PgNotification;
+use tokio::sync::broadcast;
+use tokio::sync::{Notify, OnceCell, RwLock};
 
 use qm_pg::DB;
 
+use crate::query::{fetch_user, fetch_users_page, UserField};
+
 use self::{
-    group_attributes::GroupAttributes, group_roles::GroupRoles, groups::Groups, realm::Realm,
-    roles::Roles, user_groups::UserGroups, user_roles::UserRoles, users::Users,
+    group_attributes::GroupAttributes,
+    group_roles::GroupRoles,
+    groups::Groups,
+    realm::Realm,
+    roles::Roles,
+    user_groups::UserGroups,
+    user_roles::UserRoles,
+    users::{user_from_row, Users},
 };
 
-use super::{Group, GroupDetail, QmUser};
+use super::events::{self, changed_id, CacheEvent, ChangeOp, EVENT_CAPACITY};
+use super::update::Payload;
+use super::{Group, GroupAttributeUpdate, GroupDetail, QmUser};
 
 pub mod group_attributes;
 pub mod group_roles;
@@ -35,47 +51,331 @@ pub struct UserDB {
     pub users_total: Gauge<i64, AtomicI64>,
     pub groups_total: Gauge<i64, AtomicI64>,
     pub roles_total: Gauge<i64, AtomicI64>,
+    pub events: broadcast::Sender<CacheEvent>,
+    realm_admin_username: Arc<str>,
+    loaded: OnceCell<()>,
+    loader: Option<UserLoader>,
+}
+
+/// Loads users on demand in lazy mode.
+struct UserLoader {
+    db: DB,
+    realm: Arc<str>,
+    realm_admin_username: Arc<str>,
+    prefetch: usize,
+    miss_ttl: Duration,
+    /// Lookups which found no user, they aren't repeated within `miss_ttl`.
+    misses: Mutex<HashMap<(UserField, Arc<str>), Instant>>,
+    list_ttl: Duration,
+    /// All users of the realm read for lists with the time and generation
+    /// of the read, reused within `list_ttl` while the generation matches.
+    all: tokio::sync::Mutex<Option<(Instant, u64, Arc<Users>)>>,
+    /// Incremented when users change.
+    generation: AtomicU64,
+}
+
+impl UserLoader {
+    fn is_missing(&self, field: UserField, value: &str) -> bool {
+        self.misses
+            .lock()
+            .unwrap()
+            .get(&(field, Arc::from(value)))
+            .is_some_and(|at| at.elapsed() < self.miss_ttl)
+    }
+
+    fn missing(&self, field: UserField, value: &str) {
+        let mut misses = self.misses.lock().unwrap();
+        if misses.len() >= MAX_MISSES {
+            misses.retain(|_, at| at.elapsed() < self.miss_ttl);
+        }
+        if misses.len() < MAX_MISSES {
+            misses.insert((field, Arc::from(value)), Instant::now());
+        }
+    }
 }
 
+/// Upper bound of remembered misses, further misses aren't remembered until
+/// older ones expired.
+const MAX_MISSES: usize = 10_000;
+
 impl UserDB {
     pub async fn new(
         db: &DB,
         realm_name: &str,
         realm_admin_username: &str,
     ) -> anyhow::Result<Self> {
-        let mut migrator = sqlx::migrate!("./migrations/keycloak");
-        migrator.set_ignore_missing(true);
-        migrator.run(db.pool()).await?;
+        db.run_migrations(sqlx::migrate!("./migrations/keycloak"))
+            .await?;
+        let users = Users::new(db, realm_name, realm_admin_username).await?;
+        let result = Self::with_users(db, realm_name, realm_admin_username, users, None).await?;
+        result.load_realm_data(db, realm_name).await?;
+        result.loaded.set(()).ok();
+        Ok(result)
+    }
+
+    /// Creates the cache without loading users, groups and roles. Groups,
+    /// roles and their mappings are loaded on first use, users on demand and
+    /// by [`UserDB::prefetch`] up to `capacity`. Lookups of missing users
+    /// are not repeated within `miss_ttl`, users read for lists are reused
+    /// within `list_ttl`.
+    pub async fn new_lazy(
+        db: &DB,
+        realm_name: &str,
+        realm_admin_username: &str,
+        capacity: usize,
+        prefetch: usize,
+        miss_ttl: Duration,
+        list_ttl: Duration,
+    ) -> anyhow::Result<Self> {
+        db.run_migrations(sqlx::migrate!("./migrations/keycloak"))
+            .await?;
+        let loader = UserLoader {
+            db: db.clone(),
+            realm: Arc::from(realm_name),
+            realm_admin_username: Arc::from(realm_admin_username),
+            prefetch,
+            miss_ttl,
+            misses: Default::default(),
+            list_ttl,
+            all: Default::default(),
+            generation: AtomicU64::new(0),
+        };
+        Self::with_users(
+            db,
+            realm_name,
+            realm_admin_username,
+            Users::lazy(capacity),
+            Some(loader),
+        )
+        .await
+    }
+
+    async fn with_users(
+        db: &DB,
+        realm_name: &str,
+        realm_admin_username: &str,
+        users: Users,
+        loader: Option<UserLoader>,
+    ) -> anyhow::Result<Self> {
         let realm = RwLock::new(Realm::new(db, realm_name).await?);
-        let roles = RwLock::new(Roles::new(db, realm_name).await?);
-        let groups = RwLock::new(Groups::new(db, realm_name).await?);
-        let group_attributes = RwLock::new(GroupAttributes::new(db, realm_name).await?);
-        let user_groups = RwLock::new(UserGroups::new(db, realm_name).await?);
-        let user_roles = RwLock::new(UserRoles::new(db, realm_name).await?);
-        let group_roles = RwLock::new(GroupRoles::new(db, realm_name).await?);
-        let users = RwLock::new(Users::new(db, realm_name, realm_admin_username).await?);
         let users_total = Gauge::default();
-        users_total.set(users.read().await.total());
-        let groups_total = Gauge::default();
-        groups_total.set(groups.read().await.total());
-        let roles_total = Gauge::default();
-        roles_total.set(roles.read().await.total());
+        users_total.set(users.total());
         Ok(Self {
             realm,
-            roles,
-            groups,
-            group_attributes,
-            user_groups,
-            user_roles,
-            group_roles,
-            users,
+            roles: Default::default(),
+            groups: Default::default(),
+            group_attributes: Default::default(),
+            user_groups: Default::default(),
+            user_roles: Default::default(),
+            group_roles: Default::default(),
+            users: RwLock::new(users),
             users_total,
-            groups_total,
-            roles_total,
+            groups_total: Gauge::default(),
+            roles_total: Gauge::default(),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            realm_admin_username: Arc::from(realm_admin_username),
+            loaded: OnceCell::new(),
+            loader,
         })
     }
 
+    async fn load_realm_data(&self, db: &DB, realm_name: &str) -> anyhow::Result<()> {
+        let roles = Roles::new(db, realm_name).await?;
+        let groups = Groups::new(db, realm_name).await?;
+        let group_attributes = GroupAttributes::new(db, realm_name).await?;
+        let user_groups = UserGroups::new(db, realm_name).await?;
+        let user_roles = UserRoles::new(db, realm_name).await?;
+        let group_roles = GroupRoles::new(db, realm_name).await?;
+        self.roles_total.set(roles.total());
+        self.groups_total.set(groups.total());
+        *self.roles.write().await = roles;
+        *self.groups.write().await = groups;
+        *self.group_attributes.write().await = group_attributes;
+        *self.user_groups.write().await = user_groups;
+        *self.user_roles.write().await = user_roles;
+        *self.group_roles.write().await = group_roles;
+        Ok(())
+    }
+
+    /// Loads groups, roles and their mappings on first use in lazy mode,
+    /// they are always loaded in eager mode.
+    pub async fn ensure_loaded(&self) -> anyhow::Result<()> {
+        let Some(loader) = self.loader.as_ref() else {
+            return Ok(());
+        };
+        self.loaded
+            .get_or_try_init(|| async {
+                self.load_realm_data(&loader.db, &loader.realm).await?;
+                tracing::info!("loaded groups and roles of realm {}", loader.realm);
+                Ok::<_, anyhow::Error>(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for [`UserDB::ensure_loaded`], lookups see empty groups and
+    /// roles if loading fails.
+    pub async fn loaded(&self) -> &Self {
+        if let Err(err) = self.ensure_loaded().await {
+            tracing::error!("unable to load groups and roles: {err:#}");
+        }
+        self
+    }
+
+    pub async fn user_context(&self, user_id: &str) -> Option<InfraContext> {
+        self.loaded().await;
+        let user_roles = self.user_roles.read().await;
+        let roles = self.roles.read().await;
+        user_roles
+            .by_user_id(user_id)
+            .and_then(|r| r.iter().find_map(|r| roles.get(r).and_then(|r| r.context)))
+    }
+
+    /// Ids of the users with a role in `context` or one of its children.
+    pub async fn user_ids_by_context(&self, context: &InfraContext) -> Vec<Arc<str>> {
+        self.loaded().await;
+        let user_roles = self.user_roles.read().await;
+        let roles = self.roles.read().await;
+        user_roles
+            .iter()
+            .filter(|(_, role_ids)| {
+                role_ids.iter().any(|r| {
+                    let role_context = roles.get(r).and_then(|r| r.context);
+                    events::visible(Some(context), role_context.as_ref())
+                })
+            })
+            .map(|(user_id, _)| user_id.clone())
+            .collect()
+    }
+
+    pub async fn group_context(&self, group_id: &str) -> Option<InfraContext> {
+        self.loaded()
+            .await
+            .group_attributes
+            .read()
+            .await
+            .get(group_id)
+            .and_then(|g| g.context)
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    /// Fetches a user missing in a lazy cache, `None` in eager mode.
+    pub async fn load_user(&self, field: UserField, value: &str) -> Option<Arc<QmUser>> {
+        let loader = self.loader.as_ref()?;
+        if loader.is_missing(field, value) {
+            return None;
+        }
+        match fetch_user(
+            &loader.db,
+            &loader.realm,
+            &loader.realm_admin_username,
+            field,
+            value,
+        )
+        .await
+        {
+            Ok(row) => {
+                let Some(user) = row.and_then(user_from_row) else {
+                    loader.missing(field, value);
+                    return None;
+                };
+                self.new_user(user.clone()).await;
+                Some(user)
+            }
+            Err(err) => {
+                tracing::error!("unable to load user by {field:?} '{value}': {err:#}");
+                None
+            }
+        }
+    }
+
+    /// All users of the realm in lazy mode, `None` in eager mode where the
+    /// cache holds all users. They are read from the database at most once
+    /// per `list_ttl`, concurrent callers wait for the same read.
+    pub async fn fetch_all(&self) -> Option<anyhow::Result<Arc<Users>>> {
+        let loader = self.loader.as_ref()?;
+        let mut all = loader.all.lock().await;
+        let generation = loader.generation.load(Ordering::Acquire);
+        if let Some((at, read_generation, users)) = all.as_ref() {
+            if *read_generation == generation && at.elapsed() < loader.list_ttl {
+                return Some(Ok(users.clone()));
+            }
+        }
+        let result = Users::new(&loader.db, &loader.realm, &loader.realm_admin_username)
+            .await
+            .map(Arc::new);
+        if let Ok(users) = result.as_ref() {
+            *all = Some((Instant::now(), generation, users.clone()));
+        }
+        Some(result)
+    }
+
+    /// Forgets the remembered misses, a user may have been created.
+    fn clear_misses(&self) {
+        if let Some(loader) = self.loader.as_ref() {
+            loader.misses.lock().unwrap().clear();
+        }
+    }
+
+    /// Discards the users read for lists, a user was changed.
+    fn users_changed(&self) {
+        if let Some(loader) = self.loader.as_ref() {
+            loader.generation.fetch_add(1, Ordering::Release);
+        }
+    }
+
+    /// Loads groups and roles and then users page by page until the lazy
+    /// cache is full, users already cached are kept as they are.
+    pub async fn prefetch(&self) -> anyhow::Result<()> {
+        self.ensure_loaded().await?;
+        let Some(loader) = self.loader.as_ref().filter(|loader| loader.prefetch > 0) else {
+            return Ok(());
+        };
+        let limit = loader.prefetch as i64;
+        let mut offset = 0;
+        loop {
+            let rows = fetch_users_page(
+                &loader.db,
+                &loader.realm,
+                &loader.realm_admin_username,
+                limit,
+                offset,
+            )
+            .await?;
+            let count = rows.len() as i64;
+            {
+                let mut users = self.users.write().await;
+                for user in rows.into_iter().filter_map(user_from_row) {
+                    if users.is_full() {
+                        break;
+                    }
+                    if !users.contains(&user.id) {
+                        users.new_user(user);
+                    }
+                }
+                self.users_total.set(users.total());
+                if users.is_full() {
+                    break;
+                }
+            }
+            if count < limit {
+                break;
+            }
+            offset += limit;
+        }
+        tracing::info!(
+            "prefetched {} users of realm {}",
+            self.users_total.get(),
+            loader.realm
+        );
+        Ok(())
+    }
+
     pub async fn new_roles(&self, roles: Vec<RoleRepresentation>) {
+        self.loaded().await;
         self.roles.write().await.new_roles(roles);
         self.roles_total.set(self.roles.read().await.total());
     }
@@ -86,6 +386,7 @@ impl UserDB {
         parent_name: Arc<str>,
         group_detail: Arc<GroupDetail>,
     ) {
+        self.loaded().await;
         self.group_attributes
             .write()
             .await
@@ -94,22 +395,33 @@ impl UserDB {
         self.groups_total.set(self.groups.read().await.total());
     }
 
+    /// Replaces the details of an existing group, attribute updates are not
+    /// part of the change events.
+    pub async fn update_group_detail(&self, group_id: Arc<str>, group_detail: Arc<GroupDetail>) {
+        self.loaded().await;
+        self.group_attributes
+            .write()
+            .await
+            .new_group(group_id, group_detail);
+    }
+
     pub async fn new_user(&self, user: Arc<QmUser>) {
+        self.clear_misses();
         self.users.write().await.new_user(user);
         self.users_total.set(self.users.read().await.total());
     }
 
     pub async fn cleanup(db: &DB) -> anyhow::Result<()> {
-        let mut migrator = sqlx::migrate!("./migrations/keycloak");
-        migrator.set_ignore_missing(true);
-        migrator.undo(db.pool(), 0).await?;
-        Ok(())
+        db.undo_migrations(sqlx::migrate!("./migrations/keycloak"))
+            .await
     }
 
+    /// Applies the notifications of the keycloak database, the cache is
+    /// reloaded after notifications were missed during a reconnect.
     pub async fn listen(&self, db: &DB) -> anyhow::Result<()> {
-        let mut listener = PgListener::connect_with(db.pool()).await?;
-        listener
-            .listen_all([
+        let gap = Arc::new(Notify::new());
+        let notifications = db.listen(
+            [
                 "realm_update",
                 "user_entity_update",
                 "keycloak_role_update",
@@ -117,75 +429,145 @@ impl UserDB {
                 "user_role_mapping_update",
                 "user_group_membership_update",
                 "group_attribute_update",
-            ])
-            .await?;
-
-        while let Some(notification) = listener.try_recv().await? {
-            match notification.channel() {
-                "realm_update" => {
-                    self.realm.write().await.update(notification.payload())?;
+            ],
+            {
+                let gap = gap.clone();
+                move || gap.notify_one()
+            },
+        );
+        tokio::pin!(notifications);
+        loop {
+            tokio::select! {
+                _ = gap.notified() => {
+                    self.reload(db).await?;
+                    tracing::info!("reloaded users after missed notifications");
                 }
-                "user_entity_update" => {
+                Some(notification) = notifications.next() => {
+                    self.notification(&notification).await?;
+                }
+            }
+        }
+    }
+
+    /// Reloads the realm, groups, roles and their mappings. Users are
+    /// reloaded in eager mode and dropped in lazy mode, they are loaded again
+    /// on demand.
+    pub async fn reload(&self, db: &DB) -> anyhow::Result<()> {
+        let realm_name = self.realm.read().await.name().to_string();
+        let realm = Realm::new(db, &realm_name).await?;
+        let users = match self.users.read().await.capacity() {
+            Some(capacity) => Users::lazy(capacity),
+            None => Users::new(db, &realm_name, &self.realm_admin_username).await?,
+        };
+        self.clear_misses();
+        self.users_changed();
+        *self.realm.write().await = realm;
+        self.users_total.set(users.total());
+        *self.users.write().await = users;
+        self.load_realm_data(db, &realm_name).await?;
+        self.loaded.set(()).ok();
+        Ok(())
+    }
+
+    async fn notification(&self, notification: &PgNotification) -> anyhow::Result<()> {
+        self.ensure_loaded().await?;
+        match notification.channel() {
+            "realm_update" => {
+                self.realm.write().await.update(notification.payload())?;
+            }
+            "user_entity_update" => {
+                self.clear_misses();
+                self.users_changed();
+                let changed = changed_id::<Arc<str>>(notification.payload());
+                let previous = match changed.as_ref() {
+                    Some((_, id)) => self.user_context(id).await,
+                    None => None,
+                };
+                {
                     let realm = self.realm.read().await;
                     self.users
                         .write()
                         .await
                         .update(&realm, notification.payload())?;
-                    self.users_total.set(self.users.read().await.total());
                 }
-                "keycloak_role_update" => {
-                    let realm = self.realm.read().await;
-                    self.roles
-                        .write()
-                        .await
-                        .update(&realm, notification.payload())?;
-                    self.roles_total.set(self.roles.read().await.total());
+                self.users_total.set(self.users.read().await.total());
+                if let Some((op, id)) = changed {
+                    let context = self.user_context(&id).await.or(previous);
+                    self.events.send(CacheEvent::User { op, id, context }).ok();
                 }
-                "keycloak_group_update" => {
+            }
+            "keycloak_role_update" => {
+                let realm = self.realm.read().await;
+                self.roles
+                    .write()
+                    .await
+                    .update(&realm, notification.payload())?;
+                self.roles_total.set(self.roles.read().await.total());
+            }
+            "keycloak_group_update" => {
+                {
                     let realm = self.realm.read().await;
                     self.groups
                         .write()
                         .await
                         .update(&realm, notification.payload())?;
-                    self.groups_total.set(self.groups.read().await.total());
                 }
-                "group_attribute_update" => {
+                self.groups_total.set(self.groups.read().await.total());
+                if let Some((op, id)) = changed_id::<Arc<str>>(notification.payload()) {
+                    let context = self.group_context(&id).await;
+                    self.events.send(CacheEvent::Group { op, id, context }).ok();
+                }
+            }
+            "group_attribute_update" => {
+                let group_id =
+                    serde_json::from_str::<Payload<GroupAttributeUpdate>>(notification.payload())
+                        .ok()
+                        .and_then(|p| p.new.or(p.old))
+                        .map(|v| v.group_id);
+                {
                     let groups = self.groups.read().await;
                     self.group_attributes
                         .write()
                         .await
                         .update(&groups, notification.payload())?;
                 }
-                "user_role_mapping_update" => {
-                    let users = self.users.read().await;
-                    let roles = self.roles.read().await;
-                    self.user_roles
-                        .write()
-                        .await
-                        .update(&users, &roles, notification.payload())?;
-                }
-                "user_group_membership_update" => {
-                    let users = self.users.read().await;
-                    let groups = self.groups.read().await;
-                    self.user_groups.write().await.update(
-                        &users,
-                        &groups,
-                        notification.payload(),
-                    )?;
+                if let Some(id) = group_id {
+                    let context = self.group_context(&id).await;
+                    self.events
+                        .send(CacheEvent::Group {
+                            op: ChangeOp::Updated,
+                            id,
+                            context,
+                        })
+                        .ok();
                 }
-                "group_role_mapping_update" => {
-                    let roles = self.roles.read().await;
-                    let groups = self.groups.read().await;
-                    self.group_roles.write().await.update(
-                        &groups,
-                        &roles,
-                        notification.payload(),
-                    )?;
-                }
-                _ => {}
             }
+            "user_role_mapping_update" => {
+                let users = self.users.read().await;
+                let roles = self.roles.read().await;
+                self.user_roles
+                    .write()
+                    .await
+                    .update(&users, &roles, notification.payload())?;
+            }
+            "user_group_membership_update" => {
+                let users = self.users.read().await;
+                let groups = self.groups.read().await;
+                self.user_groups
+                    .write()
+                    .await
+                    .update(&users, &groups, notification.payload())?;
+            }
+            "group_role_mapping_update" => {
+                let roles = self.roles.read().await;
+                let groups = self.groups.read().await;
+                self.group_roles
+                    .write()
+                    .await
+                    .update(&groups, &roles, notification.payload())?;
+            }
+            _ => {}
         }
-        tracing::error!("postgresql listener disconnected");
-        std::process::exit(1);
+        Ok(())
     }
 }
@@ -24,6 +24,10 @@ impl Realm {
         })
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn update(&mut self, payload: &str) -> anyhow::Result<()> {
         let payload: Payload<RealmUpdate> = serde_json::from_str(payload)?;
         if let (Op::Insert, Some(new)) = (payload.op, payload.new) {
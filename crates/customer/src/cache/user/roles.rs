@@ -20,6 +20,7 @@ fn parse_context(name: &str) -> Option<InfraContext> {
     None
 }
 
+#[derive(Default)]
 pub struct Roles {
     role_name_map: RoleMap,
     role_id_map: RoleIdMap,
@@ -10,6 +10,7 @@ use crate::query::fetch_user_groups;
 
 use super::{groups::Groups, users::Users};
 
+#[derive(Default)]
 pub struct UserGroups {
     user_id_group_map: UserGroupMap,
     group_id_user_map: UserGroupMap,
@@ -53,7 +54,7 @@ impl UserGroups {
         let payload: Payload<UserGroupMembershipUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if users.contains(&new.user_id) && groups.contains(&new.group_id) {
+                if users.has_mappings(&new.user_id) && groups.contains(&new.group_id) {
                     self.user_id_group_map
                         .entry(new.user_id.clone())
                         .or_default()
@@ -66,7 +67,7 @@ impl UserGroups {
                 }
             }
             (Op::Delete, None, Some(old)) => {
-                if users.contains(&old.user_id) && groups.contains(&old.group_id) {
+                if users.has_mappings(&old.user_id) && groups.contains(&old.group_id) {
                     let e = self
                         .user_id_group_map
                         .entry(old.user_id.clone())
@@ -10,6 +10,7 @@ use crate::query::fetch_user_roles;
 
 use super::{roles::Roles, users::Users};
 
+#[derive(Default)]
 pub struct UserRoles {
     user_id_role_map: UserRoleMap,
     role_id_user_map: UserRoleMap,
@@ -44,11 +45,15 @@ impl UserRoles {
         self.user_id_role_map.get(user_id)
     }
 
+    pub fn iter(&self) -> impl Iterator<Item = (&Arc<str>, &HashSet<Arc<str>>)> {
+        self.user_id_role_map.iter()
+    }
+
     pub fn update(&mut self, users: &Users, roles: &Roles, payload: &str) -> anyhow::Result<bool> {
         let payload: Payload<UserRoleMappingUpdate> = serde_json::from_str(payload)?;
         match (payload.op, payload.new, payload.old) {
             (Op::Insert, Some(new), None) => {
-                if users.contains(&new.user_id) && roles.contains(&new.role_id) {
+                if users.has_mappings(&new.user_id) && roles.contains(&new.role_id) {
                     self.user_id_role_map
                         .entry(new.user_id.clone())
                         .or_default()
@@ -61,7 +66,7 @@ impl UserRoles {
                 }
             }
             (Op::Delete, None, Some(old)) => {
-                if users.contains(&old.user_id) && roles.contains(&old.role_id) {
+                if users.has_mappings(&old.user_id) && roles.contains(&old.role_id) {
                     let e = self
                         .user_id_role_map
                         .entry(old.user_id.clone())
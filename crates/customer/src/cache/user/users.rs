@@ -1,44 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use qm_pg::DB;
 
 use crate::{
+    cache::sorted::directed,
     cache::{
         update::{Op, Payload},
         QmUser, UserEntityUpdate, UserMap,
     },
+    model::{KcUserQuery, ListSort, ListSortField},
     query::fetch_users,
 };
 
 use super::realm::Realm;
 
+/// Converts a fetched row, `None` if a required field is missing.
+pub fn user_from_row(row: KcUserQuery) -> Option<Arc<QmUser>> {
+    if !row.has_all_fields() {
+        return None;
+    }
+    Some(Arc::new(QmUser {
+        id: Arc::from(row.id.unwrap()),
+        username: Arc::from(row.username.unwrap()),
+        email: Arc::from(row.email.unwrap()),
+        firstname: Arc::from(row.firstname.unwrap()),
+        lastname: Arc::from(row.lastname.unwrap()),
+        enabled: row.enabled,
+    }))
+}
+
+/// Least recently used bookkeeping of a lazy [`Users`] cache, accesses only
+/// need shared references so lookups can stay behind a read lock.
+#[derive(Default)]
+struct Lru {
+    capacity: usize,
+    tick: AtomicU64,
+    last_access: HashMap<Arc<str>, AtomicU64>,
+}
+
+impl Lru {
+    fn touch(&self, id: &str) {
+        if let Some(last_access) = self.last_access.get(id) {
+            let tick = self.tick.fetch_add(1, Ordering::Relaxed);
+            last_access.store(tick, Ordering::Relaxed);
+        }
+    }
+
+    fn insert(&mut self, id: Arc<str>) {
+        let tick = self.tick.fetch_add(1, Ordering::Relaxed);
+        self.last_access.insert(id, AtomicU64::new(tick));
+    }
+
+    /// Returns the ids to evict, a tenth of the capacity is freed at once so
+    /// the scan doesn't run on every insert.
+    fn evict(&mut self) -> Vec<Arc<str>> {
+        if self.last_access.len() <= self.capacity {
+            return vec![];
+        }
+        let count = self.last_access.len() - self.capacity + self.capacity / 10;
+        let mut entries: Vec<(u64, Arc<str>)> = self
+            .last_access
+            .iter()
+            .map(|(id, tick)| (tick.load(Ordering::Relaxed), id.clone()))
+            .collect();
+        entries.sort_unstable_by_key(|(tick, _)| *tick);
+        entries.truncate(count);
+        entries
+            .into_iter()
+            .map(|(_, id)| {
+                self.last_access.remove(&id);
+                id
+            })
+            .collect()
+    }
+}
+
+type UserSortMap = BTreeMap<(Arc<str>, Arc<str>), Arc<QmUser>>;
+type UserSortKey = fn(&QmUser) -> &Arc<str>;
+
+/// Users ordered by the sortable fields, keys are the field value and the id.
+#[derive(Default)]
+struct UserSortIndex {
+    by_username: UserSortMap,
+    by_email: UserSortMap,
+    by_firstname: UserSortMap,
+    by_lastname: UserSortMap,
+}
+
+impl UserSortIndex {
+    fn maps(&mut self) -> [(&mut UserSortMap, UserSortKey); 4] {
+        [
+            (&mut self.by_username, |u| &u.username),
+            (&mut self.by_email, |u| &u.email),
+            (&mut self.by_firstname, |u| &u.firstname),
+            (&mut self.by_lastname, |u| &u.lastname),
+        ]
+    }
+
+    fn insert(&mut self, user: &Arc<QmUser>) {
+        for (map, field) in self.maps() {
+            map.insert((field(user).clone(), user.id.clone()), user.clone());
+        }
+    }
+
+    fn remove(&mut self, user: &QmUser) {
+        for (map, field) in self.maps() {
+            map.remove(&(field(user).clone(), user.id.clone()));
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Users {
     pub user_id_map: UserMap,
     pub users: UserMap,
     pub user_email_map: UserMap,
+    sorted: UserSortIndex,
+    lru: Option<Lru>,
 }
 
 impl Users {
+    /// Creates an empty cache holding at most about `capacity` users.
+    pub fn lazy(capacity: usize) -> Self {
+        Self {
+            lru: Some(Lru {
+                capacity,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Capacity of a lazy cache.
+    pub fn capacity(&self) -> Option<usize> {
+        self.lru.as_ref().map(|lru| lru.capacity)
+    }
+
+    pub fn is_lazy(&self) -> bool {
+        self.lru.is_some()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.lru
+            .as_ref()
+            .is_some_and(|lru| self.user_id_map.len() >= lru.capacity)
+    }
+
     pub async fn new(db: &DB, realm: &str, realm_admin_username: &str) -> anyhow::Result<Self> {
         let user_id_map = fetch_users(db, realm, realm_admin_username)
             .await?
             .into_iter()
-            .filter(|row| row.has_all_fields())
-            .fold(UserMap::default(), |mut state, row| {
-                let id: Arc<str> = Arc::from(row.id.unwrap());
-                let firstname: Arc<str> = Arc::from(row.firstname.unwrap());
-                let lastname: Arc<str> = Arc::from(row.lastname.unwrap());
-                let username: Arc<str> = Arc::from(row.username.unwrap());
-                let email: Arc<str> = Arc::from(row.email.unwrap());
-                state.entry(id.clone()).or_insert(Arc::new(QmUser {
-                    id,
-                    username,
-                    email,
-                    firstname,
-                    lastname,
-                    enabled: row.enabled,
-                }));
+            .filter_map(user_from_row)
+            .fold(UserMap::default(), |mut state, user| {
+                state.entry(user.id.clone()).or_insert(user);
                 state
             });
         let users = UserMap::from_iter(
@@ -48,11 +164,17 @@ impl Users {
         );
         let user_email_map =
             UserMap::from_iter(user_id_map.values().map(|v| (v.email.clone(), v.clone())));
+        let mut sorted = UserSortIndex::default();
+        for user in user_id_map.values() {
+            sorted.insert(user);
+        }
 
         Ok(Self {
             user_id_map,
             users,
             user_email_map,
+            sorted,
+            lru: None,
         })
     }
 
@@ -61,25 +183,75 @@ impl Users {
     }
 
     pub fn new_user(&mut self, user: Arc<QmUser>) {
+        if let Some(lru) = self.lru.as_mut() {
+            lru.insert(user.id.clone());
+        }
         self.user_id_map.insert(user.id.clone(), user.clone());
         self.users.insert(user.username.clone(), user.clone());
+        self.sorted.insert(&user);
         self.user_email_map.insert(user.email.clone(), user);
+        let evicted = self.lru.as_mut().map(Lru::evict).unwrap_or_default();
+        for id in evicted {
+            self.remove(&id);
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(user) = self.user_id_map.remove(id) {
+            self.users.remove(&user.username);
+            self.user_email_map.remove(&user.email);
+            self.sorted.remove(&user);
+        }
+        if let Some(lru) = self.lru.as_mut() {
+            lru.last_access.remove(id);
+        }
+    }
+
+    fn touched(&self, user: Option<&Arc<QmUser>>) {
+        if let Some((lru, user)) = self.lru.as_ref().zip(user) {
+            lru.touch(&user.id);
+        }
     }
 
     pub fn list(&self) -> Arc<[Arc<QmUser>]> {
         self.user_id_map.values().cloned().collect()
     }
 
+    /// All users in the order of `sort`, users are sorted by username for
+    /// fields they don't have. Sorting by creation time is rejected by the
+    /// schema.
+    pub fn sorted(&self, sort: ListSort) -> Box<dyn Iterator<Item = &Arc<QmUser>> + '_> {
+        let map = match sort.field {
+            ListSortField::Email => &self.sorted.by_email,
+            ListSortField::Firstname => &self.sorted.by_firstname,
+            ListSortField::Lastname => &self.sorted.by_lastname,
+            ListSortField::Name | ListSortField::CreatedAt => &self.sorted.by_username,
+        };
+        directed(map.values(), sort.direction)
+    }
+
     pub fn get(&self, user_id: &str) -> Option<&Arc<QmUser>> {
-        self.user_id_map.get(user_id)
+        let user = self.user_id_map.get(user_id);
+        self.touched(user);
+        user
     }
 
     pub fn by_username(&self, username: &str) -> Option<&Arc<QmUser>> {
-        self.users.get(username)
+        let user = self.users.get(username);
+        self.touched(user);
+        user
     }
 
     pub fn by_email(&self, email: &str) -> Option<&Arc<QmUser>> {
-        self.user_email_map.get(email)
+        let user = self.user_email_map.get(email);
+        self.touched(user);
+        user
+    }
+
+    /// Whether role and group mappings of the user are kept, a lazy cache
+    /// keeps them for users which aren't loaded.
+    pub fn has_mappings(&self, user_id: &str) -> bool {
+        self.is_lazy() || self.contains(user_id)
     }
 
     pub fn contains(&self, user_id: &str) -> bool {
@@ -115,19 +287,13 @@ impl Users {
                         lastname: new.last_name.unwrap(),
                         enabled: new.enabled,
                     });
-                    self.user_id_map.remove(&user.id);
-                    self.users.remove(&user.username);
-                    self.user_email_map.remove(&user.email);
+                    self.remove(&user.id);
                     self.new_user(user);
                 }
             }
             (Op::Delete, None, Some(old)) => {
                 if realm.equals(old.realm_id.as_deref()) {
-                    self.user_id_map.remove(&old.id);
-                    self.users.remove(&old.username);
-                    if old.email.is_some() {
-                        self.user_email_map.remove(&old.email.unwrap());
-                    }
+                    self.remove(&old.id);
                 }
             }
             _ => {}
@@ -135,3 +301,56 @@ impl Users {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: usize) -> Arc<QmUser> {
+        Arc::new(QmUser {
+            id: Arc::from(format!("id-{id}")),
+            username: Arc::from(format!("user-{id}")),
+            email: Arc::from(format!("user-{id}@example.com")),
+            firstname: Arc::from("first"),
+            lastname: Arc::from("last"),
+            enabled: true,
+        })
+    }
+
+    #[test]
+    fn test_lazy_eviction() {
+        let mut users = Users::lazy(10);
+        for id in 0..10 {
+            users.new_user(user(id));
+        }
+        assert!(users.is_full());
+        assert!(users.by_username("user-0").is_some());
+        users.new_user(user(10));
+        assert_eq!(users.total(), 9);
+        assert!(users.get("id-0").is_some());
+        assert!(users.get("id-1").is_none());
+        assert!(users.has_mappings("id-1"));
+        assert!(users.by_email("user-2@example.com").is_none());
+        assert!(users.get("id-10").is_some());
+    }
+
+    #[test]
+    fn test_sorted() {
+        use crate::model::SortDirection;
+        let mut users = Users::default();
+        for id in [2, 1, 3] {
+            users.new_user(user(id));
+        }
+        let ids = |users: &Users, sort| -> Vec<String> {
+            users.sorted(sort).map(|u| u.id.to_string()).collect()
+        };
+        assert_eq!(ids(&users, ListSort::default()), ["id-1", "id-2", "id-3"]);
+        let desc = ListSort {
+            field: ListSortField::Email,
+            direction: SortDirection::Desc,
+        };
+        assert_eq!(ids(&users, desc), ["id-3", "id-2", "id-1"]);
+        users.remove("id-2");
+        assert_eq!(ids(&users, desc), ["id-3", "id-1"]);
+    }
+}
@@ -9,7 +9,9 @@ use strum::AsRefStr;
 use tokio::sync::Semaphore;
 use tracing::error;
 
+use qm_entity::ids::CustomerId;
 use qm_entity::ids::CustomerIds;
+use qm_entity::ids::InstitutionId;
 use qm_entity::ids::InstitutionIds;
 use qm_entity::ids::OrganizationIds;
 use qm_keycloak::Keycloak;
@@ -22,10 +24,24 @@ use sqlx::types::Uuid;
 pub enum CleanupTaskType {
     #[strum(serialize = "customers")]
     Customers(CustomerIds),
+    /// Merges the first customer into the second, requested by the user.
+    #[strum(serialize = "merge_customers")]
+    MergeCustomers(CustomerId, CustomerId, Option<Uuid>),
     #[strum(serialize = "organizations")]
     Organizations(OrganizationIds),
     #[strum(serialize = "institutions")]
     Institutions(InstitutionIds),
+    /// Institution moved from the first to the second id.
+    #[strum(serialize = "move_institution")]
+    MoveInstitution(InstitutionId, InstitutionId),
+    #[strum(serialize = "user_import")]
+    UserImport(String),
+    #[strum(serialize = "expire_api_clients")]
+    ExpireApiClients,
+    #[strum(serialize = "suspend_users")]
+    SuspendUsers(String),
+    #[strum(serialize = "reactivate_users")]
+    ReactivateUsers(String),
     #[default]
     #[strum(serialize = "none")]
     None,
@@ -93,6 +109,11 @@ pub async fn cleanup_api_clients(
                         || client_ids_set.contains(&InfraContext::Organization(v.parent()))
                         || client_ids_set.contains(&InfraContext::Customer(v.root()))
                 }
+                InfraContext::OrganizationUnit(v) => {
+                    client_ids_set.contains(&id)
+                        || client_ids_set.contains(&v.parent())
+                        || client_ids_set.contains(&InfraContext::Customer(v.root()))
+                }
             })
             .unwrap_or(false)
     });
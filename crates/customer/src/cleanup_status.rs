@@ -0,0 +1,280 @@
+//! Status of cleanup tasks.
+//!
+//! Tasks enqueued with [`enqueue`] are recorded as pending and updated by the
+//! cleanup worker, records are stored in the [`CLEANUP_TASK_COLLECTION`]
+//! collection of the object database and can be queried by admins with
+//! `qmCleanupTasks`.
+
+use async_graphql::{Context, Enum, Object, ResultExt, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use qm_entity::err;
+use qm_entity::model::ListFilter;
+use qm_kafka::producer::EventType;
+use qm_mongodb::bson::{doc, Document};
+use qm_mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::cleanup::CleanupTask;
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::schema::auth::AuthCtx;
+
+pub const CLEANUP_TASK_COLLECTION: &str = "qm_cleanup_tasks";
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmCleanupTaskStatus")]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupTaskStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QmCleanupTask {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub ty: String,
+    pub status: CleanupTaskStatus,
+    pub error: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl QmCleanupTask {
+    pub fn new(task: &CleanupTask) -> Self {
+        let now = Utc::now();
+        Self {
+            id: task.id.to_string(),
+            ty: task.ty.as_ref().to_string(),
+            status: CleanupTaskStatus::Pending,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub async fn save(&self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(CLEANUP_TASK_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// Updates the status of `task`, tasks which were not enqueued with
+    /// [`enqueue`] are recorded on their first update.
+    pub async fn set_status(
+        db: &qm_mongodb::DB,
+        task: &CleanupTask,
+        status: CleanupTaskStatus,
+        error: Option<String>,
+    ) -> qm_mongodb::error::Result<Option<Self>> {
+        let now = qm_mongodb::bson::DateTime::from_chrono(Utc::now());
+        let collection = db.get().collection::<Self>(CLEANUP_TASK_COLLECTION);
+        collection
+            .update_one(
+                doc! { "_id": task.id.to_string() },
+                doc! {
+                    "$set": {
+                        "status": qm_mongodb::bson::to_bson(&status)?,
+                        "error": error,
+                        "updatedAt": now,
+                    },
+                    "$setOnInsert": {
+                        "ty": task.ty.as_ref(),
+                        "createdAt": now,
+                    },
+                },
+            )
+            .upsert(true)
+            .await?;
+        collection
+            .find_one(doc! { "_id": task.id.to_string() })
+            .await
+    }
+}
+
+#[derive(Debug, Clone, Default, SimpleObject)]
+pub struct QmCleanupTaskCounts {
+    pub pending: i64,
+    pub running: i64,
+    pub done: i64,
+    pub failed: i64,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmCleanupTaskList {
+    pub items: Vec<QmCleanupTask>,
+    pub counts: QmCleanupTaskCounts,
+    pub limit: Option<i64>,
+    pub total: Option<i64>,
+    pub page: Option<i64>,
+}
+
+/// Records `task` as pending and adds it to the cleanup queue.
+pub async fn enqueue<Store>(store: &Store, task: &CleanupTask) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    QmCleanupTask::new(task).save(store.as_ref()).await?;
+    store.cleanup_task_producer().add_item(task).await
+}
+
+/// Stores the outcome of `task` and emits an update event for it, failures
+/// are logged and don't fail the task.
+pub async fn finish<Store>(store: &Store, task: &CleanupTask, result: &anyhow::Result<()>)
+where
+    Store: RelatedStorage,
+{
+    let (status, error) = match result {
+        Ok(_) => (CleanupTaskStatus::Done, None),
+        Err(err) => (CleanupTaskStatus::Failed, Some(format!("{err:#}"))),
+    };
+    let record = match QmCleanupTask::set_status(store.as_ref(), task, status, error).await {
+        Ok(record) => record,
+        Err(err) => {
+            tracing::error!(
+                "unable to record status of cleanup task '{}': {err:#}",
+                task.id
+            );
+            return;
+        }
+    };
+    if let Some((producer, record)) = store.mutation_event_producer().zip(record) {
+        if let Err(err) = producer
+            .event(
+                EventType::Update,
+                "cleanup_task",
+                "cleanup_task",
+                "sys",
+                &record,
+            )
+            .await
+        {
+            tracing::error!(
+                "unable to emit event for cleanup task '{}': {err:#}",
+                task.id
+            );
+        }
+    }
+}
+
+pub async fn list(
+    db: &qm_mongodb::DB,
+    query: Document,
+    filter: Option<ListFilter>,
+) -> qm_mongodb::error::Result<QmCleanupTaskList> {
+    let collection = db
+        .get()
+        .collection::<QmCleanupTask>(CLEANUP_TASK_COLLECTION);
+    let limit = filter.as_ref().and_then(|f| f.limit).unwrap_or(100) as i64;
+    let page = filter.as_ref().and_then(|f| f.page).unwrap_or(0) as i64;
+    let total = collection.count_documents(query.clone()).await?;
+    let mut counts = QmCleanupTaskCounts::default();
+    for (status, count) in [
+        (CleanupTaskStatus::Pending, &mut counts.pending),
+        (CleanupTaskStatus::Running, &mut counts.running),
+        (CleanupTaskStatus::Done, &mut counts.done),
+        (CleanupTaskStatus::Failed, &mut counts.failed),
+    ] {
+        *count = collection
+            .count_documents(doc! { "status": qm_mongodb::bson::to_bson(&status)? })
+            .await? as i64;
+    }
+    let options = FindOptions::builder()
+        .sort(doc! { "createdAt": -1 })
+        .limit(limit)
+        .skip((page * limit) as u64)
+        .build();
+    let items = collection
+        .find(query)
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(QmCleanupTaskList {
+        items,
+        counts,
+        limit: Some(limit),
+        total: Some(total as i64),
+        page: Some(page),
+    })
+}
+
+pub struct CleanupTaskQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for CleanupTaskQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    CleanupTaskQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    async fn qm_cleanup_tasks(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ListFilter>,
+        status: Option<CleanupTaskStatus>,
+        ty: Option<String>,
+    ) -> async_graphql::FieldResult<QmCleanupTaskList> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        if !auth_ctx.is_admin {
+            return err!(unauthorized(&auth_ctx.auth)).extend();
+        }
+        let mut query = Document::new();
+        if let Some(status) = status {
+            query.insert("status", qm_mongodb::bson::to_bson(&status)?);
+        }
+        if let Some(ty) = ty {
+            query.insert("ty", ty);
+        }
+        Ok(list(
+            AsRef::<qm_mongodb::DB>::as_ref(auth_ctx.store),
+            query,
+            filter,
+        )
+        .await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup::CleanupTaskType;
+
+    #[test]
+    fn test_new_task() {
+        let task = CleanupTask::new(CleanupTaskType::UserImport("i1".to_string()));
+        let record = QmCleanupTask::new(&task);
+        assert_eq!(record.id, task.id.to_string());
+        assert_eq!(record.ty, "user_import");
+        assert_eq!(record.status, CleanupTaskStatus::Pending);
+        assert_eq!(
+            qm_mongodb::bson::to_bson(&CleanupTaskStatus::Failed).unwrap(),
+            qm_mongodb::bson::Bson::String("failed".to_string())
+        );
+    }
+}
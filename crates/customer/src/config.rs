@@ -22,10 +22,36 @@ impl<'a> ConfigBuilder<'a> {
     }
 }
 
+/// How the user cache is populated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// All users of the realm are loaded at startup.
+    #[default]
+    Eager,
+    /// Users are loaded on demand and by a background prefetch, the least
+    /// recently used ones are evicted above `cache_capacity`.
+    Lazy,
+}
+
 #[derive(Clone, serde::Deserialize, Debug)]
 pub struct Config {
     #[serde(default)]
     allow_multiple_admin_users: bool,
+    #[serde(default)]
+    cache_mode: CacheMode,
+    cache_capacity: Option<usize>,
+    cache_prefetch: Option<usize>,
+    cache_miss_ttl: Option<u64>,
+    cache_list_ttl: Option<u64>,
+    invitation_ttl: Option<u64>,
+    invitation_redirect_url: Option<String>,
+    invitation_client_id: Option<String>,
+    webhook_max_attempts: Option<u32>,
+    webhook_backoff_ms: Option<u64>,
+    webhook_timeout: Option<u64>,
+    #[serde(default)]
+    webhook_allowed_hosts: Vec<String>,
 }
 
 impl Config {
@@ -36,6 +62,66 @@ impl Config {
     pub fn builder<'a>() -> ConfigBuilder<'a> {
         ConfigBuilder::default()
     }
+
+    pub fn cache_mode(&self) -> CacheMode {
+        self.cache_mode
+    }
+
+    pub fn cache_capacity(&self) -> usize {
+        self.cache_capacity.unwrap_or(10_000)
+    }
+
+    /// Number of users loaded per page by the background prefetch, `0`
+    /// disables it.
+    pub fn cache_prefetch(&self) -> usize {
+        self.cache_prefetch.unwrap_or(1_000)
+    }
+
+    /// Seconds a lookup of a missing user isn't repeated in lazy mode.
+    pub fn cache_miss_ttl(&self) -> u64 {
+        self.cache_miss_ttl.unwrap_or(30)
+    }
+
+    /// Seconds the users read for lists are reused in lazy mode, changes of
+    /// users discard them earlier.
+    pub fn cache_list_ttl(&self) -> u64 {
+        self.cache_list_ttl.unwrap_or(30)
+    }
+
+    /// Seconds until an invitation link expires, defaults to 7 days.
+    pub fn invitation_ttl(&self) -> u64 {
+        self.invitation_ttl.unwrap_or(604_800)
+    }
+
+    pub fn invitation_redirect_url(&self) -> Option<&str> {
+        self.invitation_redirect_url.as_deref()
+    }
+
+    pub fn invitation_client_id(&self) -> Option<&str> {
+        self.invitation_client_id.as_deref()
+    }
+
+    /// Attempts to deliver a webhook event before it is logged as failed.
+    pub fn webhook_max_attempts(&self) -> u32 {
+        self.webhook_max_attempts.unwrap_or(5)
+    }
+
+    /// Delay before the first retry of a webhook delivery, doubled for each
+    /// further retry.
+    pub fn webhook_backoff_ms(&self) -> u64 {
+        self.webhook_backoff_ms.unwrap_or(1_000)
+    }
+
+    /// Seconds to wait for the response of a webhook endpoint.
+    pub fn webhook_timeout(&self) -> u64 {
+        self.webhook_timeout.unwrap_or(10)
+    }
+
+    /// Hosts webhooks may be delivered to even if they resolve to non-public
+    /// addresses, comma separated.
+    pub fn webhook_allowed_hosts(&self) -> &[String] {
+        &self.webhook_allowed_hosts
+    }
 }
 
 pub struct SchemaConfig<'a>(Option<&'a Config>);
@@ -50,4 +136,67 @@ impl<'a> SchemaConfig<'a> {
             .map(|v| v.allow_multiple_admin_users)
             .unwrap_or(false)
     }
+
+    pub fn invitation_ttl(&self) -> u64 {
+        self.0.map(Config::invitation_ttl).unwrap_or(604_800)
+    }
+
+    pub fn invitation_redirect_url(&self) -> Option<&str> {
+        self.0.and_then(Config::invitation_redirect_url)
+    }
+
+    pub fn invitation_client_id(&self) -> Option<&str> {
+        self.0.and_then(Config::invitation_client_id)
+    }
+
+    pub fn webhook_allowed_hosts(&self) -> &[String] {
+        self.0
+            .map(Config::webhook_allowed_hosts)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cache_config_test() -> envy::Result<()> {
+        std::env::set_var("LAZY_QM_CUSTOMER_CACHE_MODE", "lazy");
+        std::env::set_var("LAZY_QM_CUSTOMER_CACHE_CAPACITY", "500");
+        let cfg = Config::builder().with_prefix("LAZY_QM_CUSTOMER_").build()?;
+        assert_eq!(cfg.cache_mode(), CacheMode::Lazy);
+        assert_eq!(cfg.cache_capacity(), 500);
+        assert_eq!(cfg.cache_prefetch(), 1_000);
+        assert_eq!(cfg.cache_miss_ttl(), 30);
+        assert_eq!(cfg.cache_list_ttl(), 30);
+        let cfg = Config::builder()
+            .with_prefix("DEFAULT_QM_CUSTOMER_")
+            .build()?;
+        assert_eq!(cfg.cache_mode(), CacheMode::Eager);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_invitation_config_test() -> envy::Result<()> {
+        std::env::set_var("INVITE_QM_CUSTOMER_INVITATION_TTL", "3600");
+        std::env::set_var(
+            "INVITE_QM_CUSTOMER_INVITATION_REDIRECT_URL",
+            "https://app.example.com/welcome",
+        );
+        let cfg = Config::builder()
+            .with_prefix("INVITE_QM_CUSTOMER_")
+            .build()?;
+        assert_eq!(cfg.invitation_ttl(), 3600);
+        assert_eq!(
+            cfg.invitation_redirect_url(),
+            Some("https://app.example.com/welcome")
+        );
+        assert_eq!(cfg.invitation_client_id(), None);
+        let cfg = Config::builder()
+            .with_prefix("DEFAULT_INVITE_QM_CUSTOMER_")
+            .build()?;
+        assert_eq!(cfg.invitation_ttl(), 604_800);
+        Ok(())
+    }
 }
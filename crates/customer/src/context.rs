@@ -1,5 +1,9 @@
+use std::collections::HashMap;
 use std::hash::Hash;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use async_graphql::{Context, FieldResult, Guard};
 
 use qm_entity::AsNumber;
 use qm_entity::FromGraphQLContext;
@@ -17,11 +21,13 @@ use qm_redis::Redis;
 // use crate::cache::Cache;
 // use crate::cache::CacheDB;
 use crate::groups::RelatedGroups;
+use crate::marker::StoreMarker;
 // use crate::roles::RoleDB;
 // use crate::schema::customer::CustomerDB;
 // use crate::schema::institution::InstitutionDB;
 // use crate::schema::organization::OrganizationDB;
 // use crate::schema::organization_unit::OrganizationUnitDB;
+use crate::schema::auth::AuthCtx;
 use crate::schema::user::KeycloakClient;
 // use crate::schema::user::UserDB;
 use crate::worker::CleanupTaskProducer;
@@ -163,3 +169,119 @@ pub trait RelatedPermission:
     + 'static
 {
 }
+
+/// Per request cache of authorization decisions.
+///
+/// Services add it to the request data, e.g. `request.data(Guards::default())`,
+/// without it every check resolves the session and the roles again.
+#[derive(Default)]
+pub struct Guards {
+    decisions: Mutex<HashMap<Arc<str>, FieldResult<()>>>,
+}
+
+impl Guards {
+    pub fn decision(&self, key: &str) -> Option<FieldResult<()>> {
+        self.decisions.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: impl Into<Arc<str>>, decision: FieldResult<()>) {
+        self.decisions.lock().unwrap().insert(key.into(), decision);
+    }
+
+    /// Checks if the current session satisfies `role`, decisions are reused
+    /// within the request if [`Guards`] is part of the request data.
+    pub async fn check<Auth, Store, Resource, Permission>(
+        ctx: &Context<'_>,
+        role: &qm_role::Role<Resource, Permission>,
+    ) -> FieldResult<()>
+    where
+        Auth: RelatedAuth<Resource, Permission>,
+        Store: RelatedStorage,
+        Resource: RelatedResource,
+        Permission: RelatedPermission,
+    {
+        let guards = ctx.data_opt::<Guards>();
+        let key = role.to_string();
+        if let Some(decision) = guards.and_then(|guards| guards.decision(&key)) {
+            return decision;
+        }
+        let decision = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(ctx, role)
+            .await
+            .map(|_| ());
+        if let Some(guards) = guards {
+            guards.insert(key, decision.clone());
+        }
+        decision
+    }
+}
+
+/// Guard for a resource and permission pair, e.g.
+/// `#[graphql(guard = "ResourceGuard::<Auth, Store, Resource, Permission>::view(Resource::user())")]`.
+pub struct ResourceGuard<Auth, Store, Resource, Permission>
+where
+    Resource: std::fmt::Debug + std::marker::Copy + Clone,
+    Permission: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    role: qm_role::Role<Resource, Permission>,
+    _marker: StoreMarker<Auth, Store>,
+}
+
+impl<Auth, Store, Resource, Permission> ResourceGuard<Auth, Store, Resource, Permission>
+where
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    pub fn new(resource: Resource, permission: Permission) -> Self {
+        Self {
+            role: qm_role::role!(resource, permission),
+            _marker: Default::default(),
+        }
+    }
+
+    pub fn list(resource: Resource) -> Self {
+        Self::new(resource, Permission::list())
+    }
+
+    pub fn view(resource: Resource) -> Self {
+        Self::new(resource, Permission::view())
+    }
+
+    pub fn create(resource: Resource) -> Self {
+        Self::new(resource, Permission::create())
+    }
+
+    pub fn update(resource: Resource) -> Self {
+        Self::new(resource, Permission::update())
+    }
+
+    pub fn delete(resource: Resource) -> Self {
+        Self::new(resource, Permission::delete())
+    }
+}
+
+impl<Auth, Store, Resource, Permission> Guard for ResourceGuard<Auth, Store, Resource, Permission>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    async fn check(&self, ctx: &Context<'_>) -> FieldResult<()> {
+        Guards::check::<Auth, Store, Resource, Permission>(ctx, &self.role).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guards() {
+        let guards = Guards::default();
+        assert!(guards.decision("user:view").is_none());
+        guards.insert("user:view", Ok(()));
+        guards.insert("user:delete", Err("unauthorized".into()));
+        assert!(guards.decision("user:view").unwrap().is_ok());
+        assert!(guards.decision("user:delete").unwrap().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Role bundles defined by customers in addition to the built-in groups.
+//!
+//! The Keycloak group holds the members and role mappings, the definition is
+//! stored in the [`CUSTOM_GROUP_COLLECTION`] collection of the object database
+//! with the Keycloak group id as key.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_graphql::{ComplexObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use qm_entity::ids::InfraContext;
+use qm_mongodb::bson::doc;
+use qm_role::AccessLevel;
+use serde::{Deserialize, Serialize};
+
+pub const CUSTOM_GROUP_COLLECTION: &str = "qm_custom_groups";
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "QmCustomGroup", complex)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomGroup {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub context: String,
+    pub name: String,
+    pub path: String,
+    #[graphql(skip)]
+    pub allowed_access_levels: Vec<String>,
+    pub allowed_types: Vec<String>,
+    pub roles: Vec<String>,
+    pub created_by: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    pub updated_by: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[ComplexObject]
+impl CustomGroup {
+    async fn allowed_access_levels(&self) -> Vec<AccessLevel> {
+        self.access_levels().into_iter().collect()
+    }
+}
+
+impl CustomGroup {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: impl Into<String>,
+        context: &InfraContext,
+        name: String,
+        path: String,
+        allowed_access_levels: &HashSet<AccessLevel>,
+        allowed_types: &HashSet<String>,
+        roles: &HashSet<String>,
+        created_by: Option<&impl ToString>,
+    ) -> Self {
+        let now = Utc::now();
+        let created_by = created_by.map(ToString::to_string);
+        let mut group = Self {
+            id: id.into(),
+            context: context.to_string(),
+            name,
+            path,
+            allowed_access_levels: vec![],
+            allowed_types: vec![],
+            roles: vec![],
+            updated_by: created_by.clone(),
+            created_by,
+            created_at: now,
+            updated_at: now,
+        };
+        group.set(allowed_access_levels, allowed_types, roles);
+        group
+    }
+
+    /// Replaces the access levels, types and roles, values are sorted to keep
+    /// the stored definition stable.
+    pub fn set(
+        &mut self,
+        allowed_access_levels: &HashSet<AccessLevel>,
+        allowed_types: &HashSet<String>,
+        roles: &HashSet<String>,
+    ) {
+        self.allowed_access_levels = sorted(allowed_access_levels.iter().map(|l| l.as_ref()));
+        self.allowed_types = sorted(allowed_types.iter().map(String::as_str));
+        self.roles = sorted(roles.iter().map(String::as_str));
+    }
+
+    pub fn access_levels(&self) -> HashSet<AccessLevel> {
+        self.allowed_access_levels
+            .iter()
+            .filter_map(|l| l.parse().ok())
+            .collect()
+    }
+
+    pub async fn by_id(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<Option<Self>> {
+        db.get()
+            .collection::<Self>(CUSTOM_GROUP_COLLECTION)
+            .find_one(doc! { "_id": id })
+            .await
+    }
+
+    pub async fn list(
+        db: &qm_mongodb::DB,
+        context: &InfraContext,
+    ) -> qm_mongodb::error::Result<Vec<Self>> {
+        db.get()
+            .collection::<Self>(CUSTOM_GROUP_COLLECTION)
+            .find(doc! { "context": context.to_string() })
+            .sort(doc! { "name": 1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    pub async fn save(&self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(CUSTOM_GROUP_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(CUSTOM_GROUP_COLLECTION)
+            .delete_one(doc! { "_id": id })
+            .await?;
+        Ok(())
+    }
+}
+
+fn sorted<'a>(values: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut values: Vec<String> = values.map(str::to_string).collect();
+    values.sort();
+    values
+}
+
+/// Roles of a custom group which are not granted by any built-in group, the
+/// role matrix of the application is the upper bound for custom groups.
+pub fn ungranted_roles<'a>(
+    roles: &'a HashSet<String>,
+    granted: &HashSet<Arc<str>>,
+) -> Vec<&'a str> {
+    let mut result: Vec<&str> = roles
+        .iter()
+        .map(String::as_str)
+        .filter(|role| !granted.contains(*role))
+        .collect();
+    result.sort();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_group() {
+        use qm_entity::ids::CustomerId;
+        let context = InfraContext::Customer(CustomerId::from(1i64));
+        let roles = HashSet::from(["user:view".to_string(), "user:create".to_string()]);
+        let group = CustomGroup::new(
+            "g1",
+            &context,
+            "Reviewers".to_string(),
+            "/custom@V1/reviewers".to_string(),
+            &HashSet::from([AccessLevel::Customer, AccessLevel::Institution]),
+            &HashSet::new(),
+            &roles,
+            None::<&String>,
+        );
+        assert_eq!(group.roles, vec!["user:create", "user:view"]);
+        assert_eq!(
+            group.access_levels(),
+            HashSet::from([AccessLevel::Customer, AccessLevel::Institution])
+        );
+        let granted = HashSet::from([Arc::from("user:view")]);
+        assert_eq!(ungranted_roles(&roles, &granted), vec!["user:create"]);
+        let granted = HashSet::from([Arc::from("user:view"), Arc::from("user:create")]);
+        assert!(ungranted_roles(&roles, &granted).is_empty());
+    }
+}
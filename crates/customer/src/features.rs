@@ -0,0 +1,358 @@
+//! Feature flags of customers.
+//!
+//! Flags are stored in the `customer_features` table and cached in
+//! [`crate::cache::CacheDB`], the cache of every instance is updated by the
+//! `customer_features_update` notifications. Organizations and institutions
+//! inherit the flags of their customer, resolvers check them with
+//! [`HasFeature`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use async_graphql::{Context, FieldResult, Object, ResultExt, SimpleObject};
+use qm_entity::err;
+use qm_entity::error::{EntityError, EntityResult};
+use qm_entity::ids::{CustomerId, InfraContext, InfraId};
+use qm_pg::DB;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::FromRow;
+use tokio::sync::RwLock;
+
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
+use crate::cache::update::{Op, Payload};
+use crate::cache::CacheDB;
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::model::QmCustomer;
+use crate::schema::auth::AuthCtx;
+
+pub const FEATURE_NAME_MAX_LEN: usize = 255;
+
+#[derive(Debug, Clone, PartialEq, Eq, SimpleObject, FromRow, Serialize, Deserialize)]
+pub struct QmFeature {
+    #[graphql(skip)]
+    pub customer_id: InfraId,
+    pub name: String,
+    pub enabled: bool,
+}
+
+/// Names may contain alphanumeric characters, `_`, `-`, `.` and `:`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= FEATURE_NAME_MAX_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+pub async fn fetch_features(db: &DB) -> anyhow::Result<Vec<QmFeature>> {
+    Ok(
+        sqlx::query_as("SELECT customer_id, name, enabled FROM customer_features")
+            .fetch_all(db.read())
+            .await?,
+    )
+}
+
+pub async fn set_feature(
+    db: &DB,
+    customer_id: InfraId,
+    name: &str,
+    enabled: bool,
+    updated_by: &Uuid,
+) -> anyhow::Result<QmFeature> {
+    Ok(sqlx::query_as(
+        r#"
+INSERT INTO customer_features ( customer_id, name, enabled, updated_by )
+VALUES ( $1, $2, $3, $4 )
+ON CONFLICT ( customer_id, name )
+DO UPDATE SET enabled = $3, updated_by = $4, updated_at = NOW()
+RETURNING customer_id, name, enabled"#,
+    )
+    .bind(customer_id.as_ref())
+    .bind(name)
+    .bind(enabled)
+    .bind(updated_by)
+    .fetch_one(db.pool())
+    .await?)
+}
+
+/// Cached flags by customer.
+#[derive(Default)]
+pub struct FeatureDB {
+    features: RwLock<HashMap<InfraId, BTreeMap<Arc<str>, bool>>>,
+}
+
+impl FeatureDB {
+    pub async fn load(&self, db: &DB) -> anyhow::Result<()> {
+        for feature in fetch_features(db).await? {
+            self.set(feature).await;
+        }
+        Ok(())
+    }
+
+    pub async fn set(&self, feature: QmFeature) {
+        self.features
+            .write()
+            .await
+            .entry(feature.customer_id)
+            .or_default()
+            .insert(Arc::from(feature.name), feature.enabled);
+    }
+
+    pub async fn remove(&self, feature: &QmFeature) {
+        let mut features = self.features.write().await;
+        if let Some(v) = features.get_mut(&feature.customer_id) {
+            v.remove(feature.name.as_str());
+            if v.is_empty() {
+                features.remove(&feature.customer_id);
+            }
+        }
+    }
+
+    pub async fn is_enabled(&self, customer_id: &InfraId, name: &str) -> bool {
+        self.features
+            .read()
+            .await
+            .get(customer_id)
+            .and_then(|v| v.get(name))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub async fn by_customer_id(&self, customer_id: &InfraId) -> Vec<QmFeature> {
+        self.features
+            .read()
+            .await
+            .get(customer_id)
+            .into_iter()
+            .flatten()
+            .map(|(name, enabled)| QmFeature {
+                customer_id: *customer_id,
+                name: name.to_string(),
+                enabled: *enabled,
+            })
+            .collect()
+    }
+
+    pub(crate) async fn update(&self, payload: &str) -> anyhow::Result<()> {
+        let payload: Payload<QmFeature> = serde_json::from_str(payload)?;
+        match (payload.op, payload.new, payload.old) {
+            (Op::Insert | Op::Update, Some(new), _) => self.set(new).await,
+            (Op::Delete, None, Some(old)) => self.remove(&old).await,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Checks feature flags of the current tenant.
+#[async_trait::async_trait]
+pub trait HasFeature {
+    async fn has_feature(&self, name: &str) -> bool;
+
+    /// Fails with `NotAllowed` if the feature is not enabled.
+    async fn require_feature(&self, name: &str) -> EntityResult<()> {
+        if !self.has_feature(name).await {
+            return err!(not_allowed(format!("the feature '{name}' is not enabled")));
+        }
+        Ok(())
+    }
+}
+
+/// Feature flags of `context`, which are the flags of its customer.
+pub async fn context_has_feature(cache: &CacheDB, context: &InfraContext, name: &str) -> bool {
+    cache
+        .features()
+        .is_enabled(&context.customer_id(), name)
+        .await
+}
+
+/// Admins and support users have all features, other users the features of
+/// the customer of their session.
+#[async_trait::async_trait]
+impl<Auth, Store, Resource, Permission> HasFeature
+    for AuthCtx<'_, Auth, Store, Resource, Permission>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    async fn has_feature(&self, name: &str) -> bool {
+        if self.is_admin || self.is_support {
+            return true;
+        }
+        let context = self
+            .auth
+            .session_access()
+            .and_then(|access| access.id())
+            .and_then(|id| InfraContext::parse(id).ok());
+        match context {
+            Some(context) => context_has_feature(self.store.cache_db(), &context, name).await,
+            None => false,
+        }
+    }
+}
+
+pub struct FeatureQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for FeatureQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    FeatureQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Feature flags of the customer of `context`, defaults to the context
+    /// of the session.
+    async fn features(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<InfraContext>,
+    ) -> FieldResult<Vec<QmFeature>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        let context = auth_ctx
+            .enforce_current_context(context)
+            .await
+            .extend()?
+            .ok_or(EntityError::bad_request("QmFeature", "context is required"))
+            .extend()?;
+        Ok(auth_ctx
+            .store
+            .cache_db()
+            .features()
+            .by_customer_id(&context.customer_id())
+            .await)
+    }
+}
+
+pub struct FeatureMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for FeatureMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    FeatureMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Enables or disables a feature of the customer, admins only.
+    async fn set_feature(
+        &self,
+        ctx: &Context<'_>,
+        context: CustomerId,
+        name: String,
+        enabled: bool,
+    ) -> FieldResult<QmFeature> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        if !auth_ctx.is_admin {
+            return err!(unauthorized(&auth_ctx.auth)).extend();
+        }
+        if !is_valid_name(&name) {
+            return err!(bad_request(
+                "QmFeature",
+                format!("invalid feature name '{name}'")
+            ))
+            .extend();
+        }
+        let customer_id: InfraId = context.into();
+        let cache = auth_ctx.store.cache_db();
+        if cache.customer_by_id(&customer_id).await.is_none() {
+            return err!(not_found_by_id::<QmCustomer>(context.to_string())).extend();
+        }
+        let before = cache
+            .features()
+            .by_customer_id(&customer_id)
+            .await
+            .into_iter()
+            .find(|v| v.name == name);
+        let feature = set_feature(
+            auth_ctx.store.customer_db(),
+            customer_id,
+            &name,
+            enabled,
+            auth_ctx.auth.user_id().unwrap(),
+        )
+        .await?;
+        cache.features().set(feature.clone()).await;
+        let context = InfraContext::Customer(context);
+        auth_ctx
+            .audit(
+                QmAuditEntry::new(AuditEntity::Customer, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(before.as_ref(), Some(&feature)),
+            )
+            .await;
+        Ok(feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("reports.export"));
+        assert!(is_valid_name("beta:new-ui_v2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("with space"));
+        assert!(!is_valid_name(&"a".repeat(FEATURE_NAME_MAX_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_feature_db() {
+        let db = FeatureDB::default();
+        let customer_id = InfraId::from(1i64);
+        let feature = |name: &str, enabled| QmFeature {
+            customer_id,
+            name: name.to_string(),
+            enabled,
+        };
+        db.set(feature("a", true)).await;
+        db.set(feature("b", false)).await;
+        assert!(db.is_enabled(&customer_id, "a").await);
+        assert!(!db.is_enabled(&customer_id, "b").await);
+        assert!(!db.is_enabled(&InfraId::from(2i64), "a").await);
+        db.update(
+            r#"{ "op": "DELETE", "old": { "customer_id": 1, "name": "a", "enabled": true } }"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            db.by_customer_id(&customer_id).await,
+            vec![feature("b", false)]
+        );
+    }
+}
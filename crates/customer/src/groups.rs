@@ -61,6 +61,16 @@ where
     P: std::fmt::Debug,
 {
     fn built_in_groups() -> &'static [&'static str];
+
+    /// Definitions of the built-in groups rendered by `qmPermissionMatrix`,
+    /// usually `groups()` of the generated roles module.
+    fn group_definitions() -> Vec<qm_role::Group<R, P>>
+    where
+        R: Copy,
+        P: Copy,
+    {
+        Vec::new()
+    }
 }
 
 pub trait RelatedBuiltInGroup:
@@ -0,0 +1,150 @@
+//! Invitations of users created with `inviteUser`.
+//!
+//! The Keycloak user is created disabled and enabled once the invitation email
+//! with the link to set a password was sent. Invitations are stored in redis
+//! and expire together with the link.
+
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Duration, Utc};
+use qm_redis::redis::AsyncCommands;
+use qm_redis::Redis;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+use crate::model::QmRequiredUserAction;
+
+const USER_KEY_PREFIX: &str = "qm_invitation:user";
+const TOKEN_KEY_PREFIX: &str = "qm_invitation:token";
+
+pub const INVITATION_ACTIONS: [QmRequiredUserAction; 2] = [
+    QmRequiredUserAction::VerifyEmail,
+    QmRequiredUserAction::UpdatePassword,
+];
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "QmInvitation")]
+#[serde(rename_all = "camelCase")]
+pub struct Invitation {
+    #[graphql(skip)]
+    pub token: String,
+    pub user_id: String,
+    pub email: String,
+    #[graphql(skip)]
+    pub context: Option<String>,
+    pub created_by: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    pub fn new(
+        user_id: impl Into<String>,
+        email: impl Into<String>,
+        context: Option<String>,
+        created_by: Option<&impl ToString>,
+        ttl: u64,
+    ) -> Self {
+        Self {
+            token: Uuid::new_v4().simple().to_string(),
+            user_id: user_id.into(),
+            email: email.into(),
+            context,
+            created_by: created_by.map(ToString::to_string),
+            expires_at: Utc::now() + Duration::seconds(ttl as i64),
+        }
+    }
+
+    /// Replaces the token and restarts the expiry.
+    pub fn renew(&mut self, ttl: u64) {
+        self.token = Uuid::new_v4().simple().to_string();
+        self.expires_at = Utc::now() + Duration::seconds(ttl as i64);
+    }
+
+    /// Url the user is sent to after completing the invitation actions.
+    pub fn redirect_url(&self, base: Option<&str>) -> Option<String> {
+        base.map(|base| {
+            let separator = if base.contains('?') { '&' } else { '?' };
+            format!("{base}{separator}invitation={}", self.token)
+        })
+    }
+
+    fn ttl(&self) -> u64 {
+        (self.expires_at - Utc::now()).num_seconds().max(1) as u64
+    }
+
+    pub async fn save(&self, redis: &Redis) -> anyhow::Result<()> {
+        let mut con = redis.connect().await?;
+        let ttl = self.ttl();
+        let _: () = con
+            .set_ex(user_key(&self.user_id), serde_json::to_string(self)?, ttl)
+            .await?;
+        let _: () = con
+            .set_ex(token_key(&self.token), &self.user_id, ttl)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn by_user_id(redis: &Redis, user_id: &str) -> anyhow::Result<Option<Self>> {
+        let mut con = redis.connect().await?;
+        let value: Option<String> = con.get(user_key(user_id)).await?;
+        Ok(value.map(|v| serde_json::from_str(&v)).transpose()?)
+    }
+
+    pub async fn by_token(redis: &Redis, token: &str) -> anyhow::Result<Option<Self>> {
+        let mut con = redis.connect().await?;
+        let user_id: Option<String> = con.get(token_key(token)).await?;
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+        Ok(Self::by_user_id(redis, &user_id)
+            .await?
+            .filter(|v| v.token == token))
+    }
+
+    pub async fn remove(&self, redis: &Redis) -> anyhow::Result<()> {
+        let mut con = redis.connect().await?;
+        let _: () = con
+            .del(&[user_key(&self.user_id), token_key(&self.token)])
+            .await?;
+        Ok(())
+    }
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("{USER_KEY_PREFIX}:{user_id}")
+}
+
+fn token_key(token: &str) -> String {
+    format!("{TOKEN_KEY_PREFIX}:{token}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invitation() {
+        let mut invitation = Invitation::new("u1", "max@example.com", None, None::<&String>, 60);
+        assert_eq!(invitation.token.len(), 32);
+        assert!(invitation.ttl() <= 60 && invitation.ttl() > 50);
+        assert_eq!(
+            invitation.redirect_url(Some("https://app.example.com/welcome")),
+            Some(format!(
+                "https://app.example.com/welcome?invitation={}",
+                invitation.token
+            ))
+        );
+        assert_eq!(
+            invitation.redirect_url(Some("https://app.example.com/?lang=de")),
+            Some(format!(
+                "https://app.example.com/?lang=de&invitation={}",
+                invitation.token
+            ))
+        );
+        assert_eq!(invitation.redirect_url(None), None);
+        let token = invitation.token.clone();
+        invitation.renew(120);
+        assert_ne!(invitation.token, token);
+        assert!(invitation.ttl() > 60);
+        assert_eq!(user_key("u1"), "qm_invitation:user:u1");
+    }
+}
@@ -1,14 +1,23 @@
+pub mod audit;
 pub mod cache;
 pub mod cleanup;
+pub mod cleanup_status;
 pub mod config;
 pub mod context;
+pub mod custom_groups;
+pub mod features;
 pub mod groups;
+pub mod invitation;
+pub mod lifecycle;
 pub mod marker;
+pub mod merge;
 pub mod model;
 pub mod mutation;
 pub mod query;
 pub mod roles;
 pub mod schema;
+pub mod user_import;
+pub mod webhook;
 pub mod worker;
 
 #[macro_export]
@@ -0,0 +1,139 @@
+//! Cascades the suspension of customers, organizations and institutions to
+//! their Keycloak users.
+//!
+//! Suspended users are marked with the suspended context, reactivating a
+//! context only enables the users disabled by it.
+
+use qm_entity::err;
+use qm_entity::error::EntityResult;
+use qm_entity::ids::InfraContext;
+use qm_keycloak::{Keycloak, UserRepresentation};
+
+use crate::cache::CacheDB;
+use crate::cleanup::{CleanupTask, CleanupTaskType};
+use crate::model::QmEntityStatus;
+
+pub const SUSPENDED_ATTRIBUTE: &str = "qm.suspended_by";
+
+pub fn check_transition(old: QmEntityStatus, new: QmEntityStatus) -> EntityResult<()> {
+    if !old.can_transition(new) {
+        return err!(bad_request(
+            "QmEntityStatus",
+            format!(
+                "the status cannot change from '{}' to '{}'",
+                old.as_ref(),
+                new.as_ref()
+            )
+        ));
+    }
+    Ok(())
+}
+
+/// Task suspending or reactivating the users of `context`, `None` if the
+/// status change does not affect them.
+pub fn users_task(
+    context: &InfraContext,
+    old: QmEntityStatus,
+    new: QmEntityStatus,
+) -> Option<CleanupTask> {
+    if old.is_suspended() == new.is_suspended() {
+        return None;
+    }
+    let context = context.to_string();
+    Some(CleanupTask::new(if new.is_suspended() {
+        CleanupTaskType::SuspendUsers(context)
+    } else {
+        CleanupTaskType::ReactivateUsers(context)
+    }))
+}
+
+/// Applies the suspension to `user` and returns `true` if it changed.
+fn apply(user: &mut UserRepresentation, context: &str, suspend: bool) -> bool {
+    let attributes = user.attributes.get_or_insert_with(Default::default);
+    if suspend {
+        if user.enabled != Some(true) {
+            return false;
+        }
+        attributes.insert(SUSPENDED_ATTRIBUTE.to_string(), vec![context.to_string()]);
+        user.enabled = Some(false);
+        true
+    } else {
+        let suspended_by = attributes
+            .get(SUSPENDED_ATTRIBUTE)
+            .and_then(|v| v.first())
+            .map(String::as_str);
+        if suspended_by != Some(context) {
+            return false;
+        }
+        attributes.remove(SUSPENDED_ATTRIBUTE);
+        user.enabled = Some(true);
+        true
+    }
+}
+
+/// Disables (`suspend`) or enables the users with a role in `context`,
+/// returns the number of updated users.
+pub async fn update_users(
+    keycloak: &Keycloak,
+    cache: &CacheDB,
+    context: &InfraContext,
+    suspend: bool,
+) -> anyhow::Result<usize> {
+    let realm = keycloak.config().realm();
+    let context_str = context.to_string();
+    let mut count = 0;
+    for user_id in cache.user().user_ids_by_context(context).await {
+        let Some(mut user) = keycloak.user_by_id(realm, &user_id).await? else {
+            continue;
+        };
+        if apply(&mut user, &context_str, suspend) {
+            keycloak.update_user(realm, &user_id, &user).await?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_users_task() {
+        use qm_entity::ids::CustomerId;
+        use QmEntityStatus::*;
+        let context = InfraContext::Customer(CustomerId::from(1i64));
+        assert!(users_task(&context, Suspended, Archived).is_none());
+        assert_eq!(
+            users_task(&context, Active, Suspended).map(|t| t.ty),
+            Some(CleanupTaskType::SuspendUsers(context.to_string()))
+        );
+        assert_eq!(
+            users_task(&context, Suspended, Active).map(|t| t.ty),
+            Some(CleanupTaskType::ReactivateUsers(context.to_string()))
+        );
+        assert!(check_transition(Archived, Active).is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut user = UserRepresentation {
+            enabled: Some(true),
+            ..Default::default()
+        };
+        assert!(apply(&mut user, "V1", true));
+        assert_eq!(user.enabled, Some(false));
+        assert!(!apply(&mut user, "V1O2", true));
+        assert!(!apply(&mut user, "V1O2", false));
+        assert_eq!(user.enabled, Some(false));
+        assert!(apply(&mut user, "V1", false));
+        assert_eq!(user.enabled, Some(true));
+        let mut disabled = UserRepresentation {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        assert!(!apply(&mut disabled, "V1", true));
+        assert!(!apply(&mut disabled, "V1", false));
+        assert_eq!(disabled.enabled, Some(false));
+    }
+}
@@ -0,0 +1,307 @@
+//! Merges a source customer into a target customer.
+//!
+//! The organizations and institutions of the source keep their ids and are
+//! moved to the target, organizations with a name already used by the target
+//! are renamed. The resources of the source, including those in its tenant
+//! database, and its custom groups are moved to the target, the users of the
+//! source contexts get the access roles of the new contexts and the source
+//! customer is removed last. The outcome is recorded as [`QmMergeReport`] in
+//! the audit log of the target.
+//!
+//! Every step is idempotent and the completed steps are stored as
+//! [`MergeProgress`] in the [`CUSTOMER_MERGE_COLLECTION`] collection, a
+//! retried merge task continues after the last completed step.
+
+use std::collections::{BTreeMap, HashSet};
+
+use qm_entity::ids::{CustomerId, InfraContext, InstitutionId, OrganizationId, OrganizationUnitId};
+use qm_mongodb::bson::doc;
+use qm_role::AccessLevel;
+use serde::{Deserialize, Serialize};
+
+pub const CUSTOMER_MERGE_COLLECTION: &str = "qm_customer_merges";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QmMergeReport {
+    pub source: String,
+    pub target: String,
+    /// Ids of the moved organizations in the target customer.
+    pub organizations: Vec<String>,
+    /// Ids of the moved institutions in the target customer.
+    pub institutions: Vec<String>,
+    /// New names of organizations by their previous name.
+    pub renamed_organizations: BTreeMap<String, String>,
+    /// Ids of the users which got new access roles.
+    pub users: Vec<String>,
+}
+
+/// Steps of a merge in the order they are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeStep {
+    MoveInfra,
+    MoveDocuments,
+    MoveTenantDocuments,
+    MoveCustomGroups,
+    MigrateRoles,
+    CleanupApiClients,
+    RemoveSource,
+}
+
+/// Organization of the source with a name already used by the target.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedOrganization {
+    pub id: i64,
+    pub name: String,
+    pub new_name: String,
+}
+
+/// Organizations and institutions of the source, determined before anything
+/// is moved.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePlan {
+    pub organizations: Vec<i64>,
+    /// Organization and institution ids.
+    pub institutions: Vec<(i64, i64)>,
+    pub renamed_organizations: Vec<RenamedOrganization>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeProgress {
+    /// Id of the merge task.
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub source: CustomerId,
+    pub target: CustomerId,
+    pub plan: MergePlan,
+    pub completed: Vec<MergeStep>,
+    /// Ids of the users migrated so far.
+    pub users: Vec<String>,
+}
+
+impl MergeProgress {
+    pub fn new(
+        id: impl Into<String>,
+        source: CustomerId,
+        target: CustomerId,
+        plan: MergePlan,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            source,
+            target,
+            plan,
+            completed: vec![],
+            users: vec![],
+        }
+    }
+
+    pub fn is_completed(&self, step: MergeStep) -> bool {
+        self.completed.contains(&step)
+    }
+
+    pub async fn by_id(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<Option<Self>> {
+        db.get()
+            .collection::<Self>(CUSTOMER_MERGE_COLLECTION)
+            .find_one(doc! { "_id": id })
+            .await
+    }
+
+    pub async fn save(&self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(CUSTOMER_MERGE_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// Records `step` as completed.
+    pub async fn complete(
+        &mut self,
+        db: &qm_mongodb::DB,
+        step: MergeStep,
+    ) -> qm_mongodb::error::Result<()> {
+        if !self.is_completed(step) {
+            self.completed.push(step);
+        }
+        self.save(db).await
+    }
+
+    /// Previous and new access roles of the source contexts.
+    pub fn roles(&self) -> Vec<(String, String)> {
+        let (source, target) = (self.source.unzip(), self.target.unzip());
+        fn access(level: AccessLevel, id: &impl std::fmt::Display) -> String {
+            qm_role::Access::new(level)
+                .with_fmt_id(Some(id))
+                .to_string()
+        }
+        let mut roles = vec![(
+            access(AccessLevel::Customer, &self.source),
+            access(AccessLevel::Customer, &self.target),
+        )];
+        for oid in self.plan.organizations.iter().copied() {
+            roles.push((
+                access(
+                    AccessLevel::Organization,
+                    &OrganizationId::from((source, oid)),
+                ),
+                access(
+                    AccessLevel::Organization,
+                    &OrganizationId::from((target, oid)),
+                ),
+            ));
+        }
+        for (oid, iid) in self.plan.institutions.iter().copied() {
+            roles.push((
+                access(
+                    AccessLevel::Institution,
+                    &InstitutionId::from((source, oid, iid)),
+                ),
+                access(
+                    AccessLevel::Institution,
+                    &InstitutionId::from((target, oid, iid)),
+                ),
+            ));
+        }
+        roles
+    }
+
+    pub fn report(&self) -> QmMergeReport {
+        let target = self.target.unzip();
+        QmMergeReport {
+            source: self.source.to_string(),
+            target: self.target.to_string(),
+            organizations: self
+                .plan
+                .organizations
+                .iter()
+                .map(|oid| OrganizationId::from((target, *oid)).to_string())
+                .collect(),
+            institutions: self
+                .plan
+                .institutions
+                .iter()
+                .map(|(oid, iid)| InstitutionId::from((target, *oid, *iid)).to_string())
+                .collect(),
+            renamed_organizations: self
+                .plan
+                .renamed_organizations
+                .iter()
+                .map(|v| (v.name.clone(), v.new_name.clone()))
+                .collect(),
+            users: self.users.clone(),
+        }
+    }
+}
+
+/// Context of `context` in the target, `None` if `context` doesn't belong to
+/// the source.
+pub fn merged_context(
+    context: &InfraContext,
+    source: &CustomerId,
+    target: &CustomerId,
+) -> Option<InfraContext> {
+    if !context.has_customer(source) {
+        return None;
+    }
+    let cid = target.unzip();
+    Some(match context {
+        InfraContext::Customer(_) => InfraContext::Customer(*target),
+        InfraContext::Organization(v) => {
+            InfraContext::Organization(OrganizationId::from((cid, v.id())))
+        }
+        InfraContext::Institution(v) => InfraContext::Institution(InstitutionId { cid, ..*v }),
+        InfraContext::OrganizationUnit(v) => {
+            InfraContext::OrganizationUnit(OrganizationUnitId { cid, ..*v })
+        }
+    })
+}
+
+/// First of `name`, `name (2)`, `name (3)`, ... which is not `taken`.
+pub fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{name} ({n})"))
+        .find(|v| !taken.contains(v))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_name() {
+        let taken: HashSet<String> = ["Berlin", "Berlin (2)", "Hamburg"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(unique_name("Munich", &taken), "Munich");
+        assert_eq!(unique_name("Hamburg", &taken), "Hamburg (2)");
+        assert_eq!(unique_name("Berlin", &taken), "Berlin (3)");
+    }
+
+    #[test]
+    fn test_merge_progress() {
+        let (source, target) = (CustomerId::from(1), CustomerId::from(2));
+        let progress = MergeProgress::new(
+            "t1",
+            source,
+            target,
+            MergePlan {
+                organizations: vec![3],
+                institutions: vec![(3, 4)],
+                renamed_organizations: vec![RenamedOrganization {
+                    id: 3,
+                    name: "Berlin".to_string(),
+                    new_name: "Berlin (2)".to_string(),
+                }],
+            },
+        );
+        let roles = progress.roles();
+        assert_eq!(roles.len(), 3);
+        assert_eq!(
+            roles[2],
+            (
+                qm_role::Access::new(AccessLevel::Institution)
+                    .with_fmt_id(Some(&InstitutionId::from((1, 3, 4))))
+                    .to_string(),
+                qm_role::Access::new(AccessLevel::Institution)
+                    .with_fmt_id(Some(&InstitutionId::from((2, 3, 4))))
+                    .to_string(),
+            )
+        );
+        let report = progress.report();
+        assert_eq!(
+            report.organizations,
+            vec![OrganizationId::from((2, 3)).to_string()]
+        );
+        assert_eq!(report.renamed_organizations["Berlin"], "Berlin (2)");
+        assert!(!progress.is_completed(MergeStep::MoveInfra));
+    }
+
+    #[test]
+    fn test_merged_context() {
+        let (source, target) = (CustomerId::from(1), CustomerId::from(2));
+        let institution = InfraContext::Institution(InstitutionId::from((1, 3, 4)));
+        assert_eq!(
+            merged_context(&institution, &source, &target),
+            Some(InfraContext::Institution(InstitutionId::from((2, 3, 4))))
+        );
+        assert_eq!(
+            merged_context(&InfraContext::Customer(source), &source, &target),
+            Some(InfraContext::Customer(target))
+        );
+        assert_eq!(
+            merged_context(&InfraContext::Customer(target), &source, &target),
+            None
+        );
+    }
+}
@@ -6,6 +6,8 @@ use sqlx::FromRow;
 
 use std::sync::Arc;
 
+use super::QmEntityStatus;
+
 use time::PrimitiveDateTime;
 
 #[derive(Debug, InputObject)]
@@ -27,8 +29,12 @@ pub struct CustomerData(pub String, pub Option<String>, pub Option<i64>);
 pub struct QmCustomer {
     #[graphql(skip)]
     pub id: InfraId,
+    #[sqlx(try_from = "String")]
     pub name: Arc<str>,
+    #[sqlx(try_from = "String")]
     pub ty: Arc<str>,
+    #[sqlx(try_from = "String")]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: PrimitiveDateTime,
     pub updated_by: Option<Uuid>,
@@ -40,6 +46,8 @@ pub struct CustomerUpdate {
     pub id: InfraId,
     pub name: Arc<str>,
     pub ty: Arc<str>,
+    #[serde(default)]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: String,
     pub updated_by: Option<Uuid>,
@@ -82,3 +90,9 @@ impl<'a> From<&'a QmCustomer> for CustomerId {
         (*val.id.as_ref()).into()
     }
 }
+
+impl qm_entity::IsSuspended for QmCustomer {
+    fn is_suspended(&self) -> bool {
+        self.status.is_suspended()
+    }
+}
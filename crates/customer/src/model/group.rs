@@ -1,7 +1,7 @@
 use qm_entity::ids::InfraContext;
-use qm_role::AccessLevel;
+use qm_role::{AccessLevel, AssignableGroup};
 use sqlx::FromRow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 
 #[derive(Debug, serde::Deserialize)]
@@ -90,7 +90,136 @@ pub struct GroupDetail {
     pub context: Option<InfraContext>,
 }
 
+impl AssignableGroup for GroupDetail {
+    fn group_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or_default()
+    }
+
+    fn assignable_types(&self) -> Vec<&str> {
+        self.allowed_types
+            .iter()
+            .flat_map(|types| types.iter().map(AsRef::as_ref))
+            .collect()
+    }
+}
+
 pub type GroupIdMap = HashMap<Arc<str>, Arc<Group>>;
 pub type GroupMap = HashMap<Arc<str>, HashMap<Arc<str>, Arc<Group>>>;
 pub type GroupDetailsMap = HashMap<Arc<str>, Arc<GroupDetail>>;
 pub type GroupRoleMap = HashMap<Arc<str>, HashSet<Arc<str>>>;
+
+/// Role of a built-in group, e.g. `user:view`.
+#[derive(Debug, Clone, PartialEq, Eq, async_graphql::SimpleObject)]
+pub struct QmPermissionRole {
+    pub name: String,
+    pub resource: String,
+    pub permission: Option<String>,
+}
+
+impl From<String> for QmPermissionRole {
+    fn from(name: String) -> Self {
+        let (resource, permission) = match name.split_once(':') {
+            Some((resource, permission)) => (resource.to_string(), Some(permission.to_string())),
+            None => (name.clone(), None),
+        };
+        Self {
+            name,
+            resource,
+            permission,
+        }
+    }
+}
+
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct QmPermissionGroup {
+    pub name: String,
+    pub path: String,
+    pub allowed_access_levels: Vec<AccessLevel>,
+    /// Context types the group can be assigned in, empty if unrestricted.
+    pub allowed_types: Vec<String>,
+    pub roles: Vec<QmPermissionRole>,
+}
+
+impl<R, P> From<&qm_role::Group<R, P>> for QmPermissionGroup
+where
+    R: AsRef<str> + std::fmt::Debug + Copy,
+    P: AsRef<str> + std::fmt::Debug + Copy,
+{
+    fn from(group: &qm_role::Group<R, P>) -> Self {
+        Self {
+            name: group.name.clone(),
+            path: group.path.clone(),
+            allowed_access_levels: group.allowed_access_levels().to_vec(),
+            allowed_types: group.allowed_types().to_vec(),
+            roles: group.resources().into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Built-in groups with their roles, as generated from the roles markdown.
+#[derive(Debug, Clone, async_graphql::SimpleObject)]
+pub struct QmPermissionMatrix {
+    pub groups: Vec<QmPermissionGroup>,
+    /// Resources granted by any of the groups.
+    pub resources: Vec<String>,
+}
+
+impl<R, P> FromIterator<qm_role::Group<R, P>> for QmPermissionMatrix
+where
+    R: AsRef<str> + std::fmt::Debug + Copy,
+    P: AsRef<str> + std::fmt::Debug + Copy,
+{
+    fn from_iter<T: IntoIterator<Item = qm_role::Group<R, P>>>(iter: T) -> Self {
+        let groups: Vec<QmPermissionGroup> = iter.into_iter().map(|g| (&g).into()).collect();
+        let resources: BTreeSet<String> = groups
+            .iter()
+            .flat_map(|g| g.roles.iter().map(|r| r.resource.clone()))
+            .collect();
+        Self {
+            groups,
+            resources: resources.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_matrix() {
+        let group = qm_role::Group::new(
+            "Reader".to_string(),
+            "/app/reader".to_string(),
+            vec![AccessLevel::Customer],
+            vec!["eco".to_string()],
+            vec![
+                qm_role::Role {
+                    ty: "user",
+                    permission: Some("view"),
+                },
+                qm_role::Role {
+                    ty: "administration",
+                    permission: None,
+                },
+            ],
+        );
+        let matrix: QmPermissionMatrix = Some(group).into_iter().collect();
+        assert_eq!(matrix.resources, vec!["administration", "user"]);
+        assert_eq!(
+            matrix.groups[0].roles,
+            vec![
+                QmPermissionRole {
+                    name: "user:view".to_string(),
+                    resource: "user".to_string(),
+                    permission: Some("view".to_string()),
+                },
+                QmPermissionRole {
+                    name: "administration".to_string(),
+                    resource: "administration".to_string(),
+                    permission: None,
+                },
+            ]
+        );
+    }
+}
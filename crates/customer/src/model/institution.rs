@@ -8,6 +8,8 @@ use sqlx::FromRow;
 
 use std::sync::Arc;
 
+use super::QmEntityStatus;
+
 pub struct InstitutionData(
     pub OrganizationId,
     pub String,
@@ -32,8 +34,12 @@ pub struct QmInstitution {
     pub customer_id: InfraId,
     #[graphql(skip)]
     pub organization_id: InfraId,
+    #[sqlx(try_from = "String")]
     pub name: Arc<str>,
+    #[sqlx(try_from = "String")]
     pub ty: Arc<str>,
+    #[sqlx(try_from = "String")]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: PrimitiveDateTime,
     pub updated_by: Option<Uuid>,
@@ -47,6 +53,8 @@ pub struct InstitutionUpdate {
     pub organization_id: InfraId,
     pub name: Arc<str>,
     pub ty: Arc<str>,
+    #[serde(default)]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: String,
     pub updated_by: Option<Uuid>,
@@ -117,3 +125,9 @@ impl<'a> From<&'a QmInstitution> for CustomerId {
         cid.into()
     }
 }
+
+impl qm_entity::IsSuspended for QmInstitution {
+    fn is_suspended(&self) -> bool {
+        self.status.is_suspended()
+    }
+}
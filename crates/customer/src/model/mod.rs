@@ -12,5 +12,9 @@ mod realm;
 pub use realm::*;
 mod role;
 pub use role::*;
+mod sort;
+pub use sort::*;
+mod status;
+pub use status::*;
 mod user;
 pub use user::*;
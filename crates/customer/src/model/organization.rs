@@ -7,6 +7,8 @@ use sqlx::FromRow;
 
 use std::sync::Arc;
 
+use super::QmEntityStatus;
+
 pub struct OrganizationData(pub InfraId, pub String, pub Option<String>, pub Option<i64>);
 
 #[derive(Debug, InputObject)]
@@ -28,8 +30,12 @@ pub struct QmOrganization {
     pub id: InfraId,
     #[graphql(skip)]
     pub customer_id: InfraId,
+    #[sqlx(try_from = "String")]
     pub name: Arc<str>,
+    #[sqlx(try_from = "String")]
     pub ty: Arc<str>,
+    #[sqlx(try_from = "String")]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: PrimitiveDateTime,
     pub updated_by: Option<Uuid>,
@@ -42,6 +48,8 @@ pub struct OrganizationUpdate {
     pub customer_id: InfraId,
     pub name: Arc<str>,
     pub ty: Arc<str>,
+    #[serde(default)]
+    pub status: QmEntityStatus,
     pub created_by: Uuid,
     pub created_at: String,
     pub updated_by: Option<Uuid>,
@@ -96,3 +104,9 @@ impl<'a> From<&'a QmOrganization> for CustomerId {
         cid.into()
     }
 }
+
+impl qm_entity::IsSuspended for QmOrganization {
+    fn is_suspended(&self) -> bool {
+        self.status.is_suspended()
+    }
+}
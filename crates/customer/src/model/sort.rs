@@ -0,0 +1,31 @@
+use async_graphql::{Enum, InputObject};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ListSortField {
+    /// Name of customers, organizations and institutions, username of users.
+    #[default]
+    Name,
+    /// Creation time, not supported for users.
+    CreatedAt,
+    /// Email of users, other entities are sorted by name.
+    Email,
+    /// First name of users, other entities are sorted by name.
+    Firstname,
+    /// Last name of users, other entities are sorted by name.
+    Lastname,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, InputObject)]
+pub struct ListSort {
+    #[graphql(default)]
+    pub field: ListSortField,
+    #[graphql(default)]
+    pub direction: SortDirection,
+}
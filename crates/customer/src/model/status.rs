@@ -0,0 +1,67 @@
+use async_graphql::Enum;
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+
+/// Lifecycle state of customers, organizations and institutions.
+///
+/// `active` and `suspended` can be switched in both directions, both may be
+/// archived and `archived` is final.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Enum, Serialize, Deserialize, AsRefStr, EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum QmEntityStatus {
+    #[default]
+    Active,
+    Suspended,
+    Archived,
+}
+
+impl QmEntityStatus {
+    pub fn can_transition(self, to: Self) -> bool {
+        matches!(
+            (self, to),
+            (Self::Active, Self::Suspended)
+                | (Self::Suspended, Self::Active)
+                | (Self::Active | Self::Suspended, Self::Archived)
+        )
+    }
+
+    /// Users of suspended and archived entities are disabled.
+    pub fn is_suspended(self) -> bool {
+        self != Self::Active
+    }
+}
+
+impl TryFrom<String> for QmEntityStatus {
+    type Error = strum::ParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        use QmEntityStatus::*;
+        assert!(Active.can_transition(Suspended));
+        assert!(Suspended.can_transition(Active));
+        assert!(Active.can_transition(Archived));
+        assert!(Suspended.can_transition(Archived));
+        assert!(!Active.can_transition(Active));
+        assert!(!Archived.can_transition(Active));
+        assert!(!Archived.can_transition(Suspended));
+        assert!(!Active.is_suspended());
+        assert!(Archived.is_suspended());
+        assert_eq!(Suspended.as_ref(), "suspended");
+        assert_eq!(
+            QmEntityStatus::try_from("archived".to_string()).unwrap(),
+            Archived
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use async_graphql::{Enum, InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
 use qm_entity::ids::{InfraContext, InstitutionId, PartialEqual};
+use qm_keycloak::brute_force::BruteForceStatus;
+use qm_keycloak::UserSessionRepresentation;
 use sqlx::types::Uuid;
 use sqlx::FromRow;
 use std::collections::{HashMap, HashSet};
@@ -132,6 +135,38 @@ pub struct QmCreateUserInput {
     pub required_actions: Option<Vec<QmRequiredUserAction>>,
 }
 
+#[derive(Default, serde::Deserialize, serde::Serialize, Debug, Clone, InputObject)]
+#[serde(rename_all = "camelCase")]
+pub struct QmInviteUserInput {
+    pub username: String,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+    pub phone: Option<String>,
+    pub salutation: Option<String>,
+    pub room_number: Option<String>,
+    pub job_title: Option<String>,
+}
+
+impl From<QmInviteUserInput> for QmCreateUserInput {
+    /// Disabled user with a random temporary password.
+    fn from(value: QmInviteUserInput) -> Self {
+        Self {
+            username: value.username,
+            firstname: value.firstname,
+            lastname: value.lastname,
+            password: sqlx::types::Uuid::new_v4().to_string(),
+            email: value.email,
+            phone: value.phone,
+            salutation: value.salutation,
+            room_number: value.room_number,
+            job_title: value.job_title,
+            enabled: Some(false),
+            required_actions: Some(vec![QmRequiredUserAction::UpdatePassword]),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct CreateUserPayload {
     pub user: QmCreateUserInput,
@@ -140,7 +175,7 @@ pub struct CreateUserPayload {
     pub context: Option<InfraContext>,
 }
 
-#[derive(Debug, Clone, SimpleObject)]
+#[derive(Debug, Clone, SimpleObject, serde::Serialize)]
 pub struct QmUser {
     pub id: Arc<str>,
     pub username: Arc<str>,
@@ -150,6 +185,56 @@ pub struct QmUser {
     pub enabled: bool,
 }
 
+/// Active Keycloak session of a user.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmUserSession {
+    pub id: Option<String>,
+    pub ip_address: Option<String>,
+    pub start: Option<DateTime<Utc>>,
+    pub last_access: Option<DateTime<Utc>>,
+    pub remember_me: bool,
+    /// Client ids of the clients the session is used by.
+    pub clients: Vec<String>,
+}
+
+impl From<UserSessionRepresentation> for QmUserSession {
+    fn from(v: UserSessionRepresentation) -> Self {
+        let mut clients: Vec<String> = v.clients.unwrap_or_default().into_values().collect();
+        clients.sort();
+        Self {
+            id: v.id,
+            ip_address: v.ip_address,
+            start: v.start.and_then(DateTime::from_timestamp_millis),
+            last_access: v.last_access.and_then(DateTime::from_timestamp_millis),
+            remember_me: v.remember_me.unwrap_or_default(),
+            clients,
+        }
+    }
+}
+
+/// Brute force lockout state of a user.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmUserLockStatus {
+    /// The user is locked out after too many failed logins.
+    pub locked: bool,
+    pub failed_logins: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_failure_ip_address: Option<String>,
+}
+
+impl From<BruteForceStatus> for QmUserLockStatus {
+    fn from(v: BruteForceStatus) -> Self {
+        Self {
+            locked: v.disabled,
+            failed_logins: v.num_failures,
+            last_failure: (v.last_failure > 0)
+                .then(|| DateTime::from_timestamp_millis(v.last_failure))
+                .flatten(),
+            last_failure_ip_address: v.last_ip_failure.filter(|ip| ip != "n/a"),
+        }
+    }
+}
+
 pub type UserMap = HashMap<Arc<str>, Arc<QmUser>>;
 pub type UserUidMap = HashMap<Uuid, Arc<QmUser>>;
 pub type UserGroupMap = HashMap<Arc<str>, HashSet<Arc<str>>>;
@@ -201,6 +286,7 @@ impl PartialEqual<'_, InfraContext> for QmUserDetails {
                 InfraContext::Customer(v) => context.has_customer(v),
                 InfraContext::Organization(v) => context.has_organization(v),
                 InfraContext::Institution(v) => context.has_institution(v),
+                InfraContext::OrganizationUnit(v) => context.has_organization_unit(v),
             }
         } else {
             false
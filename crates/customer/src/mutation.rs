@@ -1,7 +1,9 @@
+use crate::merge::{MergePlan, RenamedOrganization};
 use crate::model::*;
 use qm_entity::ids::InfraId;
 use sqlx::types::Uuid;
 use sqlx::PgPool;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 pub const DEFAULT_TYPE: &str = "none";
@@ -64,6 +66,7 @@ RETURNING
             id: rec.id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -94,6 +97,7 @@ RETURNING
             id: rec.id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -118,6 +122,7 @@ RETURNING
     v.id as id,
     v.name as name,
     v.ty as ty,
+    v.status as status,
     v.created_by as created_by,
     v.created_at as created_at,
     v.updated_by as updated_by,
@@ -134,6 +139,47 @@ RETURNING
         id: rec.id.into(),
         name: Arc::from(rec.name),
         ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
+        created_by: rec.created_by,
+        created_at: rec.created_at,
+        updated_by: rec.updated_by,
+        updated_at: rec.updated_at,
+    })
+}
+
+pub async fn update_customer_status(
+    pool: &PgPool,
+    id: InfraId,
+    status: QmEntityStatus,
+    updated_by: &Uuid,
+) -> anyhow::Result<QmCustomer> {
+    let rec = sqlx::query!(
+        r#"
+UPDATE customers AS v
+SET status = $2, updated_by = $3, updated_at = NOW()
+WHERE v.id = $1
+RETURNING
+    v.id as id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        id.as_ref(),
+        status.as_ref(),
+        updated_by
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(QmCustomer {
+        id: rec.id.into(),
+        name: Arc::from(rec.name),
+        ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
         created_by: rec.created_by,
         created_at: rec.created_at,
         updated_by: rec.updated_by,
@@ -162,6 +208,176 @@ pub async fn remove_customers(pool: &PgPool, ids: &[i64]) -> anyhow::Result<u64>
     Ok(result)
 }
 
+/// Organizations and institutions of `source_id` to merge into `target_id`,
+/// organizations with a name used by the target get a new name.
+pub async fn plan_merge(
+    pool: &PgPool,
+    source_id: InfraId,
+    target_id: InfraId,
+) -> anyhow::Result<MergePlan> {
+    let names: Vec<(i64, i64, String)> = sqlx::query!(
+        "SELECT id, customer_id, name FROM organizations WHERE customer_id = $1 OR customer_id = $2 ORDER BY id",
+        source_id.as_ref(),
+        target_id.as_ref()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|rec| (rec.id, rec.customer_id, rec.name))
+    .collect();
+    let target_names: HashSet<String> = names
+        .iter()
+        .filter(|(_, customer_id, _)| customer_id == target_id.as_ref())
+        .map(|(_, _, name)| name.clone())
+        .collect();
+    let mut taken: HashSet<String> = names.iter().map(|(_, _, name)| name.clone()).collect();
+    let mut plan = MergePlan::default();
+    for (id, customer_id, name) in names.into_iter() {
+        if customer_id != *source_id.as_ref() {
+            continue;
+        }
+        plan.organizations.push(id);
+        if target_names.contains(&name) {
+            let new_name = crate::merge::unique_name(&name, &taken);
+            taken.insert(new_name.clone());
+            plan.renamed_organizations
+                .push(RenamedOrganization { id, name, new_name });
+        }
+    }
+    plan.institutions = sqlx::query!(
+        "SELECT organization_id, id FROM institutions WHERE customer_id = $1 ORDER BY id",
+        source_id.as_ref()
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|rec| (rec.organization_id, rec.id))
+    .collect();
+    Ok(plan)
+}
+
+/// Organizations and institutions moved by [`merge_customers`].
+pub struct MergedCustomers {
+    pub organizations: Vec<QmOrganization>,
+    pub institutions: Vec<QmInstitution>,
+}
+
+/// Renames the organizations of `plan` and moves the organizations,
+/// institutions and organization units of `source_id` to `target_id`. The
+/// source customer is kept, moved rows are not returned again if the merge
+/// is repeated.
+pub async fn merge_customers(
+    pool: &PgPool,
+    source_id: InfraId,
+    target_id: InfraId,
+    plan: &MergePlan,
+) -> anyhow::Result<MergedCustomers> {
+    let mut tx = pool.begin().await?;
+    for renamed in plan.renamed_organizations.iter() {
+        sqlx::query!(
+            "UPDATE organizations SET name = $3 WHERE id = $1 AND customer_id = $2",
+            renamed.id,
+            source_id.as_ref(),
+            &renamed.new_name
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    let organizations = sqlx::query!(
+        r#"
+UPDATE organizations AS v
+SET customer_id = $2, updated_at = NOW()
+WHERE v.customer_id = $1
+RETURNING
+    v.id as id,
+    v.customer_id as customer_id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        source_id.as_ref(),
+        target_id.as_ref()
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|rec| {
+        Ok(QmOrganization {
+            id: rec.id.into(),
+            customer_id: rec.customer_id.into(),
+            name: Arc::from(rec.name),
+            ty: Arc::from(rec.ty),
+            status: rec.status.parse()?,
+            created_by: rec.created_by,
+            created_at: rec.created_at,
+            updated_by: rec.updated_by,
+            updated_at: rec.updated_at,
+        })
+    })
+    .collect::<anyhow::Result<_>>()?;
+    let institutions = sqlx::query!(
+        r#"
+UPDATE institutions AS v
+SET customer_id = $2, updated_at = NOW()
+WHERE v.customer_id = $1
+RETURNING
+    v.id as id,
+    v.customer_id as customer_id,
+    v.organization_id as organization_id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        source_id.as_ref(),
+        target_id.as_ref()
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .map(|rec| {
+        Ok(QmInstitution {
+            id: rec.id.into(),
+            customer_id: rec.customer_id.into(),
+            organization_id: rec.organization_id.into(),
+            name: Arc::from(rec.name),
+            ty: Arc::from(rec.ty),
+            status: rec.status.parse()?,
+            created_by: rec.created_by,
+            created_at: rec.created_at,
+            updated_by: rec.updated_by,
+            updated_at: rec.updated_at,
+        })
+    })
+    .collect::<anyhow::Result<_>>()?;
+    sqlx::query!(
+        "UPDATE organization_units SET customer_id = $2 WHERE customer_id = $1",
+        source_id.as_ref(),
+        target_id.as_ref()
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "UPDATE organization_unit_members SET customer_id = $2 WHERE customer_id = $1",
+        source_id.as_ref(),
+        target_id.as_ref()
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(MergedCustomers {
+        organizations,
+        institutions,
+    })
+}
+
 pub async fn create_organization(
     pool: &PgPool,
     id: Option<i64>,
@@ -201,6 +417,7 @@ pub async fn create_organization(
             customer_id: rec.customer_id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -234,6 +451,7 @@ pub async fn create_organization(
             customer_id: rec.customer_id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -258,6 +476,7 @@ RETURNING
     v.customer_id as customer_id,
     v.name as name,
     v.ty as ty,
+    v.status as status,
     v.created_by as created_by,
     v.created_at as created_at,
     v.updated_by as updated_by,
@@ -275,6 +494,49 @@ RETURNING
         customer_id: rec.customer_id.into(),
         name: Arc::from(rec.name),
         ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
+        created_by: rec.created_by,
+        created_at: rec.created_at,
+        updated_by: rec.updated_by,
+        updated_at: rec.updated_at,
+    })
+}
+
+pub async fn update_organization_status(
+    pool: &PgPool,
+    id: InfraId,
+    status: QmEntityStatus,
+    updated_by: &Uuid,
+) -> anyhow::Result<QmOrganization> {
+    let rec = sqlx::query!(
+        r#"
+UPDATE organizations AS v
+SET status = $2, updated_by = $3, updated_at = NOW()
+WHERE v.id = $1
+RETURNING
+    v.id as id,
+    v.customer_id as customer_id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        id.as_ref(),
+        status.as_ref(),
+        updated_by,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(QmOrganization {
+        id: rec.id.into(),
+        customer_id: rec.customer_id.into(),
+        name: Arc::from(rec.name),
+        ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
         created_by: rec.created_by,
         created_at: rec.created_at,
         updated_by: rec.updated_by,
@@ -346,6 +608,7 @@ RETURNING
             organization_id: rec.organization_id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -382,6 +645,7 @@ RETURNING
             organization_id: rec.organization_id.into(),
             name: Arc::from(rec.name),
             ty: Arc::from(rec.ty),
+            status: QmEntityStatus::Active,
             created_by: rec.created_by,
             created_at: rec.created_at,
             updated_by: rec.updated_by,
@@ -408,6 +672,7 @@ RETURNING
     v.organization_id as organization_id,
     v.name as name,
     v.ty as ty,
+    v.status as status,
     v.created_by as created_by,
     v.created_at as created_at,
     v.updated_by as updated_by,
@@ -426,6 +691,121 @@ RETURNING
         organization_id: rec.organization_id.into(),
         name: Arc::from(rec.name),
         ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
+        created_by: rec.created_by,
+        created_at: rec.created_at,
+        updated_by: rec.updated_by,
+        updated_at: rec.updated_at,
+    })
+}
+
+pub async fn update_institution_status(
+    pool: &PgPool,
+    id: InfraId,
+    status: QmEntityStatus,
+    updated_by: &Uuid,
+) -> anyhow::Result<QmInstitution> {
+    let rec = sqlx::query!(
+        r#"
+UPDATE institutions AS v
+SET status = $2, updated_by = $3, updated_at = NOW()
+WHERE v.id = $1
+RETURNING
+    v.id as id,
+    v.customer_id as customer_id,
+    v.organization_id as organization_id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        id.as_ref(),
+        status.as_ref(),
+        updated_by,
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(QmInstitution {
+        id: rec.id.into(),
+        customer_id: rec.customer_id.into(),
+        organization_id: rec.organization_id.into(),
+        name: Arc::from(rec.name),
+        ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
+        created_by: rec.created_by,
+        created_at: rec.created_at,
+        updated_by: rec.updated_by,
+        updated_at: rec.updated_at,
+    })
+}
+
+/// Moves the institution to `organization_id` of the same customer, the
+/// memberships in organization units of the previous organization are
+/// removed.
+pub async fn move_institution(
+    pool: &PgPool,
+    id: InfraId,
+    organization_id: InfraId,
+    updated_by: &Uuid,
+) -> anyhow::Result<QmInstitution> {
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"
+DELETE FROM organization_unit_members AS m
+USING organization_units AS u
+WHERE m.organization_unit_id = u.id
+    AND m.institution_id = $1
+    AND u.organization_id IS NOT NULL"#,
+        id.as_ref()
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+UPDATE organization_unit_members
+SET organization_id = $2
+WHERE institution_id = $1"#,
+        id.as_ref(),
+        organization_id.as_ref()
+    )
+    .execute(&mut *tx)
+    .await?;
+    let rec = sqlx::query!(
+        r#"
+UPDATE institutions AS v
+SET organization_id = $2, updated_by = $3, updated_at = NOW()
+WHERE v.id = $1
+RETURNING
+    v.id as id,
+    v.customer_id as customer_id,
+    v.organization_id as organization_id,
+    v.name as name,
+    v.ty as ty,
+    v.status as status,
+    v.created_by as created_by,
+    v.created_at as created_at,
+    v.updated_by as updated_by,
+    v.updated_at as updated_at
+"#,
+        id.as_ref(),
+        organization_id.as_ref(),
+        updated_by
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(QmInstitution {
+        id: rec.id.into(),
+        customer_id: rec.customer_id.into(),
+        organization_id: rec.organization_id.into(),
+        name: Arc::from(rec.name),
+        ty: Arc::from(rec.ty),
+        status: rec.status.parse()?,
         created_by: rec.created_by,
         created_at: rec.created_at,
         updated_by: rec.updated_by,
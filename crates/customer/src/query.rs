@@ -23,7 +23,68 @@ WHERE re.name = $1 AND u.username != $2;"#,
         realm,
         realm_admin_username
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
+    .await?)
+}
+
+const USER_COLUMNS: &str = r#"
+SELECT
+    u.id AS id,
+    u.first_name AS firstname,
+    u.last_name AS lastname,
+    u.username AS username,
+    u.email AS email,
+    u.enabled AS enabled
+FROM realm re
+    JOIN user_entity u on re.id = u.realm_id"#;
+
+/// Field used to look up a single user with [`fetch_user`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserField {
+    Id,
+    Username,
+    Email,
+}
+
+/// Fetches a user other than the realm admin.
+pub async fn fetch_user(
+    db: &DB,
+    realm: &str,
+    realm_admin_username: &str,
+    field: UserField,
+    value: &str,
+) -> anyhow::Result<Option<KcUserQuery>> {
+    let column = match field {
+        UserField::Id => "u.id",
+        UserField::Username => "u.username",
+        UserField::Email => "u.email",
+    };
+    Ok(sqlx::query_as(&format!(
+        "{USER_COLUMNS}\nWHERE re.name = $1 AND u.username != $2 AND {column} = $3;"
+    ))
+    .bind(realm)
+    .bind(realm_admin_username)
+    .bind(value)
+    .fetch_optional(db.pool())
+    .await?)
+}
+
+/// Fetches a page of users ordered by id, used by the lazy cache prefetch.
+pub async fn fetch_users_page(
+    db: &DB,
+    realm: &str,
+    realm_admin_username: &str,
+    limit: i64,
+    offset: i64,
+) -> anyhow::Result<Vec<KcUserQuery>> {
+    Ok(sqlx::query_as(&format!(
+        "{USER_COLUMNS}\nWHERE re.name = $1 AND u.username != $2 ORDER BY u.id LIMIT $3 OFFSET $4;"
+    ))
+    .bind(realm)
+    .bind(realm_admin_username)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -40,7 +101,7 @@ FROM realm re
 WHERE re.name = $1;"#,
         realm
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -56,7 +117,7 @@ FROM realm re
     WHERE re.name = $1;"#,
         realm
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -90,7 +151,7 @@ FROM realm re
 WHERE re.name = $1"#,
         realm
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -108,7 +169,7 @@ WHERE re.name = $1;
     "#,
         realm
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -164,7 +225,7 @@ WHERE re.name = $1;
     "#,
         realm
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
@@ -183,58 +244,58 @@ pub async fn fetch_realm_info(db: &DB, name: &str) -> anyhow::Result<Option<KcRe
 }
 
 pub async fn fetch_customers(db: &DB) -> anyhow::Result<Vec<QmCustomer>> {
-    Ok(query_as!(
-        QmCustomer,
+    Ok(sqlx::query_as(
         r#"
 SELECT
     id,
     name,
     ty,
+    status,
     created_by,
     created_at,
     updated_by,
     updated_at
-FROM customers;"#
+FROM customers;"#,
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
 pub async fn fetch_organizations(db: &DB) -> anyhow::Result<Vec<QmOrganization>> {
-    Ok(query_as!(
-        QmOrganization,
+    Ok(sqlx::query_as(
         r#"
 SELECT
     id,
     name,
     ty,
+    status,
     customer_id,
     created_by,
     created_at,
     updated_by,
     updated_at
-FROM organizations;"#
+FROM organizations;"#,
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
 
 pub async fn fetch_institutions(db: &DB) -> anyhow::Result<Vec<QmInstitution>> {
-    Ok(query_as!(
-        QmInstitution,
+    Ok(sqlx::query_as(
         r#"
 SELECT
     id,
     name,
     ty,
+    status,
     customer_id,
     organization_id,
     created_by,
     created_at,
     updated_by,
     updated_at
-FROM institutions;"#
+FROM institutions;"#,
     )
-    .fetch_all(db.pool())
+    .fetch_all(db.read())
     .await?)
 }
@@ -51,3 +51,33 @@ pub async fn ensure(
     }
     Ok(roles)
 }
+
+/// Assigns `new_role` to the members of `old_role` and removes `old_role`,
+/// returns the ids of the migrated users.
+pub async fn migrate(
+    keycloak: &Keycloak,
+    old_role: &str,
+    new_role: &str,
+) -> anyhow::Result<Vec<String>> {
+    let realm = keycloak.config().realm();
+    let role = ensure(keycloak, Some(new_role.to_string()).into_iter())
+        .await?
+        .pop()
+        .ok_or(anyhow::anyhow!("unable to ensure role {new_role}"))?;
+    let users = match keycloak.role_members(realm, old_role).await {
+        Ok(users) => users,
+        Err(KeycloakError::HttpFailure { status: 404, .. }) => return Ok(vec![]),
+        Err(err) => Err(err)?,
+    };
+    let mut result = Vec::with_capacity(users.len());
+    for user_id in users.into_iter().filter_map(|user| user.id) {
+        tracing::debug!("move user {user_id} to role {new_role}");
+        keycloak
+            .add_user_role(realm, &user_id, role.clone())
+            .await?;
+        result.push(user_id);
+    }
+    tracing::debug!("remove role from keycloak {old_role}");
+    keycloak.remove_role(realm, old_role).await?;
+    Ok(result)
+}
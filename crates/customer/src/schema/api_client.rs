@@ -0,0 +1,470 @@
+//! Secrets, expiry and usage of the API clients of customers, organizations
+//! and institutions, their Keycloak client id is the id of the context.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_graphql::{Context, FieldResult, Object, ResultExt, SimpleObject};
+use chrono::{DateTime, Utc};
+use qm_entity::err;
+use qm_entity::error::EntityError;
+use qm_entity::ids::{ContextFilter, InfraContext};
+use qm_keycloak::{ClientRepresentation, Keycloak};
+use qm_redis::Redis;
+
+use crate::cache::events::visible;
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::schema::auth::AuthCtx;
+
+pub const EXPIRES_AT_ATTRIBUTE: &str = "qm.expires_at";
+pub const EXPIRED_ATTRIBUTE: &str = "qm.expired";
+const LAST_USED_KEY_PREFIX: &str = "qm_api_client:last_used";
+/// Usage of a client is written at most once per interval and process.
+const TRACK_USAGE_INTERVAL: Duration = Duration::from_secs(60);
+
+lazy_static::lazy_static! {
+    static ref USAGE_THROTTLE: UsageThrottle = UsageThrottle::new(TRACK_USAGE_INTERVAL);
+}
+
+/// Remembers when the usage of a client was written last.
+struct UsageThrottle {
+    interval: Duration,
+    last: Mutex<HashMap<String, Instant>>,
+}
+
+impl UsageThrottle {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::default(),
+        }
+    }
+
+    /// Returns `true` and remembers `now` if the usage of `client_id` wasn't
+    /// written within the interval.
+    fn allow(&self, client_id: &str, now: Instant) -> bool {
+        let mut last = self.last.lock().unwrap();
+        if last
+            .get(client_id)
+            .is_some_and(|at| now.saturating_duration_since(*at) < self.interval)
+        {
+            return false;
+        }
+        if last.len() >= 10_000 {
+            last.retain(|_, at| now.saturating_duration_since(*at) < self.interval);
+        }
+        last.insert(client_id.to_string(), now);
+        true
+    }
+
+    /// Allows the next write, the last one failed.
+    fn forget(&self, client_id: &str) {
+        self.last.lock().unwrap().remove(client_id);
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmApiClient {
+    pub client_id: String,
+    pub enabled: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmApiClientSecret {
+    pub client_id: String,
+    pub secret: String,
+}
+
+fn attribute<'a>(client: &'a ClientRepresentation, name: &str) -> Option<&'a str> {
+    client
+        .attributes
+        .as_ref()
+        .and_then(|a| a.get(name))
+        .map(String::as_str)
+}
+
+fn set_attribute(client: &mut ClientRepresentation, name: &str, value: Option<String>) {
+    let attributes = client.attributes.get_or_insert_with(HashMap::new);
+    match value {
+        Some(value) => attributes.insert(name.to_string(), value),
+        None => attributes.remove(name),
+    };
+}
+
+pub fn expires_at(client: &ClientRepresentation) -> Option<DateTime<Utc>> {
+    attribute(client, EXPIRES_AT_ATTRIBUTE)
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|v| v.with_timezone(&Utc))
+}
+
+pub fn is_expired(client: &ClientRepresentation, now: &DateTime<Utc>) -> bool {
+    expires_at(client).map(|v| &v <= now).unwrap_or(false)
+}
+
+fn client_context(client: &ClientRepresentation) -> Option<InfraContext> {
+    client.client_id.as_ref().and_then(|v| v.parse().ok())
+}
+
+fn last_used_key(client_id: &str) -> String {
+    format!("{LAST_USED_KEY_PREFIX}:{client_id}")
+}
+
+/// Records that a token of `client_id` was used, to be called by the token
+/// introspection of the auth implementation. The usage is written at most
+/// once a minute per client and process.
+pub async fn track_usage(redis: &Redis, client_id: &str) -> anyhow::Result<()> {
+    if !USAGE_THROTTLE.allow(client_id, Instant::now()) {
+        return Ok(());
+    }
+    let result = write_usage(redis, client_id).await;
+    if result.is_err() {
+        USAGE_THROTTLE.forget(client_id);
+    }
+    result
+}
+
+async fn write_usage(redis: &Redis, client_id: &str) -> anyhow::Result<()> {
+    let mut con = redis.connect().await?;
+    let _: () = qm_redis::redis::cmd("SET")
+        .arg(last_used_key(client_id))
+        .arg(Utc::now().to_rfc3339())
+        .query_async(&mut con)
+        .await?;
+    Ok(())
+}
+
+async fn last_used(
+    redis: &Redis,
+    client_ids: &[&str],
+) -> anyhow::Result<Vec<Option<DateTime<Utc>>>> {
+    if client_ids.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut con = redis.connect().await?;
+    let keys: Vec<String> = client_ids.iter().map(|id| last_used_key(id)).collect();
+    let values: Vec<Option<String>> = qm_redis::redis::cmd("MGET")
+        .arg(&keys)
+        .query_async(&mut con)
+        .await?;
+    Ok(values
+        .into_iter()
+        .map(|v| {
+            v.and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                .map(|v| v.with_timezone(&Utc))
+        })
+        .collect())
+}
+
+/// Disables the API clients with a passed expiry date and returns their
+/// client ids, used by the cleanup worker.
+pub async fn disable_expired(keycloak: &Keycloak) -> anyhow::Result<Vec<String>> {
+    let realm = keycloak.config().realm();
+    let now = Utc::now();
+    let mut result = vec![];
+    for mut client in keycloak.clients(realm).await? {
+        if client.enabled != Some(true) || !is_expired(&client, &now) {
+            continue;
+        }
+        let (Some(id), Some(client_id)) = (client.id.clone(), client.client_id.clone()) else {
+            continue;
+        };
+        client.enabled = Some(false);
+        set_attribute(&mut client, EXPIRED_ATTRIBUTE, Some("true".to_string()));
+        keycloak.update_client(realm, &id, client).await?;
+        tracing::info!("disabled expired api client '{client_id}'");
+        result.push(client_id);
+    }
+    Ok(result)
+}
+
+pub(crate) fn role<Resource, Permission>(
+    context: &InfraContext,
+    permission: Permission,
+) -> qm_role::Role<Resource, Permission>
+where
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let resource = match context {
+        InfraContext::Customer(_) => Resource::customer(),
+        InfraContext::Organization(_) => Resource::organization(),
+        InfraContext::Institution(_) => Resource::institution(),
+        InfraContext::OrganizationUnit(_) => Resource::organization_unit(),
+    };
+    qm_role::Role::new(resource, Some(permission))
+}
+
+pub struct Ctx<'a, Auth, Store, Resource, Permission>(
+    pub &'a AuthCtx<'a, Auth, Store, Resource, Permission>,
+)
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission;
+
+impl<'a, Auth, Store, Resource, Permission> Ctx<'a, Auth, Store, Resource, Permission>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    pub async fn list(&self, context: Option<InfraContext>) -> FieldResult<Vec<QmApiClient>> {
+        let scope = self.0.enforce_current_context(context).await.extend()?;
+        let keycloak = self.0.store.keycloak();
+        let clients: Vec<ClientRepresentation> = keycloak
+            .clients(keycloak.config().realm())
+            .await?
+            .into_iter()
+            .filter(|client| {
+                client_context(client).is_some_and(|context| {
+                    visible(scope.as_ref(), Some(&context))
+                        && (self.0.is_admin
+                            || self.0.auth.satisfies(&role::<Resource, Permission>(
+                                &context,
+                                Permission::view(),
+                            )))
+                })
+            })
+            .collect();
+        let client_ids: Vec<&str> = clients
+            .iter()
+            .filter_map(|c| c.client_id.as_deref())
+            .collect();
+        let last_used = last_used(self.0.store.redis(), &client_ids).await?;
+        Ok(clients
+            .iter()
+            .zip(last_used)
+            .map(|(client, last_used_at)| QmApiClient {
+                client_id: client.client_id.clone().unwrap_or_default(),
+                enabled: client.enabled.unwrap_or(false),
+                expires_at: expires_at(client),
+                last_used_at,
+            })
+            .collect())
+    }
+
+    /// Returns the client if the session may update it.
+    async fn client(&self, client_id: &str) -> FieldResult<ClientRepresentation> {
+        let keycloak = self.0.store.keycloak();
+        let client = keycloak
+            .get_client_by_id(keycloak.config().realm(), client_id)
+            .await?
+            .filter(|c| c.client_id.as_deref() == Some(client_id))
+            .ok_or(EntityError::not_found_by_field::<QmApiClient>(
+                "clientId", client_id,
+            ))
+            .extend()?;
+        let context = client_context(&client)
+            .ok_or(EntityError::not_found_by_field::<QmApiClient>(
+                "clientId", client_id,
+            ))
+            .extend()?;
+        if !self.0.is_admin
+            && !self.0.auth.satisfies(&role::<Resource, Permission>(
+                &context,
+                Permission::update(),
+            ))
+        {
+            return err!(unauthorized(&self.0.auth)).extend();
+        }
+        self.0.can_mutate(Some(&context)).await.extend()?;
+        Ok(client)
+    }
+
+    pub async fn rotate_secret(&self, client_id: &str) -> FieldResult<QmApiClientSecret> {
+        let client = self.client(client_id).await?;
+        let keycloak = self.0.store.keycloak();
+        let secret = keycloak
+            .regenerate_client_secret(
+                keycloak.config().realm(),
+                client.id.as_deref().unwrap_or_default(),
+            )
+            .await?
+            .ok_or(EntityError::internal())
+            .extend()?;
+        Ok(QmApiClientSecret {
+            client_id: client_id.to_string(),
+            secret,
+        })
+    }
+
+    /// Sets or clears the expiry date, a client disabled because it expired
+    /// is enabled again if the new date is in the future.
+    pub async fn set_expiry(
+        &self,
+        client_id: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> FieldResult<QmApiClient> {
+        let mut client = self.client(client_id).await?;
+        set_attribute(
+            &mut client,
+            EXPIRES_AT_ATTRIBUTE,
+            expires_at.map(|v| v.to_rfc3339()),
+        );
+        if attribute(&client, EXPIRED_ATTRIBUTE).is_some() && !is_expired(&client, &Utc::now()) {
+            set_attribute(&mut client, EXPIRED_ATTRIBUTE, None);
+            client.enabled = Some(true);
+        }
+        let keycloak = self.0.store.keycloak();
+        let enabled = client.enabled.unwrap_or(false);
+        let id = client.id.clone().unwrap_or_default();
+        keycloak
+            .update_client(keycloak.config().realm(), &id, client)
+            .await?;
+        let last_used_at = last_used(self.0.store.redis(), &[client_id])
+            .await?
+            .pop()
+            .flatten();
+        Ok(QmApiClient {
+            client_id: client_id.to_string(),
+            enabled,
+            expires_at,
+            last_used_at,
+        })
+    }
+}
+
+pub struct ApiClientQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for ApiClientQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    ApiClientQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    async fn api_clients(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<ContextFilter>,
+    ) -> FieldResult<Vec<QmApiClient>> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .list(ContextFilter::into_context(context))
+            .await
+    }
+}
+
+pub struct ApiClientMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for ApiClientMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    ApiClientMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Generates a new secret, the previous secret stops working immediately.
+    async fn rotate_api_client_secret(
+        &self,
+        ctx: &Context<'_>,
+        client_id: String,
+    ) -> FieldResult<QmApiClientSecret> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .rotate_secret(&client_id)
+            .await
+    }
+
+    /// Expired clients are disabled by the cleanup worker, see
+    /// [`crate::worker::schedule_api_client_expiry`].
+    async fn set_api_client_expiry(
+        &self,
+        ctx: &Context<'_>,
+        client_id: String,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> FieldResult<QmApiClient> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .set_expiry(&client_id, expires_at)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expiry() {
+        let now = Utc::now();
+        let mut client = ClientRepresentation::default();
+        assert_eq!(expires_at(&client), None);
+        assert!(!is_expired(&client, &now));
+        set_attribute(
+            &mut client,
+            EXPIRES_AT_ATTRIBUTE,
+            Some((now - chrono::Duration::minutes(1)).to_rfc3339()),
+        );
+        assert!(is_expired(&client, &now));
+        set_attribute(
+            &mut client,
+            EXPIRES_AT_ATTRIBUTE,
+            Some((now + chrono::Duration::days(1)).to_rfc3339()),
+        );
+        assert!(!is_expired(&client, &now));
+        set_attribute(&mut client, EXPIRES_AT_ATTRIBUTE, None);
+        assert_eq!(attribute(&client, EXPIRES_AT_ATTRIBUTE), None);
+    }
+
+    #[test]
+    fn test_usage_throttle() {
+        let throttle = UsageThrottle::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert!(throttle.allow("a", now));
+        assert!(!throttle.allow("a", now + Duration::from_secs(59)));
+        assert!(throttle.allow("b", now + Duration::from_secs(59)));
+        assert!(throttle.allow("a", now + Duration::from_secs(60)));
+        throttle.forget("b");
+        assert!(throttle.allow("b", now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_context() {
+        let customer = InfraContext::Customer(qm_entity::ids::CustomerId::from(1i64));
+        let client = ClientRepresentation {
+            client_id: Some(customer.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(client_context(&client), Some(customer));
+        let client = ClientRepresentation {
+            client_id: Some("spa".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(client_context(&client), None);
+    }
+}
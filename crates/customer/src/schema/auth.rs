@@ -21,6 +21,8 @@ use tokio::sync::RwLock;
 use qm_entity::err;
 use qm_entity::error::EntityError;
 
+use crate::audit::QmAuditEntry;
+use crate::context::Guards;
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
 use crate::context::RelatedResource;
@@ -94,7 +96,28 @@ where
     pub async fn new(graphql_context: &'ctx Context<'_>) -> FieldResult<Self> {
         let auth = Auth::from_graphql_context(graphql_context).await.extend()?;
         let store = graphql_context.data_unchecked::<Store>();
-        Self::try_from((store, auth))
+        let result = Self::try_from((store, auth))?;
+        result.ensure_not_suspended().await?;
+        Ok(result)
+    }
+
+    /// Rejects sessions in the context of a suspended or archived customer,
+    /// organization or institution.
+    async fn ensure_not_suspended(&self) -> FieldResult<()> {
+        if self.is_admin || self.is_support {
+            return Ok(());
+        }
+        let context = self
+            .auth
+            .session_access()
+            .and_then(|access| access.id())
+            .and_then(|id| InfraContext::parse(id).ok());
+        if let Some(context) = context {
+            if self.store.cache_db().is_suspended(&context).await {
+                return err!(suspended(context)).extend();
+            }
+        }
+        Ok(())
     }
 
     pub async fn new_with_role(
@@ -108,7 +131,7 @@ where
             tracing::debug!("new with role {role:?} resolved to admin");
             return Ok(result);
         }
-        if !result.auth.has_role_object(role) {
+        if !result.auth.satisfies(role) {
             return err!(unauthorized(&result.auth)).extend();
         }
         tracing::debug!("new with role {role:?} resolved as non admin");
@@ -127,13 +150,23 @@ where
             return Ok(result);
         }
         for role in roles {
-            if !result.auth.has_role_object(&role) {
+            if !result.auth.satisfies(&role) {
                 return err!(unauthorized(&result.auth)).extend();
             }
         }
         Ok(result)
     }
 
+    /// Records `entry` in the audit log with the current user as actor and
+    /// publishes it to the webhooks.
+    pub async fn audit(&self, entry: QmAuditEntry) {
+        let entry = entry.with_actor(self.auth.user_id());
+        crate::webhook::publish(self.store, &entry).await;
+        entry
+            .record(AsRef::<qm_mongodb::DB>::as_ref(self.store))
+            .await
+    }
+
     async fn with_customer(self, customer_id: CustomerId) -> FieldResult<Self> {
         let cache = self.store.cache_db();
         let _ = cache
@@ -475,6 +508,12 @@ where
                 }
                 err!(unauthorized(&self.auth))
             }
+            InfraContext::OrganizationUnit(v) => {
+                if object_context.has_organization_unit(v) {
+                    return Ok(());
+                }
+                err!(unauthorized(&self.auth))
+            }
         }
     }
 }
@@ -509,7 +548,6 @@ where
     Permission: RelatedPermission,
 {
     async fn check(&self, ctx: &Context<'_>) -> FieldResult<()> {
-        AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(ctx, &self.role).await?;
-        Ok(())
+        Guards::check::<Auth, Store, Resource, Permission>(ctx, &self.role).await
     }
 }
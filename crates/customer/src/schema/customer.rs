@@ -9,25 +9,33 @@ use qm_entity::error::EntityResult;
 use qm_entity::ids::CustomerId;
 use qm_entity::ids::CustomerIds;
 
+use qm_entity::ids::InfraContext;
 use qm_entity::ids::InfraId;
 use qm_entity::model::ListFilter;
 use qm_mongodb::bson::doc;
 use qm_role::AccessLevel;
 use sqlx::types::Uuid;
 
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cleanup::CleanupTask;
 use crate::cleanup::CleanupTaskType;
+use crate::cleanup_status;
+use crate::cleanup_status::QmCleanupTask;
 use crate::context::RelatedStorage;
 use crate::context::{RelatedAuth, RelatedPermission, RelatedResource};
 use crate::groups::RelatedBuiltInGroup;
+use crate::lifecycle;
 use crate::marker::Marker;
 use crate::model::CustomerData;
+use crate::model::ListSort;
 use crate::model::QmCreateCustomerInput;
 use crate::model::QmCustomer;
 use crate::model::QmCustomerList;
+use crate::model::QmEntityStatus;
 use crate::model::QmUpdateCustomerInput;
 use crate::mutation::remove_customers;
 use crate::mutation::update_customer;
+use crate::mutation::update_customer_status;
 use crate::roles;
 use crate::schema::auth::AuthCtx;
 use async_graphql::ComplexObject;
@@ -57,9 +65,15 @@ where
     pub async fn list(
         &self,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmCustomerList> {
-        Ok(self.0.store.cache_db().customer_list(filter, ty).await)
+        Ok(self
+            .0
+            .store
+            .cache_db()
+            .customer_list(filter, sort, ty)
+            .await)
     }
 
     pub async fn by_id(&self, id: CustomerId) -> Option<Arc<QmCustomer>> {
@@ -92,6 +106,8 @@ where
                     let roles =
                         roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
                     self.0.store.cache_db().user().new_roles(roles).await;
+                    qm_entity::tenant::provision(AsRef::<qm_mongodb::DB>::as_ref(self.0.store), id)
+                        .await?;
                     if let Some(producer) = self.0.store.mutation_event_producer() {
                         producer
                             .create_event(
@@ -109,6 +125,14 @@ where
                         .infra()
                         .new_customer(customer.clone())
                         .await;
+                    let context = InfraContext::from(id);
+                    self.0
+                        .audit(
+                            QmAuditEntry::new(AuditEntity::Customer, AuditAction::Create, context)
+                                .with_context(Some(context))
+                                .with_diff(None::<&QmCustomer>, Some(customer.as_ref())),
+                        )
+                        .await;
                     (customer, false)
                 },
             )
@@ -123,6 +147,7 @@ where
 
     pub async fn update(&self, id: CustomerId, name: String) -> EntityResult<Arc<QmCustomer>> {
         let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
         let id: InfraId = id.into();
         let old = self
             .0
@@ -139,22 +164,109 @@ where
             .infra()
             .update_customer(new.clone(), old.as_ref().into())
             .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Customer, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        Ok(new)
+    }
+
+    /// Changes the lifecycle status, the users are suspended or reactivated
+    /// by the cleanup worker.
+    pub async fn set_status(
+        &self,
+        id: CustomerId,
+        status: QmEntityStatus,
+    ) -> EntityResult<Arc<QmCustomer>> {
+        let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
+        let id: InfraId = id.into();
+        let old = self.0.store.cache_db().customer_by_id(&id).await.ok_or(
+            EntityError::not_found_by_id::<QmCustomer>(context.to_string()),
+        )?;
+        lifecycle::check_transition(old.status, status)?;
+        let result =
+            update_customer_status(self.0.store.customer_db().pool(), id, status, user_id).await?;
+        let new = Arc::new(result);
+        self.0
+            .store
+            .cache_db()
+            .infra()
+            .update_customer(new.clone(), old.as_ref().into())
+            .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Customer, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        if let Some(task) = lifecycle::users_task(&context, old.status, status) {
+            cleanup_status::enqueue(self.0.store, &task).await?;
+        }
         Ok(new)
     }
 
+    /// Enqueues the merge of `source_id` into `target_id`, the status can be
+    /// followed with `qmCleanupTasks`.
+    pub async fn merge(
+        &self,
+        source_id: CustomerId,
+        target_id: CustomerId,
+    ) -> EntityResult<QmCleanupTask> {
+        if source_id == target_id {
+            return err!(bad_request(
+                "CustomerId",
+                "the source and target customer must differ"
+            ));
+        }
+        let cache = self.0.store.cache_db();
+        for id in [source_id, target_id] {
+            if cache.customer_by_id(&id.into()).await.is_none() {
+                return err!(not_found_by_id::<QmCustomer>(id.to_string()));
+            }
+        }
+        let task = CleanupTask::new(CleanupTaskType::MergeCustomers(
+            source_id,
+            target_id,
+            self.0.auth.user_id().copied(),
+        ));
+        cleanup_status::enqueue(self.0.store, &task).await?;
+        tracing::debug!("emit merge task {}", task.id.to_string());
+        Ok(QmCleanupTask::new(&task))
+    }
+
     pub async fn remove(&self, ids: CustomerIds) -> EntityResult<u64> {
         let v: Vec<i64> = ids.iter().map(CustomerId::unzip).collect();
+        let mut removed = vec![];
+        for id in ids.iter() {
+            if let Some(old) = self.0.store.cache_db().customer_by_id(&(*id).into()).await {
+                removed.push((InfraContext::from(*id), old));
+            }
+        }
         let delete_count = remove_customers(self.0.store.customer_db().pool(), &v).await?;
         if delete_count != 0 {
+            for (context, old) in removed {
+                self.0
+                    .audit(
+                        QmAuditEntry::new(AuditEntity::Customer, AuditAction::Remove, context)
+                            .with_context(Some(context))
+                            .with_diff(Some(old.as_ref()), None::<&QmCustomer>),
+                    )
+                    .await;
+            }
             let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
+            cleanup_status::enqueue(
+                self.0.store,
+                &CleanupTask {
                     id,
                     ty: CleanupTaskType::Customers(ids),
-                })
-                .await?;
+                },
+            )
+            .await?;
             tracing::debug!("emit cleanup task {}", id.to_string());
             return Ok(delete_count);
         }
@@ -186,6 +298,17 @@ where
     Permission: RelatedPermission,
     BuiltInGroup: RelatedBuiltInGroup,
 {
+    /// Resolves `QmCustomer` references of a federated gateway.
+    #[cfg(feature = "federation")]
+    #[graphql(entity)]
+    async fn find_qm_customer_by_id(
+        &self,
+        ctx: &Context<'_>,
+        id: CustomerId,
+    ) -> async_graphql::FieldResult<Option<Arc<QmCustomer>>> {
+        self.qm_customer_by_id(ctx, id).await
+    }
+
     async fn qm_customer_by_id(
         &self,
         ctx: &Context<'_>,
@@ -207,6 +330,7 @@ where
         &self,
         ctx: &Context<'_>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmCustomerList> {
         Ctx(
@@ -216,7 +340,7 @@ where
             )
             .await?,
         )
-        .list(filter, ty)
+        .list(filter, sort, ty)
         .await
         .extend()
     }
@@ -296,4 +420,70 @@ where
         .await
         .extend()
     }
+
+    /// Moves the organizations, institutions and users of `source_id` to
+    /// `target_id` and removes `source_id`, admins only.
+    async fn qm_merge_customers(
+        &self,
+        ctx: &Context<'_>,
+        source_id: CustomerId,
+        target_id: CustomerId,
+    ) -> async_graphql::FieldResult<QmCleanupTask> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        if !auth_ctx.is_admin {
+            return err!(unauthorized(&auth_ctx.auth)).extend();
+        }
+        Ctx(&auth_ctx).merge(source_id, target_id).await.extend()
+    }
+
+    async fn qm_suspend_customer(
+        &self,
+        ctx: &Context<'_>,
+        context: CustomerId,
+    ) -> async_graphql::FieldResult<Arc<QmCustomer>> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::customer(), Permission::update()),
+            )
+            .await?,
+        )
+        .set_status(context, QmEntityStatus::Suspended)
+        .await
+        .extend()
+    }
+
+    async fn qm_reactivate_customer(
+        &self,
+        ctx: &Context<'_>,
+        context: CustomerId,
+    ) -> async_graphql::FieldResult<Arc<QmCustomer>> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::customer(), Permission::update()),
+            )
+            .await?,
+        )
+        .set_status(context, QmEntityStatus::Active)
+        .await
+        .extend()
+    }
+
+    async fn qm_archive_customer(
+        &self,
+        ctx: &Context<'_>,
+        context: CustomerId,
+    ) -> async_graphql::FieldResult<Arc<QmCustomer>> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::customer(), Permission::update()),
+            )
+            .await?,
+        )
+        .set_status(context, QmEntityStatus::Archived)
+        .await
+        .extend()
+    }
 }
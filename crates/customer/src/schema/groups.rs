@@ -3,21 +3,23 @@ use async_graphql::{ErrorExtensions, ResultExt};
 use futures::StreamExt;
 use qm_entity::error::EntityError;
 use qm_entity::exerr;
-use qm_entity::ids::InfraContext;
-use qm_keycloak::realm::ensure_groups_with_roles;
+use qm_entity::ids::{ContextFilter, InfraContext};
+use qm_keycloak::realm::{ensure_groups_with_roles, ensure_roles};
 
 use std::collections::HashSet;
 
 use std::sync::Arc;
 
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cache::CacheDB;
+use crate::context::ResourceGuard;
+use crate::custom_groups::{ungranted_roles, CustomGroup};
 use crate::query::fetch_group_by_id;
-use crate::schema::auth::AuthGuard;
 use sqlx::types::Uuid;
 
 use crate::groups::RelatedBuiltInGroup;
 use crate::marker::Marker;
-use crate::model::{Group, GroupDetail, Role, UserGroup};
+use crate::model::{Group, GroupDetail, QmPermissionMatrix, Role, UserGroup};
 use qm_role::AccessLevel;
 
 use crate::model::{QmCustomer, QmInstitution, QmOrganization /* OrganizationUnit */};
@@ -154,8 +156,9 @@ impl Groups {
     async fn custom(
         &self,
         ctx: &Context<'_>,
-        context: InfraContext,
+        context: ContextFilter,
     ) -> FieldResult<Vec<UserGroup>> {
+        let context = InfraContext::from(context);
         let cache = ctx.data_unchecked::<CacheDB>();
         let parent = format!("custom@{context}");
         let groups = cache.groups_by_parent(&parent).await;
@@ -186,6 +189,59 @@ where
     Resource: RelatedResource,
     Permission: RelatedPermission,
 {
+    fn object_db(&self) -> &qm_mongodb::DB {
+        self.0.store.as_ref()
+    }
+
+    /// Checks the definition of a custom group against the access of the
+    /// current user and the roles of the built-in groups.
+    pub async fn validate(
+        &self,
+        allowed_access_levels: &HashSet<AccessLevel>,
+        roles: &HashSet<qm_role::Role<Resource, Permission>>,
+    ) -> async_graphql::FieldResult<HashSet<String>> {
+        if allowed_access_levels
+            .iter()
+            .any(|lvl| matches!(lvl, &AccessLevel::Admin | AccessLevel::None))
+        {
+            return exerr!(bad_request(
+                "UserGroup",
+                "unable to create custom group with allowed access level ADMIN or NONE"
+            ));
+        }
+        if roles.iter().any(|r| r.ty.is_admin()) {
+            return exerr!(bad_request(
+                "UserGroup",
+                "unable to create custom group with role 'administration'"
+            ));
+        }
+        if !self.0.is_admin {
+            for role in roles.iter() {
+                if !self.0.auth.satisfies(role) {
+                    return exerr!(unauthorized(&self.0.auth));
+                }
+            }
+        }
+        let roles: HashSet<String> = roles.iter().map(ToString::to_string).collect();
+        let granted = self
+            .0
+            .store
+            .cache_db()
+            .built_in_role_names(allowed_access_levels)
+            .await;
+        let ungranted = ungranted_roles(&roles, &granted);
+        if !ungranted.is_empty() {
+            return exerr!(bad_request(
+                "UserGroup",
+                format!(
+                    "the roles '{}' are not granted to the allowed access levels",
+                    ungranted.join(", ")
+                )
+            ));
+        }
+        Ok(roles)
+    }
+
     pub async fn create(
         &self,
         name: String,
@@ -208,6 +264,8 @@ where
         {
             return exerr!(name_conflict::<Group>(name));
         }
+        let name_for_definition = name.clone();
+        let role_names: HashSet<String> = roles.iter().map(ToString::to_string).collect();
         let groups = ensure_groups_with_roles(
             self.0.store.keycloak().config().realm(),
             self.0.store.keycloak(),
@@ -222,11 +280,45 @@ where
         )
         .await?;
         let kc_group = groups.get(&path).ok_or(EntityError::internal())?;
-        let group_query = fetch_group_by_id(
-            self.0.store.keycloak_db(),
-            kc_group.id.as_ref().ok_or(EntityError::internal())?,
-        )
-        .await?;
+        let kc_group_id = kc_group.id.as_deref().ok_or(EntityError::internal())?;
+        let mut kc_group = self
+            .0
+            .store
+            .keycloak()
+            .group_by_id(self.0.store.keycloak().config().realm(), kc_group_id)
+            .await?;
+        kc_group
+            .attributes
+            .get_or_insert_with(Default::default)
+            .insert("context".to_string(), vec![context.to_string()]);
+        self.0
+            .store
+            .keycloak()
+            .update_group(
+                self.0.store.keycloak().config().realm(),
+                kc_group_id,
+                kc_group,
+            )
+            .await?;
+        let definition = CustomGroup::new(
+            kc_group_id,
+            &context,
+            name_for_definition,
+            path.clone(),
+            &allowed_access_levels,
+            &allowed_types,
+            &role_names,
+            self.0.auth.user_id(),
+        );
+        definition.save(self.object_db()).await?;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Group, AuditAction::Create, kc_group_id)
+                    .with_context(Some(context))
+                    .with_diff(None::<&CustomGroup>, Some(&definition)),
+            )
+            .await;
+        let group_query = fetch_group_by_id(self.0.store.keycloak_db(), kc_group_id).await?;
         let parent_name = Arc::from(group_query.parent_name.unwrap());
         let group = Arc::new(Group {
             id: Arc::from(group_query.group_id.unwrap()),
@@ -253,6 +345,107 @@ where
         }))
     }
 
+    /// Replaces the display name, access levels, types and roles of a custom
+    /// group, the path and context stay the same.
+    pub async fn update(
+        &self,
+        id: Arc<str>,
+        group_detail: &GroupDetail,
+        name: Option<String>,
+        allowed_access_levels: HashSet<AccessLevel>,
+        allowed_types: HashSet<String>,
+        roles: HashSet<String>,
+    ) -> async_graphql::FieldResult<Arc<UserGroup>> {
+        let keycloak = self.0.store.keycloak();
+        let realm = keycloak.config().realm();
+        let context = group_detail.context.ok_or(EntityError::internal())?;
+        let mut kc_group = keycloak.group_by_id(realm, &id).await?;
+        let name = name
+            .or_else(|| group_detail.display_name.as_deref().map(str::to_string))
+            .or_else(|| kc_group.name.clone())
+            .unwrap_or_default();
+        let before = CustomGroup::by_id(self.object_db(), &id).await?;
+        let mut definition = before.clone().unwrap_or_else(|| {
+            CustomGroup::new(
+                id.as_ref(),
+                &context,
+                name.clone(),
+                kc_group.path.clone().unwrap_or_default(),
+                &HashSet::new(),
+                &HashSet::new(),
+                &HashSet::new(),
+                None::<&String>,
+            )
+        });
+        definition.name = name.clone();
+        definition.set(&allowed_access_levels, &allowed_types, &roles);
+        definition.updated_by = self.0.auth.user_id().map(ToString::to_string);
+        definition.updated_at = chrono::Utc::now();
+
+        let attributes = kc_group.attributes.get_or_insert_with(Default::default);
+        attributes.insert("display_name".to_string(), vec![name.clone()]);
+        attributes.insert(
+            "allowed_access_levels".to_string(),
+            vec![definition.allowed_access_levels.join(",")],
+        );
+        attributes.insert(
+            "allowed_types".to_string(),
+            vec![definition.allowed_types.join(",")],
+        );
+        attributes.insert("context".to_string(), vec![context.to_string()]);
+        keycloak.update_group(realm, &id, kc_group).await?;
+
+        let current = keycloak.realm_role_mappings_by_group_id(realm, &id).await?;
+        let current_names: HashSet<&str> =
+            current.iter().filter_map(|r| r.name.as_deref()).collect();
+        let added = roles
+            .iter()
+            .filter(|r| !current_names.contains(r.as_str()))
+            .cloned()
+            .collect();
+        let added = ensure_roles(realm, keycloak, added).await?;
+        if !added.is_empty() {
+            keycloak
+                .create_realm_role_mappings_by_group_id(realm, &id, added)
+                .await?;
+        }
+        let removed: Vec<_> = current
+            .into_iter()
+            .filter(|r| r.name.as_ref().is_some_and(|name| !roles.contains(name)))
+            .collect();
+        if !removed.is_empty() {
+            keycloak
+                .remove_realm_role_mappings_by_group_id(realm, &id, removed)
+                .await?;
+        }
+
+        definition.save(self.object_db()).await?;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Group, AuditAction::Update, &id)
+                    .with_context(Some(context))
+                    .with_diff(before.as_ref(), Some(&definition)),
+            )
+            .await;
+        let group_detail = Arc::new(GroupDetail {
+            allowed_access_levels: Some(allowed_access_levels.into_iter().collect()),
+            allowed_types: Some(allowed_types.into_iter().map(|s| s.into()).collect()),
+            built_in: false,
+            context: Some(context),
+            display_name: Some(Arc::from(name)),
+        });
+        self.0
+            .store
+            .cache_db()
+            .user()
+            .update_group_detail(id.clone(), group_detail.clone())
+            .await;
+        Ok(Arc::new(UserGroup {
+            group_detail,
+            group_id: id,
+        }))
+    }
+
     pub async fn remove(&self, ids: &[Arc<str>]) -> async_graphql::FieldResult<u64> {
         let mut i = 0;
         for id in ids {
@@ -261,6 +454,15 @@ where
                 .keycloak()
                 .remove_group(self.0.store.keycloak().config().realm(), id)
                 .await?;
+            let before = CustomGroup::by_id(self.object_db(), id).await?;
+            CustomGroup::remove(self.object_db(), id).await?;
+            self.0
+                .audit(
+                    QmAuditEntry::new(AuditEntity::Group, AuditAction::Remove, id)
+                        .with_context(before.as_ref().and_then(|group| group.context.parse().ok()))
+                        .with_diff(before.as_ref(), None::<&CustomGroup>),
+                )
+                .await;
             i += 1;
         }
         Ok(i)
@@ -292,11 +494,53 @@ where
     BuiltInGroup: RelatedBuiltInGroup,
 {
     #[graphql(
-        guard = "AuthGuard::<Auth, Store, Resource, Permission>::new(qm_role::role!(Resource::user(), Permission::create()))"
+        guard = "ResourceGuard::<Auth, Store, Resource, Permission>::create(Resource::user())"
     )]
     async fn groups(&self) -> Groups {
         Groups
     }
+
+    /// Built-in groups with their roles and allowed types.
+    async fn qm_permission_matrix(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::FieldResult<QmPermissionMatrix> {
+        AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?;
+        Ok(Auth::group_definitions().into_iter().collect())
+    }
+
+    async fn custom_group(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+    ) -> async_graphql::FieldResult<Option<CustomGroup>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::view()),
+        )
+        .await?;
+        let group = CustomGroup::by_id(auth_ctx.store.as_ref(), &id.to_string()).await?;
+        if let Some(group) = group.as_ref() {
+            let context: InfraContext = group.context.parse()?;
+            auth_ctx.can_mutate(Some(&context)).await?;
+        }
+        Ok(group)
+    }
+
+    async fn custom_groups(
+        &self,
+        ctx: &Context<'_>,
+        context: ContextFilter,
+    ) -> async_graphql::FieldResult<Vec<CustomGroup>> {
+        let context = InfraContext::from(context);
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::view()),
+        )
+        .await?;
+        auth_ctx.can_mutate(Some(&context)).await?;
+        Ok(CustomGroup::list(auth_ctx.store.as_ref(), &context).await?)
+    }
 }
 
 pub struct GroupMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
@@ -326,46 +570,68 @@ where
     async fn create_group(
         &self,
         ctx: &Context<'_>,
-        context: InfraContext,
+        context: ContextFilter,
         name: String,
         allowed_access_levels: HashSet<AccessLevel>,
         allowed_types: HashSet<String>,
         roles: HashSet<qm_role::Role<Resource, Permission>>,
     ) -> async_graphql::FieldResult<Arc<UserGroup>> {
+        let context = InfraContext::from(context);
         let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
             ctx,
             &qm_role::role!(Resource::user(), Permission::create()),
         )
         .await?;
         auth_ctx.can_mutate(Some(&context)).await?;
-        if allowed_access_levels
-            .iter()
-            .any(|lvl| matches!(lvl, &AccessLevel::Admin | AccessLevel::None))
-        {
-            return exerr!(bad_request(
-                "UserGroup",
-                "unable to create custom group with allowed access level ADMIN or NONE"
-            ));
-        }
-        if roles.iter().any(|r| r.ty.is_admin()) {
-            return exerr!(bad_request(
-                "UserGroup",
-                "unable to create custom group with role 'administration'"
-            ));
-        }
-        if !auth_ctx.is_admin {
-            for role in roles.iter() {
-                if !auth_ctx.auth.has_role_object(role) {
-                    return exerr!(unauthorized(&auth_ctx.auth));
-                }
-            }
-        }
-        let roles = roles.into_iter().collect();
+        Ctx(&auth_ctx)
+            .validate(&allowed_access_levels, &roles)
+            .await?;
         Ctx(&auth_ctx)
             .create(name, context, allowed_access_levels, allowed_types, roles)
             .await
     }
 
+    async fn update_group(
+        &self,
+        ctx: &Context<'_>,
+        id: Uuid,
+        name: Option<String>,
+        allowed_access_levels: HashSet<AccessLevel>,
+        allowed_types: HashSet<String>,
+        roles: HashSet<qm_role::Role<Resource, Permission>>,
+    ) -> async_graphql::FieldResult<Arc<UserGroup>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::update()),
+        )
+        .await?;
+        let id: Arc<str> = Arc::from(id.to_string());
+        let group_detail = auth_ctx
+            .store
+            .cache_db()
+            .group_detail_by_id(&id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<Group>(id.as_ref()))
+            .extend()?;
+        if group_detail.built_in {
+            return exerr!(bad_request("Group", "unable to update built in groups"));
+        }
+        auth_ctx.can_mutate(group_detail.context.as_ref()).await?;
+        let roles = Ctx(&auth_ctx)
+            .validate(&allowed_access_levels, &roles)
+            .await?;
+        Ctx(&auth_ctx)
+            .update(
+                id,
+                &group_detail,
+                name,
+                allowed_access_levels,
+                allowed_types,
+                roles,
+            )
+            .await
+    }
+
     async fn remove_groups(
         &self,
         ctx: &Context<'_>,
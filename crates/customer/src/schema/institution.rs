@@ -17,18 +17,25 @@ use sqlx::types::Uuid;
 
 use crate::cache::CacheDB;
 
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cleanup::{CleanupTask, CleanupTaskType};
+use crate::cleanup_status;
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
 use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
 use crate::groups::RelatedBuiltInGroup;
+use crate::lifecycle;
 use crate::marker::Marker;
+use crate::model::ListSort;
 use crate::model::QmCustomer;
+use crate::model::QmEntityStatus;
 use crate::model::QmInstitution;
 use crate::model::QmOrganization;
 use crate::model::{CreateInstitutionInput, UpdateInstitutionInput};
 use crate::model::{InstitutionData, QmInstitutionList};
+use crate::mutation::move_institution;
+use crate::mutation::update_institution_status;
 use crate::mutation::{remove_institutions, update_institution};
 use crate::roles;
 use crate::schema::auth::AuthCtx;
@@ -79,6 +86,7 @@ where
         &self,
         mut context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmInstitutionList> {
         context = self
@@ -90,7 +98,7 @@ where
             .0
             .store
             .cache_db()
-            .institution_list(context, filter, ty)
+            .institution_list(context, filter, sort, ty)
             .await)
     }
 
@@ -159,6 +167,18 @@ where
                         .infra()
                         .new_institution(institution.clone())
                         .await;
+                    let context = InfraContext::from(id);
+                    self.0
+                        .audit(
+                            QmAuditEntry::new(
+                                AuditEntity::Institution,
+                                AuditAction::Create,
+                                context,
+                            )
+                            .with_context(Some(context))
+                            .with_diff(None::<&QmInstitution>, Some(institution.as_ref())),
+                        )
+                        .await;
                     (institution, false)
                 },
             )
@@ -177,6 +197,7 @@ where
         name: String,
     ) -> EntityResult<Arc<QmInstitution>> {
         let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
         let id: InfraId = id.into();
         let old = self.0.store.cache_db().institution_by_id(&id).await.ok_or(
             EntityError::not_found_by_field::<QmInstitution>("name", &name),
@@ -190,22 +211,178 @@ where
             .infra()
             .update_institution(new.clone(), old.as_ref().into())
             .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Institution, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        Ok(new)
+    }
+
+    /// Changes the lifecycle status, the users are suspended or reactivated
+    /// by the cleanup worker.
+    pub async fn set_status(
+        &self,
+        id: InstitutionId,
+        status: QmEntityStatus,
+    ) -> EntityResult<Arc<QmInstitution>> {
+        let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
+        let id: InfraId = id.into();
+        let old = self.0.store.cache_db().institution_by_id(&id).await.ok_or(
+            EntityError::not_found_by_id::<QmInstitution>(context.to_string()),
+        )?;
+        lifecycle::check_transition(old.status, status)?;
+        let result =
+            update_institution_status(self.0.store.customer_db().pool(), id, status, user_id)
+                .await?;
+        let new = Arc::new(result);
+        self.0
+            .store
+            .cache_db()
+            .infra()
+            .update_institution(new.clone(), old.as_ref().into())
+            .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Institution, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        if let Some(task) = lifecycle::users_task(&context, old.status, status) {
+            cleanup_status::enqueue(self.0.store, &task).await?;
+        }
+        Ok(new)
+    }
+
+    /// Moves the institution to another organization of the same customer.
+    ///
+    /// The institution keeps its numeric id, the owner references of its
+    /// resources and the access roles of its users are migrated by the
+    /// cleanup worker.
+    pub async fn move_to(
+        &self,
+        id: InstitutionId,
+        organization_id: OrganizationId,
+    ) -> EntityResult<Arc<QmInstitution>> {
+        let user_id = self.0.auth.user_id().unwrap();
+        let cache = self.0.store.cache_db();
+        let old =
+            cache
+                .institution_by_id(&id.into())
+                .await
+                .ok_or(EntityError::not_found_by_id::<QmInstitution>(
+                    id.to_string(),
+                ))?;
+        if id.root() != organization_id.root() {
+            return err!(bad_request(
+                "OrganizationId",
+                "the institution can only be moved within the same customer"
+            ));
+        }
+        if id.parent() == organization_id {
+            return err!(bad_request(
+                "OrganizationId",
+                "the institution already belongs to the organization"
+            ));
+        }
+        if cache
+            .organization_by_id(&organization_id.into())
+            .await
+            .is_none()
+        {
+            return err!(not_found_by_id::<QmOrganization>(
+                organization_id.to_string()
+            ));
+        }
+        if cache
+            .institution_by_name(old.customer_id, organization_id.into(), old.name.clone())
+            .await
+            .is_some()
+        {
+            return err!(name_conflict::<QmInstitution>(old.name.to_string()));
+        }
+        let result = move_institution(
+            self.0.store.customer_db().pool(),
+            id.into(),
+            organization_id.into(),
+            user_id,
+        )
+        .await?;
+        let new_id: InstitutionId = (&result).into();
+        let access = qm_role::Access::new(AccessLevel::Institution)
+            .with_fmt_id(Some(&new_id))
+            .to_string();
+        let roles = roles::ensure(self.0.store.keycloak(), Some(access).into_iter()).await?;
+        cache.user().new_roles(roles).await;
+        let new = Arc::new(result);
+        cache
+            .infra()
+            .update_institution(new.clone(), old.as_ref().into())
+            .await;
+        let context = InfraContext::from(id);
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Institution, AuditAction::Update, context)
+                    .with_context(Some(InfraContext::from(new_id)))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        if let Some(producer) = self.0.store.mutation_event_producer() {
+            producer
+                .update_event(
+                    &qm_kafka::producer::EventNs::Institution,
+                    "institution",
+                    "sys",
+                    new.as_ref(),
+                )
+                .await?;
+        }
+        cleanup_status::enqueue(
+            self.0.store,
+            &CleanupTask::new(CleanupTaskType::MoveInstitution(id, new_id)),
+        )
+        .await?;
         Ok(new)
     }
 
     pub async fn remove(&self, ids: InstitutionIds) -> EntityResult<u64> {
         let v: Vec<i64> = ids.iter().map(InstitutionId::id).collect();
+        let mut removed = vec![];
+        for id in ids.iter() {
+            if let Some(old) = self
+                .0
+                .store
+                .cache_db()
+                .institution_by_id(&(*id).into())
+                .await
+            {
+                removed.push((InfraContext::from(*id), old));
+            }
+        }
         let delete_count = remove_institutions(self.0.store.customer_db().pool(), &v).await?;
         if delete_count != 0 {
+            for (context, old) in removed {
+                self.0
+                    .audit(
+                        QmAuditEntry::new(AuditEntity::Institution, AuditAction::Remove, context)
+                            .with_context(Some(context))
+                            .with_diff(Some(old.as_ref()), None::<&QmInstitution>),
+                    )
+                    .await;
+            }
             let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
+            cleanup_status::enqueue(
+                self.0.store,
+                &CleanupTask {
                     id,
                     ty: CleanupTaskType::Institutions(ids),
-                })
-                .await?;
+                },
+            )
+            .await?;
             tracing::debug!("emit cleanup task {}", id.to_string());
             return Ok(delete_count);
         }
@@ -237,6 +414,17 @@ where
     Permission: RelatedPermission,
     BuiltInGroup: RelatedBuiltInGroup,
 {
+    /// Resolves `QmInstitution` references of a federated gateway.
+    #[cfg(feature = "federation")]
+    #[graphql(entity)]
+    async fn find_qm_institution_by_id(
+        &self,
+        ctx: &Context<'_>,
+        id: InstitutionId,
+    ) -> async_graphql::FieldResult<Option<Arc<QmInstitution>>> {
+        self.qm_institution_by_id(ctx, id).await
+    }
+
     async fn qm_institution_by_id(
         &self,
         ctx: &Context<'_>,
@@ -278,6 +466,7 @@ where
         ctx: &Context<'_>,
         context: Option<CustomerOrOrganization>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmInstitutionList> {
         Ctx(
@@ -287,7 +476,7 @@ where
             )
             .await?,
         )
-        .list(context, filter, ty)
+        .list(context, filter, sort, ty)
         .await
         .extend()
     }
@@ -374,4 +563,85 @@ where
         }
         Ctx(&auth_ctx).remove(ids).await.extend()
     }
+
+    /// Moves the institution to another organization of the same customer.
+    async fn qm_move_institution(
+        &self,
+        ctx: &Context<'_>,
+        institution_id: InstitutionId,
+        new_organization_id: OrganizationId,
+    ) -> async_graphql::FieldResult<Arc<QmInstitution>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::institution(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Institution(institution_id)))
+            .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Organization(new_organization_id)))
+            .await?;
+        Ctx(&auth_ctx)
+            .move_to(institution_id, new_organization_id)
+            .await
+            .extend()
+    }
+
+    async fn qm_suspend_institution(
+        &self,
+        ctx: &Context<'_>,
+        context: InstitutionId,
+    ) -> async_graphql::FieldResult<Arc<QmInstitution>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::institution(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Institution(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Suspended)
+            .await
+            .extend()
+    }
+
+    async fn qm_reactivate_institution(
+        &self,
+        ctx: &Context<'_>,
+        context: InstitutionId,
+    ) -> async_graphql::FieldResult<Arc<QmInstitution>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::institution(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Institution(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Active)
+            .await
+            .extend()
+    }
+
+    async fn qm_archive_institution(
+        &self,
+        ctx: &Context<'_>,
+        context: InstitutionId,
+    ) -> async_graphql::FieldResult<Arc<QmInstitution>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::institution(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Institution(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Archived)
+            .await
+            .extend()
+    }
 }
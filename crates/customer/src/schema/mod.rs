@@ -1,11 +1,17 @@
 use async_graphql::MergedObject;
 
+pub mod api_client;
 pub mod auth;
 pub mod customer;
 pub mod groups;
 pub mod institution;
 pub mod organization;
+pub mod search;
+pub mod subscription;
 pub mod user;
+pub mod webhook;
+
+pub use subscription::QmCustomerSubscriptionRoot;
 
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
@@ -20,6 +26,12 @@ pub struct QmCustomerQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>(
     institution::InstitutionQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     user::UserQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     groups::GroupQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    crate::audit::AuditQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    crate::cleanup_status::CleanupTaskQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    crate::features::FeatureQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    api_client::ApiClientQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    search::SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    webhook::WebhookQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
 )
 where
     Auth: RelatedAuth<Resource, Permission>,
@@ -44,6 +56,12 @@ where
             institution::InstitutionQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             user::UserQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             groups::GroupQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            crate::audit::AuditQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            crate::cleanup_status::CleanupTaskQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            crate::features::FeatureQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            api_client::ApiClientQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            search::SearchQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            webhook::WebhookQueryRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
         )
     }
 }
@@ -55,6 +73,9 @@ pub struct QmCustomerMutationRoot<Auth, Store, Resource, Permission, BuiltInGrou
     institution::InstitutionMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     user::UserMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
     groups::GroupMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    api_client::ApiClientMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    webhook::WebhookMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
+    crate::features::FeatureMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>,
 )
 where
     Auth: RelatedAuth<Resource, Permission>,
@@ -79,6 +100,9 @@ where
             institution::InstitutionMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             user::UserMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
             groups::GroupMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            api_client::ApiClientMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            webhook::WebhookMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
+            crate::features::FeatureMutationRoot::<Auth, Store, Resource, Permission, BuiltInGroup>::default(),
         )
     }
 }
@@ -22,22 +22,28 @@ use sqlx::types::Uuid;
 
 use crate::cache::CacheDB;
 
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cleanup::CleanupTask;
 use crate::cleanup::CleanupTaskType;
+use crate::cleanup_status;
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
 use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
 use crate::groups::RelatedBuiltInGroup;
+use crate::lifecycle;
 use crate::marker::Marker;
 use crate::model::CreateOrganizationInput;
+use crate::model::ListSort;
 use crate::model::OrganizationData;
 use crate::model::QmCustomer;
+use crate::model::QmEntityStatus;
 use crate::model::QmOrganization;
 use crate::model::QmOrganizationList;
 use crate::model::UpdateOrganizationInput;
 use crate::mutation::remove_organizations;
 use crate::mutation::update_organization;
+use crate::mutation::update_organization_status;
 use crate::roles;
 use crate::schema::auth::AuthCtx;
 
@@ -77,6 +83,7 @@ where
         &self,
         mut context: Option<CustomerId>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmOrganizationList> {
         context = self.0.enforce_customer_context(context).await.extend()?;
@@ -84,7 +91,7 @@ where
             .0
             .store
             .cache_db()
-            .organization_list(context, filter, ty)
+            .organization_list(context, filter, sort, ty)
             .await)
     }
 
@@ -155,6 +162,18 @@ where
                         .infra()
                         .new_organization(organization.clone())
                         .await;
+                    let context = InfraContext::from(id);
+                    self.0
+                        .audit(
+                            QmAuditEntry::new(
+                                AuditEntity::Organization,
+                                AuditAction::Create,
+                                context,
+                            )
+                            .with_context(Some(context))
+                            .with_diff(None::<&QmOrganization>, Some(organization.as_ref())),
+                        )
+                        .await;
                     (organization, false)
                 },
             )
@@ -173,6 +192,7 @@ where
         name: String,
     ) -> EntityResult<Arc<QmOrganization>> {
         let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
         let id: InfraId = id.into();
         let old = self
             .0
@@ -192,22 +212,93 @@ where
             .infra()
             .update_organization(new.clone(), old.as_ref().into())
             .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Organization, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        Ok(new)
+    }
+
+    /// Changes the lifecycle status, the users are suspended or reactivated
+    /// by the cleanup worker.
+    pub async fn set_status(
+        &self,
+        id: OrganizationId,
+        status: QmEntityStatus,
+    ) -> EntityResult<Arc<QmOrganization>> {
+        let user_id = self.0.auth.user_id().unwrap();
+        let context = InfraContext::from(id);
+        let id: InfraId = id.into();
+        let old = self
+            .0
+            .store
+            .cache_db()
+            .organization_by_id(&id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<QmOrganization>(
+                context.to_string(),
+            ))?;
+        lifecycle::check_transition(old.status, status)?;
+        let result =
+            update_organization_status(self.0.store.customer_db().pool(), id, status, user_id)
+                .await?;
+        let new = Arc::new(result);
+        self.0
+            .store
+            .cache_db()
+            .infra()
+            .update_organization(new.clone(), old.as_ref().into())
+            .await;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::Organization, AuditAction::Update, context)
+                    .with_context(Some(context))
+                    .with_diff(Some(old.as_ref()), Some(new.as_ref())),
+            )
+            .await;
+        if let Some(task) = lifecycle::users_task(&context, old.status, status) {
+            cleanup_status::enqueue(self.0.store, &task).await?;
+        }
         Ok(new)
     }
 
     pub async fn remove(&self, ids: OrganizationIds) -> EntityResult<u64> {
         let v: Vec<i64> = ids.iter().map(OrganizationId::id).collect();
+        let mut removed = vec![];
+        for id in ids.iter() {
+            if let Some(old) = self
+                .0
+                .store
+                .cache_db()
+                .organization_by_id(&(*id).into())
+                .await
+            {
+                removed.push((InfraContext::from(*id), old));
+            }
+        }
         let delete_count = remove_organizations(self.0.store.customer_db().pool(), &v).await?;
         if delete_count != 0 {
+            for (context, old) in removed {
+                self.0
+                    .audit(
+                        QmAuditEntry::new(AuditEntity::Organization, AuditAction::Remove, context)
+                            .with_context(Some(context))
+                            .with_diff(Some(old.as_ref()), None::<&QmOrganization>),
+                    )
+                    .await;
+            }
             let id = Uuid::new_v4();
-            self.0
-                .store
-                .cleanup_task_producer()
-                .add_item(&CleanupTask {
+            cleanup_status::enqueue(
+                self.0.store,
+                &CleanupTask {
                     id,
                     ty: CleanupTaskType::Organizations(ids),
-                })
-                .await?;
+                },
+            )
+            .await?;
             tracing::debug!("emit cleanup task {}", id.to_string());
             return Ok(delete_count);
         }
@@ -239,6 +330,17 @@ where
     Permission: RelatedPermission,
     BuiltInGroup: RelatedBuiltInGroup,
 {
+    /// Resolves `QmOrganization` references of a federated gateway.
+    #[cfg(feature = "federation")]
+    #[graphql(entity)]
+    async fn find_qm_organization_by_id(
+        &self,
+        ctx: &Context<'_>,
+        id: OrganizationId,
+    ) -> async_graphql::FieldResult<Option<Arc<QmOrganization>>> {
+        self.qm_organization_by_id(ctx, id).await
+    }
+
     async fn qm_organization_by_id(
         &self,
         ctx: &Context<'_>,
@@ -279,6 +381,7 @@ where
         ctx: &Context<'_>,
         context: Option<CustomerId>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
         ty: Option<String>,
     ) -> async_graphql::FieldResult<QmOrganizationList> {
         Ctx(
@@ -288,7 +391,7 @@ where
             )
             .await?,
         )
-        .list(context, filter, ty)
+        .list(context, filter, sort, ty)
         .await
         .extend()
     }
@@ -380,4 +483,61 @@ where
         }
         Ctx(&auth_ctx).remove(ids).await.extend()
     }
+
+    async fn qm_suspend_organization(
+        &self,
+        ctx: &Context<'_>,
+        context: OrganizationId,
+    ) -> async_graphql::FieldResult<Arc<QmOrganization>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::organization(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Organization(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Suspended)
+            .await
+            .extend()
+    }
+
+    async fn qm_reactivate_organization(
+        &self,
+        ctx: &Context<'_>,
+        context: OrganizationId,
+    ) -> async_graphql::FieldResult<Arc<QmOrganization>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::organization(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Organization(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Active)
+            .await
+            .extend()
+    }
+
+    async fn qm_archive_organization(
+        &self,
+        ctx: &Context<'_>,
+        context: OrganizationId,
+    ) -> async_graphql::FieldResult<Arc<QmOrganization>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::organization(), Permission::update()),
+        )
+        .await?;
+        auth_ctx
+            .can_mutate(Some(&InfraContext::Organization(context)))
+            .await?;
+        Ctx(&auth_ctx)
+            .set_status(context, QmEntityStatus::Archived)
+            .await
+            .extend()
+    }
 }
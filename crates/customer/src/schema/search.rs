@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use async_graphql::{Context, Object, ResultExt, SimpleObject, Union};
+use qm_entity::ids::{ContextFilter, InfraContext};
+
+use crate::cache::search::SearchKey;
+use crate::context::RelatedAuth;
+use crate::context::RelatedPermission;
+use crate::context::RelatedResource;
+use crate::context::RelatedStorage;
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::model::{QmCustomer, QmInstitution, QmOrganization, QmUserDetails};
+use crate::schema::auth::AuthCtx;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Union)]
+#[graphql(name = "QmSearchEntity")]
+pub enum SearchEntity {
+    Customer(Arc<QmCustomer>),
+    Organization(Arc<QmOrganization>),
+    Institution(Arc<QmInstitution>),
+    User(QmUserDetails),
+}
+
+#[derive(SimpleObject)]
+#[graphql(name = "QmSearchResult")]
+pub struct SearchResult {
+    /// Between 0 and 1, exact matches score 1.
+    pub score: f64,
+    pub entity: SearchEntity,
+}
+
+pub struct SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    SearchQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// Ranked search over the names of customers, organizations, institutions
+    /// and users the current user is allowed to list.
+    async fn qm_search(
+        &self,
+        ctx: &Context<'_>,
+        term: String,
+        context: Option<ContextFilter>,
+        limit: Option<usize>,
+    ) -> async_graphql::FieldResult<Vec<SearchResult>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx)
+            .await
+            .extend()?;
+        let context = auth_ctx
+            .enforce_current_context(ContextFilter::into_context(context))
+            .await
+            .extend()?;
+        let allowed = |resource: Resource| {
+            auth_ctx.is_admin
+                || auth_ctx
+                    .auth
+                    .satisfies(&qm_role::role!(resource, Permission::list()))
+        };
+        let customers = allowed(Resource::customer());
+        let organizations = allowed(Resource::organization());
+        let institutions = allowed(Resource::institution());
+        let users = allowed(Resource::user());
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+        let cache = auth_ctx.store.cache_db();
+        let hits = cache
+            .search(&term, context.as_ref(), limit, |key| match key {
+                SearchKey::Infra(InfraContext::Customer(_)) => customers,
+                SearchKey::Infra(InfraContext::Organization(_)) => organizations,
+                SearchKey::Infra(InfraContext::Institution(_)) => institutions,
+                SearchKey::Infra(InfraContext::OrganizationUnit(_)) => false,
+                SearchKey::User(_) => users,
+            })
+            .await;
+        let mut result = Vec::with_capacity(hits.len());
+        for (key, score) in hits {
+            let entity = match key {
+                SearchKey::Infra(InfraContext::Customer(id)) => cache
+                    .customer_by_id(&id.into())
+                    .await
+                    .map(SearchEntity::Customer),
+                SearchKey::Infra(InfraContext::Organization(id)) => cache
+                    .organization_by_id(&id.into())
+                    .await
+                    .map(SearchEntity::Organization),
+                SearchKey::Infra(InfraContext::Institution(id)) => cache
+                    .institution_by_id(&id.into())
+                    .await
+                    .map(SearchEntity::Institution),
+                SearchKey::Infra(InfraContext::OrganizationUnit(_)) => None,
+                SearchKey::User(id) => cache.user_details_by_id(&id).await.map(SearchEntity::User),
+            };
+            if let Some(entity) = entity {
+                result.push(SearchResult { score, entity });
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use async_graphql::{Context, ResultExt, SimpleObject, Subscription};
+use futures::{Stream, StreamExt};
+use qm_entity::ids::{ContextFilter, CustomerId, InfraContext};
+
+use crate::cache::events::{self, CacheEvent, ChangeOp};
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::model::{QmCustomer, QmUserDetails};
+use crate::schema::auth::AuthCtx;
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmCustomerChanged {
+    pub op: ChangeOp,
+    pub id: CustomerId,
+    /// Current state, `null` if the customer was removed.
+    pub customer: Option<Arc<QmCustomer>>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmUserChanged {
+    pub op: ChangeOp,
+    pub id: String,
+    /// Current state, `null` if the user was removed.
+    pub user: Option<QmUserDetails>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmGroupChanged {
+    pub op: ChangeOp,
+    pub id: String,
+    /// Current name, `null` if the group was removed.
+    pub name: Option<String>,
+}
+
+pub struct QmCustomerSubscriptionRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for QmCustomerSubscriptionRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Subscription]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    QmCustomerSubscriptionRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    async fn customer_changed(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<ContextFilter>,
+    ) -> async_graphql::FieldResult<impl Stream<Item = QmCustomerChanged>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::customer(), Permission::list()),
+        )
+        .await?;
+        let scope = auth_ctx
+            .enforce_current_context(ContextFilter::into_context(context))
+            .await
+            .extend()?;
+        let cache = auth_ctx.store.cache_db().clone();
+        let rx = cache.infra().events.subscribe();
+        Ok(events::stream(rx).filter_map(move |event| {
+            let cache = cache.clone();
+            async move {
+                let CacheEvent::Customer { op, id } = event else {
+                    return None;
+                };
+                let customer_id: CustomerId = (*id.as_ref()).into();
+                let entity = InfraContext::Customer(customer_id);
+                if !events::visible(scope.as_ref(), Some(&entity)) {
+                    return None;
+                }
+                let customer = cache.customer_by_id(&id).await;
+                Some(QmCustomerChanged {
+                    op,
+                    id: customer_id,
+                    customer,
+                })
+            }
+        }))
+    }
+
+    async fn user_changed(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<ContextFilter>,
+    ) -> async_graphql::FieldResult<impl Stream<Item = QmUserChanged>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::list()),
+        )
+        .await?;
+        let scope = auth_ctx
+            .enforce_current_context(ContextFilter::into_context(context))
+            .await
+            .extend()?;
+        let cache = auth_ctx.store.cache_db().clone();
+        let rx = cache.user().events.subscribe();
+        Ok(events::stream(rx).filter_map(move |event| {
+            let cache = cache.clone();
+            async move {
+                let CacheEvent::User { op, id, context } = event else {
+                    return None;
+                };
+                if !events::visible(scope.as_ref(), context.as_ref()) {
+                    return None;
+                }
+                let user = if op == ChangeOp::Removed {
+                    None
+                } else {
+                    cache.user_details_by_id(&id).await
+                };
+                Some(QmUserChanged {
+                    op,
+                    id: id.to_string(),
+                    user,
+                })
+            }
+        }))
+    }
+
+    async fn group_changed(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<ContextFilter>,
+    ) -> async_graphql::FieldResult<impl Stream<Item = QmGroupChanged>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::list()),
+        )
+        .await?;
+        let scope = auth_ctx
+            .enforce_current_context(ContextFilter::into_context(context))
+            .await
+            .extend()?;
+        let cache = auth_ctx.store.cache_db().clone();
+        let rx = cache.user().events.subscribe();
+        Ok(events::stream(rx).filter_map(move |event| {
+            let cache = cache.clone();
+            async move {
+                let CacheEvent::Group { op, id, context } = event else {
+                    return None;
+                };
+                if !events::visible(scope.as_ref(), context.as_ref()) {
+                    return None;
+                }
+                let name = cache.group_by_id(&id).await.map(|g| g.name.to_string());
+                Some(QmGroupChanged {
+                    op,
+                    id: id.to_string(),
+                    name,
+                })
+            }
+        }))
+    }
+}
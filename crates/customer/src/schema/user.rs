@@ -1,7 +1,7 @@
 use async_graphql::ComplexObject;
 use async_graphql::{Context, ErrorExtensions, FieldResult, Object, ResultExt};
 use qm_entity::exerr;
-use qm_entity::ids::InfraContext;
+use qm_entity::ids::{ContextFilter, InfraContext};
 
 use qm_entity::model::ListFilter;
 use qm_keycloak::RoleRepresentation;
@@ -10,15 +10,26 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cache::CacheDB;
+use crate::cleanup::{CleanupTask, CleanupTaskType};
 use crate::config::SchemaConfig;
 use crate::groups::RelatedBuiltInGroup;
+use crate::invitation::{Invitation, INVITATION_ACTIONS};
 use crate::marker::Marker;
+use crate::model::ListSort;
+use crate::model::ListSortField;
 use crate::model::QmUser;
 use crate::model::QmUserList;
+use crate::model::QmUserLockStatus;
+use crate::model::QmUserSession;
 use crate::model::{CreateUserPayload, QmInstitution, QmOrganization, QmUserDetails};
 use crate::model::{Group, QmRequiredUserAction, Role, UserGroup};
-use crate::model::{QmCreateUserInput, QmCustomer};
+use crate::model::{QmCreateUserInput, QmCustomer, QmInviteUserInput};
+use crate::user_import::{
+    self, DuplicateHandling, QmUserExport, UserExportRow, UserFileFormat, UserImport,
+    UserImportItem, UserImportStatus,
+};
 use qm_entity::err;
 use qm_entity::error::EntityError;
 use qm_entity::error::EntityResult;
@@ -273,6 +284,114 @@ impl QmUserDetails {
     }
 }
 
+/// Creates the user in keycloak, assigns group and access role and adds it to the cache.
+pub async fn insert_user<Store>(store: &Store, input: CreateUserPayload) -> FieldResult<Arc<QmUser>>
+where
+    Store: RelatedStorage,
+{
+    let CreateUserPayload {
+        user: mut user_input,
+        access,
+        group_id,
+        context: _,
+    } = input;
+    let mut conflict_fields = Vec::new();
+    let user_exists_by_username = store
+        .cache_db()
+        .user_by_username(&user_input.username)
+        .await;
+    if user_exists_by_username.is_some() {
+        conflict_fields.push("username");
+    }
+    let user_exists_by_email = store.cache_db().user_by_email(&user_input.email).await;
+    if user_exists_by_email.is_some() {
+        conflict_fields.push("email");
+    }
+
+    if !conflict_fields.is_empty() {
+        return err!(
+            fields_conflict::<QmUser>(user_input.username.as_str(), &conflict_fields[..]).extend()
+        );
+    }
+
+    if user_input.enabled.is_none() {
+        user_input.enabled = Some(true);
+    }
+
+    let cache = store.cache_db();
+    let group = match group_id.as_ref() {
+        Some(group_id) => Some(
+            cache
+                .group_by_id(group_id)
+                .await
+                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+                .extend()?,
+        ),
+        None => None,
+    };
+
+    let keycloak = store.keycloak();
+    let realm = keycloak.config().realm();
+    let k_user = create_keycloak_user(realm, keycloak, user_input.clone()).await?;
+    let user_id = k_user.id.as_ref().unwrap().clone();
+
+    if user_input
+        .required_actions
+        .map(|actions| actions.contains(&QmRequiredUserAction::VerifyEmail))
+        .unwrap_or_default()
+    {
+        if let Err(err) = keycloak.send_verify_email_user(realm, &user_id, None).await {
+            tracing::warn!(
+                "Verification email could not be sent: {}",
+                keycloak.error_message(&err)
+            );
+        }
+    }
+
+    let user_uuid = Uuid::parse_str(&user_id).map_err(|err| {
+        tracing::error!("Unable to parse user id to Uuid: {err:#?}");
+        EntityError::Internal
+    })?;
+    let mut user_groups = vec![];
+    if let Some(group) = group {
+        tracing::info!(
+            "add user {} to group {group:#?}",
+            user_input.username.as_str()
+        );
+        keycloak
+            .add_user_to_group(realm, &user_id, &group.id)
+            .await?;
+        user_groups.push(group);
+    }
+    let mut user_roles = vec![];
+    if let Some(access) = access.as_ref() {
+        if let Some(role) = cache.role_by_name(access).await {
+            keycloak
+                .add_user_role(
+                    realm,
+                    &user_id,
+                    RoleRepresentation {
+                        id: Some(role.id.to_string()),
+                        name: Some(role.name.to_string()),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            user_roles.push(role);
+        }
+    }
+    let user = Arc::new(QmUser {
+        id: Arc::from(user_uuid.to_string()),
+        username: Arc::from(user_input.username),
+        firstname: Arc::from(user_input.firstname),
+        lastname: Arc::from(user_input.lastname),
+        email: Arc::from(user_input.email),
+        enabled: user_input.enabled.unwrap(),
+    });
+    cache.user().new_user(user.clone()).await;
+    Ok(user)
+}
+
 pub struct Ctx<'a, Auth, Store, Resource, Permission>(
     pub &'a AuthCtx<'a, Auth, Store, Resource, Permission>,
 )
@@ -294,9 +413,20 @@ where
         &self,
         mut context: Option<InfraContext>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
     ) -> async_graphql::FieldResult<QmUserList> {
+        if sort.is_some_and(|sort| sort.field == ListSortField::CreatedAt) {
+            return err!(
+                bad_request("ListSort", "users can't be sorted by creation time").extend()
+            );
+        }
         context = self.0.enforce_current_context(context).await?;
-        Ok(self.0.store.cache_db().user_list(context, filter).await)
+        Ok(self
+            .0
+            .store
+            .cache_db()
+            .user_list(context, filter, sort)
+            .await)
     }
 
     pub async fn by_id(&self, id: &str) -> Option<QmUserDetails> {
@@ -304,120 +434,299 @@ where
     }
 
     pub async fn create(&self, input: CreateUserPayload) -> FieldResult<Arc<QmUser>> {
-        let CreateUserPayload {
-            user: mut user_input,
-            access,
-            group_id,
-            context: _,
-        } = input;
-        let mut conflict_fields = Vec::new();
-        let user_exists_by_username = self
+        let context = input.context;
+        let user = insert_user(self.0.store, input).await?;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::User, AuditAction::Create, &user.id)
+                    .with_context(context)
+                    .with_diff(None::<&QmUser>, Some(user.as_ref())),
+            )
+            .await;
+        Ok(user)
+    }
+
+    /// Creates a disabled user and sends the invitation email.
+    pub async fn invite(
+        &self,
+        config: &SchemaConfig<'_>,
+        payload: CreateUserPayload,
+    ) -> FieldResult<Invitation> {
+        let context = payload.context.map(|c| c.to_string());
+        let email = payload.user.email.clone();
+        let user = self.create(payload).await?;
+        let invitation = Invitation::new(
+            user.id.as_ref(),
+            email,
+            context,
+            self.0.auth.user_id(),
+            config.invitation_ttl(),
+        );
+        self.send_invitation(config, &invitation).await?;
+        invitation.save(self.0.store.redis()).await?;
+        Ok(invitation)
+    }
+
+    /// Enables the invited user and sends the email with the link to verify
+    /// the email address and set a password, Keycloak does not send action
+    /// emails to disabled users.
+    pub async fn send_invitation(
+        &self,
+        config: &SchemaConfig<'_>,
+        invitation: &Invitation,
+    ) -> FieldResult<()> {
+        let keycloak = self.0.store.keycloak();
+        let realm = keycloak.config().realm();
+        let mut user = keycloak
+            .user_by_id(realm, &invitation.user_id)
+            .await?
+            .ok_or(EntityError::not_found_by_id::<QmUser>(&invitation.user_id))
+            .extend()?;
+        let was_enabled = user.enabled == Some(true);
+        if !was_enabled {
+            user.enabled = Some(true);
+            keycloak
+                .update_user(realm, &invitation.user_id, &user)
+                .await?;
+        }
+        let result = keycloak
+            .send_execute_actions_email(
+                realm,
+                &invitation.user_id,
+                config.invitation_client_id().map(str::to_string),
+                Some(config.invitation_ttl().min(i32::MAX as u64) as i32),
+                invitation.redirect_url(config.invitation_redirect_url()),
+                INVITATION_ACTIONS.iter().map(ToString::to_string).collect(),
+            )
+            .await;
+        if let Err(err) = result {
+            if !was_enabled {
+                user.enabled = Some(false);
+                keycloak
+                    .update_user(realm, &invitation.user_id, &user)
+                    .await?;
+            }
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    /// Returns true while the invited user has not set a password.
+    pub async fn is_invitation_pending(&self, user_id: &str) -> FieldResult<bool> {
+        let keycloak = self.0.store.keycloak();
+        let user = keycloak
+            .user_by_id(keycloak.config().realm(), user_id)
+            .await?
+            .ok_or(EntityError::not_found_by_id::<QmUser>(user_id))
+            .extend()?;
+        let action = QmRequiredUserAction::UpdatePassword.to_string();
+        Ok(user
+            .required_actions
+            .map(|actions| actions.contains(&action))
+            .unwrap_or(false))
+    }
+
+    /// Type of the customer, organization or institution of `context`.
+    async fn context_type(&self, context: &InfraContext) -> Option<Arc<str>> {
+        let cache = self.0.store.cache_db();
+        match context {
+            InfraContext::Customer(_) => cache
+                .customer_by_id(&context.customer_id())
+                .await
+                .map(|v| v.ty.clone()),
+            InfraContext::Organization(_) => cache
+                .organization_by_id(&context.organization_id()?)
+                .await
+                .map(|v| v.ty.clone()),
+            InfraContext::Institution(_) => cache
+                .institution_by_id(&context.institution_id()?)
+                .await
+                .map(|v| v.ty.clone()),
+            InfraContext::OrganizationUnit(_) => None,
+        }
+    }
+
+    /// Fails if a user with `access_level` in `context` must not be assigned
+    /// to the group.
+    pub async fn check_group(
+        &self,
+        group_id: &str,
+        access_level: &AccessLevel,
+        context: Option<&InfraContext>,
+    ) -> FieldResult<()> {
+        let group = self
             .0
             .store
             .cache_db()
-            .user_by_username(&user_input.username)
-            .await;
-        if user_exists_by_username.is_some() {
-            conflict_fields.push("username");
+            .group_detail_by_id(group_id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+            .extend()?;
+        if group
+            .allowed_access_levels
+            .as_ref()
+            .map(|lvls| !lvls.iter().any(|l| l == access_level))
+            .unwrap_or(false)
+        {
+            return err!(not_allowed("invalid access level for selected group").extend());
         }
-        let user_exists_by_email = self
+        let context_ty = match context {
+            Some(context) => self.context_type(context).await,
+            None => None,
+        };
+        qm_role::validate_assignment(group.as_ref(), context_ty.as_deref())
+            .map_err(EntityError::from)
+            .extend()?;
+
+        let group_roles = self
             .0
             .store
             .cache_db()
-            .user_by_email(&user_input.username)
-            .await;
-        if user_exists_by_email.is_some() {
-            conflict_fields.push("email");
-        }
+            .roles_by_group_id(group_id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<Group>(group_id))
+            .extend()?;
 
-        if !conflict_fields.is_empty() {
-            return err!(fields_conflict::<QmUser>(
-                user_input.username.as_str(),
-                &conflict_fields[..]
-            )
-            .extend());
+        for role in group_roles.iter() {
+            if let Ok(role) = qm_role::Role::<Resource, Permission>::from_str(role.name.as_ref()) {
+                if role.ty.is_admin() {
+                    return err!(not_allowed("invalid group selected").extend());
+                }
+                if !self.0.is_admin && !self.0.auth.satisfies(&role) {
+                    return err!(not_allowed("invalid group selected").extend());
+                }
+            } else {
+                return err!(internal().extend());
+            }
         }
+        Ok(())
+    }
+
+    /// Access role for a new user with `access_level` in `context`.
+    pub fn access(
+        &self,
+        access_level: AccessLevel,
+        context: Option<&InfraContext>,
+    ) -> FieldResult<Access> {
+        Ok(if let Some(context) = context {
+            let access = Access::new(access_level).with_fmt_id(Some(context));
+            if !self.0.auth.has_access(&access) {
+                return err!(unauthorized(&self.0.auth).extend());
+            }
+            access
+        } else {
+            let own_access_level_id = self
+                .0
+                .auth
+                .session_access()
+                .ok_or(EntityError::unauthorized(&self.0.auth))?;
+            if own_access_level_id.id().is_some() {
+                return err!(unauthorized(&self.0.auth).extend());
+            }
+            if access_level.id_required() {
+                return err!(bad_request(
+                    "InfraContext",
+                    "'context' is required for specified access level"
+                )
+                .extend());
+            }
+            Access::new(access_level)
+        })
+    }
 
-        if user_input.enabled.is_none() {
-            user_input.enabled = Some(true);
+    /// Details of the user with `user_id` if the current user may manage it,
+    /// the realm admin can't be managed.
+    async fn managed_user(&self, user_id: &str) -> FieldResult<QmUserDetails> {
+        let details = self
+            .0
+            .store
+            .cache_db()
+            .user_details_by_id(user_id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<QmUser>(user_id))
+            .extend()?;
+        if details.user.username.as_ref() == self.0.store.keycloak().config().realm_admin_username()
+        {
+            return exerr!(unauthorized(&self.0.auth));
         }
+        self.0.can_mutate(details.context.as_ref()).await.extend()?;
+        Ok(details)
+    }
+
+    pub async fn sessions(&self, user_id: &str) -> FieldResult<Vec<QmUserSession>> {
+        self.managed_user(user_id).await?;
+        let keycloak = self.0.store.keycloak();
+        Ok(keycloak
+            .user_sessions(keycloak.config().realm(), user_id)
+            .await?
+            .into_iter()
+            .map(QmUserSession::from)
+            .collect())
+    }
 
+    /// Logs the user out of all sessions, returns the number of terminated
+    /// sessions.
+    pub async fn terminate_sessions(&self, user_id: &str) -> FieldResult<u64> {
+        let details = self.managed_user(user_id).await?;
         let keycloak = self.0.store.keycloak();
         let realm = keycloak.config().realm();
-        let k_user = create_keycloak_user(realm, keycloak, user_input.clone()).await?;
-        let user_id = k_user.id.as_ref().unwrap().clone();
+        let count = keycloak.user_sessions(realm, user_id).await?.len() as u64;
+        keycloak.logout_user(realm, user_id).await?;
+        self.0
+            .audit(
+                QmAuditEntry::new(AuditEntity::User, AuditAction::Update, user_id)
+                    .with_context(details.context),
+            )
+            .await;
+        Ok(count)
+    }
 
-        if user_input
-            .required_actions
-            .map(|actions| actions.contains(&QmRequiredUserAction::VerifyEmail))
-            .unwrap_or_default()
-        {
-            if let Err(err) = keycloak.send_verify_email_user(realm, &user_id, None).await {
-                tracing::warn!(
-                    "Verification email could not be sent: {}",
-                    keycloak.error_message(&err)
-                );
-            }
-        }
+    pub async fn lock_status(&self, user_id: &str) -> FieldResult<QmUserLockStatus> {
+        self.managed_user(user_id).await?;
+        let keycloak = self.0.store.keycloak();
+        Ok(keycloak
+            .brute_force_status(keycloak.config().realm(), user_id)
+            .await?
+            .into())
+    }
 
-        let user_uuid = Uuid::parse_str(&user_id).map_err(|err| {
-            tracing::error!("Unable to parse user id to Uuid: {err:#?}");
-            EntityError::Internal
-        })?;
-        let mut user_groups = vec![];
-        let cache = self.0.store.cache_db();
-        if let Some(group_id) = group_id.as_ref() {
-            if let Some(group) = cache.group_by_id(group_id).await {
-                tracing::info!(
-                    "add user {} to group {group:#?}",
-                    user_input.username.as_str()
-                );
-                keycloak
-                    .add_user_to_group(realm, &user_id, &group.id)
-                    .await?;
-                user_groups.push(group);
-            } else {
-                panic!("group with id {group_id} was not found");
-            }
-        }
-        let mut user_roles = vec![];
-        if let Some(access) = access.as_ref() {
-            if let Some(role) = cache.role_by_name(access).await {
-                keycloak
-                    .add_user_role(
-                        realm,
-                        &user_id,
-                        RoleRepresentation {
-                            id: Some(role.id.to_string()),
-                            name: Some(role.name.to_string()),
-                            ..Default::default()
-                        },
-                    )
-                    .await?;
-                user_roles.push(role);
-            }
+    /// Clears the failed logins of a user locked out by the brute force
+    /// detection, returns `false` if the user wasn't locked.
+    pub async fn unlock(&self, user_id: &str) -> FieldResult<bool> {
+        let details = self.managed_user(user_id).await?;
+        let keycloak = self.0.store.keycloak();
+        let realm = keycloak.config().realm();
+        let locked = keycloak.brute_force_status(realm, user_id).await?.disabled;
+        keycloak.clear_brute_force(realm, user_id).await?;
+        if locked {
+            self.0
+                .audit(
+                    QmAuditEntry::new(AuditEntity::User, AuditAction::Update, user_id)
+                        .with_context(details.context),
+                )
+                .await;
         }
-        let user = Arc::new(QmUser {
-            id: Arc::from(user_uuid.to_string()),
-            username: Arc::from(user_input.username),
-            firstname: Arc::from(user_input.firstname),
-            lastname: Arc::from(user_input.lastname),
-            email: Arc::from(user_input.email),
-            enabled: user_input.enabled.unwrap(),
-        });
-        cache.user().new_user(user.clone()).await;
-        Ok(user)
+        Ok(locked)
     }
 
     pub async fn remove(&self, ids: Arc<[Arc<str>]>) -> EntityResult<u64> {
         let keycloak = self.0.store.keycloak();
         let mut user_ids = Vec::default();
         for id in ids.iter() {
+            let old = self.0.store.cache_db().user_by_id(id).await;
             match keycloak
                 .remove_user(keycloak.config().realm(), id.as_ref())
                 .await
             {
-                Ok(_) => user_ids.push(id.as_ref()),
+                Ok(_) => {
+                    self.0
+                        .audit(
+                            QmAuditEntry::new(AuditEntity::User, AuditAction::Remove, id)
+                                .with_diff(old.as_deref(), None::<&QmUser>),
+                        )
+                        .await;
+                    user_ids.push(id.as_ref())
+                }
                 Err(err) => {
                     tracing::error!("{err:#?}");
                 }
@@ -480,11 +789,46 @@ where
         .await)
     }
 
+    /// Active sessions of the user.
+    async fn user_sessions(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<Vec<QmUserSession>> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::user(), Permission::update()),
+            )
+            .await?,
+        )
+        .sessions(&user_id.to_string())
+        .await
+    }
+
+    /// Brute force lockout state of the user.
+    async fn user_lock_status(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<QmUserLockStatus> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::user(), Permission::update()),
+            )
+            .await?,
+        )
+        .lock_status(&user_id.to_string())
+        .await
+    }
+
     async fn users(
         &self,
         ctx: &Context<'_>,
-        context: Option<InfraContext>,
+        context: Option<ContextFilter>,
         filter: Option<ListFilter>,
+        sort: Option<ListSort>,
     ) -> async_graphql::FieldResult<QmUserList> {
         Ctx(
             &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
@@ -493,10 +837,116 @@ where
             )
             .await?,
         )
-        .list(context, filter)
+        .list(ContextFilter::into_context(context), filter, sort)
         .await
         .extend()
     }
+
+    /// Exports the users of `context`, groups are exported as paths of built-in groups.
+    async fn export_users(
+        &self,
+        ctx: &Context<'_>,
+        format: UserFileFormat,
+        context: Option<ContextFilter>,
+    ) -> async_graphql::FieldResult<QmUserExport> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::list()),
+        )
+        .await?;
+        let context = auth_ctx
+            .enforce_current_context(ContextFilter::into_context(context))
+            .await
+            .extend()?;
+        let cache = auth_ctx.store.cache_db();
+        let realm_admin_username = auth_ctx.store.keycloak().config().realm_admin_username();
+        let list = cache.user_list(context, None, None).await;
+        let mut rows = Vec::with_capacity(list.items.len());
+        for details in list.items.iter() {
+            let user = details.user.as_ref();
+            if user.username.as_ref() == realm_admin_username {
+                continue;
+            }
+            let mut group = None;
+            for user_group in cache
+                .groups_by_user_id(&user.id)
+                .await
+                .iter()
+                .flat_map(|g| g.iter())
+            {
+                if user_group.group_detail.built_in {
+                    group = cache
+                        .group_by_id(&user_group.group_id)
+                        .await
+                        .map(|g| format!("/app/{}", g.name));
+                    break;
+                }
+            }
+            rows.push(UserExportRow {
+                username: &user.username,
+                email: &user.email,
+                firstname: &user.firstname,
+                lastname: &user.lastname,
+                group,
+                enabled: user.enabled,
+                access: details.access.as_ref().map(ToString::to_string),
+            });
+        }
+        let count = rows.len() as i64;
+        let content = user_import::render(format, &rows)?;
+        #[cfg(feature = "s3")]
+        if let Some(s3) = ctx.data_opt::<qm_s3::S3>() {
+            let key = format!("user-exports/{}.{}", Uuid::new_v4(), format.extension());
+            let len = content.len() as u64;
+            s3.put_object(&key, content, len, Some(format.content_type()))
+                .await?;
+            let url = s3.presign_get(&key, user_import::DOWNLOAD_URL_EXPIRY)?;
+            return Ok(QmUserExport {
+                format,
+                count,
+                url: Some(url.to_string()),
+                content: None,
+            });
+        }
+        Ok(QmUserExport {
+            format,
+            count,
+            url: None,
+            content: Some(content),
+        })
+    }
+
+    /// Pending invitation of the given token, does not require a session.
+    async fn invitation(
+        &self,
+        ctx: &Context<'_>,
+        token: String,
+    ) -> async_graphql::FieldResult<Option<Invitation>> {
+        let store = ctx.data_unchecked::<Store>();
+        Ok(Invitation::by_token(store.redis(), &token).await?)
+    }
+
+    /// Progress and result of a user import.
+    async fn user_import(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> async_graphql::FieldResult<Option<UserImport>> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::create()),
+        )
+        .await?;
+        let Some(import) = UserImport::by_id(auth_ctx.store.as_ref(), &id).await? else {
+            return Ok(None);
+        };
+        let context = import
+            .context
+            .as_deref()
+            .and_then(|v| v.parse::<InfraContext>().ok());
+        auth_ctx.can_mutate(context.as_ref()).await.extend()?;
+        Ok(Some(import))
+    }
 }
 
 pub struct UserMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
@@ -530,8 +980,9 @@ where
         access_level: AccessLevel,
         group_id: Option<String>,
         input: QmCreateUserInput,
-        context: Option<InfraContext>,
+        context: Option<ContextFilter>,
     ) -> async_graphql::FieldResult<Arc<QmUser>> {
+        let context = ContextFilter::into_context(context);
         let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
             ctx,
             &qm_role::role!(Resource::user(), Permission::create()),
@@ -540,70 +991,14 @@ where
         if !SchemaConfig::new(ctx).allow_multiple_admin_users() && access_level.is_admin() {
             return err!(not_allowed("creating multiple admin users").extend());
         }
+        let ctx_user = Ctx(&auth_ctx);
         if let Some(group_id) = group_id.as_ref() {
-            let group = auth_ctx
-                .store
-                .cache_db()
-                .group_detail_by_id(group_id)
-                .await
-                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
-                .extend()?;
-            if group
-                .allowed_access_levels
-                .as_ref()
-                .map(|lvls| !lvls.iter().any(|l| l == &access_level))
-                .unwrap_or(false)
-            {
-                return err!(not_allowed("invalid access level for selected group").extend());
-            }
-
-            let group_roles = auth_ctx
-                .store
-                .cache_db()
-                .roles_by_group_id(group_id)
-                .await
-                .ok_or(EntityError::not_found_by_id::<Group>(group_id))
-                .extend()?;
-
-            for role in group_roles.iter() {
-                if let Ok(role) =
-                    qm_role::Role::<Resource, Permission>::from_str(role.name.as_ref())
-                {
-                    if role.ty.is_admin() {
-                        return err!(not_allowed("invalid group selected").extend());
-                    }
-                    if !auth_ctx.is_admin && !auth_ctx.auth.has_role_object(&role) {
-                        return err!(not_allowed("invalid group selected").extend());
-                    }
-                } else {
-                    return err!(internal().extend());
-                }
-            }
+            ctx_user
+                .check_group(group_id, &access_level, context.as_ref())
+                .await?;
         }
-        let access = if let Some(context) = context.as_ref() {
-            let access = Access::new(access_level).with_fmt_id(Some(&context));
-            if !auth_ctx.auth.has_access(&access) {
-                return err!(unauthorized(&auth_ctx.auth).extend());
-            }
-            access
-        } else {
-            let own_access_level_id = auth_ctx
-                .auth
-                .session_access()
-                .ok_or(EntityError::unauthorized(&auth_ctx.auth))?;
-            if own_access_level_id.id().is_some() {
-                return err!(unauthorized(&auth_ctx.auth).extend());
-            }
-            if access_level.id_required() {
-                return err!(bad_request(
-                    "InfraContext",
-                    "'context' is required for specified access level"
-                )
-                .extend());
-            }
-            Access::new(access_level)
-        };
-        Ctx(&auth_ctx)
+        let access = ctx_user.access(access_level, context.as_ref())?;
+        ctx_user
             .create(CreateUserPayload {
                 access: Some(access.to_string()),
                 user: input,
@@ -614,6 +1009,281 @@ where
             .extend()
     }
 
+    /// Creates a disabled user and sends an invitation email with a link that
+    /// expires after the configured invitation ttl.
+    async fn invite_user(
+        &self,
+        ctx: &Context<'_>,
+        access_level: AccessLevel,
+        group_id: Option<String>,
+        input: QmInviteUserInput,
+        context: Option<ContextFilter>,
+    ) -> async_graphql::FieldResult<Invitation> {
+        let context = ContextFilter::into_context(context);
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::create()),
+        )
+        .await?;
+        if !SchemaConfig::new(ctx).allow_multiple_admin_users() && access_level.is_admin() {
+            return err!(not_allowed("creating multiple admin users").extend());
+        }
+        let ctx_user = Ctx(&auth_ctx);
+        if let Some(group_id) = group_id.as_ref() {
+            ctx_user
+                .check_group(group_id, &access_level, context.as_ref())
+                .await?;
+        }
+        let access = ctx_user.access(access_level, context.as_ref())?;
+        ctx_user
+            .invite(
+                &SchemaConfig::new(ctx),
+                CreateUserPayload {
+                    access: Some(access.to_string()),
+                    user: input.into(),
+                    group_id,
+                    context,
+                },
+            )
+            .await
+    }
+
+    /// Sends a new invitation link, previous links stop working.
+    async fn resend_invitation(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<Invitation> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::create()),
+        )
+        .await?;
+        let user_id = user_id.to_string();
+        let details = auth_ctx
+            .store
+            .cache_db()
+            .user_details_by_id(&user_id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<QmUser>(&user_id))
+            .extend()?;
+        auth_ctx
+            .can_mutate(details.context.as_ref())
+            .await
+            .extend()?;
+        let ctx_user = Ctx(&auth_ctx);
+        if !ctx_user.is_invitation_pending(&user_id).await? {
+            return err!(bad_request("QmInvitation", "invitation was already accepted").extend());
+        }
+        let config = SchemaConfig::new(ctx);
+        let redis = auth_ctx.store.redis();
+        let invitation = match Invitation::by_user_id(redis, &user_id).await? {
+            Some(mut invitation) => {
+                invitation.remove(redis).await?;
+                invitation.renew(config.invitation_ttl());
+                invitation
+            }
+            None => Invitation::new(
+                user_id.as_str(),
+                details.user.email.as_ref(),
+                details.context.map(|c| c.to_string()),
+                auth_ctx.auth.user_id(),
+                config.invitation_ttl(),
+            ),
+        };
+        ctx_user.send_invitation(&config, &invitation).await?;
+        invitation.save(redis).await?;
+        Ok(invitation)
+    }
+
+    /// Removes the invitation and the invited user if the invitation was not
+    /// accepted yet, returns true if the user was removed.
+    async fn revoke_invitation(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<bool> {
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::delete()),
+        )
+        .await?;
+        let user_id = user_id.to_string();
+        let details = auth_ctx
+            .store
+            .cache_db()
+            .user_details_by_id(&user_id)
+            .await
+            .ok_or(EntityError::not_found_by_id::<QmUser>(&user_id))
+            .extend()?;
+        auth_ctx
+            .can_mutate(details.context.as_ref())
+            .await
+            .extend()?;
+        let redis = auth_ctx.store.redis();
+        if let Some(invitation) = Invitation::by_user_id(redis, &user_id).await? {
+            invitation.remove(redis).await?;
+        }
+        let ctx_user = Ctx(&auth_ctx);
+        if !ctx_user.is_invitation_pending(&user_id).await? {
+            return Ok(false);
+        }
+        Ok(ctx_user
+            .remove(Arc::from([Arc::from(user_id)]))
+            .await
+            .extend()?
+            > 0)
+    }
+
+    /// Validates the users in `payload` and creates them in the cleanup worker,
+    /// the progress is available with `userImport`.
+    #[allow(clippy::too_many_arguments)]
+    async fn import_users(
+        &self,
+        ctx: &Context<'_>,
+        format: UserFileFormat,
+        payload: String,
+        access_level: AccessLevel,
+        group: Option<BuiltInGroup>,
+        context: Option<ContextFilter>,
+        duplicates: Option<DuplicateHandling>,
+    ) -> async_graphql::FieldResult<UserImport> {
+        let context = ContextFilter::into_context(context);
+        let duplicates = duplicates.unwrap_or_default();
+        let auth_ctx = AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+            ctx,
+            &qm_role::role!(Resource::user(), Permission::create()),
+        )
+        .await?;
+        if access_level.is_admin() {
+            return err!(not_allowed("importing admin users").extend());
+        }
+        let ctx_user = Ctx(&auth_ctx);
+        let access = ctx_user.access(access_level, context.as_ref())?;
+        let rows = user_import::parse(format, &payload)
+            .map_err(|err| EntityError::bad_request("QmUserImport", err.to_string()))
+            .extend()?;
+        let default_group = group.as_ref().map(|g| g.as_ref().to_string());
+        let mut items = Vec::with_capacity(rows.len());
+        let mut group_paths = Vec::with_capacity(rows.len());
+        for (i, mut row) in rows.into_iter().enumerate() {
+            let group = match row.group.take() {
+                Some(name) => BuiltInGroup::from_str(&name)
+                    .or_else(|_| BuiltInGroup::from_str(&format!("/app/{name}")))
+                    .map(|g| g.as_ref().to_string())
+                    .map_err(|_| format!("unknown group '{name}'")),
+                None => Ok(default_group.clone().unwrap_or_default()),
+            };
+            items.push(UserImportItem::new(i, row));
+            group_paths.push(group);
+        }
+        if user_import::skip_duplicates(&mut items) > 0 && duplicates == DuplicateHandling::Reject {
+            return err!(bad_request("QmUserImport", "duplicate users in file").extend());
+        }
+        let cache = auth_ctx.store.cache_db();
+        let mut groups: HashMap<String, Result<String, String>> = HashMap::new();
+        for (item, group_path) in items.iter_mut().zip(group_paths) {
+            if !item.is_pending() {
+                continue;
+            }
+            let mut conflict_fields = vec![];
+            if cache.user_by_username(&item.username).await.is_some() {
+                conflict_fields.push("username");
+            }
+            if cache.user_by_email(&item.email).await.is_some() {
+                conflict_fields.push("email");
+            }
+            if !conflict_fields.is_empty() {
+                if duplicates == DuplicateHandling::Reject {
+                    return err!(fields_conflict::<QmUser>(
+                        item.username.as_str(),
+                        &conflict_fields[..]
+                    )
+                    .extend());
+                }
+                item.skip("user already exists");
+                continue;
+            }
+            let path = match group_path {
+                Ok(path) if path.is_empty() => continue,
+                Ok(path) => path,
+                Err(err) => {
+                    item.fail(err);
+                    continue;
+                }
+            };
+            if !groups.contains_key(&path) {
+                let group_id = match cache.group_id_by_path(&path).await {
+                    Some(group_id) => ctx_user
+                        .check_group(&group_id, &access_level, context.as_ref())
+                        .await
+                        .map(|_| group_id)
+                        .map_err(|err| err.message),
+                    None => Err(format!("group '{path}' not found")),
+                };
+                groups.insert(path.clone(), group_id);
+            }
+            match &groups[&path] {
+                Ok(group_id) => item.group_id = Some(group_id.clone()),
+                Err(err) => item.fail(err.clone()),
+            }
+        }
+        let mut import = UserImport::new(
+            items,
+            access.to_string(),
+            context.as_ref(),
+            auth_ctx.auth.user_id(),
+        );
+        if import.processed == import.total {
+            import.status = UserImportStatus::Completed;
+        }
+        import.save(auth_ctx.store.as_ref()).await?;
+        if import.status == UserImportStatus::Pending {
+            crate::cleanup_status::enqueue(
+                auth_ctx.store,
+                &CleanupTask::new(CleanupTaskType::UserImport(import.id.clone())),
+            )
+            .await?;
+        }
+        Ok(import)
+    }
+
+    /// Ends all sessions of the user, returns the number of terminated
+    /// sessions.
+    async fn terminate_user_sessions(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<u64> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::user(), Permission::update()),
+            )
+            .await?,
+        )
+        .terminate_sessions(&user_id.to_string())
+        .await
+    }
+
+    /// Unlocks a user locked out after too many failed logins, returns
+    /// `false` if the user wasn't locked.
+    async fn unlock_user(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Uuid,
+    ) -> async_graphql::FieldResult<bool> {
+        Ctx(
+            &AuthCtx::<'_, Auth, Store, Resource, Permission>::new_with_role(
+                ctx,
+                &qm_role::role!(Resource::user(), Permission::update()),
+            )
+            .await?,
+        )
+        .unlock(&user_id.to_string())
+        .await
+    }
+
     async fn update_user(
         &self,
         _ctx: &Context<'_>,
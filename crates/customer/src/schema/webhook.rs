@@ -0,0 +1,327 @@
+//! Webhook endpoints of customers, organizations and institutions, see
+//! [`crate::webhook`] for the delivery.
+
+use async_graphql::{Context, FieldResult, InputObject, Object, ResultExt, SimpleObject};
+use chrono::Utc;
+use qm_entity::err;
+use qm_entity::error::EntityError;
+use qm_entity::ids::{ContextFilter, InfraContext};
+use qm_entity::model::ListFilter;
+use qm_mongodb::bson::{doc, Document};
+
+use crate::cache::events::visible;
+use crate::config::SchemaConfig;
+use crate::context::{RelatedAuth, RelatedPermission, RelatedResource, RelatedStorage};
+use crate::groups::RelatedBuiltInGroup;
+use crate::marker::Marker;
+use crate::schema::api_client::role;
+use crate::schema::auth::AuthCtx;
+use crate::webhook::{
+    self, check_url, generate_secret, is_valid_filter, is_valid_url, QmWebhookDeliveryList,
+    Webhook, WebhookDeliveryStatus,
+};
+
+#[derive(Debug, InputObject)]
+pub struct QmCreateWebhookInput {
+    pub context: Option<ContextFilter>,
+    pub url: String,
+    /// Event names like `user.create` or `user.*`, all events if empty.
+    #[graphql(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, InputObject)]
+pub struct QmUpdateWebhookInput {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmWebhookSecret {
+    pub webhook: Webhook,
+    /// Key of the HMAC-SHA256 signature sent with every callback.
+    pub secret: String,
+}
+
+async fn validate(
+    url: Option<&str>,
+    events: Option<&[String]>,
+    allowed_hosts: &[String],
+) -> FieldResult<()> {
+    if url.is_some_and(|url| !is_valid_url(url)) {
+        return err!(bad_request(
+            "QmWebhook",
+            "url must be an absolute http(s) URL"
+        ))
+        .extend();
+    }
+    if let Some(url) = url {
+        if let Err(reason) = check_url(url, allowed_hosts).await {
+            return err!(bad_request(
+                "QmWebhook",
+                format!("url must point to a public address: {reason}")
+            ))
+            .extend();
+        }
+    }
+    if let Some(filter) = events.and_then(|events| events.iter().find(|f| !is_valid_filter(f))) {
+        return err!(bad_request(
+            "QmWebhook",
+            format!("invalid event filter '{filter}'")
+        ))
+        .extend();
+    }
+    Ok(())
+}
+
+pub struct Ctx<'a, Auth, Store, Resource, Permission>(
+    pub &'a AuthCtx<'a, Auth, Store, Resource, Permission>,
+)
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission;
+
+impl<'a, Auth, Store, Resource, Permission> Ctx<'a, Auth, Store, Resource, Permission>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    fn db(&self) -> &qm_mongodb::DB {
+        self.0.store.as_ref()
+    }
+
+    fn allowed(&self, context: &InfraContext, permission: Permission) -> bool {
+        self.0.is_admin
+            || self
+                .0
+                .auth
+                .satisfies(&role::<Resource, Permission>(context, permission))
+    }
+
+    pub async fn list(&self, context: Option<InfraContext>) -> FieldResult<Vec<Webhook>> {
+        let scope = self.0.enforce_current_context(context).await.extend()?;
+        Ok(Webhook::list(self.db(), Document::new())
+            .await?
+            .into_iter()
+            .filter(|webhook| {
+                webhook.infra_context().is_some_and(|context| {
+                    visible(scope.as_ref(), Some(&context))
+                        && self.allowed(&context, Permission::view())
+                })
+            })
+            .collect())
+    }
+
+    /// Returns the webhook if the session may access it with `permission`.
+    async fn webhook(&self, id: &str, permission: Permission) -> FieldResult<Webhook> {
+        let webhook = Webhook::by_id(self.db(), id)
+            .await?
+            .ok_or(EntityError::not_found_by_id::<Webhook>(id))
+            .extend()?;
+        let context = webhook
+            .infra_context()
+            .ok_or(EntityError::not_found_by_id::<Webhook>(id))
+            .extend()?;
+        if !self.allowed(&context, permission) {
+            return err!(unauthorized(&self.0.auth)).extend();
+        }
+        self.0.can_mutate(Some(&context)).await.extend()?;
+        Ok(webhook)
+    }
+
+    pub async fn create(
+        &self,
+        input: QmCreateWebhookInput,
+        allowed_hosts: &[String],
+    ) -> FieldResult<QmWebhookSecret> {
+        validate(Some(&input.url), Some(&input.events), allowed_hosts).await?;
+        let context = self
+            .0
+            .enforce_current_context(ContextFilter::into_context(input.context))
+            .await
+            .extend()?
+            .ok_or(EntityError::bad_request("QmWebhook", "context is required"))
+            .extend()?;
+        if !self.allowed(&context, Permission::update()) {
+            return err!(unauthorized(&self.0.auth)).extend();
+        }
+        self.0.can_mutate(Some(&context)).await.extend()?;
+        let secret = generate_secret();
+        let webhook = Webhook::new(
+            &context,
+            input.url,
+            secret.clone(),
+            input.events,
+            self.0.auth.user_id(),
+        );
+        webhook.save(self.db()).await?;
+        Ok(QmWebhookSecret { webhook, secret })
+    }
+
+    pub async fn update(
+        &self,
+        id: &str,
+        input: QmUpdateWebhookInput,
+        allowed_hosts: &[String],
+    ) -> FieldResult<Webhook> {
+        validate(input.url.as_deref(), input.events.as_deref(), allowed_hosts).await?;
+        let mut webhook = self.webhook(id, Permission::update()).await?;
+        if let Some(url) = input.url {
+            webhook.url = url;
+        }
+        if let Some(events) = input.events {
+            webhook.events = events;
+        }
+        if let Some(enabled) = input.enabled {
+            webhook.enabled = enabled;
+        }
+        webhook.updated_at = Utc::now();
+        webhook.save(self.db()).await?;
+        Ok(webhook)
+    }
+
+    pub async fn rotate_secret(&self, id: &str) -> FieldResult<QmWebhookSecret> {
+        let mut webhook = self.webhook(id, Permission::update()).await?;
+        let secret = generate_secret();
+        webhook.secret.clone_from(&secret);
+        webhook.updated_at = Utc::now();
+        webhook.save(self.db()).await?;
+        Ok(QmWebhookSecret { webhook, secret })
+    }
+
+    pub async fn remove(&self, id: &str) -> FieldResult<bool> {
+        self.webhook(id, Permission::update()).await?;
+        Ok(Webhook::remove(self.db(), id).await?)
+    }
+
+    pub async fn deliveries(
+        &self,
+        id: &str,
+        filter: Option<ListFilter>,
+        status: Option<WebhookDeliveryStatus>,
+    ) -> FieldResult<QmWebhookDeliveryList> {
+        self.webhook(id, Permission::view()).await?;
+        let mut query = doc! { "webhookId": id };
+        if let Some(status) = status {
+            query.insert("status", qm_mongodb::bson::to_bson(&status)?);
+        }
+        Ok(webhook::deliveries(self.db(), query, filter).await?)
+    }
+}
+
+pub struct WebhookQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for WebhookQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    WebhookQueryRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    async fn webhooks(
+        &self,
+        ctx: &Context<'_>,
+        context: Option<ContextFilter>,
+    ) -> FieldResult<Vec<Webhook>> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .list(ContextFilter::into_context(context))
+            .await
+    }
+
+    /// Delivery log of the webhook, latest deliveries first.
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook_id: String,
+        filter: Option<ListFilter>,
+        status: Option<WebhookDeliveryStatus>,
+    ) -> FieldResult<QmWebhookDeliveryList> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .deliveries(&webhook_id, filter, status)
+            .await
+    }
+}
+
+pub struct WebhookMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup> {
+    _marker: Marker<Auth, Store, Resource, Permission, BuiltInGroup>,
+}
+
+impl<Auth, Store, Resource, Permission, BuiltInGroup> Default
+    for WebhookMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+{
+    fn default() -> Self {
+        Self {
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+#[Object]
+impl<Auth, Store, Resource, Permission, BuiltInGroup>
+    WebhookMutationRoot<Auth, Store, Resource, Permission, BuiltInGroup>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+    BuiltInGroup: RelatedBuiltInGroup,
+{
+    /// The secret is only returned here and by `rotateWebhookSecret`.
+    async fn create_webhook(
+        &self,
+        ctx: &Context<'_>,
+        input: QmCreateWebhookInput,
+    ) -> FieldResult<QmWebhookSecret> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .create(input, SchemaConfig::new(ctx).webhook_allowed_hosts())
+            .await
+    }
+
+    async fn update_webhook(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        input: QmUpdateWebhookInput,
+    ) -> FieldResult<Webhook> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .update(&id, input, SchemaConfig::new(ctx).webhook_allowed_hosts())
+            .await
+    }
+
+    /// Generates a new secret, the previous secret stops working immediately.
+    async fn rotate_webhook_secret(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> FieldResult<QmWebhookSecret> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .rotate_secret(&id)
+            .await
+    }
+
+    async fn remove_webhook(&self, ctx: &Context<'_>, id: String) -> FieldResult<bool> {
+        Ctx(&AuthCtx::<'_, Auth, Store, Resource, Permission>::new(ctx).await?)
+            .remove(&id)
+            .await
+    }
+}
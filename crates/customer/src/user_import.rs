@@ -0,0 +1,611 @@
+//! Bulk import and export of users.
+//!
+//! Imports are validated when submitted and processed by the cleanup worker,
+//! the progress is stored in the [`USER_IMPORT_COLLECTION`] collection of the
+//! object database. CSV files have a header line.
+
+use std::collections::HashSet;
+
+use async_graphql::{ComplexObject, Context, Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use qm_entity::ids::InfraContext;
+use qm_mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
+use crate::context::RelatedStorage;
+use crate::model::{CreateUserPayload, QmCreateUserInput, QmRequiredUserAction, QmUser};
+use crate::schema::user::insert_user;
+
+pub const USER_IMPORT_COLLECTION: &str = "qm_user_imports";
+#[cfg(feature = "s3")]
+pub const DOWNLOAD_URL_EXPIRY: std::time::Duration = std::time::Duration::from_secs(3600);
+const PROGRESS_INTERVAL: usize = 10;
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmUserFileFormat")]
+pub enum UserFileFormat {
+    Csv,
+    Json,
+}
+
+impl UserFileFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv",
+            Self::Json => "application/json",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Enum, PartialEq, Eq)]
+#[graphql(name = "QmDuplicateUserHandling")]
+pub enum DuplicateHandling {
+    /// Skip rows with a username or email that is already taken.
+    #[default]
+    Skip,
+    /// Reject the whole import if any username or email is already taken.
+    Reject,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserImportRow {
+    pub username: String,
+    pub email: String,
+    pub firstname: String,
+    pub lastname: String,
+    /// Name or path of a built-in group.
+    #[serde(default)]
+    pub group: Option<String>,
+}
+
+const COLUMNS: [&str; 5] = ["username", "email", "firstname", "lastname", "group"];
+
+pub fn parse(format: UserFileFormat, payload: &str) -> anyhow::Result<Vec<UserImportRow>> {
+    match format {
+        UserFileFormat::Json => Ok(serde_json::from_str(payload)?),
+        UserFileFormat::Csv => parse_csv(payload),
+    }
+}
+
+fn parse_csv(payload: &str) -> anyhow::Result<Vec<UserImportRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(payload.as_bytes());
+    let header = reader.headers()?.clone();
+    let position = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let mut columns = [None; 5];
+    for (i, name) in COLUMNS.iter().enumerate() {
+        columns[i] = position(name);
+        if columns[i].is_none() && *name != "group" {
+            anyhow::bail!("missing column '{name}'");
+        }
+    }
+    reader
+        .records()
+        .map(|record| {
+            let record = record?;
+            let field = |c: Option<usize>| c.and_then(|c| record.get(c)).map(str::to_string);
+            Ok(UserImportRow {
+                username: field(columns[0]).unwrap_or_default(),
+                email: field(columns[1]).unwrap_or_default(),
+                firstname: field(columns[2]).unwrap_or_default(),
+                lastname: field(columns[3]).unwrap_or_default(),
+                group: field(columns[4]).filter(|v| !v.is_empty()),
+            })
+        })
+        .collect()
+}
+
+fn write_csv<'a, R>(records: impl IntoIterator<Item = R>) -> anyhow::Result<String>
+where
+    R: IntoIterator<Item = &'a str>,
+{
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for record in records {
+        writer.write_record(record)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// Returns a reason if the row can not be imported.
+pub fn validate(row: &UserImportRow) -> Option<&'static str> {
+    if row.username.is_empty() || row.username.contains(char::is_whitespace) {
+        return Some("invalid username");
+    }
+    match row.email.split_once('@') {
+        Some((local, domain))
+            if !local.is_empty() && domain.contains('.') && !domain.contains('@') => {}
+        _ => return Some("invalid email"),
+    }
+    if row.firstname.is_empty() || row.lastname.is_empty() {
+        return Some("firstname and lastname are required");
+    }
+    None
+}
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmUserImportStatus")]
+pub enum UserImportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmUserImportRowStatus")]
+pub enum UserImportRowStatus {
+    Pending,
+    Created,
+    Skipped,
+    Failed,
+}
+
+impl UserImportRowStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Created => "created",
+            Self::Skipped => "skipped",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "QmUserImportRow")]
+#[serde(rename_all = "camelCase")]
+pub struct UserImportItem {
+    /// Position of the row in the imported file, starting at 1.
+    pub row: i64,
+    pub username: String,
+    pub email: String,
+    pub firstname: String,
+    pub lastname: String,
+    #[graphql(skip)]
+    pub group_id: Option<String>,
+    pub status: UserImportRowStatus,
+    pub message: Option<String>,
+    pub user_id: Option<String>,
+}
+
+impl UserImportItem {
+    pub fn new(row: usize, value: UserImportRow) -> Self {
+        let message = validate(&value);
+        Self {
+            row: row as i64 + 1,
+            username: value.username,
+            email: value.email,
+            firstname: value.firstname,
+            lastname: value.lastname,
+            group_id: None,
+            status: if message.is_some() {
+                UserImportRowStatus::Failed
+            } else {
+                UserImportRowStatus::Pending
+            },
+            message: message.map(str::to_string),
+            user_id: None,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == UserImportRowStatus::Pending
+    }
+
+    pub fn skip(&mut self, message: impl Into<String>) {
+        self.status = UserImportRowStatus::Skipped;
+        self.message = Some(message.into());
+    }
+
+    pub fn fail(&mut self, message: impl Into<String>) {
+        self.status = UserImportRowStatus::Failed;
+        self.message = Some(message.into());
+    }
+}
+
+/// Skips pending rows repeating the username or email of a previous row and
+/// returns the number of skipped rows.
+pub fn skip_duplicates(items: &mut [UserImportItem]) -> usize {
+    let mut usernames = HashSet::new();
+    let mut emails = HashSet::new();
+    let mut skipped = 0;
+    for item in items.iter_mut().filter(|item| item.is_pending()) {
+        let username = usernames.insert(item.username.to_lowercase());
+        let email = emails.insert(item.email.to_lowercase());
+        if !username || !email {
+            item.skip("duplicate in file");
+            skipped += 1;
+        }
+    }
+    skipped
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "QmUserImport", complex)]
+#[serde(rename_all = "camelCase")]
+pub struct UserImport {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub status: UserImportStatus,
+    #[graphql(skip)]
+    pub context: Option<String>,
+    #[graphql(skip)]
+    pub access: String,
+    #[graphql(skip)]
+    pub created_by: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    pub total: i64,
+    pub processed: i64,
+    pub created: i64,
+    pub skipped: i64,
+    pub failed: i64,
+    pub items: Vec<UserImportItem>,
+    #[graphql(skip)]
+    pub result_key: Option<String>,
+}
+
+#[ComplexObject]
+impl UserImport {
+    /// Download url of the result file once the import completed, requires
+    /// an S3 client in the schema data.
+    async fn result_url(&self, ctx: &Context<'_>) -> Option<String> {
+        #[cfg(feature = "s3")]
+        {
+            let s3 = ctx.data_opt::<qm_s3::S3>()?;
+            let key = self.result_key.as_deref()?;
+            s3.presign_get(key, DOWNLOAD_URL_EXPIRY)
+                .map(|url| url.to_string())
+                .ok()
+        }
+        #[cfg(not(feature = "s3"))]
+        {
+            let _ = ctx;
+            None
+        }
+    }
+}
+
+impl UserImport {
+    pub fn new(
+        items: Vec<UserImportItem>,
+        access: String,
+        context: Option<&InfraContext>,
+        created_by: Option<&impl ToString>,
+    ) -> Self {
+        let mut result = Self {
+            id: Uuid::new_v4().to_string(),
+            status: UserImportStatus::Pending,
+            context: context.map(ToString::to_string),
+            access,
+            created_by: created_by.map(ToString::to_string),
+            created_at: Utc::now(),
+            total: items.len() as i64,
+            processed: 0,
+            created: 0,
+            skipped: 0,
+            failed: 0,
+            items,
+            result_key: None,
+        };
+        result.count();
+        result
+    }
+
+    fn count(&mut self) {
+        let count = |status| self.items.iter().filter(|v| v.status == status).count() as i64;
+        self.created = count(UserImportRowStatus::Created);
+        self.skipped = count(UserImportRowStatus::Skipped);
+        self.failed = count(UserImportRowStatus::Failed);
+        self.processed = self.total - count(UserImportRowStatus::Pending);
+    }
+
+    pub async fn by_id(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<Option<Self>> {
+        db.get()
+            .collection::<Self>(USER_IMPORT_COLLECTION)
+            .find_one(doc! { "_id": id })
+            .await
+    }
+
+    pub async fn save(&mut self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        self.count();
+        db.get()
+            .collection::<Self>(USER_IMPORT_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, &*self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// CSV file with the outcome of every row.
+    pub fn result_csv(&self) -> anyhow::Result<String> {
+        let rows: Vec<String> = self.items.iter().map(|item| item.row.to_string()).collect();
+        write_csv(
+            std::iter::once(vec![
+                "row", "username", "email", "status", "message", "userId",
+            ])
+            .chain(self.items.iter().zip(rows.iter()).map(|(item, row)| {
+                vec![
+                    row.as_str(),
+                    &item.username,
+                    &item.email,
+                    item.status.as_str(),
+                    item.message.as_deref().unwrap_or_default(),
+                    item.user_id.as_deref().unwrap_or_default(),
+                ]
+            })),
+        )
+    }
+}
+
+/// Creates the pending users of the import with the given id, used by the
+/// cleanup worker. The import is marked as failed if it can not be finished.
+pub async fn run<Store>(
+    store: &Store,
+    #[cfg(feature = "s3")] s3: Option<&qm_s3::S3>,
+    id: &str,
+) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    let db: &qm_mongodb::DB = store.as_ref();
+    let Some(mut import) = UserImport::by_id(db, id).await? else {
+        tracing::warn!("user import with id '{id}' not found");
+        return Ok(());
+    };
+    let result = run_import(
+        store,
+        #[cfg(feature = "s3")]
+        s3,
+        &mut import,
+    )
+    .await;
+    if result.is_err() {
+        import.status = UserImportStatus::Failed;
+        if let Err(err) = import.save(db).await {
+            tracing::error!("unable to record failure of user import '{id}': {err:#}");
+        }
+    }
+    result
+}
+
+async fn run_import<Store>(
+    store: &Store,
+    #[cfg(feature = "s3")] s3: Option<&qm_s3::S3>,
+    import: &mut UserImport,
+) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    let db: &qm_mongodb::DB = store.as_ref();
+    import.status = UserImportStatus::Running;
+    import.save(db).await?;
+    let context = import
+        .context
+        .as_deref()
+        .map(str::parse::<InfraContext>)
+        .transpose()?;
+    let mut processed = 0;
+    for i in 0..import.items.len() {
+        if !import.items[i].is_pending() {
+            continue;
+        }
+        let item = &import.items[i];
+        let payload = CreateUserPayload {
+            user: QmCreateUserInput {
+                username: item.username.clone(),
+                firstname: item.firstname.clone(),
+                lastname: item.lastname.clone(),
+                password: Uuid::new_v4().to_string(),
+                email: item.email.clone(),
+                phone: None,
+                salutation: None,
+                room_number: None,
+                job_title: None,
+                enabled: Some(true),
+                required_actions: Some(vec![
+                    QmRequiredUserAction::VerifyEmail,
+                    QmRequiredUserAction::UpdatePassword,
+                ]),
+            },
+            group_id: item.group_id.clone(),
+            access: Some(import.access.clone()),
+            context,
+        };
+        match insert_user(store, payload).await {
+            Ok(user) => {
+                QmAuditEntry::new(AuditEntity::User, AuditAction::Create, &user.id)
+                    .with_actor(import.created_by.as_ref())
+                    .with_context(context)
+                    .with_diff(None::<&QmUser>, Some(user.as_ref()))
+                    .record(db)
+                    .await;
+                let item = &mut import.items[i];
+                item.status = UserImportRowStatus::Created;
+                item.user_id = Some(user.id.to_string());
+            }
+            Err(err) => {
+                tracing::warn!("unable to import user '{}': {}", item.username, err.message);
+                import.items[i].fail(err.message);
+            }
+        }
+        processed += 1;
+        if processed % PROGRESS_INTERVAL == 0 {
+            import.save(db).await?;
+        }
+    }
+    #[cfg(feature = "s3")]
+    if let Some(s3) = s3 {
+        let key = format!("user-imports/{}.csv", import.id);
+        let body = import.result_csv()?;
+        let len = body.len() as u64;
+        match s3.put_object(&key, body, len, Some("text/csv")).await {
+            Ok(()) => import.result_key = Some(key),
+            Err(err) => tracing::error!("unable to upload user import result: {err}"),
+        }
+    }
+    import.status = UserImportStatus::Completed;
+    import.save(db).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserExportRow<'a> {
+    pub username: &'a str,
+    pub email: &'a str,
+    pub firstname: &'a str,
+    pub lastname: &'a str,
+    /// Path of the built-in group.
+    pub group: Option<String>,
+    pub enabled: bool,
+    pub access: Option<String>,
+}
+
+pub fn render(format: UserFileFormat, rows: &[UserExportRow<'_>]) -> anyhow::Result<String> {
+    match format {
+        UserFileFormat::Json => Ok(serde_json::to_string(rows)?),
+        UserFileFormat::Csv => write_csv(
+            std::iter::once(vec![
+                "username",
+                "email",
+                "firstname",
+                "lastname",
+                "group",
+                "enabled",
+                "access",
+            ])
+            .chain(rows.iter().map(|row| {
+                vec![
+                    row.username,
+                    row.email,
+                    row.firstname,
+                    row.lastname,
+                    row.group.as_deref().unwrap_or_default(),
+                    if row.enabled { "true" } else { "false" },
+                    row.access.as_deref().unwrap_or_default(),
+                ]
+            })),
+        ),
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmUserExport {
+    pub format: UserFileFormat,
+    pub count: i64,
+    /// Presigned download url, set if an S3 client is in the schema data.
+    pub url: Option<String>,
+    /// File content, set if no S3 client is available.
+    pub content: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(username: &str, email: &str) -> UserImportRow {
+        UserImportRow {
+            username: username.into(),
+            email: email.into(),
+            firstname: "Max".into(),
+            lastname: "Mustermann".into(),
+            group: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let payload = "Email,Username,Firstname,Lastname,Group\n\
+            max@example.com,max,Max,\"Muster, \"\"Mann\"\"\",owner\n\
+            \n\
+            eva@example.com,eva,Eva,Muster,\n";
+        let rows = parse(UserFileFormat::Csv, payload).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].username, "max");
+        assert_eq!(rows[0].lastname, "Muster, \"Mann\"");
+        assert_eq!(rows[0].group.as_deref(), Some("owner"));
+        assert_eq!(rows[1].group, None);
+        assert!(parse(UserFileFormat::Csv, "username,email\nmax,max@example.com").is_err());
+        assert!(parse(
+            UserFileFormat::Csv,
+            "username,email,firstname,lastname\nmax,max@example.com,Max"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let payload =
+            r#"[{"username":"max","email":"max@example.com","firstname":"Max","lastname":"M"}]"#;
+        let rows = parse(UserFileFormat::Json, payload).unwrap();
+        assert_eq!(rows[0].email, "max@example.com");
+        assert_eq!(rows[0].group, None);
+    }
+
+    #[test]
+    fn test_validate_and_duplicates() {
+        assert_eq!(validate(&row("max", "max@example.com")), None);
+        assert_eq!(
+            validate(&row("m ax", "max@example.com")),
+            Some("invalid username")
+        );
+        assert_eq!(validate(&row("max", "max@example")), Some("invalid email"));
+        let mut items: Vec<UserImportItem> = [
+            row("max", "max@example.com"),
+            row("MAX", "other@example.com"),
+            row("eva", "eva@example"),
+            row("eva", "eva@example.com"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| UserImportItem::new(i, r))
+        .collect();
+        assert_eq!(skip_duplicates(&mut items), 1);
+        let status: Vec<_> = items.iter().map(|v| v.status).collect();
+        assert_eq!(
+            status,
+            [
+                UserImportRowStatus::Pending,
+                UserImportRowStatus::Skipped,
+                UserImportRowStatus::Failed,
+                UserImportRowStatus::Pending,
+            ]
+        );
+        let import = UserImport::new(items, "access".into(), None, None::<&String>);
+        assert_eq!((import.total, import.processed), (4, 2));
+        assert!(import.result_csv().unwrap().starts_with(
+            "row,username,email,status,message,userId\n1,max,max@example.com,pending,,\n"
+        ));
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let rows = [UserExportRow {
+            username: "max",
+            email: "max@example.com",
+            firstname: "Max",
+            lastname: "Muster, M",
+            group: Some("/app/owner".into()),
+            enabled: true,
+            access: None,
+        }];
+        let csv = render(UserFileFormat::Csv, &rows).unwrap();
+        assert_eq!(
+            csv,
+            "username,email,firstname,lastname,group,enabled,access\n\
+             max,max@example.com,Max,\"Muster, M\",/app/owner,true,\n"
+        );
+        let parsed = parse(UserFileFormat::Csv, &csv).unwrap();
+        assert_eq!(parsed[0].lastname, "Muster, M");
+    }
+}
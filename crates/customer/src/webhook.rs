@@ -0,0 +1,765 @@
+//! Webhooks notifying tenants about mutations.
+//!
+//! Audit entries are published as [`WebhookEvent`] to a Redis work queue, the
+//! webhook worker delivers them to the matching webhooks of the
+//! [`WEBHOOK_COLLECTION`] collection as signed HTTP callbacks and logs every
+//! delivery in the [`WEBHOOK_DELIVERY_COLLECTION`] collection.
+//!
+//! Callbacks are only sent to public addresses. The host of a webhook is
+//! resolved when the webhook is saved and again by the [`WebhookClient`]
+//! when it connects, so a DNS record changed in between can't point the
+//! callbacks to loopback, link-local or internal addresses. Redirects are
+//! not followed. Hosts in `webhook_allowed_hosts` are exempt, e.g. for
+//! receivers inside the cluster.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::{Enum, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::stream::TryStreamExt;
+use hmac::{Hmac, Mac};
+use qm_entity::ids::InfraContext;
+use qm_entity::model::ListFilter;
+use qm_mongodb::bson::{doc, Document};
+use qm_mongodb::options::FindOptions;
+use qm_mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::types::Uuid;
+
+use crate::audit::{AuditAction, AuditEntity, QmAuditChange, QmAuditEntry};
+use crate::cache::events::visible;
+use crate::config::Config;
+use crate::context::RelatedStorage;
+
+pub const WEBHOOK_COLLECTION: &str = "qm_webhooks";
+pub const WEBHOOK_DELIVERY_COLLECTION: &str = "qm_webhook_deliveries";
+pub const SIGNATURE_HEADER: &str = "X-Qm-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Qm-Timestamp";
+pub const EVENT_HEADER: &str = "X-Qm-Event";
+pub const DELIVERY_HEADER: &str = "X-Qm-Delivery";
+
+lazy_static::lazy_static! {
+    pub static ref PREFIX: String = {
+        std::env::var("CUSTOMER_WEBHOOK_EVENT_PREFIX").unwrap_or("webhook_events".to_string())
+    };
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[graphql(name = "QmWebhook")]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub context: String,
+    pub url: String,
+    #[graphql(skip)]
+    pub secret: String,
+    /// Names of the delivered events like `user.create` or `user.*`, all
+    /// events are delivered if empty.
+    pub events: Vec<String>,
+    pub enabled: bool,
+    pub created_by: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    pub fn new(
+        context: &InfraContext,
+        url: String,
+        secret: String,
+        events: Vec<String>,
+        created_by: Option<&impl ToString>,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            id: Uuid::new_v4().to_string(),
+            context: context.to_string(),
+            url,
+            secret,
+            events,
+            enabled: true,
+            created_by: created_by.map(ToString::to_string),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn infra_context(&self) -> Option<InfraContext> {
+        InfraContext::parse(&self.context).ok()
+    }
+
+    /// True if the webhook is enabled, `event` happened in its context and
+    /// passes its event filter.
+    pub fn matches(&self, event: &WebhookEvent) -> bool {
+        let context = self.infra_context();
+        let event_context = event.context.as_deref().and_then(|c| c.parse().ok());
+        self.enabled
+            && context.is_some()
+            && visible(context.as_ref(), event_context.as_ref())
+            && (self.events.is_empty()
+                || self.events.iter().any(|filter| {
+                    filter == &event.name
+                        || filter
+                            .strip_suffix(".*")
+                            .is_some_and(|entity| event.name.split('.').next() == Some(entity))
+                }))
+    }
+
+    pub async fn by_id(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<Option<Self>> {
+        db.get()
+            .collection::<Self>(WEBHOOK_COLLECTION)
+            .find_one(doc! { "_id": id })
+            .await
+    }
+
+    pub async fn list(
+        db: &qm_mongodb::DB,
+        query: Document,
+    ) -> qm_mongodb::error::Result<Vec<Self>> {
+        db.get()
+            .collection::<Self>(WEBHOOK_COLLECTION)
+            .find(query)
+            .sort(doc! { "createdAt": 1 })
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// Creates the index used by [`Webhook::query`], existing indexes are
+    /// kept.
+    pub async fn ensure_indexes(db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(WEBHOOK_COLLECTION)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "enabled": 1, "context": 1, "events": 1 })
+                    .build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Query for the enabled webhooks which may match `event`, webhooks of
+    /// the event context and its parents with a filter for the event name,
+    /// its entity or without filter. `None` if no webhook can match.
+    pub fn query(event: &WebhookEvent) -> Option<Document> {
+        let context: InfraContext = event.context.as_deref()?.parse().ok()?;
+        let mut contexts = vec![InfraContext::Customer(match &context {
+            InfraContext::Customer(v) => *v,
+            InfraContext::Organization(v) => v.root(),
+            InfraContext::Institution(v) => v.root(),
+            InfraContext::OrganizationUnit(v) => v.root(),
+        })];
+        contexts.extend(match &context {
+            InfraContext::Customer(_) => None,
+            InfraContext::Organization(v) => Some(InfraContext::Organization(*v)),
+            InfraContext::Institution(v) => Some(InfraContext::Organization(v.parent())),
+            InfraContext::OrganizationUnit(v) => v.organization().map(InfraContext::Organization),
+        });
+        if !contexts.contains(&context) {
+            contexts.push(context);
+        }
+        let contexts: Vec<String> = contexts.iter().map(ToString::to_string).collect();
+        let entity = event.name.split('.').next().unwrap_or_default();
+        Some(doc! {
+            "enabled": true,
+            "context": { "$in": contexts },
+            "$or": [
+                { "events": { "$size": 0 } },
+                { "events": { "$in": [&event.name, format!("{entity}.*")] } },
+            ],
+        })
+    }
+
+    pub async fn save(&self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(WEBHOOK_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    /// Removes the webhook and its delivery log.
+    pub async fn remove(db: &qm_mongodb::DB, id: &str) -> qm_mongodb::error::Result<bool> {
+        let result = db
+            .get()
+            .collection::<Self>(WEBHOOK_COLLECTION)
+            .delete_one(doc! { "_id": id })
+            .await?;
+        db.get()
+            .collection::<QmWebhookDelivery>(WEBHOOK_DELIVERY_COLLECTION)
+            .delete_many(doc! { "webhookId": id })
+            .await?;
+        Ok(result.deleted_count > 0)
+    }
+}
+
+const ENTITIES: [&str; 5] = ["customer", "organization", "institution", "user", "group"];
+const ACTIONS: [&str; 3] = ["create", "update", "remove"];
+
+/// True if `filter` is an event name or `<entity>.*`.
+pub fn is_valid_filter(filter: &str) -> bool {
+    filter.split_once('.').is_some_and(|(entity, action)| {
+        ENTITIES.contains(&entity) && (action == "*" || ACTIONS.contains(&action))
+    })
+}
+
+/// True for absolute http and https URLs.
+pub fn is_valid_url(url: &str) -> bool {
+    reqwest::Url::parse(url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// True for addresses reachable on the public internet, false for
+/// loopback, private, link-local, unique local, shared, multicast and
+/// unspecified addresses.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ipv4(ip),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // shared address space of carrier grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // reserved, 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // site-local, fec0::/10
+        || (first & 0xffc0) == 0xfec0
+        // documentation, 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+fn is_allowed_host(allowed_hosts: &[String], host: &str) -> bool {
+    allowed_hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+}
+
+async fn resolve_public(host: &str, port: u16) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|err| format!("unable to resolve '{host}': {err}"))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("unable to resolve '{host}'"));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!(
+            "'{host}' resolves to the non-public address {}",
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Checks that `url` is valid and all addresses of its host are public,
+/// unless the host is in `allowed_hosts`.
+pub async fn check_url(url: &str, allowed_hosts: &[String]) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|err| err.to_string())?;
+    if !is_valid_url(url) {
+        return Err("url must be an absolute http(s) URL".to_string());
+    }
+    let host = parsed
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+        .unwrap_or_default();
+    if is_allowed_host(allowed_hosts, host) {
+        return Ok(());
+    }
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    resolve_public(host, port).await.map(|_| ())
+}
+
+/// Resolver of the [`WebhookClient`] which fails for hosts with non-public
+/// addresses, checked on every connect.
+struct PublicResolver {
+    allowed_hosts: Arc<[String]>,
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let host = name.as_str().to_string();
+        let allowed = is_allowed_host(&self.allowed_hosts, &host);
+        Box::pin(async move {
+            let addrs = if allowed {
+                tokio::net::lookup_host((host.as_str(), 0))
+                    .await?
+                    .collect::<Vec<_>>()
+            } else {
+                resolve_public(&host, 0).await?
+            };
+            let addrs: reqwest::dns::Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// HTTP client of the webhook deliveries, which only connects to public
+/// addresses and doesn't follow redirects.
+#[derive(Clone)]
+pub struct WebhookClient {
+    client: reqwest::Client,
+    allowed_hosts: Arc<[String]>,
+}
+
+impl WebhookClient {
+    pub fn new(config: &Config) -> reqwest::Result<Self> {
+        Self::with_allowed_hosts(config.webhook_allowed_hosts().to_vec())
+    }
+
+    pub fn with_allowed_hosts(allowed_hosts: Vec<String>) -> reqwest::Result<Self> {
+        let allowed_hosts: Arc<[String]> = allowed_hosts.into();
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .dns_resolver(Arc::new(PublicResolver {
+                allowed_hosts: allowed_hosts.clone(),
+            }))
+            .build()?;
+        Ok(Self {
+            client,
+            allowed_hosts,
+        })
+    }
+
+    pub fn allowed_hosts(&self) -> &[String] {
+        &self.allowed_hosts
+    }
+}
+
+/// Random secret used to sign the callbacks of a webhook.
+pub fn generate_secret() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Mutation delivered to webhooks, the name is `<entity>.<action>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookEvent {
+    pub id: String,
+    pub name: String,
+    pub entity: AuditEntity,
+    pub entity_id: String,
+    pub action: AuditAction,
+    pub actor: Option<String>,
+    pub context: Option<String>,
+    pub changes: Vec<QmAuditChange>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<&QmAuditEntry> for WebhookEvent {
+    fn from(entry: &QmAuditEntry) -> Self {
+        let entity = match entry.entity {
+            AuditEntity::Customer => "customer",
+            AuditEntity::Organization => "organization",
+            AuditEntity::Institution => "institution",
+            AuditEntity::User => "user",
+            AuditEntity::Group => "group",
+        };
+        let action = match entry.action {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Remove => "remove",
+        };
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: format!("{entity}.{action}"),
+            entity: entry.entity,
+            entity_id: entry.entity_id.clone(),
+            action: entry.action,
+            actor: entry.actor.clone(),
+            context: entry.context.clone(),
+            changes: entry.changes.clone(),
+            created_at: entry.created_at,
+        }
+    }
+}
+
+/// Adds the event of `entry` to the webhook queue, failures are logged and
+/// don't fail the mutation.
+pub async fn publish<Store>(store: &Store, entry: &QmAuditEntry)
+where
+    Store: RelatedStorage,
+{
+    let event = WebhookEvent::from(entry);
+    let producer = qm_redis::Producer::new_with_client(store.redis().pool(), PREFIX.as_str());
+    if let Err(err) = producer.add_item(&event).await {
+        tracing::error!("unable to publish webhook event '{}': {err:#}", event.name);
+    }
+}
+
+/// Hex encoded HMAC-SHA256 of `<timestamp>.<body>` with the webhook secret,
+/// sent as `sha256=<signature>` in the [`SIGNATURE_HEADER`].
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff: Duration,
+    pub timeout: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            max_attempts: config.webhook_max_attempts().max(1),
+            backoff: Duration::from_millis(config.webhook_backoff_ms()),
+            timeout: Duration::from_secs(config.webhook_timeout()),
+        }
+    }
+
+    /// Delay before retrying after `attempt` failed, doubles per attempt and
+    /// is capped at one minute.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(Duration::from_secs(60))
+    }
+
+    /// Upper bound of the time needed to deliver an event to one webhook.
+    pub fn max_duration(&self) -> Duration {
+        (1..self.max_attempts)
+            .map(|attempt| self.delay(attempt))
+            .sum::<Duration>()
+            + self.timeout * self.max_attempts
+    }
+}
+
+#[derive(Debug, Clone, Copy, Enum, PartialEq, Eq, Serialize, Deserialize)]
+#[graphql(name = "QmWebhookDeliveryStatus")]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookDeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, SimpleObject, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QmWebhookDelivery {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub webhook_id: String,
+    pub event_id: String,
+    pub event: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "qm_mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl QmWebhookDelivery {
+    pub fn new(webhook: &Webhook, event: &WebhookEvent) -> Self {
+        let now = Utc::now();
+        Self {
+            id: format!("{}:{}", webhook.id, event.id),
+            webhook_id: webhook.id.clone(),
+            event_id: event.id.clone(),
+            event: event.name.clone(),
+            status: WebhookDeliveryStatus::Pending,
+            attempts: 0,
+            response_status: None,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub async fn save(&self, db: &qm_mongodb::DB) -> qm_mongodb::error::Result<()> {
+        db.get()
+            .collection::<Self>(WEBHOOK_DELIVERY_COLLECTION)
+            .replace_one(doc! { "_id": &self.id }, self)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct QmWebhookDeliveryList {
+    pub items: Vec<QmWebhookDelivery>,
+    pub limit: Option<i64>,
+    pub total: Option<i64>,
+    pub page: Option<i64>,
+}
+
+pub async fn deliveries(
+    db: &qm_mongodb::DB,
+    query: Document,
+    filter: Option<ListFilter>,
+) -> qm_mongodb::error::Result<QmWebhookDeliveryList> {
+    let collection = db
+        .get()
+        .collection::<QmWebhookDelivery>(WEBHOOK_DELIVERY_COLLECTION);
+    let limit = filter.as_ref().and_then(|f| f.limit).unwrap_or(100) as i64;
+    let page = filter.as_ref().and_then(|f| f.page).unwrap_or(0) as i64;
+    let total = collection.count_documents(query.clone()).await?;
+    let options = FindOptions::builder()
+        .sort(doc! { "createdAt": -1 })
+        .limit(limit)
+        .skip((page * limit) as u64)
+        .build();
+    let items = collection
+        .find(query)
+        .with_options(options)
+        .await?
+        .try_collect()
+        .await?;
+    Ok(QmWebhookDeliveryList {
+        items,
+        limit: Some(limit),
+        total: Some(total as i64),
+        page: Some(page),
+    })
+}
+
+async fn attempt(
+    client: &WebhookClient,
+    webhook: &Webhook,
+    event: &WebhookEvent,
+    body: &[u8],
+    timeout: Duration,
+) -> Result<u16, (Option<u16>, String)> {
+    // IP literals don't pass the resolver of the client
+    check_url(&webhook.url, client.allowed_hosts())
+        .await
+        .map_err(|err| (None, err))?;
+    let timestamp = Utc::now().timestamp();
+    let response = client
+        .client
+        .post(&webhook.url)
+        .timeout(timeout)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &event.name)
+        .header(DELIVERY_HEADER, &event.id)
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(
+            SIGNATURE_HEADER,
+            format!("sha256={}", sign(&webhook.secret, timestamp, body)),
+        )
+        .body(body.to_vec())
+        .send()
+        .await
+        .map_err(|err| (None, err.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err((Some(status.as_u16()), format!("unexpected status {status}")))
+    }
+}
+
+/// Delivers `event` to `webhook`, failed attempts are retried with
+/// exponential backoff and every attempt is recorded in the delivery log.
+pub async fn deliver(
+    db: &qm_mongodb::DB,
+    client: &WebhookClient,
+    policy: &RetryPolicy,
+    webhook: &Webhook,
+    event: &WebhookEvent,
+) -> anyhow::Result<QmWebhookDelivery> {
+    let body = serde_json::to_vec(event)?;
+    let mut delivery = QmWebhookDelivery::new(webhook, event);
+    delivery.save(db).await?;
+    loop {
+        delivery.attempts += 1;
+        let result = attempt(client, webhook, event, &body, policy.timeout).await;
+        delivery.updated_at = Utc::now();
+        match result {
+            Ok(status) => {
+                delivery.status = WebhookDeliveryStatus::Delivered;
+                delivery.response_status = Some(status);
+                delivery.error = None;
+            }
+            Err((status, error)) => {
+                delivery.response_status = status;
+                delivery.error = Some(error);
+                if delivery.attempts >= policy.max_attempts {
+                    delivery.status = WebhookDeliveryStatus::Failed;
+                }
+            }
+        }
+        delivery.save(db).await?;
+        if delivery.status != WebhookDeliveryStatus::Pending {
+            return Ok(delivery);
+        }
+        tokio::time::sleep(policy.delay(delivery.attempts)).await;
+    }
+}
+
+/// Delivers `event` to all matching webhooks, including the retries. Called
+/// by the webhook worker, mutations only [`publish`] the event.
+pub async fn dispatch(
+    db: &qm_mongodb::DB,
+    client: &WebhookClient,
+    policy: &RetryPolicy,
+    event: &WebhookEvent,
+) -> anyhow::Result<()> {
+    let Some(query) = Webhook::query(event) else {
+        return Ok(());
+    };
+    let webhooks = Webhook::list(db, query).await?;
+    let results = futures::future::join_all(
+        webhooks
+            .iter()
+            .filter(|webhook| webhook.matches(event))
+            .map(|webhook| deliver(db, client, policy, webhook, event)),
+    )
+    .await;
+    for result in results {
+        if let Err(err) = result {
+            tracing::error!("unable to deliver webhook event '{}': {err:#}", event.id);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qm_entity::ids::{CustomerId, InstitutionId};
+
+    #[test]
+    fn test_matches() {
+        let customer = InfraContext::Customer(CustomerId::from(1i64));
+        let institution = InfraContext::Institution(InstitutionId::from((1i64, 2i64, 3i64)));
+        let entry = QmAuditEntry::new(AuditEntity::User, AuditAction::Create, "u1")
+            .with_context(Some(institution));
+        let event = WebhookEvent::from(&entry);
+        assert_eq!(event.name, "user.create");
+        let mut webhook = Webhook::new(
+            &customer,
+            "https://example.com".to_string(),
+            generate_secret(),
+            vec![],
+            None::<&String>,
+        );
+        assert!(webhook.matches(&event));
+        webhook.events = vec!["user.*".to_string()];
+        assert!(webhook.matches(&event));
+        webhook.events = vec!["user.remove".to_string(), "customer.*".to_string()];
+        assert!(!webhook.matches(&event));
+        webhook.events = vec![];
+        webhook.context = InfraContext::Customer(CustomerId::from(2i64)).to_string();
+        assert!(!webhook.matches(&event));
+        assert!(is_valid_filter("group.*"));
+        assert!(!is_valid_filter("group"));
+        assert!(!is_valid_filter("users.create"));
+        assert!(is_valid_url("https://example.com/hook"));
+        assert!(!is_valid_url("ftp://example.com"));
+    }
+
+    #[test]
+    fn test_query() {
+        let institution = InstitutionId::from((1i64, 2i64, 3i64));
+        let entry = QmAuditEntry::new(AuditEntity::User, AuditAction::Create, "u1")
+            .with_context(Some(InfraContext::Institution(institution)));
+        let query = Webhook::query(&WebhookEvent::from(&entry)).unwrap();
+        let contexts: Vec<String> = [
+            InfraContext::Customer(institution.root()),
+            InfraContext::Organization(institution.parent()),
+            InfraContext::Institution(institution),
+        ]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        assert_eq!(
+            query,
+            doc! {
+                "enabled": true,
+                "context": { "$in": contexts },
+                "$or": [
+                    { "events": { "$size": 0 } },
+                    { "events": { "$in": ["user.create", "user.*"] } },
+                ],
+            }
+        );
+        let entry = QmAuditEntry::new(AuditEntity::User, AuditAction::Create, "u1");
+        assert!(Webhook::query(&WebhookEvent::from(&entry)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "169.254.169.254",
+            "100.64.0.1",
+            "::1",
+            "fd00::1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(!is_public_ip("::ffff:192.168.0.1".parse().unwrap()));
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(is_public_ip("2606:4700::1111".parse().unwrap()));
+
+        assert!(check_url("http://169.254.169.254/latest", &[])
+            .await
+            .is_err());
+        assert!(check_url("http://[::1]:8080/hook", &[]).await.is_err());
+        assert!(check_url("http://localhost/hook", &[]).await.is_err());
+        assert!(check_url("http://93.184.216.34/hook", &[]).await.is_ok());
+        let allowed = vec!["LOCALHOST".to_string()];
+        assert!(check_url("http://localhost/hook", &allowed).await.is_ok());
+    }
+
+    #[test]
+    fn test_sign_and_retry() {
+        assert_eq!(
+            sign("secret", 1, b"{}"),
+            "1122767b193110cfec322b6f199b599edbf608ed087f2d27afb0b97d99523908"
+        );
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            backoff: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+        assert_eq!(policy.delay(10), Duration::from_secs(60));
+        assert_eq!(policy.max_duration(), Duration::from_secs(47));
+    }
+}
@@ -1,18 +1,25 @@
+use crate::audit::{AuditAction, AuditEntity, QmAuditEntry};
 use crate::cleanup::cleanup_api_clients;
 use crate::cleanup::cleanup_roles;
 use crate::cleanup::CleanupTaskType;
+use crate::cleanup_status::{CleanupTaskStatus, QmCleanupTask};
+use crate::config::Config;
 use crate::context::RelatedAuth;
 use crate::context::RelatedPermission;
 use crate::context::RelatedResource;
 use crate::context::RelatedStorage;
+use crate::custom_groups::{CustomGroup, CUSTOM_GROUP_COLLECTION};
 use crate::marker::Marker;
+use crate::merge::{merged_context, MergeProgress, MergeStep, QmMergeReport};
+use crate::webhook::{RetryPolicy, WebhookClient, WebhookEvent};
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 use std::sync::Arc;
 
 use crate::cleanup::CleanupTask;
 use qm_entity::ids::CustomerId;
 use qm_entity::ids::CustomerIds;
+use qm_entity::ids::InfraContext;
 
 use qm_entity::ids::InstitutionId;
 use qm_entity::ids::InstitutionIds;
@@ -20,9 +27,12 @@ use qm_entity::ids::InstitutionIds;
 use qm_entity::ids::OrganizationId;
 use qm_entity::ids::OrganizationIds;
 
+use futures::stream::TryStreamExt;
 use qm_entity::ids::INSTITUTION_ID_PREFIX;
 use qm_entity::ids::ORGANIZATION_ID_PREFIX;
 use qm_kafka::producer::EventNs;
+use qm_keycloak::GroupRepresentation;
+use qm_keycloak::KeycloakError;
 use qm_mongodb::bson::doc;
 
 use qm_mongodb::bson::Document;
@@ -68,6 +78,8 @@ impl AsRef<Producer> for CleanupProducer {
 
 pub struct CleanupWorkerCtx<Auth, Store, Resource, Permission> {
     pub store: Store,
+    #[cfg(feature = "s3")]
+    pub s3: Option<qm_s3::S3>,
     _marker: Marker<Auth, Store, Resource, Permission, ()>,
 }
 
@@ -75,9 +87,18 @@ impl<Auth, Store, Resource, Permission> CleanupWorkerCtx<Auth, Store, Resource,
     pub fn new(store: Store) -> Self {
         Self {
             store,
+            #[cfg(feature = "s3")]
+            s3: None,
             _marker: std::marker::PhantomData,
         }
     }
+
+    /// Stores the results of user imports in S3.
+    #[cfg(feature = "s3")]
+    pub fn with_s3(mut self, s3: qm_s3::S3) -> Self {
+        self.s3 = Some(s3);
+        self
+    }
 }
 
 impl<Auth, Store, Resource, Permission> Clone
@@ -88,6 +109,8 @@ where
     fn clone(&self) -> Self {
         Self {
             store: self.store.clone(),
+            #[cfg(feature = "s3")]
+            s3: self.s3.clone(),
             _marker: self._marker,
         }
     }
@@ -108,6 +131,22 @@ async fn remove_documents(
     Ok(result.deleted_count)
 }
 
+async fn update_documents(
+    db: &DB,
+    session: &mut ClientSession,
+    collection: &str,
+    query: &Document,
+    update: &Document,
+) -> anyhow::Result<u64> {
+    let result = db
+        .get()
+        .collection::<Document>(collection)
+        .update_many(query.clone(), update.clone())
+        .session(session)
+        .await?;
+    Ok(result.modified_count)
+}
+
 async fn cleanup_customers<Auth, Store, Resource, Permission>(
     worker_ctx: WorkerContext<CleanupWorkerCtx<Auth, Store, Resource, Permission>>,
     ty: &str,
@@ -322,6 +361,329 @@ where
     Ok(())
 }
 
+/// Rewrites the owner references of the resources of a moved institution and
+/// migrates its users from the previous access role.
+async fn move_institution<Auth, Store, Resource, Permission>(
+    worker_ctx: WorkerContext<CleanupWorkerCtx<Auth, Store, Resource, Permission>>,
+    ty: &str,
+    id: Uuid,
+    from: &InstitutionId,
+    to: &InstitutionId,
+) -> anyhow::Result<()>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let store: &Store = &worker_ctx.ctx().store;
+    let db = store.as_ref();
+    let mut session = db.session().await?;
+    let (cid, oid, iid) = from.unzip();
+    let query = doc! {
+        "owner.cid": cid,
+        "owner.oid": oid,
+        "owner.iid": iid,
+    };
+    let update = doc! {
+        "$set": {
+            "owner.oid": to.parent().id()
+        }
+    };
+    for collection in db
+        .get()
+        .list_collection_names()
+        .session(&mut session)
+        .await?
+    {
+        tracing::debug!("move institution related resources in db {collection}");
+        update_documents(db, &mut session, &collection, &query, &update).await?;
+    }
+    let old_role = qm_role::Access::new(AccessLevel::Institution)
+        .with_fmt_id(Some(from))
+        .to_string();
+    let new_role = qm_role::Access::new(AccessLevel::Institution)
+        .with_fmt_id(Some(to))
+        .to_string();
+    crate::roles::migrate(store.keycloak(), &old_role, &new_role).await?;
+    worker_ctx.complete().await?;
+    tracing::debug!("finished task '{ty}' with id '{id}'");
+    Ok(())
+}
+
+/// Moves the documents of `source` in its tenant database to the database of
+/// `target`, documents are upserted before they are removed from the source.
+async fn move_tenant_documents(
+    db: &DB,
+    source: &CustomerId,
+    target: &CustomerId,
+) -> anyhow::Result<()> {
+    if !db.tenants().is_enabled() {
+        return Ok(());
+    }
+    let (source_tenant, target_tenant) = (source.to_string(), target.to_string());
+    db.provision_tenant(&target_tenant).await?;
+    let collections: Vec<String> = db.tenants().collections().map(String::from).collect();
+    for collection in collections {
+        let from = db.collection_for::<Document>(Some(&source_tenant), &collection);
+        let to = db.collection_for::<Document>(Some(&target_tenant), &collection);
+        tracing::debug!("move customer related resources in tenant collection {collection}");
+        let mut cursor = from.find(doc! {}).await?;
+        while let Some(mut document) = cursor.try_next().await? {
+            let Some(id) = document.get("_id").cloned() else {
+                continue;
+            };
+            if let Ok(owner) = document.get_document_mut("owner") {
+                if owner.get_i64("cid").ok() == Some(source.unzip()) {
+                    owner.insert("cid", target.unzip());
+                }
+            }
+            to.replace_one(doc! { "_id": &id }, &document)
+                .upsert(true)
+                .await?;
+            from.delete_one(doc! { "_id": id }).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves the custom groups of the source contexts below the matching
+/// contexts of the target, groups with a name used in the target context get
+/// a new name.
+async fn move_custom_groups<Store>(
+    store: &Store,
+    source: &CustomerId,
+    target: &CustomerId,
+) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    let db: &DB = store.as_ref();
+    let keycloak = store.keycloak();
+    let realm = keycloak.config().realm();
+    let groups: Vec<CustomGroup> = db
+        .get()
+        .collection::<CustomGroup>(CUSTOM_GROUP_COLLECTION)
+        .find(doc! {})
+        .await?
+        .try_collect()
+        .await?;
+    let mut parents = BTreeSet::new();
+    for mut group in groups {
+        let Some(context) = InfraContext::parse(&group.context)
+            .ok()
+            .and_then(|context| merged_context(&context, source, target))
+        else {
+            continue;
+        };
+        let parent_name = format!("custom@{context}");
+        let parent = match keycloak.group_by_path(realm, &parent_name).await {
+            Ok(parent) => parent,
+            Err(KeycloakError::HttpFailure { status: 404, .. }) => {
+                keycloak
+                    .create_group(
+                        realm,
+                        GroupRepresentation {
+                            name: Some(parent_name.clone()),
+                            ..Default::default()
+                        },
+                    )
+                    .await?;
+                keycloak.group_by_path(realm, &parent_name).await?
+            }
+            Err(err) => Err(err)?,
+        };
+        let parent_id = parent
+            .id
+            .ok_or_else(|| anyhow::anyhow!("group '{parent_name}' has no id"))?;
+        let leaf = group
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let mut taken = HashSet::new();
+        let mut name = leaf.clone();
+        while store
+            .cache_db()
+            .group_id_by_path(&format!("/{parent_name}/{name}"))
+            .await
+            .is_some()
+        {
+            taken.insert(name);
+            name = crate::merge::unique_name(&leaf, &taken);
+        }
+        let mut kc_group = keycloak.group_by_id(realm, &group.id).await?;
+        kc_group.name = Some(name.clone());
+        kc_group
+            .attributes
+            .get_or_insert_with(Default::default)
+            .insert("context".to_string(), vec![context.to_string()]);
+        tracing::debug!("move custom group {} to {parent_name}", group.path);
+        keycloak
+            .create_sub_group_with_id(realm, &parent_id, kc_group.clone())
+            .await?;
+        keycloak.update_group(realm, &group.id, kc_group).await?;
+        parents.insert(format!("/custom@{}", group.context));
+        group.context = context.to_string();
+        group.path = format!("/{parent_name}/{name}");
+        group.updated_at = chrono::Utc::now();
+        group.save(db).await?;
+    }
+    for parent in parents {
+        match keycloak.remove_group_by_path(realm, &parent).await {
+            Ok(()) | Err(KeycloakError::HttpFailure { status: 404, .. }) => {}
+            Err(err) => Err(err)?,
+        }
+    }
+    Ok(())
+}
+
+/// Merges `source` into `target` and records the report in the audit log.
+///
+/// The steps are recorded in [`MergeProgress`], a retried task skips the
+/// completed steps. The source customer is removed after everything else is
+/// moved.
+async fn merge_customers<Auth, Store, Resource, Permission>(
+    worker_ctx: WorkerContext<CleanupWorkerCtx<Auth, Store, Resource, Permission>>,
+    ty: &str,
+    id: Uuid,
+    source: &CustomerId,
+    target: &CustomerId,
+    actor: Option<&Uuid>,
+) -> anyhow::Result<()>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    let store: &Store = &worker_ctx.ctx().store;
+    let cache = store.cache_db();
+    let db: &DB = store.as_ref();
+    let pool = store.customer_db().pool();
+    let (source_cid, target_cid) = (source.unzip(), target.unzip());
+    let mut progress = match MergeProgress::by_id(db, &id.to_string()).await? {
+        Some(progress) => progress,
+        None => {
+            let plan =
+                crate::mutation::plan_merge(pool, (*source).into(), (*target).into()).await?;
+            let progress = MergeProgress::new(id.to_string(), *source, *target, plan);
+            progress.save(db).await?;
+            progress
+        }
+    };
+    if !progress.is_completed(MergeStep::MoveInfra) {
+        let merged = crate::mutation::merge_customers(
+            pool,
+            (*source).into(),
+            (*target).into(),
+            &progress.plan,
+        )
+        .await?;
+        for organization in merged.organizations {
+            let new = Arc::new(organization);
+            if let Some(old) = cache.organization_by_id(&new.id).await {
+                cache
+                    .infra()
+                    .update_organization(new.clone(), old.as_ref().into())
+                    .await;
+            }
+        }
+        for institution in merged.institutions {
+            let new = Arc::new(institution);
+            if let Some(old) = cache.institution_by_id(&new.id).await {
+                cache
+                    .infra()
+                    .update_institution(new.clone(), old.as_ref().into())
+                    .await;
+            }
+        }
+        progress.complete(db, MergeStep::MoveInfra).await?;
+    }
+    if !progress.is_completed(MergeStep::MoveDocuments) {
+        let mut session = db.session().await?;
+        let query = doc! {
+            "owner.cid": source_cid,
+        };
+        let update = doc! {
+            "$set": {
+                "owner.cid": target_cid
+            }
+        };
+        for collection in db
+            .get()
+            .list_collection_names()
+            .session(&mut session)
+            .await?
+        {
+            tracing::debug!("move customer related resources in db {collection}");
+            update_documents(db, &mut session, &collection, &query, &update).await?;
+        }
+        progress.complete(db, MergeStep::MoveDocuments).await?;
+    }
+    if !progress.is_completed(MergeStep::MoveTenantDocuments) {
+        move_tenant_documents(db, source, target).await?;
+        progress
+            .complete(db, MergeStep::MoveTenantDocuments)
+            .await?;
+    }
+    if !progress.is_completed(MergeStep::MoveCustomGroups) {
+        move_custom_groups(store, source, target).await?;
+        progress.complete(db, MergeStep::MoveCustomGroups).await?;
+    }
+    if !progress.is_completed(MergeStep::MigrateRoles) {
+        for (old_role, new_role) in progress.roles() {
+            let users = crate::roles::migrate(store.keycloak(), &old_role, &new_role).await?;
+            let mut all: BTreeSet<String> = progress.users.drain(..).collect();
+            all.extend(users);
+            progress.users = all.into_iter().collect();
+            progress.save(db).await?;
+        }
+        progress.complete(db, MergeStep::MigrateRoles).await?;
+    }
+    if !progress.is_completed(MergeStep::CleanupApiClients) {
+        tracing::debug!("cleanup api clients");
+        cleanup_api_clients(store.keycloak(), vec![source.to_string()]).await?;
+        progress.complete(db, MergeStep::CleanupApiClients).await?;
+    }
+    if !progress.is_completed(MergeStep::RemoveSource) {
+        crate::mutation::remove_customers(pool, &[source_cid]).await?;
+        progress.complete(db, MergeStep::RemoveSource).await?;
+    }
+    let report = progress.report();
+    if let Some(producer) = store.mutation_event_producer() {
+        producer
+            .update_event(
+                &EventNs::Organization,
+                "organization",
+                "sys",
+                &report.organizations,
+            )
+            .await?;
+        producer
+            .update_event(
+                &EventNs::Institution,
+                "institution",
+                "sys",
+                &report.institutions,
+            )
+            .await?;
+        producer
+            .delete_event(&EventNs::Customer, "customer", "sys", vec![source_cid])
+            .await?;
+    }
+    let entry = QmAuditEntry::new(AuditEntity::Customer, AuditAction::Update, target)
+        .with_actor(actor)
+        .with_context(Some(InfraContext::Customer(*target)))
+        .with_diff(None::<&QmMergeReport>, Some(&report));
+    crate::webhook::publish(store, &entry).await;
+    entry.record(db).await;
+    worker_ctx.complete().await?;
+    tracing::debug!("finished task '{ty}' with id '{id}'");
+    Ok(())
+}
+
 pub struct CleanupWorker;
 
 #[async_trait::async_trait]
@@ -343,22 +705,120 @@ where
             item.ty.as_ref(),
             item.id
         );
-        match &item.ty {
-            CleanupTaskType::Customers(ids) => {
-                cleanup_customers(ctx, item.ty.as_ref(), item.id, ids).await?;
-            }
-            CleanupTaskType::Organizations(ids) => {
-                cleanup_organizations(ctx, item.ty.as_ref(), item.id, ids).await?;
-            }
-            CleanupTaskType::Institutions(ids) => {
-                cleanup_institutions(ctx, item.ty.as_ref(), item.id, ids).await?;
-            }
-            CleanupTaskType::None => {
-                ctx.complete().await?;
+        let store = ctx.ctx().store.clone();
+        if let Err(err) =
+            QmCleanupTask::set_status(store.as_ref(), &item, CleanupTaskStatus::Running, None).await
+        {
+            tracing::error!(
+                "unable to record status of cleanup task '{}': {err:#}",
+                item.id
+            );
+        }
+        let result = run_task(ctx, &item).await;
+        crate::cleanup_status::finish(&store, &item, &result).await;
+        result
+    }
+}
+
+async fn run_task<Auth, Store, Resource, Permission>(
+    ctx: WorkerContext<CleanupWorkerCtx<Auth, Store, Resource, Permission>>,
+    item: &CleanupTask,
+) -> anyhow::Result<()>
+where
+    Auth: RelatedAuth<Resource, Permission>,
+    Store: RelatedStorage,
+    Resource: RelatedResource,
+    Permission: RelatedPermission,
+{
+    match &item.ty {
+        CleanupTaskType::Customers(ids) => {
+            cleanup_customers(ctx, item.ty.as_ref(), item.id, ids).await?;
+        }
+        CleanupTaskType::MergeCustomers(source, target, actor) => {
+            merge_customers(
+                ctx,
+                item.ty.as_ref(),
+                item.id,
+                source,
+                target,
+                actor.as_ref(),
+            )
+            .await?;
+        }
+        CleanupTaskType::Organizations(ids) => {
+            cleanup_organizations(ctx, item.ty.as_ref(), item.id, ids).await?;
+        }
+        CleanupTaskType::Institutions(ids) => {
+            cleanup_institutions(ctx, item.ty.as_ref(), item.id, ids).await?;
+        }
+        CleanupTaskType::MoveInstitution(from, to) => {
+            move_institution(ctx, item.ty.as_ref(), item.id, from, to).await?;
+        }
+        CleanupTaskType::UserImport(id) => {
+            let worker_ctx = ctx.ctx();
+            crate::user_import::run(
+                &worker_ctx.store,
+                #[cfg(feature = "s3")]
+                worker_ctx.s3.as_ref(),
+                id,
+            )
+            .await?;
+            ctx.complete().await?;
+            tracing::debug!("finished user import task with id '{}'", item.id);
+        }
+        CleanupTaskType::ExpireApiClients => {
+            let disabled =
+                crate::schema::api_client::disable_expired(ctx.ctx().store.keycloak()).await?;
+            ctx.complete().await?;
+            if !disabled.is_empty() {
+                tracing::info!("disabled expired api clients: {}", disabled.join(", "));
             }
         }
-        Ok(())
+        CleanupTaskType::SuspendUsers(context) | CleanupTaskType::ReactivateUsers(context) => {
+            let suspend = matches!(&item.ty, CleanupTaskType::SuspendUsers(_));
+            let context = InfraContext::parse(context)?;
+            let store = &ctx.ctx().store;
+            let count = crate::lifecycle::update_users(
+                store.keycloak(),
+                store.cache_db(),
+                &context,
+                suspend,
+            )
+            .await?;
+            ctx.complete().await?;
+            tracing::debug!(
+                "finished task '{}' with id '{}', updated {count} users of '{context}'",
+                item.ty.as_ref(),
+                item.id
+            );
+        }
+        CleanupTaskType::None => {
+            ctx.complete().await?;
+        }
     }
+    Ok(())
+}
+
+/// Periodically enqueues a task disabling expired API clients, the task is
+/// run by the cleanup worker started with [`run`].
+pub fn schedule_api_client_expiry<Store>(
+    store: Store,
+    interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()>
+where
+    Store: CleanupTaskProducer + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let task = CleanupTask::new(CleanupTaskType::ExpireApiClients);
+            if let Err(err) = store.cleanup_task_producer().add_item(&task).await {
+                tracing::error!("unable to schedule api client expiry: {err:#?}");
+            }
+        }
+    })
 }
 
 pub async fn run<Auth, Store, Resource, Permission>(
@@ -382,3 +842,74 @@ where
         .await?;
     Ok(())
 }
+
+#[derive(Clone)]
+pub struct WebhookWorkerCtx<Store> {
+    pub store: Store,
+    pub client: WebhookClient,
+    pub policy: RetryPolicy,
+}
+
+impl<Store> WebhookWorkerCtx<Store> {
+    pub fn new(store: Store, config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            store,
+            client: WebhookClient::new(config)?,
+            policy: RetryPolicy::new(config),
+        })
+    }
+}
+
+pub struct WebhookWorker;
+
+#[async_trait::async_trait]
+impl<Store> Work<WebhookWorkerCtx<Store>, WebhookEvent> for WebhookWorker
+where
+    Store: RelatedStorage,
+{
+    async fn run(
+        &self,
+        ctx: WorkerContext<WebhookWorkerCtx<Store>>,
+        item: WebhookEvent,
+    ) -> anyhow::Result<()> {
+        tracing::debug!(
+            "dispatch webhook event '{}' with id '{}'",
+            item.name,
+            item.id
+        );
+        let worker_ctx = ctx.ctx();
+        crate::webhook::dispatch(
+            worker_ctx.store.as_ref(),
+            &worker_ctx.client,
+            &worker_ctx.policy,
+            &item,
+        )
+        .await?;
+        ctx.complete().await?;
+        Ok(())
+    }
+}
+
+/// Starts the workers delivering the events published by
+/// [`crate::webhook::publish`], the lease covers all retries of a delivery.
+pub async fn run_webhooks<Store>(
+    workers: &Workers,
+    ctx: WebhookWorkerCtx<Store>,
+    num_workers: usize,
+) -> anyhow::Result<()>
+where
+    Store: RelatedStorage,
+{
+    crate::webhook::Webhook::ensure_indexes(ctx.store.as_ref()).await?;
+    let lease_duration = ctx.policy.max_duration().as_secs() + 60;
+    workers
+        .start(
+            ctx,
+            AsyncWorker::new(crate::webhook::PREFIX.as_str())
+                .with_num_workers(num_workers)
+                .with_lease_duration(lease_duration)
+                .run(WebhookWorker),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use darling::{ast::NestedMeta, FromMeta};
+use inflector::Inflector;
+use quote::{format_ident, quote};
+
+use crate::paths::qm_entity;
+
+#[derive(Debug, Default, FromMeta)]
+struct EntityArgs {
+    #[darling(default)]
+    collection: Option<String>,
+    #[darling(default)]
+    relations: darling::util::PathList,
+}
+
+fn expand_impl(args: EntityArgs, ast: syn::ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    if !ast.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            "#[entity] does not support generic structs",
+        ));
+    }
+    let syn::Fields::Named(fields) = &ast.fields else {
+        return Err(syn::Error::new_spanned(
+            &ast,
+            "#[entity] requires a struct with named fields",
+        ));
+    };
+    for field in fields.named.iter() {
+        let name = field.ident.as_ref().map(ToString::to_string);
+        if matches!(name.as_deref(), Some("id" | "owner")) {
+            return Err(syn::Error::new_spanned(
+                field,
+                "the fields `id` and `owner` are generated by #[entity]",
+            ));
+        }
+    }
+    let e = qm_entity();
+    let attrs = &ast.attrs;
+    let vis = &ast.vis;
+    let ident = &ast.ident;
+    let input_ident = format_ident!("{}Input", ident);
+    let list_ident = format_ident!("{}List", ident);
+    let collection = args
+        .collection
+        .unwrap_or_else(|| ident.to_string().to_snake_case().to_plural());
+
+    let field_names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    let field_types: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+    let field_attrs: Vec<Vec<_>> = fields
+        .named
+        .iter()
+        .map(|f| f.attrs.iter().collect())
+        .collect();
+    let field_docs: Vec<Vec<_>> = fields
+        .named
+        .iter()
+        .map(|f| {
+            f.attrs
+                .iter()
+                .filter(|a| a.path().is_ident("doc"))
+                .collect()
+        })
+        .collect();
+
+    let relations: Vec<_> = args.relations.iter().collect();
+    let relation_fns: Vec<_> = relations
+        .iter()
+        .map(|p| {
+            let name = p
+                .segments
+                .last()
+                .map(|s| s.ident.to_string())
+                .unwrap_or_default();
+            format_ident!("__{}", name.to_snake_case())
+        })
+        .collect();
+    let complex = if relations.is_empty() {
+        quote!()
+    } else {
+        quote!(#[graphql(complex)])
+    };
+    let complex_impl = if relations.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            const _: () = {
+                // required by the expansion of flattened resolvers
+                use async_graphql::OutputType as _;
+
+                #[async_graphql::ComplexObject]
+                impl #ident {
+                    #(
+                        #[graphql(flatten)]
+                        async fn #relation_fns(&self) -> #relations {
+                            #relations(self.id)
+                        }
+                    )*
+                }
+            };
+        }
+    };
+
+    Ok(quote! {
+        #[derive(Debug, Clone, async_graphql::SimpleObject, serde::Serialize, serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        #complex
+        #(#attrs)*
+        #vis struct #ident {
+            #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+            pub id: Option<#e::ids::ObjectId>,
+            #[graphql(skip)]
+            #[serde(default)]
+            pub owner: #e::ids::Owner,
+            #(
+                #(#field_attrs)*
+                pub #field_names: #field_types,
+            )*
+        }
+
+        #[derive(Debug, Clone, async_graphql::InputObject)]
+        #vis struct #input_ident {
+            #(
+                #(#field_docs)*
+                pub #field_names: #field_types,
+            )*
+        }
+
+        #[derive(Debug, Clone, async_graphql::SimpleObject)]
+        #vis struct #list_ident {
+            pub items: Vec<#ident>,
+            pub limit: Option<i64>,
+            pub total: Option<i64>,
+            pub page: Option<i64>,
+        }
+
+        impl #ident {
+            pub fn new(owner: impl Into<#e::ids::Owner>, input: #input_ident) -> Self {
+                Self {
+                    id: None,
+                    owner: owner.into(),
+                    #(#field_names: input.#field_names,)*
+                }
+            }
+
+            pub fn collection(db: &#e::__private::Database) -> #e::Collection<Self> {
+                #e::Collection(
+                    <Self as #e::owned::MongoCollection>::mongo_collection(db),
+                )
+            }
+        }
+
+        #complex_impl
+
+        impl AsRef<Option<#e::ids::ObjectId>> for #ident {
+            fn as_ref(&self) -> &Option<#e::ids::ObjectId> {
+                &self.id
+            }
+        }
+
+        impl AsMut<Option<#e::ids::ObjectId>> for #ident {
+            fn as_mut(&mut self) -> &mut Option<#e::ids::ObjectId> {
+                &mut self.id
+            }
+        }
+
+        impl #e::owned::MongoCollection for #ident {
+            const COLLECTION: &'static str = #collection;
+        }
+
+        impl From<#e::model::ListResult<#ident>> for #list_ident {
+            fn from(value: #e::model::ListResult<#ident>) -> Self {
+                Self {
+                    items: value.items,
+                    limit: value.limit,
+                    total: value.total,
+                    page: value.page,
+                }
+            }
+        }
+    })
+}
+
+pub fn expand(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let args = match NestedMeta::parse_meta_list(args.into()) {
+        Ok(v) => v,
+        Err(e) => return darling::Error::from(e).write_errors().into(),
+    };
+    let args = match EntityArgs::from_list(&args) {
+        Ok(v) => v,
+        Err(e) => return e.write_errors().into(),
+    };
+    let ast = syn::parse_macro_input!(input as syn::ItemStruct);
+    expand_impl(args, ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,8 +1,24 @@
 use proc_macro::TokenStream;
 
+mod entity;
 mod m2m;
+mod member;
 mod o2m;
 mod o2o;
+mod patch;
+mod paths;
+mod relation;
+mod validate;
+
+#[proc_macro_attribute]
+pub fn entity(args: TokenStream, input: TokenStream) -> TokenStream {
+    entity::expand(args, input)
+}
+
+#[proc_macro_attribute]
+pub fn member(args: TokenStream, input: TokenStream) -> TokenStream {
+    member::expand(args, input)
+}
 
 #[proc_macro]
 pub fn m2m(item: TokenStream) -> TokenStream {
@@ -18,3 +34,19 @@ pub fn o2m(item: TokenStream) -> TokenStream {
 pub fn o2o(item: TokenStream) -> TokenStream {
     o2o::expand(item)
 }
+
+/// Implements `qm_entity::patch::Patch` for the type given with
+/// `#[patch(target = "..")]`, fields accept `skip`, `nested` and
+/// `rename = ".."`.
+#[proc_macro_derive(Patch, attributes(patch))]
+pub fn patch(input: TokenStream) -> TokenStream {
+    patch::expand(input)
+}
+
+/// Implements `qm_entity::validate::Validate` from `#[validate(...)]` field
+/// attributes: `length(min = .., max = ..)`, `email`, `regex = ".."`,
+/// `custom = "path::to::fn"` and `nested`.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn validate(input: TokenStream) -> TokenStream {
+    validate::expand(input)
+}
@@ -0,0 +1,51 @@
+use quote::{format_ident, quote};
+
+fn expand_impl(mut ast: syn::ItemStruct) -> syn::Result<proc_macro2::TokenStream> {
+    if !ast.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &ast.generics,
+            "#[member] does not support generic structs",
+        ));
+    }
+    let syn::Fields::Named(fields) = &mut ast.fields else {
+        return Err(syn::Error::new_spanned(
+            &ast,
+            "#[member] requires a struct with named fields",
+        ));
+    };
+    for field in fields.named.iter_mut() {
+        field.vis = syn::parse_quote!(pub);
+    }
+    let input_name = format_ident!("{}Input", ast.ident).to_string();
+    Ok(quote! {
+        #[derive(
+            Debug,
+            Clone,
+            async_graphql::SimpleObject,
+            async_graphql::InputObject,
+            serde::Serialize,
+            serde::Deserialize,
+        )]
+        #[graphql(input_name = #input_name)]
+        #[serde(rename_all = "camelCase")]
+        #ast
+    })
+}
+
+pub fn expand(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    if !args.is_empty() {
+        return syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "#[member] does not take any arguments",
+        )
+        .into_compile_error()
+        .into();
+    }
+    let ast = syn::parse_macro_input!(input as syn::ItemStruct);
+    expand_impl(ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -1,13 +1,5 @@
-use quote::quote;
-
-fn expand_impl(ast: syn::ExprTuple) -> syn::Result<proc_macro2::TokenStream> {
-    eprintln!("TODO: implement O2O, {ast:#?}");
-    Ok(quote!())
-}
+use crate::relation::{self, Kind};
 
 pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let ast = syn::parse_macro_input!(input as syn::ExprTuple);
-    expand_impl(ast)
-        .unwrap_or_else(syn::Error::into_compile_error)
-        .into()
+    relation::expand(Kind::OneToOne, input)
 }
@@ -0,0 +1,84 @@
+use darling::{ast, util::Flag, FromDeriveInput, FromField};
+use inflector::Inflector;
+use quote::quote;
+
+use crate::paths::qm_entity;
+
+#[derive(Debug, FromField)]
+#[darling(attributes(patch))]
+struct PatchField {
+    ident: Option<syn::Ident>,
+    skip: Flag,
+    nested: Flag,
+    #[darling(default)]
+    rename: Option<String>,
+}
+
+#[derive(Debug, FromDeriveInput)]
+#[darling(attributes(patch), supports(struct_named))]
+struct PatchInput {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    data: ast::Data<(), PatchField>,
+    target: syn::Path,
+}
+
+fn expand_field(field: &PatchField) -> Option<proc_macro2::TokenStream> {
+    if field.skip.is_present() {
+        return None;
+    }
+    let e = qm_entity();
+    let ident = field.ident.as_ref().unwrap();
+    let name = field
+        .rename
+        .clone()
+        .unwrap_or_else(|| ident.to_string().to_camel_case());
+    Some(if field.nested.is_present() {
+        quote! {
+            if let Some(patch) = self.#ident {
+                changes.nest(#name, #e::patch::Patch::apply_to(patch, &mut target.#ident));
+            }
+        }
+    } else {
+        quote! {
+            changes.add(
+                #name,
+                #e::patch::PatchField::patch_field(self.#ident, &mut target.#ident),
+            );
+        }
+    })
+}
+
+fn expand_impl(input: PatchInput) -> syn::Result<proc_macro2::TokenStream> {
+    let e = qm_entity();
+    let ident = &input.ident;
+    let target = &input.target;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let fields = input
+        .data
+        .take_struct()
+        .expect("supports(struct_named)")
+        .fields;
+    let patches = fields.iter().filter_map(expand_field);
+    Ok(quote! {
+        impl #impl_generics #e::patch::Patch<#target> for #ident #ty_generics #where_clause {
+            fn apply_to(self, target: &mut #target) -> #e::patch::PatchChanges {
+                #[allow(unused_mut)]
+                let mut changes = #e::patch::PatchChanges::new();
+                #(#patches)*
+                changes
+            }
+        }
+    })
+}
+
+pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    let input = match PatchInput::from_derive_input(&input) {
+        Ok(v) => v,
+        Err(e) => return e.write_errors().into(),
+    };
+    expand_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,22 @@
+use proc_macro2::{Span, TokenStream};
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::quote;
+
+/// Path of the `qm-entity` crate as seen from the calling crate, either used
+/// directly or through the `qm` umbrella crate.
+pub fn qm_entity() -> TokenStream {
+    match crate_name("qm-entity") {
+        Ok(FoundCrate::Itself) => quote!(crate),
+        Ok(FoundCrate::Name(name)) => {
+            let ident = syn::Ident::new(&name, Span::call_site());
+            quote!(::#ident)
+        }
+        Err(_) => match crate_name("qm") {
+            Ok(FoundCrate::Name(name)) => {
+                let ident = syn::Ident::new(&name, Span::call_site());
+                quote!(::#ident::entity)
+            }
+            _ => quote!(::qm_entity),
+        },
+    }
+}
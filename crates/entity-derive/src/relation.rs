@@ -0,0 +1,171 @@
+use inflector::Inflector;
+use quote::{format_ident, quote};
+
+use crate::paths::qm_entity;
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    OneToOne,
+    OneToMany,
+    ManyToMany,
+}
+
+type Entities = syn::punctuated::Punctuated<syn::Path, syn::Token![,]>;
+
+fn last_ident(path: &syn::Path) -> &syn::Ident {
+    &path.segments.last().expect("path has segments").ident
+}
+
+fn expand_impl(kind: Kind, ast: Entities) -> syn::Result<proc_macro2::TokenStream> {
+    if ast.len() != 2 {
+        return Err(syn::Error::new_spanned(
+            &ast,
+            "expected two entity types, e.g. `Appointment, Employee`",
+        ));
+    }
+    let e = qm_entity();
+    let left = &ast[0];
+    let right = &ast[1];
+    let left_ident = last_ident(left);
+    let right_ident = last_ident(right);
+    let left_name = left_ident.to_string();
+    let right_name = right_ident.to_string();
+
+    let link = format_ident!("{left_name}{right_name}Link");
+    let collection = format!(
+        "{}_{}",
+        left_name.to_snake_case(),
+        right_name.to_snake_case().to_plural()
+    );
+    let left_field = format!("{}Id", left_name.to_camel_case());
+    let right_field = format!("{}Id", right_name.to_camel_case());
+    let left_arg = format_ident!("{}_id", left_name.to_snake_case());
+    let right_arg = format_ident!("{}_id", right_name.to_snake_case());
+
+    let (kind_tokens, left_many, right_many) = match kind {
+        Kind::OneToOne => (quote!(OneToOne), false, false),
+        Kind::OneToMany => (quote!(OneToMany), true, false),
+        Kind::ManyToMany => (quote!(ManyToMany), true, true),
+    };
+
+    // resolver object used on the left entity, traverses to the right side
+    let (left_resolver, left_fn, left_ty, left_body) = if left_many {
+        (
+            format_ident!(
+                "{left_name}{}",
+                right_name.to_snake_case().to_plural().to_pascal_case()
+            ),
+            format_ident!("{}", right_name.to_snake_case().to_plural()),
+            quote!(Vec<#right>),
+            quote!(#e::relation::load_rights::<#link>(ctx, self.0.as_ref()).await),
+        )
+    } else {
+        (
+            format_ident!("{left_name}{right_name}"),
+            format_ident!("{}", right_name.to_snake_case()),
+            quote!(Option<#right>),
+            quote!(Ok(#e::relation::load_rights::<#link>(ctx, self.0.as_ref())
+                .await?
+                .into_iter()
+                .next())),
+        )
+    };
+    // resolver object used on the right entity, traverses to the left side
+    let (right_resolver, right_fn, right_ty, right_body) = if right_many {
+        (
+            format_ident!(
+                "{right_name}{}",
+                left_name.to_snake_case().to_plural().to_pascal_case()
+            ),
+            format_ident!("{}", left_name.to_snake_case().to_plural()),
+            quote!(Vec<#left>),
+            quote!(#e::relation::load_lefts::<#link>(ctx, self.0.as_ref()).await),
+        )
+    } else {
+        (
+            format_ident!("{right_name}{left_name}"),
+            format_ident!("{}", left_name.to_snake_case()),
+            quote!(Option<#left>),
+            quote!(Ok(#e::relation::load_lefts::<#link>(ctx, self.0.as_ref())
+                .await?
+                .into_iter()
+                .next())),
+        )
+    };
+
+    Ok(quote! {
+        pub struct #link;
+
+        impl #e::relation::Relation for #link {
+            type Left = #left;
+            type Right = #right;
+            const KIND: #e::relation::RelationKind = #e::relation::RelationKind::#kind_tokens;
+            const COLLECTION: &'static str = #collection;
+            const LEFT_FIELD: &'static str = #left_field;
+            const RIGHT_FIELD: &'static str = #right_field;
+        }
+
+        impl #link {
+            pub async fn add(
+                db: &#e::__private::Database,
+                owner: &#e::ids::Owner,
+                #left_arg: #e::ids::ObjectId,
+                #right_arg: #e::ids::ObjectId,
+            ) -> #e::__private::MongoResult<()> {
+                #e::relation::add::<Self>(db, owner, #left_arg, #right_arg).await
+            }
+
+            pub async fn remove(
+                db: &#e::__private::Database,
+                #left_arg: #e::ids::ObjectId,
+                #right_arg: #e::ids::ObjectId,
+            ) -> #e::__private::MongoResult<u64> {
+                #e::relation::remove::<Self>(db, #left_arg, #right_arg).await
+            }
+
+            pub async fn ensure_indexes(
+                db: &#e::__private::Database,
+            ) -> #e::__private::MongoResult<#e::index::IndexReport> {
+                #e::relation::ensure_indexes::<Self>(db).await
+            }
+
+            pub fn register<Q, M, S>(
+                builder: async_graphql::SchemaBuilder<Q, M, S>,
+                db: &#e::__private::Database,
+            ) -> async_graphql::SchemaBuilder<Q, M, S> {
+                #e::relation::register::<Self, Q, M, S>(builder, db)
+            }
+        }
+
+        pub struct #left_resolver(pub Option<#e::ids::ObjectId>);
+
+        #[async_graphql::Object]
+        impl #left_resolver {
+            async fn #left_fn(
+                &self,
+                ctx: &async_graphql::Context<'_>,
+            ) -> async_graphql::FieldResult<#left_ty> {
+                #left_body
+            }
+        }
+
+        pub struct #right_resolver(pub Option<#e::ids::ObjectId>);
+
+        #[async_graphql::Object]
+        impl #right_resolver {
+            async fn #right_fn(
+                &self,
+                ctx: &async_graphql::Context<'_>,
+            ) -> async_graphql::FieldResult<#right_ty> {
+                #right_body
+            }
+        }
+    })
+}
+
+pub fn expand(kind: Kind, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse_macro_input!(input with Entities::parse_terminated);
+    expand_impl(kind, ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,121 @@
+use darling::{ast, util::Flag, FromDeriveInput, FromField, FromMeta};
+use inflector::Inflector;
+use quote::quote;
+
+use crate::paths::qm_entity;
+
+#[derive(Debug, Default, FromMeta)]
+struct Length {
+    #[darling(default)]
+    min: Option<usize>,
+    #[darling(default)]
+    max: Option<usize>,
+}
+
+#[derive(Debug, FromField)]
+#[darling(attributes(validate))]
+struct ValidateField {
+    ident: Option<syn::Ident>,
+    #[darling(default)]
+    length: Option<Length>,
+    email: Flag,
+    #[darling(default)]
+    regex: Option<syn::LitStr>,
+    #[darling(default, multiple)]
+    custom: Vec<syn::Path>,
+    nested: Flag,
+}
+
+#[derive(Debug, FromDeriveInput)]
+#[darling(supports(struct_named))]
+struct ValidateInput {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    data: ast::Data<(), ValidateField>,
+}
+
+fn expand_field(field: &ValidateField) -> syn::Result<proc_macro2::TokenStream> {
+    let e = qm_entity();
+    let ident = field.ident.as_ref().unwrap();
+    let name = ident.to_string().to_camel_case();
+    let mut checks = Vec::new();
+    if let Some(Length { min, max }) = &field.length {
+        if min.is_none() && max.is_none() {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "length requires `min` or `max`",
+            ));
+        }
+        let min = min.map_or(quote!(None), |v| quote!(Some(#v)));
+        let max = max.map_or(quote!(None), |v| quote!(Some(#v)));
+        checks.push(quote!(#e::validate::length(&self.#ident, #min, #max)));
+    }
+    if field.email.is_present() {
+        checks.push(quote!(#e::validate::email(&self.#ident)));
+    }
+    if let Some(pattern) = &field.regex {
+        if let Err(err) = regex::Regex::new(&pattern.value()) {
+            return Err(syn::Error::new_spanned(pattern, err));
+        }
+        checks.push(quote!({
+            static REGEX: #e::validate::__private::OnceLock<#e::validate::__private::Regex> =
+                #e::validate::__private::OnceLock::new();
+            #e::validate::regex(
+                &self.#ident,
+                REGEX.get_or_init(|| #e::validate::__private::Regex::new(#pattern).unwrap()),
+            )
+        }));
+    }
+    for custom in field.custom.iter() {
+        checks.push(quote!(#custom(&self.#ident)));
+    }
+    let nested = field.nested.is_present().then(|| {
+        quote! {
+            errors.nest(#name, #e::validate::Validate::validate(&self.#ident));
+        }
+    });
+    Ok(quote! {
+        #(
+            if let Err(err) = #checks {
+                errors.add(#name, err);
+            }
+        )*
+        #nested
+    })
+}
+
+fn expand_impl(input: ValidateInput) -> syn::Result<proc_macro2::TokenStream> {
+    let e = qm_entity();
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let fields = input
+        .data
+        .take_struct()
+        .expect("supports(struct_named)")
+        .fields;
+    let checks = fields
+        .iter()
+        .map(expand_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+    Ok(quote! {
+        impl #impl_generics #e::validate::Validate for #ident #ty_generics #where_clause {
+            fn validate(&self) -> ::core::result::Result<(), #e::validate::ValidationErrors> {
+                #[allow(unused_mut)]
+                let mut errors = #e::validate::ValidationErrors::new();
+                #(#checks)*
+                errors.into_result()
+            }
+        }
+    })
+}
+
+pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    let input = match ValidateInput::from_derive_input(&input) {
+        Ok(v) => v,
+        Err(e) => return e.write_errors().into(),
+    };
+    expand_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,305 @@
+//! Encryption of sensitive fields at rest with AES-256-GCM.
+//!
+//! Fields annotated with `#[serde(with = "qm_entity::encrypted")]` are
+//! serialized to JSON, encrypted with the active key of the installed
+//! [`Keyring`] and stored as `enc:<key id>:<base64 nonce and ciphertext>`.
+//! Reading decrypts with the key referenced by the value, so older keys stay
+//! usable after a rotation. Values without the `enc:` prefix are read as
+//! plaintext, which allows encrypting existing collections incrementally.
+//! Plaintext strings which start with `enc:` can't be told apart from
+//! ciphertext and fail to read, such values must be encrypted before the
+//! field is annotated.
+//!
+//! Writing a value other than `None` and reading an encrypted value fail
+//! with [`EncryptionError::MissingKeyring`] until [`set_keyring`] was called,
+//! services using encrypted fields should install the keyring at startup
+//! and refuse to start without keys.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Person {
+//!     #[serde(default, with = "qm_entity::encrypted")]
+//!     confession: Option<String>,
+//! }
+//! ```
+
+use std::{collections::HashMap, sync::Arc, sync::OnceLock};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::engine::{general_purpose::STANDARD, Engine};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+const PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum EncryptionError {
+    #[error("no encryption keyring installed")]
+    MissingKeyring,
+    #[error("encryption key '{0}' is not configured")]
+    UnknownKey(String),
+    #[error("encryption key '{0}' must be 32 bytes encoded as base64")]
+    InvalidKey(String),
+    #[error("no encryption keys configured")]
+    NoKeys,
+    #[error("malformed encrypted value")]
+    Malformed,
+    #[error("encryption failed")]
+    Encrypt,
+    #[error("decryption failed")]
+    Decrypt,
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// Keys as `<id>:<base64 key>`, separated by commas.
+    #[serde(default)]
+    keys: Vec<Arc<str>>,
+    /// Id of the key used for encryption, defaults to the last key.
+    active_key: Option<Arc<str>>,
+}
+
+impl Config {
+    pub fn new() -> envy::Result<Self> {
+        ConfigBuilder::default().build()
+    }
+
+    pub fn builder<'a>() -> ConfigBuilder<'a> {
+        ConfigBuilder::default()
+    }
+
+    pub fn has_keys(&self) -> bool {
+        !self.keys.is_empty()
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder<'a> {
+    prefix: Option<&'a str>,
+}
+
+impl<'a> ConfigBuilder<'a> {
+    pub fn with_prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    pub fn build(self) -> envy::Result<Config> {
+        envy::prefixed(self.prefix.unwrap_or("ENCRYPTION_")).from_env()
+    }
+}
+
+/// Keys for encryption and decryption, identified by their id.
+#[derive(Clone)]
+pub struct Keyring {
+    active: Arc<str>,
+    keys: HashMap<Arc<str>, Aes256Gcm>,
+}
+
+impl Keyring {
+    pub fn new(cfg: &Config) -> Result<Self, EncryptionError> {
+        let mut keys = HashMap::with_capacity(cfg.keys.len());
+        let mut last = None;
+        for entry in cfg.keys.iter() {
+            let (id, key) = entry
+                .split_once(':')
+                .ok_or_else(|| EncryptionError::InvalidKey(entry.to_string()))?;
+            let bytes = STANDARD
+                .decode(key.trim())
+                .map_err(|_| EncryptionError::InvalidKey(id.to_string()))?;
+            let cipher = Aes256Gcm::new_from_slice(&bytes)
+                .map_err(|_| EncryptionError::InvalidKey(id.to_string()))?;
+            let id: Arc<str> = Arc::from(id.trim());
+            keys.insert(id.clone(), cipher);
+            last = Some(id);
+        }
+        let active = cfg
+            .active_key
+            .clone()
+            .or(last)
+            .ok_or(EncryptionError::NoKeys)?;
+        if !keys.contains_key(&active) {
+            return Err(EncryptionError::UnknownKey(active.to_string()));
+        }
+        Ok(Self { active, keys })
+    }
+
+    pub fn active_key(&self) -> &str {
+        &self.active
+    }
+
+    pub fn encrypt<T: Serialize + ?Sized>(&self, value: &T) -> Result<String, EncryptionError> {
+        let plaintext = serde_json::to_vec(value)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut payload = self.keys[&self.active]
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| EncryptionError::Encrypt)?;
+        payload.splice(0..0, nonce);
+        Ok(format!(
+            "{PREFIX}{}:{}",
+            self.active,
+            STANDARD.encode(payload)
+        ))
+    }
+
+    pub fn decrypt<T: DeserializeOwned>(&self, value: &str) -> Result<T, EncryptionError> {
+        let (id, payload) = value
+            .strip_prefix(PREFIX)
+            .and_then(|v| v.split_once(':'))
+            .ok_or(EncryptionError::Malformed)?;
+        let cipher = self
+            .keys
+            .get(id)
+            .ok_or_else(|| EncryptionError::UnknownKey(id.to_string()))?;
+        let payload = STANDARD
+            .decode(payload)
+            .map_err(|_| EncryptionError::Malformed)?;
+        if payload.len() < NONCE_LEN {
+            return Err(EncryptionError::Malformed);
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| EncryptionError::Decrypt)?;
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Returns `true` if `value` was encrypted with another than the active
+    /// key and should be written again.
+    pub fn needs_rotation(&self, value: &str) -> bool {
+        value
+            .strip_prefix(PREFIX)
+            .and_then(|v| v.split_once(':'))
+            .is_some_and(|(id, _)| id != self.active.as_ref())
+    }
+}
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+/// Installs the global keyring, returns `false` if one was already
+/// installed.
+pub fn set_keyring(keyring: Keyring) -> bool {
+    KEYRING.set(keyring).is_ok()
+}
+
+pub fn keyring() -> Result<&'static Keyring, EncryptionError> {
+    KEYRING.get().ok_or(EncryptionError::MissingKeyring)
+}
+
+/// Encrypts `value` with the installed keyring, `None` is written as `null`
+/// without requiring a keyring.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    let value = serde_json::to_value(value).map_err(serde::ser::Error::custom)?;
+    if value.is_null() {
+        return serializer.serialize_none();
+    }
+    keyring()
+        .and_then(|keyring| keyring.encrypt(&value))
+        .map_err(serde::ser::Error::custom)?
+        .serialize(serializer)
+}
+
+/// Decrypts strings starting with `enc:`, other values are read as
+/// plaintext.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(s) if s.starts_with(PREFIX) => keyring()
+            .and_then(|keyring| keyring.decrypt(&s))
+            .map_err(serde::de::Error::custom),
+        value => T::deserialize(value).map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring(keys: &[&str], active: Option<&str>) -> Keyring {
+        Keyring::new(&Config {
+            keys: keys.iter().map(|k| Arc::from(*k)).collect(),
+            active_key: active.map(Arc::from),
+        })
+        .unwrap()
+    }
+
+    const K1: &str = "k1:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+    const K2: &str = "k2:AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE=";
+
+    #[test]
+    fn test_roundtrip_and_rotation() {
+        let old = keyring(&[K1], None);
+        let encrypted = old.encrypt(&vec!["DE", "AT"]).unwrap();
+        assert!(encrypted.starts_with("enc:k1:"));
+
+        let rotated = keyring(&[K1, K2], None);
+        assert_eq!(rotated.active_key(), "k2");
+        assert!(rotated.needs_rotation(&encrypted));
+        let value: Vec<String> = rotated.decrypt(&encrypted).unwrap();
+        assert_eq!(value, ["DE", "AT"]);
+        assert!(!rotated.needs_rotation(&rotated.encrypt(&value).unwrap()));
+
+        let removed = keyring(&[K2], None);
+        assert!(matches!(
+            removed.decrypt::<Vec<String>>(&encrypted),
+            Err(EncryptionError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let cfg = Config {
+            keys: vec![Arc::from("k1:c2hvcnQ=")],
+            active_key: None,
+        };
+        assert!(matches!(
+            Keyring::new(&cfg),
+            Err(EncryptionError::InvalidKey(_))
+        ));
+        let cfg = Config {
+            keys: vec![Arc::from(K1)],
+            active_key: Some(Arc::from("k2")),
+        };
+        assert!(matches!(
+            Keyring::new(&cfg),
+            Err(EncryptionError::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_serde_with() {
+        #[derive(Serialize, Deserialize)]
+        struct Person {
+            #[serde(default, with = "super")]
+            confession: Option<String>,
+        }
+        set_keyring(keyring(&[K1], None));
+        let person = Person {
+            confession: Some("none".into()),
+        };
+        let json = serde_json::to_value(&person).unwrap();
+        assert!(json["confession"].as_str().unwrap().starts_with("enc:k1:"));
+        let person: Person = serde_json::from_value(json).unwrap();
+        assert_eq!(person.confession.as_deref(), Some("none"));
+
+        let plain: Person = serde_json::from_str(r#"{"confession":"legacy"}"#).unwrap();
+        assert_eq!(plain.confession.as_deref(), Some("legacy"));
+        let missing: Person = serde_json::from_str("{}").unwrap();
+        assert_eq!(missing.confession, None);
+        let json = serde_json::to_value(Person { confession: None }).unwrap();
+        assert!(json["confession"].is_null());
+    }
+}
@@ -1,3 +1,5 @@
+use std::{collections::BTreeMap, sync::OnceLock};
+
 use crate::UserId;
 use async_graphql::ErrorExtensions;
 use qm_keycloak::KeycloakError;
@@ -54,6 +56,12 @@ pub enum EntityError {
     /// not found by field.
     #[error("the resource {0} with {1} '{2}' was not found")]
     NotFoundByField(String, String, String),
+    /// Unauthorized access to a named resource.
+    #[error("the resource {0} '{1}' is unauthorized")]
+    UnauthorizedName(String, String),
+    /// Access to a suspended or archived resource.
+    #[error("the resource '{0}' is suspended")]
+    Suspended(String),
     /// not allowed
     #[error("the feature '{0}' is not enabled")]
     NotAllowed(String),
@@ -68,6 +76,14 @@ pub enum EntityError {
     NotSameOwner,
     #[error("Bson could not be serialized: {0}")]
     Bson(String),
+    #[error(transparent)]
+    InvalidIds(#[from] crate::ids::IdErrors),
+    /// Group assigned in a context of a type it is not allowed for.
+    #[error(transparent)]
+    NotAssignable(#[from] qm_role::AssignmentError),
+    /// Fields violating validation rules.
+    #[error(transparent)]
+    Validation(#[from] crate::validate::ValidationErrors),
 }
 
 pub type EntityResult<T> = Result<T, EntityError>;
@@ -119,33 +135,350 @@ impl EntityError {
         Self::NotAllowed(err_msg.into())
     }
 
+    pub fn suspended(context: impl ToString) -> Self {
+        Self::Suspended(context.to_string())
+    }
+
     pub fn internal() -> Self {
         Self::Internal
     }
+
+    pub fn unauthorized_name(ty: impl Into<String>, name: impl Into<String>) -> Self {
+        Self::UnauthorizedName(ty.into(), name.into())
+    }
+
+    /// Machine-readable description of the error, used for the GraphQL
+    /// error extensions and for localized messages.
+    pub fn detail(&self) -> ErrorDetail {
+        match self {
+            Self::Lock(_) => ErrorDetail::new(ErrorCode::Internal, "LOCKED"),
+            Self::Database(_)
+            | Self::SQLDatabase(_)
+            | Self::KeycloakRequest(_)
+            | Self::KeycloakError(_)
+            | Self::UnexpectedError(_)
+            | Self::SerdeJson(_)
+            | Self::Internal => ErrorDetail::new(ErrorCode::Internal, "INTERNAL"),
+            Self::Bson(_) => ErrorDetail::new(ErrorCode::Internal, "BSON"),
+            Self::IdConflict(ty, id) => ErrorDetail::new(ErrorCode::Conflict, "ID_CONFLICT")
+                .with_field("id")
+                .with_param("type", ty)
+                .with_param("id", id),
+            Self::NameConflict(ty, name) => ErrorDetail::new(ErrorCode::Conflict, "NAME_CONFLICT")
+                .with_field("name")
+                .with_param("type", ty)
+                .with_param("name", name),
+            Self::FieldsConflict(ty, name, _) => {
+                ErrorDetail::new(ErrorCode::Conflict, "FIELDS_CONFLICT")
+                    .with_param("type", ty)
+                    .with_param("name", name)
+            }
+            Self::Forbidden => ErrorDetail::new(ErrorCode::Forbidden, "FORBIDDEN"),
+            Self::NotFound => ErrorDetail::new(ErrorCode::NotFound, "NOT_FOUND"),
+            Self::RequiredFields => ErrorDetail::new(ErrorCode::BadRequest, "REQUIRED_FIELDS"),
+            Self::Unauthorized(user_id) => {
+                ErrorDetail::new(ErrorCode::Unauthorized, "UNAUTHORIZED")
+                    .with_param("userId", user_id)
+            }
+            Self::UnauthorizedName(ty, name) => {
+                ErrorDetail::new(ErrorCode::Unauthorized, "UNAUTHORIZED_NAME")
+                    .with_field("name")
+                    .with_param("type", ty)
+                    .with_param("name", name)
+            }
+            Self::NotFoundById(ty, id) => ErrorDetail::new(ErrorCode::NotFound, "NOT_FOUND_BY_ID")
+                .with_field("id")
+                .with_param("type", ty)
+                .with_param("id", id),
+            Self::NotFoundByField(ty, field, value) => {
+                ErrorDetail::new(ErrorCode::NotFound, "NOT_FOUND_BY_FIELD")
+                    .with_field(field)
+                    .with_param("type", ty)
+                    .with_param("value", value)
+            }
+            Self::Suspended(context) => {
+                ErrorDetail::new(ErrorCode::Forbidden, "SUSPENDED").with_param("context", context)
+            }
+            Self::NotAllowed(feature) => ErrorDetail::new(ErrorCode::NotAllowed, "NOT_ALLOWED")
+                .with_param("feature", feature),
+            Self::BadRequest(ty, _) => {
+                ErrorDetail::new(ErrorCode::BadRequest, "BAD_REQUEST").with_param("type", ty)
+            }
+            Self::NoId => ErrorDetail::new(ErrorCode::Internal, "NO_ID"),
+            Self::NotEmpty => ErrorDetail::new(ErrorCode::BadRequest, "NOT_EMPTY"),
+            Self::NotSameOwner => ErrorDetail::new(ErrorCode::BadRequest, "NOT_SAME_OWNER"),
+            Self::InvalidIds(err) => ErrorDetail::new(ErrorCode::BadRequest, "INVALID_IDS")
+                .with_param(
+                    "indexes",
+                    err.errors()
+                        .iter()
+                        .map(|e| e.index.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+            Self::NotAssignable(err) => ErrorDetail::new(ErrorCode::BadRequest, "NOT_ASSIGNABLE")
+                .with_field("groupId")
+                .with_param("group", err.group.as_str())
+                .with_param("type", err.context_type.as_deref().unwrap_or_default())
+                .with_param("allowedTypes", err.allowed_types.join(",")),
+            Self::Validation(err) => {
+                let detail = ErrorDetail::new(ErrorCode::BadRequest, "VALIDATION");
+                match err.fields().next() {
+                    Some(field) => detail.with_field(field),
+                    None => detail,
+                }
+            }
+        }
+    }
+
+    /// Whether the request may succeed when it is repeated, e.g. after a
+    /// lock was released or a connection was restored.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Lock(_) => true,
+            Self::Database(err) => {
+                matches!(
+                    err.kind.as_ref(),
+                    qm_mongodb::error::ErrorKind::Io(_)
+                        | qm_mongodb::error::ErrorKind::ConnectionPoolCleared { .. }
+                        | qm_mongodb::error::ErrorKind::ServerSelection { .. }
+                ) || err.contains_label(qm_mongodb::error::RETRYABLE_WRITE_ERROR)
+                    || err.contains_label(qm_mongodb::error::TRANSIENT_TRANSACTION_ERROR)
+            }
+            Self::SQLDatabase(err) => matches!(err, sea_orm::DbErr::ConnectionAcquire(_)),
+            Self::KeycloakRequest(err)
+            | Self::KeycloakError(KeycloakError::ReqwestFailure(err)) => {
+                err.is_timeout() || err.is_connect()
+            }
+            Self::KeycloakError(KeycloakError::HttpFailure { status, .. }) => {
+                *status == 429 || *status >= 500
+            }
+            _ => false,
+        }
+    }
+
+    /// Message of the error, localized by the installed [`MessageFormatter`]
+    /// if there is one.
+    pub fn message(&self) -> String {
+        MESSAGE_FORMATTER
+            .get()
+            .and_then(|formatter| formatter.format(&self.detail(), self))
+            .unwrap_or_else(|| self.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    NotAllowed,
+    Conflict,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::BadRequest => "BAD_REQUEST",
+            Self::Unauthorized => "UNAUTHORIZED",
+            Self::Forbidden => "FORBIDDEN",
+            Self::NotFound => "NOT_FOUND",
+            Self::NotAllowed => "NOT_ALLOWED",
+            Self::Conflict => "CONFLICT",
+            Self::Internal => "INTERNAL",
+        }
+    }
+
+    /// HTTP like status, kept as `code` extension for existing clients.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::NotAllowed => 405,
+            Self::Conflict => 409,
+            Self::Internal => 500,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorDetail {
+    pub code: ErrorCode,
+    /// Identifies the message, e.g. `NAME_CONFLICT`.
+    pub key: &'static str,
+    pub field: Option<String>,
+    pub params: BTreeMap<String, String>,
+}
+
+impl ErrorDetail {
+    pub fn new(code: ErrorCode, key: &'static str) -> Self {
+        Self {
+            code,
+            key,
+            field: None,
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Produces localized error messages, `None` falls back to the default
+/// english message.
+pub trait MessageFormatter: Send + Sync + 'static {
+    fn format(&self, detail: &ErrorDetail, err: &EntityError) -> Option<String>;
+}
+
+impl<F> MessageFormatter for F
+where
+    F: Fn(&ErrorDetail, &EntityError) -> Option<String> + Send + Sync + 'static,
+{
+    fn format(&self, detail: &ErrorDetail, err: &EntityError) -> Option<String> {
+        self(detail, err)
+    }
+}
+
+static MESSAGE_FORMATTER: OnceLock<Box<dyn MessageFormatter>> = OnceLock::new();
+
+/// Installs the global message formatter, returns `false` if one was
+/// already installed.
+pub fn set_message_formatter(formatter: impl MessageFormatter) -> bool {
+    MESSAGE_FORMATTER.set(Box::new(formatter)).is_ok()
 }
 
 impl ErrorExtensions for EntityError {
     fn extend(&self) -> async_graphql::Error {
-        async_graphql::Error::new(format!("{}", self)).extend_with(|_err, e| match self {
-            EntityError::NameConflict(ty, _) => {
-                e.set("code", 409);
-                e.set("type", ty);
-                e.set("field", "name");
+        let detail = self.detail();
+        async_graphql::Error::new(self.message()).extend_with(|_err, e| {
+            e.set("code", detail.code.status());
+            e.set("error", detail.code.as_str());
+            e.set("key", detail.key);
+            e.set("retryable", self.is_retryable());
+            if let Some(field) = detail.field.as_ref() {
+                e.set("field", field.as_str());
             }
-            EntityError::FieldsConflict(ty, _, fields) => {
-                e.set("code", 409);
-                e.set("type", ty);
-                e.set("details", fields.clone());
+            if !detail.params.is_empty() {
+                e.set(
+                    "params",
+                    async_graphql::Value::Object(
+                        detail
+                            .params
+                            .iter()
+                            .map(|(k, v)| (async_graphql::Name::new(k), v.as_str().into()))
+                            .collect(),
+                    ),
+                );
             }
-            EntityError::Unauthorized(_) => e.set("code", 401),
-            EntityError::NotAllowed(_) => e.set("code", 405),
-            EntityError::Forbidden => e.set("code", 403),
-            EntityError::Internal => e.set("code", 500),
-            EntityError::BadRequest(ty, _) => {
-                e.set("code", 400);
-                e.set("details", ty);
+            match self {
+                EntityError::NameConflict(ty, _) | EntityError::UnauthorizedName(ty, _) => {
+                    e.set("type", ty.as_str());
+                }
+                EntityError::FieldsConflict(ty, _, fields) => {
+                    e.set("type", ty.as_str());
+                    e.set("details", fields.clone());
+                }
+                EntityError::BadRequest(ty, _) => {
+                    e.set("details", ty.as_str());
+                }
+                EntityError::NotAssignable(err) => {
+                    e.set("details", err.allowed_types.clone());
+                }
+                EntityError::Validation(err) => {
+                    e.set(
+                        "details",
+                        async_graphql::Value::List(
+                            err.errors()
+                                .iter()
+                                .map(|err| {
+                                    async_graphql::value!({
+                                        "field": err.field.as_str(),
+                                        "code": err.error.code.as_ref(),
+                                        "params": err.error.params.clone(),
+                                    })
+                                })
+                                .collect(),
+                        ),
+                    );
+                }
+                _ => {}
             }
-            _ => {}
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extensions() {
+        let err = EntityError::NameConflict("Customer".into(), "acme".into()).extend();
+        let ext = err.extensions.unwrap();
+        assert_eq!(ext.get("code"), Some(&async_graphql::Value::from(409)));
+        assert_eq!(ext.get("error"), Some(&"CONFLICT".into()));
+        assert_eq!(ext.get("key"), Some(&"NAME_CONFLICT".into()));
+        assert_eq!(ext.get("field"), Some(&"name".into()));
+        assert_eq!(ext.get("retryable"), Some(&false.into()));
+        let err = EntityError::from(KeycloakError::HttpFailure {
+            status: 503,
+            body: None,
+            text: String::new(),
+        });
+        assert!(err.is_retryable());
+        assert_eq!(err.detail().key, "INTERNAL");
+        let detail = EntityError::not_found_by_field::<String>("email", "a@b.c").detail();
+        assert_eq!(detail.code, ErrorCode::NotFound);
+        assert_eq!(detail.field.as_deref(), Some("email"));
+        assert_eq!(
+            detail.params.get("value").map(String::as_str),
+            Some("a@b.c")
+        );
+        let detail = EntityError::suspended("V1C1").detail();
+        assert_eq!(detail.code, ErrorCode::Forbidden);
+        assert_eq!(detail.key, "SUSPENDED");
+        let err = EntityError::from(qm_role::AssignmentError {
+            group: "Reader".into(),
+            context_type: Some("clinic".into()),
+            allowed_types: vec!["school".into()],
+        })
+        .extend();
+        let ext = err.extensions.unwrap();
+        assert_eq!(ext.get("key"), Some(&"NOT_ASSIGNABLE".into()));
+        assert_eq!(ext.get("details"), Some(&vec!["school"].into()));
+        let mut errors = crate::validate::ValidationErrors::new();
+        errors.add(
+            "person.firstname",
+            crate::validate::ValidationError::new("LENGTH").with_param("min", 1),
+        );
+        let err = EntityError::from(errors).extend();
+        let ext = err.extensions.unwrap();
+        assert_eq!(ext.get("code"), Some(&async_graphql::Value::from(400)));
+        assert_eq!(ext.get("field"), Some(&"person.firstname".into()));
+        assert_eq!(
+            ext.get("details"),
+            Some(&async_graphql::value!([{
+                "field": "person.firstname",
+                "code": "LENGTH",
+                "params": { "min": "1" },
+            }]))
+        );
+    }
+}
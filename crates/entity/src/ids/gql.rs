@@ -9,10 +9,13 @@ use async_graphql::Value;
 
 use crate::ids::CustomerId;
 use crate::ids::CustomerResourceId;
+use crate::ids::InfraContext;
 use crate::ids::InstitutionId;
 use crate::ids::InstitutionResourceId;
 use crate::ids::OrganizationId;
 use crate::ids::OrganizationResourceId;
+use crate::ids::OrganizationUnitId;
+use crate::ids::OrganizationUnitResourceId;
 
 #[macro_export]
 macro_rules! impl_id_scalar {
@@ -43,6 +46,47 @@ impl_id_scalar!(OrganizationId);
 impl_id_scalar!(OrganizationResourceId);
 impl_id_scalar!(InstitutionId);
 impl_id_scalar!(InstitutionResourceId);
+impl_id_scalar!(OrganizationUnitId);
+impl_id_scalar!(OrganizationUnitResourceId);
+impl_id_scalar!(InfraContext);
+
+/// Input to select the context of a query, alternative to the prefixed
+/// string form of the [`InfraContext`] scalar.
+#[derive(Debug, Clone, Copy, OneofObject)]
+pub enum ContextFilter {
+    Customer(CustomerId),
+    Organization(OrganizationId),
+    Institution(InstitutionId),
+    OrganizationUnit(OrganizationUnitId),
+}
+
+impl From<ContextFilter> for InfraContext {
+    fn from(value: ContextFilter) -> Self {
+        match value {
+            ContextFilter::Customer(v) => InfraContext::Customer(v),
+            ContextFilter::Organization(v) => InfraContext::Organization(v),
+            ContextFilter::Institution(v) => InfraContext::Institution(v),
+            ContextFilter::OrganizationUnit(v) => InfraContext::OrganizationUnit(v),
+        }
+    }
+}
+
+impl From<InfraContext> for ContextFilter {
+    fn from(value: InfraContext) -> Self {
+        match value {
+            InfraContext::Customer(v) => ContextFilter::Customer(v),
+            InfraContext::Organization(v) => ContextFilter::Organization(v),
+            InfraContext::Institution(v) => ContextFilter::Institution(v),
+            InfraContext::OrganizationUnit(v) => ContextFilter::OrganizationUnit(v),
+        }
+    }
+}
+
+impl ContextFilter {
+    pub fn into_context(filter: Option<Self>) -> Option<InfraContext> {
+        filter.map(InfraContext::from)
+    }
+}
 
 #[derive(OneofObject)]
 pub enum CustomerOrOrganization {
@@ -63,3 +107,19 @@ pub type OrganizationIds = Arc<[OrganizationId]>;
 pub type OrganizationResourceIds = Arc<[OrganizationResourceId]>;
 pub type InstitutionIds = Arc<[InstitutionId]>;
 pub type InstitutionResourceIds = Arc<[InstitutionResourceId]>;
+pub type OrganizationUnitIds = Arc<[OrganizationUnitId]>;
+pub type OrganizationUnitResourceIds = Arc<[OrganizationUnitResourceId]>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infra_context_scalar() {
+        let ctx = InfraContext::Institution((1_i64, 2_i64, 3_i64).into());
+        let value = ctx.to_value();
+        assert_eq!(<InfraContext as ScalarType>::parse(value).unwrap(), ctx);
+        assert!(<InfraContext as ScalarType>::parse(Value::String("X1".into())).is_err());
+        assert!(<InfraContext as ScalarType>::parse(Value::Boolean(true)).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 //! ID Implementations for multiple scenarios of infrastructure and ownership.
 //!
 //! The smallest unit has a min length of 3 characters. The biggest Resource ID can go up to 76 characters.
+//! Resource IDs are generic over the ID part, with the `uuid7` feature [`Uuid7Id`](super::Uuid7Id) can be used
+//! instead of [`ID`], its ID part has 32 instead of 24 characters.
 //!
 //! |Prefix|                            Structure                                  |           Type            | min length | max length | real size  |
 //! |------|-----------------------------------------------------------------------|---------------------------|------------|------------|------------|
@@ -10,12 +12,18 @@
 //! |  S   | CustomerId + OrganizationId + ID (24 Characters)                      | OrganizationResourceId    |     29     |     59     |     28     |
 //! |  R   | CustomerId + OrganizationId + InstitutionId                           | InstitutionId             |     7      |     52     |     24     |
 //! |  Q   | CustomerId + OrganizationId + InstitutionId + ID (24 Characters)      | InstitutionResourceId     |     31     |     76     |     36     |
+//! |  P   | CustomerId + OrganizationId + OrganizationUnitId                      | OrganizationUnitId        |     7      |     52     |     24     |
+//! |  O   | CustomerId + OrganizationId + OrganizationUnitId + ID (24 Characters) | OrganizationUnitResourceId|     31     |     76     |     36     |
 
-use async_graphql::OneofObject;
-
+use sqlx::error::BoxDynError;
 use sqlx::postgres::PgArgumentBuffer;
+use sqlx::postgres::PgHasArrayType;
+use sqlx::postgres::PgTypeInfo;
+use sqlx::postgres::PgValueRef;
+use sqlx::Decode;
 use sqlx::Encode;
 use sqlx::Postgres;
+use sqlx::Type;
 use std::fmt::Write;
 use std::str::FromStr;
 
@@ -27,8 +35,32 @@ pub const ORGANIZATION_ID_PREFIX: char = 'T';
 pub const ORGANIZATION_RESOURCE_ID_PREFIX: char = 'S';
 pub const INSTITUTION_ID_PREFIX: char = 'R';
 pub const INSTITUTION_RESOURCE_ID_PREFIX: char = 'Q';
+pub const ORGANIZATION_UNIT_ID_PREFIX: char = 'P';
+pub const ORGANIZATION_UNIT_RESOURCE_ID_PREFIX: char = 'O';
 pub const ID_LENGTH: usize = 24;
 
+/// ID part of resource ids.
+pub trait ResourceIdPart: Copy {
+    /// Length of the hex encoding.
+    const LENGTH: usize;
+
+    fn to_hex(&self) -> String;
+
+    fn parse_hex(s: &str) -> anyhow::Result<Self>;
+}
+
+impl ResourceIdPart for ID {
+    const LENGTH: usize = ID_LENGTH;
+
+    fn to_hex(&self) -> String {
+        ID::to_hex(*self)
+    }
+
+    fn parse_hex(s: &str) -> anyhow::Result<Self> {
+        Ok(ID::parse_str(s)?)
+    }
+}
+
 #[derive(
     Debug,
     Clone,
@@ -81,6 +113,34 @@ impl<'q> Encode<'q, Postgres> for InfraId {
     }
 }
 
+impl<'r> Decode<'r, Postgres> for InfraId {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        <i64 as Decode<Postgres>>::decode(value).map(Self)
+    }
+}
+
+macro_rules! impl_pg_type {
+    ($t:ty, $r:ty) => {
+        impl Type<Postgres> for $t {
+            fn type_info() -> PgTypeInfo {
+                <$r as Type<Postgres>>::type_info()
+            }
+
+            fn compatible(ty: &PgTypeInfo) -> bool {
+                <$r as Type<Postgres>>::compatible(ty)
+            }
+        }
+
+        impl PgHasArrayType for $t {
+            fn array_type_info() -> PgTypeInfo {
+                <$r as PgHasArrayType>::array_type_info()
+            }
+        }
+    };
+}
+
+impl_pg_type!(InfraId, i64);
+
 trait Prefixed {
     const PREFIX: char;
 }
@@ -110,11 +170,21 @@ macro_rules! impl_display_for_id {
     };
 }
 
-macro_rules! impl_display_for_resource_id {
-    ($t:ty) => {
-        impl std::fmt::Display for $t {
+macro_rules! impl_resource_id {
+    ($t:ident, $p:expr) => {
+        impl $t {
+            pub fn parse(value: &str) -> anyhow::Result<Self> {
+                Self::from_str(value)
+            }
+        }
+
+        impl Prefixed for $t {
+            const PREFIX: char = $p;
+        }
+
+        impl<I: ResourceIdPart> std::fmt::Display for $t<I> {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                f.write_char(Self::PREFIX)?;
+                f.write_char($p)?;
                 f.write_str(&self.parent().to_hex())?;
                 f.write_str(&self.id.to_hex())
             }
@@ -170,8 +240,8 @@ macro_rules! impl_institution_id_from_ty_tuple {
 
 macro_rules! impl_customer_resource_id_from_ty_tuple {
     ($n:ty) => {
-        impl From<($n, ID)> for CustomerResourceId {
-            fn from(value: ($n, ID)) -> Self {
+        impl<I> From<($n, I)> for CustomerResourceId<I> {
+            fn from(value: ($n, I)) -> Self {
                 CustomerResourceId {
                     cid: value.0 as i64,
                     id: value.1,
@@ -183,8 +253,8 @@ macro_rules! impl_customer_resource_id_from_ty_tuple {
 
 macro_rules! impl_organization_resource_id_from_ty_tuple {
     ($n:ty) => {
-        impl From<($n, $n, ID)> for OrganizationResourceId {
-            fn from(value: ($n, $n, ID)) -> Self {
+        impl<I> From<($n, $n, I)> for OrganizationResourceId<I> {
+            fn from(value: ($n, $n, I)) -> Self {
                 OrganizationResourceId {
                     cid: value.0 as i64,
                     oid: value.1 as i64,
@@ -197,8 +267,8 @@ macro_rules! impl_organization_resource_id_from_ty_tuple {
 
 macro_rules! impl_institution_resource_id_from_ty_tuple {
     ($n:ty) => {
-        impl From<($n, $n, $n, ID)> for InstitutionResourceId {
-            fn from(value: ($n, $n, $n, ID)) -> Self {
+        impl<I> From<($n, $n, $n, I)> for InstitutionResourceId<I> {
+            fn from(value: ($n, $n, $n, I)) -> Self {
                 InstitutionResourceId {
                     cid: value.0 as i64,
                     oid: value.1 as i64,
@@ -210,6 +280,44 @@ macro_rules! impl_institution_resource_id_from_ty_tuple {
     };
 }
 
+macro_rules! impl_organization_unit_id_from_ty_tuple {
+    ($n:ty) => {
+        impl From<($n, $n, $n)> for OrganizationUnitId {
+            fn from(value: ($n, $n, $n)) -> Self {
+                OrganizationUnitId {
+                    cid: value.0 as i64,
+                    oid: value.1 as i64,
+                    uid: value.2 as i64,
+                }
+            }
+        }
+        impl From<(($n, $n), $n)> for OrganizationUnitId {
+            fn from(value: (($n, $n), $n)) -> Self {
+                OrganizationUnitId {
+                    cid: value.0 .0 as i64,
+                    oid: value.0 .1 as i64,
+                    uid: value.1 as i64,
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_organization_unit_resource_id_from_ty_tuple {
+    ($n:ty) => {
+        impl<I> From<($n, $n, $n, I)> for OrganizationUnitResourceId<I> {
+            fn from(value: ($n, $n, $n, I)) -> Self {
+                OrganizationUnitResourceId {
+                    cid: value.0 as i64,
+                    oid: value.1 as i64,
+                    uid: value.2 as i64,
+                    id: value.3,
+                }
+            }
+        }
+    };
+}
+
 /// Customer Id
 ///
 /// - Prefix: V
@@ -291,6 +399,22 @@ impl From<CustomerId> for InfraId {
 }
 
 impl_id!(CustomerId, CUSTOMER_ID_PREFIX);
+impl_pg_type!(CustomerId, i64);
+
+impl<'q> Encode<'q, Postgres> for CustomerId {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        <i64 as Encode<Postgres>>::encode_by_ref(&self.cid, buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for CustomerId {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        <i64 as Decode<Postgres>>::decode(value).map(CustomerId::from)
+    }
+}
 impl_display_for_id!(CustomerId);
 impl_customer_id_from_ty!(i64);
 impl_customer_id_from_ty!(u64);
@@ -336,12 +460,12 @@ impl_customer_id_from_ty!(i8);
     serde::Deserialize,
     async_graphql::Description,
 )]
-pub struct CustomerResourceId {
+pub struct CustomerResourceId<I = ID> {
     cid: i64,
-    id: ID,
+    id: I,
 }
 
-impl CustomerResourceId {
+impl<I: ResourceIdPart> CustomerResourceId<I> {
     pub fn root(&self) -> CustomerId {
         CustomerId::from(self.cid)
     }
@@ -350,34 +474,33 @@ impl CustomerResourceId {
         CustomerId::from(self.cid)
     }
 
-    pub fn unzip(&self) -> (i64, ID) {
+    pub fn unzip(&self) -> (i64, I) {
         (self.cid, self.id)
     }
 }
 
-impl FromStr for CustomerResourceId {
+impl<I: ResourceIdPart> FromStr for CustomerResourceId<I> {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with(Self::PREFIX) {
+        if !s.starts_with(CUSTOMER_RESOURCE_ID_PREFIX) {
             anyhow::bail!("Invalid CustomerResourceId")
         }
-        let mut parser = StringParser::<1>::new(&s[1..]).with_object_id();
+        let mut parser = StringParser::<1>::new(&s[1..]).with_id_length(I::LENGTH);
         let CustomerId { cid }: CustomerId = parser.next().map(From::from).ok_or(
             anyhow::anyhow!("unable to parse '{s}' into CustomerResourceId"),
         )?;
         let start = parser.end();
-        let end = start + ID_LENGTH;
+        let end = start + I::LENGTH;
         if end > s.len() {
             anyhow::bail!("Invalid length for CustomerResourceId");
         }
-        let id = ID::from_str(&s[start..end])?;
+        let id = I::parse_hex(&s[start..end])?;
         Ok(Self { cid, id })
     }
 }
 
-impl_id!(CustomerResourceId, CUSTOMER_RESOURCE_ID_PREFIX);
-impl_display_for_resource_id!(CustomerResourceId);
+impl_resource_id!(CustomerResourceId, CUSTOMER_RESOURCE_ID_PREFIX);
 impl_customer_resource_id_from_ty_tuple!(i64);
 impl_customer_resource_id_from_ty_tuple!(u64);
 impl_customer_resource_id_from_ty_tuple!(i32);
@@ -448,7 +571,7 @@ impl OrganizationId {
         (self.cid, self.oid)
     }
 
-    pub fn resource(&self, id: ID) -> OrganizationResourceId {
+    pub fn resource<I>(&self, id: I) -> OrganizationResourceId<I> {
         OrganizationResourceId::from((self.cid, self.oid, id))
     }
 }
@@ -533,13 +656,13 @@ impl_organization_id_from_ty_tuple!(i8);
     serde::Deserialize,
     async_graphql::Description,
 )]
-pub struct OrganizationResourceId {
+pub struct OrganizationResourceId<I = ID> {
     cid: i64,
     oid: i64,
-    id: ID,
+    id: I,
 }
 
-impl OrganizationResourceId {
+impl<I: ResourceIdPart> OrganizationResourceId<I> {
     pub fn root(&self) -> CustomerId {
         CustomerId::from(self.cid)
     }
@@ -548,23 +671,23 @@ impl OrganizationResourceId {
         OrganizationId::from((self.cid, self.oid))
     }
 
-    pub fn id(&self) -> &ID {
+    pub fn id(&self) -> &I {
         &self.id
     }
 
-    pub fn unzip(&self) -> (i64, i64, ID) {
+    pub fn unzip(&self) -> (i64, i64, I) {
         (self.cid, self.oid, self.id)
     }
 }
 
-impl FromStr for OrganizationResourceId {
+impl<I: ResourceIdPart> FromStr for OrganizationResourceId<I> {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with(Self::PREFIX) {
+        if !s.starts_with(ORGANIZATION_RESOURCE_ID_PREFIX) {
             anyhow::bail!("Invalid OrganizationResourceId")
         }
-        let mut parser = StringParser::<2>::new(&s[1..]).with_object_id();
+        let mut parser = StringParser::<2>::new(&s[1..]).with_id_length(I::LENGTH);
         let OrganizationId { cid, oid }: OrganizationId = parser
             .next()
             .zip(parser.next())
@@ -573,17 +696,16 @@ impl FromStr for OrganizationResourceId {
                 "unable to parse '{s}' into OrganizationResourceId"
             ))?;
         let start = parser.end();
-        let end = start + ID_LENGTH;
+        let end = start + I::LENGTH;
         if end > s.len() {
             anyhow::bail!("Invalid length for OrganizationResourceId");
         }
-        let id = ID::from_str(&s[start..end])?;
+        let id = I::parse_hex(&s[start..end])?;
         Ok(Self { cid, oid, id })
     }
 }
 
-impl_id!(OrganizationResourceId, ORGANIZATION_RESOURCE_ID_PREFIX);
-impl_display_for_resource_id!(OrganizationResourceId);
+impl_resource_id!(OrganizationResourceId, ORGANIZATION_RESOURCE_ID_PREFIX);
 impl_organization_resource_id_from_ty_tuple!(i64);
 impl_organization_resource_id_from_ty_tuple!(u64);
 impl_organization_resource_id_from_ty_tuple!(i32);
@@ -658,7 +780,7 @@ impl InstitutionId {
         (self.cid, (self.oid, self.iid))
     }
 
-    pub fn resource(&self, id: ID) -> InstitutionResourceId {
+    pub fn resource<I>(&self, id: I) -> InstitutionResourceId<I> {
         InstitutionResourceId::from((self.cid, self.oid, self.iid, id))
     }
 }
@@ -744,14 +866,14 @@ impl_institution_id_from_ty_tuple!(i8);
     serde::Deserialize,
     async_graphql::Description,
 )]
-pub struct InstitutionResourceId {
+pub struct InstitutionResourceId<I = ID> {
     cid: i64,
     oid: i64,
     iid: i64,
-    id: ID,
+    id: I,
 }
 
-impl InstitutionResourceId {
+impl<I: ResourceIdPart> InstitutionResourceId<I> {
     pub fn root(&self) -> CustomerId {
         CustomerId::from(self.cid)
     }
@@ -760,19 +882,19 @@ impl InstitutionResourceId {
         InstitutionId::from((self.cid, self.oid, self.iid))
     }
 
-    pub fn unzip(&self) -> (i64, i64, i64, ID) {
+    pub fn unzip(&self) -> (i64, i64, i64, I) {
         (self.cid, self.oid, self.iid, self.id)
     }
 }
 
-impl FromStr for InstitutionResourceId {
+impl<I: ResourceIdPart> FromStr for InstitutionResourceId<I> {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with(Self::PREFIX) {
+        if !s.starts_with(INSTITUTION_RESOURCE_ID_PREFIX) {
             anyhow::bail!("Invalid InstitutionResourceId")
         }
-        let mut parser = StringParser::<3>::new(&s[1..]).with_object_id();
+        let mut parser = StringParser::<3>::new(&s[1..]).with_id_length(I::LENGTH);
         let InstitutionId { cid, oid, iid }: InstitutionId = parser
             .next()
             .zip(parser.next())
@@ -782,17 +904,16 @@ impl FromStr for InstitutionResourceId {
                 "unable to parse '{s}' into InstitutionResourceId"
             ))?;
         let start = parser.end();
-        let end = start + ID_LENGTH;
+        let end = start + I::LENGTH;
         if end > s.len() {
             anyhow::bail!("Invalid length for InstitutionResourceId");
         }
-        let id = ID::from_str(&s[start..end])?;
+        let id = I::parse_hex(&s[start..end])?;
         Ok(Self { cid, oid, iid, id })
     }
 }
 
-impl_id!(InstitutionResourceId, INSTITUTION_RESOURCE_ID_PREFIX);
-impl_display_for_resource_id!(InstitutionResourceId);
+impl_resource_id!(InstitutionResourceId, INSTITUTION_RESOURCE_ID_PREFIX);
 impl_institution_resource_id_from_ty_tuple!(i64);
 impl_institution_resource_id_from_ty_tuple!(u64);
 impl_institution_resource_id_from_ty_tuple!(i32);
@@ -802,11 +923,241 @@ impl_institution_resource_id_from_ty_tuple!(i16);
 impl_institution_resource_id_from_ty_tuple!(u8);
 impl_institution_resource_id_from_ty_tuple!(i8);
 
-#[derive(Debug, Clone, Copy, OneofObject, Hash, PartialEq, Eq, PartialOrd, Ord)]
+/// Organization Unit Id
+///
+/// - Prefix: P
+/// - Min Length: 7
+/// - Max Length: 52
+/// - Real size: 24
+///
+/// Organization units belong either to a customer or to an organization,
+/// units of a customer have `0` as organization id.
+///
+/// # Examples
+///
+/// ```rust
+/// use qm_entity::ids::{OrganizationId, OrganizationUnitId};
+///
+/// let id1 = OrganizationUnitId::parse("P010203").expect("Organization Unit Id");
+/// let id2 = OrganizationUnitId::parse("P010003").expect("Organization Unit Id");
+///
+/// assert_eq!((1, 2, 3), id1.unzip());
+/// assert_eq!(Some(OrganizationId::from((1, 2))), id1.organization());
+/// assert_eq!((1, 0, 3), id2.unzip());
+/// assert_eq!(None, id2.organization());
+/// ```
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    async_graphql::Description,
+)]
+pub struct OrganizationUnitId {
+    pub cid: i64,
+    pub oid: i64,
+    pub uid: i64,
+}
+
+impl OrganizationUnitId {
+    pub fn id(&self) -> i64 {
+        self.uid
+    }
+
+    pub fn root(&self) -> CustomerId {
+        CustomerId::from(self.cid)
+    }
+
+    pub fn organization(&self) -> Option<OrganizationId> {
+        (self.oid != 0).then(|| OrganizationId::from((self.cid, self.oid)))
+    }
+
+    /// Customer or organization the unit belongs to.
+    pub fn parent(&self) -> InfraContext {
+        self.organization()
+            .map(InfraContext::Organization)
+            .unwrap_or_else(|| InfraContext::Customer(self.root()))
+    }
+
+    fn to_hex(self) -> String {
+        StringWriter::from((self.cid, self.oid, self.uid)).into_inner()
+    }
+
+    pub fn unzip(&self) -> (i64, i64, i64) {
+        (self.cid, self.oid, self.uid)
+    }
+
+    pub fn resource<I>(&self, id: I) -> OrganizationUnitResourceId<I> {
+        OrganizationUnitResourceId::from((self.cid, self.oid, self.uid, id))
+    }
+}
+
+impl FromStr for OrganizationUnitId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with(Self::PREFIX) {
+            anyhow::bail!("Invalid OrganizationUnitId")
+        }
+        let mut parser = StringParser::<3>::new(&s[1..]);
+        parser
+            .next()
+            .zip(parser.next())
+            .zip(parser.next())
+            .map(From::from)
+            .ok_or(anyhow::anyhow!(
+                "unable to get OrganizationUnitId from '{s}'"
+            ))
+    }
+}
+
+impl From<OrganizationUnitId> for i64 {
+    fn from(value: OrganizationUnitId) -> Self {
+        value.id()
+    }
+}
+
+impl<'a> From<&'a OrganizationUnitId> for InfraId {
+    fn from(value: &'a OrganizationUnitId) -> Self {
+        InfraId(value.uid)
+    }
+}
+
+impl From<OrganizationUnitId> for InfraId {
+    fn from(value: OrganizationUnitId) -> Self {
+        InfraId(value.uid)
+    }
+}
+
+impl<'a> From<&'a OrganizationUnitId> for CustomerId {
+    fn from(value: &'a OrganizationUnitId) -> Self {
+        value.root()
+    }
+}
+
+impl_id!(OrganizationUnitId, ORGANIZATION_UNIT_ID_PREFIX);
+impl_display_for_id!(OrganizationUnitId);
+impl_organization_unit_id_from_ty_tuple!(i64);
+impl_organization_unit_id_from_ty_tuple!(u64);
+impl_organization_unit_id_from_ty_tuple!(i32);
+impl_organization_unit_id_from_ty_tuple!(u32);
+impl_organization_unit_id_from_ty_tuple!(u16);
+impl_organization_unit_id_from_ty_tuple!(i16);
+impl_organization_unit_id_from_ty_tuple!(u8);
+impl_organization_unit_id_from_ty_tuple!(i8);
+
+/// Organization Unit Resource Id
+///
+/// - Prefix: O
+/// - Min Length: 31
+/// - Max Length: 76
+/// - Real size: 36
+///
+/// # Examples
+///
+/// ```rust
+/// use std::str::FromStr;
+/// use qm_entity::ids::{OrganizationUnitResourceId, ID};
+///
+/// let id1 = OrganizationUnitResourceId::parse("O0102036603f7b32b1753f84a719e01").expect("Organization Unit Resource Id");
+/// let id2 = OrganizationUnitResourceId::parse("O120001226603f7b32b1753f84a719e02").expect("Organization Unit Resource Id");
+///
+/// assert_eq!((1, 2, 3, ID::from_str("6603f7b32b1753f84a719e01").expect("Object ID")), id1.unzip());
+/// assert_eq!((0x20, 0, 0x22, ID::from_str("6603f7b32b1753f84a719e02").expect("Object ID")), id2.unzip());
+/// ```
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+    async_graphql::Description,
+)]
+pub struct OrganizationUnitResourceId<I = ID> {
+    cid: i64,
+    oid: i64,
+    uid: i64,
+    id: I,
+}
+
+impl<I: ResourceIdPart> OrganizationUnitResourceId<I> {
+    pub fn root(&self) -> CustomerId {
+        CustomerId::from(self.cid)
+    }
+
+    pub fn parent(&self) -> OrganizationUnitId {
+        OrganizationUnitId::from((self.cid, self.oid, self.uid))
+    }
+
+    pub fn id(&self) -> &I {
+        &self.id
+    }
+
+    pub fn unzip(&self) -> (i64, i64, i64, I) {
+        (self.cid, self.oid, self.uid, self.id)
+    }
+}
+
+impl<I: ResourceIdPart> FromStr for OrganizationUnitResourceId<I> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.starts_with(ORGANIZATION_UNIT_RESOURCE_ID_PREFIX) {
+            anyhow::bail!("Invalid OrganizationUnitResourceId")
+        }
+        let mut parser = StringParser::<3>::new(&s[1..]).with_id_length(I::LENGTH);
+        let OrganizationUnitId { cid, oid, uid }: OrganizationUnitId = parser
+            .next()
+            .zip(parser.next())
+            .zip(parser.next())
+            .map(From::from)
+            .ok_or(anyhow::anyhow!(
+                "unable to parse '{s}' into OrganizationUnitResourceId"
+            ))?;
+        let start = parser.end();
+        let end = start + I::LENGTH;
+        if end > s.len() {
+            anyhow::bail!("Invalid length for OrganizationUnitResourceId");
+        }
+        let id = I::parse_hex(&s[start..end])?;
+        Ok(Self { cid, oid, uid, id })
+    }
+}
+
+impl_resource_id!(
+    OrganizationUnitResourceId,
+    ORGANIZATION_UNIT_RESOURCE_ID_PREFIX
+);
+impl_organization_unit_resource_id_from_ty_tuple!(i64);
+impl_organization_unit_resource_id_from_ty_tuple!(u64);
+impl_organization_unit_resource_id_from_ty_tuple!(i32);
+impl_organization_unit_resource_id_from_ty_tuple!(u32);
+impl_organization_unit_resource_id_from_ty_tuple!(u16);
+impl_organization_unit_resource_id_from_ty_tuple!(i16);
+impl_organization_unit_resource_id_from_ty_tuple!(u8);
+impl_organization_unit_resource_id_from_ty_tuple!(i8);
+
+/// Context of a customer, organization, institution or organization unit,
+/// represented as the prefixed string form of the respective id (e.g. `V01`).
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, async_graphql::Description)]
 pub enum InfraContext {
     Customer(CustomerId),
     Organization(OrganizationId),
     Institution(InstitutionId),
+    OrganizationUnit(OrganizationUnitId),
 }
 
 impl InfraContext {
@@ -815,6 +1166,7 @@ impl InfraContext {
             InfraContext::Customer(b) => b.cid.into(),
             InfraContext::Organization(b) => b.cid.into(),
             InfraContext::Institution(b) => b.cid.into(),
+            InfraContext::OrganizationUnit(b) => b.cid.into(),
         }
     }
 
@@ -823,6 +1175,7 @@ impl InfraContext {
             InfraContext::Customer(_) => None,
             InfraContext::Organization(b) => Some(b.oid.into()),
             InfraContext::Institution(b) => Some(b.oid.into()),
+            InfraContext::OrganizationUnit(b) => b.organization().map(|o| o.oid.into()),
         }
     }
 
@@ -831,6 +1184,16 @@ impl InfraContext {
             InfraContext::Customer(_) => None,
             InfraContext::Organization(_) => None,
             InfraContext::Institution(b) => Some(b.iid.into()),
+            InfraContext::OrganizationUnit(_) => None,
+        }
+    }
+
+    pub fn organization_unit_id(&self) -> Option<InfraId> {
+        match self {
+            InfraContext::Customer(_) => None,
+            InfraContext::Organization(_) => None,
+            InfraContext::Institution(_) => None,
+            InfraContext::OrganizationUnit(b) => Some(b.uid.into()),
         }
     }
 
@@ -839,6 +1202,7 @@ impl InfraContext {
             InfraContext::Customer(_) => true,
             InfraContext::Organization(_) => false,
             InfraContext::Institution(_) => false,
+            InfraContext::OrganizationUnit(_) => false,
         }
     }
 
@@ -847,6 +1211,7 @@ impl InfraContext {
             InfraContext::Customer(_) => false,
             InfraContext::Organization(_) => true,
             InfraContext::Institution(_) => false,
+            InfraContext::OrganizationUnit(_) => false,
         }
     }
 
@@ -855,6 +1220,16 @@ impl InfraContext {
             InfraContext::Customer(_) => false,
             InfraContext::Organization(_) => false,
             InfraContext::Institution(_) => true,
+            InfraContext::OrganizationUnit(_) => false,
+        }
+    }
+
+    pub fn is_organization_unit(&self) -> bool {
+        match self {
+            InfraContext::Customer(_) => false,
+            InfraContext::Organization(_) => false,
+            InfraContext::Institution(_) => false,
+            InfraContext::OrganizationUnit(_) => true,
         }
     }
 
@@ -863,6 +1238,7 @@ impl InfraContext {
             InfraContext::Customer(b) => a.cid == b.cid,
             InfraContext::Organization(b) => a.cid == b.cid,
             InfraContext::Institution(b) => a.cid == b.cid,
+            InfraContext::OrganizationUnit(b) => a.cid == b.cid,
         }
     }
     pub fn has_organization(&self, a: &OrganizationId) -> bool {
@@ -870,6 +1246,7 @@ impl InfraContext {
             InfraContext::Customer(_) => false,
             InfraContext::Organization(b) => a == b,
             InfraContext::Institution(b) => a.cid == b.cid && a.oid == b.oid,
+            InfraContext::OrganizationUnit(b) => a.cid == b.cid && a.oid == b.oid,
         }
     }
     pub fn has_institution(&self, a: &InstitutionId) -> bool {
@@ -877,6 +1254,15 @@ impl InfraContext {
             InfraContext::Customer(_) => false,
             InfraContext::Organization(_) => false,
             InfraContext::Institution(b) => a == b,
+            InfraContext::OrganizationUnit(_) => false,
+        }
+    }
+    pub fn has_organization_unit(&self, a: &OrganizationUnitId) -> bool {
+        match self {
+            InfraContext::Customer(_) => false,
+            InfraContext::Organization(_) => false,
+            InfraContext::Institution(_) => false,
+            InfraContext::OrganizationUnit(b) => a == b,
         }
     }
 
@@ -885,6 +1271,7 @@ impl InfraContext {
             InfraContext::Customer(_) => "customer",
             InfraContext::Organization(_) => "organization",
             InfraContext::Institution(_) => "institution",
+            InfraContext::OrganizationUnit(_) => "organization_unit",
         }
     }
 
@@ -912,6 +1299,13 @@ impl InfraContext {
                     self
                 }
             }
+            InfraContext::OrganizationUnit(v) => {
+                if query_context.has_organization_unit(v) {
+                    query_context
+                } else {
+                    self
+                }
+            }
         }
     }
 }
@@ -922,6 +1316,7 @@ impl std::fmt::Display for InfraContext {
             Self::Customer(v) => v.fmt(f),
             Self::Organization(v) => v.fmt(f),
             Self::Institution(v) => v.fmt(f),
+            Self::OrganizationUnit(v) => v.fmt(f),
         }
     }
 }
@@ -948,6 +1343,12 @@ impl From<InstitutionId> for InfraContext {
     }
 }
 
+impl From<OrganizationUnitId> for InfraContext {
+    fn from(value: OrganizationUnitId) -> Self {
+        InfraContext::OrganizationUnit(value)
+    }
+}
+
 impl<'a> From<&'a CustomerId> for InfraContext {
     fn from(value: &'a CustomerId) -> Self {
         InfraContext::Customer(*value)
@@ -964,6 +1365,31 @@ impl<'a> From<&'a InstitutionId> for InfraContext {
     }
 }
 
+impl<'a> From<&'a OrganizationUnitId> for InfraContext {
+    fn from(value: &'a OrganizationUnitId) -> Self {
+        InfraContext::OrganizationUnit(*value)
+    }
+}
+
+impl_pg_type!(InfraContext, String);
+
+/// Stored as text in the prefixed string form.
+impl<'q> Encode<'q, Postgres> for InfraContext {
+    fn encode_by_ref(
+        &self,
+        buf: &mut PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, BoxDynError> {
+        <String as Encode<Postgres>>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for InfraContext {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let s = <&str as Decode<Postgres>>::decode(value)?;
+        Ok(InfraContext::parse(s)?)
+    }
+}
+
 impl std::str::FromStr for InfraContext {
     type Err = anyhow::Error;
 
@@ -973,6 +1399,9 @@ impl std::str::FromStr for InfraContext {
                 CustomerId::PREFIX => CustomerId::parse(s).map(InfraContext::Customer),
                 OrganizationId::PREFIX => OrganizationId::parse(s).map(InfraContext::Organization),
                 InstitutionId::PREFIX => InstitutionId::parse(s).map(InfraContext::Institution),
+                OrganizationUnitId::PREFIX => {
+                    OrganizationUnitId::parse(s).map(InfraContext::OrganizationUnit)
+                }
                 _ => anyhow::bail!("invalid prefix '{first_char}'"),
             };
         }
@@ -1063,7 +1492,7 @@ struct StringParser<'a, const N: usize> {
     count: usize,
     start: usize,
     end: usize,
-    has_object_id_at_end: bool,
+    id_length: Option<usize>,
     s: &'a str,
 }
 
@@ -1073,13 +1502,13 @@ impl<'a, const N: usize> StringParser<'a, N> {
             count: 0,
             start: 0,
             end: 1,
-            has_object_id_at_end: false,
+            id_length: None,
             s,
         }
     }
 
-    fn with_object_id(mut self) -> Self {
-        self.has_object_id_at_end = true;
+    fn with_id_length(mut self, id_length: usize) -> Self {
+        self.id_length = Some(id_length);
         self
     }
 
@@ -1112,8 +1541,8 @@ impl<'a, const N: usize> Iterator for StringParser<'a, N> {
         self.end = self.start + 1;
         self.count += 1;
         let l = self.s.len();
-        if self.has_object_id_at_end {
-            if self.count == N && self.end + 23 != l {
+        if let Some(id_length) = self.id_length {
+            if self.count == N && self.end + id_length - 1 != l {
                 return None;
             }
         } else if self.count == N && self.end != l + 1 {
@@ -1127,6 +1556,7 @@ impl<'a, const N: usize> Iterator for StringParser<'a, N> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ids::PartialEqual;
 
     #[test]
     fn test_string_parser() {
@@ -1152,6 +1582,8 @@ mod tests {
         assert_eq!('S', OrganizationResourceId::PREFIX);
         assert_eq!('R', InstitutionId::PREFIX);
         assert_eq!('Q', InstitutionResourceId::PREFIX);
+        assert_eq!('P', OrganizationUnitId::PREFIX);
+        assert_eq!('O', OrganizationUnitResourceId::PREFIX);
     }
 
     #[test]
@@ -1162,6 +1594,48 @@ mod tests {
         assert_eq!(None, OrganizationResourceId::parse("T01").ok());
         assert_eq!(None, InstitutionId::parse("Q01").ok());
         assert_eq!(None, InstitutionResourceId::parse("R01").ok());
+        assert_eq!(None, OrganizationUnitId::parse("R010203").ok());
+        assert_eq!(None, OrganizationUnitResourceId::parse("P010203").ok());
+    }
+
+    #[test]
+    #[cfg(feature = "uuid7")]
+    fn test_uuid7_resource_id() {
+        use crate::ids::Uuid7Id;
+        let id = Uuid7Id::new();
+        let rid = InstitutionId::from((1, 2, 3)).resource(id);
+        assert_eq!(rid.to_string(), format!("Q010203{}", id.to_hex()));
+        assert_eq!(rid.to_string().parse::<InstitutionResourceId<Uuid7Id>>().unwrap(), rid);
+        assert_eq!(None, format!("Q010203{}", &id.to_hex()[1..]).parse::<InstitutionResourceId<Uuid7Id>>().ok());
+        assert_eq!(None, rid.to_string().parse::<InstitutionResourceId>().ok());
+        let rid = CustomerResourceId::from((1, id));
+        assert_eq!(rid.to_string().parse::<CustomerResourceId<Uuid7Id>>().unwrap().unzip(), (1, id));
+    }
+
+    #[test]
+    fn test_organization_unit_id() {
+        let id1 = OrganizationUnitId::parse("P010203").unwrap();
+        let id2 = OrganizationUnitId::parse("P12000122").unwrap();
+        assert_eq!(OrganizationUnitId { cid: 1, oid: 2, uid: 3 }, id1);
+        assert_eq!(OrganizationUnitId { cid: 0x20, oid: 0, uid: 0x22 }, id2);
+        assert_eq!(id1.to_string(), "P010203");
+        assert_eq!(id2.to_string(), "P12000122");
+        assert_eq!(id2.organization(), None);
+        assert_eq!(id2.parent(), InfraContext::Customer(CustomerId { cid: 0x20 }));
+        assert_eq!(None, OrganizationUnitId::parse("P0102").ok());
+        assert_eq!(None, OrganizationUnitId::parse("P01020304").ok());
+        let ctx = InfraContext::parse("P010203").unwrap();
+        assert_eq!(ctx, InfraContext::OrganizationUnit(id1));
+        assert!(ctx.has_customer(&CustomerId { cid: 1 }));
+        assert!(ctx.has_organization(&OrganizationId { cid: 1, oid: 2 }));
+        assert!(id1.partial_equal(&CustomerId { cid: 1 }));
+        let ctx = InfraContext::Organization(OrganizationId { cid: 1, oid: 2 });
+        assert_eq!(ctx.combine(InfraContext::OrganizationUnit(id1)), InfraContext::OrganizationUnit(id1));
+        assert_eq!(ctx.combine(InfraContext::OrganizationUnit(id2)), ctx);
+        let rid = id1.resource(ID::from_str("6603f7b32b1753f84a719e01").unwrap());
+        assert_eq!(rid.to_string(), "O0102036603f7b32b1753f84a719e01");
+        assert_eq!(OrganizationUnitResourceId::parse(&rid.to_string()).unwrap(), rid);
+        assert_eq!(rid.parent(), id1);
     }
 
     #[test]
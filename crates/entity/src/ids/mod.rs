@@ -6,3 +6,9 @@ mod infra;
 pub use infra::*;
 mod object;
 pub use object::*;
+mod parse;
+pub use parse::*;
+#[cfg(feature = "uuid7")]
+mod uuid;
+#[cfg(feature = "uuid7")]
+pub use self::uuid::*;
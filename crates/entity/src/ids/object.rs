@@ -1,9 +1,10 @@
-use qm_mongodb::bson::oid::ObjectId;
+pub use qm_mongodb::bson::oid::ObjectId;
 
 use crate::ids::InfraContext;
 
-use super::{CustomerId, InstitutionId, OrganizationId};
+use super::{CustomerId, InstitutionId, OrganizationId, OrganizationUnitId};
 
+/// Id of resources, used for the resource ids of the infrastructure.
 pub type ID = ObjectId;
 
 #[derive(
@@ -26,6 +27,8 @@ pub struct OwnerId {
     pub oid: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub iid: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<i64>,
 }
 
 impl From<CustomerId> for OwnerId {
@@ -55,6 +58,19 @@ impl From<InstitutionId> for OwnerId {
             cid: Some(cid),
             oid: Some(oid),
             iid: Some(iid),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<OrganizationUnitId> for OwnerId {
+    fn from(value: OrganizationUnitId) -> Self {
+        let (cid, oid, uid) = value.unzip();
+        Self {
+            cid: Some(cid),
+            oid: value.organization().map(|_| oid),
+            uid: Some(uid),
+            ..Default::default()
         }
     }
 }
@@ -65,6 +81,7 @@ impl From<InfraContext> for OwnerId {
             InfraContext::Customer(v) => v.into(),
             InfraContext::Organization(v) => v.into(),
             InfraContext::Institution(v) => v.into(),
+            InfraContext::OrganizationUnit(v) => v.into(),
         }
     }
 }
@@ -74,20 +91,31 @@ impl<'a> TryFrom<&'a OwnerId> for InfraContext {
 
     fn try_from(value: &'a OwnerId) -> Result<Self, Self::Error> {
         match value {
+            OwnerId {
+                cid: Some(cid),
+                oid,
+                iid: None,
+                uid: Some(uid),
+            } => Ok(InfraContext::OrganizationUnit(
+                (*cid, oid.unwrap_or_default(), *uid).into(),
+            )),
             OwnerId {
                 cid: Some(cid),
                 oid: Some(oid),
                 iid: Some(iid),
+                uid: None,
             } => Ok(InfraContext::Institution((*cid, *oid, *iid).into())),
             OwnerId {
                 cid: Some(cid),
                 oid: Some(oid),
                 iid: None,
+                uid: None,
             } => Ok(InfraContext::Organization((*cid, *oid).into())),
             OwnerId {
                 cid: Some(cid),
                 oid: None,
                 iid: None,
+                uid: None,
             } => Ok(InfraContext::Customer((*cid).into())),
             _ => anyhow::bail!("invalid owner id"),
         }
@@ -125,6 +153,7 @@ pub enum OwnerType {
     Customer(OwnerId),
     Organization(OwnerId),
     Institution(OwnerId),
+    OrganizationUnit(OwnerId),
 }
 
 impl OwnerType {
@@ -135,9 +164,10 @@ impl OwnerType {
     pub fn as_owner_id(&self) -> Option<&OwnerId> {
         match self {
             OwnerType::None => None,
-            OwnerType::Customer(id) | OwnerType::Organization(id) | OwnerType::Institution(id) => {
-                Some(id)
-            }
+            OwnerType::Customer(id)
+            | OwnerType::Organization(id)
+            | OwnerType::Institution(id)
+            | OwnerType::OrganizationUnit(id) => Some(id),
         }
     }
 }
@@ -148,6 +178,7 @@ impl From<InfraContext> for OwnerType {
             InfraContext::Customer(v) => OwnerType::Customer(v.into()),
             InfraContext::Organization(v) => OwnerType::Organization(v.into()),
             InfraContext::Institution(v) => OwnerType::Institution(v.into()),
+            InfraContext::OrganizationUnit(v) => OwnerType::OrganizationUnit(v.into()),
         }
     }
 }
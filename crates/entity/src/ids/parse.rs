@@ -0,0 +1,167 @@
+use std::{fmt::Display, marker::PhantomData, str::FromStr};
+
+use async_graphql::{validators::CustomValidator, ErrorExtensions, InputValueError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdError {
+    pub index: usize,
+    pub value: String,
+    pub message: String,
+}
+
+/// All invalid entries of a list of ids.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{} invalid id(s): {}", .0.len(), IdErrors::describe(.0))]
+pub struct IdErrors(pub Vec<IdError>);
+
+impl IdErrors {
+    fn describe(errors: &[IdError]) -> String {
+        errors
+            .iter()
+            .map(|e| format!("'{}' at {}", e.value, e.index))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn errors(&self) -> &[IdError] {
+        &self.0
+    }
+}
+
+impl ErrorExtensions for IdErrors {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_err, e| {
+            e.set("code", 400);
+            e.set("error", "BAD_REQUEST");
+            e.set("key", "INVALID_IDS");
+            e.set(
+                "details",
+                async_graphql::Value::List(
+                    self.0
+                        .iter()
+                        .map(|err| {
+                            let mut m = async_graphql::indexmap::IndexMap::new();
+                            m.insert(async_graphql::Name::new("index"), err.index.into());
+                            m.insert(async_graphql::Name::new("value"), err.value.as_str().into());
+                            m.insert(
+                                async_graphql::Name::new("message"),
+                                err.message.as_str().into(),
+                            );
+                            async_graphql::Value::Object(m)
+                        })
+                        .collect(),
+                ),
+            );
+        })
+    }
+}
+
+/// Parses all `values`, collecting every failure with its position instead
+/// of stopping at the first one.
+pub fn parse_many<T>(values: &[String]) -> Result<Vec<T>, IdErrors>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    let mut result = Vec::with_capacity(values.len());
+    let mut errors = vec![];
+    for (index, value) in values.iter().enumerate() {
+        match T::from_str(value) {
+            Ok(id) => result.push(id),
+            Err(err) => errors.push(IdError {
+                index,
+                value: value.clone(),
+                message: err.to_string(),
+            }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(result)
+    } else {
+        Err(IdErrors(errors))
+    }
+}
+
+/// Validates GraphQL list inputs of string ids, e.g.
+/// `#[graphql(validator(custom = "IdsValidator::<CustomerResourceId>::new()"))]`.
+pub struct IdsValidator<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T> IdsValidator<T> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for IdsValidator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CustomValidator<Vec<String>> for IdsValidator<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn check(&self, value: &Vec<String>) -> Result<(), InputValueError<Vec<String>>> {
+        parse_many::<T>(value)
+            .map(|_| ())
+            .map_err(InputValueError::custom)
+    }
+}
+
+/// Validates a single GraphQL string id.
+pub struct IdValidator<T> {
+    _marker: PhantomData<T>,
+}
+
+impl<T> IdValidator<T> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for IdValidator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> CustomValidator<String> for IdValidator<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn check(&self, value: &String) -> Result<(), InputValueError<String>> {
+        T::from_str(value)
+            .map(|_| ())
+            .map_err(InputValueError::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::CustomerId;
+
+    #[test]
+    fn test_parse_many() {
+        let ids = parse_many::<CustomerId>(&["V01".into(), "V120".into()]).unwrap();
+        assert_eq!(ids, vec![CustomerId::from(1), CustomerId::from(0x20)]);
+        let err = parse_many::<CustomerId>(&["V01".into(), "X1".into(), "V".into()]).unwrap_err();
+        assert_eq!(
+            err.errors().iter().map(|e| e.index).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(err.errors()[0].value, "X1");
+        assert!(IdsValidator::<CustomerId>::new()
+            .check(&vec!["V01".into(), "T01".into()])
+            .is_err());
+    }
+}
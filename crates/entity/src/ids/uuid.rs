@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use super::ResourceIdPart;
+
+/// UUIDv7 based id, alternative to the MongoDB `ObjectId` enabled with the
+/// `uuid7` feature, e.g. `CustomerResourceId<Uuid7Id>`.
+///
+/// Encoded as 32 lowercase hex characters, the encoding sorts in creation
+/// order like the one of `ObjectId`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Uuid7Id(uuid::Uuid);
+
+impl Uuid7Id {
+    pub fn new() -> Self {
+        Self(uuid::Uuid::now_v7())
+    }
+
+    pub fn parse_str(s: &str) -> anyhow::Result<Self> {
+        Self::from_str(s)
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.0.simple().to_string()
+    }
+
+    /// Creation time in milliseconds since the unix epoch.
+    pub fn timestamp_millis(&self) -> Option<u64> {
+        self.0.get_timestamp().map(|ts| {
+            let (secs, nanos) = ts.to_unix();
+            secs * 1000 + u64::from(nanos) / 1_000_000
+        })
+    }
+
+    pub fn as_uuid(&self) -> &uuid::Uuid {
+        &self.0
+    }
+}
+
+impl FromStr for Uuid7Id {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(uuid::Uuid::try_parse(s)?))
+    }
+}
+
+impl std::fmt::Display for Uuid7Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0.simple(), f)
+    }
+}
+
+impl From<uuid::Uuid> for Uuid7Id {
+    fn from(value: uuid::Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Uuid7Id> for uuid::Uuid {
+    fn from(value: Uuid7Id) -> Self {
+        value.0
+    }
+}
+
+impl ResourceIdPart for Uuid7Id {
+    const LENGTH: usize = 32;
+
+    fn to_hex(&self) -> String {
+        Uuid7Id::to_hex(self)
+    }
+
+    fn parse_hex(s: &str) -> anyhow::Result<Self> {
+        Self::from_str(s)
+    }
+}
+
+impl serde::Serialize for Uuid7Id {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Uuid7Id {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid7_id() {
+        let a = Uuid7Id::new();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let b = Uuid7Id::new();
+        assert_eq!(a.to_hex().len(), 32);
+        assert!(a.to_hex() < b.to_hex());
+        assert_eq!(Uuid7Id::parse_str(&a.to_string()).unwrap(), a);
+        assert!(a.timestamp_millis().is_some());
+    }
+}
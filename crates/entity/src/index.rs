@@ -0,0 +1,237 @@
+use std::time::Duration;
+
+use qm_mongodb::{
+    bson::{Bson, Document},
+    options::IndexOptions,
+    IndexModel,
+};
+
+pub trait Indexes {
+    fn indexes() -> Vec<IndexSpec>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSpec {
+    pub keys: Document,
+    pub name: Option<String>,
+    pub unique: bool,
+    pub expire_after: Option<Duration>,
+}
+
+impl IndexSpec {
+    pub fn new(keys: Document) -> Self {
+        Self {
+            keys,
+            name: None,
+            unique: false,
+            expire_after: None,
+        }
+    }
+
+    pub fn asc(field: &str) -> Self {
+        let mut keys = Document::new();
+        keys.insert(field, 1);
+        Self::new(keys)
+    }
+
+    pub fn compound<'a>(fields: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut keys = Document::new();
+        for field in fields {
+            keys.insert(field, 1);
+        }
+        Self::new(keys)
+    }
+
+    pub fn text<'a>(fields: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut keys = Document::new();
+        for field in fields {
+            keys.insert(field, "text");
+        }
+        Self::new(keys)
+    }
+
+    pub fn ttl(field: &str, expire_after: Duration) -> Self {
+        Self::asc(field).with_expire_after(expire_after)
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn unique(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    pub fn with_expire_after(mut self, expire_after: Duration) -> Self {
+        self.expire_after = Some(expire_after);
+        self
+    }
+
+    /// Name of the index, either the explicit one or the name MongoDB
+    /// generates by default (`field_1_other_-1`).
+    pub fn index_name(&self) -> String {
+        if let Some(name) = self.name.as_ref() {
+            return name.clone();
+        }
+        self.keys
+            .iter()
+            .map(|(k, v)| match v {
+                Bson::String(s) => format!("{k}_{s}"),
+                Bson::Int32(i) => format!("{k}_{i}"),
+                Bson::Int64(i) => format!("{k}_{i}"),
+                Bson::Double(d) => format!("{k}_{d}"),
+                v => format!("{k}_{v}"),
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    }
+
+    pub fn to_index_model(&self) -> IndexModel {
+        IndexModel::builder()
+            .keys(self.keys.clone())
+            .options(
+                IndexOptions::builder()
+                    .name(self.index_name())
+                    .unique(self.unique.then_some(true))
+                    .expire_after(self.expire_after)
+                    .build(),
+            )
+            .build()
+    }
+
+    fn drift(&self, existing: &IndexModel) -> Option<IndexDrift> {
+        let options = existing.options.as_ref();
+        let unique = options.and_then(|o| o.unique).unwrap_or(false);
+        let expire_after = options.and_then(|o| o.expire_after);
+        let keys_differ = if self.is_text() {
+            // text indexes are stored as `{ _fts: "text", _ftsx: 1 }`
+            !existing.keys.contains_key("_fts")
+        } else {
+            existing.keys != self.keys
+        };
+        if keys_differ || unique != self.unique || expire_after != self.expire_after {
+            Some(IndexDrift {
+                name: self.index_name(),
+                expected: self.to_index_model(),
+                actual: existing.clone(),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        self.keys
+            .values()
+            .any(|v| matches!(v, Bson::String(s) if s == "text"))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexDrift {
+    pub name: String,
+    pub expected: IndexModel,
+    pub actual: IndexModel,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct IndexReport {
+    pub created: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub drift: Vec<IndexDrift>,
+}
+
+impl IndexReport {
+    pub fn has_drift(&self) -> bool {
+        !self.drift.is_empty()
+    }
+}
+
+pub(crate) async fn ensure_indexes<T>(
+    collection: &qm_mongodb::Collection<T>,
+    specs: &[IndexSpec],
+) -> qm_mongodb::error::Result<IndexReport>
+where
+    T: Send + Sync,
+{
+    use futures::stream::TryStreamExt;
+
+    let existing: Vec<IndexModel> = if collection
+        .client()
+        .database(collection.namespace().db.as_str())
+        .list_collection_names()
+        .await?
+        .iter()
+        .any(|name| name == collection.name())
+    {
+        collection.list_indexes().await?.try_collect().await?
+    } else {
+        vec![]
+    };
+    let mut report = IndexReport::default();
+    for spec in specs {
+        let name = spec.index_name();
+        let found = existing.iter().find(|index| {
+            index
+                .options
+                .as_ref()
+                .and_then(|o| o.name.as_deref())
+                .map(|n| n == name)
+                .unwrap_or(false)
+        });
+        if let Some(index) = found {
+            if let Some(drift) = spec.drift(index) {
+                tracing::warn!(
+                    "index '{name}' on collection '{}' differs from its specification",
+                    collection.name()
+                );
+                report.drift.push(drift);
+            } else {
+                report.unchanged.push(name);
+            }
+        } else {
+            collection.create_index(spec.to_index_model()).await?;
+            report.created.push(name);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qm_mongodb::bson::doc;
+
+    #[test]
+    fn test_index_name() {
+        assert_eq!(IndexSpec::asc("name").index_name(), "name_1");
+        assert_eq!(
+            IndexSpec::new(doc! { "owner.cid": 1, "name": -1 }).index_name(),
+            "owner.cid_1_name_-1"
+        );
+        assert_eq!(
+            IndexSpec::text(["name", "description"]).index_name(),
+            "name_text_description_text"
+        );
+        assert_eq!(
+            IndexSpec::asc("name").with_name("by_name").index_name(),
+            "by_name"
+        );
+    }
+
+    #[test]
+    fn test_drift() {
+        let spec = IndexSpec::asc("name").unique();
+        let same = spec.to_index_model();
+        assert!(spec.drift(&same).is_none());
+        let other = IndexSpec::asc("name").to_index_model();
+        assert!(spec.drift(&other).is_some());
+        let ttl = IndexSpec::ttl("createdAt", Duration::from_secs(60));
+        assert!(ttl.drift(&ttl.to_index_model()).is_none());
+        assert!(ttl
+            .drift(&IndexSpec::asc("createdAt").to_index_model())
+            .is_some());
+    }
+}
@@ -9,17 +9,27 @@ use qm_mongodb::{
     results::DeleteResult,
 };
 
-use crate::{
-    ids::ID,
-    model::{ListFilter, ListResult},
-};
+use crate::model::{ListFilter, ListResult};
 
 pub mod ctx;
+pub mod encrypted;
 pub mod error;
 pub mod ids;
+pub mod index;
 pub mod list;
+pub mod loader;
 pub mod model;
 pub mod owned;
+pub mod patch;
+pub mod pipeline;
+pub mod relation;
+pub mod scoped;
+pub mod tenant;
+pub mod transaction;
+pub mod validate;
+pub mod watch;
+
+pub use qm_entity_derive::{entity, m2m, member, o2m, o2o};
 
 pub trait MutatePermissions {
     fn create() -> Self;
@@ -40,9 +50,7 @@ where
 }
 
 pub fn conflicting_name<T>(ty: &str, name: &str) -> Result<T, async_graphql::Error> {
-    Err(conflict(async_graphql::Error::new(format!(
-        "{ty} with the name '{name}' already exists."
-    ))))
+    Err(error::EntityError::NameConflict(ty.into(), name.into()).extend())
 }
 
 pub fn unauthorized<E>(err: E) -> async_graphql::Error
@@ -53,9 +61,7 @@ where
 }
 
 pub fn unauthorized_name<T>(ty: &str, name: &str) -> Result<T, async_graphql::Error> {
-    Err(unauthorized(async_graphql::Error::new(format!(
-        "{ty} '{name}' nicht authorisiert."
-    ))))
+    Err(error::EntityError::unauthorized_name(ty, name).extend())
 }
 
 #[async_trait::async_trait]
@@ -75,6 +81,14 @@ pub trait IsSupport {
     }
 }
 
+/// Suspended or archived entities, the sessions of their users are
+/// rejected by the authorization layer.
+pub trait IsSuspended {
+    fn is_suspended(&self) -> bool {
+        false
+    }
+}
+
 pub trait HasAccess {
     fn has_access(&self, a: &qm_role::Access) -> bool;
 }
@@ -86,6 +100,11 @@ where
 {
     fn has_role(&self, r: &R, p: &P) -> bool;
     fn has_role_object(&self, role: &qm_role::Role<R, P>) -> bool;
+    /// Like [`HasRole::has_role_object`] but with wildcard and admin
+    /// semantics, see [`qm_role::RoleSet`].
+    fn satisfies(&self, role: &qm_role::Role<R, P>) -> bool {
+        self.has_role_object(role)
+    }
 }
 
 pub trait UserId {
@@ -111,6 +130,47 @@ where
     }
 }
 
+impl<T> Collection<T>
+where
+    T: Send + Sync,
+{
+    pub fn scoped(&self, context: Option<ids::InfraContext>) -> scoped::ScopedCollection<'_, T> {
+        scoped::ScopedCollection::new(self, context)
+    }
+
+    pub async fn ensure_indexes(
+        &self,
+        specs: &[index::IndexSpec],
+    ) -> qm_mongodb::error::Result<index::IndexReport> {
+        index::ensure_indexes(self.as_ref(), specs).await
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: index::Indexes + Send + Sync,
+{
+    pub async fn ensure_default_indexes(&self) -> qm_mongodb::error::Result<index::IndexReport> {
+        self.ensure_indexes(&T::indexes()).await
+    }
+}
+
+/// Page, limit and find options of `filter`, at most 1000 items are
+/// returned by default.
+fn page_options(
+    filter: Option<&ListFilter>,
+    projection: Option<Document>,
+) -> (usize, i64, FindOptions) {
+    let limit = filter.and_then(|filter| filter.limit).unwrap_or(1000) as i64;
+    let page = filter.and_then(|filter| filter.page).unwrap_or(0);
+    let mut options = FindOptions::builder()
+        .limit(limit)
+        .skip(page as u64 * limit as u64)
+        .build();
+    options.projection = projection;
+    (page, limit, options)
+}
+
 impl<T> Collection<T>
 where
     T: DeserializeOwned + Send + Sync + Unpin,
@@ -119,6 +179,14 @@ where
         self.as_ref().find_one(doc! { "_id": id }).await
     }
 
+    pub async fn by_ids(&self, ids: &[ObjectId]) -> qm_mongodb::error::Result<Vec<T>> {
+        self.as_ref()
+            .find(doc! { "_id": { "$in": ids } })
+            .await?
+            .try_collect()
+            .await
+    }
+
     pub async fn by_name(&self, name: &str) -> qm_mongodb::error::Result<Option<T>> {
         self.as_ref().find_one(doc! { "name": name }).await
     }
@@ -166,25 +234,100 @@ where
         query: Option<Document>,
         filter: Option<ListFilter>,
     ) -> qm_mongodb::error::Result<ListResult<T>> {
+        self.find_page(query, filter.as_ref(), None).await
+    }
+
+    pub async fn aggregate<R>(
+        &self,
+        pipeline: pipeline::Pipeline,
+    ) -> qm_mongodb::error::Result<Vec<R>>
+    where
+        R: DeserializeOwned + Send + Sync,
+    {
+        self.as_ref()
+            .aggregate(pipeline.into_inner())
+            .with_type::<R>()
+            .await?
+            .try_collect::<Vec<R>>()
+            .await
+    }
+
+    pub async fn aggregate_page<R>(
+        &self,
+        pipeline: pipeline::Pipeline,
+        filter: Option<ListFilter>,
+    ) -> qm_mongodb::error::Result<ListResult<R>>
+    where
+        R: DeserializeOwned + Send + Sync,
+    {
+        let (page, limit) = pipeline::page_and_limit(filter.as_ref());
+        let result = self
+            .aggregate::<pipeline::FacetPage<R>>(pipeline.facet_paginate(filter.as_ref()))
+            .await?
+            .pop();
+        let (items, total) = result
+            .map(|page| {
+                let total = page.total();
+                (page.items, total)
+            })
+            .unwrap_or_default();
+        Ok(ListResult {
+            items,
+            limit: Some(limit as i64),
+            total: Some(total),
+            page: Some(page as i64),
+        })
+    }
+
+    pub async fn by_id_projected<P>(
+        &self,
+        id: &ObjectId,
+        projection: Document,
+    ) -> qm_mongodb::error::Result<Option<P>>
+    where
+        P: DeserializeOwned + Send + Sync + Unpin,
+    {
+        self.as_ref()
+            .clone_with_type::<P>()
+            .find_one(doc! { "_id": id })
+            .projection(projection)
+            .await
+    }
+
+    /// Like [`Collection::list`], only the fields of `projection` are
+    /// fetched and deserialized as `P`.
+    pub async fn list_projected<P>(
+        &self,
+        query: Option<Document>,
+        filter: Option<ListFilter>,
+        projection: Document,
+    ) -> qm_mongodb::error::Result<ListResult<P>>
+    where
+        P: DeserializeOwned + Send + Sync + Unpin,
+    {
+        self.find_page(query, filter.as_ref(), Some(projection))
+            .await
+    }
+
+    async fn find_page<P>(
+        &self,
+        query: Option<Document>,
+        filter: Option<&ListFilter>,
+        projection: Option<Document>,
+    ) -> qm_mongodb::error::Result<ListResult<P>>
+    where
+        P: DeserializeOwned + Send + Sync + Unpin,
+    {
         let query = query.unwrap_or_default();
-        let limit = filter
-            .as_ref()
-            .and_then(|filter| filter.limit.as_ref().copied())
-            .unwrap_or(1000) as i64;
-        let page = filter
-            .as_ref()
-            .and_then(|filter| filter.page.as_ref().copied())
-            .unwrap_or(0);
-        let offset = page as u64 * limit as u64;
+        let (page, limit, options) = page_options(filter, projection);
         let total = self.as_ref().count_documents(query.clone()).await?;
-        let options = FindOptions::builder().limit(limit).skip(offset).build();
-
         let items = self
             .as_ref()
+            .clone_with_type::<P>()
             .find(query)
             .with_options(options)
             .await?
-            .try_collect::<Vec<T>>()
+            .try_collect::<Vec<P>>()
             .await?;
         Ok(ListResult {
             items,
@@ -197,7 +340,7 @@ where
 
 impl<T> Collection<T>
 where
-    T: Serialize + Send + Sync + Unpin + AsMut<Option<ID>>,
+    T: Serialize + Send + Sync + Unpin + AsMut<Option<ObjectId>>,
 {
     pub async fn save(&self, mut value: T) -> qm_mongodb::error::Result<T> {
         let id: qm_mongodb::bson::Bson = self.as_ref().insert_one(&value).await?.inserted_id;
@@ -217,6 +360,8 @@ pub mod __private {
     pub use crate::error::EntityError;
     #[doc(hidden)]
     pub use core::result::Result::Err;
+    pub use qm_mongodb::error::Result as MongoResult;
+    pub use qm_mongodb::Database;
 }
 
 #[macro_export]
@@ -231,3 +376,27 @@ macro_rules! exerr {
         $crate::__private::Err($crate::__private::EntityError::$($arg)*.extend())
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_options() {
+        let (page, limit, options) = page_options(None, None);
+        assert_eq!((page, limit), (0, 1000));
+        assert_eq!(options.skip, Some(0));
+        assert_eq!(options.projection, None);
+
+        let filter = ListFilter {
+            page: Some(2),
+            limit: Some(20),
+        };
+        let projection = doc! { "name": 1, "history": 0 };
+        let (page, limit, options) = page_options(Some(&filter), Some(projection.clone()));
+        assert_eq!((page, limit), (2, 20));
+        assert_eq!(options.limit, Some(20));
+        assert_eq!(options.skip, Some(40));
+        assert_eq!(options.projection, Some(projection));
+    }
+}
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, FieldResult, SchemaBuilder,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{ids::ObjectId, Collection};
+
+/// Batches `by_id` lookups of all resolvers within one request into a
+/// single `$in` query against the collection.
+pub struct EntityLoader<T>
+where
+    T: Send + Sync,
+{
+    collection: Collection<T>,
+}
+
+impl<T> EntityLoader<T>
+where
+    T: Send + Sync,
+{
+    pub fn new(collection: Collection<T>) -> Self {
+        Self { collection }
+    }
+}
+
+impl<T> Loader<ObjectId> for EntityLoader<T>
+where
+    T: AsRef<Option<ObjectId>> + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    type Value = T;
+    type Error = Arc<qm_mongodb::error::Error>;
+
+    async fn load(&self, keys: &[ObjectId]) -> Result<HashMap<ObjectId, Self::Value>, Self::Error> {
+        Ok(self
+            .collection
+            .by_ids(keys)
+            .await
+            .map_err(Arc::new)?
+            .into_iter()
+            .filter_map(|entity| {
+                let id: Option<ObjectId> = *AsRef::<Option<ObjectId>>::as_ref(&entity);
+                id.map(|id| (id, entity))
+            })
+            .collect())
+    }
+}
+
+/// Installs an [`EntityLoader`] for `collection` into the schema.
+pub fn register<T, Q, M, S>(
+    builder: SchemaBuilder<Q, M, S>,
+    collection: Collection<T>,
+) -> SchemaBuilder<Q, M, S>
+where
+    T: AsRef<Option<ObjectId>> + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    builder.data(DataLoader::new(EntityLoader::new(collection), tokio::spawn))
+}
+
+pub async fn load_one<T>(ctx: &Context<'_>, id: &ObjectId) -> FieldResult<Option<T>>
+where
+    T: AsRef<Option<ObjectId>> + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    let loader = ctx.data::<DataLoader<EntityLoader<T>>>()?;
+    Ok(loader.load_one(*id).await?)
+}
+
+pub async fn load_many<T>(ctx: &Context<'_>, ids: &[ObjectId]) -> FieldResult<HashMap<ObjectId, T>>
+where
+    T: AsRef<Option<ObjectId>> + DeserializeOwned + Clone + Send + Sync + Unpin + 'static,
+{
+    let loader = ctx.data::<DataLoader<EntityLoader<T>>>()?;
+    Ok(loader.load_many(ids.iter().copied()).await?)
+}
@@ -17,11 +17,12 @@ use qm_mongodb::{
 use crate::{
     error::EntityError,
     ids::{
-        CustomerId, CustomerOrOrganization, CustomerResourceId, InstitutionId,
+        CustomerId, CustomerOrOrganization, CustomerResourceId, InfraContext, InstitutionId,
         InstitutionResourceId, OrganizationId, OrganizationOrInstitution, OrganizationResourceId,
-        OwnerId,
+        OrganizationUnitId, OwnerId,
     },
     model::ListFilter,
+    validate::Validate,
 };
 
 const EMPTY_ID: &str = "000000000000000000000000";
@@ -153,6 +154,13 @@ impl ToMongoFilterMany for InstitutionId {
     }
 }
 
+impl ToMongoFilterMany for OrganizationUnitId {
+    fn to_mongo_filter_many(&self) -> Option<Document> {
+        let (cid, _, uid) = self.unzip();
+        Some(doc! { "owner.cid": cid, "owner.uid": uid })
+    }
+}
+
 impl ToMongoFilterMany for CustomerOrOrganization {
     fn to_mongo_filter_many(&self) -> Option<Document> {
         match self {
@@ -171,6 +179,121 @@ impl ToMongoFilterMany for OrganizationOrInstitution {
     }
 }
 
+impl ToMongoFilterMany for InfraContext {
+    fn to_mongo_filter_many(&self) -> Option<Document> {
+        OwnerQuery::new(*self).to_mongo_filter_many()
+    }
+}
+
+/// Filter for the entities owned by an [`InfraContext`] and, unless
+/// [`OwnerQuery::exact`] is set, by the contexts below it, e.g. all entities
+/// of an organization and its institutions.
+///
+/// Entities store their owner either flat in `owner` ([`EntityOwned`]) or
+/// in a nested path like `owner.entityId`, every path added with
+/// [`OwnerQuery::with_path`] is matched as alternative with `$or`.
+#[derive(Debug, Clone)]
+pub struct OwnerQuery {
+    context: InfraContext,
+    paths: Vec<Cow<'static, str>>,
+    exact: bool,
+}
+
+impl OwnerQuery {
+    pub fn new(context: InfraContext) -> Self {
+        Self {
+            context,
+            paths: vec![Cow::Borrowed("owner")],
+            exact: false,
+        }
+    }
+
+    /// Replaces the owner paths.
+    pub fn with_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Cow<'static, str>>,
+    {
+        self.paths = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds an alternative owner path.
+    pub fn with_path(mut self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Only matches entities owned by the context itself.
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = exact;
+        self
+    }
+
+    fn owner(&self, p: &str) -> Document {
+        let absent = doc! { "$exists": false };
+        let mut query = Document::new();
+        match self.context {
+            InfraContext::Customer(v) => {
+                query.insert(format!("{p}.cid"), v.unzip());
+                if self.exact {
+                    query.insert(format!("{p}.oid"), absent.clone());
+                    query.insert(format!("{p}.iid"), absent.clone());
+                    query.insert(format!("{p}.uid"), absent);
+                }
+            }
+            InfraContext::Organization(v) => {
+                let (cid, oid) = v.unzip();
+                query.insert(format!("{p}.cid"), cid);
+                query.insert(format!("{p}.oid"), oid);
+                if self.exact {
+                    query.insert(format!("{p}.iid"), absent.clone());
+                    query.insert(format!("{p}.uid"), absent);
+                }
+            }
+            InfraContext::Institution(v) => {
+                let (cid, oid, iid) = v.unzip();
+                query.insert(format!("{p}.cid"), cid);
+                query.insert(format!("{p}.oid"), oid);
+                query.insert(format!("{p}.iid"), iid);
+                if self.exact {
+                    query.insert(format!("{p}.uid"), absent);
+                }
+            }
+            InfraContext::OrganizationUnit(v) => {
+                let (cid, _, uid) = v.unzip();
+                query.insert(format!("{p}.cid"), cid);
+                query.insert(format!("{p}.uid"), uid);
+            }
+        }
+        query
+    }
+
+    pub fn to_document(&self) -> Document {
+        let mut owners: Vec<Document> = self.paths.iter().map(|p| self.owner(p)).collect();
+        if owners.len() == 1 {
+            owners.pop().unwrap()
+        } else {
+            doc! { "$or": owners }
+        }
+    }
+
+    /// Combines the owner filter with the `query` of a list resolver.
+    pub fn filter(&self, query: Document) -> Document {
+        if query.is_empty() {
+            self.to_document()
+        } else {
+            doc! { "$and": [self.to_document(), query] }
+        }
+    }
+}
+
+impl ToMongoFilterMany for OwnerQuery {
+    fn to_mongo_filter_many(&self) -> Option<Document> {
+        Some(self.to_document())
+    }
+}
+
 pub trait ToMongoFilterOne {
     fn to_mongo_filter_one(&self) -> Document;
 }
@@ -353,6 +476,24 @@ pub trait UpdateEntity<T: Clone> {
     fn update_entity(self, entity: &T) -> Result<Cow<T>, EntityError>;
 }
 
+/// Validates the result of the wrapped update, see
+/// [`EntityOwned::update_validated`].
+struct ValidatedUpdate<U>(U);
+
+impl<T, U> UpdateEntity<T> for ValidatedUpdate<U>
+where
+    T: Clone + Validate,
+    U: UpdateEntity<T>,
+{
+    fn update_entity(self, entity: &T) -> Result<Cow<T>, EntityError> {
+        let updated = self.0.update_entity(entity)?;
+        if let Cow::Owned(updated) = &updated {
+            updated.validate()?;
+        }
+        Ok(updated)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct EntityOwned<T, ID = Id> {
     #[serde(rename = "_id")]
@@ -643,6 +784,75 @@ where
     }
 }
 
+impl<T> EntityOwned<T>
+where
+    T: DeserializeOwned + Serialize + MongoCollection + Validate + Send + Sync + Unpin,
+{
+    /// Validates `fields` and inserts them as new entity.
+    pub async fn create_validated(
+        db: &Database,
+        owner: impl Into<OwnerId>,
+        fields: T,
+        user_id: Uuid,
+    ) -> Result<Self, EntityError> {
+        fields.validate()?;
+        Self::create(db, owner, fields, user_id).await
+    }
+}
+
+impl<T, ID> EntityOwned<T, ID>
+where
+    T: DeserializeOwned + Serialize + MongoCollection + Validate + Send + Sync + Unpin,
+    ID: DeserializeOwned + Serialize + Send + Sync + Unpin,
+{
+    /// Like [`EntityOwned::update`], the updated fields are validated
+    /// before they are saved.
+    pub async fn update_validated(
+        db: &Database,
+        context: impl ToMongoFilterOne,
+        input: impl UpdateEntity<T>,
+        user_id: Uuid,
+    ) -> Result<Self, EntityError>
+    where
+        T: Clone,
+    {
+        Self::update(db, context, ValidatedUpdate(input), user_id).await
+    }
+
+    /// Validates `input` and saves it like [`EntityOwned::save`].
+    pub async fn save_validated<C>(
+        db: &Database,
+        context: C,
+        input: impl Into<T>,
+        user_id: Uuid,
+    ) -> Result<bool, EntityError>
+    where
+        T: Clone + std::fmt::Debug,
+        C: ToMongoFilterOne + Into<OwnerId>,
+    {
+        let fields: T = input.into();
+        fields.validate()?;
+        Self::save(db, context, fields, user_id).await
+    }
+
+    /// Validates `input` and saves it like [`EntityOwned::save_with_id`].
+    pub async fn save_with_id_validated<C, I>(
+        db: &Database,
+        context: C,
+        input: I,
+        user_id: Uuid,
+    ) -> Result<Option<C>, EntityError>
+    where
+        T: Clone + std::fmt::Debug,
+        C: FromMongoId + IsMongoInsert + ToMongoFilterOne + Into<OwnerId> + Clone,
+        I: Into<T> + Send + Sync,
+    {
+        let fields: T = input.into();
+        fields.validate()?;
+        Self::save_with_id(db, context, fields, user_id).await
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Defaults {
     pub created: UserModification,
@@ -710,3 +920,78 @@ pub trait MongoCollection {
         db.collection(Self::COLLECTION)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_query() {
+        let oid: OrganizationId = (1_i64, 2_i64).into();
+        let context = InfraContext::Organization(oid);
+        assert_eq!(
+            context.to_mongo_filter_many(),
+            Some(doc! { "owner.cid": 1_i64, "owner.oid": 2_i64 })
+        );
+        assert_eq!(
+            OwnerQuery::new(context)
+                .with_path("owner.entityId")
+                .to_document(),
+            doc! { "$or": [
+                { "owner.cid": 1_i64, "owner.oid": 2_i64 },
+                { "owner.entityId.cid": 1_i64, "owner.entityId.oid": 2_i64 },
+            ] }
+        );
+        assert_eq!(
+            OwnerQuery::new(context)
+                .exact(true)
+                .filter(doc! { "name": "a" }),
+            doc! { "$and": [
+                {
+                    "owner.cid": 1_i64,
+                    "owner.oid": 2_i64,
+                    "owner.iid": { "$exists": false },
+                    "owner.uid": { "$exists": false },
+                },
+                { "name": "a" },
+            ] }
+        );
+    }
+    #[test]
+    fn test_validated_update() {
+        #[derive(Clone)]
+        struct Name(String);
+
+        impl Validate for Name {
+            fn validate(&self) -> Result<(), crate::validate::ValidationErrors> {
+                let mut errors = crate::validate::ValidationErrors::new();
+                if self.0.is_empty() {
+                    errors.add("name", crate::validate::ValidationError::new("LENGTH"));
+                }
+                errors.into_result()
+            }
+        }
+
+        struct Rename(&'static str);
+
+        impl UpdateEntity<Name> for Rename {
+            fn update_entity(self, entity: &Name) -> Result<Cow<Name>, EntityError> {
+                Ok(if entity.0 == self.0 {
+                    Cow::Borrowed(entity)
+                } else {
+                    Cow::Owned(Name(self.0.to_string()))
+                })
+            }
+        }
+
+        let name = Name("a".to_string());
+        assert!(ValidatedUpdate(Rename("b")).update_entity(&name).is_ok());
+        assert!(matches!(
+            ValidatedUpdate(Rename("")).update_entity(&name),
+            Err(EntityError::Validation(_))
+        ));
+        // unchanged fields are not validated
+        let empty = Name(String::new());
+        assert!(ValidatedUpdate(Rename("")).update_entity(&empty).is_ok());
+    }
+}
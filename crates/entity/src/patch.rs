@@ -0,0 +1,286 @@
+//! Applies update inputs onto models.
+//!
+//! `#[derive(Patch)]` implements [`Patch`] for an input whose fields mirror
+//! the fields of the target:
+//!
+//! - `MaybeUndefined<V>` onto `Option<V>`, undefined keeps the value, null
+//!   clears it and a value replaces it.
+//! - `Option<V>` onto `V`, `None` keeps the value.
+//! - `#[patch(nested)]` on `Option<P>` with `P: Patch<V>` onto `V`.
+//! - `#[patch(skip)]` ignores a field, e.g. the id of the entity.
+//!
+//! ```ignore
+//! #[derive(Patch)]
+//! #[patch(target = "Person")]
+//! struct UpdatePersonInput {
+//!     firstname: Option<String>,
+//!     middlename: MaybeUndefined<String>,
+//! }
+//! ```
+//!
+//! Only fields whose value differs are reported as changed, the update
+//! document built by [`PatchChanges::to_update`] contains just those.
+
+use async_graphql::MaybeUndefined;
+use qm_mongodb::bson::{self, Bson, Document};
+use serde::Serialize;
+
+use crate::error::EntityError;
+
+pub use qm_entity_derive::Patch;
+
+pub trait Patch<T> {
+    fn apply_to(self, target: &mut T) -> PatchChanges;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldChange {
+    Set,
+    Unset,
+}
+
+/// Applies a single input field onto the field of the target.
+pub trait PatchField<T> {
+    fn patch_field(self, target: &mut T) -> Option<FieldChange>;
+}
+
+impl<V: PartialEq> PatchField<Option<V>> for MaybeUndefined<V> {
+    fn patch_field(self, target: &mut Option<V>) -> Option<FieldChange> {
+        match self {
+            MaybeUndefined::Undefined => None,
+            MaybeUndefined::Null => target.take().map(|_| FieldChange::Unset),
+            MaybeUndefined::Value(value) => {
+                if target.as_ref() == Some(&value) {
+                    return None;
+                }
+                *target = Some(value);
+                Some(FieldChange::Set)
+            }
+        }
+    }
+}
+
+impl<V: PartialEq> PatchField<V> for Option<V> {
+    fn patch_field(self, target: &mut V) -> Option<FieldChange> {
+        match self {
+            Some(value) if *target != value => {
+                *target = value;
+                Some(FieldChange::Set)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Fields changed by [`Patch::apply_to`], as serialized field names with
+/// nested fields separated by dots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchChanges {
+    set: Vec<String>,
+    unset: Vec<String>,
+}
+
+impl PatchChanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.unset.is_empty()
+    }
+
+    pub fn set_fields(&self) -> &[String] {
+        &self.set
+    }
+
+    pub fn unset_fields(&self) -> &[String] {
+        &self.unset
+    }
+
+    pub fn add(&mut self, field: impl Into<String>, change: Option<FieldChange>) {
+        match change {
+            Some(FieldChange::Set) => self.set.push(field.into()),
+            Some(FieldChange::Unset) => self.unset.push(field.into()),
+            None => {}
+        }
+    }
+
+    /// Adds the changes of a nested patch with their fields prefixed by
+    /// `field`.
+    pub fn nest(&mut self, field: &str, changes: PatchChanges) {
+        self.set
+            .extend(changes.set.into_iter().map(|f| format!("{field}.{f}")));
+        self.unset
+            .extend(changes.unset.into_iter().map(|f| format!("{field}.{f}")));
+    }
+
+    /// Update document with `$set` and `$unset` for the changed fields,
+    /// `None` if nothing changed.
+    ///
+    /// The values are taken from the serialized `target`, so renamed and
+    /// encrypted fields are stored like on insert. Fields skipped during
+    /// serialization are unset.
+    pub fn to_update<T: Serialize>(&self, target: &T) -> Result<Option<Document>, EntityError> {
+        self.to_update_with_prefix(target, None)
+    }
+
+    /// Like [`PatchChanges::to_update`] for a target stored under `prefix`,
+    /// e.g. flattened entities with their fields in the root document pass
+    /// `None`, embedded documents their field name.
+    pub fn to_update_with_prefix<T: Serialize>(
+        &self,
+        target: &T,
+        prefix: Option<&str>,
+    ) -> Result<Option<Document>, EntityError> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let serialized =
+            bson::to_document(target).map_err(|err| EntityError::Bson(err.to_string()))?;
+        let path = |field: &str| match prefix {
+            Some(prefix) => format!("{prefix}.{field}"),
+            None => field.to_string(),
+        };
+        let mut set = Document::new();
+        let mut unset = Document::new();
+        for field in self.set.iter() {
+            match lookup(&serialized, field) {
+                Some(value) if value != &Bson::Null => {
+                    set.insert(path(field), value.clone());
+                }
+                _ => {
+                    unset.insert(path(field), "");
+                }
+            }
+        }
+        for field in self.unset.iter() {
+            unset.insert(path(field), "");
+        }
+        let mut update = Document::new();
+        if !set.is_empty() {
+            update.insert("$set", set);
+        }
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        Ok(Some(update))
+    }
+}
+
+fn lookup<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    let (head, tail) = match path.split_once('.') {
+        Some((head, tail)) => (head, Some(tail)),
+        None => (path, None),
+    };
+    let value = document.get(head)?;
+    match tail {
+        Some(tail) => lookup(value.as_document()?, tail),
+        None => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use qm_mongodb::bson::doc;
+
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Address {
+        zip_code: Option<String>,
+        city: Option<String>,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Person {
+        first_name: String,
+        middle_name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        address: Address,
+        notes: Vec<String>,
+    }
+
+    #[derive(Patch)]
+    #[patch(target = "Address")]
+    struct UpdateAddressInput {
+        zip_code: MaybeUndefined<String>,
+        city: MaybeUndefined<String>,
+    }
+
+    #[derive(Patch)]
+    #[patch(target = "Person")]
+    struct UpdatePersonInput {
+        #[patch(skip)]
+        #[allow(dead_code)]
+        id: String,
+        first_name: Option<String>,
+        middle_name: MaybeUndefined<String>,
+        title: MaybeUndefined<String>,
+        #[patch(nested)]
+        address: Option<UpdateAddressInput>,
+        #[patch(rename = "notes")]
+        notes: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn test_apply_to() -> Result<(), EntityError> {
+        let mut person = Person {
+            first_name: "Max".into(),
+            middle_name: Some("M".into()),
+            title: None,
+            address: Address {
+                zip_code: Some("12345".into()),
+                city: Some("Berlin".into()),
+            },
+            notes: vec![],
+        };
+        let changes = UpdatePersonInput {
+            id: "1".into(),
+            first_name: Some("Max".into()),
+            middle_name: MaybeUndefined::Null,
+            title: MaybeUndefined::Value("Dr.".into()),
+            address: Some(UpdateAddressInput {
+                zip_code: MaybeUndefined::Undefined,
+                city: MaybeUndefined::Value("Hamburg".into()),
+            }),
+            notes: None,
+        }
+        .apply_to(&mut person);
+        assert_eq!(person.middle_name, None);
+        assert_eq!(person.title.as_deref(), Some("Dr."));
+        assert_eq!(person.address.city.as_deref(), Some("Hamburg"));
+        assert_eq!(person.address.zip_code.as_deref(), Some("12345"));
+        assert_eq!(changes.set_fields(), ["title", "address.city"]);
+        assert_eq!(changes.unset_fields(), ["middleName"]);
+        assert_eq!(
+            changes.to_update(&person)?,
+            Some(doc! {
+                "$set": { "title": "Dr.", "address.city": "Hamburg" },
+                "$unset": { "middleName": "" },
+            })
+        );
+        assert_eq!(
+            changes.to_update_with_prefix(&person, Some("person"))?,
+            Some(doc! {
+                "$set": { "person.title": "Dr.", "person.address.city": "Hamburg" },
+                "$unset": { "person.middleName": "" },
+            })
+        );
+
+        let changes = UpdatePersonInput {
+            id: "1".into(),
+            first_name: None,
+            middle_name: MaybeUndefined::Undefined,
+            title: MaybeUndefined::Value("Dr.".into()),
+            address: None,
+            notes: None,
+        }
+        .apply_to(&mut person);
+        assert!(changes.is_empty());
+        assert_eq!(changes.to_update(&person)?, None);
+        Ok(())
+    }
+}
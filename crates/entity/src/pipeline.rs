@@ -0,0 +1,172 @@
+use qm_mongodb::bson::{doc, Document};
+use serde::Deserialize;
+
+use crate::{model::ListFilter, owned::ToMongoFilterMany};
+
+const DEFAULT_LIMIT: usize = 1000;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Pipeline {
+    stages: Vec<Document>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stage(mut self, stage: Document) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    pub fn match_query(self, query: Document) -> Self {
+        self.stage(doc! { "$match": query })
+    }
+
+    /// Restricts the documents to the owner given by `ctx`, does nothing if
+    /// the context doesn't produce a filter (e.g. admin without context).
+    pub fn match_owner(self, ctx: &impl ToMongoFilterMany) -> Self {
+        if let Some(filter) = ctx.to_mongo_filter_many() {
+            self.match_query(filter)
+        } else {
+            self
+        }
+    }
+
+    pub fn lookup(self, from: &str, local_field: &str, foreign_field: &str, as_: &str) -> Self {
+        self.stage(doc! {
+            "$lookup": {
+                "from": from,
+                "localField": local_field,
+                "foreignField": foreign_field,
+                "as": as_,
+            }
+        })
+    }
+
+    pub fn unwind(self, path: &str) -> Self {
+        self.stage(doc! { "$unwind": format!("${path}") })
+    }
+
+    pub fn sort(self, sort: Document) -> Self {
+        self.stage(doc! { "$sort": sort })
+    }
+
+    pub fn project(self, projection: Document) -> Self {
+        self.stage(doc! { "$project": projection })
+    }
+
+    /// Groups by `field` and counts the documents per group, results are
+    /// deserializable as [`GroupCount`].
+    pub fn group_count(self, field: &str) -> Self {
+        self.stage(doc! {
+            "$group": {
+                "_id": format!("${field}"),
+                "count": { "$sum": 1 },
+            }
+        })
+    }
+
+    /// Adds a `$facet` stage returning a single [`FacetPage`] with the
+    /// requested page and the total count of the matched documents.
+    pub fn facet_paginate(self, filter: Option<&ListFilter>) -> Self {
+        let (page, limit) = page_and_limit(filter);
+        self.stage(doc! {
+            "$facet": {
+                "items": [
+                    { "$skip": (page * limit) as i64 },
+                    { "$limit": limit as i64 },
+                ],
+                "total": [
+                    { "$count": "count" },
+                ],
+            }
+        })
+    }
+
+    pub fn stages(&self) -> &[Document] {
+        &self.stages
+    }
+
+    pub fn into_inner(self) -> Vec<Document> {
+        self.stages
+    }
+}
+
+impl From<Pipeline> for Vec<Document> {
+    fn from(value: Pipeline) -> Self {
+        value.stages
+    }
+}
+
+pub(crate) fn page_and_limit(filter: Option<&ListFilter>) -> (usize, usize) {
+    let page = filter.and_then(|f| f.page).unwrap_or(0);
+    let limit = filter.and_then(|f| f.limit).unwrap_or(DEFAULT_LIMIT);
+    (page, limit)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GroupCount<K> {
+    #[serde(rename = "_id")]
+    pub key: K,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FacetTotal {
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FacetPage<T> {
+    pub items: Vec<T>,
+    pub total: Vec<FacetTotal>,
+}
+
+impl<T> FacetPage<T> {
+    pub fn total(&self) -> i64 {
+        self.total.first().map(|t| t.count).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::CustomerId;
+
+    #[test]
+    fn test_match_owner() {
+        let cid = CustomerId::from(1_i64);
+        let pipeline = Pipeline::new().match_owner(&cid);
+        assert_eq!(
+            pipeline.stages(),
+            &[doc! { "$match": { "owner.cid": 1_i64 } }]
+        );
+        let pipeline = Pipeline::new().match_owner(&None::<CustomerId>);
+        assert!(pipeline.stages().is_empty());
+    }
+
+    #[test]
+    fn test_facet_paginate() {
+        let filter = ListFilter {
+            page: Some(2),
+            limit: Some(10),
+        };
+        let pipeline = Pipeline::new()
+            .group_count("ty")
+            .facet_paginate(Some(&filter));
+        assert_eq!(
+            pipeline.into_inner(),
+            vec![
+                doc! { "$group": { "_id": "$ty", "count": { "$sum": 1 } } },
+                doc! {
+                    "$facet": {
+                        "items": [{ "$skip": 20_i64 }, { "$limit": 10_i64 }],
+                        "total": [{ "$count": "count" }],
+                    }
+                },
+            ]
+        );
+    }
+}
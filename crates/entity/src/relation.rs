@@ -0,0 +1,300 @@
+use std::{collections::HashMap, marker::PhantomData, sync::Arc};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, FieldResult, SchemaBuilder,
+};
+use futures::stream::TryStreamExt;
+use qm_mongodb::{
+    bson::{doc, to_bson, Document},
+    Database,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    ids::{ObjectId, Owner},
+    index::{IndexReport, IndexSpec},
+    owned::MongoCollection,
+    Collection,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelationKind {
+    OneToOne,
+    OneToMany,
+    ManyToMany,
+}
+
+pub trait RelationEntity:
+    MongoCollection + AsRef<Option<ObjectId>> + DeserializeOwned + Clone + Send + Sync + Unpin + 'static
+{
+}
+
+impl<T> RelationEntity for T where
+    T: MongoCollection
+        + AsRef<Option<ObjectId>>
+        + DeserializeOwned
+        + Clone
+        + Send
+        + Sync
+        + Unpin
+        + 'static
+{
+}
+
+/// Relation between two entities, stored as link documents in a join
+/// collection. Implemented by the `m2m!`, `o2m!` and `o2o!` macros.
+pub trait Relation: Send + Sync + 'static {
+    type Left: RelationEntity;
+    type Right: RelationEntity;
+    const KIND: RelationKind;
+    const COLLECTION: &'static str;
+    const LEFT_FIELD: &'static str;
+    const RIGHT_FIELD: &'static str;
+}
+
+fn links<R: Relation>(db: &Database) -> qm_mongodb::Collection<Document> {
+    db.collection(R::COLLECTION)
+}
+
+pub fn indexes<R: Relation>() -> Vec<IndexSpec> {
+    match R::KIND {
+        RelationKind::ManyToMany => vec![
+            IndexSpec::compound([R::LEFT_FIELD, R::RIGHT_FIELD]).unique(),
+            IndexSpec::asc(R::RIGHT_FIELD),
+        ],
+        RelationKind::OneToMany => vec![
+            IndexSpec::asc(R::LEFT_FIELD),
+            IndexSpec::asc(R::RIGHT_FIELD).unique(),
+        ],
+        RelationKind::OneToOne => vec![
+            IndexSpec::asc(R::LEFT_FIELD).unique(),
+            IndexSpec::asc(R::RIGHT_FIELD).unique(),
+        ],
+    }
+}
+
+pub async fn ensure_indexes<R: Relation>(db: &Database) -> qm_mongodb::error::Result<IndexReport> {
+    Collection(links::<R>(db))
+        .ensure_indexes(&indexes::<R>())
+        .await
+}
+
+/// Links `left` with `right`, existing links violating the cardinality of
+/// the relation are replaced.
+pub async fn add<R: Relation>(
+    db: &Database,
+    owner: &Owner,
+    left: ObjectId,
+    right: ObjectId,
+) -> qm_mongodb::error::Result<()> {
+    let (l, r) = (R::LEFT_FIELD, R::RIGHT_FIELD);
+    let collection = links::<R>(db);
+    match R::KIND {
+        RelationKind::ManyToMany => {}
+        RelationKind::OneToMany => {
+            collection
+                .delete_many(doc! { r: right, l: { "$ne": left } })
+                .await?;
+        }
+        RelationKind::OneToOne => {
+            collection
+                .delete_many(doc! {
+                    "$or": [
+                        { l: left, r: { "$ne": right } },
+                        { r: right, l: { "$ne": left } },
+                    ]
+                })
+                .await?;
+        }
+    }
+    let owner = to_bson(owner)?;
+    collection
+        .update_one(
+            doc! { l: left, r: right },
+            doc! { "$setOnInsert": { "owner": owner } },
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+pub async fn remove<R: Relation>(
+    db: &Database,
+    left: ObjectId,
+    right: ObjectId,
+) -> qm_mongodb::error::Result<u64> {
+    let (l, r) = (R::LEFT_FIELD, R::RIGHT_FIELD);
+    Ok(links::<R>(db)
+        .delete_many(doc! { l: left, r: right })
+        .await?
+        .deleted_count)
+}
+
+pub async fn remove_left<R: Relation>(
+    db: &Database,
+    left: ObjectId,
+) -> qm_mongodb::error::Result<u64> {
+    let l = R::LEFT_FIELD;
+    Ok(links::<R>(db)
+        .delete_many(doc! { l: left })
+        .await?
+        .deleted_count)
+}
+
+pub async fn remove_right<R: Relation>(
+    db: &Database,
+    right: ObjectId,
+) -> qm_mongodb::error::Result<u64> {
+    let r = R::RIGHT_FIELD;
+    Ok(links::<R>(db)
+        .delete_many(doc! { r: right })
+        .await?
+        .deleted_count)
+}
+
+async fn resolve<R, T>(
+    db: &Database,
+    keys: &[ObjectId],
+    from: &str,
+    to: &str,
+) -> qm_mongodb::error::Result<HashMap<ObjectId, Vec<T>>>
+where
+    R: Relation,
+    T: RelationEntity,
+{
+    let pairs: Vec<(ObjectId, ObjectId)> = links::<R>(db)
+        .find(doc! { from: { "$in": keys } })
+        .await?
+        .try_collect::<Vec<Document>>()
+        .await?
+        .iter()
+        .filter_map(|link| {
+            link.get_object_id(from)
+                .ok()
+                .zip(link.get_object_id(to).ok())
+        })
+        .collect();
+    let ids: Vec<ObjectId> = pairs.iter().map(|(_, id)| *id).collect();
+    let entities: HashMap<ObjectId, T> = T::mongo_collection::<T>(db)
+        .find(doc! { "_id": { "$in": ids } })
+        .await?
+        .try_collect::<Vec<T>>()
+        .await?
+        .into_iter()
+        .filter_map(|entity| {
+            let id: Option<ObjectId> = *AsRef::<Option<ObjectId>>::as_ref(&entity);
+            id.map(|id| (id, entity))
+        })
+        .collect();
+    let mut result: HashMap<ObjectId, Vec<T>> = HashMap::with_capacity(keys.len());
+    for (key, id) in pairs {
+        if let Some(entity) = entities.get(&id) {
+            result.entry(key).or_default().push(entity.clone());
+        }
+    }
+    Ok(result)
+}
+
+pub async fn rights<R: Relation>(
+    db: &Database,
+    lefts: &[ObjectId],
+) -> qm_mongodb::error::Result<HashMap<ObjectId, Vec<R::Right>>> {
+    resolve::<R, R::Right>(db, lefts, R::LEFT_FIELD, R::RIGHT_FIELD).await
+}
+
+pub async fn lefts<R: Relation>(
+    db: &Database,
+    rights: &[ObjectId],
+) -> qm_mongodb::error::Result<HashMap<ObjectId, Vec<R::Left>>> {
+    resolve::<R, R::Left>(db, rights, R::RIGHT_FIELD, R::LEFT_FIELD).await
+}
+
+/// Loads the right side entities of a relation by the id of the left side.
+pub struct RightLoader<R> {
+    db: Database,
+    _marker: PhantomData<R>,
+}
+
+impl<R> RightLoader<R> {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Relation> Loader<ObjectId> for RightLoader<R> {
+    type Value = Vec<R::Right>;
+    type Error = Arc<qm_mongodb::error::Error>;
+
+    async fn load(&self, keys: &[ObjectId]) -> Result<HashMap<ObjectId, Self::Value>, Self::Error> {
+        rights::<R>(&self.db, keys).await.map_err(Arc::new)
+    }
+}
+
+/// Loads the left side entities of a relation by the id of the right side.
+pub struct LeftLoader<R> {
+    db: Database,
+    _marker: PhantomData<R>,
+}
+
+impl<R> LeftLoader<R> {
+    pub fn new(db: Database) -> Self {
+        Self {
+            db,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R: Relation> Loader<ObjectId> for LeftLoader<R> {
+    type Value = Vec<R::Left>;
+    type Error = Arc<qm_mongodb::error::Error>;
+
+    async fn load(&self, keys: &[ObjectId]) -> Result<HashMap<ObjectId, Self::Value>, Self::Error> {
+        lefts::<R>(&self.db, keys).await.map_err(Arc::new)
+    }
+}
+
+/// Installs the data loaders of the relation `R` into the schema.
+pub fn register<R, Q, M, S>(
+    builder: SchemaBuilder<Q, M, S>,
+    db: &Database,
+) -> SchemaBuilder<Q, M, S>
+where
+    R: Relation,
+{
+    builder
+        .data(DataLoader::new(
+            RightLoader::<R>::new(db.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            LeftLoader::<R>::new(db.clone()),
+            tokio::spawn,
+        ))
+}
+
+pub async fn load_rights<R: Relation>(
+    ctx: &Context<'_>,
+    id: Option<&ObjectId>,
+) -> FieldResult<Vec<R::Right>> {
+    let Some(id) = id else {
+        return Ok(vec![]);
+    };
+    let loader = ctx.data::<DataLoader<RightLoader<R>>>()?;
+    Ok(loader.load_one(*id).await?.unwrap_or_default())
+}
+
+pub async fn load_lefts<R: Relation>(
+    ctx: &Context<'_>,
+    id: Option<&ObjectId>,
+) -> FieldResult<Vec<R::Left>> {
+    let Some(id) = id else {
+        return Ok(vec![]);
+    };
+    let loader = ctx.data::<DataLoader<LeftLoader<R>>>()?;
+    Ok(loader.load_one(*id).await?.unwrap_or_default())
+}
@@ -0,0 +1,173 @@
+use qm_mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    results::DeleteResult,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    error::{EntityError, EntityResult},
+    ids::InfraContext,
+    model::{ListFilter, ListResult},
+    Collection, IsAdmin, IsSupport, SessionAccess, UserId,
+};
+
+pub const DEFAULT_OWNER_PATH: &str = "owner.entityId";
+
+/// Wraps a [`Collection`] and injects the owner filter of the current context
+/// into every query, so access-control filtering can't be forgotten.
+pub struct ScopedCollection<'a, T>
+where
+    T: Send + Sync,
+{
+    collection: &'a Collection<T>,
+    owner_path: &'a str,
+    context: Option<InfraContext>,
+}
+
+impl<'a, T> ScopedCollection<'a, T>
+where
+    T: Send + Sync,
+{
+    /// Creates a scoped collection, `None` means unrestricted access and
+    /// should only be used for admin or support users.
+    pub fn new(collection: &'a Collection<T>, context: Option<InfraContext>) -> Self {
+        Self {
+            collection,
+            owner_path: DEFAULT_OWNER_PATH,
+            context,
+        }
+    }
+
+    pub fn from_session<A>(collection: &'a Collection<T>, auth: &A) -> EntityResult<Self>
+    where
+        A: SessionAccess + IsAdmin + IsSupport + UserId,
+    {
+        if auth.is_admin() || auth.is_support() {
+            return Ok(Self::new(collection, None));
+        }
+        let id = auth
+            .session_access()
+            .and_then(|access| access.id())
+            .ok_or(EntityError::unauthorized(auth))?;
+        let context = InfraContext::parse(id).map_err(|_| EntityError::unauthorized(auth))?;
+        Ok(Self::new(collection, Some(context)))
+    }
+
+    pub fn with_owner_path(mut self, owner_path: &'a str) -> Self {
+        self.owner_path = owner_path;
+        self
+    }
+
+    pub fn context(&self) -> Option<&InfraContext> {
+        self.context.as_ref()
+    }
+
+    /// Returns `query` with the owner fields of the current context applied,
+    /// owner fields given by the caller are overwritten.
+    pub fn scope(&self, query: Document) -> Document {
+        apply_owner(query, self.owner_path, self.context.as_ref())
+    }
+
+    pub async fn count(&self, query: Option<Document>) -> qm_mongodb::error::Result<u64> {
+        self.collection
+            .as_ref()
+            .count_documents(self.scope(query.unwrap_or_default()))
+            .await
+    }
+
+    pub async fn delete_one(&self, query: Document) -> qm_mongodb::error::Result<DeleteResult> {
+        self.collection.as_ref().delete_one(self.scope(query)).await
+    }
+
+    pub async fn delete_many(&self, query: Document) -> qm_mongodb::error::Result<DeleteResult> {
+        self.collection
+            .as_ref()
+            .delete_many(self.scope(query))
+            .await
+    }
+}
+
+impl<'a, T> ScopedCollection<'a, T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin,
+{
+    pub async fn find_one(&self, query: Document) -> qm_mongodb::error::Result<Option<T>> {
+        self.collection.as_ref().find_one(self.scope(query)).await
+    }
+
+    pub async fn by_id(&self, id: &ObjectId) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { "_id": id }).await
+    }
+
+    pub async fn by_name(&self, name: &str) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { "name": name }).await
+    }
+
+    pub async fn by_field(&self, field: &str, value: &str) -> qm_mongodb::error::Result<Option<T>> {
+        self.find_one(doc! { field: value }).await
+    }
+
+    pub async fn list(
+        &self,
+        query: Option<Document>,
+        filter: Option<ListFilter>,
+    ) -> qm_mongodb::error::Result<ListResult<T>> {
+        self.collection
+            .list(Some(self.scope(query.unwrap_or_default())), filter)
+            .await
+    }
+}
+
+fn apply_owner(mut query: Document, p: &str, context: Option<&InfraContext>) -> Document {
+    match context {
+        Some(InfraContext::Customer(v)) => {
+            query.insert(format!("{p}.cid"), v.unzip());
+        }
+        Some(InfraContext::Organization(v)) => {
+            let (cid, oid) = v.unzip();
+            query.insert(format!("{p}.cid"), cid);
+            query.insert(format!("{p}.oid"), oid);
+        }
+        Some(InfraContext::Institution(v)) => {
+            let (cid, oid, iid) = v.unzip();
+            query.insert(format!("{p}.cid"), cid);
+            query.insert(format!("{p}.oid"), oid);
+            query.insert(format!("{p}.iid"), iid);
+        }
+        Some(InfraContext::OrganizationUnit(v)) => {
+            let (cid, _, uid) = v.unzip();
+            query.insert(format!("{p}.cid"), cid);
+            query.insert(format!("{p}.uid"), uid);
+        }
+        None => {}
+    }
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ids::{CustomerId, InstitutionId};
+
+    #[test]
+    fn test_apply_owner() {
+        let cid: CustomerId = 1_i64.into();
+        assert_eq!(
+            apply_owner(
+                doc! { "name": "a", "owner.entityId.cid": 2_i64 },
+                DEFAULT_OWNER_PATH,
+                Some(&InfraContext::Customer(cid)),
+            ),
+            doc! { "name": "a", "owner.entityId.cid": 1_i64 }
+        );
+        let iid: InstitutionId = (1_i64, 2_i64, 3_i64).into();
+        assert_eq!(
+            apply_owner(doc! {}, "owner", Some(&InfraContext::Institution(iid))),
+            doc! { "owner.cid": 1_i64, "owner.oid": 2_i64, "owner.iid": 3_i64 }
+        );
+        assert_eq!(
+            apply_owner(doc! { "name": "a" }, DEFAULT_OWNER_PATH, None),
+            doc! { "name": "a" }
+        );
+    }
+}
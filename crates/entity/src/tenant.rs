@@ -0,0 +1,50 @@
+//! Collections in the database of the customer of an [`InfraContext`], see
+//! [`qm_mongodb::tenant`].
+
+use std::sync::Arc;
+
+use qm_mongodb::{Database, DB};
+
+use crate::{
+    ids::{CustomerId, InfraContext},
+    owned::MongoCollection,
+    Collection,
+};
+
+/// Tenant of `context`, the id of its customer.
+pub fn tenant(context: &InfraContext) -> String {
+    CustomerId::from(*context.customer_id()).to_string()
+}
+
+/// Database of `T`, the shared database without context or if `T` is not a
+/// tenant collection.
+pub fn database<T: MongoCollection>(db: &DB, context: Option<&InfraContext>) -> Database {
+    db.database_for(context.map(tenant).as_deref(), T::COLLECTION)
+}
+
+pub fn collection<T>(db: &DB, context: Option<&InfraContext>) -> Collection<T>
+where
+    T: MongoCollection + Send + Sync,
+{
+    Collection(T::mongo_collection(&database::<T>(db, context)))
+}
+
+/// Provisions the database of a new customer, `None` if routing is disabled.
+pub async fn provision(
+    db: &DB,
+    customer: CustomerId,
+) -> qm_mongodb::error::Result<Option<Arc<str>>> {
+    db.provision_tenant(&customer.to_string()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant() {
+        let customer = CustomerId::from(1);
+        let context = InfraContext::Customer(customer);
+        assert_eq!(tenant(&context), customer.to_string());
+    }
+}
@@ -0,0 +1,163 @@
+use std::future::Future;
+
+use futures::future::BoxFuture;
+use qm_mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    error::{TRANSIENT_TRANSACTION_ERROR, UNKNOWN_TRANSACTION_COMMIT_RESULT},
+    results::{DeleteResult, UpdateResult},
+    ClientSession, DB,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{EntityError, EntityResult},
+    Collection,
+};
+
+const MAX_ATTEMPTS: usize = 5;
+
+/// Running MongoDB transaction, passed to the closure of
+/// [`WithTransaction::transaction`].
+pub struct Transaction {
+    session: ClientSession,
+}
+
+impl Transaction {
+    pub fn session(&mut self) -> &mut ClientSession {
+        &mut self.session
+    }
+}
+
+pub trait WithTransaction: AsRef<DB> + Sync {
+    /// Runs `f` inside a transaction and commits it afterwards, the whole
+    /// transaction is retried on transient transaction errors.
+    ///
+    /// ```ignore
+    /// storage.transaction(|tx| async move {
+    ///     let employee = employees.save_in(tx, employee).await?;
+    ///     work_times.save_in(tx, work_time).await?;
+    ///     Ok(employee)
+    /// }.boxed()).await?;
+    /// ```
+    fn transaction<F, R>(&self, f: F) -> impl Future<Output = EntityResult<R>> + Send
+    where
+        F: for<'a> FnMut(&'a mut Transaction) -> BoxFuture<'a, EntityResult<R>> + Send,
+        R: Send,
+    {
+        run(self.as_ref(), f)
+    }
+}
+
+impl<T> WithTransaction for T where T: AsRef<DB> + Sync {}
+
+fn has_label(err: &EntityError, label: &str) -> bool {
+    matches!(err, EntityError::Database(err) if err.contains_label(label))
+}
+
+pub async fn run<F, R>(db: &DB, mut f: F) -> EntityResult<R>
+where
+    F: for<'a> FnMut(&'a mut Transaction) -> BoxFuture<'a, EntityResult<R>> + Send,
+    R: Send,
+{
+    let mut tx = Transaction {
+        session: db.session().await?,
+    };
+    let mut attempt = 1;
+    'transaction: loop {
+        tx.session.start_transaction().await?;
+        let result = match f(&mut tx).await {
+            Ok(result) => result,
+            Err(err) => {
+                // the server may already have aborted the transaction
+                tx.session.abort_transaction().await.ok();
+                if has_label(&err, TRANSIENT_TRANSACTION_ERROR) && attempt < MAX_ATTEMPTS {
+                    attempt += 1;
+                    continue 'transaction;
+                }
+                return Err(err);
+            }
+        };
+        loop {
+            match tx.session.commit_transaction().await {
+                Ok(()) => return Ok(result),
+                Err(err)
+                    if err.contains_label(UNKNOWN_TRANSACTION_COMMIT_RESULT)
+                        && attempt < MAX_ATTEMPTS =>
+                {
+                    attempt += 1;
+                }
+                Err(err)
+                    if err.contains_label(TRANSIENT_TRANSACTION_ERROR)
+                        && attempt < MAX_ATTEMPTS =>
+                {
+                    attempt += 1;
+                    continue 'transaction;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin,
+{
+    pub async fn by_id_in(
+        &self,
+        tx: &mut Transaction,
+        id: &ObjectId,
+    ) -> qm_mongodb::error::Result<Option<T>> {
+        self.as_ref()
+            .find_one(doc! { "_id": id })
+            .session(tx.session())
+            .await
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: Send + Sync,
+{
+    pub async fn update_one_in(
+        &self,
+        tx: &mut Transaction,
+        query: Document,
+        update: Document,
+    ) -> qm_mongodb::error::Result<UpdateResult> {
+        self.as_ref()
+            .update_one(query, update)
+            .session(tx.session())
+            .await
+    }
+
+    pub async fn delete_one_in(
+        &self,
+        tx: &mut Transaction,
+        query: Document,
+    ) -> qm_mongodb::error::Result<DeleteResult> {
+        self.as_ref().delete_one(query).session(tx.session()).await
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: Serialize + Send + Sync + Unpin + AsMut<Option<ObjectId>>,
+{
+    pub async fn save_in(
+        &self,
+        tx: &mut Transaction,
+        mut value: T,
+    ) -> qm_mongodb::error::Result<T> {
+        let id = self
+            .as_ref()
+            .insert_one(&value)
+            .session(tx.session())
+            .await?
+            .inserted_id;
+        if let qm_mongodb::bson::Bson::ObjectId(oid) = id {
+            *value.as_mut() = Some(oid);
+        }
+        Ok(value)
+    }
+}
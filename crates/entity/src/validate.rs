@@ -0,0 +1,373 @@
+//! Validation of models before they are persisted.
+//!
+//! Implement [`Validate`] with `#[derive(Validate)]` and annotate fields
+//! with `#[validate(...)]`:
+//!
+//! ```ignore
+//! #[derive(Validate)]
+//! struct Person {
+//!     #[validate(length(min = 1, max = 100))]
+//!     firstname: String,
+//!     #[validate(email)]
+//!     email: Option<String>,
+//!     #[validate(regex = "^[0-9]{5}$")]
+//!     zip_code: Option<String>,
+//!     #[validate(custom = "validate_gender")]
+//!     gender: String,
+//!     #[validate(nested)]
+//!     address: Option<Address>,
+//! }
+//! ```
+//!
+//! Field names are reported in camel case like in the GraphQL schema, rules
+//! on `None` or undefined values are skipped. The `*_validated` mutations
+//! of [`crate::owned::EntityOwned`] validate before they persist.
+
+use std::{borrow::Cow, collections::BTreeMap, sync::Arc};
+
+use async_graphql::MaybeUndefined;
+
+pub use qm_entity_derive::Validate;
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// A failed rule, `code` identifies the rule, e.g. `LENGTH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub code: Cow<'static, str>,
+    pub params: BTreeMap<String, String>,
+}
+
+impl ValidationError {
+    pub fn new(code: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            code: code.into(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_param(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.params.insert(name.into(), value.to_string());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    /// Path of the field, nested fields and list items are separated by
+    /// dots, e.g. `person.languages.0`.
+    pub field: String,
+    pub error: ValidationError,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, thiserror::Error)]
+#[error("invalid fields: {}", self.fields().collect::<Vec<_>>().join(", "))]
+pub struct ValidationErrors(Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn errors(&self) -> &[FieldError] {
+        &self.0
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|e| e.field.as_str())
+    }
+
+    pub fn add(&mut self, field: impl Into<String>, error: ValidationError) {
+        self.0.push(FieldError {
+            field: field.into(),
+            error,
+        });
+    }
+
+    /// Adds the errors of a nested value with their fields prefixed by
+    /// `field`.
+    pub fn nest(&mut self, field: &str, result: Result<(), ValidationErrors>) {
+        if let Err(nested) = result {
+            self.0.extend(nested.0.into_iter().map(|mut e| {
+                e.field = if e.field.is_empty() {
+                    field.to_string()
+                } else {
+                    format!("{field}.{}", e.field)
+                };
+                e
+            }));
+        }
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl<T: Validate + ?Sized> Validate for &T {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        (**self).validate()
+    }
+}
+
+impl<T: Validate + ?Sized> Validate for Box<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        (**self).validate()
+    }
+}
+
+impl<T: Validate + ?Sized> Validate for Arc<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        (**self).validate()
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.as_ref().map_or(Ok(()), Validate::validate)
+    }
+}
+
+impl<T: Validate> Validate for MaybeUndefined<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.value().map_or(Ok(()), Validate::validate)
+    }
+}
+
+impl<T: Validate> Validate for [T] {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (i, item) in self.iter().enumerate() {
+            errors.nest(&i.to_string(), item.validate());
+        }
+        errors.into_result()
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.as_slice().validate()
+    }
+}
+
+/// Values with a length, `None` skips the rules.
+pub trait HasLength {
+    fn length(&self) -> Option<usize>;
+}
+
+impl HasLength for str {
+    fn length(&self) -> Option<usize> {
+        Some(self.chars().count())
+    }
+}
+
+impl HasLength for String {
+    fn length(&self) -> Option<usize> {
+        self.as_str().length()
+    }
+}
+
+impl HasLength for Arc<str> {
+    fn length(&self) -> Option<usize> {
+        self.as_ref().length()
+    }
+}
+
+impl<T> HasLength for Vec<T> {
+    fn length(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: HasLength> HasLength for Option<T> {
+    fn length(&self) -> Option<usize> {
+        self.as_ref().and_then(HasLength::length)
+    }
+}
+
+impl<T: HasLength> HasLength for MaybeUndefined<T> {
+    fn length(&self) -> Option<usize> {
+        self.value().and_then(HasLength::length)
+    }
+}
+
+/// Text values, `None` skips the rules.
+pub trait AsText {
+    fn as_text(&self) -> Option<&str>;
+}
+
+impl AsText for str {
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsText for String {
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl AsText for Arc<str> {
+    fn as_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl<T: AsText> AsText for Option<T> {
+    fn as_text(&self) -> Option<&str> {
+        self.as_ref().and_then(AsText::as_text)
+    }
+}
+
+impl<T: AsText> AsText for MaybeUndefined<T> {
+    fn as_text(&self) -> Option<&str> {
+        self.value().and_then(AsText::as_text)
+    }
+}
+
+pub fn length<T: HasLength + ?Sized>(
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> Result<(), ValidationError> {
+    let Some(len) = value.length() else {
+        return Ok(());
+    };
+    if min.is_some_and(|min| len < min) || max.is_some_and(|max| len > max) {
+        let mut err = ValidationError::new("LENGTH");
+        if let Some(min) = min {
+            err = err.with_param("min", min);
+        }
+        if let Some(max) = max {
+            err = err.with_param("max", max);
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Checks the shape `local@domain.tld` without whitespace, deliverability is
+/// not checked.
+pub fn email<T: AsText + ?Sized>(value: &T) -> Result<(), ValidationError> {
+    let Some(value) = value.as_text() else {
+        return Ok(());
+    };
+    let valid = value.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !domain.contains('@')
+            && domain
+                .split_once('.')
+                .is_some_and(|(name, _)| !name.is_empty())
+            && !domain.ends_with('.')
+    }) && !value.chars().any(char::is_whitespace);
+    if valid {
+        Ok(())
+    } else {
+        Err(ValidationError::new("EMAIL"))
+    }
+}
+
+pub fn regex<T: AsText + ?Sized>(value: &T, regex: &regex::Regex) -> Result<(), ValidationError> {
+    match value.as_text() {
+        Some(text) if !regex.is_match(text) => {
+            Err(ValidationError::new("REGEX").with_param("pattern", regex.as_str()))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use regex::Regex;
+    pub use std::sync::OnceLock;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate_gender(value: &str) -> Result<(), ValidationError> {
+        if matches!(value, "m" | "f" | "d") {
+            Ok(())
+        } else {
+            Err(ValidationError::new("GENDER"))
+        }
+    }
+
+    #[derive(Validate)]
+    struct Address {
+        #[validate(regex = "^[0-9]{5}$")]
+        zip_code: Option<String>,
+    }
+
+    #[derive(Validate)]
+    struct Person {
+        #[validate(length(min = 1, max = 5))]
+        first_name: String,
+        #[validate(email)]
+        email: Option<String>,
+        #[validate(custom = "validate_gender")]
+        gender: String,
+        #[validate(length(max = 1), nested)]
+        addresses: Vec<Address>,
+    }
+
+    #[test]
+    fn test_derive() {
+        let mut person = Person {
+            first_name: "Max".into(),
+            email: None,
+            gender: "d".into(),
+            addresses: vec![Address { zip_code: None }],
+        };
+        assert_eq!(person.validate(), Ok(()));
+
+        person.first_name = String::new();
+        person.email = Some("max@localhost".into());
+        person.gender = "x".into();
+        person.addresses = vec![
+            Address {
+                zip_code: Some("12345".into()),
+            },
+            Address {
+                zip_code: Some("1234".into()),
+            },
+        ];
+        let errors = person.validate().unwrap_err();
+        assert_eq!(
+            errors.fields().collect::<Vec<_>>(),
+            [
+                "firstName",
+                "email",
+                "gender",
+                "addresses",
+                "addresses.1.zipCode"
+            ]
+        );
+        assert_eq!(errors.errors()[0].error.code, "LENGTH");
+        assert_eq!(errors.errors()[0].error.params["min"], "1");
+        assert_eq!(errors.errors()[2].error.code, "GENDER");
+    }
+
+    #[test]
+    fn test_email() {
+        assert!(email("a@b.de").is_ok());
+        assert!(email(&None::<String>).is_ok());
+        assert!(email("a@b").is_err());
+        assert!(email("@b.de").is_err());
+        assert!(email("a b@c.de").is_err());
+        assert!(email("a@b@c.de").is_err());
+        assert!(email("a@.de").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use futures::{stream::BoxStream, StreamExt};
+use qm_mongodb::{
+    bson::{self, doc, Document},
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    options::FullDocumentType,
+};
+use qm_redis::{redis::AsyncCommands, Redis};
+use serde::de::DeserializeOwned;
+
+use crate::{error::EntityResult, ids::ObjectId, Collection};
+
+const RESUME_TOKEN_PREFIX: &str = "qm:watch";
+
+#[derive(Debug, Clone)]
+pub enum ChangeEvent<T> {
+    Inserted {
+        id: ObjectId,
+        entity: T,
+    },
+    /// Updated or replaced entity, `entity` is `None` if the document was
+    /// deleted before it could be looked up.
+    Updated {
+        id: ObjectId,
+        entity: Option<T>,
+    },
+    Deleted {
+        id: ObjectId,
+    },
+}
+
+impl<T> ChangeEvent<T> {
+    pub fn id(&self) -> &ObjectId {
+        match self {
+            Self::Inserted { id, .. } | Self::Updated { id, .. } | Self::Deleted { id } => id,
+        }
+    }
+}
+
+pub type ChangeEventStream<T> = BoxStream<'static, EntityResult<ChangeEvent<T>>>;
+
+/// Persists the resume token of a change stream in Redis, so a restarted
+/// watcher continues where the previous one stopped.
+#[derive(Clone)]
+pub struct ResumeTokenStore {
+    redis: Redis,
+    key: String,
+}
+
+impl ResumeTokenStore {
+    pub fn new(redis: Redis, name: &str) -> Self {
+        Self {
+            redis,
+            key: format!("{RESUME_TOKEN_PREFIX}:{name}"),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub async fn load(&self) -> EntityResult<Option<ResumeToken>> {
+        let mut con = self.redis.connect().await.map_err(anyhow::Error::from)?;
+        let bytes: Option<Vec<u8>> = con.get(&self.key).await.map_err(anyhow::Error::from)?;
+        bytes.map(|b| decode_token(&b)).transpose()
+    }
+
+    pub async fn save(&self, token: &ResumeToken) -> EntityResult<()> {
+        let bytes = encode_token(token)?;
+        let mut con = self.redis.connect().await.map_err(anyhow::Error::from)?;
+        con.set::<_, _, ()>(&self.key, bytes)
+            .await
+            .map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    pub async fn clear(&self) -> EntityResult<()> {
+        let mut con = self.redis.connect().await.map_err(anyhow::Error::from)?;
+        con.del::<_, ()>(&self.key)
+            .await
+            .map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+}
+
+fn encode_token(token: &ResumeToken) -> EntityResult<Vec<u8>> {
+    let token = bson::to_bson(token).map_err(anyhow::Error::from)?;
+    Ok(bson::to_vec(&doc! { "token": token }).map_err(anyhow::Error::from)?)
+}
+
+fn decode_token(bytes: &[u8]) -> EntityResult<ResumeToken> {
+    let mut doc: Document = bson::from_slice(bytes).map_err(anyhow::Error::from)?;
+    let token = doc
+        .remove("token")
+        .ok_or_else(|| anyhow::anyhow!("invalid resume token"))?;
+    Ok(bson::from_bson(token).map_err(anyhow::Error::from)?)
+}
+
+fn to_change_event<T>(event: ChangeStreamEvent<T>) -> Option<ChangeEvent<T>> {
+    let id = event
+        .document_key
+        .as_ref()
+        .and_then(|key| key.get_object_id("_id").ok())?;
+    match event.operation_type {
+        OperationType::Insert => event
+            .full_document
+            .map(|entity| ChangeEvent::Inserted { id, entity }),
+        OperationType::Update | OperationType::Replace => Some(ChangeEvent::Updated {
+            id,
+            entity: event.full_document,
+        }),
+        OperationType::Delete => Some(ChangeEvent::Deleted { id }),
+        _ => None,
+    }
+}
+
+impl<T> Collection<T>
+where
+    T: DeserializeOwned + Send + Sync + Unpin + 'static,
+{
+    /// Watches the collection for inserted, updated and deleted entities.
+    ///
+    /// `filter` is matched against the change events, e.g.
+    /// `doc! { "fullDocument.owner.entityId.cid": 1 }`. With a `store` the
+    /// stream resumes after the last consumed event; the token of an event is
+    /// saved when the next one is requested, so events are delivered at
+    /// least once.
+    pub async fn watch(
+        &self,
+        filter: Option<Document>,
+        store: Option<ResumeTokenStore>,
+    ) -> EntityResult<ChangeEventStream<T>> {
+        let resume_after = match store.as_ref() {
+            Some(store) => store.load().await?,
+            None => None,
+        };
+        let pipeline = filter.map(|filter| doc! { "$match": filter });
+        let stream = self
+            .as_ref()
+            .watch()
+            .pipeline(pipeline)
+            .full_document(FullDocumentType::UpdateLookup)
+            .resume_after(resume_after)
+            .await?;
+        Ok(futures::stream::unfold(
+            (stream, store, None::<ResumeToken>),
+            |(mut stream, store, mut pending)| async move {
+                loop {
+                    if let (Some(store), Some(token)) = (store.as_ref(), pending.take()) {
+                        if let Err(err) = store.save(&token).await {
+                            // saved again when the next event is requested
+                            return Some((Err(err), (stream, Some(store.clone()), Some(token))));
+                        }
+                    }
+                    let event = match stream.next().await? {
+                        Ok(event) => event,
+                        Err(err) => return Some((Err(err.into()), (stream, store, None))),
+                    };
+                    let token = event.id.clone();
+                    if let Some(event) = to_change_event(event) {
+                        return Some((Ok(event), (stream, store, Some(token))));
+                    }
+                    // ignore events without entity, but remember their position
+                    pending = Some(token);
+                }
+            },
+        )
+        .boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qm_mongodb::bson::oid::ObjectId;
+
+    #[test]
+    fn test_to_change_event() {
+        let id = ObjectId::new();
+        let event: ChangeStreamEvent<Document> = bson::from_document(doc! {
+            "_id": { "_data": "826" },
+            "operationType": "delete",
+            "documentKey": { "_id": id },
+        })
+        .unwrap();
+        assert!(matches!(
+            to_change_event(event),
+            Some(ChangeEvent::Deleted { id: deleted }) if deleted == id
+        ));
+        let event: ChangeStreamEvent<Document> = bson::from_document(doc! {
+            "_id": { "_data": "827" },
+            "operationType": "insert",
+            "documentKey": { "_id": id },
+            "fullDocument": { "_id": id, "name": "a" },
+        })
+        .unwrap();
+        assert!(matches!(
+            to_change_event(event),
+            Some(ChangeEvent::Inserted { entity, .. }) if entity.get_str("name") == Ok("a")
+        ));
+    }
+
+    #[test]
+    fn test_resume_token_roundtrip() {
+        let event: ChangeStreamEvent<Document> = bson::from_document(doc! {
+            "_id": { "_data": "8263" },
+            "operationType": "drop",
+        })
+        .unwrap();
+        let bytes = encode_token(&event.id).unwrap();
+        assert_eq!(decode_token(&bytes).unwrap(), event.id);
+    }
+}
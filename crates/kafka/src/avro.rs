@@ -0,0 +1,168 @@
+//! Avro binary encoding of serializable values and the Confluent schema
+//! registry wire format.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::typed::ValueSerializer;
+
+/// Parsed Avro schema.
+#[derive(Debug, Clone)]
+pub struct AvroSchema(apache_avro::Schema);
+
+impl AvroSchema {
+    pub fn parse(schema: &str) -> anyhow::Result<Self> {
+        Ok(Self(apache_avro::Schema::parse_str(schema)?))
+    }
+
+    /// Avro binary encoding of `value`, the serialized value is resolved
+    /// against the schema first, e.g. maps to records, strings to enums and
+    /// missing fields to their defaults.
+    pub fn encode<T: serde::Serialize>(&self, value: &T) -> anyhow::Result<Vec<u8>> {
+        let value = apache_avro::to_value(value)?.resolve(&self.0)?;
+        Ok(apache_avro::to_avro_datum(&self.0, value)?)
+    }
+}
+
+struct RegistryInner {
+    url: Arc<str>,
+    client: reqwest::Client,
+    ids: Mutex<HashMap<String, u32>>,
+}
+
+/// Client of a Confluent compatible schema registry.
+#[derive(Clone)]
+pub struct SchemaRegistry {
+    inner: Arc<RegistryInner>,
+}
+
+#[derive(serde::Deserialize)]
+struct RegisterResponse {
+    id: u32,
+}
+
+impl SchemaRegistry {
+    pub fn new(url: &str) -> Self {
+        Self {
+            inner: Arc::new(RegistryInner {
+                url: Arc::from(url.trim_end_matches('/')),
+                client: reqwest::Client::new(),
+                ids: Mutex::default(),
+            }),
+        }
+    }
+
+    /// Registers `schema` for `subject` and returns its id, ids are cached
+    /// per subject.
+    pub async fn register(&self, subject: &str, schema: &str) -> anyhow::Result<u32> {
+        if let Some(id) = self
+            .inner
+            .ids
+            .lock()
+            .ok()
+            .and_then(|ids| ids.get(subject).copied())
+        {
+            return Ok(id);
+        }
+        let response: RegisterResponse = self
+            .inner
+            .client
+            .post(format!("{}/subjects/{subject}/versions", self.inner.url))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/vnd.schemaregistry.v1+json",
+            )
+            .json(&serde_json::json!({ "schema": schema }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Ok(mut ids) = self.inner.ids.lock() {
+            ids.insert(subject.to_string(), response.id);
+        }
+        Ok(response.id)
+    }
+}
+
+/// Avro payloads in the Confluent wire format, the schema is registered
+/// with the subject `{topic}-value` on first use.
+#[derive(Clone)]
+pub struct AvroSerializer {
+    registry: SchemaRegistry,
+    schema: Arc<AvroSchema>,
+    raw: Arc<str>,
+}
+
+impl AvroSerializer {
+    pub fn new(registry: SchemaRegistry, schema: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            registry,
+            schema: Arc::new(AvroSchema::parse(schema)?),
+            raw: Arc::from(schema),
+        })
+    }
+}
+
+fn frame(id: u32, payload: Vec<u8>) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(payload.len() + 5);
+    buf.push(0);
+    buf.extend(id.to_be_bytes());
+    buf.extend(payload);
+    buf
+}
+
+#[async_trait::async_trait]
+impl<T> ValueSerializer<T> for AvroSerializer
+where
+    T: serde::Serialize + Send + Sync,
+{
+    async fn serialize(&self, topic: &str, value: &T) -> anyhow::Result<Vec<u8>> {
+        let payload = self.schema.encode(value)?;
+        let id = self
+            .registry
+            .register(&format!("{topic}-value"), &self.raw)
+            .await?;
+        Ok(frame(id, payload))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_record() -> anyhow::Result<()> {
+        let schema = AvroSchema::parse(
+            r#"{
+                "type": "record",
+                "name": "User",
+                "namespace": "qm",
+                "fields": [
+                    { "name": "id", "type": "long" },
+                    { "name": "name", "type": "string" },
+                    { "name": "email", "type": ["null", "string"], "default": null },
+                    { "name": "tags", "type": { "type": "array", "items": "string" } },
+                    { "name": "state", "type": { "type": "enum", "name": "State", "symbols": ["A", "B"] } },
+                    { "name": "next", "type": ["null", "User"] }
+                ]
+            }"#,
+        )?;
+        let payload = schema.encode(&serde_json::json!({
+            "id": -2,
+            "name": "ab",
+            "tags": ["x"],
+            "state": "B",
+            "next": { "id": 1, "name": "", "tags": [], "state": "A", "next": null },
+        }))?;
+        assert_eq!(
+            payload,
+            vec![3, 4, b'a', b'b', 0, 2, 2, b'x', 0, 2, 2, 2, 0, 0, 0, 0, 0]
+        );
+        assert!(schema.encode(&serde_json::json!({ "id": "x" })).is_err());
+        assert_eq!(frame(7, vec![1]), vec![0, 0, 0, 0, 7, 1]);
+        Ok(())
+    }
+}
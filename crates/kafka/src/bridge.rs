@@ -0,0 +1,316 @@
+//! Forwards mutation events between NATS JetStream and Kafka.
+//!
+//! Both directions set an event id, Kafka messages carry it in the
+//! [`EVENT_ID`] header and JetStream messages in `Nats-Msg-Id`, so the
+//! JetStream duplicate window and idempotent consumers can drop events
+//! forwarded twice. Forwarded messages are marked with [`ORIGIN`] and are not
+//! sent back. Failed publishes and acknowledgements are retried with backoff.
+
+use std::{sync::Arc, time::Duration};
+
+use async_nats::jetstream::{self, consumer::pull, AckKind};
+use futures::StreamExt;
+use qm_utils::retry::{retry, RetryPolicy};
+use rdkafka::{
+    consumer::{CommitMode, Consumer, StreamConsumer},
+    message::{Header, Headers, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig, Message,
+};
+use tokio::sync::oneshot;
+
+use crate::{config::Config, consumer::RunningConsumer};
+
+pub const EVENT_ID: &str = "event-id";
+pub const ORIGIN: &str = "bridge-origin";
+const NATS_MSG_ID: &str = "Nats-Msg-Id";
+#[cfg(feature = "otel")]
+const TRACEPARENT: &str = "traceparent";
+
+fn nats_event_id(headers: Option<&async_nats::HeaderMap>, stream: &str, sequence: u64) -> String {
+    headers
+        .and_then(|headers| headers.get(NATS_MSG_ID))
+        .map(|v| v.as_str().to_string())
+        .unwrap_or_else(|| format!("{stream}:{sequence}"))
+}
+
+fn kafka_header<'a>(message: &'a impl Message, name: &str) -> Option<&'a str> {
+    message
+        .headers()?
+        .iter()
+        .find(|header| header.key == name)
+        .and_then(|header| std::str::from_utf8(header.value?).ok())
+}
+
+/// NATS subject token of a Kafka key, characters which would add tokens or
+/// wildcards are replaced with `_`.
+fn subject_token(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            '.' | '*' | '>' => '_',
+            c if c.is_whitespace() || c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn kafka_event_id(event_id: Option<&str>, topic: &str, partition: i32, offset: i64) -> String {
+    event_id
+        .map(String::from)
+        .unwrap_or_else(|| format!("{topic}:{partition}:{offset}"))
+}
+
+/// Connects a JetStream context with the Kafka cluster of a [`Config`].
+#[derive(Clone)]
+pub struct Bridge {
+    js: jetstream::Context,
+    producer: FutureProducer,
+    address: Arc<str>,
+    group_prefix: Arc<str>,
+    retry_policy: RetryPolicy,
+}
+
+impl Bridge {
+    pub fn new(js: jetstream::Context, config: &Config) -> anyhow::Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", config.address())
+            .set("enable.idempotence", "true")
+            .set("message.timeout.ms", "30000")
+            .create()?;
+        Ok(Self {
+            js,
+            producer,
+            address: Arc::from(config.address()),
+            group_prefix: Arc::from(config.consumer_group_mutation_events_prefix()),
+            retry_policy: RetryPolicy::exponential(Duration::from_millis(200))
+                .with_max_attempts(10),
+        })
+    }
+
+    /// Retries of publishes and acknowledgements, the forwarding stops if
+    /// they are exhausted.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Forwards messages of `stream` matching `filter_subject` to `topic`
+    /// using a durable pull consumer, messages are acknowledged after Kafka
+    /// confirmed the delivery. The returned consumer joins a coordinated
+    /// shutdown with [`RunningConsumer::register_shutdown`].
+    pub async fn jetstream_to_kafka(
+        &self,
+        stream: &str,
+        durable: &str,
+        filter_subject: &str,
+        topic: &str,
+    ) -> anyhow::Result<RunningConsumer> {
+        let consumer = self
+            .js
+            .get_stream(stream)
+            .await?
+            .get_or_create_consumer(
+                durable,
+                pull::Config {
+                    durable_name: Some(durable.to_string()),
+                    filter_subject: filter_subject.to_string(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        let mut messages = consumer.messages().await?;
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let producer = self.producer.clone();
+        let policy = self.retry_policy;
+        let stream = stream.to_string();
+        let topic = topic.to_string();
+        tracing::info!("bridge {stream} '{filter_subject}' -> kafka {topic}");
+        let handle = tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    message = messages.next() => match message {
+                        Some(Ok(message)) => message,
+                        Some(Err(err)) => {
+                            tracing::warn!("bridge {stream} receive error: {err}");
+                            continue;
+                        }
+                        None => break,
+                    },
+                };
+                let ack = |kind| {
+                    let message = &message;
+                    async move {
+                        retry(&policy, || message.ack_with(kind))
+                            .await
+                            .map_err(|err| anyhow::anyhow!("{err}"))
+                    }
+                };
+                let origin = message
+                    .headers
+                    .as_ref()
+                    .and_then(|headers| headers.get(ORIGIN));
+                if origin.is_some_and(|origin| origin.as_str() == "kafka") {
+                    ack(AckKind::Ack).await?;
+                    continue;
+                }
+                let sequence = message
+                    .info()
+                    .map_err(|err| anyhow::anyhow!("{err}"))?
+                    .stream_sequence;
+                let event_id = nats_event_id(message.headers.as_ref(), &stream, sequence);
+                let headers = OwnedHeaders::new()
+                    .insert(Header {
+                        key: EVENT_ID,
+                        value: Some(&event_id),
+                    })
+                    .insert(Header {
+                        key: ORIGIN,
+                        value: Some("nats"),
+                    });
+                let timestamp = crate::producer::now();
+                let result = retry(&policy, || {
+                    let record = FutureRecord::to(&topic)
+                        .key(message.subject.as_str())
+                        .payload(message.payload.as_ref())
+                        .headers(headers.clone())
+                        .timestamp(timestamp);
+                    async {
+                        producer
+                            .send(record, Duration::from_secs(30))
+                            .await
+                            .map_err(|(err, _)| err)
+                    }
+                })
+                .await;
+                match result {
+                    Ok(_) => ack(AckKind::Ack).await?,
+                    Err(err) => {
+                        tracing::warn!("unable to forward {event_id} to kafka {topic}: {err}");
+                        ack(AckKind::Nak(Some(Duration::from_secs(1)))).await?;
+                    }
+                }
+            }
+            Ok(())
+        });
+        Ok(RunningConsumer::new(shutdown_tx, handle))
+    }
+
+    /// Publishes messages of `topic` to `{subject_prefix}.{key}`, offsets
+    /// are committed after JetStream acknowledged the message. The key is
+    /// published as a single subject token, `.`, `*`, `>` and whitespace
+    /// are replaced with `_`. The returned consumer joins a coordinated
+    /// shutdown with [`RunningConsumer::register_shutdown`].
+    pub fn kafka_to_jetstream(
+        &self,
+        topic: &str,
+        group: &str,
+        subject_prefix: &str,
+    ) -> anyhow::Result<RunningConsumer> {
+        if subject_prefix.is_empty()
+            || subject_prefix
+                .split('.')
+                .any(|token| token.is_empty() || token != subject_token(token))
+        {
+            anyhow::bail!("invalid subject prefix '{subject_prefix}'");
+        }
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", self.address.as_ref())
+            .set("group.id", format!("{}_{group}", self.group_prefix))
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+        consumer.subscribe(&[topic])?;
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let js = self.js.clone();
+        let policy = self.retry_policy;
+        let subject_prefix = subject_prefix.to_string();
+        tracing::info!("bridge kafka {topic} -> '{subject_prefix}'");
+        let handle = tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    message = consumer.recv() => match message {
+                        Ok(message) => message,
+                        Err(err) => {
+                            tracing::warn!("bridge receive error: {err}");
+                            continue;
+                        }
+                    },
+                };
+                if kafka_header(&message, ORIGIN) != Some("nats") {
+                    let event_id = kafka_event_id(
+                        kafka_header(&message, EVENT_ID),
+                        message.topic(),
+                        message.partition(),
+                        message.offset(),
+                    );
+                    let subject = match message.key().map(String::from_utf8_lossy) {
+                        Some(key) if !key.is_empty() => {
+                            format!("{subject_prefix}.{}", subject_token(&key))
+                        }
+                        _ => subject_prefix.clone(),
+                    };
+                    let mut headers = async_nats::HeaderMap::new();
+                    headers.insert(NATS_MSG_ID, event_id.as_str());
+                    headers.insert(ORIGIN, "kafka");
+                    let payload = message.payload().unwrap_or_default().to_vec();
+                    #[cfg(feature = "otel")]
+                    let span = {
+                        let span = tracing::info_span!(
+                            "nats.publish",
+                            subject = %subject,
+                            event_id = %event_id
+                        );
+                        if let Some(traceparent) = kafka_header(&message, TRACEPARENT) {
+                            qm_utils::telemetry::set_parent(&span, traceparent);
+                        }
+                        if let Some(traceparent) = span.in_scope(qm_utils::telemetry::traceparent) {
+                            headers.insert(TRACEPARENT, traceparent.as_str());
+                        }
+                        span
+                    };
+                    let publish = retry(&policy, || async {
+                        let ack = js
+                            .publish_with_headers(
+                                subject.clone(),
+                                headers.clone(),
+                                payload.clone().into(),
+                            )
+                            .await?;
+                        anyhow::Ok(ack.await?)
+                    });
+                    #[cfg(feature = "otel")]
+                    let publish = tracing::Instrument::instrument(publish, span);
+                    publish.await?;
+                }
+                consumer.commit_message(&message, CommitMode::Async)?;
+            }
+            Ok(())
+        });
+        Ok(RunningConsumer::new(shutdown_tx, handle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_ids() {
+        let mut headers = async_nats::HeaderMap::new();
+        assert_eq!(nats_event_id(Some(&headers), "EVENTS", 7), "EVENTS:7");
+        headers.insert(NATS_MSG_ID, "abc");
+        assert_eq!(nats_event_id(Some(&headers), "EVENTS", 7), "abc");
+        assert_eq!(kafka_event_id(None, "events", 1, 2), "events:1:2");
+        assert_eq!(kafka_event_id(Some("abc"), "events", 1, 2), "abc");
+    }
+
+    #[test]
+    fn test_subject_token() {
+        assert_eq!(subject_token("V01"), "V01");
+        assert_eq!(subject_token("a.b"), "a_b");
+        assert_eq!(subject_token("*"), "_");
+        assert_eq!(subject_token("> x\n"), "__x_");
+    }
+}
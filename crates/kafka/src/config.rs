@@ -8,6 +8,7 @@ pub struct Config {
     address: Option<Arc<str>>,
     topic_mutation_events: Option<Arc<str>>,
     consumer_group_mutation_events_prefix: Option<Arc<str>>,
+    schema_registry_url: Option<Arc<str>>,
 }
 
 impl Config {
@@ -32,6 +33,10 @@ impl Config {
             .as_deref()
             .unwrap()
     }
+
+    pub fn schema_registry_url(&self) -> &str {
+        self.schema_registry_url.as_deref().unwrap()
+    }
 }
 
 #[derive(Default)]
@@ -64,6 +69,9 @@ impl<'a> ConfigBuilder<'a> {
         if cfg.consumer_group_mutation_events_prefix.is_none() {
             cfg.consumer_group_mutation_events_prefix = Some(Arc::from("qm_consumer_group"));
         }
+        if cfg.schema_registry_url.is_none() {
+            cfg.schema_registry_url = Some(Arc::from("http://127.0.0.1:8081"));
+        }
         Ok(cfg)
     }
 }
@@ -0,0 +1,567 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use qm_utils::retry::{retry, RetryPolicy};
+use qm_utils::shutdown::{Shutdown, ShutdownPhase};
+use rdkafka::{
+    consumer::{CommitMode, Consumer, ConsumerContext, Rebalance, StreamConsumer},
+    message::{Header, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig, ClientContext, Message, Offset, TopicPartitionList,
+};
+use serde::de::DeserializeOwned;
+use tokio::{
+    sync::{mpsc, oneshot, watch, Semaphore},
+    task::JoinHandle,
+};
+
+use crate::config::Config;
+
+/// Headers of messages moved to the dead letter topic.
+pub const DEAD_LETTER_TOPIC: &str = "dlq-topic";
+pub const DEAD_LETTER_PARTITION: &str = "dlq-partition";
+pub const DEAD_LETTER_OFFSET: &str = "dlq-offset";
+pub const DEAD_LETTER_ERROR: &str = "dlq-error";
+
+/// Origin of a consumed message.
+#[derive(Debug, Clone)]
+pub struct MessageInfo {
+    pub topic: String,
+    pub partition: i32,
+    pub offset: i64,
+    pub key: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait Handle<T>: Send + Sync
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    async fn handle(&self, info: &MessageInfo, item: T) -> anyhow::Result<()>;
+}
+
+#[derive(Default)]
+struct PartitionOffsets {
+    pending: BTreeSet<i64>,
+    next: i64,
+    committed: i64,
+}
+
+/// Tracks in-flight offsets per partition, an offset is committable once all
+/// messages before it have been handled.
+#[derive(Default)]
+struct OffsetTracker {
+    partitions: HashMap<(String, i32), PartitionOffsets>,
+}
+
+impl OffsetTracker {
+    fn begin(&mut self, topic: &str, partition: i32, offset: i64) {
+        let p = self
+            .partitions
+            .entry((topic.to_string(), partition))
+            .or_default();
+        p.pending.insert(offset);
+        p.next = p.next.max(offset + 1);
+    }
+
+    /// Returns the new offset to commit if it advanced.
+    fn complete(&mut self, topic: &str, partition: i32, offset: i64) -> Option<i64> {
+        let p = self.partitions.get_mut(&(topic.to_string(), partition))?;
+        p.pending.remove(&offset);
+        let committable = p.pending.first().copied().unwrap_or(p.next);
+        (committable > p.committed).then(|| {
+            p.committed = committable;
+            committable
+        })
+    }
+
+    /// Forgets a revoked partition, its in-flight messages are redelivered to
+    /// the new owner.
+    fn revoke(&mut self, topic: &str, partition: i32) {
+        self.partitions.remove(&(topic.to_string(), partition));
+    }
+}
+
+/// Resets the offsets of revoked partitions. The stored offsets of revoked
+/// partitions are committed by librdkafka before they are handed over.
+struct WorkerContext {
+    tracker: Arc<Mutex<OffsetTracker>>,
+}
+
+impl ClientContext for WorkerContext {}
+
+impl ConsumerContext for WorkerContext {
+    fn pre_rebalance(&self, rebalance: &Rebalance<'_>) {
+        if let Rebalance::Revoke(tpl) = rebalance {
+            let mut tracker = self.tracker.lock().unwrap();
+            for elem in tpl.elements() {
+                tracker.revoke(elem.topic(), elem.partition());
+            }
+        }
+    }
+}
+
+type WorkerConsumer = StreamConsumer<WorkerContext>;
+
+struct DeadLetter {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl DeadLetter {
+    async fn send(
+        &self,
+        info: &MessageInfo,
+        payload: &[u8],
+        err: &anyhow::Error,
+    ) -> anyhow::Result<()> {
+        let (partition, offset, error) = (
+            info.partition.to_string(),
+            info.offset.to_string(),
+            format!("{err:#}"),
+        );
+        let headers = OwnedHeaders::new()
+            .insert(Header {
+                key: DEAD_LETTER_TOPIC,
+                value: Some(&info.topic),
+            })
+            .insert(Header {
+                key: DEAD_LETTER_PARTITION,
+                value: Some(&partition),
+            })
+            .insert(Header {
+                key: DEAD_LETTER_OFFSET,
+                value: Some(&offset),
+            })
+            .insert(Header {
+                key: DEAD_LETTER_ERROR,
+                value: Some(&error),
+            });
+        let mut record = FutureRecord::<str, [u8]>::to(&self.topic)
+            .payload(payload)
+            .headers(headers);
+        if let Some(key) = info.key.as_deref() {
+            record = record.key(key);
+        }
+        self.producer
+            .send(record, Duration::from_secs(30))
+            .await
+            .map_err(|(err, _)| err)?;
+        Ok(())
+    }
+}
+
+struct Done {
+    info: MessageInfo,
+    payload: Vec<u8>,
+    failures: u32,
+    result: anyhow::Result<()>,
+}
+
+/// Consumer group worker with at-least-once delivery, offsets are committed
+/// after the handler succeeded for the message and all messages before it.
+///
+/// A message whose handler still fails after the retries is moved to the
+/// dead letter topic if one is configured, otherwise it is redelivered with
+/// backoff and its partition doesn't advance until it is handled.
+pub struct ConsumerWorker<T>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    group: String,
+    topics: Vec<String>,
+    concurrency: usize,
+    max_pending: usize,
+    max_retries: u32,
+    dead_letter_topic: Option<String>,
+    handler: Option<Arc<dyn Handle<T>>>,
+}
+
+impl<T> ConsumerWorker<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    /// `group` is appended to the consumer group prefix of the config.
+    pub fn new<S>(group: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            group: group.into(),
+            topics: vec![],
+            concurrency: 1,
+            max_pending: 100,
+            max_retries: 3,
+            dead_letter_topic: None,
+            handler: None,
+        }
+    }
+
+    /// Topics to subscribe, defaults to the mutation events topic.
+    pub fn with_topics<I, S>(mut self, topics: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.topics = topics.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Number of received but unhandled messages at which the assigned
+    /// partitions are paused.
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending.max(1);
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Topic for messages which can't be handled.
+    pub fn with_dead_letter_topic<S>(mut self, topic: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.dead_letter_topic = Some(topic.into());
+        self
+    }
+
+    pub fn run(mut self, handler: impl Handle<T> + 'static) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn start(self, config: &Config) -> anyhow::Result<RunningConsumer> {
+        let handler = self
+            .handler
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("consumer worker '{}' has no handler", self.group))?;
+        let tracker = Arc::new(Mutex::new(OffsetTracker::default()));
+        let consumer: WorkerConsumer = ClientConfig::new()
+            .set("bootstrap.servers", config.address())
+            .set(
+                "group.id",
+                format!(
+                    "{}_{}",
+                    config.consumer_group_mutation_events_prefix(),
+                    self.group
+                ),
+            )
+            .set("enable.auto.commit", "true")
+            .set("enable.auto.offset.store", "false")
+            .set("auto.offset.reset", "earliest")
+            .create_with_context(WorkerContext {
+                tracker: tracker.clone(),
+            })?;
+        let topics = if self.topics.is_empty() {
+            vec![config.topic_mutation_events().to_string()]
+        } else {
+            self.topics.clone()
+        };
+        let dead_letter = self
+            .dead_letter_topic
+            .clone()
+            .map(|topic| {
+                anyhow::Ok(Arc::new(DeadLetter {
+                    producer: ClientConfig::new()
+                        .set("bootstrap.servers", config.address())
+                        .set("message.timeout.ms", "5000")
+                        .create()?,
+                    topic,
+                }))
+            })
+            .transpose()?;
+        consumer.subscribe(&topics.iter().map(String::as_str).collect::<Vec<_>>())?;
+        tracing::info!("start consumer worker {} for {topics:?}", self.group);
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let handle = tokio::spawn(run(
+            consumer,
+            self,
+            handler,
+            dead_letter,
+            tracker,
+            shutdown_rx,
+        ));
+        Ok(RunningConsumer::new(shutdown_tx, handle))
+    }
+}
+
+/// Stores the offset for the next (auto) commit.
+fn store(consumer: &WorkerConsumer, topic: &str, partition: i32, offset: i64) {
+    let mut tpl = TopicPartitionList::new();
+    let result = tpl
+        .add_partition_offset(topic, partition, Offset::Offset(offset))
+        .and_then(|_| consumer.store_offsets(&tpl));
+    if let Err(err) = result {
+        tracing::warn!("unable to store offset {offset} of {topic}/{partition}: {err}");
+    }
+}
+
+fn set_paused(consumer: &WorkerConsumer, paused: bool) -> anyhow::Result<()> {
+    let assignment = consumer.assignment()?;
+    if paused {
+        consumer.pause(&assignment)?;
+    } else {
+        consumer.resume(&assignment)?;
+    }
+    Ok(())
+}
+
+async fn handle<T>(
+    handler: Arc<dyn Handle<T>>,
+    dead_letter: Option<Arc<DeadLetter>>,
+    info: &MessageInfo,
+    payload: &[u8],
+    max_retries: u32,
+) -> anyhow::Result<()>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    if let Err(err) = serde_json::from_slice::<T>(payload) {
+        tracing::error!(
+            "skipping invalid message {}/{}@{}: {err}",
+            info.topic,
+            info.partition,
+            info.offset
+        );
+        return Ok(());
+    }
+    let policy = RetryPolicy::exponential(Duration::from_millis(200))
+        .with_max_attempts(max_retries.saturating_add(1));
+    let result = retry(&policy, || async {
+        let item = serde_json::from_slice::<T>(payload)?;
+        handler.handle(info, item).await.with_context(|| {
+            format!(
+                "handling {}/{}@{} failed",
+                info.topic, info.partition, info.offset
+            )
+        })
+    })
+    .await;
+    let (Err(err), Some(dead_letter)) = (result, dead_letter) else {
+        return Ok(());
+    };
+    retry(&policy, || dead_letter.send(info, payload, &err))
+        .await
+        .with_context(|| format!("{err:#}, moving it to {} failed", dead_letter.topic))?;
+    tracing::error!("moved message to {}: {err:#}", dead_letter.topic);
+    Ok(())
+}
+
+/// Spawns the handling of messages and reports them as [`Done`].
+struct Dispatcher<T>
+where
+    T: DeserializeOwned + Send + Sync,
+{
+    semaphore: Arc<Semaphore>,
+    handler: Arc<dyn Handle<T>>,
+    dead_letter: Option<Arc<DeadLetter>>,
+    done_tx: mpsc::UnboundedSender<Done>,
+    max_retries: u32,
+    stopping: watch::Receiver<bool>,
+}
+
+impl<T> Dispatcher<T>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    /// Handles the message after `delay`, a pending redelivery is given up
+    /// when the worker stops.
+    fn dispatch(&self, info: MessageInfo, payload: Vec<u8>, failures: u32, delay: Duration) {
+        let semaphore = self.semaphore.clone();
+        let handler = self.handler.clone();
+        let dead_letter = self.dead_letter.clone();
+        let done_tx = self.done_tx.clone();
+        let max_retries = self.max_retries;
+        let mut stopping = self.stopping.clone();
+        tokio::spawn(async move {
+            let result = tokio::select! {
+                _ = tokio::time::sleep(delay) => match semaphore.acquire_owned().await {
+                    Ok(_permit) => handle(handler, dead_letter, &info, &payload, max_retries).await,
+                    Err(err) => Err(err.into()),
+                },
+                _ = stopping.changed(), if !delay.is_zero() => {
+                    Err(anyhow::anyhow!("redelivery cancelled"))
+                }
+            };
+            done_tx
+                .send(Done {
+                    info,
+                    payload,
+                    failures,
+                    result,
+                })
+                .ok();
+        });
+    }
+}
+
+async fn run<T>(
+    consumer: WorkerConsumer,
+    worker: ConsumerWorker<T>,
+    handler: Arc<dyn Handle<T>>,
+    dead_letter: Option<Arc<DeadLetter>>,
+    tracker: Arc<Mutex<OffsetTracker>>,
+    mut shutdown: oneshot::Receiver<()>,
+) -> anyhow::Result<()>
+where
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<Done>();
+    let (stopping_tx, stopping) = watch::channel(false);
+    let dispatcher = Dispatcher {
+        semaphore: Arc::new(Semaphore::new(worker.concurrency)),
+        handler,
+        dead_letter,
+        done_tx,
+        max_retries: worker.max_retries,
+        stopping,
+    };
+    let redelivery = RetryPolicy::exponential(Duration::from_secs(1));
+    let mut in_flight = 0;
+    let mut paused = false;
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            Some(done) = done_rx.recv() => {
+                let Done { info, payload, failures, result } = done;
+                if let Err(err) = result {
+                    let failures = failures + 1;
+                    let delay = redelivery.delay(failures);
+                    tracing::error!(
+                        "consumer worker {} failed on {}/{}@{}, redelivering in {delay:?}: {err:#}",
+                        worker.group, info.topic, info.partition, info.offset
+                    );
+                    dispatcher.dispatch(info, payload, failures, delay);
+                    continue;
+                }
+                in_flight -= 1;
+                let next = tracker.lock().unwrap().complete(&info.topic, info.partition, info.offset);
+                if let Some(next) = next {
+                    store(&consumer, &info.topic, info.partition, next);
+                }
+                if paused && in_flight < worker.max_pending {
+                    set_paused(&consumer, false)?;
+                    paused = false;
+                }
+            }
+            message = consumer.recv() => {
+                let message = match message {
+                    Ok(message) => message,
+                    Err(err) => {
+                        tracing::warn!("consumer worker {} receive error: {err}", worker.group);
+                        continue;
+                    }
+                };
+                let info = MessageInfo {
+                    topic: message.topic().to_string(),
+                    partition: message.partition(),
+                    offset: message.offset(),
+                    key: message.key().map(|k| String::from_utf8_lossy(k).into_owned()),
+                };
+                let payload = message.payload().map(<[u8]>::to_vec).unwrap_or_default();
+                tracker.lock().unwrap().begin(&info.topic, info.partition, info.offset);
+                in_flight += 1;
+                dispatcher.dispatch(info, payload, 0, Duration::ZERO);
+                if !paused && in_flight >= worker.max_pending {
+                    set_paused(&consumer, true)?;
+                    paused = true;
+                }
+            }
+        }
+    }
+    tracing::info!(
+        "stopping consumer worker {}, waiting for {in_flight} messages",
+        worker.group
+    );
+    stopping_tx.send(true).ok();
+    while in_flight > 0 {
+        let Some(Done { info, result, .. }) = done_rx.recv().await else {
+            break;
+        };
+        in_flight -= 1;
+        if result.is_ok() {
+            let next = tracker
+                .lock()
+                .unwrap()
+                .complete(&info.topic, info.partition, info.offset);
+            if let Some(next) = next {
+                store(&consumer, &info.topic, info.partition, next);
+            }
+        }
+    }
+    consumer
+        .commit_consumer_state(CommitMode::Sync)
+        .or_else(|err| match err.rdkafka_error_code() {
+            Some(rdkafka::types::RDKafkaErrorCode::NoOffset) => Ok(()),
+            _ => Err(err),
+        })?;
+    Ok(())
+}
+
+pub struct RunningConsumer {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<anyhow::Result<()>>,
+}
+
+impl RunningConsumer {
+    pub(crate) fn new(
+        shutdown: oneshot::Sender<()>,
+        handle: JoinHandle<anyhow::Result<()>>,
+    ) -> Self {
+        Self { shutdown, handle }
+    }
+
+    /// Stops receiving, waits for in-flight messages and commits their
+    /// offsets.
+    pub async fn terminate(self) -> anyhow::Result<()> {
+        self.shutdown.send(()).ok();
+        self.handle.await?
+    }
+
+    /// Terminates the consumer in the [`ShutdownPhase::Consumers`] of
+    /// `shutdown`.
+    pub fn register_shutdown<S>(self, shutdown: &Shutdown, name: S, deadline: Duration)
+    where
+        S: Into<String>,
+    {
+        shutdown.register(name, ShutdownPhase::Consumers, deadline, || {
+            self.terminate()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_tracker() {
+        let mut tracker = OffsetTracker::default();
+        for offset in 0..3 {
+            tracker.begin("a", 0, offset);
+        }
+        tracker.begin("a", 1, 7);
+        assert_eq!(tracker.complete("a", 0, 1), None);
+        assert_eq!(tracker.complete("a", 0, 0), Some(2));
+        assert_eq!(tracker.complete("a", 1, 7), Some(8));
+        assert_eq!(tracker.complete("a", 0, 2), Some(3));
+        assert_eq!(tracker.complete("b", 0, 0), None);
+        tracker.begin("a", 1, 8);
+        tracker.revoke("a", 1);
+        assert_eq!(tracker.complete("a", 1, 8), None);
+        tracker.begin("a", 1, 3);
+        assert_eq!(tracker.complete("a", 1, 3), Some(4));
+    }
+}
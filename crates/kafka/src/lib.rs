@@ -1,3 +1,9 @@
+#[cfg(feature = "avro")]
+pub mod avro;
+#[cfg(feature = "bridge")]
+pub mod bridge;
 pub mod config;
+pub mod consumer;
 pub mod producer;
 pub mod topics;
+pub mod typed;
@@ -120,6 +120,10 @@ impl Producer {
         &self.inner.config
     }
 
+    pub fn future_producer(&self) -> &FutureProducer {
+        &self.inner.producer
+    }
+
     pub async fn create_event<O>(
         &self,
         event_ns: &EventNs,
@@ -254,7 +258,7 @@ impl Producer {
     }
 }
 
-fn now() -> i64 {
+pub(crate) fn now() -> i64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -1,15 +1,95 @@
+use std::time::Duration;
+
 use rdkafka::admin::AdminClient;
 use rdkafka::admin::ConfigEntry;
+use rdkafka::admin::ConfigSource;
 use rdkafka::admin::ResourceSpecifier;
 use rdkafka::admin::TopicReplication;
-use rdkafka::admin::{AdminOptions, NewTopic};
+use rdkafka::admin::{AdminOptions, AlterConfig, NewPartitions, NewTopic};
 use rdkafka::client::DefaultClientContext;
 use rdkafka::groups::GroupList;
 use rdkafka::metadata::Metadata;
+use rdkafka::types::RDKafkaErrorCode;
 use rdkafka::ClientConfig;
 
 use crate::config::Config as KafkaConfig;
 
+/// Desired state of a topic, see [`Client::ensure_topic_specs`].
+#[derive(Debug, Clone)]
+pub struct TopicSpec {
+    pub name: String,
+    pub partitions: i32,
+    pub replication: i32,
+    /// `None` keeps the retention of the topic or the broker default.
+    pub retention: Option<Duration>,
+    pub compaction: bool,
+    pub config: Vec<(String, String)>,
+}
+
+impl TopicSpec {
+    pub fn new<S>(name: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            partitions: 1,
+            replication: 1,
+            retention: None,
+            compaction: false,
+            config: vec![],
+        }
+    }
+
+    pub fn with_partitions(mut self, partitions: i32) -> Self {
+        self.partitions = partitions;
+        self
+    }
+
+    pub fn with_replication(mut self, replication: i32) -> Self {
+        self.replication = replication;
+        self
+    }
+
+    pub fn with_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    pub fn with_compaction(mut self) -> Self {
+        self.compaction = true;
+        self
+    }
+
+    pub fn with_config<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.config.push((key.into(), value.into()));
+        self
+    }
+
+    fn configs(&self) -> Vec<(String, String)> {
+        let cleanup_policy = if self.compaction { "compact" } else { "delete" };
+        let mut configs = vec![("cleanup.policy".to_string(), cleanup_policy.to_string())];
+        if let Some(retention) = self.retention {
+            configs.push((
+                "retention.ms".to_string(),
+                retention.as_millis().to_string(),
+            ));
+        }
+        configs.extend(self.config.iter().cloned());
+        configs
+    }
+}
+
+impl From<&str> for TopicSpec {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
 pub struct Client {
     inner: AdminClient<DefaultClientContext>,
 }
@@ -86,6 +166,80 @@ impl Client {
         Ok(())
     }
 
+    /// Creates missing topics, raises the partition count and applies the
+    /// configuration of existing ones.
+    ///
+    /// `AlterConfigs` replaces the whole topic configuration, the dynamic
+    /// configuration of existing topics is therefore merged with the keys of
+    /// the spec and only written if one of them differs.
+    pub async fn ensure_topic_specs(&self, specs: &[TopicSpec]) -> anyhow::Result<()> {
+        let metadata = self.metadata()?;
+        let opts = AdminOptions::default();
+        let mut new_topics = vec![];
+        let mut new_partitions = vec![];
+        let mut merged_configs = vec![];
+        for spec in specs {
+            match metadata.topics().iter().find(|t| t.name() == spec.name) {
+                None => new_topics.push((spec, spec.configs())),
+                Some(topic) => {
+                    if (topic.partitions().len() as i32) < spec.partitions {
+                        new_partitions
+                            .push(NewPartitions::new(&spec.name, spec.partitions as usize));
+                    }
+                    let current = self.topic_config(&spec.name).await?;
+                    if let Some(configs) = merge_configs(&spec.name, &current, &spec.configs())? {
+                        merged_configs.push((spec, configs));
+                    }
+                }
+            }
+        }
+        if !new_topics.is_empty() {
+            let new_topics: Vec<_> = new_topics
+                .iter()
+                .map(|(spec, configs)| NewTopic {
+                    name: &spec.name,
+                    num_partitions: spec.partitions,
+                    replication: TopicReplication::Fixed(spec.replication),
+                    config: configs
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str()))
+                        .collect(),
+                })
+                .collect();
+            for result in self.inner.create_topics(&new_topics, &opts).await? {
+                match result {
+                    Ok(name) => tracing::info!("created topic {name}"),
+                    Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {}
+                    Err((name, code)) => anyhow::bail!("unable to create topic {name}: {code}"),
+                }
+            }
+        }
+        if !new_partitions.is_empty() {
+            for result in self.inner.create_partitions(&new_partitions, &opts).await? {
+                if let Err((name, code)) = result {
+                    anyhow::bail!("unable to add partitions to topic {name}: {code}");
+                }
+            }
+        }
+        if !merged_configs.is_empty() {
+            let alter_configs: Vec<_> = merged_configs
+                .iter()
+                .map(|(spec, configs)| {
+                    configs.iter().fold(
+                        AlterConfig::new(ResourceSpecifier::Topic(&spec.name)),
+                        |alter, (k, v)| alter.set(k, v),
+                    )
+                })
+                .collect();
+            for result in self.inner.alter_configs(&alter_configs, &opts).await? {
+                if let Err((resource, code)) = result {
+                    anyhow::bail!("unable to configure {resource:?}: {code}");
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub async fn topic_config(&self, topic_name: &str) -> anyhow::Result<Vec<ConfigEntry>> {
         Ok(self
             .inner
@@ -124,3 +278,91 @@ impl Client {
         Ok(())
     }
 }
+
+/// Merges the dynamic configuration `current` of a topic with `owned`,
+/// returns `None` if all keys of `owned` already have the expected value.
+fn merge_configs(
+    topic: &str,
+    current: &[ConfigEntry],
+    owned: &[(String, String)],
+) -> anyhow::Result<Option<Vec<(String, String)>>> {
+    let up_to_date = owned.iter().all(|(k, v)| {
+        current
+            .iter()
+            .any(|e| &e.name == k && e.value.as_ref() == Some(v))
+    });
+    if up_to_date {
+        return Ok(None);
+    }
+    let mut configs = vec![];
+    for entry in current {
+        if entry.source != ConfigSource::DynamicTopic || owned.iter().any(|(k, _)| k == &entry.name)
+        {
+            continue;
+        }
+        match entry.value.as_ref() {
+            Some(value) => configs.push((entry.name.clone(), value.clone())),
+            None => anyhow::bail!(
+                "unable to configure topic {topic}: value of {} is not readable",
+                entry.name
+            ),
+        }
+    }
+    configs.extend(owned.iter().cloned());
+    Ok(Some(configs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_topic_spec_configs() {
+        let spec = TopicSpec::new("events")
+            .with_retention(Duration::from_secs(60))
+            .with_compaction()
+            .with_config("min.insync.replicas", "2");
+        assert_eq!(
+            spec.configs(),
+            vec![
+                ("cleanup.policy".to_string(), "compact".to_string()),
+                ("retention.ms".to_string(), "60000".to_string()),
+                ("min.insync.replicas".to_string(), "2".to_string()),
+            ]
+        );
+        assert!(TopicSpec::from("a")
+            .configs()
+            .iter()
+            .all(|(k, _)| k != "retention.ms"));
+    }
+
+    fn entry(name: &str, value: &str, source: ConfigSource) -> ConfigEntry {
+        ConfigEntry {
+            name: name.to_string(),
+            value: Some(value.to_string()),
+            is_default: source == ConfigSource::Default,
+            source,
+            is_read_only: false,
+            is_sensitive: false,
+        }
+    }
+
+    #[test]
+    fn test_merge_configs() {
+        let current = vec![
+            entry("cleanup.policy", "delete", ConfigSource::Default),
+            entry("max.message.bytes", "2097152", ConfigSource::DynamicTopic),
+            entry("segment.ms", "604800000", ConfigSource::StaticBroker),
+        ];
+        let owned = TopicSpec::new("a").with_compaction().configs();
+        assert_eq!(
+            merge_configs("a", &current, &owned).unwrap(),
+            Some(vec![
+                ("max.message.bytes".to_string(), "2097152".to_string()),
+                ("cleanup.policy".to_string(), "compact".to_string()),
+            ])
+        );
+        let owned = TopicSpec::new("a").configs();
+        assert_eq!(merge_configs("a", &current, &owned).unwrap(), None);
+    }
+}
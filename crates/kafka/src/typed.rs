@@ -0,0 +1,128 @@
+use std::{
+    borrow::Cow,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use rdkafka::producer::{DeliveryFuture, FutureRecord};
+
+use crate::producer::Producer;
+
+/// Event with a message key and optionally a fixed partition.
+pub trait KeyedEvent {
+    fn key(&self) -> Cow<'_, str>;
+
+    fn partition(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Serializes values of `T` into message payloads.
+#[async_trait::async_trait]
+pub trait ValueSerializer<T>: Send + Sync {
+    async fn serialize(&self, topic: &str, value: &T) -> anyhow::Result<Vec<u8>>;
+}
+
+/// Plain JSON payloads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+#[async_trait::async_trait]
+impl<T> ValueSerializer<T> for Json
+where
+    T: serde::Serialize + Send + Sync,
+{
+    async fn serialize(&self, _: &str, value: &T) -> anyhow::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(value)?)
+    }
+}
+
+/// Resolves to partition and offset once the broker acknowledged the message.
+pub struct Delivery(DeliveryFuture);
+
+impl Future for Delivery {
+    type Output = anyhow::Result<(i32, i64)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| match result {
+            Ok(Ok(v)) => Ok(v),
+            Ok(Err((err, _))) => Err(anyhow::anyhow!("delivery failed: {err}")),
+            Err(_) => Err(anyhow::anyhow!("delivery canceled")),
+        })
+    }
+}
+
+/// Producer for a single topic with typed values.
+pub struct TypedProducer<T, S = Json> {
+    producer: Producer,
+    topic: Arc<str>,
+    serializer: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T, S: Clone> Clone for TypedProducer<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            producer: self.producer.clone(),
+            topic: self.topic.clone(),
+            serializer: self.serializer.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, S> TypedProducer<T, S>
+where
+    T: KeyedEvent + Send + Sync,
+    S: ValueSerializer<T>,
+{
+    pub fn new(producer: Producer, topic: &str, serializer: S) -> Self {
+        Self {
+            producer,
+            topic: Arc::from(topic),
+            serializer,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Enqueues `value` and returns the pending [`Delivery`].
+    pub async fn send(&self, value: &T) -> anyhow::Result<Delivery> {
+        let payload = self.serializer.serialize(&self.topic, value).await?;
+        let key = value.key();
+        let mut record = FutureRecord::to(&self.topic)
+            .key(key.as_ref())
+            .payload(&payload)
+            .timestamp(crate::producer::now());
+        if let Some(partition) = value.partition() {
+            record = record.partition(partition);
+        }
+        let delivery = self
+            .producer
+            .future_producer()
+            .send_result(record)
+            .map_err(|(err, _)| anyhow::anyhow!("unable to enqueue message: {err}"))?;
+        Ok(Delivery(delivery))
+    }
+
+    /// Sends `value` and waits for the acknowledgement of the broker.
+    pub async fn send_and_wait(&self, value: &T) -> anyhow::Result<(i32, i64)> {
+        self.send(value).await?.await
+    }
+}
+
+impl Producer {
+    /// [`TypedProducer`] with JSON payloads for `topic`.
+    pub fn typed<T>(&self, topic: &str) -> TypedProducer<T>
+    where
+        T: KeyedEvent + serde::Serialize + Send + Sync,
+    {
+        TypedProducer::new(self.clone(), topic, Json)
+    }
+}
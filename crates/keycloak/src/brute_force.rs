@@ -0,0 +1,93 @@
+//! Brute force detection of a realm.
+//!
+//! Keycloak temporarily or permanently disables users after too many failed
+//! logins. [`BruteForceStatus`] is the state of a single user,
+//! [`BruteForceSettings`] the realm settings controlling the lockout.
+
+use keycloak::types::RealmRepresentation;
+use serde::{Deserialize, Serialize};
+
+/// Login failures of a user as reported by the attack detection.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BruteForceStatus {
+    #[serde(default)]
+    pub num_failures: u32,
+    /// The user is currently locked out.
+    #[serde(default)]
+    pub disabled: bool,
+    /// IP address of the last failed login, `n/a` if unknown.
+    #[serde(default, rename = "lastIPFailure")]
+    pub last_ip_failure: Option<String>,
+    /// Time of the last failed login in milliseconds since the epoch, `0` if
+    /// there was none.
+    #[serde(default)]
+    pub last_failure: i64,
+}
+
+/// Brute force settings of a realm, `None` keeps the value of the realm.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BruteForceSettings {
+    pub enabled: Option<bool>,
+    pub permanent_lockout: Option<bool>,
+    /// Failed logins until the user is locked out.
+    pub failure_factor: Option<i32>,
+    pub wait_increment_seconds: Option<i32>,
+    pub max_failure_wait_seconds: Option<i32>,
+    pub max_delta_time_seconds: Option<i32>,
+    pub quick_login_check_milli_seconds: Option<i64>,
+    pub minimum_quick_login_wait_seconds: Option<i32>,
+}
+
+impl BruteForceSettings {
+    pub fn from_realm(realm: &RealmRepresentation) -> Self {
+        Self {
+            enabled: realm.brute_force_protected,
+            permanent_lockout: realm.permanent_lockout,
+            failure_factor: realm.failure_factor,
+            wait_increment_seconds: realm.wait_increment_seconds,
+            max_failure_wait_seconds: realm.max_failure_wait_seconds,
+            max_delta_time_seconds: realm.max_delta_time_seconds,
+            quick_login_check_milli_seconds: realm.quick_login_check_milli_seconds,
+            minimum_quick_login_wait_seconds: realm.minimum_quick_login_wait_seconds,
+        }
+    }
+
+    /// Writes the configured values to `realm`, returns `true` if `realm`
+    /// changed.
+    pub fn apply(&self, realm: &mut RealmRepresentation) -> bool {
+        fn set<T: PartialEq + Copy>(target: &mut Option<T>, value: Option<T>) -> bool {
+            match value {
+                Some(value) if *target != Some(value) => {
+                    *target = Some(value);
+                    true
+                }
+                _ => false,
+            }
+        }
+        let mut changed = set(&mut realm.brute_force_protected, self.enabled);
+        changed |= set(&mut realm.permanent_lockout, self.permanent_lockout);
+        changed |= set(&mut realm.failure_factor, self.failure_factor);
+        changed |= set(
+            &mut realm.wait_increment_seconds,
+            self.wait_increment_seconds,
+        );
+        changed |= set(
+            &mut realm.max_failure_wait_seconds,
+            self.max_failure_wait_seconds,
+        );
+        changed |= set(
+            &mut realm.max_delta_time_seconds,
+            self.max_delta_time_seconds,
+        );
+        changed |= set(
+            &mut realm.quick_login_check_milli_seconds,
+            self.quick_login_check_milli_seconds,
+        );
+        changed |= set(
+            &mut realm.minimum_quick_login_wait_seconds,
+            self.minimum_quick_login_wait_seconds,
+        );
+        changed
+    }
+}
@@ -1,16 +1,26 @@
-use std::{borrow::Cow, sync::Arc};
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
 
 pub use keycloak::{
     types::{
         AuthenticationExecutionInfoRepresentation, AuthenticationFlowRepresentation,
-        AuthenticatorConfigRepresentation, ClientRepresentation, CredentialRepresentation,
-        GroupRepresentation, RealmRepresentation, RoleRepresentation, TypeMap, UserRepresentation,
+        AuthenticatorConfigRepresentation, ClientRepresentation, ComponentRepresentation,
+        CredentialRepresentation, GroupRepresentation, KeysMetadataRepresentation,
+        RealmEventsConfigRepresentation, RealmRepresentation, RoleRepresentation, TypeMap,
+        UPAttribute, UPConfig, UPGroup, UnmanagedAttributePolicy, UserRepresentation,
+        UserSessionRepresentation,
     },
     KeycloakAdmin, KeycloakError, KeycloakTokenSupplier,
 };
 use serde_json::Value;
 
+use crate::brute_force::{BruteForceSettings, BruteForceStatus};
+use crate::events::{EventsConfig, HttpEventListener};
+use crate::keys::{self, KeyAlgorithm, KeyProvider, KEY_PROVIDER_TYPE};
+use crate::limiter::RateLimiter;
+use crate::localization::Localization;
+use crate::otp_policy::OtpPolicy;
 use crate::session::{KeycloakSession, KeycloakSessionClient};
+use crate::user_profile::UserProfile;
 
 pub use crate::config::Config as KeycloakConfig;
 
@@ -48,12 +58,19 @@ struct Inner {
     client: reqwest::Client,
     session: KeycloakSession,
     admin: KeycloakAdmin<KeycloakSession>,
+    user_profile: Option<UserProfile>,
+    localization: Option<Localization>,
+    events: Option<EventsConfig>,
+    limiter: RateLimiter,
 }
 
 #[derive(Default)]
 pub struct KeycloakBuilder {
     no_refresh: bool,
     env_prefix: Option<&'static str>,
+    user_profile: Option<UserProfile>,
+    localization: Option<Localization>,
+    events: Option<EventsConfig>,
 }
 
 impl KeycloakBuilder {
@@ -67,6 +84,31 @@ impl KeycloakBuilder {
         self
     }
 
+    /// Custom attributes expected in the user profile of the realm, checked
+    /// and provisioned by the realm validation.
+    pub fn with_user_profile(mut self, user_profile: UserProfile) -> Self {
+        self.user_profile = Some(user_profile);
+        self
+    }
+
+    /// Localization texts expected in the realm, checked and provisioned by
+    /// the realm validation.
+    pub fn with_localization(mut self, localization: Localization) -> Self {
+        self.localization = Some(localization);
+        self
+    }
+
+    /// Events configuration expected in the realm, checked and provisioned by
+    /// the realm validation.
+    pub fn with_events(mut self, events: EventsConfig) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.build", skip_all)
+    )]
     pub async fn build(self) -> anyhow::Result<Keycloak> {
         let mut config_builder = KeycloakConfig::builder();
         if let Some(prefix) = self.env_prefix {
@@ -78,6 +120,7 @@ impl KeycloakBuilder {
         let username: Arc<str> = Arc::from(config.username().to_string());
         let password: Arc<str> = Arc::from(config.password().to_string());
         let client = reqwest::Client::new();
+        let limiter = RateLimiter::new(config.rate_limit());
         let session_client = KeycloakSessionClient::new(config.address(), "master", "admin-cli");
         let session =
             KeycloakSession::new(session_client, &username, &password, refresh_token_enabled)
@@ -89,6 +132,10 @@ impl KeycloakBuilder {
                 client: client.clone(),
                 session: session.clone(),
                 admin: KeycloakAdmin::new(&url, session, client),
+                user_profile: self.user_profile,
+                localization: self.localization,
+                events: self.events,
+                limiter,
             }),
         })
     }
@@ -120,6 +167,36 @@ impl Keycloak {
         &self.inner.config
     }
 
+    pub fn expected_user_profile(&self) -> Option<&UserProfile> {
+        self.inner.user_profile.as_ref()
+    }
+
+    pub fn expected_localization(&self) -> Option<&Localization> {
+        self.inner.localization.as_ref()
+    }
+
+    pub fn expected_events(&self) -> Option<&EventsConfig> {
+        self.inner.events.as_ref()
+    }
+
+    /// Limiter of the user write operations, see [`crate::limiter`].
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.inner.limiter
+    }
+
+    /// Bulk operation on `users` of `realm` which respects the limiter.
+    pub fn batch(&self, realm: &str, users: Vec<UserRepresentation>) -> UserBatch {
+        UserBatch {
+            keycloak: self.clone(),
+            realm: Arc::from(realm),
+            users,
+        }
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.users", skip_all, fields(realm = %realm))
+    )]
     pub async fn users(
         &self,
         realm: &str,
@@ -153,6 +230,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_realm", skip_all)
+    )]
     pub async fn create_realm(
         &self,
         realm_representation: RealmRepresentation,
@@ -169,10 +250,18 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_realm", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_realm(&self, realm: &str) -> Result<(), KeycloakError> {
         self.inner.admin.realm_delete(realm).await
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_group", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_group(&self, realm: &str, id: &str) -> Result<(), KeycloakError> {
         self.inner
             .admin
@@ -184,6 +273,14 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.remove_group_by_path",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn remove_group_by_path(&self, realm: &str, path: &str) -> Result<(), KeycloakError> {
         let group = self
             .inner
@@ -202,6 +299,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_role", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_role(&self, realm: &str, role_name: &str) -> Result<(), KeycloakError> {
         self.inner
             .admin
@@ -213,6 +314,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_role_by_id", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_role_by_id(&self, realm: &str, role_id: &str) -> Result<(), KeycloakError> {
         self.inner
             .admin
@@ -224,6 +329,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.realms", skip_all)
+    )]
     pub async fn realms(&self) -> Result<Vec<String>, KeycloakError> {
         let builder = self
             .inner
@@ -255,6 +364,10 @@ impl Keycloak {
             .collect())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.clients", skip_all, fields(realm = %realm))
+    )]
     pub async fn clients(&self, realm: &str) -> Result<Vec<ClientRepresentation>, KeycloakError> {
         let page_offset = 1000;
         let mut offset = 0;
@@ -286,6 +399,10 @@ impl Keycloak {
         Ok(clients)
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.realm_by_name", skip_all, fields(realm = %realm))
+    )]
     pub async fn realm_by_name(&self, realm: &str) -> Result<RealmRepresentation, KeycloakError> {
         self.inner.admin.realm_get(realm).await.map_err(|e| {
             tracing::error!("{e:#?}");
@@ -293,6 +410,14 @@ impl Keycloak {
         })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.update_realm_by_name",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn update_realm_by_name(
         &self,
         realm: &str,
@@ -304,6 +429,10 @@ impl Keycloak {
         })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.roles", skip_all, fields(realm = %realm))
+    )]
     pub async fn roles(&self, realm: &str) -> Result<Vec<RoleRepresentation>, KeycloakError> {
         self.inner
             .admin
@@ -315,6 +444,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.all_roles", skip_all, fields(realm = %realm))
+    )]
     pub async fn all_roles(&self, realm: &str) -> Result<Vec<RoleRepresentation>, KeycloakError> {
         let page_offset = 1000;
         let mut offset = 0;
@@ -338,6 +471,10 @@ impl Keycloak {
         Ok(roles)
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.realm_role_by_name", skip_all, fields(realm = %realm))
+    )]
     pub async fn realm_role_by_name(
         &self,
         realm: &str,
@@ -353,6 +490,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_role", skip_all, fields(realm = %realm))
+    )]
     pub async fn create_role(
         &self,
         realm: &str,
@@ -368,6 +509,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_group", skip_all, fields(realm = %realm))
+    )]
     pub async fn create_group(
         &self,
         realm: &str,
@@ -383,6 +528,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.group_by_path", skip_all, fields(realm = %realm))
+    )]
     pub async fn group_by_path(
         &self,
         realm: &str,
@@ -398,6 +547,98 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.group_by_id", skip_all, fields(realm = %realm))
+    )]
+    pub async fn group_by_id(
+        &self,
+        realm: &str,
+        id: &str,
+    ) -> Result<GroupRepresentation, KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_get(realm, id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    /// Members of the group `group_id` starting at `offset`, at most `max` or
+    /// all of them if `max` is `None`.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.group_members", skip_all, fields(realm = %realm))
+    )]
+    pub async fn group_members(
+        &self,
+        realm: &str,
+        group_id: &str,
+        offset: Option<i32>,
+        max: Option<i32>,
+        brief: Option<bool>,
+    ) -> Result<Vec<UserRepresentation>, KeycloakError> {
+        let page_offset = 1000;
+        let mut offset = offset.unwrap_or(0);
+        let mut members = vec![];
+        loop {
+            let page_size = match max {
+                Some(max) => page_offset.min(max - members.len() as i32),
+                None => page_offset,
+            };
+            if page_size <= 0 {
+                break;
+            }
+            let result = self
+                .inner
+                .admin
+                .realm_groups_with_group_id_members_get(
+                    realm,
+                    group_id,
+                    brief,
+                    Some(offset),
+                    Some(page_size),
+                )
+                .await
+                .map_err(|e| {
+                    tracing::error!("{e:#?}");
+                    e
+                })?;
+            if result.is_empty() {
+                break;
+            }
+            offset += page_size;
+            members.extend(result);
+        }
+        Ok(members)
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.update_group", skip_all, fields(realm = %realm))
+    )]
+    pub async fn update_group(
+        &self,
+        realm: &str,
+        id: &str,
+        rep: GroupRepresentation,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_put(realm, id, rep)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.role_members", skip_all, fields(realm = %realm))
+    )]
     pub async fn role_members(
         &self,
         realm: &str,
@@ -413,6 +654,14 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.create_sub_group_with_id",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn create_sub_group_with_id(
         &self,
         realm: &str,
@@ -430,6 +679,14 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.create_realm_role_mappings_by_group_id",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn create_realm_role_mappings_by_group_id(
         &self,
         realm: &str,
@@ -446,6 +703,57 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.realm_role_mappings_by_group_id",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn realm_role_mappings_by_group_id(
+        &self,
+        realm: &str,
+        id: &str,
+    ) -> Result<Vec<RoleRepresentation>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_role_mappings_realm_get(realm, id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.remove_realm_role_mappings_by_group_id",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn remove_realm_role_mappings_by_group_id(
+        &self,
+        realm: &str,
+        id: &str,
+        roles: Vec<RoleRepresentation>,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_groups_with_group_id_role_mappings_realm_delete(realm, id, roles)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.user_by_id", skip_all, fields(realm = %realm))
+    )]
     pub async fn user_by_id(
         &self,
         realm: &str,
@@ -463,6 +771,10 @@ impl Keycloak {
             .ok())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.user_by_role", skip_all, fields(realm = %realm))
+    )]
     pub async fn user_by_role(
         &self,
         realm: &str,
@@ -487,6 +799,10 @@ impl Keycloak {
             }))
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.user_by_username", skip_all, fields(realm = %realm))
+    )]
     pub async fn user_by_username(
         &self,
         realm: &str,
@@ -527,6 +843,10 @@ impl Keycloak {
             }))
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.info", skip_all, fields(realm = %realm))
+    )]
     pub async fn info(&self, realm: &str) -> Result<RealmInfo, KeycloakError> {
         let builder = self
             .inner
@@ -543,6 +863,10 @@ impl Keycloak {
             .await?)
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.get_client", skip_all, fields(realm = %realm))
+    )]
     pub async fn get_client(
         &self,
         realm: &str,
@@ -567,6 +891,10 @@ impl Keycloak {
             .pop())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.get_client_by_id", skip_all, fields(realm = %realm))
+    )]
     pub async fn get_client_by_id(
         &self,
         realm: &str,
@@ -592,6 +920,14 @@ impl Keycloak {
             .pop())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.get_client_service_account",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn get_client_service_account(
         &self,
         realm: &str,
@@ -607,6 +943,10 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_client", skip_all, fields(realm = %realm))
+    )]
     pub async fn create_client(
         &self,
         realm: &str,
@@ -623,6 +963,10 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_client", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_client(&self, realm: &str, client_id: &str) -> Result<(), KeycloakError> {
         let client = self
             .get_client_by_id(realm, client_id)
@@ -643,6 +987,14 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.remove_client_with_uuid",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn remove_client_with_uuid(
         &self,
         realm: &str,
@@ -659,6 +1011,10 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.update_client", skip_all, fields(realm = %realm))
+    )]
     pub async fn update_client(
         &self,
         realm: &str,
@@ -675,11 +1031,42 @@ impl Keycloak {
             })
     }
 
+    /// Generates a new secret for the client and returns it.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.regenerate_client_secret",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn regenerate_client_secret(
+        &self,
+        realm: &str,
+        client_uuid: &str,
+    ) -> Result<Option<String>, KeycloakError> {
+        Ok(self
+            .inner
+            .admin
+            .realm_clients_with_client_uuid_client_secret_post(realm, client_uuid)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?
+            .value)
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_user", skip_all, fields(realm = %realm))
+    )]
     pub async fn create_user(
         &self,
         realm: &str,
         user: UserRepresentation,
     ) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_post(realm, user)
@@ -691,12 +1078,17 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.update_password", skip_all, fields(realm = %realm))
+    )]
     pub async fn update_password(
         &self,
         realm: &str,
         user_id: &str,
         credential: CredentialRepresentation,
     ) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_reset_password_put(realm, user_id, credential)
@@ -708,12 +1100,17 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.update_user", skip_all, fields(realm = %realm))
+    )]
     pub async fn update_user(
         &self,
         realm: &str,
         user_id: &str,
         user: &UserRepresentation,
     ) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_put(realm, user_id, user.to_owned())
@@ -725,12 +1122,17 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.add_user_to_group", skip_all, fields(realm = %realm))
+    )]
     pub async fn add_user_to_group(
         &self,
         realm: &str,
         user_id: &str,
         group_id: &str,
     ) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_groups_with_group_id_put(realm, user_id, group_id)
@@ -742,12 +1144,17 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.add_user_role", skip_all, fields(realm = %realm))
+    )]
     pub async fn add_user_role(
         &self,
         realm: &str,
         user_id: &str,
         role: RoleRepresentation,
     ) -> Result<Option<String>, KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_role_mappings_realm_post(realm, user_id, vec![role])
@@ -758,12 +1165,21 @@ impl Keycloak {
             })
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.remove_user_from_group",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn remove_user_from_group(
         &self,
         realm: &str,
         user_id: &str,
         group_id: &str,
     ) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_groups_with_group_id_delete(realm, user_id, group_id)
@@ -775,7 +1191,12 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_user", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_user(&self, realm: &str, user_id: &str) -> Result<(), KeycloakError> {
+        let _permit = self.inner.limiter.acquire(realm).await;
         self.inner
             .admin
             .realm_users_with_user_id_delete(realm, user_id)
@@ -787,6 +1208,50 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.user_sessions", skip_all, fields(realm = %realm))
+    )]
+    pub async fn user_sessions(
+        &self,
+        realm: &str,
+        user_id: &str,
+    ) -> Result<Vec<UserSessionRepresentation>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_users_with_user_id_sessions_get(realm, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    /// Removes all sessions of the user.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.logout_user", skip_all, fields(realm = %realm))
+    )]
+    pub async fn logout_user(&self, realm: &str, user_id: &str) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_users_with_user_id_logout_post(realm, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.send_verify_email_user",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn send_verify_email_user(
         &self,
         realm: &str,
@@ -810,22 +1275,53 @@ impl Keycloak {
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.send_custom_email_user",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn send_custom_email_user(
         &self,
         realm: &str,
         user_id: &str,
         redirect_url: Option<String>,
         body: Vec<String>,
+    ) -> Result<(), KeycloakError> {
+        self.send_execute_actions_email(realm, user_id, None, None, redirect_url, body)
+            .await
+    }
+
+    /// Sends an email with a link to perform `actions`, the link expires
+    /// after `lifespan` seconds.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.send_execute_actions_email",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn send_execute_actions_email(
+        &self,
+        realm: &str,
+        user_id: &str,
+        client_id: Option<String>,
+        lifespan: Option<i32>,
+        redirect_url: Option<String>,
+        actions: Vec<String>,
     ) -> Result<(), KeycloakError> {
         self.inner
             .admin
             .realm_users_with_user_id_execute_actions_email_put(
                 realm,
                 user_id,
-                None,
-                None,
+                client_id,
+                lifespan,
                 redirect_url,
-                body,
+                actions,
             )
             .await
             .map_err(|e| {
@@ -856,6 +1352,14 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.get_authentication_flows",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn get_authentication_flows(
         &self,
         realm: &str,
@@ -863,6 +1367,14 @@ impl Keycloak {
         self.inner.admin.realm_authentication_flows_get(realm).await
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.copy_authentication_flow",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn copy_authentication_flow(
         &self,
         realm: &str,
@@ -886,6 +1398,10 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.get_flow_executions", skip_all, fields(realm = %realm))
+    )]
     pub async fn get_flow_executions(
         &self,
         realm: &str,
@@ -908,6 +1424,10 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.remove_execution", skip_all, fields(realm = %realm))
+    )]
     pub async fn remove_execution(&self, realm: &str, id: &str) -> Result<(), KeycloakError> {
         let result = self
             .inner
@@ -926,6 +1446,10 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.create_subflow", skip_all, fields(realm = %realm))
+    )]
     pub async fn create_subflow(
         &self,
         realm: &str,
@@ -949,6 +1473,14 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.modify_flow_execution",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn modify_flow_execution(
         &self,
         realm: &str,
@@ -972,6 +1504,14 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.create_flow_execution",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn create_flow_execution(
         &self,
         realm: &str,
@@ -997,6 +1537,14 @@ impl Keycloak {
         }
     }
 
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.add_authenticator_config",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
     pub async fn add_authenticator_config(
         &self,
         realm: &str,
@@ -1013,4 +1561,485 @@ impl Keycloak {
             .await?;
         Ok(())
     }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.user_profile", skip_all, fields(realm = %realm))
+    )]
+    pub async fn user_profile(&self, realm: &str) -> Result<UPConfig, KeycloakError> {
+        self.inner.admin.realm_users_profile_get(realm).await
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.update_user_profile",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn update_user_profile(
+        &self,
+        realm: &str,
+        config: UPConfig,
+    ) -> Result<UPConfig, KeycloakError> {
+        self.inner
+            .admin
+            .realm_users_profile_put(realm, config)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.brute_force_status", skip_all, fields(realm = %realm))
+    )]
+    pub async fn brute_force_status(
+        &self,
+        realm: &str,
+        user_id: &str,
+    ) -> Result<BruteForceStatus, KeycloakError> {
+        let status = self
+            .inner
+            .admin
+            .realm_attack_detection_brute_force_users_with_user_id_get(realm, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?;
+        Ok(serde_json::from_value(Value::Object(status.into_iter().collect())).unwrap_or_default())
+    }
+
+    /// Clears the login failures of the user, unlocks a temporarily
+    /// disabled user.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.clear_brute_force", skip_all, fields(realm = %realm))
+    )]
+    pub async fn clear_brute_force(&self, realm: &str, user_id: &str) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_attack_detection_brute_force_users_with_user_id_delete(realm, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    /// Clears the login failures of all users of the realm.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.clear_all_brute_force",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn clear_all_brute_force(&self, realm: &str) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_attack_detection_brute_force_users_delete(realm)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    pub async fn brute_force_settings(
+        &self,
+        realm: &str,
+    ) -> Result<BruteForceSettings, KeycloakError> {
+        Ok(BruteForceSettings::from_realm(
+            &self.realm_by_name(realm).await?,
+        ))
+    }
+
+    /// Updates the configured brute force settings of the realm, returns
+    /// `true` if the realm changed.
+    pub async fn update_brute_force_settings(
+        &self,
+        realm: &str,
+        settings: &BruteForceSettings,
+    ) -> Result<bool, KeycloakError> {
+        let mut rep = self.realm_by_name(realm).await?;
+        if !settings.apply(&mut rep) {
+            return Ok(false);
+        }
+        self.update_realm_by_name(realm, rep).await?;
+        Ok(true)
+    }
+
+    /// Locales with localization texts in the realm.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.localization_locales", skip_all, fields(realm = %realm))
+    )]
+    pub async fn localization_locales(&self, realm: &str) -> Result<Vec<String>, KeycloakError> {
+        self.inner.admin.realm_localization_get(realm).await
+    }
+
+    /// Localization texts of the realm for `locale`, without fallback to the
+    /// default locale of the realm.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.localization_texts", skip_all, fields(realm = %realm))
+    )]
+    pub async fn localization_texts(
+        &self,
+        realm: &str,
+        locale: &str,
+    ) -> Result<HashMap<String, String>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_localization_with_locale_get(realm, locale, Some(false))
+            .await
+    }
+
+    /// Adds or replaces the `texts` of `locale`, other texts are kept.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.update_localization_texts",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn update_localization_texts(
+        &self,
+        realm: &str,
+        locale: &str,
+        texts: HashMap<String, String>,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_localization_with_locale_post(realm, locale, texts)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?;
+        Ok(())
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.update_localization_text",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn update_localization_text(
+        &self,
+        realm: &str,
+        locale: &str,
+        key: &str,
+        text: String,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_localization_with_locale_with_key_put(realm, key, locale, text)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.remove_localization_text",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn remove_localization_text(
+        &self,
+        realm: &str,
+        locale: &str,
+        key: &str,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_localization_with_locale_with_key_delete(realm, key, locale)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    pub async fn otp_policy(&self, realm: &str) -> Result<OtpPolicy, KeycloakError> {
+        Ok(OtpPolicy::from_realm(&self.realm_by_name(realm).await?))
+    }
+
+    /// Updates the OTP policy of the realm, returns `true` if the realm
+    /// changed.
+    pub async fn update_otp_policy(
+        &self,
+        realm: &str,
+        policy: &OtpPolicy,
+    ) -> Result<bool, KeycloakError> {
+        let mut rep = self.realm_by_name(realm).await?;
+        if !policy.apply(&mut rep) {
+            return Ok(false);
+        }
+        self.update_realm_by_name(realm, rep).await?;
+        Ok(true)
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.events_config", skip_all, fields(realm = %realm))
+    )]
+    pub async fn events_config(
+        &self,
+        realm: &str,
+    ) -> Result<RealmEventsConfigRepresentation, KeycloakError> {
+        self.inner
+            .admin
+            .realm_events_config_get(realm)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(
+            name = "keycloak.update_events_config",
+            skip_all,
+            fields(realm = %realm)
+        )
+    )]
+    pub async fn update_events_config(
+        &self,
+        realm: &str,
+        rep: RealmEventsConfigRepresentation,
+    ) -> Result<(), KeycloakError> {
+        self.inner
+            .admin
+            .realm_events_config_put(realm, rep)
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    /// Applies `events` and the settings of its HTTP listener to the realm,
+    /// returns `true` if the realm changed.
+    pub async fn apply_events_config(
+        &self,
+        realm: &str,
+        events: &EventsConfig,
+    ) -> Result<bool, KeycloakError> {
+        let mut changed = false;
+        if let Some(listener) = &events.http_listener {
+            let mut rep = self.realm_by_name(realm).await?;
+            if listener.apply(&mut rep) {
+                self.update_realm_by_name(realm, rep).await?;
+                changed = true;
+            }
+        }
+        let mut rep = self.events_config(realm).await?;
+        if events.apply(&mut rep) {
+            self.update_events_config(realm, rep).await?;
+            changed = true;
+        }
+        Ok(changed)
+    }
+
+    /// Registers the HTTP event listener SPI in the realm, other listeners
+    /// are kept. Returns `true` if the realm changed.
+    pub async fn register_http_event_listener(
+        &self,
+        realm: &str,
+        listener: HttpEventListener,
+    ) -> Result<bool, KeycloakError> {
+        let events = EventsConfig::new()
+            .without_storage()
+            .with_http_listener(listener);
+        self.apply_events_config(realm, &events).await
+    }
+
+    /// Keys of the realm with their status, `active` maps the algorithms to
+    /// the kid used for signing.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.realm_keys", skip_all, fields(realm = %realm))
+    )]
+    pub async fn realm_keys(
+        &self,
+        realm: &str,
+    ) -> Result<KeysMetadataRepresentation, KeycloakError> {
+        self.inner.admin.realm_keys_get(realm).await.map_err(|e| {
+            tracing::error!("{e:#?}");
+            e
+        })
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.key_providers", skip_all, fields(realm = %realm))
+    )]
+    pub async fn key_providers(
+        &self,
+        realm: &str,
+    ) -> Result<Vec<ComponentRepresentation>, KeycloakError> {
+        self.inner
+            .admin
+            .realm_components_get(realm, None, None, Some(KEY_PROVIDER_TYPE.to_string()))
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })
+    }
+
+    /// Adds a generated key provider to the realm, returns its component id.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.add_key_provider", skip_all, fields(realm = %realm))
+    )]
+    pub async fn add_key_provider(
+        &self,
+        realm: &str,
+        provider: &KeyProvider,
+    ) -> Result<String, KeycloakError> {
+        let realm_id = self.realm_by_name(realm).await?.id.unwrap_or_default();
+        self.inner
+            .admin
+            .realm_components_post(realm, provider.to_component(&realm_id))
+            .await
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?
+            .ok_or_else(|| KeycloakError::HttpFailure {
+                status: 500,
+                body: None,
+                text: format!("key provider '{}' was created without id", provider.name),
+            })
+    }
+
+    /// Creates a new active key provider for `algorithm` with a higher
+    /// priority than the existing ones and returns the kid of the new key.
+    /// Previous providers of the algorithm are demoted to passive if
+    /// `keep_previous` is set, so tokens signed with them stay valid until
+    /// they expire, otherwise they are removed. Instances validating tokens
+    /// should call [`crate::JwtStore::invalidate`] afterwards.
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "keycloak.rotate_realm_keys", skip_all, fields(realm = %realm))
+    )]
+    pub async fn rotate_realm_keys(
+        &self,
+        realm: &str,
+        algorithm: KeyAlgorithm,
+        keep_previous: bool,
+    ) -> Result<String, KeycloakError> {
+        let previous: Vec<ComponentRepresentation> = self
+            .key_providers(realm)
+            .await?
+            .into_iter()
+            .filter(|component| keys::is_provider_of(component, algorithm))
+            .collect();
+        let priority = previous
+            .iter()
+            .map(keys::component_priority)
+            .max()
+            .map(|v| v + 1)
+            .unwrap_or(100);
+        let name = format!(
+            "{}-{}",
+            algorithm.provider_id(),
+            chrono::Utc::now().format("%Y%m%d%H%M%S")
+        );
+        let id = self
+            .add_key_provider(realm, &KeyProvider::new(name, algorithm, priority))
+            .await?;
+        for mut component in previous {
+            let Some(component_id) = component.id.clone() else {
+                continue;
+            };
+            if keep_previous {
+                keys::set_component_config(&mut component, "active", false.to_string());
+                self.inner
+                    .admin
+                    .realm_components_with_id_put(realm, &component_id, component)
+                    .await
+            } else {
+                self.inner
+                    .admin
+                    .realm_components_with_id_delete(realm, &component_id)
+                    .await
+            }
+            .map_err(|e| {
+                tracing::error!("{e:#?}");
+                e
+            })?;
+        }
+        self.realm_keys(realm)
+            .await?
+            .keys
+            .unwrap_or_default()
+            .into_iter()
+            .find(|key| key.provider_id.as_deref() == Some(id.as_str()))
+            .and_then(|key| key.kid)
+            .ok_or_else(|| KeycloakError::HttpFailure {
+                status: 404,
+                body: None,
+                text: format!("key of provider '{id}' not found"),
+            })
+    }
+}
+
+pub struct UserBatch {
+    keycloak: Keycloak,
+    realm: Arc<str>,
+    users: Vec<UserRepresentation>,
+}
+
+impl UserBatch {
+    /// Creates all users, the results are in the order of the users. Users
+    /// are created concurrently up to the limits of the realm.
+    pub async fn create(self) -> Vec<Result<(), KeycloakError>> {
+        let mut tasks = tokio::task::JoinSet::new();
+        let count = self.users.len();
+        for (idx, user) in self.users.into_iter().enumerate() {
+            let keycloak = self.keycloak.clone();
+            let realm = self.realm.clone();
+            tasks.spawn(async move { (idx, keycloak.create_user(&realm, user).await) });
+        }
+        let mut results: Vec<Option<Result<(), KeycloakError>>> =
+            std::iter::repeat_with(|| None).take(count).collect();
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok((idx, result)) => results[idx] = Some(result),
+                Err(err) => tracing::error!("user creation task failed: {err:#}"),
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                result.unwrap_or_else(|| {
+                    Err(KeycloakError::HttpFailure {
+                        status: 500,
+                        body: None,
+                        text: "user creation task failed".to_string(),
+                    })
+                })
+            })
+            .collect()
+    }
 }
@@ -1,8 +1,17 @@
 use std::sync::Arc;
 
+use qm_utils::secret::SecretSource;
+
+use crate::limiter::RateLimit;
+use crate::otp_policy::OtpPolicy;
+
+/// Fields which are resolved with the [`SecretSource`].
+const SECRETS: [&str; 2] = ["PASSWORD", "REALM_ADMIN_PASSWORD"];
+
 #[derive(Default)]
 pub struct ConfigBuilder<'a> {
     prefix: Option<&'a str>,
+    secrets: Option<SecretSource>,
 }
 
 impl<'a> ConfigBuilder<'a> {
@@ -11,13 +20,20 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
+    /// Source of the secrets, [`SecretSource::from_env`] by default.
+    pub fn with_secrets(mut self, secrets: SecretSource) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
     pub fn build(self) -> envy::Result<Config> {
-        let mut cfg: Config = if let Some(prefix) = self.prefix {
-            envy::prefixed(prefix)
-        } else {
-            envy::prefixed("KEYCLOAK_")
-        }
-        .from_env()?;
+        let prefix = self.prefix.unwrap_or("KEYCLOAK_");
+        let vars = self
+            .secrets
+            .unwrap_or_else(SecretSource::from_env)
+            .vars(prefix, &SECRETS)
+            .map_err(|err| envy::Error::Custom(format!("{err:#}")))?;
+        let mut cfg: Config = envy::prefixed(prefix).from_iter(vars)?;
         if cfg.realm.is_none() {
             cfg.realm = Some("rmp".into());
         }
@@ -91,6 +107,14 @@ pub struct Config {
     smtp_ssl: Option<bool>,
     browser_flow: Option<Arc<str>>,
     authenticator_email_subject: Option<Arc<str>>,
+    otp_policy_type: Option<Arc<str>>,
+    otp_policy_digits: Option<i32>,
+    otp_policy_period: Option<i32>,
+    otp_policy_algorithm: Option<Arc<str>>,
+    max_concurrency: Option<usize>,
+    max_concurrency_per_realm: Option<usize>,
+    requests_per_second: Option<f64>,
+    request_burst: Option<u32>,
 }
 
 impl Config {
@@ -181,4 +205,38 @@ impl Config {
     pub fn authenticator_email_subject(&self) -> Option<&str> {
         self.authenticator_email_subject.as_deref()
     }
+
+    /// OTP policy enforced on the realm, unset or invalid values fall back to
+    /// the defaults of [`OtpPolicy`].
+    pub fn otp_policy(&self) -> OtpPolicy {
+        let defaults = OtpPolicy::default();
+        OtpPolicy {
+            policy_type: self
+                .otp_policy_type
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.policy_type),
+            digits: self.otp_policy_digits.unwrap_or(defaults.digits),
+            period: self.otp_policy_period.unwrap_or(defaults.period),
+            algorithm: self
+                .otp_policy_algorithm
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.algorithm),
+        }
+    }
+
+    /// Limits of the admin requests, unset values fall back to the defaults
+    /// of [`RateLimit`].
+    pub fn rate_limit(&self) -> RateLimit {
+        let defaults = RateLimit::default();
+        RateLimit {
+            max_concurrency: self.max_concurrency.unwrap_or(defaults.max_concurrency),
+            max_concurrency_per_realm: self
+                .max_concurrency_per_realm
+                .unwrap_or(defaults.max_concurrency_per_realm),
+            requests_per_second: self.requests_per_second.or(defaults.requests_per_second),
+            burst: self.request_burst.unwrap_or(defaults.burst),
+        }
+    }
 }
@@ -0,0 +1,249 @@
+//! Login and admin events of a realm.
+//!
+//! [`EventsConfig`] is the expected events configuration of a realm: whether
+//! events are stored, how long they are kept, the event listeners and the
+//! enabled event types. Listeners and event types which are not part of the
+//! configuration are kept, e.g. the default `jboss-logging` listener.
+//!
+//! [`HttpEventListener`] registers our HTTP event listener SPI, which posts
+//! the events of a realm to a webhook, e.g. for user login analytics. The
+//! SPI reads its settings from the realm attributes `<id>.url`,
+//! `<id>.secret` and `<id>.events`.
+
+use std::collections::HashMap;
+
+use keycloak::types::{RealmEventsConfigRepresentation, RealmRepresentation};
+
+pub const DEFAULT_HTTP_LISTENER_ID: &str = "qm-http";
+
+/// Event types of user logins, see [`EventsConfig::with_event_types`].
+pub const LOGIN_EVENT_TYPES: [&str; 4] = ["LOGIN", "LOGIN_ERROR", "LOGOUT", "CODE_TO_TOKEN"];
+
+/// Event listener SPI posting the events of the realm to `url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpEventListener {
+    pub id: String,
+    pub url: String,
+    /// Shared secret the SPI uses to sign the requests.
+    pub secret: Option<String>,
+    /// Event types posted to `url`, all enabled event types if empty.
+    pub event_types: Vec<String>,
+}
+
+impl HttpEventListener {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            id: DEFAULT_HTTP_LISTENER_ID.to_string(),
+            url: url.into(),
+            secret: None,
+            event_types: vec![],
+        }
+    }
+
+    /// Provider id of the SPI, [`DEFAULT_HTTP_LISTENER_ID`] by default.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn with_event_types<I, S>(mut self, event_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.event_types
+            .extend(event_types.into_iter().map(Into::into));
+        self
+    }
+
+    /// Realm attributes read by the SPI.
+    pub fn attributes(&self) -> HashMap<String, String> {
+        let mut attributes = HashMap::new();
+        attributes.insert(format!("{}.url", self.id), self.url.clone());
+        if let Some(secret) = &self.secret {
+            attributes.insert(format!("{}.secret", self.id), secret.clone());
+        }
+        if !self.event_types.is_empty() {
+            attributes.insert(format!("{}.events", self.id), self.event_types.join(","));
+        }
+        attributes
+    }
+
+    pub fn attributes_match(&self, realm: &RealmRepresentation) -> bool {
+        let existing = realm.attributes.as_ref();
+        self.attributes()
+            .iter()
+            .all(|(k, v)| existing.and_then(|a| a.get(k)) == Some(v))
+    }
+
+    /// Writes the attributes to `realm`, returns `true` if `realm` changed.
+    pub fn apply(&self, realm: &mut RealmRepresentation) -> bool {
+        if self.attributes_match(realm) {
+            return false;
+        }
+        realm
+            .attributes
+            .get_or_insert_with(Default::default)
+            .extend(self.attributes());
+        true
+    }
+}
+
+/// Expected events configuration of a realm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventsConfig {
+    /// Login events must be stored in the database.
+    pub enabled: bool,
+    /// Seconds until stored events are removed, `None` keeps the value of
+    /// the realm.
+    pub expiration: Option<i64>,
+    pub listeners: Vec<String>,
+    /// Event types which must be enabled in addition to those of the realm.
+    pub event_types: Vec<String>,
+    pub admin_events: Option<bool>,
+    pub admin_events_details: Option<bool>,
+    pub http_listener: Option<HttpEventListener>,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            expiration: None,
+            listeners: vec![],
+            event_types: vec![],
+            admin_events: None,
+            admin_events_details: None,
+            http_listener: None,
+        }
+    }
+}
+
+impl EventsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Doesn't require the realm to store events, e.g. if only the listeners
+    /// are needed.
+    pub fn without_storage(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    pub fn with_expiration(mut self, seconds: i64) -> Self {
+        self.expiration = Some(seconds);
+        self
+    }
+
+    pub fn with_listener(mut self, listener: impl Into<String>) -> Self {
+        let listener = listener.into();
+        if !self.listeners.contains(&listener) {
+            self.listeners.push(listener);
+        }
+        self
+    }
+
+    /// Event types which must be enabled, e.g. [`LOGIN_EVENT_TYPES`].
+    pub fn with_event_types<I, S>(mut self, event_types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        for event_type in event_types.into_iter().map(Into::into) {
+            if !self.event_types.contains(&event_type) {
+                self.event_types.push(event_type);
+            }
+        }
+        self
+    }
+
+    pub fn with_admin_events(mut self, details: bool) -> Self {
+        self.admin_events = Some(true);
+        self.admin_events_details = Some(details);
+        self
+    }
+
+    /// Registers the listener and enables the event types it posts.
+    pub fn with_http_listener(self, listener: HttpEventListener) -> Self {
+        let mut cfg = self
+            .with_listener(listener.id.clone())
+            .with_event_types(listener.event_types.iter().cloned());
+        cfg.http_listener = Some(listener);
+        cfg
+    }
+
+    pub fn enabled_matches(&self, rep: &RealmEventsConfigRepresentation) -> bool {
+        !self.enabled || rep.events_enabled == Some(true)
+    }
+
+    pub fn expiration_matches(&self, rep: &RealmEventsConfigRepresentation) -> bool {
+        self.expiration.is_none() || rep.events_expiration == self.expiration
+    }
+
+    pub fn admin_events_match(&self, rep: &RealmEventsConfigRepresentation) -> bool {
+        (self.admin_events.is_none() || rep.admin_events_enabled == self.admin_events)
+            && (self.admin_events_details.is_none()
+                || rep.admin_events_details_enabled == self.admin_events_details)
+    }
+
+    /// Listeners of the configuration missing in the realm.
+    pub fn missing_listeners(&self, rep: &RealmEventsConfigRepresentation) -> Vec<&str> {
+        missing(&self.listeners, rep.events_listeners.as_deref())
+    }
+
+    /// Event types of the configuration not enabled in the realm.
+    pub fn missing_event_types(&self, rep: &RealmEventsConfigRepresentation) -> Vec<&str> {
+        missing(&self.event_types, rep.enabled_event_types.as_deref())
+    }
+
+    /// Writes the configuration to `rep`, returns `true` if `rep` changed.
+    pub fn apply(&self, rep: &mut RealmEventsConfigRepresentation) -> bool {
+        let mut changed = false;
+        if !self.enabled_matches(rep) {
+            rep.events_enabled = Some(true);
+            changed = true;
+        }
+        if !self.expiration_matches(rep) {
+            rep.events_expiration = self.expiration;
+            changed = true;
+        }
+        if !self.admin_events_match(rep) {
+            if self.admin_events.is_some() {
+                rep.admin_events_enabled = self.admin_events;
+            }
+            if self.admin_events_details.is_some() {
+                rep.admin_events_details_enabled = self.admin_events_details;
+            }
+            changed = true;
+        }
+        changed |= extend(&mut rep.events_listeners, &self.listeners);
+        changed |= extend(&mut rep.enabled_event_types, &self.event_types);
+        changed
+    }
+}
+
+fn missing<'a>(expected: &'a [String], existing: Option<&[String]>) -> Vec<&'a str> {
+    expected
+        .iter()
+        .filter(|v| !existing.unwrap_or_default().contains(v))
+        .map(String::as_str)
+        .collect()
+}
+
+fn extend(target: &mut Option<Vec<String>>, values: &[String]) -> bool {
+    let missing: Vec<String> = missing(values, target.as_deref())
+        .into_iter()
+        .map(String::from)
+        .collect();
+    if missing.is_empty() {
+        return false;
+    }
+    target.get_or_insert_with(Vec::new).extend(missing);
+    true
+}
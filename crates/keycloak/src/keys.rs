@@ -0,0 +1,168 @@
+//! Key providers of a realm used to sign tokens.
+//!
+//! Keycloak signs tokens with the active key of the highest priority, passive
+//! keys are only used to verify tokens which were signed before a rotation.
+
+use std::{fmt, str::FromStr};
+
+use keycloak::types::ComponentRepresentation;
+
+pub const KEY_PROVIDER_TYPE: &str = "org.keycloak.keys.KeyProvider";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAlgorithm {
+    #[default]
+    Rs256,
+    Rs384,
+    Rs512,
+    Es256,
+    Es384,
+    Es512,
+    Hs256,
+    Hs384,
+    Hs512,
+}
+
+impl KeyAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rs256 => "RS256",
+            Self::Rs384 => "RS384",
+            Self::Rs512 => "RS512",
+            Self::Es256 => "ES256",
+            Self::Es384 => "ES384",
+            Self::Es512 => "ES512",
+            Self::Hs256 => "HS256",
+            Self::Hs384 => "HS384",
+            Self::Hs512 => "HS512",
+        }
+    }
+
+    /// Id of the Keycloak provider which generates keys for the algorithm.
+    pub fn provider_id(&self) -> &'static str {
+        match self {
+            Self::Rs256 | Self::Rs384 | Self::Rs512 => "rsa-generated",
+            Self::Es256 | Self::Es384 | Self::Es512 => "ecdsa-generated",
+            Self::Hs256 | Self::Hs384 | Self::Hs512 => "hmac-generated",
+        }
+    }
+
+    fn key_config(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Rs256 | Self::Rs384 | Self::Rs512 => ("keySize", "2048"),
+            Self::Es256 => ("ecdsaEllipticCurveKey", "P-256"),
+            Self::Es384 => ("ecdsaEllipticCurveKey", "P-384"),
+            Self::Es512 => ("ecdsaEllipticCurveKey", "P-521"),
+            Self::Hs256 | Self::Hs384 | Self::Hs512 => ("secretSize", "64"),
+        }
+    }
+}
+
+impl FromStr for KeyAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "RS256" => Ok(Self::Rs256),
+            "RS384" => Ok(Self::Rs384),
+            "RS512" => Ok(Self::Rs512),
+            "ES256" => Ok(Self::Es256),
+            "ES384" => Ok(Self::Es384),
+            "ES512" => Ok(Self::Es512),
+            "HS256" => Ok(Self::Hs256),
+            "HS384" => Ok(Self::Hs384),
+            "HS512" => Ok(Self::Hs512),
+            _ => Err(format!("invalid key algorithm '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for KeyAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Generated key provider of a realm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyProvider {
+    pub name: String,
+    pub algorithm: KeyAlgorithm,
+    pub priority: i64,
+    /// Active keys sign tokens, passive keys only verify them.
+    pub active: bool,
+    pub enabled: bool,
+}
+
+impl KeyProvider {
+    pub fn new(name: impl Into<String>, algorithm: KeyAlgorithm, priority: i64) -> Self {
+        Self {
+            name: name.into(),
+            algorithm,
+            priority,
+            active: true,
+            enabled: true,
+        }
+    }
+
+    pub fn to_component(&self, realm_id: &str) -> ComponentRepresentation {
+        let (key, value) = self.algorithm.key_config();
+        let config = [
+            ("priority", self.priority.to_string()),
+            ("enabled", self.enabled.to_string()),
+            ("active", self.active.to_string()),
+            ("algorithm", self.algorithm.to_string()),
+            (key, value.to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), vec![v]))
+        .collect();
+        ComponentRepresentation {
+            name: Some(self.name.clone()),
+            parent_id: Some(realm_id.to_string()),
+            provider_id: Some(self.algorithm.provider_id().to_string()),
+            provider_type: Some(KEY_PROVIDER_TYPE.to_string()),
+            config: Some(config),
+            ..Default::default()
+        }
+    }
+}
+
+/// First value of `key` in the config of a component.
+pub fn component_config<'a>(component: &'a ComponentRepresentation, key: &str) -> Option<&'a str> {
+    component
+        .config
+        .as_ref()
+        .and_then(|config| config.get(key))
+        .and_then(|values| values.first())
+        .map(String::as_str)
+}
+
+pub fn set_component_config(component: &mut ComponentRepresentation, key: &str, value: String) {
+    component
+        .config
+        .get_or_insert_with(Default::default)
+        .insert(key.to_string(), vec![value]);
+}
+
+/// Whether `component` is a generated provider of `algorithm`. ECDSA keys
+/// are identified by their curve, other providers without an algorithm are
+/// RS256 and HS256 by default.
+pub fn is_provider_of(component: &ComponentRepresentation, algorithm: KeyAlgorithm) -> bool {
+    if component.provider_id.as_deref() != Some(algorithm.provider_id()) {
+        return false;
+    }
+    if let (key @ "ecdsaEllipticCurveKey", curve) = algorithm.key_config() {
+        return component_config(component, key).unwrap_or("P-256") == curve;
+    }
+    match component_config(component, "algorithm") {
+        Some(v) => v == algorithm.as_str(),
+        None => matches!(algorithm, KeyAlgorithm::Rs256 | KeyAlgorithm::Hs256),
+    }
+}
+
+pub fn component_priority(component: &ComponentRepresentation) -> i64 {
+    component_config(component, "priority")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
@@ -9,15 +9,24 @@
 //! `k8s/helm-charts/skaffold/skaffold.infra-1-base.yaml`.
 //!
 //! Default username/password: `admin`/`Admin123`
+pub mod brute_force;
 mod client;
+pub mod keys;
+pub mod limiter;
 
 pub mod session;
 pub use client::*;
 pub mod config;
+pub mod events;
+pub mod localization;
+pub mod otp_policy;
 pub mod realm;
 pub mod schema;
 pub mod token;
+pub mod user_profile;
 pub mod validation;
+pub use token::jwt::{JwtError, ValidationOptions};
+pub use token::service_account::ServiceAccountTokenProvider;
 pub use token::store::JwtStore;
 
 #[macro_export]
@@ -0,0 +1,197 @@
+//! Limits for admin requests of the [`crate::Keycloak`] client.
+//!
+//! Requests wait in FIFO order for a slot of their realm and then for a
+//! global slot, so bulk operations of one tenant can't use all slots and
+//! block the requests of other realms. An optional token bucket limits the
+//! rate of requests across all realms, requests wait for their token before
+//! taking a global slot so throttled requests don't keep slots idle.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+pub const DEFAULT_MAX_CONCURRENCY: usize = 16;
+pub const DEFAULT_MAX_CONCURRENCY_PER_REALM: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Requests running at the same time.
+    pub max_concurrency: usize,
+    /// Requests of one realm running at the same time.
+    pub max_concurrency_per_realm: usize,
+    /// Requests started per second, unlimited if `None`.
+    pub requests_per_second: Option<f64>,
+    /// Requests which can be started at once before the rate applies.
+    pub burst: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            max_concurrency_per_realm: DEFAULT_MAX_CONCURRENCY_PER_REALM,
+            requests_per_second: None,
+            burst: 1,
+        }
+    }
+}
+
+struct Bucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    /// Takes a token and returns the time to wait until it is available.
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst) - 1.0;
+        self.last = now;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Slot of a running request, released on drop.
+pub struct Permit {
+    _realm: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+pub struct RateLimiter {
+    limit: RateLimit,
+    global: Arc<Semaphore>,
+    realms: Mutex<HashMap<String, Arc<Semaphore>>>,
+    bucket: Option<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let bucket = limit
+            .requests_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| {
+                let burst = limit.burst.max(1) as f64;
+                Mutex::new(Bucket {
+                    rate,
+                    burst,
+                    tokens: burst,
+                    last: Instant::now(),
+                })
+            });
+        Self {
+            limit,
+            global: Arc::new(Semaphore::new(limit.max_concurrency.max(1))),
+            realms: Mutex::default(),
+            bucket,
+        }
+    }
+
+    pub fn limit(&self) -> &RateLimit {
+        &self.limit
+    }
+
+    fn realm(&self, realm: &str) -> Arc<Semaphore> {
+        let mut realms = self.realms.lock().unwrap();
+        if let Some(semaphore) = realms.get(realm) {
+            return semaphore.clone();
+        }
+        let semaphore = Arc::new(Semaphore::new(self.limit.max_concurrency_per_realm.max(1)));
+        realms.insert(realm.to_string(), semaphore.clone());
+        semaphore
+    }
+
+    /// Waits until a request for `realm` may start.
+    pub async fn acquire(&self, realm: &str) -> Permit {
+        // the semaphores are never closed
+        let realm = self.realm(realm).acquire_owned().await.unwrap();
+        let delay = self
+            .bucket
+            .as_ref()
+            .map(|bucket| bucket.lock().unwrap().reserve(Instant::now()))
+            .unwrap_or_default();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        let global = self.global.clone().acquire_owned().await.unwrap();
+        Permit {
+            _realm: realm,
+            _global: global,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_concurrency: usize, requests_per_second: Option<f64>) -> Arc<RateLimiter> {
+        Arc::new(RateLimiter::new(RateLimit {
+            max_concurrency,
+            max_concurrency_per_realm: 1,
+            requests_per_second,
+            burst: 1,
+        }))
+    }
+
+    #[test]
+    fn test_reserve() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            rate: 2.0,
+            burst: 2.0,
+            tokens: 2.0,
+            last: start,
+        };
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::from_millis(500));
+        assert_eq!(bucket.reserve(start), Duration::from_secs(1));
+        assert_eq!(
+            bucket.reserve(start + Duration::from_secs(1)),
+            Duration::from_millis(500)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_realm_queue() {
+        let limiter = limiter(2, None);
+        let first = limiter.acquire("a").await;
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { drop(limiter.acquire("a").await) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!queued.is_finished());
+        // other realms aren't blocked by the queue of realm `a`
+        drop(limiter.acquire("b").await);
+        drop(first);
+        queued.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttled_without_global_slot() {
+        let limiter = limiter(1, Some(1.0));
+        drop(limiter.acquire("a").await);
+        let start = Instant::now();
+        let throttled = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { drop(limiter.acquire("a").await) }
+        });
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(!throttled.is_finished());
+        assert_eq!(limiter.global.available_permits(), 1);
+        throttled.await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,68 @@
+//! Realm localization texts, e.g. translated email and login strings.
+//!
+//! A [`Localization`] registered with
+//! [`crate::KeycloakBuilder::with_localization`] is checked by the realm
+//! validation and provisioned by the updater. Texts not part of the
+//! definition are left untouched.
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Localization {
+    /// Texts by key for each locale.
+    pub texts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Localization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_text(
+        mut self,
+        locale: impl Into<String>,
+        key: impl Into<String>,
+        text: impl Into<String>,
+    ) -> Self {
+        self.texts
+            .entry(locale.into())
+            .or_default()
+            .insert(key.into(), text.into());
+        self
+    }
+
+    pub fn with_texts<K, V>(
+        mut self,
+        locale: impl Into<String>,
+        texts: impl IntoIterator<Item = (K, V)>,
+    ) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.texts
+            .entry(locale.into())
+            .or_default()
+            .extend(texts.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.texts.keys().map(String::as_str)
+    }
+
+    /// Texts of `locale` missing or different in `existing`.
+    pub fn drift(
+        &self,
+        locale: &str,
+        existing: &HashMap<String, String>,
+    ) -> HashMap<String, String> {
+        self.texts
+            .get(locale)
+            .into_iter()
+            .flatten()
+            .filter(|(key, text)| existing.get(*key) != Some(*text))
+            .map(|(key, text)| (key.clone(), text.clone()))
+            .collect()
+    }
+}
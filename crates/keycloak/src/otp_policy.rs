@@ -0,0 +1,176 @@
+//! OTP policy of a realm used for TOTP/HOTP second factors.
+
+use std::{fmt, str::FromStr};
+
+use keycloak::types::RealmRepresentation;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OtpType {
+    /// Time based one-time passwords.
+    #[default]
+    Totp,
+    /// Counter based one-time passwords.
+    Hotp,
+}
+
+impl OtpType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Totp => "totp",
+            Self::Hotp => "hotp",
+        }
+    }
+}
+
+impl FromStr for OtpType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "totp" => Ok(Self::Totp),
+            "hotp" => Ok(Self::Hotp),
+            _ => Err(format!("invalid OTP type '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for OtpType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OtpAlgorithm {
+    #[default]
+    HmacSha1,
+    HmacSha256,
+    HmacSha512,
+}
+
+impl OtpAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HmacSha1 => "HmacSHA1",
+            Self::HmacSha256 => "HmacSHA256",
+            Self::HmacSha512 => "HmacSHA512",
+        }
+    }
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "HmacSHA1" => Ok(Self::HmacSha1),
+            "HmacSHA256" => Ok(Self::HmacSha256),
+            "HmacSHA512" => Ok(Self::HmacSha512),
+            _ => Err(format!("invalid OTP algorithm '{s}'")),
+        }
+    }
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// OTP policy fields of a realm, the defaults match the defaults of
+/// Keycloak and the common authenticator apps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OtpPolicy {
+    pub policy_type: OtpType,
+    pub digits: i32,
+    /// Seconds a TOTP is valid.
+    pub period: i32,
+    pub algorithm: OtpAlgorithm,
+}
+
+impl Default for OtpPolicy {
+    fn default() -> Self {
+        Self {
+            policy_type: OtpType::Totp,
+            digits: 6,
+            period: 30,
+            algorithm: OtpAlgorithm::HmacSha1,
+        }
+    }
+}
+
+impl OtpPolicy {
+    /// Reads the policy of `realm`, unset or unknown values fall back to the
+    /// defaults.
+    pub fn from_realm(realm: &RealmRepresentation) -> Self {
+        let defaults = Self::default();
+        Self {
+            policy_type: realm
+                .otp_policy_type
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.policy_type),
+            digits: realm.otp_policy_digits.unwrap_or(defaults.digits),
+            period: realm.otp_policy_period.unwrap_or(defaults.period),
+            algorithm: realm
+                .otp_policy_algorithm
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.algorithm),
+        }
+    }
+
+    pub fn type_matches(&self, realm: &RealmRepresentation) -> bool {
+        realm.otp_policy_type.as_deref() == Some(self.policy_type.as_str())
+    }
+
+    pub fn digits_match(&self, realm: &RealmRepresentation) -> bool {
+        realm.otp_policy_digits == Some(self.digits)
+    }
+
+    pub fn period_matches(&self, realm: &RealmRepresentation) -> bool {
+        realm.otp_policy_period == Some(self.period)
+    }
+
+    pub fn algorithm_matches(&self, realm: &RealmRepresentation) -> bool {
+        realm.otp_policy_algorithm.as_deref() == Some(self.algorithm.as_str())
+    }
+
+    pub fn set_type(&self, realm: &mut RealmRepresentation) {
+        realm.otp_policy_type = Some(self.policy_type.as_str().to_string());
+    }
+
+    pub fn set_digits(&self, realm: &mut RealmRepresentation) {
+        realm.otp_policy_digits = Some(self.digits);
+    }
+
+    pub fn set_period(&self, realm: &mut RealmRepresentation) {
+        realm.otp_policy_period = Some(self.period);
+    }
+
+    pub fn set_algorithm(&self, realm: &mut RealmRepresentation) {
+        realm.otp_policy_algorithm = Some(self.algorithm.as_str().to_string());
+    }
+
+    /// Writes the policy to `realm`, returns `true` if `realm` changed.
+    pub fn apply(&self, realm: &mut RealmRepresentation) -> bool {
+        let mut changed = false;
+        if !self.type_matches(realm) {
+            self.set_type(realm);
+            changed = true;
+        }
+        if !self.digits_match(realm) {
+            self.set_digits(realm);
+            changed = true;
+        }
+        if !self.period_matches(realm) {
+            self.set_period(realm);
+            changed = true;
+        }
+        if !self.algorithm_matches(realm) {
+            self.set_algorithm(realm);
+            changed = true;
+        }
+        changed
+    }
+}
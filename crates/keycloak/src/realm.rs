@@ -1,5 +1,6 @@
 use crate::schema::UserInput;
 use crate::validation::context::{Config, ValidationContext};
+use crate::validation::model::{RealmConfigErrorInput, RealmDriftReport};
 use crate::validation::updater::{get_smtp_server_defaults, update_for_errors};
 use crate::validation::validator::validate_realm;
 use crate::Keycloak;
@@ -9,6 +10,7 @@ use crate::{
 };
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
+use qm_role::sync::{Change, RealmBackend};
 use qm_role::Group;
 
 lazy_static::lazy_static! {
@@ -65,14 +67,9 @@ where
     P: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
 {
     let realm = keycloak.config().realm();
-    let url = APP_URL.as_str();
-    let keycloak_config = keycloak.config();
+    let config = validation_config(keycloak);
     let ctx = ValidationContext {
-        config: &Config {
-            realm,
-            keycloak: keycloak_config,
-            public_url: url,
-        },
+        config: &config,
         keycloak,
     };
     let max_tries = 5;
@@ -100,6 +97,50 @@ where
     Ok(())
 }
 
+fn validation_config(keycloak: &Keycloak) -> Config<'_> {
+    Config {
+        realm: keycloak.config().realm(),
+        keycloak: keycloak.config(),
+        public_url: APP_URL.as_str(),
+    }
+}
+
+/// Validates the configured realm against the expected settings and, if
+/// `repair` is set, updates the realm and validates it again.
+pub async fn check_realm(keycloak: &Keycloak, repair: bool) -> anyhow::Result<RealmDriftReport> {
+    let config = validation_config(keycloak);
+    let ctx = ValidationContext {
+        config: &config,
+        keycloak,
+    };
+    let drift = validate_realm(&ctx).await?.unwrap_or_default();
+    let remaining = if repair && !drift.is_empty() {
+        tracing::info!(
+            "repairing {} settings of realm '{}'",
+            drift.len(),
+            config.realm()
+        );
+        update_for_errors(
+            &ctx,
+            drift
+                .iter()
+                .map(|e| RealmConfigErrorInput { id: e.id.clone() })
+                .collect(),
+        )
+        .await?;
+        Some(validate_realm(&ctx).await?.unwrap_or_default())
+    } else if repair {
+        Some(vec![])
+    } else {
+        None
+    };
+    Ok(RealmDriftReport {
+        realm: config.realm().to_string(),
+        drift,
+        remaining,
+    })
+}
+
 fn set_attributes(attributes: HashMap<&str, Option<String>>, u: &mut UserRepresentation) {
     if u.attributes.is_none() {
         u.attributes = Some(HashMap::new());
@@ -523,3 +564,90 @@ where
         .await
     }
 }
+
+impl RealmBackend for Keycloak {
+    async fn roles(&self, realm: &str) -> anyhow::Result<BTreeSet<String>> {
+        Ok(self
+            .all_roles(realm)
+            .await?
+            .into_iter()
+            .filter_map(|role| role.name)
+            .collect())
+    }
+
+    async fn group_roles(
+        &self,
+        realm: &str,
+        path: &str,
+    ) -> anyhow::Result<Option<BTreeSet<String>>> {
+        let group = match self.group_by_path(realm, path).await {
+            Ok(group) => group,
+            Err(KeycloakError::HttpFailure { status: 404, .. }) => return Ok(None),
+            Err(err) => Err(err)?,
+        };
+        let Some(id) = group.id.as_deref() else {
+            return Ok(None);
+        };
+        Ok(Some(
+            self.realm_role_mappings_by_group_id(realm, id)
+                .await?
+                .into_iter()
+                .filter_map(|role| role.name)
+                .collect(),
+        ))
+    }
+
+    async fn apply(&self, realm: &str, change: &Change) -> anyhow::Result<()> {
+        tracing::info!("apply {change} to realm {realm}");
+        match change {
+            Change::CreateRole(role) => {
+                ensure_roles(realm, self, BTreeSet::from_iter([role.clone()])).await?;
+            }
+            Change::CreateGroup {
+                path,
+                name,
+                allowed_access_levels,
+                allowed_types,
+            } => {
+                let group = Group::<(), ()>::new(
+                    name.clone(),
+                    path.clone(),
+                    allowed_access_levels.clone(),
+                    allowed_types.clone(),
+                    vec![],
+                );
+                ensure_groups(
+                    realm,
+                    self,
+                    &BTreeMap::from_iter([(path.clone(), group)]),
+                    true,
+                )
+                .await?;
+            }
+            Change::AddMapping { group, role } => {
+                let id = self
+                    .group_by_path(realm, group)
+                    .await?
+                    .id
+                    .ok_or_else(|| anyhow::anyhow!("group '{group}' has no id"))?;
+                let role = self.realm_role_by_name(realm, role).await?;
+                self.create_realm_role_mappings_by_group_id(realm, &id, vec![role])
+                    .await?;
+            }
+            Change::RemoveMapping { group, role } => {
+                let id = self
+                    .group_by_path(realm, group)
+                    .await?
+                    .id
+                    .ok_or_else(|| anyhow::anyhow!("group '{group}' has no id"))?;
+                let role = self.realm_role_by_name(realm, role).await?;
+                self.remove_realm_role_mappings_by_group_id(realm, &id, vec![role])
+                    .await?;
+            }
+            Change::DeleteRole(role) => {
+                self.remove_role(realm, role).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use keycloak::KeycloakError;
 use keycloak::KeycloakTokenSupplier;
+use qm_utils::retry::{retry_if, RetryPolicy};
 use std::{sync::Arc, time::Duration};
 use tokio::runtime::Builder;
 use tokio::sync::RwLock;
@@ -31,7 +32,34 @@ impl std::fmt::Display for KeycloakSessionError {
     }
 }
 
-async fn error(response: reqwest::Response) -> Result<reqwest::Response, KeycloakSessionError> {
+impl KeycloakSessionError {
+    /// Connection failures and server errors, which may succeed when retried.
+    fn is_transient(&self) -> bool {
+        match self {
+            KeycloakSessionError::ReqwestFailure(_) => true,
+            KeycloakSessionError::HttpFailure { status, .. } => *status >= 500,
+            KeycloakSessionError::Decode(_) => false,
+        }
+    }
+}
+
+/// Keycloak restarts shouldn't end the process, refreshes are retried for
+/// about a minute before giving up.
+async fn with_retries<T, F, Fut>(f: F) -> Result<T, KeycloakSessionError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, KeycloakSessionError>>,
+{
+    let policy = RetryPolicy::exponential(Duration::from_millis(500))
+        .with_max_delay(Duration::from_secs(10))
+        .with_max_attempts(10)
+        .with_max_elapsed(Duration::from_secs(60));
+    retry_if(&policy, KeycloakSessionError::is_transient, f).await
+}
+
+pub(crate) async fn error(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, KeycloakSessionError> {
     if !response.status().is_success() {
         let status = response.status();
         let text = response.text().await;
@@ -385,7 +413,7 @@ impl KeycloakSession {
                                     as u64,
                             ))
                             .await;
-                            let next_token = async {
+                            let next_token = with_retries(|| async {
                                 try_refresh(
                                     &keycloak,
                                     &session.inner.token.read().await.refresh_token,
@@ -393,7 +421,7 @@ impl KeycloakSession {
                                     password,
                                 )
                                 .await
-                            }
+                            })
                             .await;
                             match next_token {
                                 Ok(next_token) => {
@@ -418,8 +446,7 @@ impl KeycloakSession {
                                     Ok(_) => {},
                                     Err(_) => {
                                         tracing::debug!("acquire new session");
-                                        match keycloak
-                                            .acquire(username, password)
+                                        match with_retries(|| keycloak.acquire(username, password))
                                             .await
                                             .map(KeycloakSessionToken::parse_access_token) {
                                             Ok(next_token) => {
@@ -532,14 +559,14 @@ impl KeycloakApiClientSession {
                                     as u64,
                             ))
                             .await;
-                            let next_token = async {
+                            let next_token = with_retries(|| async {
                                 try_refresh_with_secret(
                                     &keycloak,
                                     &session.inner.token.read().await.refresh_token,
                                     secret,
                                 )
                                 .await
-                            }
+                            })
                             .await;
                             match next_token {
                                 Ok(next_token) => {
@@ -564,8 +591,7 @@ impl KeycloakApiClientSession {
                                     Ok(_) => {},
                                     Err(_) => {
                                         tracing::debug!("acquire new session");
-                                        match keycloak
-                                            .acquire_with_secret(secret)
+                                        match with_retries(|| keycloak.acquire_with_secret(secret))
                                             .await
                                             .map(KeycloakSessionToken::parse_access_token) {
                                             Ok(next_token) => {
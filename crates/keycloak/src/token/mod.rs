@@ -1,3 +1,4 @@
 pub mod config;
 pub mod jwt;
+pub mod service_account;
 pub mod store;
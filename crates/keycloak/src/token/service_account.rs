@@ -0,0 +1,120 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
+use crate::session::{error, KeycloakSessionError};
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: Arc<str>,
+    expires_in: u64,
+}
+
+struct CachedToken {
+    access_token: Arc<str>,
+    refresh_at: Instant,
+}
+
+struct Inner {
+    url: String,
+    client_id: Arc<str>,
+    client_secret: Arc<str>,
+    refresh_margin: Duration,
+    client: reqwest::Client,
+    token: Mutex<Option<CachedToken>>,
+}
+
+/// Access tokens of a confidential client acquired with the client
+/// credentials grant, e.g. for background workers calling other services.
+/// Tokens are cached and refreshed shortly before they expire.
+#[derive(Clone)]
+pub struct ServiceAccountTokenProvider {
+    inner: Arc<Inner>,
+}
+
+impl ServiceAccountTokenProvider {
+    pub fn new<T>(address: &str, realm: &str, client_id: T, client_secret: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self::with_refresh_margin(
+            address,
+            realm,
+            client_id,
+            client_secret,
+            Duration::from_secs(30),
+        )
+    }
+
+    /// Tokens are refreshed `refresh_margin` before they expire, at most
+    /// after half of their lifetime.
+    pub fn with_refresh_margin<T>(
+        address: &str,
+        realm: &str,
+        client_id: T,
+        client_secret: T,
+        refresh_margin: Duration,
+    ) -> Self
+    where
+        T: Into<String>,
+    {
+        let address = address.trim_end_matches('/');
+        Self {
+            inner: Arc::new(Inner {
+                url: format!("{address}/realms/{realm}/protocol/openid-connect/token"),
+                client_id: Arc::from(client_id.into()),
+                client_secret: Arc::from(client_secret.into()),
+                refresh_margin,
+                client: reqwest::Client::default(),
+                token: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// Returns the cached access token or acquires a new one. Concurrent
+    /// callers wait for a single refresh.
+    pub async fn access_token(&self) -> Result<Arc<str>, KeycloakSessionError> {
+        let mut token = self.inner.token.lock().await;
+        if let Some(token) = token.as_ref().filter(|t| t.refresh_at > Instant::now()) {
+            return Ok(token.access_token.clone());
+        }
+        let acquired = self.acquire().await?;
+        let lifetime = Duration::from_secs(acquired.expires_in);
+        let refresh_in = lifetime.saturating_sub(self.inner.refresh_margin.min(lifetime / 2));
+        let access_token = acquired.access_token;
+        *token = Some(CachedToken {
+            access_token: access_token.clone(),
+            refresh_at: Instant::now() + refresh_in,
+        });
+        Ok(access_token)
+    }
+
+    /// Value for the `Authorization` header.
+    pub async fn bearer(&self) -> Result<String, KeycloakSessionError> {
+        Ok(format!("Bearer {}", self.access_token().await?))
+    }
+
+    /// Drops the cached token, e.g. after a request was rejected with 401.
+    pub async fn invalidate(&self) {
+        self.inner.token.lock().await.take();
+    }
+
+    async fn acquire(&self) -> Result<TokenResponse, KeycloakSessionError> {
+        let response = self
+            .inner
+            .client
+            .post(&self.inner.url)
+            .form(&[
+                ("client_id", self.inner.client_id.as_ref()),
+                ("client_secret", self.inner.client_secret.as_ref()),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await?;
+        let result = error(response).await?.json::<serde_json::Value>().await?;
+        serde_json::from_value(result).map_err(|err| KeycloakSessionError::Decode(Arc::new(err)))
+    }
+}
@@ -13,7 +13,7 @@ use crate::{
 
 use super::{
     config::Config,
-    jwt::{LogoutClaims, PartialClaims},
+    jwt::{JwtError, LogoutClaims, PartialClaims, ValidationOptions},
 };
 pub trait JwtConfig {
     fn address(&self) -> &str;
@@ -43,6 +43,7 @@ struct Inner {
     public_url: Arc<str>,
     client: Client,
     keys: RwLock<HashMap<String, Jwt>>,
+    options: ValidationOptions,
 }
 
 #[derive(Clone)]
@@ -52,6 +53,10 @@ pub struct JwtStore {
 
 impl JwtStore {
     pub fn new(config: &impl JwtConfig) -> Self {
+        Self::with_options(config, ValidationOptions::default())
+    }
+
+    pub fn with_options(config: &impl JwtConfig, options: ValidationOptions) -> Self {
         let client = reqwest::Client::new();
         let url = Arc::from(config.address());
         let public_url = Arc::from(config.public_url());
@@ -61,6 +66,7 @@ impl JwtStore {
                 client,
                 public_url,
                 keys: Default::default(),
+                options,
             }),
         }
     }
@@ -79,9 +85,9 @@ impl JwtStore {
             .public_key
             .ok_or(anyhow::anyhow!("unable to get public key"))?;
         match (header.alg, header.kid) {
-            (Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512, Some(kid)) => {
-                Ok(Jwt::new(header.alg, kid, &public_key)?)
-            }
+            (Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512, Some(kid)) => Ok(
+                Jwt::with_options(header.alg, kid, &public_key, &self.inner.options)?,
+            ),
             _ => anyhow::bail!("Invalid token"),
         }
     }
@@ -123,16 +129,26 @@ impl JwtStore {
         Err(anyhow::anyhow!("Invalid token"))
     }
 
-    pub async fn decode(&self, token: &str) -> anyhow::Result<Claims> {
+    /// Drops the cached keys, e.g. after the keys of a realm were rotated.
+    pub async fn invalidate(&self) {
+        self.inner.keys.write().await.clear();
+    }
+
+    /// Drops the cached key `kid`, returns `true` if it was cached.
+    pub async fn remove_key(&self, kid: &str) -> bool {
+        self.inner.keys.write().await.remove(kid).is_some()
+    }
+
+    pub async fn decode(&self, token: &str) -> Result<Claims, JwtError> {
         self.decode_custom(token).await
     }
 
-    pub async fn decode_custom<C: DeserializeOwned>(&self, token: &str) -> anyhow::Result<C> {
+    pub async fn decode_custom<C: DeserializeOwned>(&self, token: &str) -> Result<C, JwtError> {
         let token_header = jsonwebtoken::decode_header(token)?;
         let kid = token_header
             .kid
             .as_ref()
-            .ok_or(anyhow::anyhow!("Invalid token"))?;
+            .ok_or(JwtError::Invalid(anyhow::anyhow!("missing kid")))?;
         {
             if let Some(key) = self.inner.keys.read().await.get(kid) {
                 return key.decode_custom(token);
@@ -144,19 +160,19 @@ impl JwtStore {
         Ok(claims)
     }
 
-    pub async fn decode_logout_token(&self, token: &str) -> anyhow::Result<LogoutClaims> {
+    pub async fn decode_logout_token(&self, token: &str) -> Result<LogoutClaims, JwtError> {
         self.decode_logout_token_custom(token).await
     }
 
     pub async fn decode_logout_token_custom<C: DeserializeOwned>(
         &self,
         token: &str,
-    ) -> anyhow::Result<C> {
+    ) -> Result<C, JwtError> {
         let token_header = jsonwebtoken::decode_header(token)?;
         let kid = token_header
             .kid
             .as_ref()
-            .ok_or(anyhow::anyhow!("Invalid token"))?;
+            .ok_or(JwtError::Invalid(anyhow::anyhow!("missing kid")))?;
         {
             if let Some(key) = self.inner.keys.read().await.get(kid) {
                 return key.decode_logout_token_custom(token);
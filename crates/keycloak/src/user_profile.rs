@@ -0,0 +1,276 @@
+//! Typed definitions of custom attributes in the declarative user profile
+//! of a realm.
+//!
+//! A [`UserProfile`] registered with
+//! [`crate::KeycloakBuilder::with_user_profile`] is checked by the realm
+//! validation and provisioned by the updater. Attributes and groups not
+//! part of the definition are left untouched.
+
+use keycloak::types::{
+    TypeMap, UPAttribute, UPAttributePermissions, UPAttributeRequired, UPConfig, UPGroup,
+};
+use serde_json::Value;
+
+const ADMIN: &str = "admin";
+const USER: &str = "user";
+
+/// Custom attribute of the user profile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileAttribute {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub multivalued: bool,
+    pub required: bool,
+    /// Users can only view the attribute, only admins edit it.
+    pub admin_only: bool,
+    pub validations: TypeMap<String, TypeMap<String, Value>>,
+    pub annotations: TypeMap<String, Value>,
+}
+
+impl ProfileAttribute {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            display_name: None,
+            multivalued: false,
+            required: false,
+            admin_only: false,
+            validations: Default::default(),
+            annotations: Default::default(),
+        }
+    }
+
+    pub fn with_display_name(mut self, display_name: impl Into<String>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn multivalued(mut self) -> Self {
+        self.multivalued = true;
+        self
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    pub fn admin_only(mut self) -> Self {
+        self.admin_only = true;
+        self
+    }
+
+    /// Adds a Keycloak validator, e.g. `length` with `{ "max": 255 }`.
+    pub fn with_validation(
+        mut self,
+        validator: impl Into<String>,
+        config: TypeMap<String, Value>,
+    ) -> Self {
+        self.validations.insert(validator.into(), config);
+        self
+    }
+
+    pub fn with_annotation(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.annotations.insert(name.into(), value.into());
+        self
+    }
+
+    fn permissions(&self) -> UPAttributePermissions {
+        let edit = if self.admin_only {
+            vec![ADMIN.to_string()]
+        } else {
+            vec![ADMIN.to_string(), USER.to_string()]
+        };
+        UPAttributePermissions {
+            edit: Some(edit),
+            view: Some(vec![ADMIN.to_string(), USER.to_string()]),
+        }
+    }
+
+    /// Returns `true` if `rep` has the properties of this attribute, other
+    /// properties and additional validations are ignored.
+    pub fn matches(&self, rep: &UPAttribute, group: &str) -> bool {
+        rep.group.as_deref() == Some(group)
+            && rep.display_name == self.display_name
+            && rep.multivalued.unwrap_or(false) == self.multivalued
+            && rep.required.is_some() == self.required
+            && rep.permissions.as_ref() == Some(&self.permissions())
+            && self.validations.iter().all(|(validator, config)| {
+                rep.validations
+                    .as_ref()
+                    .and_then(|v| v.get(validator))
+                    .is_some_and(|c| c == config)
+            })
+    }
+
+    pub fn to_representation(&self, group: &str) -> UPAttribute {
+        UPAttribute {
+            annotations: (!self.annotations.is_empty()).then(|| self.annotations.clone()),
+            display_name: self.display_name.clone(),
+            group: Some(group.to_string()),
+            multivalued: Some(self.multivalued),
+            name: Some(self.name.clone()),
+            permissions: Some(self.permissions()),
+            required: self.required.then(|| UPAttributeRequired {
+                roles: Some(vec![ADMIN.to_string(), USER.to_string()]),
+                scopes: None,
+            }),
+            selector: None,
+            validations: (!self.validations.is_empty()).then(|| self.validations.clone()),
+        }
+    }
+}
+
+/// Group of attributes, shown as section in the account console and in
+/// our UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeGroup {
+    pub name: String,
+    pub display_header: Option<String>,
+    pub display_description: Option<String>,
+    pub attributes: Vec<ProfileAttribute>,
+}
+
+impl AttributeGroup {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            display_header: None,
+            display_description: None,
+            attributes: vec![],
+        }
+    }
+
+    pub fn with_display_header(mut self, display_header: impl Into<String>) -> Self {
+        self.display_header = Some(display_header.into());
+        self
+    }
+
+    pub fn with_display_description(mut self, display_description: impl Into<String>) -> Self {
+        self.display_description = Some(display_description.into());
+        self
+    }
+
+    pub fn with_attribute(mut self, attribute: ProfileAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub fn to_representation(&self) -> UPGroup {
+        UPGroup {
+            annotations: None,
+            display_description: self.display_description.clone(),
+            display_header: self.display_header.clone(),
+            name: Some(self.name.clone()),
+        }
+    }
+}
+
+/// Differences between a [`UserProfile`] and the configuration of a realm.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UserProfileDrift {
+    pub missing_groups: Vec<String>,
+    pub missing_attributes: Vec<String>,
+    pub mismatched_attributes: Vec<String>,
+}
+
+impl UserProfileDrift {
+    pub fn is_empty(&self) -> bool {
+        self.missing_groups.is_empty()
+            && self.missing_attributes.is_empty()
+            && self.mismatched_attributes.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UserProfile {
+    pub groups: Vec<AttributeGroup>,
+}
+
+impl UserProfile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_group(mut self, group: AttributeGroup) -> Self {
+        self.groups.push(group);
+        self
+    }
+
+    fn attributes(&self) -> impl Iterator<Item = (&AttributeGroup, &ProfileAttribute)> {
+        self.groups
+            .iter()
+            .flat_map(|g| g.attributes.iter().map(move |a| (g, a)))
+    }
+
+    pub fn drift(&self, config: &UPConfig) -> UserProfileDrift {
+        let groups = config.groups.as_deref().unwrap_or_default();
+        let attributes = config.attributes.as_deref().unwrap_or_default();
+        let mut drift = UserProfileDrift::default();
+        for group in self.groups.iter() {
+            if !groups
+                .iter()
+                .any(|g| g.name.as_deref() == Some(group.name.as_str()))
+            {
+                drift.missing_groups.push(group.name.clone());
+            }
+        }
+        for (group, attribute) in self.attributes() {
+            match attributes
+                .iter()
+                .find(|a| a.name.as_deref() == Some(attribute.name.as_str()))
+            {
+                None => drift.missing_attributes.push(attribute.name.clone()),
+                Some(existing) if !attribute.matches(existing, &group.name) => {
+                    drift.mismatched_attributes.push(attribute.name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        drift
+    }
+
+    /// Adds or replaces the groups and attributes of the definition in
+    /// `config`, returns `true` if `config` changed.
+    pub fn apply(&self, config: &mut UPConfig) -> bool {
+        let mut changed = false;
+        let groups = config.groups.get_or_insert_with(Vec::new);
+        for group in self.groups.iter() {
+            match groups
+                .iter_mut()
+                .find(|g| g.name.as_deref() == Some(group.name.as_str()))
+            {
+                Some(existing)
+                    if existing.display_header == group.display_header
+                        && existing.display_description == group.display_description => {}
+                Some(existing) => {
+                    existing.display_header = group.display_header.clone();
+                    existing.display_description = group.display_description.clone();
+                    changed = true;
+                }
+                None => {
+                    groups.push(group.to_representation());
+                    changed = true;
+                }
+            }
+        }
+        let attributes = config.attributes.get_or_insert_with(Vec::new);
+        for (group, attribute) in self.attributes() {
+            match attributes
+                .iter_mut()
+                .find(|a| a.name.as_deref() == Some(attribute.name.as_str()))
+            {
+                Some(existing) if attribute.matches(existing, &group.name) => {}
+                Some(existing) => {
+                    *existing = attribute.to_representation(&group.name);
+                    changed = true;
+                }
+                None => {
+                    attributes.push(attribute.to_representation(&group.name));
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
@@ -25,3 +25,28 @@ impl RealmConfigError {
         Self { id, key }
     }
 }
+
+/// Outcome of [`crate::realm::check_realm`].
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct RealmDriftReport {
+    pub realm: String,
+    /// Differences between the realm and the expected configuration.
+    pub drift: Vec<RealmConfigError>,
+    /// Differences left after a repair, `None` if no repair was run.
+    pub remaining: Option<Vec<RealmConfigError>>,
+}
+
+impl RealmDriftReport {
+    /// Returns true if the realm matches the expected configuration, after
+    /// the repair if one was run.
+    pub fn is_clean(&self) -> bool {
+        self.remaining.as_ref().unwrap_or(&self.drift).is_empty()
+    }
+
+    /// Returns true if `error` was fixed by the repair.
+    pub fn is_repaired(&self, error: &RealmConfigError) -> bool {
+        self.remaining
+            .as_ref()
+            .is_some_and(|remaining| remaining.iter().all(|e| e.id != error.id))
+    }
+}
@@ -2,6 +2,9 @@ pub const REALM_PREFIX: &str = "realm-";
 pub const CLIENTS_CLIENT_PREFIX: &str = "clients-client-";
 pub const REALM_AUTHENTICATION_FLOW_2FAEMAIL_PREFIX: &str = "authentication_flow_2faemail-";
 pub const REALM_BROWSER_FLOW_PREFIX: &str = "browser_flow";
+pub const USER_PROFILE_PREFIX: &str = "user_profile-";
+pub const LOCALIZATION_PREFIX: &str = "localization-";
+pub const EVENTS_PREFIX: &str = "events-";
 pub const REALM_DEFAULT_LOCALE_INVALID_ID: &str = "realm-default_locale-invalid";
 pub const REALM_DEFAULT_LOCALE_MISSING_ID: &str = "realm-default_locale-missing";
 pub const REALM_INTERNATIONALIZATION_ENABLED_ID: &str = "realm-internationalization_enabled";
@@ -24,6 +27,10 @@ pub const REALM_REGISTRATION_ALLOWED_ID: &str = "realm-registration_allowed";
 pub const REALM_RESET_PASSWORD_ALLOWED_ID: &str = "realm-reset_password_allowed";
 pub const REALM_SUPPORTED_LOCALES_INVALID_ID: &str = "realm-supported_locales-invalid";
 pub const REALM_SUPPORTED_LOCALES_MISSING_ID: &str = "realm-supported_locales-missing";
+pub const REALM_OTP_POLICY_TYPE_ID: &str = "realm-otp_policy-type";
+pub const REALM_OTP_POLICY_DIGITS_ID: &str = "realm-otp_policy-digits";
+pub const REALM_OTP_POLICY_PERIOD_ID: &str = "realm-otp_policy-period";
+pub const REALM_OTP_POLICY_ALGORITHM_ID: &str = "realm-otp_policy-algorithm";
 pub const REALM_SMTP_SERVER_MISSING_ID: &str = "realm-smtp_server-missing";
 pub const REALM_SMTP_SERVER_REPLY_TO_DISPLAY_NAME_MISSING_ID: &str =
     "realm-smtp_server-reply_to_display_name-missing";
@@ -76,6 +83,16 @@ pub const CLIENTS_CLIENT_STANDARD_FLOW_ENABLED_ID: &str = "clients-client-standa
 pub const CLIENTS_CLIENT_MISSING_ID: &str = "clients-client-missing";
 pub const CLIENTS_CLIENT_FRONTCHANNEL_LOGOUT_ENABLED_ID: &str =
     "clients-client-frontchannel_logout_enabled";
+pub const USER_PROFILE_GROUP_MISSING_ID: &str = "user_profile-group-missing";
+pub const USER_PROFILE_ATTRIBUTE_MISSING_ID: &str = "user_profile-attribute-missing";
+pub const USER_PROFILE_ATTRIBUTE_MISMATCHED_ID: &str = "user_profile-attribute-mismatched";
+pub const LOCALIZATION_TEXTS_ID: &str = "localization-texts";
+pub const EVENTS_ENABLED_ID: &str = "events-enabled";
+pub const EVENTS_EXPIRATION_ID: &str = "events-expiration";
+pub const EVENTS_ADMIN_EVENTS_ID: &str = "events-admin_events";
+pub const EVENTS_LISTENERS_MISSING_ID: &str = "events-listeners-missing";
+pub const EVENTS_EVENT_TYPES_MISSING_ID: &str = "events-event_types-missing";
+pub const EVENTS_HTTP_LISTENER_ATTRIBUTES_ID: &str = "events-http_listener-attributes";
 pub const GROUPS_CUSTOMER_ID: &str = "groups-customer";
 pub const GROUPS_OWNER_ID: &str = "groups-owner";
 pub const ROLES_CUSTOMER_ID: &str = "roles-customer_id";
@@ -113,6 +130,10 @@ pub const REALM_REGISTRATION_ALLOWED_KEY: &str = "realm.registration_allowed";
 pub const REALM_RESET_PASSWORD_ALLOWED_KEY: &str = "realm.reset_password_allowed";
 pub const REALM_SUPPORTED_LOCALES_INVALID_KEY: &str = "realm.supported_locales.invalid";
 pub const REALM_SUPPORTED_LOCALES_MISSING_KEY: &str = "realm.supported_locales.missing";
+pub const REALM_OTP_POLICY_TYPE_KEY: &str = "realm.otp_policy.type";
+pub const REALM_OTP_POLICY_DIGITS_KEY: &str = "realm.otp_policy.digits";
+pub const REALM_OTP_POLICY_PERIOD_KEY: &str = "realm.otp_policy.period";
+pub const REALM_OTP_POLICY_ALGORITHM_KEY: &str = "realm.otp_policy.algorithm";
 pub const REALM_SMTP_SERVER_MISSING_KEY: &str = "realm.smtp_server.missing";
 pub const REALM_SMTP_SERVER_REPLY_TO_DISPLAY_NAME_MISSING_KEY: &str =
     "realm.smtp_server.reply_to_display_name.missing";
@@ -165,3 +186,13 @@ pub const CLIENTS_CLIENT_STANDARD_FLOW_ENABLED_KEY: &str = "clients.client.stand
 pub const CLIENTS_CLIENT_MISSING_KEY: &str = "clients.client.missing";
 pub const CLIENTS_CLIENT_FRONTCHANNEL_LOGOUT_ENABLED_KEY: &str =
     "clients.client.frontchannel_logout_enabled";
+pub const USER_PROFILE_GROUP_MISSING_KEY: &str = "user_profile.group.missing";
+pub const USER_PROFILE_ATTRIBUTE_MISSING_KEY: &str = "user_profile.attribute.missing";
+pub const USER_PROFILE_ATTRIBUTE_MISMATCHED_KEY: &str = "user_profile.attribute.mismatched";
+pub const LOCALIZATION_TEXTS_KEY: &str = "localization.texts";
+pub const EVENTS_ENABLED_KEY: &str = "events.enabled";
+pub const EVENTS_EXPIRATION_KEY: &str = "events.expiration";
+pub const EVENTS_ADMIN_EVENTS_KEY: &str = "events.admin_events";
+pub const EVENTS_LISTENERS_MISSING_KEY: &str = "events.listeners.missing";
+pub const EVENTS_EVENT_TYPES_MISSING_KEY: &str = "events.event_types.missing";
+pub const EVENTS_HTTP_LISTENER_ATTRIBUTES_KEY: &str = "events.http_listener.attributes";
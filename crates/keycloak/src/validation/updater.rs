@@ -74,6 +74,42 @@ pub async fn update_for_errors(
     .await?;
     actions.retain(|e| !e.id.starts_with(realm_errors::REALM_BROWSER_FLOW_PREFIX));
 
+    update_user_profile(
+        ctx,
+        realm,
+        actions
+            .iter()
+            .filter(|e| e.id.starts_with(realm_errors::USER_PROFILE_PREFIX))
+            .cloned()
+            .collect(),
+    )
+    .await?;
+    actions.retain(|e| !e.id.starts_with(realm_errors::USER_PROFILE_PREFIX));
+
+    update_localization(
+        ctx,
+        realm,
+        actions
+            .iter()
+            .filter(|e| e.id.starts_with(realm_errors::LOCALIZATION_PREFIX))
+            .cloned()
+            .collect(),
+    )
+    .await?;
+    actions.retain(|e| !e.id.starts_with(realm_errors::LOCALIZATION_PREFIX));
+
+    update_events(
+        ctx,
+        realm,
+        actions
+            .iter()
+            .filter(|e| e.id.starts_with(realm_errors::EVENTS_PREFIX))
+            .cloned()
+            .collect(),
+    )
+    .await?;
+    actions.retain(|e| !e.id.starts_with(realm_errors::EVENTS_PREFIX));
+
     if !actions.is_empty() {
         tracing::error!(
             "Some unknown errors could not be resolved. Remaining: {:?}",
@@ -197,6 +233,22 @@ async fn update_realm_settings(
             tracing::trace!("Setting 'supported_locales' for realm '{}'", realm);
             rep.supported_locales = Some(vec!["de".to_string()]);
         }
+        realm_errors::REALM_OTP_POLICY_TYPE_ID => {
+            tracing::trace!("Setting 'otp_policy_type' for realm '{}'", realm);
+            ctx.cfg().keycloak().otp_policy().set_type(&mut rep);
+        }
+        realm_errors::REALM_OTP_POLICY_DIGITS_ID => {
+            tracing::trace!("Setting 'otp_policy_digits' for realm '{}'", realm);
+            ctx.cfg().keycloak().otp_policy().set_digits(&mut rep);
+        }
+        realm_errors::REALM_OTP_POLICY_PERIOD_ID => {
+            tracing::trace!("Setting 'otp_policy_period' for realm '{}'", realm);
+            ctx.cfg().keycloak().otp_policy().set_period(&mut rep);
+        }
+        realm_errors::REALM_OTP_POLICY_ALGORITHM_ID => {
+            tracing::trace!("Setting 'otp_policy_algorithm' for realm '{}'", realm);
+            ctx.cfg().keycloak().otp_policy().set_algorithm(&mut rep);
+        }
         realm_errors::REALM_SMTP_SERVER_MISSING_ID => {
             tracing::trace!("Setting 'smtp_server' for realm '{}'", realm);
             rep.smtp_server = get_smtp_server_defaults(ctx)
@@ -522,6 +574,71 @@ async fn update_browser_flow(
     Ok(())
 }
 
+async fn update_user_profile(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: Vec<RealmConfigErrorInput>,
+) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let Some(expected) = ctx.keycloak().expected_user_profile() else {
+        return Ok(());
+    };
+    let mut config = ctx.keycloak().user_profile(realm).await?;
+    if expected.apply(&mut config) {
+        tracing::info!("Updating user profile for realm '{}'", realm);
+        ctx.keycloak().update_user_profile(realm, config).await?;
+    }
+    Ok(())
+}
+
+async fn update_localization(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: Vec<RealmConfigErrorInput>,
+) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let Some(expected) = ctx.keycloak().expected_localization() else {
+        return Ok(());
+    };
+    for locale in expected.locales() {
+        let existing = ctx.keycloak().localization_texts(realm, locale).await?;
+        let texts = expected.drift(locale, &existing);
+        if !texts.is_empty() {
+            tracing::info!(
+                "Updating {} localization texts for '{}' in realm '{}'",
+                texts.len(),
+                locale,
+                realm
+            );
+            ctx.keycloak()
+                .update_localization_texts(realm, locale, texts)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn update_events(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: Vec<RealmConfigErrorInput>,
+) -> anyhow::Result<()> {
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let Some(expected) = ctx.keycloak().expected_events() else {
+        return Ok(());
+    };
+    if ctx.keycloak().apply_events_config(realm, expected).await? {
+        tracing::info!("Updated events config for realm '{}'", realm);
+    }
+    Ok(())
+}
+
 async fn update_client_settings(
     ctx: &Ctx<'_>,
     realm: &str,
@@ -11,6 +11,9 @@ pub async fn validate_realm(ctx: &Ctx<'_>) -> anyhow::Result<Option<Vec<RealmCon
     tracing::info!("validating realm '{realm}'");
     check_realm_settings(ctx, realm, &mut errors).await?;
     check_client(ctx, realm, &mut errors).await?;
+    check_user_profile(ctx, realm, &mut errors).await?;
+    check_localization(ctx, realm, &mut errors).await?;
+    check_events(ctx, realm, &mut errors).await?;
     Ok(Some(errors))
 }
 
@@ -161,6 +164,36 @@ async fn check_realm_settings(
             errors,
         );
     }
+    // otp_policy must match the configured policy
+    let otp_policy = ctx.keycloak().config().otp_policy();
+    if !otp_policy.type_matches(&rep) {
+        add_error(
+            realm_errors::REALM_OTP_POLICY_TYPE_ID,
+            realm_errors::REALM_OTP_POLICY_TYPE_KEY,
+            errors,
+        );
+    }
+    if !otp_policy.digits_match(&rep) {
+        add_error(
+            realm_errors::REALM_OTP_POLICY_DIGITS_ID,
+            realm_errors::REALM_OTP_POLICY_DIGITS_KEY,
+            errors,
+        );
+    }
+    if !otp_policy.period_matches(&rep) {
+        add_error(
+            realm_errors::REALM_OTP_POLICY_PERIOD_ID,
+            realm_errors::REALM_OTP_POLICY_PERIOD_KEY,
+            errors,
+        );
+    }
+    if !otp_policy.algorithm_matches(&rep) {
+        add_error(
+            realm_errors::REALM_OTP_POLICY_ALGORITHM_ID,
+            realm_errors::REALM_OTP_POLICY_ALGORITHM_KEY,
+            errors,
+        );
+    }
     // smtp_server must be configured
     if let Some(smtp_server) = &rep.smtp_server {
         check_realm_smtp_settings(ctx, smtp_server, errors);
@@ -410,6 +443,139 @@ async fn check_client(
     Ok(())
 }
 
+async fn check_user_profile(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: &mut Vec<RealmConfigError>,
+) -> anyhow::Result<()> {
+    let Some(expected) = ctx.keycloak().expected_user_profile() else {
+        return Ok(());
+    };
+    let drift = expected.drift(&ctx.keycloak().user_profile(realm).await?);
+    // all groups of the definition must exist
+    if !drift.missing_groups.is_empty() {
+        tracing::debug!("missing user profile groups {:?}", drift.missing_groups);
+        add_error(
+            realm_errors::USER_PROFILE_GROUP_MISSING_ID,
+            realm_errors::USER_PROFILE_GROUP_MISSING_KEY,
+            errors,
+        );
+    }
+    // all attributes of the definition must exist with the same settings
+    if !drift.missing_attributes.is_empty() {
+        tracing::debug!(
+            "missing user profile attributes {:?}",
+            drift.missing_attributes
+        );
+        add_error(
+            realm_errors::USER_PROFILE_ATTRIBUTE_MISSING_ID,
+            realm_errors::USER_PROFILE_ATTRIBUTE_MISSING_KEY,
+            errors,
+        );
+    }
+    if !drift.mismatched_attributes.is_empty() {
+        tracing::debug!(
+            "mismatched user profile attributes {:?}",
+            drift.mismatched_attributes
+        );
+        add_error(
+            realm_errors::USER_PROFILE_ATTRIBUTE_MISMATCHED_ID,
+            realm_errors::USER_PROFILE_ATTRIBUTE_MISMATCHED_KEY,
+            errors,
+        );
+    }
+    Ok(())
+}
+
+async fn check_localization(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: &mut Vec<RealmConfigError>,
+) -> anyhow::Result<()> {
+    let Some(expected) = ctx.keycloak().expected_localization() else {
+        return Ok(());
+    };
+    // all texts of the definition must exist with the same value
+    for locale in expected.locales() {
+        let existing = ctx.keycloak().localization_texts(realm, locale).await?;
+        let drift = expected.drift(locale, &existing);
+        if !drift.is_empty() {
+            tracing::debug!(
+                "missing or outdated localization texts for '{locale}' {:?}",
+                drift.keys()
+            );
+            add_error(
+                realm_errors::LOCALIZATION_TEXTS_ID,
+                realm_errors::LOCALIZATION_TEXTS_KEY,
+                errors,
+            );
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn check_events(
+    ctx: &Ctx<'_>,
+    realm: &str,
+    errors: &mut Vec<RealmConfigError>,
+) -> anyhow::Result<()> {
+    let Some(expected) = ctx.keycloak().expected_events() else {
+        return Ok(());
+    };
+    let rep = ctx.keycloak().events_config(realm).await?;
+    if !expected.enabled_matches(&rep) {
+        add_error(
+            realm_errors::EVENTS_ENABLED_ID,
+            realm_errors::EVENTS_ENABLED_KEY,
+            errors,
+        );
+    }
+    if !expected.expiration_matches(&rep) {
+        add_error(
+            realm_errors::EVENTS_EXPIRATION_ID,
+            realm_errors::EVENTS_EXPIRATION_KEY,
+            errors,
+        );
+    }
+    if !expected.admin_events_match(&rep) {
+        add_error(
+            realm_errors::EVENTS_ADMIN_EVENTS_ID,
+            realm_errors::EVENTS_ADMIN_EVENTS_KEY,
+            errors,
+        );
+    }
+    // all listeners of the configuration must be registered
+    let listeners = expected.missing_listeners(&rep);
+    if !listeners.is_empty() {
+        tracing::debug!("missing event listeners {:?}", listeners);
+        add_error(
+            realm_errors::EVENTS_LISTENERS_MISSING_ID,
+            realm_errors::EVENTS_LISTENERS_MISSING_KEY,
+            errors,
+        );
+    }
+    let event_types = expected.missing_event_types(&rep);
+    if !event_types.is_empty() {
+        tracing::debug!("missing event types {:?}", event_types);
+        add_error(
+            realm_errors::EVENTS_EVENT_TYPES_MISSING_ID,
+            realm_errors::EVENTS_EVENT_TYPES_MISSING_KEY,
+            errors,
+        );
+    }
+    if let Some(listener) = &expected.http_listener {
+        if !listener.attributes_match(&ctx.keycloak().realm_by_name(realm).await?) {
+            add_error(
+                realm_errors::EVENTS_HTTP_LISTENER_ATTRIBUTES_ID,
+                realm_errors::EVENTS_HTTP_LISTENER_ATTRIBUTES_KEY,
+                errors,
+            );
+        }
+    }
+    Ok(())
+}
+
 fn add_error<S>(error_id: S, error_key: S, errors: &mut Vec<RealmConfigError>)
 where
     S: Into<String>,
@@ -1,4 +1,4 @@
-use futures::stream::StreamExt;
+use futures::stream::{StreamExt, TryStreamExt};
 use mongodb::bson::doc;
 use mongodb::bson::Document;
 use mongodb::options::{FindOneAndUpdateOptions, IndexOptions};
@@ -7,6 +7,8 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::config::Config as MongoDbConfig;
+use crate::health::DbMetrics;
+use crate::tenant::{TenantRoute, TenantRouting, TENANT_DATABASES_COLLECTION};
 
 async fn collections(client: &Client, database: &str) -> mongodb::error::Result<Arc<[Arc<str>]>> {
     Ok(client
@@ -21,10 +23,13 @@ async fn collections(client: &Client, database: &str) -> mongodb::error::Result<
 struct Inner {
     db_name: Arc<str>,
     admin_db_name: Arc<str>,
+    username: Option<Arc<str>>,
     client: Client,
     admin: Client,
     is_sharded: bool,
     collections: RwLock<Arc<[Arc<str>]>>,
+    metrics: DbMetrics,
+    tenants: TenantRouting,
 }
 
 #[derive(serde::Deserialize)]
@@ -42,6 +47,52 @@ pub struct DbUsers {
     users: Vec<DbUser>,
 }
 
+async fn ensure_user(
+    admin: &Client,
+    database: &str,
+    username: &str,
+    password: &str,
+) -> mongodb::error::Result<()> {
+    let db_users = mongodb::bson::from_document::<DbUsers>(
+        admin
+            .database(database)
+            .run_command(doc! {
+                "usersInfo": [{
+                    "db": database,
+                    "user": username,
+                }],
+                "showPrivileges": false,
+                "showCredentials": false,
+            })
+            .await?,
+    )
+    .ok();
+    if !db_users
+        .map(|u| {
+            u.users
+                .iter()
+                .any(|u: &DbUser| u.db == database && u.user == username)
+        })
+        .unwrap_or(false)
+    {
+        tracing::info!("create user {username} for db {database}");
+        admin
+            .database(database)
+            .run_command(doc! {
+                "createUser": username,
+                "pwd": password,
+                "roles": [
+                    {
+                        "role": "readWrite",
+                        "db": database,
+                    }
+                ]
+            })
+            .await?;
+    }
+    Ok(())
+}
+
 #[derive(Clone)]
 pub struct DB {
     inner: Arc<Inner>,
@@ -60,61 +111,45 @@ impl DB {
                 .create_collection("counters")
                 .await?;
         }
-        let db_users = mongodb::bson::from_document::<DbUsers>(
-            admin
-                .database(cfg.database())
-                .run_command(doc! {
-                    "usersInfo": [{
-                        "db": cfg.database(),
-                        "user": cfg.username(),
-                    }],
-                    "showPrivileges": false,
-                    "showCredentials": false,
-                })
-                .await?,
-        )
-        .ok();
-        if !db_users
-            .map(|u| {
-                u.users
-                    .iter()
-                    .any(|u: &DbUser| u.db == cfg.database() && u.user == cfg.username())
-            })
-            .unwrap_or(false)
-        {
-            tracing::info!("{app_name} -> create db {}", cfg.database());
-            admin
-                .database(cfg.database())
-                .run_command(doc! {
-                    "createUser": cfg.username(),
-                    "pwd": cfg.password(),
-                    "roles": [
-                        {
-                            "role": "readWrite",
-                            "db": cfg.database(),
-                        }
-                    ]
-                })
-                .await?;
+        if let Some(credentials) = cfg.credentials().filter(|_| cfg.ensure_user()) {
+            ensure_user(
+                &admin,
+                cfg.database(),
+                credentials.username(),
+                credentials.password(),
+            )
+            .await?;
         }
+        let metrics = DbMetrics::default();
         let mut client_options = ClientOptions::parse(cfg.address()).await?;
         client_options.app_name = Some(app_name.to_string());
+        client_options.cmap_event_handler = Some(metrics.event_handler());
         let client = Client::with_options(client_options)?;
         let is_sharded = cfg.sharded();
         let db = Self {
             inner: Arc::new(Inner {
                 db_name: Arc::from(cfg.database()),
                 admin_db_name: Arc::from(cfg.root_database()),
+                username: cfg.credentials().map(|c| Arc::from(c.username())),
                 client,
                 admin,
                 is_sharded,
                 collections,
+                metrics,
+                tenants: TenantRouting::new(cfg),
             }),
         };
         db.setup(cfg).await?;
+        if db.tenants().is_enabled() {
+            db.load_tenant_routes().await?;
+        }
         Ok(db)
     }
 
+    pub fn metrics(&self) -> &DbMetrics {
+        &self.inner.metrics
+    }
+
     pub fn is_sharded(&self) -> bool {
         self.inner.is_sharded
     }
@@ -135,6 +170,84 @@ impl DB {
         &self.inner.db_name
     }
 
+    pub fn tenants(&self) -> &TenantRouting {
+        &self.inner.tenants
+    }
+
+    /// Database of `collection`, the database of `tenant` for tenant
+    /// collections and the shared database otherwise.
+    pub fn database_for(&self, tenant: Option<&str>, collection: &str) -> Database {
+        tenant
+            .filter(|_| self.inner.tenants.is_tenant_collection(collection))
+            .and_then(|tenant| self.inner.tenants.database(tenant))
+            .map(|database| self.inner.client.database(&database))
+            .unwrap_or_else(|| self.get())
+    }
+
+    pub fn collection_for<T>(&self, tenant: Option<&str>, name: &str) -> Collection<T>
+    where
+        T: Send + Sync,
+    {
+        self.database_for(tenant, name).collection::<T>(name)
+    }
+
+    pub async fn load_tenant_routes(&self) -> mongodb::error::Result<()> {
+        let routes: Vec<TenantRoute> = self
+            .get()
+            .collection::<TenantRoute>(TENANT_DATABASES_COLLECTION)
+            .find(doc! {})
+            .await?
+            .try_collect()
+            .await?;
+        self.inner.tenants.set_routes(routes);
+        Ok(())
+    }
+
+    /// Creates the database of `tenant` with its collections, grants the
+    /// user of the service access and stores the route in the shared
+    /// database. Returns `None` if routing is disabled.
+    pub async fn provision_tenant(&self, tenant: &str) -> mongodb::error::Result<Option<Arc<str>>> {
+        let Some(database) = self.inner.tenants.database(tenant) else {
+            return Ok(None);
+        };
+        tracing::info!("provision database {database} for tenant {tenant}");
+        if let Some(username) = self.inner.username.as_deref() {
+            self.inner
+                .admin
+                .database(self.db_name())
+                .run_command(doc! {
+                    "grantRolesToUser": username,
+                    "roles": [{ "role": "readWrite", "db": database.as_ref() }],
+                })
+                .await?;
+        }
+        if self.is_sharded() {
+            self.get_admin()
+                .run_command(doc! { "enableSharding": database.as_ref() })
+                .await?;
+        }
+        let tenant_db = self.inner.admin.database(&database);
+        let existing = tenant_db.list_collection_names().await?;
+        for collection in self.inner.tenants.collections() {
+            if !existing.iter().any(|c| c == collection) {
+                tenant_db.create_collection(collection).await?;
+            }
+        }
+        self.get()
+            .collection::<TenantRoute>(TENANT_DATABASES_COLLECTION)
+            .replace_one(
+                doc! { "_id": tenant },
+                TenantRoute {
+                    tenant: tenant.to_string(),
+                    database: database.to_string(),
+                },
+            )
+            .upsert(true)
+            .await?;
+        self.inner.tenants.set_route(tenant, &database);
+        Ok(Some(database))
+    }
+
     pub async fn setup<'a>(&'a self, cfg: &MongoDbConfig) -> mongodb::error::Result<()> {
         if self.is_sharded() {
             self.get_admin()
@@ -0,0 +1,336 @@
+//! Typed GridFS access for large binary files like generated PDFs.
+//!
+//! Files are stored with a metadata document of the form
+//! `{ owner, contentType, data }`, where `owner` is the string form of an
+//! owner context (e.g. `qm_entity::ids::InfraContext`) and `data` the
+//! serialized user metadata `M`.
+
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use futures::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId, Bson, DateTime, Document};
+pub use mongodb::gridfs::{
+    FilesCollectionDocument, GridFsBucket, GridFsDownloadStream, GridFsUploadStream,
+};
+use mongodb::options::GridFsBucketOptions;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::DB;
+
+const OWNER: &str = "owner";
+const CONTENT_TYPE: &str = "contentType";
+const DATA: &str = "data";
+
+/// A file to upload, created with [`NewFile::new`].
+pub struct NewFile<M = Document> {
+    filename: String,
+    content_type: Option<String>,
+    metadata: Option<M>,
+}
+
+impl<M> NewFile<M> {
+    pub fn new(filename: impl Into<String>) -> Self {
+        Self {
+            filename: filename.into(),
+            content_type: None,
+            metadata: None,
+        }
+    }
+
+    pub fn with_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: M) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileInfo<M = Document> {
+    pub id: ObjectId,
+    pub filename: Option<String>,
+    pub length: u64,
+    pub upload_date: DateTime,
+    pub owner: Option<String>,
+    pub content_type: Option<String>,
+    pub metadata: Option<M>,
+}
+
+impl<M> FileInfo<M>
+where
+    M: DeserializeOwned,
+{
+    fn from_files_document(file: FilesCollectionDocument) -> mongodb::error::Result<Option<Self>> {
+        let Bson::ObjectId(id) = file.id else {
+            return Ok(None);
+        };
+        let metadata = file.metadata.unwrap_or_default();
+        Ok(Some(Self {
+            id,
+            filename: file.filename,
+            length: file.length,
+            upload_date: file.upload_date,
+            owner: metadata.get_str(OWNER).ok().map(String::from),
+            content_type: metadata.get_str(CONTENT_TYPE).ok().map(String::from),
+            metadata: metadata
+                .get(DATA)
+                .cloned()
+                .map(mongodb::bson::from_bson)
+                .transpose()?,
+        }))
+    }
+}
+
+/// A GridFS bucket with metadata of type `M`, optionally restricted to the
+/// files of a single owner.
+pub struct GridFs<M = Document> {
+    bucket: GridFsBucket,
+    owner: Option<String>,
+    _metadata: PhantomData<M>,
+}
+
+impl<M> Clone for GridFs<M> {
+    fn clone(&self) -> Self {
+        Self {
+            bucket: self.bucket.clone(),
+            owner: self.owner.clone(),
+            _metadata: PhantomData,
+        }
+    }
+}
+
+impl DB {
+    /// Returns the GridFS bucket `name` of the application database.
+    pub fn gridfs<M>(&self, name: &str) -> GridFs<M> {
+        let options = GridFsBucketOptions::builder()
+            .bucket_name(name.to_string())
+            .build();
+        GridFs {
+            bucket: self.get().gridfs_bucket(options),
+            owner: None,
+            _metadata: PhantomData,
+        }
+    }
+}
+
+impl<M> GridFs<M>
+where
+    M: Serialize + DeserializeOwned,
+{
+    /// Restricts the bucket to files of `owner`, uploads are tagged with it
+    /// and files of other owners are treated as not existing. `None` means
+    /// unrestricted access.
+    pub fn scoped<O>(mut self, owner: Option<&O>) -> Self
+    where
+        O: Display,
+    {
+        self.owner = owner.map(ToString::to_string);
+        self
+    }
+
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    pub fn bucket(&self) -> &GridFsBucket {
+        &self.bucket
+    }
+
+    fn filter(&self, mut filter: Document) -> Document {
+        if let Some(owner) = self.owner.as_deref() {
+            filter.insert(format!("metadata.{OWNER}"), owner);
+        }
+        filter
+    }
+
+    pub async fn upload(&self, file: NewFile<M>, data: &[u8]) -> mongodb::error::Result<ObjectId> {
+        self.upload_from(file, data).await
+    }
+
+    /// Streams `reader` into a new file, the partially written chunks are
+    /// removed if reading fails.
+    pub async fn upload_from<R>(
+        &self,
+        file: NewFile<M>,
+        mut reader: R,
+    ) -> mongodb::error::Result<ObjectId>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let id = ObjectId::new();
+        let metadata = metadata_document(
+            self.owner.as_deref(),
+            file.content_type.as_deref(),
+            file.metadata.as_ref(),
+        )?;
+        let mut stream = self
+            .bucket
+            .open_upload_stream(&file.filename)
+            .id(id.into())
+            .metadata(metadata)
+            .await?;
+        if let Err(err) = futures::io::copy(&mut reader, &mut stream).await {
+            stream.abort().await?;
+            return Err(err.into());
+        }
+        stream.close().await?;
+        Ok(id)
+    }
+
+    pub async fn find(&self, id: &ObjectId) -> mongodb::error::Result<Option<FileInfo<M>>> {
+        match self
+            .bucket
+            .find_one(self.filter(doc! { "_id": id }))
+            .await?
+        {
+            Some(file) => FileInfo::from_files_document(file),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list(&self, filename: Option<&str>) -> mongodb::error::Result<Vec<FileInfo<M>>> {
+        let filter = match filename {
+            Some(filename) => doc! { "filename": filename },
+            None => doc! {},
+        };
+        let files: Vec<FilesCollectionDocument> = self
+            .bucket
+            .find(self.filter(filter))
+            .await?
+            .try_collect()
+            .await?;
+        let mut result = Vec::with_capacity(files.len());
+        for file in files {
+            result.extend(FileInfo::from_files_document(file)?);
+        }
+        Ok(result)
+    }
+
+    async fn exists(&self, id: &ObjectId) -> mongodb::error::Result<bool> {
+        if self.owner.is_none() {
+            return Ok(true);
+        }
+        Ok(self
+            .bucket
+            .find_one(self.filter(doc! { "_id": id }))
+            .await?
+            .is_some())
+    }
+
+    pub async fn open_download_stream(
+        &self,
+        id: &ObjectId,
+    ) -> mongodb::error::Result<Option<GridFsDownloadStream>> {
+        if !self.exists(id).await? {
+            return Ok(None);
+        }
+        match self.bucket.open_download_stream((*id).into()).await {
+            Ok(stream) => Ok(Some(stream)),
+            Err(err) if is_file_not_found(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Copies the file into `writer` and returns the number of bytes written.
+    pub async fn download_to<W>(
+        &self,
+        id: &ObjectId,
+        writer: &mut W,
+    ) -> mongodb::error::Result<Option<u64>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let Some(mut stream) = self.open_download_stream(id).await? else {
+            return Ok(None);
+        };
+        let written = futures::io::copy(&mut stream, writer).await?;
+        writer.flush().await?;
+        Ok(Some(written))
+    }
+
+    pub async fn download(&self, id: &ObjectId) -> mongodb::error::Result<Option<Vec<u8>>> {
+        let mut data = vec![];
+        Ok(self.download_to(id, &mut data).await?.map(|_| data))
+    }
+
+    /// Deletes the file and its chunks, returns `false` if it doesn't exist.
+    pub async fn delete(&self, id: &ObjectId) -> mongodb::error::Result<bool> {
+        if !self.exists(id).await? {
+            return Ok(false);
+        }
+        match self.bucket.delete((*id).into()).await {
+            Ok(()) => Ok(true),
+            Err(err) if is_file_not_found(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn is_file_not_found(err: &mongodb::error::Error) -> bool {
+    matches!(
+        err.kind.as_ref(),
+        mongodb::error::ErrorKind::GridFs(mongodb::error::GridFsErrorKind::FileNotFound { .. })
+    )
+}
+
+fn metadata_document<M>(
+    owner: Option<&str>,
+    content_type: Option<&str>,
+    data: Option<&M>,
+) -> mongodb::error::Result<Document>
+where
+    M: Serialize,
+{
+    let mut metadata = Document::new();
+    if let Some(owner) = owner {
+        metadata.insert(OWNER, owner);
+    }
+    if let Some(content_type) = content_type {
+        metadata.insert(CONTENT_TYPE, content_type);
+    }
+    if let Some(data) = data {
+        metadata.insert(DATA, mongodb::bson::to_bson(data)?);
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+    struct Report {
+        pages: i32,
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() -> mongodb::error::Result<()> {
+        let metadata = metadata_document(
+            Some("V01"),
+            Some("application/pdf"),
+            Some(&Report { pages: 3 }),
+        )?;
+        assert_eq!(
+            metadata,
+            doc! { "owner": "V01", "contentType": "application/pdf", "data": { "pages": 3 } }
+        );
+        let file: FilesCollectionDocument = mongodb::bson::from_document(doc! {
+            "_id": ObjectId::new(),
+            "length": 42_i64,
+            "chunkSize": 261120,
+            "uploadDate": DateTime::now(),
+            "filename": "report.pdf",
+            "metadata": metadata,
+        })?;
+        let info = FileInfo::<Report>::from_files_document(file)?.unwrap();
+        assert_eq!(info.owner.as_deref(), Some("V01"));
+        assert_eq!(info.content_type.as_deref(), Some("application/pdf"));
+        assert_eq!(info.metadata, Some(Report { pages: 3 }));
+        Ok(())
+    }
+}
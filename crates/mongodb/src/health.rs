@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicI64, AtomicU64};
+use std::time::{Duration, Instant};
+
+use mongodb::bson::doc;
+use mongodb::event::{cmap::CmapEvent, EventHandler};
+use prometheus_client::metrics::{counter::Counter, gauge::Gauge};
+use prometheus_client::registry::Registry;
+
+use crate::DB;
+
+/// Connection pool and health metrics of a [`DB`], the pool gauges are
+/// updated from the driver's connection events, `up` and the ping latency by
+/// the prober started with [`DB::spawn_health_prober`].
+#[derive(Clone, Default)]
+pub struct DbMetrics {
+    pub up: Gauge<i64, AtomicI64>,
+    pub ping_latency_seconds: Gauge<f64, AtomicU64>,
+    pub ping_failures_total: Counter,
+    pub connections_open: Gauge<i64, AtomicI64>,
+    pub connections_in_use: Gauge<i64, AtomicI64>,
+    pub connections_created_total: Counter,
+    pub connections_closed_total: Counter,
+    pub checkout_failures_total: Counter,
+    pub pool_cleared_total: Counter,
+}
+
+impl DbMetrics {
+    /// Registers all metrics with a `mongodb` prefix.
+    pub fn register(&self, registry: &mut Registry) {
+        let registry = registry.sub_registry_with_prefix("mongodb");
+        registry.register("up", "Whether the last ping succeeded", self.up.clone());
+        registry.register(
+            "ping_latency_seconds",
+            "Round trip time of the last ping",
+            self.ping_latency_seconds.clone(),
+        );
+        registry.register(
+            "ping_failures",
+            "Number of failed pings",
+            self.ping_failures_total.clone(),
+        );
+        registry.register(
+            "connections_open",
+            "Number of open pool connections",
+            self.connections_open.clone(),
+        );
+        registry.register(
+            "connections_in_use",
+            "Number of pool connections checked out",
+            self.connections_in_use.clone(),
+        );
+        registry.register(
+            "connections_created",
+            "Number of connections created, including reconnects",
+            self.connections_created_total.clone(),
+        );
+        registry.register(
+            "connections_closed",
+            "Number of connections closed",
+            self.connections_closed_total.clone(),
+        );
+        registry.register(
+            "checkout_failures",
+            "Number of failed connection checkouts",
+            self.checkout_failures_total.clone(),
+        );
+        registry.register(
+            "pool_cleared",
+            "Number of times a connection pool was cleared after an error",
+            self.pool_cleared_total.clone(),
+        );
+    }
+
+    pub(crate) fn handle(&self, event: CmapEvent) {
+        match event {
+            CmapEvent::ConnectionCreated(_) => {
+                self.connections_open.inc();
+                self.connections_created_total.inc();
+            }
+            CmapEvent::ConnectionClosed(_) => {
+                self.connections_open.dec();
+                self.connections_closed_total.inc();
+            }
+            CmapEvent::ConnectionCheckedOut(_) => {
+                self.connections_in_use.inc();
+            }
+            CmapEvent::ConnectionCheckedIn(_) => {
+                self.connections_in_use.dec();
+            }
+            CmapEvent::ConnectionCheckoutFailed(_) => {
+                self.checkout_failures_total.inc();
+            }
+            CmapEvent::PoolCleared(_) => {
+                self.pool_cleared_total.inc();
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn event_handler(&self) -> EventHandler<CmapEvent> {
+        let metrics = self.clone();
+        EventHandler::callback(move |event| metrics.handle(event))
+    }
+}
+
+/// Subset of the `serverStatus` command output.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ServerStatus {
+    pub host: String,
+    pub version: String,
+    pub uptime: f64,
+    pub connections: Option<ServerConnections>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerConnections {
+    pub current: i64,
+    pub available: i64,
+    pub total_created: i64,
+}
+
+impl DB {
+    /// Sends a `ping` to the server and returns the round trip time.
+    pub async fn ping(&self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        self.get().run_command(doc! { "ping": 1 }).await?;
+        Ok(start.elapsed())
+    }
+
+    pub async fn server_status(&self) -> mongodb::error::Result<ServerStatus> {
+        let status = self
+            .get_admin()
+            .run_command(doc! { "serverStatus": 1 })
+            .await?;
+        Ok(mongodb::bson::from_document(status)?)
+    }
+
+    /// Pings the server every `interval` and updates [`DbMetrics::up`] and
+    /// [`DbMetrics::ping_latency_seconds`].
+    pub fn spawn_health_prober(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let db = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                db.probe().await;
+            }
+        })
+    }
+
+    async fn probe(&self) {
+        let metrics = self.metrics();
+        match self.ping().await {
+            Ok(latency) => {
+                if metrics.up.set(1) == 0 {
+                    tracing::info!("mongodb '{}' is reachable", self.db_name());
+                }
+                metrics.ping_latency_seconds.set(latency.as_secs_f64());
+            }
+            Err(err) => {
+                if metrics.up.set(0) == 1 {
+                    tracing::warn!("mongodb '{}' is unreachable: {err:#}", self.db_name());
+                }
+                metrics.ping_failures_total.inc();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::event::cmap::{ConnectionCheckedInEvent, ConnectionCheckedOutEvent};
+
+    #[test]
+    fn test_metrics_handle() {
+        let metrics = DbMetrics::default();
+        let checked_out = || {
+            CmapEvent::ConnectionCheckedOut(
+                mongodb::bson::from_document::<ConnectionCheckedOutEvent>(
+                    doc! { "connectionId": 1 },
+                )
+                .unwrap(),
+            )
+        };
+        metrics.handle(checked_out());
+        metrics.handle(checked_out());
+        metrics.handle(CmapEvent::ConnectionCheckedIn(
+            mongodb::bson::from_document::<ConnectionCheckedInEvent>(doc! { "connectionId": 1 })
+                .unwrap(),
+        ));
+        assert_eq!(metrics.connections_in_use.get(), 1);
+
+        let mut registry = Registry::default();
+        metrics.register(&mut registry);
+        let mut body = String::new();
+        prometheus_client::encoding::text::encode(&mut body, &registry).unwrap();
+        assert!(body.contains("mongodb_connections_in_use 1"));
+        assert!(body.contains("mongodb_ping_failures_total 0"));
+    }
+}
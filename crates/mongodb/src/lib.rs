@@ -2,6 +2,10 @@ pub use mongodb::*;
 
 mod config;
 mod db;
+pub mod gridfs;
+mod health;
+pub mod tenant;
 
-pub use crate::config::Config as DbConfig;
+pub use crate::config::{Config as DbConfig, Credentials};
 pub use crate::db::{insert_always_opts, parse_vec, DB};
+pub use crate::health::{DbMetrics, ServerConnections, ServerStatus};
@@ -0,0 +1,112 @@
+//! Routing of collections to databases per tenant.
+//!
+//! The collections listed in `tenant_collections` are stored in a database
+//! per tenant, all other collections stay in the shared database. Tenant
+//! databases are named with `tenant_database_prefix` followed by the tenant,
+//! unless a route in [`TENANT_DATABASES_COLLECTION`] overrides the name.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use crate::config::Config as MongoDbConfig;
+
+pub const TENANT_DATABASES_COLLECTION: &str = "tenant_databases";
+
+/// Database of a tenant, stored in the shared database.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TenantRoute {
+    #[serde(rename = "_id")]
+    pub tenant: String,
+    pub database: String,
+}
+
+#[derive(Default)]
+pub struct TenantRouting {
+    prefix: Option<Arc<str>>,
+    collections: HashSet<Arc<str>>,
+    routes: RwLock<HashMap<Arc<str>, Arc<str>>>,
+}
+
+impl TenantRouting {
+    pub fn new(cfg: &MongoDbConfig) -> Self {
+        Self {
+            prefix: cfg.tenant_database_prefix().map(Arc::from),
+            collections: cfg.tenant_collections().iter().cloned().collect(),
+            routes: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.prefix.is_some()
+    }
+
+    pub fn is_tenant_collection(&self, collection: &str) -> bool {
+        self.is_enabled() && self.collections.contains(collection)
+    }
+
+    pub fn collections(&self) -> impl Iterator<Item = &str> {
+        self.collections.iter().map(AsRef::as_ref)
+    }
+
+    /// Name of the database of `tenant`, `None` if routing is disabled.
+    pub fn database(&self, tenant: &str) -> Option<Arc<str>> {
+        let prefix = self.prefix.as_deref()?;
+        if let Some(database) = self.routes.read().unwrap().get(tenant) {
+            return Some(database.clone());
+        }
+        Some(Arc::from(database_name(prefix, tenant)))
+    }
+
+    pub fn set_route(&self, tenant: &str, database: &str) {
+        self.routes
+            .write()
+            .unwrap()
+            .insert(Arc::from(tenant), Arc::from(database));
+    }
+
+    pub fn set_routes(&self, routes: impl IntoIterator<Item = TenantRoute>) {
+        let mut table = self.routes.write().unwrap();
+        for route in routes {
+            table.insert(Arc::from(route.tenant), Arc::from(route.database));
+        }
+    }
+}
+
+/// Replaces characters not allowed in database names and truncates the
+/// name to the maximum length of 63 bytes.
+fn database_name(prefix: &str, tenant: &str) -> String {
+    format!("{prefix}{tenant}")
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '.' | ' ' | '"' | '$' | '*' | '<' | '>' | ':' | '|' | '?' => '_',
+            c => c,
+        })
+        .take(63)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tenant_routing() -> envy::Result<()> {
+        std::env::set_var("TENANTDB_TENANT_DATABASE_PREFIX", "app_");
+        std::env::set_var("TENANTDB_TENANT_COLLECTIONS", "documents,files");
+        let cfg = MongoDbConfig::builder().with_prefix("TENANTDB_").build()?;
+        let routing = TenantRouting::new(&cfg);
+        assert!(routing.is_tenant_collection("files"));
+        assert!(!routing.is_tenant_collection("counters"));
+        assert_eq!(routing.database("V01").as_deref(), Some("app_V01"));
+        assert_eq!(routing.database("a.b").as_deref(), Some("app_a_b"));
+        routing.set_route("V01", "dedicated");
+        assert_eq!(routing.database("V01").as_deref(), Some("dedicated"));
+
+        let routing = TenantRouting::default();
+        assert!(!routing.is_tenant_collection("files"));
+        assert_eq!(routing.database("V01"), None);
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::sync::Arc;
+
+use qm_utils::secret::SecretSource;
+
+/// Fields which are resolved with the [`SecretSource`].
+const SECRETS: [&str; 2] = ["PASSWORD", "TOKEN"];
+
+#[derive(Deserialize)]
+pub struct Config {
+    host: Option<Arc<str>>,
+    port: Option<u16>,
+    user: Option<Arc<str>>,
+    password: Option<Arc<str>>,
+    token: Option<Arc<str>>,
+    #[serde(skip)]
+    address: Option<Arc<str>>,
+}
+
+impl Config {
+    pub fn new() -> envy::Result<Self> {
+        ConfigBuilder::default().build()
+    }
+
+    pub fn builder<'a>() -> ConfigBuilder<'a> {
+        ConfigBuilder::default()
+    }
+
+    pub fn address(&self) -> &str {
+        self.address.as_deref().unwrap()
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    /// Connects with user and password or with the token, if configured.
+    pub async fn connect(&self) -> anyhow::Result<async_nats::Client> {
+        let options = match (
+            self.user.as_deref(),
+            self.password.as_deref(),
+            self.token.as_deref(),
+        ) {
+            (Some(user), Some(password), _) => async_nats::ConnectOptions::with_user_and_password(
+                user.to_string(),
+                password.to_string(),
+            ),
+            (_, _, Some(token)) => async_nats::ConnectOptions::with_token(token.to_string()),
+            _ => async_nats::ConnectOptions::new(),
+        };
+        Ok(options.connect(self.address()).await?)
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder<'a> {
+    prefix: Option<&'a str>,
+    secrets: Option<SecretSource>,
+}
+
+impl<'a> ConfigBuilder<'a> {
+    pub fn with_prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    /// Source of the secrets, [`SecretSource::from_env`] by default.
+    pub fn with_secrets(mut self, secrets: SecretSource) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
+    pub fn build(self) -> envy::Result<Config> {
+        let prefix = self.prefix.unwrap_or("NATS_");
+        let vars = self
+            .secrets
+            .unwrap_or_else(SecretSource::from_env)
+            .vars(prefix, &SECRETS)
+            .map_err(|err| envy::Error::Custom(format!("{err:#}")))?;
+        let mut cfg: Config = envy::prefixed(prefix).from_iter(vars)?;
+
+        let host = cfg.host.as_deref().unwrap_or("127.0.0.1");
+        let port = cfg.port.unwrap_or(4222);
+        cfg.address = Some(Arc::from(format!("nats://{}:{}", host, port)));
+        Ok(cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_builtin_config_test() -> envy::Result<()> {
+        let cfg = super::Config::builder()
+            .with_prefix("DEFAULT_NATS_NOT_SET_IN_SHELL_")
+            .build()?;
+        assert_eq!(cfg.address(), "nats://127.0.0.1:4222");
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! Deduplication of events for consumers with at-least-once delivery.
+//!
+//! A handler wrapped with [`Dedupe::run`] claims the event id before it runs.
+//! Ids are kept as `pending` while the handler runs and as `done` for the
+//! retention window afterwards. Claims of crashed consumers are taken over
+//! after the processing timeout, failed handlers release their claim so the
+//! redelivered event is processed again.
+
+use std::{future::Future, time::Duration};
+
+use async_nats::jetstream::{
+    self,
+    kv::{self, CreateErrorKind, Operation},
+};
+
+const PENDING: &[u8] = b"pending";
+const DONE: &[u8] = b"done";
+
+/// Result of a claim in a [`DedupeStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    Claimed,
+    Pending,
+    Done,
+}
+
+/// Result of [`Dedupe::run`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome<T> {
+    /// The handler processed the event.
+    Processed(T),
+    /// The event was processed before, it can be acknowledged.
+    Duplicate,
+    /// Another consumer is processing the event, it should be redelivered
+    /// later in case that consumer fails.
+    InProgress,
+}
+
+#[async_trait::async_trait]
+pub trait DedupeStore: Send + Sync {
+    /// Claims `key`, pending claims older than `timeout` are taken over.
+    async fn claim(&self, key: &str, timeout: Duration) -> anyhow::Result<Claim>;
+    /// Marks `key` as processed for the retention window of the store.
+    async fn complete(&self, key: &str) -> anyhow::Result<()>;
+    /// Removes the claim of `key`, e.g. after the handler failed.
+    async fn release(&self, key: &str) -> anyhow::Result<()>;
+}
+
+pub struct Dedupe<S> {
+    store: S,
+    prefix: String,
+    processing_timeout: Duration,
+}
+
+impl<S: DedupeStore> Dedupe<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            prefix: String::new(),
+            processing_timeout: Duration::from_secs(300),
+        }
+    }
+
+    /// Prefix of the keys, e.g. the consumer name, when several consumers
+    /// process the same events with a shared store.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Time after which a pending claim is considered abandoned.
+    pub fn with_processing_timeout(mut self, processing_timeout: Duration) -> Self {
+        self.processing_timeout = processing_timeout;
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Runs `f` unless the event `event_id` was already processed.
+    pub async fn run<F, Fut, T>(&self, event_id: &str, f: F) -> anyhow::Result<Outcome<T>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let key = format!("{}{}", self.prefix, event_id);
+        match self.store.claim(&key, self.processing_timeout).await? {
+            Claim::Claimed => {}
+            Claim::Pending => return Ok(Outcome::InProgress),
+            Claim::Done => return Ok(Outcome::Duplicate),
+        }
+        match f().await {
+            Ok(result) => {
+                if let Err(err) = self.store.complete(&key).await {
+                    tracing::warn!("unable to complete event {key}: {err:#}");
+                }
+                Ok(Outcome::Processed(result))
+            }
+            Err(err) => {
+                if let Err(err) = self.store.release(&key).await {
+                    tracing::warn!("unable to release event {key}: {err:#}");
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Keys in a JetStream key value bucket, the retention window is the
+/// `max_age` of the bucket.
+#[derive(Clone)]
+pub struct KvStore {
+    kv: kv::Store,
+}
+
+impl KvStore {
+    pub fn new(kv: kv::Store) -> Self {
+        Self { kv }
+    }
+
+    /// Opens `bucket` or creates it with the given retention.
+    pub async fn open(
+        js: &jetstream::Context,
+        bucket: &str,
+        retention: Duration,
+    ) -> anyhow::Result<Self> {
+        let kv = match js.get_key_value(bucket).await {
+            Ok(kv) => kv,
+            Err(_) => {
+                js.create_key_value(kv::Config {
+                    bucket: bucket.to_string(),
+                    history: 1,
+                    max_age: retention,
+                    ..Default::default()
+                })
+                .await?
+            }
+        };
+        Ok(Self { kv })
+    }
+}
+
+/// Replaces characters not allowed in keys, e.g. `:` in `stream:sequence`.
+fn kv_key(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '=' | '.' | '/' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl DedupeStore for KvStore {
+    async fn claim(&self, key: &str, timeout: Duration) -> anyhow::Result<Claim> {
+        let key = kv_key(key);
+        match self.kv.create(&key, PENDING.into()).await {
+            Ok(_) => return Ok(Claim::Claimed),
+            Err(err) if err.kind() == CreateErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err.into()),
+        }
+        let Some(entry) = self.kv.entry(&key).await? else {
+            return Ok(Claim::Pending);
+        };
+        if entry.operation != Operation::Put {
+            return Ok(Claim::Pending);
+        }
+        if entry.value.as_ref() == DONE {
+            return Ok(Claim::Done);
+        }
+        let age = (time::OffsetDateTime::now_utc() - entry.created).unsigned_abs();
+        if age < timeout {
+            return Ok(Claim::Pending);
+        }
+        match self.kv.update(&key, PENDING.into(), entry.revision).await {
+            Ok(_) => Ok(Claim::Claimed),
+            Err(_) => Ok(Claim::Pending),
+        }
+    }
+
+    async fn complete(&self, key: &str) -> anyhow::Result<()> {
+        self.kv.put(kv_key(key), DONE.into()).await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> anyhow::Result<()> {
+        self.kv.delete(kv_key(key)).await?;
+        Ok(())
+    }
+}
+
+/// Keys in Redis expiring after the retention window.
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisStore {
+    redis: qm_redis::Redis,
+    retention: Duration,
+}
+
+#[cfg(feature = "redis")]
+impl RedisStore {
+    pub fn new(redis: qm_redis::Redis, retention: Duration) -> Self {
+        Self { redis, retention }
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait::async_trait]
+impl DedupeStore for RedisStore {
+    async fn claim(&self, key: &str, timeout: Duration) -> anyhow::Result<Claim> {
+        use qm_redis::redis::AsyncCommands;
+        let mut con = self.redis.connect().await?;
+        let claimed: bool = qm_redis::redis::cmd("SET")
+            .arg(key)
+            .arg(PENDING)
+            .arg("NX")
+            .arg("PX")
+            .arg(timeout.as_millis() as u64)
+            .query_async::<Option<String>>(&mut con)
+            .await?
+            .is_some();
+        if claimed {
+            return Ok(Claim::Claimed);
+        }
+        let value: Option<Vec<u8>> = con.get(key).await?;
+        match value {
+            Some(value) if value == DONE => Ok(Claim::Done),
+            _ => Ok(Claim::Pending),
+        }
+    }
+
+    async fn complete(&self, key: &str) -> anyhow::Result<()> {
+        use qm_redis::redis::AsyncCommands;
+        let mut con = self.redis.connect().await?;
+        let _: () = con
+            .pset_ex(key, DONE, self.retention.as_millis() as u64)
+            .await?;
+        Ok(())
+    }
+
+    async fn release(&self, key: &str) -> anyhow::Result<()> {
+        use qm_redis::redis::AsyncCommands;
+        let mut con = self.redis.connect().await?;
+        let _: () = con.del(key).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, Claim>>);
+
+    #[async_trait::async_trait]
+    impl DedupeStore for MemoryStore {
+        async fn claim(&self, key: &str, _: Duration) -> anyhow::Result<Claim> {
+            let mut keys = self.0.lock().unwrap();
+            match keys.get(key) {
+                Some(Claim::Done) => Ok(Claim::Done),
+                Some(_) => Ok(Claim::Pending),
+                None => {
+                    keys.insert(key.to_string(), Claim::Pending);
+                    Ok(Claim::Claimed)
+                }
+            }
+        }
+
+        async fn complete(&self, key: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().insert(key.to_string(), Claim::Done);
+            Ok(())
+        }
+
+        async fn release(&self, key: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run() -> anyhow::Result<()> {
+        let dedupe = Dedupe::new(MemoryStore::default()).with_prefix("test.");
+        let calls = AtomicUsize::new(0);
+        let handler = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            anyhow::Ok(())
+        };
+        assert_eq!(dedupe.run("1", handler).await?, Outcome::Processed(()));
+        assert_eq!(dedupe.run("1", handler).await?, Outcome::Duplicate);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let failed = dedupe
+            .run("2", || async { anyhow::bail!("failed") })
+            .await
+            .map(|_: Outcome<()>| ());
+        assert!(failed.is_err());
+        assert_eq!(dedupe.run("2", handler).await?, Outcome::Processed(()));
+
+        dedupe.store().claim("test.3", Duration::ZERO).await?;
+        assert_eq!(dedupe.run("3", handler).await?, Outcome::InProgress);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kv_key() {
+        assert_eq!(kv_key("stream:12"), "stream_12");
+        assert_eq!(kv_key("a.b-c_d/e=f"), "a.b-c_d/e=f");
+    }
+}
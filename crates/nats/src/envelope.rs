@@ -0,0 +1,250 @@
+//! Standard envelope of events published on NATS.
+//!
+//! Envelopes are JSON documents with the event metadata next to the payload.
+//! Id, type and version are repeated in the [`MSG_ID`], [`EVENT_TYPE`] and
+//! [`EVENT_VERSION`] headers, so JetStream drops duplicates within its
+//! duplicate window and consumers skip foreign events without decoding them.
+
+use async_nats::{
+    jetstream::{self, publish::PublishAck},
+    subject::ToSubject,
+    HeaderMap,
+};
+use chrono::{DateTime, Utc};
+use qm_entity::ids::InfraContext;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+pub const MSG_ID: &str = "Nats-Msg-Id";
+pub const EVENT_TYPE: &str = "Qm-Event-Type";
+pub const EVENT_VERSION: &str = "Qm-Event-Version";
+
+/// Payload of an [`EventEnvelope`].
+pub trait Event: Serialize + DeserializeOwned {
+    /// Name of the event, e.g. `customer.created`.
+    const TYPE: &'static str;
+    /// Schema version of the payload, increased on breaking changes.
+    const VERSION: u32 = 1;
+
+    /// Converts a payload of schema `version` into the one of `version + 1`,
+    /// older events are upgraded step by step while decoding.
+    fn upgrade(version: u32, payload: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        let _ = payload;
+        anyhow::bail!("unable to upgrade {} from version {version}", Self::TYPE)
+    }
+}
+
+/// Metadata of an envelope read from the message headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventMeta {
+    pub id: String,
+    pub event_type: String,
+    pub version: u32,
+}
+
+impl EventMeta {
+    pub fn from_headers(headers: Option<&HeaderMap>) -> Option<Self> {
+        let headers = headers?;
+        Some(Self {
+            id: headers.get(MSG_ID)?.as_str().to_string(),
+            event_type: headers.get(EVENT_TYPE)?.as_str().to_string(),
+            version: headers.get(EVENT_VERSION)?.as_str().parse().ok()?,
+        })
+    }
+
+    pub fn is<T: Event>(&self) -> bool {
+        self.event_type == T::TYPE
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventEnvelope<T> {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub version: u32,
+    pub occurred_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<InfraContext>,
+    pub payload: T,
+}
+
+impl<T: Event> EventEnvelope<T> {
+    pub fn new(payload: T) -> Self {
+        Self {
+            id: uuid::Uuid::now_v7().to_string(),
+            event_type: T::TYPE.to_string(),
+            version: T::VERSION,
+            occurred_at: Utc::now(),
+            actor: None,
+            context: None,
+            payload,
+        }
+    }
+
+    /// Sets a deterministic id, e.g. derived from the mutation, so retried
+    /// publishes are deduplicated.
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn with_context(mut self, context: InfraContext) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    pub fn meta(&self) -> EventMeta {
+        EventMeta {
+            id: self.id.clone(),
+            event_type: self.event_type.clone(),
+            version: self.version,
+        }
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(MSG_ID, self.id.as_str());
+        headers.insert(EVENT_TYPE, self.event_type.as_str());
+        headers.insert(EVENT_VERSION, self.version.to_string().as_str());
+        headers
+    }
+
+    /// Publishes to JetStream and waits for the acknowledgement of the stream.
+    pub async fn publish(
+        &self,
+        js: &jetstream::Context,
+        subject: impl ToSubject,
+    ) -> anyhow::Result<PublishAck> {
+        let payload = serde_json::to_vec(self)?;
+        let ack = js
+            .publish_with_headers(subject, self.headers(), payload.into())
+            .await?;
+        Ok(ack.await?)
+    }
+
+    /// Publishes with core NATS, without persistence and deduplication.
+    pub async fn publish_core(
+        &self,
+        client: &async_nats::Client,
+        subject: impl ToSubject,
+    ) -> anyhow::Result<()> {
+        let payload = serde_json::to_vec(self)?;
+        client
+            .publish_with_headers(subject, self.headers(), payload.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Decodes an envelope of `T`, events of other types are skipped with
+    /// `None`. Older versions are upgraded with [`Event::upgrade`], newer
+    /// versions are rejected.
+    pub fn decode(headers: Option<&HeaderMap>, payload: &[u8]) -> anyhow::Result<Option<Self>> {
+        if EventMeta::from_headers(headers).is_some_and(|meta| !meta.is::<T>()) {
+            return Ok(None);
+        }
+        let envelope: EventEnvelope<serde_json::Value> = serde_json::from_slice(payload)?;
+        if envelope.event_type != T::TYPE {
+            return Ok(None);
+        }
+        if envelope.version > T::VERSION {
+            anyhow::bail!(
+                "unsupported version {} of {}, latest known version is {}",
+                envelope.version,
+                T::TYPE,
+                T::VERSION
+            );
+        }
+        let mut payload = envelope.payload;
+        for version in envelope.version..T::VERSION {
+            payload = T::upgrade(version, payload)?;
+        }
+        Ok(Some(EventEnvelope {
+            id: envelope.id,
+            event_type: envelope.event_type,
+            version: T::VERSION,
+            occurred_at: envelope.occurred_at,
+            actor: envelope.actor,
+            context: envelope.context,
+            payload: serde_json::from_value(payload)?,
+        }))
+    }
+
+    pub fn from_message(message: &async_nats::Message) -> anyhow::Result<Option<Self>> {
+        Self::decode(message.headers.as_ref(), &message.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Renamed {
+        name: String,
+    }
+
+    impl Event for Renamed {
+        const TYPE: &'static str = "test.renamed";
+        const VERSION: u32 = 2;
+
+        fn upgrade(
+            version: u32,
+            mut payload: serde_json::Value,
+        ) -> anyhow::Result<serde_json::Value> {
+            anyhow::ensure!(version == 1, "unknown version {version}");
+            let title = payload["title"].take();
+            Ok(serde_json::json!({ "name": title }))
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Other;
+
+    impl Event for Other {
+        const TYPE: &'static str = "test.other";
+    }
+
+    #[test]
+    fn test_round_trip() -> anyhow::Result<()> {
+        let context: InfraContext = "V01".parse()?;
+        let envelope = EventEnvelope::new(Renamed { name: "a".into() })
+            .with_actor("user")
+            .with_context(context);
+        let headers = envelope.headers();
+        let payload = serde_json::to_vec(&envelope)?;
+        assert_eq!(
+            EventMeta::from_headers(Some(&headers)),
+            Some(envelope.meta())
+        );
+
+        let decoded = EventEnvelope::<Renamed>::decode(Some(&headers), &payload)?.unwrap();
+        assert_eq!(decoded.id, envelope.id);
+        assert_eq!(decoded.context, Some(context));
+        assert_eq!(decoded.payload, envelope.payload);
+        assert!(EventEnvelope::<Other>::decode(Some(&headers), &payload)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_versions() -> anyhow::Result<()> {
+        let v1 = br#"{"id":"1","type":"test.renamed","version":1,"occurredAt":"2024-01-01T00:00:00Z","payload":{"title":"a"}}"#;
+        let decoded = EventEnvelope::<Renamed>::decode(None, v1)?.unwrap();
+        assert_eq!(decoded.version, 2);
+        assert_eq!(decoded.payload, Renamed { name: "a".into() });
+
+        let v3 = br#"{"id":"1","type":"test.renamed","version":3,"occurredAt":"2024-01-01T00:00:00Z","payload":{}}"#;
+        assert!(EventEnvelope::<Renamed>::decode(None, v3).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,10 @@
+pub mod config;
+pub mod dedupe;
+pub mod envelope;
+pub mod schedule;
+pub mod watcher;
+
+pub use dedupe::{Dedupe, Outcome};
+pub use envelope::{Event, EventEnvelope, EventMeta};
+pub use schedule::{Publisher, Scheduler};
+pub use watcher::ConfigWatcher;
@@ -0,0 +1,334 @@
+//! Publishing of messages at a later time.
+//!
+//! [`Publisher::publish_at`] stores the message in a scheduler stream with
+//! the target subject and due time in the [`SCHEDULE_TARGET`] and
+//! [`SCHEDULE_AT`] headers. A [`Scheduler`] consumes that stream and
+//! rejects messages which are not due yet with a delayed Nak, so JetStream
+//! redelivers them at the due time. Due messages are published to their
+//! target and acknowledged, events like "subscription expires" need no
+//! extra cron service.
+//!
+//! The scheduler runs in any number of instances, JetStream delivers each
+//! message to one of them. Messages are forwarded with a [`MSG_ID`] header,
+//! so a crash between forwarding and acknowledging doesn't publish twice
+//! within the duplicate window of the target stream.
+//!
+//! Messages waiting for their due time stay pending on the consumer, at most
+//! [`Scheduler::with_max_ack_pending`] messages can wait at once. Once the
+//! limit is reached JetStream delivers no further messages, including due
+//! ones, until a pending message is published. The limit must therefore be
+//! larger than the number of messages scheduled ahead at any time.
+
+use std::time::Duration;
+
+use async_nats::{
+    jetstream::{self, consumer::pull, publish::PublishAck, AckKind},
+    HeaderMap,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+
+use crate::envelope::MSG_ID;
+
+pub const SCHEDULE_TARGET: &str = "Qm-Schedule-Target";
+pub const SCHEDULE_AT: &str = "Qm-Schedule-At";
+
+pub const DEFAULT_SCHEDULE_STREAM: &str = "QM_SCHEDULE";
+pub const DEFAULT_SCHEDULE_SUBJECT: &str = "qm.schedule";
+/// Default of [`Scheduler::with_max_ack_pending`], JetStream defaults to
+/// 1000 which is easily reached by messages scheduled far ahead.
+pub const DEFAULT_MAX_ACK_PENDING: i64 = 100_000;
+
+/// Target and due time of a scheduled message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub subject: String,
+    pub at: DateTime<Utc>,
+}
+
+impl Schedule {
+    pub fn from_headers(headers: Option<&HeaderMap>) -> anyhow::Result<Self> {
+        let header = |name| {
+            headers
+                .and_then(|headers| headers.get(name))
+                .map(|value| value.as_str())
+                .ok_or_else(|| anyhow::anyhow!("missing header {name}"))
+        };
+        Ok(Self {
+            subject: header(SCHEDULE_TARGET)?.to_string(),
+            at: DateTime::parse_from_rfc3339(header(SCHEDULE_AT)?)?.with_timezone(&Utc),
+        })
+    }
+
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(SCHEDULE_TARGET, self.subject.as_str());
+        headers.insert(SCHEDULE_AT, self.at.to_rfc3339().as_str());
+    }
+
+    /// Time until the message is due, `None` if it is due.
+    pub fn delay(&self, now: DateTime<Utc>) -> Option<Duration> {
+        (self.at - now)
+            .to_std()
+            .ok()
+            .filter(|delay| !delay.is_zero())
+    }
+}
+
+/// Publishes messages to JetStream, immediately or at a later time.
+#[derive(Clone)]
+pub struct Publisher {
+    js: jetstream::Context,
+    schedule_subject: String,
+}
+
+impl Publisher {
+    pub fn new(js: jetstream::Context) -> Self {
+        Self {
+            js,
+            schedule_subject: DEFAULT_SCHEDULE_SUBJECT.to_string(),
+        }
+    }
+
+    /// Subject prefix of the scheduler stream, see [`Scheduler::with_subject`].
+    pub fn with_schedule_subject(mut self, subject: impl Into<String>) -> Self {
+        self.schedule_subject = subject.into();
+        self
+    }
+
+    pub fn context(&self) -> &jetstream::Context {
+        &self.js
+    }
+
+    pub async fn publish(&self, subject: &str, payload: Bytes) -> anyhow::Result<PublishAck> {
+        self.publish_with_headers(subject, HeaderMap::new(), payload)
+            .await
+    }
+
+    /// Publishes and waits for the acknowledgement of the stream.
+    pub async fn publish_with_headers(
+        &self,
+        subject: &str,
+        headers: HeaderMap,
+        payload: Bytes,
+    ) -> anyhow::Result<PublishAck> {
+        let ack = self
+            .js
+            .publish_with_headers(subject.to_string(), headers, payload)
+            .await?;
+        Ok(ack.await?)
+    }
+
+    /// Publishes `payload` to `subject` at `at`, immediately if it is due.
+    pub async fn publish_at(
+        &self,
+        subject: &str,
+        payload: Bytes,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<PublishAck> {
+        self.publish_at_with_headers(subject, HeaderMap::new(), payload, at)
+            .await
+    }
+
+    /// Like [`Publisher::publish_at`], `headers` are published with the
+    /// message, e.g. those of an [`crate::EventEnvelope`].
+    pub async fn publish_at_with_headers(
+        &self,
+        subject: &str,
+        mut headers: HeaderMap,
+        payload: Bytes,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<PublishAck> {
+        let schedule = Schedule {
+            subject: subject.to_string(),
+            at,
+        };
+        if schedule.delay(Utc::now()).is_none() {
+            return self.publish_with_headers(subject, headers, payload).await;
+        }
+        schedule.insert_headers(&mut headers);
+        let subject = format!("{}.{subject}", self.schedule_subject);
+        self.publish_with_headers(&subject, headers, payload).await
+    }
+}
+
+/// Consumer of the scheduler stream which publishes due messages.
+pub struct Scheduler {
+    js: jetstream::Context,
+    stream: String,
+    subject: String,
+    consumer: String,
+    max_delay: Duration,
+    max_ack_pending: i64,
+}
+
+impl Scheduler {
+    pub fn new(js: jetstream::Context) -> Self {
+        Self {
+            js,
+            stream: DEFAULT_SCHEDULE_STREAM.to_string(),
+            subject: DEFAULT_SCHEDULE_SUBJECT.to_string(),
+            consumer: "scheduler".to_string(),
+            max_delay: Duration::from_secs(3600),
+            max_ack_pending: DEFAULT_MAX_ACK_PENDING,
+        }
+    }
+
+    pub fn with_stream(mut self, stream: impl Into<String>) -> Self {
+        self.stream = stream.into();
+        self
+    }
+
+    /// Subject prefix of scheduled messages, `<subject>.<target>`.
+    pub fn with_subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = subject.into();
+        self
+    }
+
+    /// Durable name of the consumer shared by all instances.
+    pub fn with_consumer(mut self, consumer: impl Into<String>) -> Self {
+        self.consumer = consumer.into();
+        self
+    }
+
+    /// Longest delay of a redelivery, messages due later are redelivered
+    /// several times.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Maximum number of messages waiting for their due time, `-1` removes
+    /// the limit of the consumer. The limit of the stream or account still
+    /// applies.
+    pub fn with_max_ack_pending(mut self, max_ack_pending: i64) -> Self {
+        self.max_ack_pending = max_ack_pending;
+        self
+    }
+
+    /// Creates the scheduler stream if it doesn't exist.
+    pub async fn setup(&self) -> anyhow::Result<jetstream::stream::Stream> {
+        Ok(self
+            .js
+            .get_or_create_stream(jetstream::stream::Config {
+                name: self.stream.clone(),
+                subjects: vec![format!("{}.>", self.subject)],
+                ..Default::default()
+            })
+            .await?)
+    }
+
+    /// Publishes due messages until the consumer is closed, the consumer is
+    /// created or updated with the configured limits.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let stream = self.setup().await?;
+        let consumer: jetstream::consumer::PullConsumer = stream
+            .create_consumer(pull::Config {
+                durable_name: Some(self.consumer.clone()),
+                ack_policy: jetstream::consumer::AckPolicy::Explicit,
+                max_ack_pending: self.max_ack_pending,
+                ..Default::default()
+            })
+            .await?;
+        let mut messages = consumer.messages().await?;
+        while let Some(message) = messages.next().await {
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    tracing::error!("unable to receive scheduled message: {err:#}");
+                    continue;
+                }
+            };
+            if let Err(err) = self.process(&message).await {
+                tracing::error!("unable to publish scheduled message: {err:#}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn process(&self, message: &jetstream::Message) -> anyhow::Result<()> {
+        let schedule = match Schedule::from_headers(message.headers.as_ref()) {
+            Ok(schedule) => schedule,
+            Err(err) => {
+                tracing::warn!(
+                    "dropping invalid scheduled message on {}: {err:#}",
+                    message.subject
+                );
+                return message
+                    .ack_with(AckKind::Term)
+                    .await
+                    .map_err(|err| anyhow::anyhow!(err));
+            }
+        };
+        if let Some(delay) = schedule.delay(Utc::now()) {
+            return message
+                .ack_with(AckKind::Nak(Some(delay.min(self.max_delay))))
+                .await
+                .map_err(|err| anyhow::anyhow!(err));
+        }
+        let sequence = message
+            .info()
+            .map_err(|err| anyhow::anyhow!(err))?
+            .stream_sequence;
+        let headers = forward_headers(message.headers.as_ref(), &self.stream, sequence);
+        self.js
+            .publish_with_headers(schedule.subject, headers, message.payload.clone())
+            .await?
+            .await?;
+        message.ack().await.map_err(|err| anyhow::anyhow!(err))
+    }
+}
+
+/// Headers of the published message, without the schedule headers and with
+/// a message id derived from the scheduled message if it had none.
+fn forward_headers(headers: Option<&HeaderMap>, stream: &str, sequence: u64) -> HeaderMap {
+    let mut forward = HeaderMap::new();
+    for (name, values) in headers.into_iter().flat_map(HeaderMap::iter) {
+        let name: &str = name.as_ref();
+        if name == SCHEDULE_TARGET || name == SCHEDULE_AT {
+            continue;
+        }
+        for value in values {
+            forward.append(name, value.as_str());
+        }
+    }
+    if forward.get(MSG_ID).is_none() {
+        forward.insert(MSG_ID, format!("{stream}-{sequence}").as_str());
+    }
+    forward
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() -> anyhow::Result<()> {
+        let now = Utc::now();
+        let schedule = Schedule {
+            subject: "subscription.expired".to_string(),
+            at: now + chrono::Duration::minutes(5),
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("Qm-Event-Type", "subscription.expired");
+        schedule.insert_headers(&mut headers);
+
+        let decoded = Schedule::from_headers(Some(&headers))?;
+        assert_eq!(decoded.subject, schedule.subject);
+        assert_eq!(decoded.delay(now), Some(Duration::from_secs(300)));
+        assert_eq!(decoded.delay(now + chrono::Duration::minutes(6)), None);
+        assert!(Schedule::from_headers(None).is_err());
+
+        let forward = forward_headers(Some(&headers), "QM_SCHEDULE", 7);
+        assert!(forward.get(SCHEDULE_AT).is_none());
+        assert_eq!(
+            forward.get("Qm-Event-Type").map(|v| v.as_str()),
+            Some("subscription.expired")
+        );
+        assert_eq!(
+            forward.get(MSG_ID).map(|v| v.as_str()),
+            Some("QM_SCHEDULE-7")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Distributed configuration in a JetStream key value bucket.
+//!
+//! A [`ConfigWatcher`] keeps the value of one key deserialized in an
+//! [`ArcSwap`] and reloads it whenever the key changes, so settings like log
+//! levels or limits can be changed for all instances without a redeploy.
+//! Values which can not be deserialized are logged and ignored, deleted keys
+//! fall back to the default value.
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use async_nats::jetstream::{
+    self,
+    kv::{self, Operation},
+};
+use futures::StreamExt;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::task::JoinHandle;
+
+pub struct ConfigWatcher<T> {
+    kv: kv::Store,
+    key: String,
+    value: Arc<ArcSwap<T>>,
+    handle: JoinHandle<()>,
+}
+
+impl<T> ConfigWatcher<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    /// Opens `bucket`, creating it if required, loads `key` and watches it
+    /// for changes. `default` is used while the key is not set.
+    pub async fn open(
+        js: &jetstream::Context,
+        bucket: &str,
+        key: &str,
+        default: T,
+    ) -> anyhow::Result<Self> {
+        let kv = match js.get_key_value(bucket).await {
+            Ok(kv) => kv,
+            Err(_) => {
+                js.create_key_value(kv::Config {
+                    bucket: bucket.to_string(),
+                    history: 1,
+                    ..Default::default()
+                })
+                .await?
+            }
+        };
+        Self::new(kv, key, default).await
+    }
+
+    pub async fn new(kv: kv::Store, key: &str, default: T) -> anyhow::Result<Self> {
+        let default = Arc::new(default);
+        let mut watch = kv.watch_with_history(key).await?;
+        let current = match kv.get(key).await? {
+            Some(value) => decode(key, &value).unwrap_or_else(|| default.clone()),
+            None => default.clone(),
+        };
+        let value = Arc::new(ArcSwap::new(current));
+        let handle = tokio::spawn({
+            let key = key.to_string();
+            let value = value.clone();
+            async move {
+                while let Some(entry) = watch.next().await {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(err) => {
+                            tracing::error!("unable to watch config '{key}': {err:#}");
+                            continue;
+                        }
+                    };
+                    let next = match entry.operation {
+                        Operation::Put => decode(&key, &entry.value),
+                        Operation::Delete | Operation::Purge => Some(default.clone()),
+                    };
+                    if let Some(next) = next {
+                        tracing::info!("reloaded config '{key}' at revision {}", entry.revision);
+                        value.store(next);
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            kv,
+            key: key.to_string(),
+            value,
+            handle,
+        })
+    }
+
+    /// Current value of the configuration.
+    pub fn current(&self) -> Arc<T> {
+        self.value.load_full()
+    }
+
+    /// Stores `value` in the bucket, all watchers including this one reload
+    /// it from the watch events.
+    pub async fn put(&self, value: &T) -> anyhow::Result<u64> {
+        let bytes = serde_json::to_vec(value)?;
+        Ok(self.kv.put(&self.key, bytes.into()).await?)
+    }
+
+    /// Removes the value from the bucket, watchers fall back to the default.
+    pub async fn reset(&self) -> anyhow::Result<()> {
+        self.kv.delete(&self.key).await?;
+        Ok(())
+    }
+}
+
+impl<T> Drop for ConfigWatcher<T> {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+fn decode<T: DeserializeOwned>(key: &str, value: &[u8]) -> Option<Arc<T>> {
+    match serde_json::from_slice(value) {
+        Ok(value) => Some(Arc::new(value)),
+        Err(err) => {
+            tracing::warn!("invalid config '{key}', keeping the current value: {err:#}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Limits {
+        log_level: String,
+        max_requests: u32,
+    }
+
+    #[test]
+    fn test_decode() {
+        let value = decode::<Limits>("limits", br#"{"log_level":"debug","max_requests":10}"#);
+        assert_eq!(
+            value.as_deref(),
+            Some(&Limits {
+                log_level: "debug".to_string(),
+                max_requests: 10,
+            })
+        );
+        assert!(decode::<Limits>("limits", br#"{"log_level":"debug"}"#).is_none());
+    }
+}
@@ -1,6 +1,23 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use serde::Deserialize;
 use std::sync::Arc;
 
+use qm_utils::secret::SecretSource;
+
+use crate::ddl::Access;
+
+/// Fields which are resolved with the [`SecretSource`].
+const SECRETS: [&str; 2] = ["PASSWORD", "ROOT_PASSWORD"];
+
+/// Characters of credentials and database names which are percent-encoded in
+/// the connection urls, everything except the unreserved characters of
+/// RFC 3986.
+const URL_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
 #[derive(Deserialize)]
 pub struct Config {
     host: Option<Arc<str>>,
@@ -16,10 +33,17 @@ pub struct Config {
     root_username: Option<Arc<str>>,
     root_password: Option<Arc<str>>,
     root_database: Option<Arc<str>>,
+    superuser: Option<bool>,
+    replica_host: Option<Arc<str>>,
+    replica_port: Option<u16>,
+    replica_max_connections: Option<u32>,
+    access: Option<Access>,
     #[serde(skip)]
     address: Option<Arc<str>>,
     #[serde(skip)]
     root_address: Option<Arc<str>>,
+    #[serde(skip)]
+    replica_address: Option<Arc<str>>,
 }
 
 impl Config {
@@ -67,6 +91,16 @@ impl Config {
         self.root_database.as_deref()
     }
 
+    /// Whether `ensure` creates the user as superuser, defaults to `true`.
+    pub fn superuser(&self) -> bool {
+        self.superuser.unwrap_or(true)
+    }
+
+    /// Privileges `ensure` grants to a non-superuser on its database.
+    pub fn access(&self) -> Access {
+        self.access.unwrap_or_default()
+    }
+
     pub fn address(&self) -> &str {
         self.address.as_deref().unwrap()
     }
@@ -74,11 +108,22 @@ impl Config {
     pub fn root_address(&self) -> &str {
         self.root_address.as_deref().unwrap()
     }
+
+    /// Address of the read replica, set if `replica_host` is configured.
+    pub fn replica_address(&self) -> Option<&str> {
+        self.replica_address.as_deref()
+    }
+
+    pub fn replica_max_connections(&self) -> u32 {
+        self.replica_max_connections
+            .unwrap_or_else(|| self.max_connections())
+    }
 }
 
 #[derive(Default)]
 pub struct ConfigBuilder<'a> {
     prefix: Option<&'a str>,
+    secrets: Option<SecretSource>,
 }
 
 impl<'a> ConfigBuilder<'a> {
@@ -87,40 +132,121 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
+    /// Source of the secrets, [`SecretSource::from_env`] by default.
+    pub fn with_secrets(mut self, secrets: SecretSource) -> Self {
+        self.secrets = Some(secrets);
+        self
+    }
+
     pub fn build(self) -> envy::Result<Config> {
-        let mut cfg: Config = if let Some(prefix) = self.prefix {
-            envy::prefixed(prefix)
-        } else {
-            envy::prefixed("PG_")
-        }
-        .from_env()?;
+        let prefix = self.prefix.unwrap_or("PG_");
+        let vars = self
+            .secrets
+            .unwrap_or_else(SecretSource::from_env)
+            .vars(prefix, &SECRETS)
+            .map_err(|err| envy::Error::Custom(format!("{err:#}")))?;
+        let mut cfg: Config = envy::prefixed(prefix).from_iter(vars)?;
         let host = cfg.host.as_deref().unwrap_or("127.0.0.1");
         let port = cfg.port.unwrap_or(27017);
-        let mut address = match (cfg.username.as_deref(), cfg.password.as_deref()) {
-            (Some(username), Some(password)) => {
-                format!("postgresql://{}:{}@{}:{}/", username, password, host, port)
-            }
-            (Some(username), None) => format!("postgresql://{}@{}:{}/", username, host, port),
-            _ => format!("postgresql://{}:{}/", host, port),
-        };
-        if let Some(database) = cfg.database.as_deref() {
-            address.push_str(database);
+        let address = user_address(&cfg, host, port);
+        if let Some(replica_host) = cfg.replica_host.as_deref() {
+            let replica_port = cfg.replica_port.unwrap_or(port);
+            cfg.replica_address = Some(Arc::from(user_address(&cfg, replica_host, replica_port)));
         }
         cfg.address = Some(Arc::from(address));
-        let mut root_address = match (cfg.root_username.as_deref(), cfg.root_password.as_deref()) {
-            (Some(root_username), Some(root_password)) => format!(
-                "postgresql://{}:{}@{}:{}/",
-                root_username, root_password, host, port
-            ),
-            (Some(root_username), None) => {
-                format!("postgresql://{}@{}:{}/", root_username, host, port)
-            }
-            _ => format!("postgresql://{}:{}/", host, port),
-        };
-        if let Some(root_database) = cfg.root_database.as_deref() {
-            root_address.push_str(root_database);
-        }
-        cfg.root_address = Some(Arc::from(root_address));
+        cfg.root_address = Some(Arc::from(connection_url(
+            cfg.root_username.as_deref(),
+            cfg.root_password.as_deref(),
+            host,
+            port,
+            cfg.root_database.as_deref(),
+        )));
         Ok(cfg)
     }
 }
+
+fn user_address(cfg: &Config, host: &str, port: u16) -> String {
+    connection_url(
+        cfg.username.as_deref(),
+        cfg.password.as_deref(),
+        host,
+        port,
+        cfg.database.as_deref(),
+    )
+}
+
+fn connection_url(
+    username: Option<&str>,
+    password: Option<&str>,
+    host: &str,
+    port: u16,
+    database: Option<&str>,
+) -> String {
+    let encode = |v| utf8_percent_encode(v, URL_ENCODE);
+    let mut address = match (username, password) {
+        (Some(username), Some(password)) => format!(
+            "postgresql://{}:{}@{}:{}/",
+            encode(username),
+            encode(password),
+            host,
+            port
+        ),
+        (Some(username), None) => {
+            format!("postgresql://{}@{}:{}/", encode(username), host, port)
+        }
+        _ => format!("postgresql://{}:{}/", host, port),
+    };
+    if let Some(database) = database {
+        address.extend(encode(database));
+    }
+    address
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_replica_config_test() -> envy::Result<()> {
+        std::env::set_var("REPLICA_PG_HOST", "primary");
+        std::env::set_var("REPLICA_PG_PORT", "5432");
+        std::env::set_var("REPLICA_PG_USERNAME", "user");
+        std::env::set_var("REPLICA_PG_PASSWORD", "pw");
+        std::env::set_var("REPLICA_PG_DATABASE", "app");
+        std::env::set_var("REPLICA_PG_REPLICA_HOST", "replica");
+        let cfg = super::Config::builder()
+            .with_prefix("REPLICA_PG_")
+            .build()?;
+        assert_eq!(cfg.address(), "postgresql://user:pw@primary:5432/app");
+        assert_eq!(
+            cfg.replica_address(),
+            Some("postgresql://user:pw@replica:5432/app")
+        );
+        assert_eq!(cfg.replica_max_connections(), cfg.max_connections());
+        let cfg = super::Config::builder()
+            .with_prefix("NO_REPLICA_PG_")
+            .build()?;
+        assert_eq!(cfg.replica_address(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn encode_credentials_test() {
+        assert_eq!(
+            super::connection_url(
+                Some("app user"),
+                Some("p@ss:w/rd%#?"),
+                "db",
+                5432,
+                Some("app")
+            ),
+            "postgresql://app%20user:p%40ss%3Aw%2Frd%25%23%3F@db:5432/app"
+        );
+        assert_eq!(
+            super::connection_url(Some("root"), None, "db", 5432, None),
+            "postgresql://root@db:5432/"
+        );
+        assert_eq!(
+            super::connection_url(None, Some("pw"), "db", 5432, Some("a.b-c_d~e")),
+            "postgresql://db:5432/a.b-c_d~e"
+        );
+    }
+}
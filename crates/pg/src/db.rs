@@ -1,11 +1,30 @@
 use crate::config::Config;
+use futures::future::BoxFuture;
+use qm_utils::retry::{retry_if, RetryPolicy};
+use sqlx::migrate::Migrator;
 use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+/// Default number of attempts of [`DB::transaction`].
+pub const DEFAULT_TRANSACTION_ATTEMPTS: u32 = 3;
+
+/// SQLSTATE codes of errors after which a transaction can be retried:
+/// `serialization_failure` and `deadlock_detected`.
+const RETRYABLE_CODES: &[&str] = &["40001", "40P01"];
+
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub latency: Duration,
+    pub server_version: String,
+    pub pool_size: u32,
+    pub idle_connections: usize,
+}
 
 struct Inner {
     pool: PgPool,
+    replica: Option<PgPool>,
 }
 
 #[derive(Clone)]
@@ -34,8 +53,26 @@ impl DB {
             .max_lifetime(Duration::from_secs(cfg.max_lifetime()))
             .connect(cfg.address())
             .await?;
+        let replica = if let Some(replica_address) = cfg.replica_address() {
+            tracing::info!(
+                "'{app_name}' -> connects to postgresql read replica with {} max_connections",
+                cfg.replica_max_connections(),
+            );
+            Some(
+                PgPoolOptions::new()
+                    .min_connections(cfg.min_connections())
+                    .max_connections(cfg.replica_max_connections())
+                    .acquire_timeout(Duration::from_secs(cfg.acquire_timeout()))
+                    .idle_timeout(Duration::from_secs(cfg.idle_timeout()))
+                    .max_lifetime(Duration::from_secs(cfg.max_lifetime()))
+                    .connect(replica_address)
+                    .await?,
+            )
+        } else {
+            None
+        };
         Ok(Self {
-            inner: Arc::new(Inner { pool }),
+            inner: Arc::new(Inner { pool, replica }),
         })
     }
 
@@ -58,7 +95,10 @@ impl DB {
             .connect(cfg.root_address())
             .await?;
         Ok(Self {
-            inner: Arc::new(Inner { pool }),
+            inner: Arc::new(Inner {
+                pool,
+                replica: None,
+            }),
         })
     }
 
@@ -69,4 +109,176 @@ impl DB {
     pub fn pool(&self) -> &PgPool {
         &self.inner.pool
     }
+
+    /// Pool for queries which tolerate replication lag, the read replica if
+    /// configured, otherwise the primary.
+    pub fn read(&self) -> &PgPool {
+        self.inner.replica.as_ref().unwrap_or(&self.inner.pool)
+    }
+
+    /// Pool of the primary.
+    pub fn write(&self) -> &PgPool {
+        &self.inner.pool
+    }
+
+    pub fn has_replica(&self) -> bool {
+        self.inner.replica.is_some()
+    }
+
+    /// Runs all pending migrations of `migrator`, migrations applied by other
+    /// migrators in the same database are ignored.
+    ///
+    /// ```ignore
+    /// db.run_migrations(sqlx::migrate!("./migrations")).await?;
+    /// ```
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "pg.run_migrations", skip_all)
+    )]
+    pub async fn run_migrations(&self, mut migrator: Migrator) -> anyhow::Result<()> {
+        migrator.set_ignore_missing(true);
+        migrator.run(self.pool()).await?;
+        Ok(())
+    }
+
+    /// Reverts all migrations of `migrator`.
+    pub async fn undo_migrations(&self, mut migrator: Migrator) -> anyhow::Result<()> {
+        migrator.set_ignore_missing(true);
+        migrator.undo(self.pool(), 0).await?;
+        Ok(())
+    }
+
+    /// Runs `f` in a transaction and commits it, the transaction is retried
+    /// up to [`DEFAULT_TRANSACTION_ATTEMPTS`] times on serialization failures
+    /// and deadlocks.
+    ///
+    /// ```ignore
+    /// let id = db
+    ///     .transaction(|tx| {
+    ///         Box::pin(async move {
+    ///             let id: i64 = sqlx::query_scalar("INSERT ... RETURNING id")
+    ///                 .fetch_one(&mut **tx)
+    ///                 .await?;
+    ///             Ok(id)
+    ///         })
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn transaction<F, T>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: for<'c> Fn(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, anyhow::Result<T>>,
+    {
+        self.transaction_with_attempts(DEFAULT_TRANSACTION_ATTEMPTS, f)
+            .await
+    }
+
+    #[cfg_attr(
+        feature = "otel",
+        tracing::instrument(name = "pg.transaction", skip_all, fields(attempts))
+    )]
+    pub async fn transaction_with_attempts<F, T>(&self, attempts: u32, f: F) -> anyhow::Result<T>
+    where
+        F: for<'c> Fn(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, anyhow::Result<T>>,
+    {
+        let policy =
+            RetryPolicy::exponential(Duration::from_millis(20)).with_max_attempts(attempts);
+        let f = &f;
+        retry_if(&policy, is_retryable, || async move {
+            let mut tx = self.pool().begin().await?;
+            match f(&mut tx).await {
+                Ok(value) => Ok(tx.commit().await.map(|_| value)?),
+                Err(err) => {
+                    tx.rollback().await.ok();
+                    Err(err)
+                }
+            }
+        })
+        .await
+    }
+
+    /// Sends a query to the server and returns the round trip time.
+    pub async fn ping(&self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        sqlx::query("SELECT 1").execute(self.pool()).await?;
+        Ok(start.elapsed())
+    }
+
+    pub async fn health(&self) -> anyhow::Result<Health> {
+        let start = Instant::now();
+        let server_version: String = sqlx::query_scalar("SHOW server_version")
+            .fetch_one(self.pool())
+            .await?;
+        Ok(Health {
+            latency: start.elapsed(),
+            server_version,
+            pool_size: self.pool().size(),
+            idle_connections: self.pool().num_idle(),
+        })
+    }
+}
+
+fn is_retryable(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        cause
+            .downcast_ref::<sqlx::Error>()
+            .and_then(|err| err.as_database_error())
+            .and_then(|err| err.code())
+            .is_some_and(|code| RETRYABLE_CODES.contains(&code.as_ref()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[derive(Debug)]
+    struct CodeError(&'static str);
+
+    impl std::fmt::Display for CodeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "database error {}", self.0)
+        }
+    }
+
+    impl std::error::Error for CodeError {}
+
+    impl sqlx::error::DatabaseError for CodeError {
+        fn message(&self) -> &str {
+            self.0
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.0))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> sqlx::error::ErrorKind {
+            sqlx::error::ErrorKind::Other
+        }
+    }
+
+    fn database_error(code: &'static str) -> anyhow::Error {
+        sqlx::Error::Database(Box::new(CodeError(code))).into()
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&database_error("40001")));
+        assert!(is_retryable(&database_error("40P01").context("insert")));
+        assert!(!is_retryable(&database_error("23505")));
+        assert!(!is_retryable(&anyhow::anyhow!("failed")));
+        assert!(!is_retryable(&sqlx::Error::PoolTimedOut.into()));
+    }
 }
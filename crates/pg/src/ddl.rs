@@ -0,0 +1,101 @@
+use serde::Deserialize;
+
+/// Privileges granted to the application user on its database.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    ReadOnly,
+    #[default]
+    ReadWrite,
+}
+
+/// Quotes `name` as an SQL identifier, e.g. `my"db` becomes `"my""db"`.
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quotes `value` as an SQL string literal, e.g. `it's` becomes `'it''s'`.
+pub fn quote_literal(value: &str) -> String {
+    if value.contains('\\') {
+        format!("E'{}'", value.replace('\\', "\\\\").replace('\'', "''"))
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
+
+pub(crate) fn create_database(database: &str) -> String {
+    format!("CREATE DATABASE {}", quote_ident(database))
+}
+
+pub(crate) fn create_user(username: &str, password: &str, superuser: bool) -> String {
+    format!(
+        "CREATE USER {} WITH {}LOGIN PASSWORD {}",
+        quote_ident(username),
+        if superuser { "SUPERUSER " } else { "" },
+        quote_literal(password)
+    )
+}
+
+/// Statements granting `access` on the `public` schema of `database`, they
+/// have to be executed while connected to `database`.
+pub(crate) fn grants(database: &str, username: &str, access: Access) -> Vec<String> {
+    let database = quote_ident(database);
+    let username = quote_ident(username);
+    let (schema, tables, sequences) = match access {
+        Access::ReadOnly => ("USAGE", "SELECT", "SELECT"),
+        Access::ReadWrite => (
+            "USAGE, CREATE",
+            "SELECT, INSERT, UPDATE, DELETE, TRUNCATE, REFERENCES, TRIGGER",
+            "USAGE, SELECT, UPDATE",
+        ),
+    };
+    let mut statements = vec![
+        format!("GRANT CONNECT ON DATABASE {database} TO {username}"),
+        format!("GRANT {schema} ON SCHEMA public TO {username}"),
+        format!("GRANT {tables} ON ALL TABLES IN SCHEMA public TO {username}"),
+        format!("GRANT {sequences} ON ALL SEQUENCES IN SCHEMA public TO {username}"),
+        format!("ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {tables} ON TABLES TO {username}"),
+        format!(
+            "ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT {sequences} ON SEQUENCES TO {username}"
+        ),
+    ];
+    if access == Access::ReadWrite {
+        statements.insert(
+            1,
+            format!("GRANT TEMPORARY ON DATABASE {database} TO {username}"),
+        );
+    }
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote_ident("app"), "\"app\"");
+        assert_eq!(quote_ident("my\"db; DROP"), "\"my\"\"db; DROP\"");
+        assert_eq!(quote_literal("pw"), "'pw'");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+        assert_eq!(quote_literal("a\\'b"), "E'a\\\\''b'");
+    }
+
+    #[test]
+    fn test_statements() {
+        assert_eq!(
+            create_user("app-user", "p'w", false),
+            "CREATE USER \"app-user\" WITH LOGIN PASSWORD 'p''w'"
+        );
+        assert_eq!(
+            create_user("app", "pw", true),
+            "CREATE USER \"app\" WITH SUPERUSER LOGIN PASSWORD 'pw'"
+        );
+        let grants = grants("db", "reader", Access::ReadOnly);
+        assert_eq!(grants.len(), 6);
+        assert_eq!(
+            grants[2],
+            "GRANT SELECT ON ALL TABLES IN SCHEMA public TO \"reader\""
+        );
+    }
+}
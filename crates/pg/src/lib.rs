@@ -1,10 +1,17 @@
 mod config;
 mod db;
+mod ddl;
+mod listen;
 
-use sqlx::Executor;
+use std::str::FromStr;
+
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{Connection, Executor, PgConnection};
 
 pub use crate::config::Config as DbConfig;
-pub use crate::db::DB;
+pub use crate::db::{Health, DB, DEFAULT_TRANSACTION_ATTEMPTS};
+pub use crate::ddl::{quote_ident, quote_literal, Access};
+pub use sqlx::postgres::PgNotification as Notification;
 
 pub async fn ensure(app_name: &str, cfgs: &[&DbConfig]) -> anyhow::Result<()> {
     for cfg in cfgs {
@@ -16,7 +23,7 @@ pub async fn ensure(app_name: &str, cfgs: &[&DbConfig]) -> anyhow::Result<()> {
         if let Some(database) = cfg.database() {
             if !databases.iter().any(|d| d.datname == database) {
                 db.pool()
-                    .execute(format!("CREATE DATABASE {database}").as_str())
+                    .execute(ddl::create_database(database).as_str())
                     .await?;
             }
         }
@@ -27,13 +34,57 @@ pub async fn ensure(app_name: &str, cfgs: &[&DbConfig]) -> anyhow::Result<()> {
                 .await?;
             if !users.iter().any(|u| u.usename.as_deref() == Some(username)) {
                 db.pool()
-                    .execute(
-                        format!("CREATE USER {username} WITH SUPERUSER PASSWORD '{password}'")
-                            .as_str(),
-                    )
+                    .execute(ddl::create_user(username, password, cfg.superuser()).as_str())
                     .await?;
             }
+            if !cfg.superuser() {
+                if let Some(database) = cfg.database() {
+                    grant(cfg, database, username).await?;
+                }
+            }
         }
     }
     Ok(())
 }
+
+async fn grant(cfg: &DbConfig, database: &str, username: &str) -> anyhow::Result<()> {
+    let options = PgConnectOptions::from_str(cfg.root_address())?.database(database);
+    let mut conn = PgConnection::connect_with(&options).await?;
+    for statement in ddl::grants(database, username, cfg.access()) {
+        conn.execute(statement.as_str()).await?;
+    }
+    conn.close().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Needs a PostgreSQL server, configured by `PG_TEST_ROOT_*` variables
+    /// of a superuser.
+    #[tokio::test]
+    #[ignore = "requires a running PostgreSQL"]
+    async fn test_ensure_special_characters() -> anyhow::Result<()> {
+        std::env::set_var("PG_TEST_DATABASE", "qm-test \"db\"");
+        std::env::set_var("PG_TEST_USERNAME", "qm-reader'; --");
+        std::env::set_var("PG_TEST_PASSWORD", "p'w\\\"x");
+        std::env::set_var("PG_TEST_SUPERUSER", "false");
+        std::env::set_var("PG_TEST_ACCESS", "readonly");
+        let cfg = DbConfig::builder().with_prefix("PG_TEST_").build()?;
+        ensure("qm-pg-test", &[&cfg]).await?;
+        ensure("qm-pg-test", &[&cfg]).await?;
+        let options = PgConnectOptions::from_str(cfg.root_address())?
+            .database(cfg.database().unwrap())
+            .username(cfg.username().unwrap())
+            .password(cfg.password().unwrap());
+        let mut conn = PgConnection::connect_with(&options).await?;
+        let superuser: bool = sqlx::query_scalar("SELECT usesuper FROM pg_user WHERE usename = $1")
+            .bind(cfg.username())
+            .fetch_one(&mut conn)
+            .await?;
+        assert!(!superuser);
+        assert!(conn.execute("CREATE TABLE qm_test (id INT)").await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::stream::{self, Stream};
+use sqlx::postgres::{PgListener, PgNotification};
+use sqlx::PgPool;
+
+use crate::DB;
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+struct State<F> {
+    pool: PgPool,
+    channels: Arc<[String]>,
+    on_gap: F,
+    listener: Option<PgListener>,
+    connected: bool,
+    delay: Duration,
+}
+
+impl<F> State<F>
+where
+    F: Fn() + Send + 'static,
+{
+    async fn connect(&mut self) -> PgListener {
+        loop {
+            match subscribe(&self.pool, &self.channels).await {
+                Ok(listener) => {
+                    self.delay = MIN_RECONNECT_DELAY;
+                    if self.connected {
+                        tracing::info!("postgresql listener reconnected to {:?}", self.channels);
+                        (self.on_gap)();
+                    }
+                    self.connected = true;
+                    return listener;
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        "postgresql listener failed to subscribe to {:?}, retrying in {:?}: {err:#}",
+                        self.channels,
+                        self.delay
+                    );
+                    tokio::time::sleep(self.delay).await;
+                    self.delay = next_delay(self.delay);
+                }
+            }
+        }
+    }
+
+    async fn next(&mut self) -> PgNotification {
+        loop {
+            let mut listener = match self.listener.take() {
+                Some(listener) => listener,
+                None => self.connect().await,
+            };
+            match listener.try_recv().await {
+                Ok(Some(notification)) => {
+                    self.listener = Some(listener);
+                    return notification;
+                }
+                Ok(None) => {
+                    tracing::warn!("postgresql listener disconnected from {:?}", self.channels);
+                }
+                Err(err) => {
+                    tracing::warn!("postgresql listener on {:?} failed: {err:#}", self.channels);
+                }
+            }
+        }
+    }
+}
+
+async fn subscribe(pool: &PgPool, channels: &[String]) -> sqlx::Result<PgListener> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener
+        .listen_all(channels.iter().map(String::as_str))
+        .await?;
+    Ok(listener)
+}
+
+fn next_delay(delay: Duration) -> Duration {
+    (delay * 2).min(MAX_RECONNECT_DELAY)
+}
+
+impl DB {
+    /// Subscribes to `channels` and yields their notifications, the stream
+    /// never ends. Lost connections are re-established and resubscribed with
+    /// backoff, notifications sent in between are lost, so `on_gap` is called
+    /// after each reconnect to let caches do a full reload.
+    pub fn listen<I, S, F>(&self, channels: I, on_gap: F) -> impl Stream<Item = PgNotification>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        F: Fn() + Send + 'static,
+    {
+        let state = State {
+            pool: self.pool().clone(),
+            channels: channels.into_iter().map(Into::into).collect(),
+            on_gap,
+            listener: None,
+            connected: false,
+            delay: MIN_RECONNECT_DELAY,
+        };
+        stream::unfold(state, |mut state| async move {
+            let notification = state.next().await;
+            Some((notification, state))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_delay() {
+        let mut delay = MIN_RECONNECT_DELAY;
+        for _ in 0..10 {
+            delay = next_delay(delay);
+        }
+        assert_eq!(delay, MAX_RECONNECT_DELAY);
+        assert_eq!(next_delay(MIN_RECONNECT_DELAY), Duration::from_secs(1));
+    }
+}
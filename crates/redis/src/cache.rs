@@ -0,0 +1,221 @@
+//! Cache-aside with stampede protection.
+//!
+//! Values are stored as JSON together with the time until they are fresh.
+//! Only the caller holding the refresh lock computes a missing value, others
+//! wait for it. With stale-while-revalidate, stale values are returned
+//! immediately while a single background task refreshes them.
+
+use std::{
+    future::Future,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use redis::AsyncCommands;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{lock, Redis};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    value: T,
+    fresh_until: u64,
+}
+
+impl<T> Entry<T> {
+    fn is_fresh(&self, now: u64) -> bool {
+        self.fresh_until > now
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachePolicy {
+    ttl: Duration,
+    stale: Duration,
+    lock_ttl: Duration,
+}
+
+impl CachePolicy {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            stale: Duration::ZERO,
+            lock_ttl: Duration::from_secs(5),
+        }
+    }
+
+    /// Keeps values for `stale` after they expired, they are returned while
+    /// being refreshed in the background.
+    pub fn with_stale_while_revalidate(mut self, stale: Duration) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Maximum duration of a computation, waiting callers compute the value
+    /// themselves after it elapsed.
+    pub fn with_lock_ttl(mut self, lock_ttl: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self
+    }
+
+    pub async fn get_or_compute<T, F, Fut>(
+        &self,
+        redis: &Redis,
+        key: &str,
+        f: F,
+    ) -> anyhow::Result<T>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        let lock_key = format!("{key}:lock");
+        let lock_ttl = self.lock_ttl.as_millis() as usize;
+        let mut con = redis.connect().await?;
+        if let Some(entry) = read::<T>(&mut con, key).await? {
+            if !entry.is_fresh(now_millis()) {
+                if let Ok(lock) = lock::try_lock(&mut con, &lock_key, lock_ttl).await {
+                    let policy = *self;
+                    let redis = redis.clone();
+                    let key = key.to_string();
+                    tokio::spawn(async move {
+                        if let Err(err) = policy.refresh(&redis, &key, &lock_key, lock, f).await {
+                            tracing::warn!("unable to refresh cache entry {key}: {err:#}");
+                        }
+                    });
+                }
+            }
+            return Ok(entry.value);
+        }
+        let deadline = tokio::time::Instant::now() + self.lock_ttl;
+        loop {
+            match lock::try_lock(&mut con, &lock_key, lock_ttl).await {
+                Ok(lock) => {
+                    let result = f().await;
+                    if let Some(data) = encode(&result, self)? {
+                        write(&mut con, key, data, self).await?;
+                    }
+                    lock::unlock(&mut con, &lock_key, &lock.id).await?;
+                    return result;
+                }
+                Err(lock::Error::CanNotGetLock(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return f().await;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+            if let Some(entry) = read::<T>(&mut con, key).await? {
+                return Ok(entry.value);
+            }
+        }
+    }
+
+    async fn refresh<T, F, Fut>(
+        &self,
+        redis: &Redis,
+        key: &str,
+        lock_key: &str,
+        lock: lock::Lock,
+        f: F,
+    ) -> anyhow::Result<()>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let result = f().await;
+        let mut con = redis.connect().await?;
+        if let Some(data) = encode(&result, self)? {
+            write(&mut con, key, data, self).await?;
+        }
+        lock::unlock(&mut con, lock_key, &lock.id).await?;
+        result.map(|_| ())
+    }
+}
+
+async fn read<T: DeserializeOwned>(
+    con: &mut impl AsyncCommands,
+    key: &str,
+) -> anyhow::Result<Option<Entry<T>>> {
+    let data: Option<Vec<u8>> = con.get(key).await?;
+    Ok(data.and_then(|data| {
+        serde_json::from_slice(&data)
+            .inspect_err(|err| tracing::warn!("invalid cache entry {key}: {err}"))
+            .ok()
+    }))
+}
+
+/// Serializes successfully computed values.
+fn encode<T: Serialize>(
+    result: &anyhow::Result<T>,
+    policy: &CachePolicy,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let Ok(value) = result else {
+        return Ok(None);
+    };
+    let entry = Entry {
+        value,
+        fresh_until: now_millis() + policy.ttl.as_millis() as u64,
+    };
+    Ok(Some(serde_json::to_vec(&entry)?))
+}
+
+async fn write(
+    con: &mut impl AsyncCommands,
+    key: &str,
+    data: Vec<u8>,
+    policy: &CachePolicy,
+) -> anyhow::Result<()> {
+    let expires_in = (policy.ttl + policy.stale).as_millis() as u64;
+    let _: () = con.pset_ex(key, data, expires_in).await?;
+    Ok(())
+}
+
+/// Returns the cached value of `key` or computes and caches it for `ttl`,
+/// concurrent callers wait for a single computation.
+pub async fn get_or_compute<T, F, Fut>(
+    redis: &Redis,
+    key: &str,
+    ttl: Duration,
+    f: F,
+) -> anyhow::Result<T>
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    CachePolicy::new(ttl).get_or_compute(redis, key, f).await
+}
+
+pub async fn invalidate(redis: &Redis, key: &str) -> anyhow::Result<()> {
+    let mut con = redis.connect().await?;
+    let _: () = con.del(key).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry() -> anyhow::Result<()> {
+        let data = serde_json::to_vec(&Entry {
+            value: "a",
+            fresh_until: 1000,
+        })?;
+        let entry: Entry<String> = serde_json::from_slice(&data)?;
+        assert_eq!(entry.value, "a");
+        assert!(entry.is_fresh(999));
+        assert!(!entry.is_fresh(1000));
+        Ok(())
+    }
+}
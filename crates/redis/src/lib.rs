@@ -1,11 +1,14 @@
 pub use deadpool_redis::redis;
 use deadpool_redis::Runtime;
 use std::sync::Arc;
+pub mod cache;
 mod config;
 pub mod lock;
+pub mod stream_queue;
 pub mod work_queue;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
+use qm_utils::shutdown::{Shutdown, ShutdownPhase};
 use redis::AsyncCommands;
 use redis::RedisResult;
 use serde::de::DeserializeOwned;
@@ -14,12 +17,14 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use stream_queue::StreamQueue;
 use tokio::runtime::Builder;
 use tokio::sync::RwLock;
 use tokio::task::LocalSet;
 use work_queue::Item;
 use work_queue::KeyPrefix;
+use work_queue::QueueStats;
 use work_queue::WorkQueue;
 
 pub use crate::config::Config as RedisConfig;
@@ -135,13 +140,82 @@ pub type RunningWorkers =
 
 pub type ExecItemFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>;
 
+/// Storage of the items of a worker queue, selected per queue with
+/// [`AsyncWorker::with_backend`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueBackend {
+    /// Lists with leases, see [`WorkQueue`].
+    #[default]
+    List,
+    /// Redis Stream with a consumer group, see [`StreamQueue`]. Keeps the
+    /// order of the items and the history of acknowledged items, trimmed to
+    /// approximately `max_len` entries. Items which are not acknowledged are
+    /// never trimmed.
+    Stream { max_len: Option<usize> },
+}
+
+#[derive(Clone)]
+pub enum WorkerQueue {
+    List(Arc<WorkQueue>),
+    Stream(Arc<StreamQueue>),
+}
+
+impl WorkerQueue {
+    fn new(prefix: &str, backend: QueueBackend, lease_duration: Duration) -> Self {
+        let name = KeyPrefix::new(prefix.to_string());
+        match backend {
+            QueueBackend::List => Self::List(Arc::new(WorkQueue::new(name))),
+            QueueBackend::Stream { max_len } => Self::Stream(Arc::new(
+                StreamQueue::new(name)
+                    .with_lease_duration(lease_duration)
+                    .with_max_len(max_len),
+            )),
+        }
+    }
+
+    pub async fn add_item<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<()> {
+        match self {
+            Self::List(queue) => queue.add_item(db, item).await,
+            Self::Stream(queue) => queue.add_item(db, item).await,
+        }
+    }
+
+    pub async fn complete<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<bool> {
+        match self {
+            Self::List(queue) => queue.complete(db, item).await,
+            Self::Stream(queue) => queue.complete(db, item).await,
+        }
+    }
+
+    pub async fn stats<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<QueueStats> {
+        match self {
+            Self::List(queue) => queue.stats(db).await,
+            Self::Stream(queue) => queue.stats(db).await,
+        }
+    }
+
+    pub async fn list_pending<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        limit: usize,
+    ) -> RedisResult<Vec<Item>> {
+        match self {
+            Self::List(queue) => queue.list_pending(db, limit).await,
+            Self::Stream(queue) => queue.list_pending(db, limit).await,
+        }
+    }
+}
+
 pub struct WorkerContext<Ctx>
 where
     Ctx: Clone + Send + Sync + 'static,
 {
     ctx: Ctx,
     pub worker_id: usize,
+    /// List queue of the worker prefix, use [`Self::worker_queue`] to reach
+    /// the items of workers with [`QueueBackend::Stream`].
     pub queue: Arc<WorkQueue>,
+    worker_queue: WorkerQueue,
     pub client: Arc<redis::Client>,
     pub item: Item,
 }
@@ -153,11 +227,28 @@ where
     pub fn ctx(&self) -> &Ctx {
         &self.ctx
     }
+
+    /// Queue of the backend the item was read from.
+    pub fn worker_queue(&self) -> &WorkerQueue {
+        &self.worker_queue
+    }
+
     pub async fn complete(&self) -> anyhow::Result<()> {
         let mut con = self.client.get_multiplexed_async_connection().await?;
-        self.queue.complete(&mut con, &self.item).await?;
+        self.worker_queue.complete(&mut con, &self.item).await?;
         Ok(())
     }
+
+    pub async fn stats(&self) -> anyhow::Result<QueueStats> {
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        Ok(self.worker_queue.stats(&mut con).await?)
+    }
+
+    /// Up to `limit` waiting items of the queue, oldest first.
+    pub async fn list_pending(&self, limit: usize) -> anyhow::Result<Vec<Item>> {
+        let mut con = self.client.get_multiplexed_async_connection().await?;
+        Ok(self.worker_queue.list_pending(&mut con, limit).await?)
+    }
 }
 
 async fn add(
@@ -201,6 +292,71 @@ where
     Ok(())
 }
 
+/// Runs the work for a leased item, completes empty and invalid items.
+async fn process_item<Ctx, T, C>(
+    ctx: &Ctx,
+    client: &Arc<redis::Client>,
+    worker: &AsyncWorker<Ctx, T>,
+    worker_id: usize,
+    queue: &WorkerQueue,
+    con: &mut C,
+    item: Item,
+) -> anyhow::Result<()>
+where
+    Ctx: Clone + Send + Sync + 'static,
+    T: DeserializeOwned + Send + Sync,
+    C: AsyncCommands,
+{
+    if item.data.is_empty() {
+        tracing::info!("item is empty");
+        queue.complete(con, &item).await?;
+        return Ok(());
+    }
+    if let Ok(request) = serde_json::from_slice::<T>(&item.data).inspect_err(|_| {
+        tracing::error!(
+            "invalid request item on worker {} #{worker_id} Item: {}",
+            worker.prefix,
+            String::from_utf8_lossy(&item.data)
+        );
+    }) {
+        if let Some(work) = worker.work.as_ref() {
+            let run = work.run(
+                WorkerContext {
+                    ctx: ctx.clone(),
+                    worker_id,
+                    queue: match queue {
+                        WorkerQueue::List(queue) => queue.clone(),
+                        WorkerQueue::Stream(_) => {
+                            Arc::new(WorkQueue::new(KeyPrefix::new(worker.prefix.clone())))
+                        }
+                    },
+                    worker_queue: queue.clone(),
+                    client: client.clone(),
+                    item: Item {
+                        id: item.id.clone(),
+                        data: Box::new([]),
+                    },
+                },
+                request,
+            );
+            #[cfg(feature = "otel")]
+            let run = tracing::Instrument::instrument(
+                run,
+                tracing::info_span!(
+                    "redis.work_item",
+                    worker = %worker.prefix,
+                    worker_id,
+                    item_id = %item.id
+                ),
+            );
+            run.await?;
+        }
+    } else {
+        queue.complete(con, &item).await?;
+    }
+    Ok(())
+}
+
 async fn run_worker_queue<Ctx, T>(
     ctx: Ctx,
     client: Arc<redis::Client>,
@@ -214,6 +370,7 @@ where
 {
     tracing::info!("start {} worker #{worker_id} queue", worker.prefix);
     let request_queue = Arc::new(WorkQueue::new(KeyPrefix::new(worker.prefix.clone())));
+    let queue = WorkerQueue::List(request_queue.clone());
     let mut con = client.get_multiplexed_async_connection().await?;
     loop {
         if !is_running.load(Ordering::SeqCst) {
@@ -227,37 +384,66 @@ where
             )
             .await?
         {
-            if item.data.is_empty() {
-                tracing::info!("item is empty");
-                request_queue.complete(&mut con, &item).await?;
-                continue;
-            }
-            if let Ok(request) = serde_json::from_slice::<T>(&item.data).inspect_err(|_| {
-                tracing::error!(
-                    "invalid request item on worker {} #{worker_id} Item: {}",
-                    worker.prefix,
-                    String::from_utf8_lossy(&item.data)
-                );
-            }) {
-                if let Some(work) = worker.work.as_ref() {
-                    work.run(
-                        WorkerContext {
-                            ctx: ctx.clone(),
-                            worker_id,
-                            queue: request_queue.clone(),
-                            client: client.clone(),
-                            item: Item {
-                                id: item.id.clone(),
-                                data: Box::new([]),
-                            },
-                        },
-                        request,
+            process_item(&ctx, &client, &worker, worker_id, &queue, &mut con, item).await?;
+        }
+    }
+    Ok(())
+}
+
+const STREAM_TRIM_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Like [`run_worker_queue`] on a stream, items of other consumers with an
+/// expired lease are claimed before new items are read. The acknowledged
+/// items are trimmed every [`STREAM_TRIM_INTERVAL`].
+async fn run_stream_worker_queue<Ctx, T>(
+    ctx: Ctx,
+    client: Arc<redis::Client>,
+    is_running: Arc<AtomicBool>,
+    worker: Arc<AsyncWorker<Ctx, T>>,
+    worker_id: usize,
+    max_len: Option<usize>,
+) -> anyhow::Result<()>
+where
+    Ctx: Clone + Send + Sync + 'static,
+    T: DeserializeOwned + Send + Sync,
+{
+    tracing::info!("start {} worker #{worker_id} stream", worker.prefix);
+    let request_queue = Arc::new(
+        StreamQueue::new(KeyPrefix::new(worker.prefix.clone()))
+            .with_lease_duration(Duration::from_secs(worker.lease_duration))
+            .with_max_len(max_len),
+    );
+    let queue = WorkerQueue::Stream(request_queue.clone());
+    let consumer = request_queue.consumer(worker_id);
+    let mut con = client.get_multiplexed_async_connection().await?;
+    request_queue.create_group(&mut con).await?;
+    let mut last_trim = Instant::now();
+    loop {
+        if !is_running.load(Ordering::SeqCst) {
+            break;
+        }
+        if max_len.is_some() && last_trim.elapsed() >= STREAM_TRIM_INTERVAL {
+            request_queue.trim(&mut con).await?;
+            last_trim = Instant::now();
+        }
+        let item = match request_queue
+            .claim_expired(&mut con, &consumer, 1)
+            .await?
+            .pop()
+        {
+            Some(item) => Some(item),
+            None => {
+                request_queue
+                    .read(
+                        &mut con,
+                        &consumer,
+                        Some(Duration::from_secs(worker.timeout)),
                     )
-                    .await?;
-                }
-            } else {
-                request_queue.complete(&mut con, &item).await?;
+                    .await?
             }
+        };
+        if let Some(item) = item {
+            process_item(&ctx, &client, &worker, worker_id, &queue, &mut con, item).await?;
         }
     }
     Ok(())
@@ -296,9 +482,11 @@ impl Workers {
         T: DeserializeOwned + Send + Sync + 'static,
     {
         let worker = Arc::new(worker);
-        let mut con = self.inner.client.get_multiplexed_async_connection().await?;
-        worker.recover(&mut con).await?;
-        {
+        if worker.backend == QueueBackend::List {
+            let mut con = self.inner.client.get_multiplexed_async_connection().await?;
+            worker.recover(&mut con).await?;
+        }
+        if worker.backend == QueueBackend::List {
             let instances = self.inner.instances.clone();
             let client = self.inner.client.clone();
             let worker = worker.clone();
@@ -356,9 +544,24 @@ impl Workers {
                         }),
                     )
                     .await;
-                    if let Err(err) =
-                        run_worker_queue(ctx.clone(), client, is_running, worker, worker_id).await
-                    {
+                    let result = match worker.backend {
+                        QueueBackend::List => {
+                            run_worker_queue(ctx.clone(), client, is_running, worker, worker_id)
+                                .await
+                        }
+                        QueueBackend::Stream { max_len } => {
+                            run_stream_worker_queue(
+                                ctx.clone(),
+                                client,
+                                is_running,
+                                worker,
+                                worker_id,
+                                max_len,
+                            )
+                            .await
+                        }
+                    };
+                    if let Err(err) = result {
                         tracing::error!("{err:#?}");
                         std::process::exit(1);
                     }
@@ -371,7 +574,7 @@ impl Workers {
     }
 
     pub async fn terminate(&self) -> anyhow::Result<()> {
-        if !self.inner.is_running.load(Ordering::SeqCst) {
+        if !self.inner.is_running.swap(false, Ordering::SeqCst) {
             anyhow::bail!("Workers already terminated");
         }
         let mut futs = self.inner.instances.write().await.take().unwrap();
@@ -381,11 +584,23 @@ impl Workers {
         }
         Ok(())
     }
+
+    /// Terminates the workers in the [`ShutdownPhase::Workers`] of `shutdown`.
+    pub fn register_shutdown(&self, shutdown: &Shutdown, deadline: Duration) {
+        let workers = self.clone();
+        shutdown.register(
+            "redis workers",
+            ShutdownPhase::Workers,
+            deadline,
+            || async move { workers.terminate().await },
+        );
+    }
 }
 
 pub struct Producer {
     client: Arc<deadpool_redis::Pool>,
-    queue: WorkQueue,
+    prefix: String,
+    queue: WorkerQueue,
 }
 
 impl Producer {
@@ -402,8 +617,20 @@ impl Producer {
     where
         S: Into<String>,
     {
-        let queue = WorkQueue::new(KeyPrefix::new(prefix.into()));
-        Self { client, queue }
+        let prefix = prefix.into();
+        let queue = WorkerQueue::new(&prefix, QueueBackend::List, Duration::ZERO);
+        Self {
+            client,
+            prefix,
+            queue,
+        }
+    }
+
+    /// Adds the items to the queue of `backend`, must match the backend of
+    /// the workers.
+    pub fn with_backend(mut self, backend: QueueBackend) -> Self {
+        self.queue = WorkerQueue::new(&self.prefix, backend, Duration::ZERO);
+        self
     }
 
     pub async fn add_item_with_connection<C, T>(&self, db: &mut C, data: &T) -> anyhow::Result<()>
@@ -436,6 +663,7 @@ where
     num_workers: usize,
     timeout: u64,
     lease_duration: u64,
+    backend: QueueBackend,
     recovery_key: String,
     recovery_queue: WorkQueue,
     work: Option<Box<dyn Work<Ctx, T>>>,
@@ -457,6 +685,7 @@ where
             recovery_queue: WorkQueue::new(name),
             timeout: 5,
             lease_duration: 60,
+            backend: QueueBackend::List,
             num_workers: 1,
             prefix,
             work: None,
@@ -478,11 +707,13 @@ where
         self
     }
 
+    pub fn with_backend(mut self, backend: QueueBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
     pub fn producer(&self, client: Arc<deadpool_redis::Pool>) -> Producer {
-        Producer {
-            client,
-            queue: WorkQueue::new(KeyPrefix::new(self.prefix.clone())),
-        }
+        Producer::new_with_client(client, self.prefix.clone()).with_backend(self.backend)
     }
 
     pub async fn recover<C: AsyncCommands>(&self, db: &mut C) -> anyhow::Result<()> {
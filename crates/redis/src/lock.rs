@@ -1,3 +1,4 @@
+use qm_utils::retry::RetryPolicy;
 use redis::AsyncCommands;
 use redis::RedisError;
 use redis::Value as RedisValue;
@@ -81,16 +82,19 @@ pub async fn lock<C: AsyncCommands, T>(
 where
     T: AsRef<str>,
 {
+    let mut backoff = RetryPolicy::fixed(Duration::from_millis(u64::from(retry_delay)))
+        .with_max_attempts(retry_count)
+        .backoff();
     for _ in 0..retry_count {
         let lock_result = try_lock(db, key.as_ref(), ttl).await;
         match lock_result {
             Ok(lock) => return Ok(lock),
             Err(Error::RedisError(error)) => return Err(Error::RedisError(error)),
             Err(Error::PoolError(error)) => return Err(Error::PoolError(error)),
-            Err(Error::CanNotGetLock(_)) => {
-                sleep(Duration::from_millis(u64::from(retry_delay))).await;
-                continue;
-            }
+            Err(Error::CanNotGetLock(_)) => match backoff.next() {
+                Some(delay) => sleep(delay).await,
+                None => break,
+            },
         };
     }
 
@@ -0,0 +1,270 @@
+//! Work queue on a Redis Stream with a consumer group.
+//!
+//! Entries are read with `XREADGROUP` in insertion order and acknowledged
+//! with `XACK`. Entries stay in the stream after acknowledgment, so the
+//! stream keeps the history of processed items until it is trimmed with
+//! [`StreamQueue::trim`]. Entries of crashed consumers are taken over with
+//! `XAUTOCLAIM` once they are idle for longer than the lease duration.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use deadpool_redis::redis::{
+    self,
+    streams::{
+        StreamAutoClaimOptions, StreamAutoClaimReply, StreamId, StreamInfoGroupsReply,
+        StreamPendingCountReply, StreamPendingReply, StreamRangeReply, StreamReadOptions,
+        StreamReadReply, StreamTrimOptions, StreamTrimmingMode,
+    },
+    AsyncCommands, RedisResult,
+};
+use uuid::Uuid;
+
+use crate::work_queue::{Item, KeyPrefix, QueueStats};
+
+const GROUP: &str = "workers";
+const DATA_FIELD: &str = "data";
+
+pub struct StreamQueue {
+    session: String,
+    stream_key: String,
+    lease_duration: Duration,
+    max_len: Option<usize>,
+}
+
+impl StreamQueue {
+    pub fn new(name: KeyPrefix) -> StreamQueue {
+        StreamQueue {
+            session: Uuid::new_v4().to_string(),
+            stream_key: name.of(":stream"),
+            lease_duration: Duration::from_secs(60),
+            max_len: None,
+        }
+    }
+
+    /// Entries delivered longer than `lease_duration` ago without
+    /// acknowledgment are claimed by other consumers.
+    pub fn with_lease_duration(mut self, lease_duration: Duration) -> StreamQueue {
+        self.lease_duration = lease_duration;
+        self
+    }
+
+    /// Keeps the history of acknowledged entries at approximately `max_len`
+    /// entries with [`Self::trim`], the whole history is kept with `None`.
+    pub fn with_max_len(mut self, max_len: Option<usize>) -> StreamQueue {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Name of the consumer for the worker with `worker_id` of this process.
+    pub fn consumer(&self, worker_id: usize) -> String {
+        format!("{}-{worker_id}", self.session)
+    }
+
+    /// Creates the stream and the consumer group if they don't exist, the
+    /// group starts with the first entry of the stream.
+    pub async fn create_group<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<()> {
+        match db
+            .xgroup_create_mkstream::<_, _, _, ()>(&self.stream_key, GROUP, "0")
+            .await
+        {
+            Err(err) if err.code() == Some("BUSYGROUP") => Ok(()),
+            result => result,
+        }
+    }
+
+    pub fn add_item_to_pipeline(&self, pipeline: &mut redis::Pipeline, item: &Item) {
+        pipeline.xadd(&self.stream_key, "*", &[(DATA_FIELD, item.data.as_ref())]);
+    }
+
+    /// Adds the data of `item`, read items carry the id of the stream entry
+    /// instead.
+    pub async fn add_item<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<()> {
+        let mut pipeline = Box::new(redis::pipe());
+        self.add_item_to_pipeline(&mut pipeline, item);
+        pipeline.query_async(db).await
+    }
+
+    /// Reads the next new entry for `consumer`, blocks up to `timeout` or
+    /// indefinitely if `timeout` is `None`.
+    pub async fn read<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        consumer: &str,
+        timeout: Option<Duration>,
+    ) -> RedisResult<Option<Item>> {
+        let mut options = StreamReadOptions::default().group(GROUP, consumer).count(1);
+        match timeout {
+            Some(Duration::ZERO) => {}
+            timeout => options = options.block(timeout.map_or(0, |t| t.as_millis() as usize)),
+        }
+        let reply: Option<StreamReadReply> = db
+            .xread_options(&[&self.stream_key], &[">"], &options)
+            .await?;
+        Ok(reply
+            .and_then(|reply| reply.keys.into_iter().next())
+            .and_then(|key| key.ids.into_iter().next())
+            .map(into_item))
+    }
+
+    /// Takes over up to `count` entries whose lease expired.
+    pub async fn claim_expired<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        consumer: &str,
+        count: usize,
+    ) -> RedisResult<Vec<Item>> {
+        let reply: StreamAutoClaimReply = db
+            .xautoclaim_options(
+                &self.stream_key,
+                GROUP,
+                consumer,
+                self.lease_duration.as_millis() as usize,
+                "0-0",
+                StreamAutoClaimOptions::default().count(count),
+            )
+            .await?;
+        for item_id in reply.claimed.iter() {
+            tracing::info!("claimed '{}' -> item '{}'", self.stream_key, item_id.id);
+        }
+        Ok(reply.claimed.into_iter().map(into_item).collect())
+    }
+
+    /// Acknowledges `item`, returns `false` if it wasn't pending.
+    pub async fn complete<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<bool> {
+        let acked: usize = db.xack(&self.stream_key, GROUP, &[&item.id]).await?;
+        Ok(acked > 0)
+    }
+
+    /// Removes acknowledged entries older than the newest `max_len` entries,
+    /// returns the number of removed entries.
+    ///
+    /// The stream is trimmed with `XTRIM MINID` below the oldest entry which
+    /// is pending or not yet delivered to the consumer group, so trimming
+    /// never loses work.
+    pub async fn trim<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<usize> {
+        let Some(max_len) = self.max_len else {
+            return Ok(0);
+        };
+        let groups: StreamInfoGroupsReply = db.xinfo_groups(&self.stream_key).await?;
+        let Some(group) = groups.groups.into_iter().find(|g| g.name == GROUP) else {
+            return Ok(0);
+        };
+        let pending: StreamPendingReply = db.xpending(&self.stream_key, GROUP).await?;
+        let processed = match pending {
+            StreamPendingReply::Data(data) => data.start_id,
+            StreamPendingReply::Empty => group.last_delivered_id,
+        };
+        let newest: StreamRangeReply = db
+            .xrevrange_count(&self.stream_key, "+", "-", max_len)
+            .await?;
+        if newest.ids.len() < max_len {
+            return Ok(0);
+        }
+        let Some(history) = newest.ids.last().map(|entry| entry.id.clone()) else {
+            return Ok(0);
+        };
+        let min_id = match (parse_id(&processed), parse_id(&history)) {
+            (Some(a), Some(b)) if b < a => history,
+            (Some(_), Some(_)) => processed,
+            _ => return Ok(0),
+        };
+        db.xtrim_options(
+            &self.stream_key,
+            &StreamTrimOptions::minid(StreamTrimmingMode::Approx, min_id),
+        )
+        .await
+    }
+
+    /// Queue length and processing count of the consumer group, the age of
+    /// the oldest waiting item is taken from its entry id.
+    pub async fn stats<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<QueueStats> {
+        let groups: StreamInfoGroupsReply = db.xinfo_groups(&self.stream_key).await?;
+        let Some(group) = groups.groups.into_iter().find(|g| g.name == GROUP) else {
+            return Ok(QueueStats::default());
+        };
+        let mut leased = 0;
+        if group.pending > 0 {
+            let pending: StreamPendingCountReply = db
+                .xpending_count(&self.stream_key, GROUP, "-", "+", group.pending)
+                .await?;
+            let lease_duration = self.lease_duration.as_millis() as usize;
+            leased = pending
+                .ids
+                .iter()
+                .filter(|p| p.last_delivered_ms < lease_duration)
+                .count();
+        }
+        let oldest: StreamRangeReply = db
+            .xrange_count(
+                &self.stream_key,
+                format!("({}", group.last_delivered_id),
+                "+",
+                1,
+            )
+            .await?;
+        Ok(QueueStats {
+            queue_len: group.lag.unwrap_or_default(),
+            processing: group.pending,
+            leased,
+            oldest_item_age: oldest.ids.first().and_then(|entry| entry_age(&entry.id)),
+        })
+    }
+
+    /// Up to `limit` entries not yet delivered to the consumer group, oldest
+    /// first.
+    pub async fn list_pending<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        limit: usize,
+    ) -> RedisResult<Vec<Item>> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let groups: StreamInfoGroupsReply = db.xinfo_groups(&self.stream_key).await?;
+        let start = groups
+            .groups
+            .into_iter()
+            .find(|g| g.name == GROUP)
+            .map_or_else(|| "-".to_string(), |g| format!("({}", g.last_delivered_id));
+        let reply: StreamRangeReply = db.xrange_count(&self.stream_key, start, "+", limit).await?;
+        Ok(reply.ids.into_iter().map(into_item).collect())
+    }
+}
+
+fn into_item(entry: StreamId) -> Item {
+    Item {
+        data: entry
+            .get::<Vec<u8>>(DATA_FIELD)
+            .unwrap_or_default()
+            .into_boxed_slice(),
+        id: entry.id,
+    }
+}
+
+/// Milliseconds and sequence number of an entry id.
+fn parse_id(id: &str) -> Option<(u64, u64)> {
+    let (ms, seq) = id.split_once('-')?;
+    Some((ms.parse().ok()?, seq.parse().ok()?))
+}
+
+/// Entry ids start with the milliseconds since the epoch they were added.
+fn entry_age(id: &str) -> Option<Duration> {
+    let added = Duration::from_millis(id.split_once('-')?.0.parse().ok()?);
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|now| now.saturating_sub(added))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_id() {
+        assert_eq!(parse_id("1700000000000-2"), Some((1700000000000, 2)));
+        assert!(parse_id("1700000000000-2") < parse_id("1700000000001-0"));
+        assert!(parse_id("9-1") < parse_id("10-0"));
+        assert_eq!(parse_id("invalid"), None);
+    }
+}
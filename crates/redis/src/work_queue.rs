@@ -1,5 +1,5 @@
 use std::future::Future;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use deadpool_redis::redis::{self, AsyncCommands, RedisResult, Value};
 use serde::{Deserialize, Serialize};
@@ -87,10 +87,32 @@ impl Item {
     }
 }
 
+/// Snapshot of a [`WorkQueue`] returned by [`WorkQueue::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Items waiting to be leased.
+    pub queue_len: usize,
+    /// Items moved to processing, leased or waiting for recovery.
+    pub processing: usize,
+    /// Processing items with an active lease.
+    pub leased: usize,
+    /// Age of the oldest waiting item, `None` if the queue is empty or the
+    /// item was added without enqueue time.
+    pub oldest_item_age: Option<Duration>,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 pub struct WorkQueue {
     session: String,
     main_queue_key: String,
     processing_key: String,
+    enqueued_at_key: String,
     lease_key: KeyPrefix,
     item_data_key: KeyPrefix,
 }
@@ -101,6 +123,7 @@ impl WorkQueue {
             session: Uuid::new_v4().to_string(),
             main_queue_key: name.of(":queue"),
             processing_key: name.of(":processing"),
+            enqueued_at_key: name.of(":enqueued_at"),
             lease_key: name.and(":leased_by_session:"),
             item_data_key: name.and(":item:"),
         }
@@ -127,6 +150,7 @@ impl WorkQueue {
     pub fn add_item_to_pipeline(&self, pipeline: &mut redis::Pipeline, item: &Item) {
         pipeline.set(self.item_data_key.of(&item.id), item.data.as_ref());
         pipeline.lpush(&self.main_queue_key, &item.id);
+        pipeline.zadd(&self.enqueued_at_key, &item.id, now_millis());
     }
 
     pub async fn add_item<C: AsyncCommands>(&self, db: &mut C, item: &Item) -> RedisResult<()> {
@@ -149,6 +173,71 @@ impl WorkQueue {
         db.llen(&self.processing_key)
     }
 
+    pub async fn stats<C: AsyncCommands>(&self, db: &mut C) -> RedisResult<QueueStats> {
+        let (queue_len, processing_ids, oldest_id): (usize, Vec<String>, Option<String>) =
+            redis::pipe()
+                .llen(&self.main_queue_key)
+                .lrange(&self.processing_key, 0, -1)
+                .lindex(&self.main_queue_key, -1)
+                .query_async(db)
+                .await?;
+        let mut leased = 0;
+        for item_id in processing_ids.iter() {
+            if db.exists(self.lease_key.of(item_id)).await? {
+                leased += 1;
+            }
+        }
+        let oldest_item_age = match oldest_id {
+            Some(item_id) => db
+                .zscore::<_, _, Option<f64>>(&self.enqueued_at_key, &item_id)
+                .await?
+                .map(|enqueued_at| {
+                    Duration::from_millis(now_millis().saturating_sub(enqueued_at as u64))
+                }),
+            None => None,
+        };
+        Ok(QueueStats {
+            queue_len,
+            processing: processing_ids.len(),
+            leased,
+            oldest_item_age,
+        })
+    }
+
+    /// Up to `limit` waiting items, oldest first. Meant for debugging, the
+    /// data of every item is loaded.
+    pub async fn list_pending<C: AsyncCommands>(
+        &self,
+        db: &mut C,
+        limit: usize,
+    ) -> RedisResult<Vec<Item>> {
+        if limit == 0 {
+            return Ok(vec![]);
+        }
+        let item_ids: Vec<String> = db
+            .lrange(&self.main_queue_key, -(limit as isize), -1)
+            .await?;
+        if item_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut pipeline = redis::pipe();
+        for item_id in item_ids.iter() {
+            pipeline.get(self.item_data_key.of(item_id));
+        }
+        let data: Vec<Option<Vec<u8>>> = pipeline.query_async(db).await?;
+        Ok(item_ids
+            .into_iter()
+            .zip(data)
+            .rev()
+            .filter_map(|(id, data)| {
+                data.map(|data| Item {
+                    id,
+                    data: data.into_boxed_slice(),
+                })
+            })
+            .collect())
+    }
+
     pub async fn lease<C: AsyncCommands>(
         &self,
         db: &mut C,
@@ -206,6 +295,7 @@ impl WorkQueue {
         let _: () = redis::pipe()
             .del(self.item_data_key.of(&item.id))
             .del(self.lease_key.of(&item.id))
+            .zrem(&self.enqueued_at_key, &item.id)
             .query_async(db)
             .await?;
         Ok(true)
@@ -1,8 +1,12 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
 
 mod model;
 mod parser;
 mod reader;
+mod validate;
 mod writer;
 
 pub fn generate(input_file_path: &Path) -> anyhow::Result<()> {
@@ -14,6 +18,11 @@ pub fn generate(input_file_path: &Path) -> anyhow::Result<()> {
     let out_file_path = out_dir.join(file_name);
 
     let tables = reader::Reader::from_file(input_file_path)?.read()?;
+    validate::check(
+        &tables,
+        &input_file_path.display().to_string(),
+        &HashSet::new(),
+    )?;
     let parse_result = crate::parser::parse(tables)?;
 
     writer::Writer::from_file(out_file_path)?.write(parse_result)?;
@@ -26,6 +35,11 @@ pub fn generate_to_writer<W: std::io::Write>(
     writer: W,
 ) -> anyhow::Result<()> {
     let tables = reader::Reader::from_file(input_file_path)?.read()?;
+    validate::check(
+        &tables,
+        &input_file_path.display().to_string(),
+        &HashSet::new(),
+    )?;
     let parse_result = crate::parser::parse(tables)?;
 
     writer::Writer::from_writer(writer).write(parse_result)?;
@@ -33,6 +47,65 @@ pub fn generate_to_writer<W: std::io::Write>(
     Ok(())
 }
 
+fn read_files<P: AsRef<Path>>(input_file_paths: &[P]) -> anyhow::Result<parser::ParseResult> {
+    let mut all_tables = Vec::with_capacity(input_file_paths.len());
+    for path in input_file_paths {
+        all_tables.push(reader::Reader::from_file(path.as_ref())?.read_partial());
+    }
+    let groups: Vec<String> = all_tables
+        .iter()
+        .flat_map(|t| t.user_groups.rows.iter().filter_map(|r| r.first().cloned()))
+        .collect();
+    for (path, tables) in input_file_paths.iter().zip(all_tables.iter()) {
+        let own: HashSet<&str> = tables
+            .user_groups
+            .rows
+            .iter()
+            .filter_map(|r| r.first().map(String::as_str))
+            .collect();
+        let external = groups
+            .iter()
+            .map(String::as_str)
+            .filter(|g| !own.contains(g))
+            .collect();
+        validate::check(tables, &path.as_ref().display().to_string(), &external)?;
+    }
+    let tables = model::MdTables::merge(all_tables)?;
+    if tables.user_groups.rows.is_empty() {
+        anyhow::bail!("unable to find `user_groups` table");
+    }
+    if tables.roles.rows.is_empty() {
+        anyhow::bail!("unable to find `roles` table");
+    }
+    parser::parse(tables)
+}
+
+/// Composes several markdown files, e.g. a shared base permission set and
+/// service specific roles, into one generated module `{out_name}.rs`.
+///
+/// The user groups table of each file may have an additional last column
+/// `inherits` listing groups whose roles are included, groups of other
+/// input files can be referenced in the roles table and in `inherits`.
+pub fn generate_from_files<P: AsRef<Path>>(
+    input_file_paths: &[P],
+    out_name: &str,
+) -> anyhow::Result<()> {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    let out_file_path = out_dir.join(out_name).with_extension("rs");
+    let parse_result = read_files(input_file_paths)?;
+    writer::Writer::from_file(out_file_path)?.write(parse_result)?;
+    Ok(())
+}
+
+pub fn generate_from_files_to_writer<P: AsRef<Path>, W: std::io::Write>(
+    input_file_paths: &[P],
+    writer: W,
+) -> anyhow::Result<()> {
+    let parse_result = read_files(input_file_paths)?;
+    writer::Writer::from_writer(writer).write(parse_result)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -238,6 +311,104 @@ mod test {
             .write(result)?
             .into_inner();
         eprintln!("{code}");
+        assert!(code.contains("async_graphql::Enum, AsRefStr"));
+        assert!(code.contains("pub const PERMISSION_MATRIX: &[(BuiltInGroup, qm::role::Role<Resource, Permission>)] = &["));
+        assert!(code.contains("(BuiltInGroup::AdministrationOwner, qm::role::Role { ty: Resource::Administration, permission: None }),"));
+        assert!(code.contains("(BuiltInGroup::EmployeeReader, qm::role::Role { ty: Resource::Entity, permission: Some(Permission::View) }),"));
+        assert_eq!(code.matches("(BuiltInGroup::").count(), 13);
+        assert!(code.contains("fn test_resource_from_str_as_ref() {"));
+        assert!(code.contains("fn test_permission_from_str_as_ref() {"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_operations() -> anyhow::Result<()> {
+        let input = format!(
+            r#"{TEST_INPUT}
+
+# Operations `operations`
+
+| Operation    | Type     | Roles                   |
+| ------------ | -------- | ----------------------- |
+| health       | Query    | public                  |
+| me           | Query    | authenticated           |
+| entities     | Query    | entity:list, user:list  |
+| createEntity | Mutation | entity:create           |"#
+        );
+        let result = crate::parser::parse(Reader::from_str(&input).read()?)?;
+        assert_eq!(result.operation_mappings.len(), 4);
+        let code = crate::writer::Writer::in_memory()
+            .write(result)?
+            .into_inner();
+        assert!(code.contains(".with(qm::role::operations::OperationType::Query, \"health\", qm::role::operations::Guard::Public)"));
+        assert!(code.contains(".with(qm::role::operations::OperationType::Query, \"me\", qm::role::operations::Guard::Authenticated)"));
+        assert!(code.contains(".with(qm::role::operations::OperationType::Mutation, \"createEntity\", qm::role::operations::Guard::AnyOf(vec![qm::role::Role { ty: Resource::Entity, permission: Some(Permission::Create) }]))"));
+
+        let input = input.replace("| entity:create           |", "| entity:archive          |");
+        let err = crate::parser::parse(Reader::from_str(&input).read()?)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("requires role `entity:archive`"), "{err}");
+        Ok(())
+    }
+
+    const BASE_INPUT: &str = r#"# User Groups `user_groups`
+
+| Name   | Path    | Display Name | Access Levels | Allowed Types |
+| ------ | ------- | ------------ | ------------- | ------------- |
+| Reader | /reader | Reader       | Customer      | none          |
+
+# Role Mappings `roles`
+
+| Roles       | Reader |
+| ----------- | ------ |
+| user:list   | x      |
+| user:view   | x      |"#;
+
+    const SERVICE_INPUT: &str = r#"# User Groups `user_groups`
+
+| Name   | Path    | Display Name | Access Levels | Allowed Types | Inherits |
+| ------ | ------- | ------------ | ------------- | ------------- | -------- |
+| Writer | /writer | Writer       | Customer      | none          | Reader   |
+| Owner  | /owner  | Owner        | Customer      | none          | Writer   |
+
+# Role Mappings `roles`
+
+| Roles       | Reader | Writer |
+| ----------- | ------ | ------ |
+| entity:view | x      |        |
+| user:update |        | x      |"#;
+
+    #[test]
+    fn test_merge_and_inherit() -> anyhow::Result<()> {
+        let tables = crate::model::MdTables::merge(vec![
+            Reader::from_str(BASE_INPUT).read_partial(),
+            Reader::from_str(SERVICE_INPUT).read_partial(),
+        ])?;
+        let result = crate::parser::parse(tables)?;
+        let roles = |group: &str| -> Vec<String> {
+            result
+                .role_mappings
+                .iter()
+                .find(|v| v.user_group.as_ref() == group)
+                .map(|v| v.roles.iter().map(|r| r.to_string()).collect())
+                .unwrap_or_default()
+        };
+        assert_eq!(roles("Reader"), ["user:list", "user:view", "entity:view"]);
+        assert_eq!(
+            roles("Writer"),
+            ["user:update", "user:list", "user:view", "entity:view"]
+        );
+        assert_eq!(roles("Owner"), roles("Writer"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cyclic_inheritance() -> anyhow::Result<()> {
+        let input = SERVICE_INPUT.replace("| Reader   |", "| Owner    |");
+        let tables = crate::model::MdTables::merge(vec![Reader::from_str(&input).read_partial()])?;
+        let err = crate::parser::parse(tables).unwrap_err().to_string();
+        assert!(err.contains("cyclic inheritance"), "{err}");
         Ok(())
     }
 }
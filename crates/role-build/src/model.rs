@@ -3,33 +3,171 @@ use std::rc::Rc;
 pub type Column = String;
 pub type Row = Vec<Column>;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Table {
     pub headers: Vec<String>,
     pub rows: Vec<Row>,
 }
 
+/// 1-based position of a table cell in the markdown input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// Cell locations of a [`Table`], the first row holds the headers.
+pub type Locations = Vec<Vec<Location>>;
+
 #[derive(Default)]
 pub struct OptMdTables {
-    pub user_groups: Option<Table>,
-    pub roles: Option<Table>,
+    pub user_groups: Option<(Table, Locations)>,
+    pub roles: Option<(Table, Locations)>,
+    pub operations: Option<(Table, Locations)>,
 }
 
 pub struct MdTables {
     pub user_groups: Table,
     pub roles: Table,
+    pub user_groups_locations: Locations,
+    pub roles_locations: Locations,
+    /// Optional table of the roles required by top-level GraphQL fields.
+    pub operations: Table,
+}
+
+pub const INHERITS_HEADER: &str = "inherits";
+
+impl Table {
+    pub fn column(&self, header: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(header))
+    }
+}
+
+impl MdTables {
+    /// Composes the tables of several files into one, user groups are
+    /// concatenated and the role marks of all files are combined.
+    pub fn merge(tables: Vec<MdTables>) -> anyhow::Result<MdTables> {
+        let mut user_groups = Table::default();
+        let mut role_headers: Vec<String> = vec![];
+        let mut role_rows: Vec<(String, Vec<String>)> = vec![];
+        let mut operations = Table::default();
+        for t in tables {
+            if operations.headers.is_empty() {
+                operations.headers = t.operations.headers;
+            }
+            operations.rows.extend(t.operations.rows);
+            if !t.user_groups.headers.is_empty() {
+                let inherits = t.user_groups.column(INHERITS_HEADER);
+                if user_groups.headers.is_empty() || inherits.is_some() {
+                    user_groups.headers = t.user_groups.headers.clone();
+                }
+                for mut row in t.user_groups.rows {
+                    if let Some(existing) =
+                        user_groups.rows.iter().find(|r| r.first() == row.first())
+                    {
+                        anyhow::bail!(
+                            "user group `{}` is defined more than once",
+                            existing.first().map(String::as_str).unwrap_or_default()
+                        );
+                    }
+                    if inherits.is_none() {
+                        row.push(String::new());
+                    }
+                    user_groups.rows.push(row);
+                }
+            }
+            let headers: Vec<&String> = t.roles.headers.iter().skip(1).collect();
+            for header in headers.iter() {
+                if !role_headers.contains(header) {
+                    role_headers.push((*header).clone());
+                }
+            }
+            for row in t.roles.rows.iter() {
+                let Some(role) = row.first() else {
+                    continue;
+                };
+                let groups: Vec<String> = row
+                    .iter()
+                    .skip(1)
+                    .zip(headers.iter())
+                    .filter(|(col, _)| col.trim() == "x")
+                    .map(|(_, header)| (*header).clone())
+                    .collect();
+                match role_rows.iter_mut().find(|(r, _)| r == role) {
+                    Some((_, existing)) => existing.extend(groups),
+                    None => role_rows.push((role.clone(), groups)),
+                }
+            }
+        }
+        if !user_groups.headers.is_empty() && user_groups.column(INHERITS_HEADER).is_none() {
+            user_groups.headers.push(INHERITS_HEADER.to_string());
+        }
+        let roles = Table {
+            headers: std::iter::once("Roles".to_string())
+                .chain(role_headers.iter().cloned())
+                .collect(),
+            rows: role_rows
+                .into_iter()
+                .map(|(role, groups)| {
+                    std::iter::once(role)
+                        .chain(role_headers.iter().map(|h| {
+                            if groups.contains(h) {
+                                "x".to_string()
+                            } else {
+                                String::new()
+                            }
+                        }))
+                        .collect()
+                })
+                .collect(),
+        };
+        Ok(MdTables {
+            user_groups,
+            roles,
+            user_groups_locations: Locations::default(),
+            roles_locations: Locations::default(),
+            operations,
+        })
+    }
+}
+
+impl OptMdTables {
+    pub fn into_partial(self) -> MdTables {
+        let (user_groups, user_groups_locations) = self.user_groups.unwrap_or_default();
+        let (roles, roles_locations) = self.roles.unwrap_or_default();
+        MdTables {
+            user_groups,
+            roles,
+            user_groups_locations,
+            roles_locations,
+            operations: self.operations.unwrap_or_default().0,
+        }
+    }
 }
 
 impl TryFrom<OptMdTables> for MdTables {
     type Error = anyhow::Error;
     fn try_from(value: OptMdTables) -> Result<Self, Self::Error> {
+        let (user_groups, user_groups_locations) = value
+            .user_groups
+            .ok_or(anyhow::anyhow!("unable to find `user_groups` table"))?;
+        let (roles, roles_locations) = value
+            .roles
+            .ok_or(anyhow::anyhow!("unable to find `roles` table"))?;
         Ok(Self {
-            user_groups: value
-                .user_groups
-                .ok_or(anyhow::anyhow!("unable to find `user_groups` table"))?,
-            roles: value
-                .roles
-                .ok_or(anyhow::anyhow!("unable to find `roles` table"))?,
+            user_groups,
+            roles,
+            user_groups_locations,
+            roles_locations,
+            operations: value.operations.unwrap_or_default().0,
         })
     }
 }
@@ -41,8 +179,21 @@ pub struct UserGroupNameMapping {
     pub display_name: Rc<str>,
     pub access_level: Rc<str>,
     pub allowed_types: Rc<str>,
+    pub inherits: Rc<[Rc<str>]>,
 }
 
+/// Roles of which one is required for a top-level field, or one of
+/// [`PUBLIC`] and [`AUTHENTICATED`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct OperationMapping {
+    pub operation_type: Rc<str>,
+    pub field: Rc<str>,
+    pub roles: Rc<[Rc<str>]>,
+}
+
+pub const PUBLIC: &str = "public";
+pub const AUTHENTICATED: &str = "authenticated";
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct RoleMapping {
     pub user_group: Rc<str>,
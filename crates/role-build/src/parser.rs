@@ -1,6 +1,6 @@
-use crate::model::MdTables;
-use crate::model::{RoleMapping, UserGroupNameMapping};
-use std::collections::{HashMap, HashSet};
+use crate::model::{MdTables, Table, AUTHENTICATED, INHERITS_HEADER, PUBLIC};
+use crate::model::{OperationMapping, RoleMapping, UserGroupNameMapping};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::rc::Rc;
 
 fn sorted(v: HashSet<Rc<str>>) -> Rc<[Rc<str>]> {
@@ -13,6 +13,7 @@ fn sorted(v: HashSet<Rc<str>>) -> Rc<[Rc<str>]> {
 pub struct ParseResult {
     pub user_group_name_mappings: Vec<UserGroupNameMapping>,
     pub role_mappings: Vec<RoleMapping>,
+    pub operation_mappings: Vec<OperationMapping>,
     pub permissions: Rc<[Rc<str>]>,
     pub resources: Rc<[Rc<str>]>,
 }
@@ -21,6 +22,7 @@ impl ParseResult {
     fn new(
         user_group_name_mappings: Vec<UserGroupNameMapping>,
         role_mappings: Vec<RoleMapping>,
+        operation_mappings: Vec<OperationMapping>,
     ) -> Self {
         let roles: HashSet<Rc<str>> = role_mappings
             .iter()
@@ -42,6 +44,7 @@ impl ParseResult {
         Self {
             user_group_name_mappings,
             role_mappings,
+            operation_mappings,
             permissions,
             resources,
         }
@@ -49,11 +52,23 @@ impl ParseResult {
 }
 
 pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
+    let operations = tables.operations;
+    let has_inherits = tables.user_groups.column(INHERITS_HEADER).is_some();
     let user_group_name_mappings: Vec<UserGroupNameMapping> = tables
         .user_groups
         .rows
         .into_iter()
         .filter_map(|mut t| {
+            let inherits: Rc<[Rc<str>]> = if has_inherits {
+                t.pop()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(Rc::from)
+                    .collect()
+            } else {
+                Rc::from([])
+            };
             let allowed_types = t.pop();
             let access_level = t.pop();
             let display_name = t.pop();
@@ -69,6 +84,7 @@ pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
                             path: Rc::from(path),
                             access_level: Rc::from(access_level),
                             allowed_types: allowed_types.into(),
+                            inherits: inherits.clone(),
                         }
                     },
                 )
@@ -82,26 +98,34 @@ pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
         .skip(1)
         .map(Rc::from)
         .collect();
-    let role_mappings_map: HashMap<Rc<str>, Vec<Rc<str>>> =
-        role_mappings
-            .rows
-            .into_iter()
-            .fold(HashMap::default(), |mut state, mut row| {
-                if !row.is_empty() {
-                    let role: Rc<str> = Rc::from(row.remove(0));
-                    for (idx, col) in row.into_iter().enumerate() {
-                        if let Some(user_group) = role_mapping_headers.get(idx) {
-                            if col.trim() == "x" {
-                                state
-                                    .entry(user_group.clone())
-                                    .or_default()
-                                    .push(role.clone())
-                            }
+    let mut role_mappings_map: HashMap<Rc<str>, Vec<Rc<str>>> = role_mappings
+        .rows
+        .into_iter()
+        .fold(HashMap::default(), |mut state, mut row| {
+            if !row.is_empty() {
+                let role: Rc<str> = Rc::from(row.remove(0));
+                for (idx, col) in row.into_iter().enumerate() {
+                    if let Some(user_group) = role_mapping_headers.get(idx) {
+                        if col.trim() == "x" {
+                            state
+                                .entry(user_group.clone())
+                                .or_default()
+                                .push(role.clone())
                         }
                     }
                 }
-                state
-            });
+            }
+            state
+        });
+    let inherits: BTreeMap<Rc<str>, Rc<[Rc<str>]>> = user_group_name_mappings
+        .iter()
+        .filter(|v| !v.inherits.is_empty())
+        .map(|v| (v.user_group.clone(), v.inherits.clone()))
+        .collect();
+    for user_group in inherits.keys() {
+        let roles = inherited_roles(user_group, &inherits, &role_mappings_map, &mut vec![])?;
+        role_mappings_map.insert(user_group.clone(), roles);
+    }
     let mut role_mappings: Vec<RoleMapping> = role_mappings_map
         .into_iter()
         .map(|(user_group, roles)| RoleMapping {
@@ -110,5 +134,103 @@ pub fn parse(tables: MdTables) -> anyhow::Result<ParseResult> {
         })
         .collect();
     role_mappings.sort_by_key(|v| v.user_group.clone());
-    Ok(ParseResult::new(user_group_name_mappings, role_mappings))
+    let known_roles: HashSet<Rc<str>> = role_mappings
+        .iter()
+        .flat_map(|v| v.roles.iter().cloned())
+        .collect();
+    let operation_mappings = parse_operations(operations, &known_roles)?;
+    Ok(ParseResult::new(
+        user_group_name_mappings,
+        role_mappings,
+        operation_mappings,
+    ))
+}
+
+const OPERATION_TYPES: [&str; 3] = ["query", "mutation", "subscription"];
+
+/// Rows of the `operations` table with the columns operation, type and
+/// roles, the roles must be mapped to a user group.
+fn parse_operations(
+    table: Table,
+    known_roles: &HashSet<Rc<str>>,
+) -> anyhow::Result<Vec<OperationMapping>> {
+    let mut result: Vec<OperationMapping> = Vec::with_capacity(table.rows.len());
+    for row in table.rows {
+        let [field, operation_type, roles] = row.as_slice() else {
+            anyhow::bail!(
+                "operation row has {} columns, expected 3 (operation, type, roles)",
+                row.len()
+            );
+        };
+        let operation_type = operation_type.to_lowercase();
+        if !OPERATION_TYPES.contains(&operation_type.as_str()) {
+            anyhow::bail!(
+                "unknown type `{operation_type}` of operation `{field}`, expected one of {}",
+                OPERATION_TYPES.join(", ")
+            );
+        }
+        if result
+            .iter()
+            .any(|v| v.field.as_ref() == field && v.operation_type.as_ref() == operation_type)
+        {
+            anyhow::bail!("operation `{field}` is defined more than once");
+        }
+        let roles: Vec<Rc<str>> = roles
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(Rc::from)
+            .collect();
+        if roles.is_empty() {
+            anyhow::bail!("operation `{field}` has no roles, use `{PUBLIC}` or `{AUTHENTICATED}` for operations without roles");
+        }
+        for role in roles.iter() {
+            let special = matches!(role.as_ref(), PUBLIC | AUTHENTICATED);
+            if special && roles.len() > 1 {
+                anyhow::bail!(
+                    "`{role}` can't be combined with other roles for operation `{field}`"
+                );
+            }
+            if !special && !known_roles.contains(role) {
+                anyhow::bail!("operation `{field}` requires role `{role}` which is not mapped to a user group");
+            }
+        }
+        result.push(OperationMapping {
+            operation_type: Rc::from(operation_type),
+            field: Rc::from(field.as_str()),
+            roles: Rc::from(roles),
+        });
+    }
+    Ok(result)
+}
+
+/// Roles of `user_group` followed by the roles of the groups it inherits
+/// from, transitively and without duplicates.
+fn inherited_roles(
+    user_group: &Rc<str>,
+    inherits: &BTreeMap<Rc<str>, Rc<[Rc<str>]>>,
+    role_mappings: &HashMap<Rc<str>, Vec<Rc<str>>>,
+    path: &mut Vec<Rc<str>>,
+) -> anyhow::Result<Vec<Rc<str>>> {
+    if path.contains(user_group) {
+        path.push(user_group.clone());
+        anyhow::bail!(
+            "cyclic inheritance of user groups: {}",
+            path.iter()
+                .map(|v| v.as_ref())
+                .collect::<Vec<&str>>()
+                .join(" -> ")
+        );
+    }
+    path.push(user_group.clone());
+    let mut roles = role_mappings.get(user_group).cloned().unwrap_or_default();
+    for parent in inherits.get(user_group).iter().flat_map(|v| v.iter()) {
+        for role in inherited_roles(parent, inherits, role_mappings, path)? {
+            if !roles.contains(&role) {
+                roles.push(role);
+            }
+        }
+    }
+    path.pop();
+    Ok(roles)
 }
@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use crate::model::{MdTables, OptMdTables, Table};
+use crate::model::{Location, Locations, MdTables, OptMdTables, Table};
 
 pub struct Reader<R> {
     r: R,
@@ -30,10 +30,16 @@ impl Reader<std::io::BufReader<std::fs::File>> {
 enum CurrentTable {
     UserGroups,
     Roles,
+    Operations,
     None,
 }
 
-fn set_table(rows: &mut Vec<Vec<String>>, tables: &mut OptMdTables, current_table: &CurrentTable) {
+fn set_table(
+    rows: &mut Vec<Vec<String>>,
+    locations: &mut Locations,
+    tables: &mut OptMdTables,
+    current_table: &CurrentTable,
+) {
     let headers = rows.remove(0);
     let mut table_rows = Vec::with_capacity(rows.len());
     std::mem::swap(&mut table_rows, rows);
@@ -41,12 +47,16 @@ fn set_table(rows: &mut Vec<Vec<String>>, tables: &mut OptMdTables, current_tabl
         headers,
         rows: table_rows,
     };
+    let table_locations = std::mem::take(locations);
     match current_table {
         CurrentTable::UserGroups => {
-            tables.user_groups = Some(table);
+            tables.user_groups = Some((table, table_locations));
         }
         CurrentTable::Roles => {
-            tables.roles = Some(table);
+            tables.roles = Some((table, table_locations));
+        }
+        CurrentTable::Operations => {
+            tables.operations = Some((table, table_locations));
         }
         _ => {}
     }
@@ -58,34 +68,55 @@ where
     R: std::io::BufRead,
 {
     pub fn read(self) -> anyhow::Result<MdTables> {
+        self.read_tables().try_into()
+    }
+
+    /// Like [`Reader::read`] but missing tables are empty, used for files
+    /// which only extend the groups or roles of another file.
+    pub fn read_partial(self) -> MdTables {
+        self.read_tables().into_partial()
+    }
+
+    fn read_tables(self) -> OptMdTables {
         let line_reader = self.r.lines();
 
         let mut rows = vec![];
+        let mut locations = vec![];
         let mut current_table = CurrentTable::None;
 
         let mut tables = OptMdTables::default();
 
-        for line in line_reader.into_iter().map_while(Result::ok) {
+        for (line_idx, line) in line_reader.into_iter().map_while(Result::ok).enumerate() {
             if line.trim().starts_with('|') {
                 let mut row = Vec::new();
+                let mut row_locations = Vec::new();
                 let s = line.split('|');
                 let mut is_first = true;
+                let mut offset = 0;
                 s.for_each(|col| {
                     if is_first {
                         is_first = false;
                     } else {
+                        let padding = col.chars().take_while(|c| c.is_whitespace()).count();
                         row.push(col.trim().to_string());
+                        row_locations.push(Location {
+                            line: line_idx + 1,
+                            column: offset + padding + 1,
+                        });
                     }
+                    offset += col.chars().count() + 1;
                 });
                 row.pop();
+                row_locations.pop();
                 let is_divider = row
                     .iter()
                     .all(|s| s.contains('-') && s.replace('-', "") == "");
                 if !is_divider {
                     rows.push(row);
+                    locations.push(row_locations);
                 }
             } else if !rows.is_empty() {
-                set_table(&mut rows, &mut tables, &current_table);
+                set_table(&mut rows, &mut locations, &mut tables, &current_table);
             } else {
                 if line.contains("`user_groups`") {
                     current_table = CurrentTable::UserGroups;
@@ -93,13 +124,16 @@ where
                 if line.contains("`roles`") {
                     current_table = CurrentTable::Roles;
                 }
+                if line.contains("`operations`") {
+                    current_table = CurrentTable::Operations;
+                }
             }
         }
 
         if !rows.is_empty() {
-            set_table(&mut rows, &mut tables, &current_table);
+            set_table(&mut rows, &mut locations, &mut tables, &current_table);
         }
 
-        tables.try_into()
+        tables
     }
 }
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet};
+
+use qm_role::AccessLevel;
+use strum::IntoEnumIterator;
+
+use crate::model::{Location, Locations, MdTables, INHERITS_HEADER};
+
+const USER_GROUP_COLUMNS: usize = 5;
+
+fn access_levels() -> Vec<String> {
+    AccessLevel::iter()
+        .map(|level| level.as_ref().to_string())
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub location: Location,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+fn location(locations: &Locations, row: usize, col: usize) -> Location {
+    locations
+        .get(row)
+        .and_then(|r| r.get(col).or_else(|| r.last()))
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Checks the tables for mistakes the parser would silently skip,
+/// `external_groups` are user groups defined in other input files.
+pub fn validate(tables: &MdTables, external_groups: &HashSet<&str>) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut push = |location: Location, message: String| {
+        diagnostics.push(Diagnostic { location, message });
+    };
+
+    let user_groups = &tables.user_groups;
+    let ug_locations = &tables.user_groups_locations;
+    let inherits_column = user_groups.column(INHERITS_HEADER);
+    if let Some(col) = inherits_column {
+        if col != USER_GROUP_COLUMNS {
+            push(
+                location(ug_locations, 0, col),
+                format!("`{INHERITS_HEADER}` must be the last column of the user groups table"),
+            );
+        }
+    }
+    let columns = USER_GROUP_COLUMNS + usize::from(inherits_column.is_some());
+    let access_levels = access_levels();
+    let mut group_names = external_groups.clone();
+    for (idx, row) in user_groups.rows.iter().enumerate() {
+        let row_idx = idx + 1;
+        if row.len() != columns {
+            push(
+                location(ug_locations, row_idx, 0),
+                format!(
+                    "user group row has {} columns, expected {columns} (group, name, display name, access levels, allowed types{})",
+                    row.len(),
+                    if inherits_column.is_some() { ", inherits" } else { "" },
+                ),
+            );
+            continue;
+        }
+        if !group_names.insert(row[0].as_str()) {
+            push(
+                location(ug_locations, row_idx, 0),
+                format!("duplicate user group `{}`", row[0]),
+            );
+        }
+        for access_level in row[3].split(',').map(str::trim) {
+            if !access_levels.contains(&access_level.to_lowercase()) {
+                push(
+                    location(ug_locations, row_idx, 3),
+                    format!(
+                        "unknown access level `{access_level}` for user group `{}`, expected one of {}",
+                        row[0],
+                        access_levels.join(", ")
+                    ),
+                );
+            }
+        }
+        if row[4].is_empty() {
+            push(
+                location(ug_locations, row_idx, 4),
+                format!(
+                    "allowed types of user group `{}` are empty, use `none` to allow no types",
+                    row[0]
+                ),
+            );
+        }
+    }
+
+    if inherits_column == Some(USER_GROUP_COLUMNS) {
+        for (idx, row) in user_groups.rows.iter().enumerate() {
+            let Some(inherits) = row.get(USER_GROUP_COLUMNS) else {
+                continue;
+            };
+            for parent in inherits.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                if !group_names.contains(parent) {
+                    push(
+                        location(ug_locations, idx + 1, USER_GROUP_COLUMNS),
+                        format!(
+                            "user group `{}` inherits unknown user group `{parent}`",
+                            row[0]
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    let roles = &tables.roles;
+    let r_locations = &tables.roles_locations;
+    for (idx, header) in roles.headers.iter().enumerate().skip(1) {
+        if !group_names.contains(header.as_str()) {
+            push(
+                location(r_locations, 0, idx),
+                format!("unknown user group column `{header}` in roles table"),
+            );
+        }
+    }
+    let mut seen: HashMap<&str, Location> = HashMap::new();
+    for (idx, row) in roles.rows.iter().enumerate() {
+        let row_idx = idx + 1;
+        let Some(role) = row.first() else {
+            continue;
+        };
+        let loc = location(r_locations, row_idx, 0);
+        if let Some(first) = seen.get(role.as_str()) {
+            push(
+                loc,
+                format!("duplicate role `{role}`, first defined at {first}"),
+            );
+        } else {
+            seen.insert(role.as_str(), loc);
+        }
+        if row.len() != roles.headers.len() {
+            push(
+                loc,
+                format!(
+                    "role row `{role}` has {} columns, expected {}",
+                    row.len(),
+                    roles.headers.len()
+                ),
+            );
+        }
+        for (col_idx, col) in row.iter().enumerate().skip(1) {
+            if !col.is_empty() && col != "x" {
+                push(
+                    location(r_locations, row_idx, col_idx),
+                    format!("invalid mark `{col}` for role `{role}`, expected `x` or empty"),
+                );
+            }
+        }
+    }
+    diagnostics.sort();
+    diagnostics
+}
+
+pub fn check(
+    tables: &MdTables,
+    source: &str,
+    external_groups: &HashSet<&str>,
+) -> anyhow::Result<()> {
+    let diagnostics = validate(tables, external_groups);
+    if diagnostics.is_empty() {
+        return Ok(());
+    }
+    let messages = diagnostics
+        .iter()
+        .map(|d| format!("{source}:{d}"))
+        .collect::<Vec<String>>()
+        .join("\n");
+    anyhow::bail!("invalid role definition:\n{messages}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::Reader;
+
+    const INVALID_INPUT: &str = r#"# User Groups `user_groups`
+
+| Name   | Path    | Display Name | Access Levels   | Allowed Types |
+| ------ | ------- | ------------ | --------------- | ------------- |
+| Admin  | /admin  | Admin        | Admin           | none          |
+| Reader | /reader | Reader       | Customer, Boss  |               |
+
+# Role Mappings `roles`
+
+| Roles       | Admin | Reader | Writer |
+| ----------- | ----- | ------ | ------ |
+| user:list   | x     |        |        |
+| user:view   |       | y      |        |
+| user:list   |       | x      |        |"#;
+
+    #[test]
+    fn test_validate() -> anyhow::Result<()> {
+        let tables = Reader::from_str(INVALID_INPUT).read()?;
+        let diagnostics: Vec<String> = validate(&tables, &HashSet::new())
+            .into_iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "6:37: unknown access level `Boss` for user group `Reader`, expected one of none, admin, support, customer, organization, institution",
+                "6:69: allowed types of user group `Reader` are empty, use `none` to allow no types",
+                "10:34: unknown user group column `Writer` in roles table",
+                "13:25: invalid mark `y` for role `user:view`, expected `x` or empty",
+                "14:3: duplicate role `user:list`, first defined at 12:3",
+            ]
+        );
+        let err = check(&tables, "roles.md", &HashSet::new())
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("roles.md:10:34: unknown user group column `Writer`"));
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     path::Path,
 };
 
+use crate::model::{AUTHENTICATED, PUBLIC};
 use crate::parser::ParseResult;
 
 pub struct WriteResult<W> {
@@ -45,10 +46,21 @@ impl Writer<std::fs::File> {
 }
 
 const ENUM_DERIVE: &str =
-    "#[derive(Clone, Debug, Copy, EnumString, EnumIter, AsRefStr, Ord, PartialOrd, Eq, PartialEq, Hash)]";
+    "#[derive(Clone, Debug, Copy, EnumString, EnumIter, async_graphql::Enum, AsRefStr, Ord, PartialOrd, Eq, PartialEq, Hash)]";
 const ENUM_DERIVE_BUILT_IN_GROUP: &str =
     "#[derive(Clone, Debug, Copy, EnumString, async_graphql::Enum, AsRefStr, Ord, PartialOrd, Eq, PartialEq, Hash)]";
 
+fn role_literal(role: &str) -> String {
+    if let Some((resource, permission)) = role.split_once(':') {
+        let resource = inflector::cases::classcase::to_class_case(resource);
+        let permission = inflector::cases::classcase::to_class_case(permission);
+        format!("qm::role::Role {{ ty: Resource::{resource}, permission: Some(Permission::{permission}) }}")
+    } else {
+        let resource = inflector::cases::classcase::to_class_case(role);
+        format!("qm::role::Role {{ ty: Resource::{resource}, permission: None }}")
+    }
+}
+
 impl<W> Writer<W>
 where
     W: std::io::Write,
@@ -64,8 +76,8 @@ where
             permissions,
             resources,
             role_mappings,
+            operation_mappings,
             user_group_name_mappings,
-            ..
         } = parse_result;
         let user_group_name_mappings =
             BTreeMap::from_iter(user_group_name_mappings.into_iter().map(|v| {
@@ -96,6 +108,8 @@ where
         self.write_line(1, "None,")?;
         self.write_line(0, "}")?;
         self.write_line(0, "")?;
+        self.write_line(0, "qm::role::permission_index!(Permission);")?;
+        self.write_line(0, "")?;
         self.write_line(0, ENUM_DERIVE)?;
         self.write_line(0, "pub enum Resource {")?;
         for resource in resources.iter() {
@@ -150,17 +164,7 @@ where
                     ),
                 )?;
                 for role in role_mapping.roles.iter() {
-                    if let Some((resource, permission)) = role.as_ref().split_once(':') {
-                        let resource = inflector::cases::classcase::to_class_case(resource);
-                        let permission = inflector::cases::classcase::to_class_case(permission);
-                        self.write_line(2, &format!("qm::role::Role::new(Resource::{resource}, Some(Permission::{permission})),"))?;
-                    } else {
-                        let resource = inflector::cases::classcase::to_class_case(role);
-                        self.write_line(
-                            2,
-                            &format!("qm::role::Role::new(Resource::{resource}, None),"),
-                        )?;
-                    }
+                    self.write_line(2, &format!("{},", role_literal(role)))?;
                 }
                 self.write_line(1, "])")?;
                 self.write_line(0, "}")?;
@@ -191,6 +195,19 @@ where
         self.write_line(1, "map")?;
         self.write_line(0, "}")?;
 
+        self.write_line(0, "")?;
+        self.write_line(
+            0,
+            "pub fn role_table() -> &'static qm::role::RoleTable<Resource, Permission> {",
+        )?;
+        self.write_line(
+            1,
+            "static TABLE: std::sync::OnceLock<qm::role::RoleTable<Resource, Permission>> =",
+        )?;
+        self.write_line(2, "std::sync::OnceLock::new();")?;
+        self.write_line(1, "TABLE.get_or_init(|| qm::role::RoleTable::new(roles()))")?;
+        self.write_line(0, "}")?;
+
         self.write_line(0, "")?;
         self.write_line(
             0,
@@ -242,6 +259,93 @@ where
         self.write_line(2, "}")?;
         self.write_line(1, "}")?;
         self.write_line(0, "}")?;
+        self.write_line(0, "")?;
+
+        let mut matrix = vec![];
+        for role_mapping in role_mappings.iter() {
+            if let Some((user_group_name, ..)) =
+                user_group_name_mappings.get(&role_mapping.user_group)
+            {
+                let group = inflector::cases::classcase::to_class_case(user_group_name.as_ref());
+                for role in role_mapping.roles.iter() {
+                    matrix.push((group.clone(), role_literal(role)));
+                }
+            }
+        }
+        self.write_line(
+            0,
+            "pub const PERMISSION_MATRIX: &[(BuiltInGroup, qm::role::Role<Resource, Permission>)] = &[",
+        )?;
+        for (group, role) in matrix.iter() {
+            self.write_line(1, &format!("(BuiltInGroup::{group}, {role}),"))?;
+        }
+        self.write_line(0, "];")?;
+        self.write_line(0, "")?;
+        self.write_line(
+            0,
+            "pub fn operations() -> qm::role::operations::OperationRegistry<Resource, Permission> {",
+        )?;
+        self.write_line(1, "qm::role::operations::OperationRegistry::new()")?;
+        for operation in operation_mappings.iter() {
+            let guard = match operation.roles.first().map(|v| v.as_ref()) {
+                Some(PUBLIC) => "qm::role::operations::Guard::Public".to_string(),
+                Some(AUTHENTICATED) => "qm::role::operations::Guard::Authenticated".to_string(),
+                _ => format!(
+                    "qm::role::operations::Guard::AnyOf(vec![{}])",
+                    operation
+                        .roles
+                        .iter()
+                        .map(|role| role_literal(role))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ),
+            };
+            self.write_line(
+                2,
+                &format!(
+                    ".with(qm::role::operations::OperationType::{}, \"{}\", {guard})",
+                    inflector::cases::classcase::to_class_case(&operation.operation_type),
+                    operation.field
+                ),
+            )?;
+        }
+        self.write_line(0, "}")?;
+        self.write_line(0, "")?;
+        self.write_tests()?;
         Ok(WriteResult { _w: self.w })
     }
+
+    fn write_tests(&mut self) -> anyhow::Result<()> {
+        self.write_line(0, "#[cfg(test)]")?;
+        self.write_line(0, "mod generated_roles_tests {")?;
+        self.write_line(1, "use super::*;")?;
+        self.write_line(1, "use std::str::FromStr;")?;
+        self.write_line(1, "use strum::IntoEnumIterator;")?;
+        self.write_line(0, "")?;
+        for ty in ["Resource", "Permission"] {
+            let fn_name = inflector::cases::snakecase::to_snake_case(ty);
+            self.write_line(1, "#[test]")?;
+            self.write_line(1, &format!("fn test_{fn_name}_from_str_as_ref() {{"))?;
+            self.write_line(2, &format!("for v in {ty}::iter() {{"))?;
+            self.write_line(
+                3,
+                &format!("assert_eq!({ty}::from_str(v.as_ref()), Ok(v));"),
+            )?;
+            self.write_line(2, "}")?;
+            self.write_line(1, "}")?;
+            self.write_line(0, "")?;
+        }
+        self.write_line(1, "#[test]")?;
+        self.write_line(1, "fn test_permission_matrix() {")?;
+        self.write_line(2, "for (group, role) in PERMISSION_MATRIX {")?;
+        self.write_line(
+            3,
+            "let group: qm::role::Group<Resource, Permission> = (*group).into();",
+        )?;
+        self.write_line(3, "assert!(group.resources().contains(&role.to_string()));")?;
+        self.write_line(2, "}")?;
+        self.write_line(1, "}")?;
+        self.write_line(0, "}")?;
+        Ok(())
+    }
 }
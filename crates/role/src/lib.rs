@@ -1,12 +1,19 @@
 use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
 use std::{
-    collections::{BTreeSet, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    marker::PhantomData,
     str::FromStr,
     sync::Arc,
 };
-use strum::{AsRefStr, EnumString};
+use strum::{AsRefStr, EnumIter, EnumString};
 use tokio::sync::RwLock;
 
+pub mod operations;
+pub mod sync;
+pub mod table;
+
+pub use table::RoleTable;
+
 #[macro_export]
 macro_rules! include_roles {
     ($filename:tt) => {
@@ -30,6 +37,7 @@ macro_rules! role {
     Debug,
     Copy,
     EnumString,
+    EnumIter,
     async_graphql::Enum,
     AsRefStr,
     Hash,
@@ -120,7 +128,7 @@ impl FromStr for Access {
                     let ty = AccessLevel::from_str(access)?;
                     return Ok(Access {
                         ty,
-                        id: Some(Arc::from(id.to_string())),
+                        id: Some(Arc::from(id)),
                     });
                 }
             }
@@ -268,7 +276,7 @@ where
                     let ty = AccessLevel::from_str(access)?;
                     return Ok(AccessOrRole::Access(Access {
                         ty,
-                        id: Some(Arc::from(id.to_string())),
+                        id: Some(Arc::from(id)),
                     }));
                 }
             }
@@ -341,6 +349,203 @@ where
         })
 }
 
+/// Position of a permission in the bitset of a [`RoleSet`], implement it
+/// with [`permission_index!`].
+pub trait PermissionIndex {
+    fn index(&self) -> usize;
+}
+
+/// Implements [`PermissionIndex`] for a fieldless enum without explicit
+/// discriminants, the index is the position of the variant.
+#[macro_export]
+macro_rules! permission_index {
+    ($ty:ty) => {
+        impl $crate::PermissionIndex for $ty {
+            #[inline]
+            fn index(&self) -> usize {
+                *self as usize
+            }
+        }
+    };
+}
+
+const RESOURCE_BIT: u64 = 1;
+const WILDCARD: &str = "*";
+
+fn permission_bit<P: PermissionIndex>(p: &P) -> u64 {
+    // bit 0 is reserved for roles without permission, permissions beyond
+    // the 63rd are only granted by wildcards
+    u32::try_from(p.index() + 1)
+        .ok()
+        .and_then(|i| 1_u64.checked_shl(i))
+        .unwrap_or(0)
+}
+
+/// Set of roles with wildcard semantics.
+///
+/// `entity:*` grants every permission of `entity` and any role of the admin
+/// resource (see [`RoleSet::with_admin`]) grants everything. Permissions are
+/// stored as a bitset per resource.
+#[derive(Debug, Clone)]
+pub struct RoleSet<R, P> {
+    admin_resource: Option<R>,
+    is_admin: bool,
+    resources: HashMap<R, u64>,
+    _permission: PhantomData<P>,
+}
+
+impl<R, P> Default for RoleSet<R, P> {
+    fn default() -> Self {
+        Self {
+            admin_resource: None,
+            is_admin: false,
+            resources: HashMap::default(),
+            _permission: PhantomData,
+        }
+    }
+}
+
+impl<R, P> RoleSet<R, P>
+where
+    R: std::hash::Hash + Eq + std::fmt::Debug + std::marker::Copy + Clone,
+    P: PermissionIndex + std::fmt::Debug + std::marker::Copy + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `resource` as admin resource, holding any role of it satisfies
+    /// every role.
+    pub fn with_admin(mut self, resource: R) -> Self {
+        self.is_admin = self.is_admin || self.resources.contains_key(&resource);
+        self.admin_resource = Some(resource);
+        self
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.is_admin
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub fn insert(&mut self, role: Role<R, P>) {
+        let bit = role
+            .permission
+            .as_ref()
+            .map(permission_bit)
+            .unwrap_or(RESOURCE_BIT);
+        self.insert_bits(role.ty, bit);
+    }
+
+    /// Grants all permissions of `resource`.
+    pub fn insert_wildcard(&mut self, resource: R) {
+        self.insert_bits(resource, u64::MAX);
+    }
+
+    fn insert_bits(&mut self, resource: R, bits: u64) {
+        if self.admin_resource.as_ref() == Some(&resource) {
+            self.is_admin = true;
+        }
+        *self.resources.entry(resource).or_default() |= bits;
+    }
+
+    pub fn satisfies(&self, role: &Role<R, P>) -> bool {
+        if self.is_admin {
+            return true;
+        }
+        let Some(bits) = self.resources.get(&role.ty) else {
+            return false;
+        };
+        if *bits == u64::MAX {
+            return true;
+        }
+        let bit = role
+            .permission
+            .as_ref()
+            .map(permission_bit)
+            .unwrap_or(RESOURCE_BIT);
+        bit != 0 && bits & bit == bit
+    }
+
+    pub fn satisfies_all<'a, I>(&self, roles: I) -> bool
+    where
+        I: IntoIterator<Item = &'a Role<R, P>>,
+        R: 'a,
+        P: 'a,
+    {
+        roles.into_iter().all(|role| self.satisfies(role))
+    }
+}
+
+impl<R, P> RoleSet<R, P>
+where
+    R: FromStr<Err = strum::ParseError>
+        + std::hash::Hash
+        + Eq
+        + std::fmt::Debug
+        + std::marker::Copy
+        + Clone,
+    P: FromStr<Err = strum::ParseError>
+        + PermissionIndex
+        + std::fmt::Debug
+        + std::marker::Copy
+        + Clone,
+{
+    /// Parses the roles of a token, access roles and unknown roles are
+    /// ignored.
+    pub fn parse(roles: &[Arc<str>], admin: Option<R>) -> Self {
+        let mut result = Self {
+            admin_resource: admin,
+            ..Default::default()
+        };
+        for role in roles.iter() {
+            result.insert_str(role);
+        }
+        result
+    }
+
+    pub(crate) fn insert_str(&mut self, role: &str) {
+        match role.split_once(':') {
+            Some((resource, WILDCARD)) => {
+                if let Ok(resource) = R::from_str(resource) {
+                    self.insert_wildcard(resource);
+                }
+            }
+            _ => {
+                if let Ok(AccessOrRole::Role(role)) = AccessOrRole::<R, P>::from_str(role) {
+                    self.insert(role);
+                }
+            }
+        }
+    }
+}
+
+impl<R, P> Extend<Role<R, P>> for RoleSet<R, P>
+where
+    R: std::hash::Hash + Eq + std::fmt::Debug + std::marker::Copy + Clone,
+    P: PermissionIndex + std::fmt::Debug + std::marker::Copy + Clone,
+{
+    fn extend<I: IntoIterator<Item = Role<R, P>>>(&mut self, iter: I) {
+        for role in iter {
+            self.insert(role);
+        }
+    }
+}
+
+impl<R, P> FromIterator<Role<R, P>> for RoleSet<R, P>
+where
+    R: std::hash::Hash + Eq + std::fmt::Debug + std::marker::Copy + Clone,
+    P: PermissionIndex + std::fmt::Debug + std::marker::Copy + Clone,
+{
+    fn from_iter<I: IntoIterator<Item = Role<R, P>>>(iter: I) -> Self {
+        let mut result = Self::default();
+        result.extend(iter);
+        result
+    }
+}
+
 pub struct Group<R, P>
 where
     R: std::fmt::Debug + std::marker::Copy + Clone,
@@ -383,6 +588,70 @@ where
     }
 }
 
+/// Group which may only be assigned in contexts of certain types.
+pub trait AssignableGroup {
+    fn group_name(&self) -> &str;
+    /// Allowed context types, no types means the group is not restricted.
+    fn assignable_types(&self) -> Vec<&str>;
+}
+
+impl<R, P> AssignableGroup for Group<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    fn group_name(&self) -> &str {
+        &self.name
+    }
+
+    fn assignable_types(&self) -> Vec<&str> {
+        self.allowed_types.iter().map(String::as_str).collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssignmentError {
+    pub group: String,
+    pub context_type: Option<String>,
+    pub allowed_types: Vec<String>,
+}
+
+impl std::fmt::Display for AssignmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the group '{}' can not be assigned ", self.group)?;
+        match self.context_type.as_deref() {
+            Some(ty) => write!(f, "in a context of type '{ty}'")?,
+            None => f.write_str("without a typed context")?,
+        }
+        write!(f, ", allowed types: {}", self.allowed_types.join(", "))
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+/// Fails if `group` must not be assigned to a user in a context of type
+/// `context_ty`. Empty types and `none` are ignored, as they are written by
+/// the realm setup for unrestricted groups.
+pub fn validate_assignment<G>(group: &G, context_ty: Option<&str>) -> Result<(), AssignmentError>
+where
+    G: AssignableGroup + ?Sized,
+{
+    let allowed_types: Vec<&str> = group
+        .assignable_types()
+        .into_iter()
+        .map(str::trim)
+        .filter(|ty| !ty.is_empty() && *ty != "none")
+        .collect();
+    if allowed_types.is_empty() || context_ty.is_some_and(|ty| allowed_types.contains(&ty)) {
+        return Ok(());
+    }
+    Err(AssignmentError {
+        group: group.group_name().to_string(),
+        context_type: context_ty.map(str::to_string),
+        allowed_types: allowed_types.into_iter().map(str::to_string).collect(),
+    })
+}
+
 impl<R, P> Group<R, P>
 where
     R: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
@@ -433,7 +702,7 @@ impl<T> AuthContainer<T> {
 impl<T> From<&axum::http::HeaderValue> for AuthContainer<T> {
     fn from(value: &axum::http::HeaderValue) -> Self {
         if let Ok(token) = value.to_str() {
-            if let Some(stripped) = token.strip_prefix("Bearer ") {
+            if let Some(stripped) = token.strip_prefix(BEARER_PREFIX) {
                 return Self::new(stripped);
             }
         }
@@ -441,6 +710,179 @@ impl<T> From<&axum::http::HeaderValue> for AuthContainer<T> {
     }
 }
 
+const BEARER_PREFIX: &str = "Bearer ";
+/// Default name of the cookie and query parameter holding the access token.
+pub const ACCESS_TOKEN: &str = "access_token";
+/// Prefix of the `Sec-WebSocket-Protocol` entry holding the access token,
+/// e.g. `Sec-WebSocket-Protocol: graphql-transport-ws, bearer.<token>`.
+pub const WS_PROTOCOL_TOKEN_PREFIX: &str = "bearer.";
+
+/// Location of the access token in a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    /// `Authorization: Bearer <token>` header
+    Header,
+    /// Cookie with the given name, only accepted for same-origin requests,
+    /// i.e. if the `Origin` or `Referer` header matches the `Host`. The
+    /// cookie has to be issued with `SameSite=Strict` or `SameSite=Lax`.
+    Cookie(&'static str),
+    /// `Sec-WebSocket-Protocol` entry prefixed with [`WS_PROTOCOL_TOKEN_PREFIX`]
+    WebSocketProtocol,
+    /// Query parameter, only meant for websocket upgrades where browsers
+    /// can't set headers. Query strings end up in access logs.
+    Query(&'static str),
+}
+
+/// Token sources tried by the [`axum::extract::FromRequestParts`] impl of
+/// [`AuthContainer`] unless the request has a [`TokenSources`] extension.
+pub const DEFAULT_TOKEN_SOURCES: [TokenSource; 1] = [TokenSource::Header];
+
+/// Token sources of websocket upgrade requests, in order.
+pub const WEBSOCKET_TOKEN_SOURCES: [TokenSource; 3] = [
+    TokenSource::Header,
+    TokenSource::WebSocketProtocol,
+    TokenSource::Query(ACCESS_TOKEN),
+];
+
+/// Token sources of a route, added as `axum::Extension` layer to opt in to
+/// e.g. cookie authentication.
+///
+/// ```ignore
+/// router.layer(axum::Extension(TokenSources::new([
+///     TokenSource::Header,
+///     TokenSource::Cookie(ACCESS_TOKEN),
+/// ])))
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenSources(Arc<[TokenSource]>);
+
+impl TokenSources {
+    pub fn new(sources: impl IntoIterator<Item = TokenSource>) -> Self {
+        Self(sources.into_iter().collect())
+    }
+}
+
+impl std::ops::Deref for TokenSources {
+    type Target = [TokenSource];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Authority of an `Origin` or `Referer` header value, e.g. `example.com:8080`.
+fn authority(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    rest.split(['/', '?', '#']).next().filter(|v| !v.is_empty())
+}
+
+/// True if the `Origin`, or the `Referer` without an `Origin`, of the
+/// request matches its `Host`.
+pub fn is_same_origin(headers: &axum::http::HeaderMap) -> bool {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let Some(host) = header(axum::http::header::HOST) else {
+        return false;
+    };
+    header(axum::http::header::ORIGIN)
+        .or_else(|| header(axum::http::header::REFERER))
+        .and_then(authority)
+        .is_some_and(|authority| authority.eq_ignore_ascii_case(host))
+}
+
+impl TokenSource {
+    pub fn token<'a>(
+        &self,
+        headers: &'a axum::http::HeaderMap,
+        uri: &'a axum::http::Uri,
+    ) -> Option<&'a str> {
+        match self {
+            TokenSource::Header => headers
+                .get(axum::http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.strip_prefix(BEARER_PREFIX)),
+            TokenSource::Cookie(_) if !is_same_origin(headers) => None,
+            TokenSource::Cookie(name) => headers
+                .get_all(axum::http::header::COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|v| v.trim().split_once('='))
+                .find_map(|(k, v)| (k == *name).then_some(v)),
+            TokenSource::WebSocketProtocol => headers
+                .get_all(axum::http::header::SEC_WEBSOCKET_PROTOCOL)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .find_map(|v| v.trim().strip_prefix(WS_PROTOCOL_TOKEN_PREFIX)),
+            TokenSource::Query(name) => uri
+                .query()?
+                .split('&')
+                .filter_map(|v| v.split_once('='))
+                .find_map(|(k, v)| (k == *name).then_some(v)),
+        }
+        .filter(|v| !v.is_empty())
+    }
+}
+
+impl<T> AuthContainer<T> {
+    /// Creates the container from the first of `sources` providing a token.
+    pub fn from_sources(
+        headers: &axum::http::HeaderMap,
+        uri: &axum::http::Uri,
+        sources: &[TokenSource],
+    ) -> Self {
+        sources
+            .iter()
+            .find_map(|source| source.token(headers, uri))
+            .map(Self::new)
+            .unwrap_or_default()
+    }
+
+    pub fn from_cookie(headers: &axum::http::HeaderMap, name: &'static str) -> Self {
+        Self::from_sources(
+            headers,
+            &axum::http::Uri::default(),
+            &[TokenSource::Cookie(name)],
+        )
+    }
+
+    pub fn from_websocket_protocol(headers: &axum::http::HeaderMap) -> Self {
+        Self::from_sources(
+            headers,
+            &axum::http::Uri::default(),
+            &[TokenSource::WebSocketProtocol],
+        )
+    }
+
+    pub fn from_query(uri: &axum::http::Uri, name: &'static str) -> Self {
+        Self::from_sources(
+            &axum::http::HeaderMap::default(),
+            uri,
+            &[TokenSource::Query(name)],
+        )
+    }
+}
+
+#[axum::async_trait]
+impl<S, T> axum::extract::FromRequestParts<S> for AuthContainer<T>
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut axum::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let sources = parts
+            .extensions
+            .get::<TokenSources>()
+            .map(|sources| &sources[..])
+            .unwrap_or(&DEFAULT_TOKEN_SOURCES);
+        Ok(Self::from_sources(&parts.headers, &parts.uri, sources))
+    }
+}
+
 impl<T> Default for AuthContainer<T> {
     fn default() -> Self {
         Self {
@@ -451,3 +893,158 @@ impl<T> Default for AuthContainer<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Copy, EnumString, EnumIter, AsRefStr, Eq, PartialEq, Hash)]
+    enum Resource {
+        #[strum(serialize = "administration")]
+        Administration,
+        #[strum(serialize = "entity")]
+        Entity,
+        #[strum(serialize = "user")]
+        User,
+    }
+
+    #[derive(Clone, Debug, Copy, EnumString, EnumIter, AsRefStr, Eq, PartialEq, Hash)]
+    enum Permission {
+        #[strum(serialize = "list")]
+        List,
+        #[strum(serialize = "view")]
+        View,
+        #[strum(serialize = "update")]
+        Update,
+    }
+
+    crate::permission_index!(Permission);
+
+    fn roles(roles: &[&str]) -> Vec<Arc<str>> {
+        roles.iter().map(|&r| Arc::from(r)).collect()
+    }
+
+    #[test]
+    fn test_token_sources() {
+        use axum::http::{header, HeaderMap, HeaderValue, Uri};
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("theme=dark; access_token=c.o.okie"),
+        );
+        headers.insert(
+            header::SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static("graphql-transport-ws, bearer.w.s"),
+        );
+        let uri: Uri = "/graphql?x=1&access_token=q.u.ery".parse().unwrap();
+        fn token<'a>(headers: &'a HeaderMap, uri: &'a Uri, source: TokenSource) -> Option<&'a str> {
+            source.token(headers, uri)
+        }
+        assert_eq!(token(&headers, &uri, TokenSource::Header), None);
+        // cookies are only accepted from the same origin
+        assert_eq!(
+            token(&headers, &uri, TokenSource::Cookie(ACCESS_TOKEN)),
+            None
+        );
+        headers.insert(header::HOST, HeaderValue::from_static("api.example.com"));
+        headers.insert(
+            header::ORIGIN,
+            HeaderValue::from_static("https://evil.example.com"),
+        );
+        assert_eq!(
+            token(&headers, &uri, TokenSource::Cookie(ACCESS_TOKEN)),
+            None
+        );
+        headers.insert(
+            header::ORIGIN,
+            HeaderValue::from_static("https://api.example.com"),
+        );
+        assert_eq!(
+            token(&headers, &uri, TokenSource::Cookie(ACCESS_TOKEN)),
+            Some("c.o.okie")
+        );
+        assert_eq!(
+            token(&headers, &uri, TokenSource::WebSocketProtocol),
+            Some("w.s")
+        );
+        assert_eq!(
+            token(&headers, &uri, TokenSource::Query(ACCESS_TOKEN)),
+            Some("q.u.ery")
+        );
+        let auth = AuthContainer::<()>::from_sources(&headers, &uri, &DEFAULT_TOKEN_SOURCES);
+        assert!(!auth.has_encoded());
+        let auth = AuthContainer::<()>::from_sources(&headers, &uri, &WEBSOCKET_TOKEN_SOURCES);
+        assert_eq!(auth.encoded(), Some("w.s"));
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer h"));
+        let auth = AuthContainer::<()>::from_sources(&headers, &uri, &DEFAULT_TOKEN_SOURCES);
+        assert_eq!(auth.encoded(), Some("h"));
+        assert!(!AuthContainer::<()>::from_query(&Uri::default(), ACCESS_TOKEN).has_encoded());
+    }
+
+    #[test]
+    fn test_permission_index() {
+        use strum::IntoEnumIterator;
+        for (i, p) in Permission::iter().enumerate() {
+            assert_eq!(p.index(), i);
+        }
+    }
+
+    #[test]
+    fn test_role_set_exact() {
+        let set = RoleSet::<Resource, Permission>::parse(
+            &roles(&["entity:list", "user", "customer:access@1"]),
+            Some(Resource::Administration),
+        );
+        assert!(set.satisfies(&role!(Resource::Entity, Permission::List)));
+        assert!(!set.satisfies(&role!(Resource::Entity, Permission::View)));
+        assert!(!set.satisfies(&role!(Resource::Entity)));
+        assert!(set.satisfies(&role!(Resource::User)));
+        assert!(!set.satisfies(&role!(Resource::User, Permission::Update)));
+        assert!(!set.is_admin());
+    }
+
+    #[test]
+    fn test_role_set_wildcard() {
+        let set = RoleSet::<Resource, Permission>::parse(&roles(&["entity:*"]), None);
+        assert!(set.satisfies(&role!(Resource::Entity)));
+        assert!(set.satisfies_all(&[
+            role!(Resource::Entity, Permission::List),
+            role!(Resource::Entity, Permission::Update),
+        ]));
+        assert!(!set.satisfies(&role!(Resource::User, Permission::List)));
+    }
+
+    #[test]
+    fn test_role_set_admin() {
+        let set: RoleSet<Resource, Permission> =
+            [role!(Resource::Administration)].into_iter().collect();
+        assert!(!set.satisfies(&role!(Resource::User, Permission::View)));
+        let set = set.with_admin(Resource::Administration);
+        assert!(set.is_admin());
+        assert!(set.satisfies(&role!(Resource::User, Permission::View)));
+    }
+
+    #[test]
+    fn test_validate_assignment() {
+        let group = |types: &[&str]| {
+            Group::<Resource, Permission>::new(
+                "Reader".into(),
+                "/reader".into(),
+                vec![AccessLevel::Institution],
+                types.iter().map(|t| t.to_string()).collect(),
+                vec![],
+            )
+        };
+        assert!(validate_assignment(&group(&[]), None).is_ok());
+        assert!(validate_assignment(&group(&[""]), Some("school")).is_ok());
+        let restricted = group(&["school", "kindergarten"]);
+        assert!(validate_assignment(&restricted, Some("school")).is_ok());
+        let err = validate_assignment(&restricted, Some("clinic")).unwrap_err();
+        assert_eq!(err.allowed_types, ["school", "kindergarten"]);
+        assert_eq!(
+            err.to_string(),
+            "the group 'Reader' can not be assigned in a context of type 'clinic', allowed types: school, kindergarten"
+        );
+        assert!(validate_assignment(&restricted, None).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Registry of the roles required by the top-level fields of a schema.
+//!
+//! The registry is generated by `qm-role-build` from the `operations` table
+//! and enforced by the operation guard of `qm-server`. Fields without an
+//! entry are denied, so a new resolver can't be exposed without a guard.
+
+use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+
+use crate::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+impl OperationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Query => "query",
+            Self::Mutation => "mutation",
+            Self::Subscription => "subscription",
+        }
+    }
+}
+
+impl FromStr for OperationType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "query" => Ok(Self::Query),
+            "mutation" => Ok(Self::Mutation),
+            "subscription" => Ok(Self::Subscription),
+            _ => Err(format!("invalid operation type '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for OperationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Requirement of a top-level field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Guard<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    /// Allowed without authentication.
+    Public,
+    /// Allowed for every authenticated user.
+    Authenticated,
+    /// Allowed for users holding any of the roles.
+    AnyOf(Vec<Role<R, P>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct OperationRegistry<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    guards: BTreeMap<OperationType, BTreeMap<Arc<str>, Guard<R, P>>>,
+}
+
+impl<R, P> Default for OperationRegistry<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    fn default() -> Self {
+        Self {
+            guards: BTreeMap::new(),
+        }
+    }
+}
+
+impl<R, P> OperationRegistry<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, ty: OperationType, field: &str, guard: Guard<R, P>) -> Self {
+        self.insert(ty, field, guard);
+        self
+    }
+
+    /// Registers `guard` for the top-level `field`, replacing a previous one.
+    pub fn insert(&mut self, ty: OperationType, field: &str, guard: Guard<R, P>) {
+        self.guards
+            .entry(ty)
+            .or_default()
+            .insert(Arc::from(field), guard);
+    }
+
+    /// Adds the guards of `other`, e.g. of fields provided by a library.
+    pub fn extend(&mut self, other: Self) {
+        for (ty, guards) in other.guards {
+            self.guards.entry(ty).or_default().extend(guards);
+        }
+    }
+
+    /// Guard of a top-level field, `None` if it must be denied.
+    pub fn get(&self, ty: OperationType, field: &str) -> Option<&Guard<R, P>> {
+        self.guards.get(&ty)?.get(field)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (OperationType, &str, &Guard<R, P>)> {
+        self.guards.iter().flat_map(|(ty, guards)| {
+            guards
+                .iter()
+                .map(|(field, guard)| (*ty, field.as_ref(), guard))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.guards.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+    enum Resource {
+        Customer,
+    }
+
+    #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+    enum Permission {
+        List,
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = OperationRegistry::new()
+            .with(OperationType::Query, "health", Guard::Public)
+            .with(
+                OperationType::Query,
+                "customers",
+                Guard::AnyOf(vec![Role::new(Resource::Customer, Some(Permission::List))]),
+            );
+        assert_eq!(
+            registry.get(OperationType::Query, "health"),
+            Some(&Guard::Public)
+        );
+        assert!(registry.get(OperationType::Mutation, "health").is_none());
+        assert!(registry.get(OperationType::Query, "users").is_none());
+        assert_eq!(registry.len(), 2);
+        assert_eq!("Mutation".parse(), Ok(OperationType::Mutation));
+    }
+}
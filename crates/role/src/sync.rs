@@ -0,0 +1,293 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+};
+
+use crate::{AccessLevel, Group};
+
+/// Roles Keycloak creates for every realm, never reported as stale.
+const DEFAULT_ROLES: [&str; 2] = ["offline_access", "uma_authorization"];
+const DEFAULT_ROLES_PREFIX: &str = "default-roles-";
+/// Marker of the roles created per tenant like `customer:access@1`, they
+/// are not part of the generated groups and never stale.
+const ACCESS_ROLE_MARKER: &str = "access@";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    CreateRole(String),
+    CreateGroup {
+        path: String,
+        name: String,
+        allowed_access_levels: Vec<AccessLevel>,
+        allowed_types: Vec<String>,
+    },
+    AddMapping {
+        group: String,
+        role: String,
+    },
+    RemoveMapping {
+        group: String,
+        role: String,
+    },
+    DeleteRole(String),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::CreateRole(role) => write!(f, "+ role {role}"),
+            Change::CreateGroup { path, .. } => write!(f, "+ group {path}"),
+            Change::AddMapping { group, role } => write!(f, "+ mapping {group} -> {role}"),
+            Change::RemoveMapping { group, role } => write!(f, "- mapping {group} -> {role}"),
+            Change::DeleteRole(role) => write!(f, "- role {role}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Removes stale roles and mappings instead of only reporting them.
+    pub prune: bool,
+    /// Only computes the plan without applying it.
+    pub dry_run: bool,
+    /// Additional realm roles which are not managed by the generated groups.
+    pub ignore_roles: BTreeSet<String>,
+}
+
+impl SyncOptions {
+    fn is_ignored(&self, role: &str) -> bool {
+        DEFAULT_ROLES.contains(&role)
+            || role.starts_with(DEFAULT_ROLES_PREFIX)
+            || role.contains(ACCESS_ROLE_MARKER)
+            || self.ignore_roles.contains(role)
+    }
+}
+
+/// Realm roles and realm role mappings of the managed groups.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RealmState {
+    pub roles: BTreeSet<String>,
+    /// Role mappings by group path, `None` if the group does not exist.
+    pub groups: BTreeMap<String, Option<BTreeSet<String>>>,
+}
+
+/// Result of a reconciliation, `stale` lists the differences which are
+/// only removed with [`SyncOptions::prune`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+    pub stale: Vec<Change>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.stale.is_empty()
+    }
+}
+
+impl std::fmt::Display for Plan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{change}")?;
+        }
+        for change in self.stale.iter() {
+            writeln!(f, "{change} (stale)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Access to the live realm, implemented for `qm_keycloak::Keycloak`.
+pub trait RealmBackend: Sync {
+    fn roles(&self, realm: &str) -> impl Future<Output = anyhow::Result<BTreeSet<String>>> + Send;
+
+    fn group_roles(
+        &self,
+        realm: &str,
+        path: &str,
+    ) -> impl Future<Output = anyhow::Result<Option<BTreeSet<String>>>> + Send;
+
+    fn apply(
+        &self,
+        realm: &str,
+        change: &Change,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+}
+
+/// Computes the changes to turn `live` into the state defined by `groups`.
+pub fn diff<R, P>(groups: &[Group<R, P>], live: &RealmState, options: &SyncOptions) -> Plan
+where
+    R: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
+    P: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
+{
+    let mut plan = Plan::default();
+    let desired_roles: BTreeSet<String> = groups.iter().flat_map(|g| g.resources()).collect();
+    for role in desired_roles.iter() {
+        if !live.roles.contains(role) {
+            plan.changes.push(Change::CreateRole(role.clone()));
+        }
+    }
+    for group in groups {
+        let desired: BTreeSet<String> = group.resources().into_iter().collect();
+        let existing = live.groups.get(&group.path).cloned().flatten();
+        if existing.is_none() {
+            plan.changes.push(Change::CreateGroup {
+                path: group.path.clone(),
+                name: group.name.clone(),
+                allowed_access_levels: group.allowed_access_levels().to_vec(),
+                allowed_types: group.allowed_types().to_vec(),
+            });
+        }
+        let existing = existing.unwrap_or_default();
+        for role in desired.difference(&existing) {
+            plan.changes.push(Change::AddMapping {
+                group: group.path.clone(),
+                role: role.clone(),
+            });
+        }
+        for role in existing.difference(&desired) {
+            if !options.is_ignored(role) {
+                plan.stale.push(Change::RemoveMapping {
+                    group: group.path.clone(),
+                    role: role.clone(),
+                });
+            }
+        }
+    }
+    for role in live.roles.difference(&desired_roles) {
+        if !options.is_ignored(role) {
+            plan.stale.push(Change::DeleteRole(role.clone()));
+        }
+    }
+    plan
+}
+
+pub async fn load<B, R, P>(
+    backend: &B,
+    realm: &str,
+    groups: &[Group<R, P>],
+) -> anyhow::Result<RealmState>
+where
+    B: RealmBackend,
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    let mut state = RealmState {
+        roles: backend.roles(realm).await?,
+        groups: BTreeMap::new(),
+    };
+    for group in groups {
+        let roles = backend.group_roles(realm, &group.path).await?;
+        state.groups.insert(group.path.clone(), roles);
+    }
+    Ok(state)
+}
+
+/// Reconciles the realm with the generated `groups` and returns the plan,
+/// stale roles and mappings are only removed with [`SyncOptions::prune`].
+///
+/// ```ignore
+/// let plan = qm::role::sync::sync(&keycloak, "my-realm", &roles::groups(), &Default::default()).await?;
+/// tracing::info!("{plan}");
+/// ```
+pub async fn sync<B, R, P>(
+    backend: &B,
+    realm: &str,
+    groups: &[Group<R, P>],
+    options: &SyncOptions,
+) -> anyhow::Result<Plan>
+where
+    B: RealmBackend,
+    R: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
+    P: AsRef<str> + std::fmt::Debug + std::marker::Copy + Clone,
+{
+    let live = load(backend, realm, groups).await?;
+    let plan = diff(groups, &live, options);
+    if options.dry_run {
+        return Ok(plan);
+    }
+    for change in plan.changes.iter() {
+        backend.apply(realm, change).await?;
+    }
+    if options.prune {
+        for change in plan.stale.iter() {
+            backend.apply(realm, change).await?;
+        }
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Role;
+
+    fn group(path: &str, roles: &[&'static str]) -> Group<&'static str, &'static str> {
+        Group::new(
+            path.trim_start_matches('/').to_string(),
+            path.to_string(),
+            vec![AccessLevel::Customer],
+            vec![],
+            roles
+                .iter()
+                .map(|r| match r.split_once(':') {
+                    Some((ty, p)) => Role::new(ty, Some(p)),
+                    None => Role::new(*r, None),
+                })
+                .collect(),
+        )
+    }
+
+    fn set(v: &[&str]) -> BTreeSet<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let groups = vec![
+            group("/app/owner", &["user:list", "user:view"]),
+            group("/app/reader", &["user:view"]),
+        ];
+        let live = RealmState {
+            roles: set(&[
+                "user:view",
+                "user:remove",
+                "customer:access@1",
+                "offline_access",
+                "default-roles-test",
+            ]),
+            groups: BTreeMap::from_iter([
+                (
+                    "/app/owner".to_string(),
+                    Some(set(&[
+                        "user:view",
+                        "user:remove",
+                        "institution:access@1:2:3",
+                    ])),
+                ),
+                ("/app/reader".to_string(), None),
+            ]),
+        };
+        let plan = diff(&groups, &live, &SyncOptions::default());
+        assert_eq!(
+            plan.to_string(),
+            "+ role user:list\n\
+             + mapping /app/owner -> user:list\n\
+             + group /app/reader\n\
+             + mapping /app/reader -> user:view\n\
+             - mapping /app/owner -> user:remove (stale)\n\
+             - role user:remove (stale)\n"
+        );
+        let live = RealmState {
+            roles: set(&["user:list", "user:view"]),
+            groups: BTreeMap::from_iter([
+                (
+                    "/app/owner".to_string(),
+                    Some(set(&["user:list", "user:view"])),
+                ),
+                ("/app/reader".to_string(), Some(set(&["user:view"]))),
+            ]),
+        };
+        assert!(diff(&groups, &live, &SyncOptions::default()).is_empty());
+    }
+}
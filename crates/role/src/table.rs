@@ -0,0 +1,178 @@
+//! Lookup table of the known roles of a service.
+//!
+//! Token roles are parsed on every request. A [`RoleTable`] built once from
+//! the roles generated by `qm-role-build` maps them to their [`Role`] by a
+//! single hash lookup instead of splitting and parsing every role string.
+//! Roles which are not in the table, e.g. access roles, are parsed as
+//! before.
+
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use crate::{AccessOrRole, ParseResult, PermissionIndex, Role, RoleSet, WILDCARD};
+
+#[derive(Debug, Clone, Copy)]
+enum Entry<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    Role(Role<R, P>),
+    Wildcard(R),
+}
+
+#[derive(Debug, Clone)]
+pub struct RoleTable<R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    entries: HashMap<Box<str>, Entry<R, P>>,
+}
+
+impl<R, P> RoleTable<R, P>
+where
+    R: FromStr<Err = strum::ParseError>
+        + Ord
+        + std::hash::Hash
+        + std::fmt::Debug
+        + std::marker::Copy
+        + Clone,
+    P: FromStr<Err = strum::ParseError>
+        + Ord
+        + PermissionIndex
+        + std::hash::Hash
+        + std::fmt::Debug
+        + std::marker::Copy
+        + Clone,
+{
+    /// Builds the table from role strings like `entity:list`, invalid roles
+    /// are skipped. The wildcard `<resource>:*` of every resource is added.
+    pub fn new<I, S>(roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut entries = HashMap::new();
+        for role in roles {
+            let role = role.as_ref();
+            let Ok(parsed) = Role::<R, P>::from_str(role) else {
+                continue;
+            };
+            let resource = role.split_once(':').map(|(r, _)| r).unwrap_or(role);
+            entries
+                .entry(format!("{resource}:{WILDCARD}").into_boxed_str())
+                .or_insert(Entry::Wildcard(parsed.ty));
+            entries.insert(Box::from(role), Entry::Role(parsed));
+        }
+        Self { entries }
+    }
+
+    pub fn get(&self, role: &str) -> Option<&Role<R, P>> {
+        match self.entries.get(role)? {
+            Entry::Role(role) => Some(role),
+            Entry::Wildcard(_) => None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Same result as [`crate::parse`].
+    pub fn parse(&self, roles: &[Arc<str>]) -> ParseResult<R, P> {
+        let mut result = ParseResult::default();
+        for role in roles.iter() {
+            match self.entries.get(role.as_ref()) {
+                Some(Entry::Role(role)) => {
+                    result.roles.insert(*role);
+                }
+                Some(Entry::Wildcard(_)) => {}
+                None => match AccessOrRole::<R, P>::from_str(role) {
+                    Ok(AccessOrRole::Access(access)) => {
+                        result.access.insert(access);
+                    }
+                    Ok(AccessOrRole::Role(role)) => {
+                        result.roles.insert(role);
+                    }
+                    Err(_) => {}
+                },
+            }
+        }
+        result
+    }
+
+    /// Same result as [`RoleSet::parse`].
+    pub fn role_set(&self, roles: &[Arc<str>], admin: Option<R>) -> RoleSet<R, P> {
+        let mut result = RoleSet {
+            admin_resource: admin,
+            ..Default::default()
+        };
+        for role in roles.iter() {
+            match self.entries.get(role.as_ref()) {
+                Some(Entry::Role(role)) => result.insert(*role),
+                Some(Entry::Wildcard(resource)) => result.insert_wildcard(*resource),
+                None => result.insert_str(role),
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::{AsRefStr, EnumIter, EnumString};
+
+    use super::*;
+    use crate::role;
+
+    #[derive(
+        Clone, Debug, Copy, EnumString, EnumIter, AsRefStr, Eq, PartialEq, Hash, Ord, PartialOrd,
+    )]
+    enum Resource {
+        #[strum(serialize = "administration")]
+        Administration,
+        #[strum(serialize = "entity")]
+        Entity,
+    }
+
+    #[derive(
+        Clone, Debug, Copy, EnumString, EnumIter, AsRefStr, Eq, PartialEq, Hash, Ord, PartialOrd,
+    )]
+    enum Permission {
+        #[strum(serialize = "list")]
+        List,
+        #[strum(serialize = "update")]
+        Update,
+    }
+
+    crate::permission_index!(Permission);
+
+    #[test]
+    fn test_table() {
+        let table = RoleTable::<Resource, Permission>::new(["entity:list", "administration", "x"]);
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            table.get("entity:list"),
+            Some(&role!(Resource::Entity, Permission::List))
+        );
+        assert!(table.get("entity:*").is_none());
+
+        let roles: Vec<Arc<str>> = ["entity:list", "entity:update", "customer:access@1", "x"]
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+        let parsed = table.parse(&roles);
+        let expected = crate::parse::<Resource, Permission>(&roles);
+        assert_eq!(parsed.roles, expected.roles);
+        assert_eq!(parsed.access, expected.access);
+        assert_eq!(parsed.roles.len(), 2);
+
+        let set = table.role_set(&[Arc::from("entity:*")], Some(Resource::Administration));
+        assert!(set.satisfies(&role!(Resource::Entity, Permission::Update)));
+        assert!(!set.is_admin());
+    }
+}
@@ -0,0 +1,275 @@
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use url::Url;
+
+use crate::{
+    error_for_status,
+    sign::{content_md5, sha256_hex, CONTENT_MD5, EMPTY_PAYLOAD},
+    Result, S3,
+};
+
+/// Removes objects below `prefix` after `days`.
+#[derive(Debug, Clone)]
+pub struct LifecycleRule {
+    pub id: String,
+    pub prefix: String,
+    pub expiration_days: u32,
+}
+
+impl LifecycleRule {
+    pub fn expire(prefix: impl Into<String>, days: u32) -> Self {
+        let prefix = prefix.into();
+        Self {
+            id: format!("expire-{}", prefix.trim_end_matches('/')),
+            prefix,
+            expiration_days: days,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CorsRule {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub expose_headers: Vec<String>,
+    pub max_age_seconds: Option<u32>,
+}
+
+impl CorsRule {
+    /// Allows browsers on `origins` to use presigned `GET` and `PUT` URLs.
+    pub fn presigned<I, S>(origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_origins: origins.into_iter().map(Into::into).collect(),
+            allowed_methods: vec!["GET".into(), "PUT".into(), "HEAD".into()],
+            allowed_headers: vec!["*".into()],
+            expose_headers: vec!["ETag".into()],
+            max_age_seconds: Some(3600),
+        }
+    }
+}
+
+/// Desired configuration of a bucket, see [`S3::ensure_bucket`].
+///
+/// Only settings that are set are applied, empty rule lists leave the
+/// existing lifecycle and CORS configuration untouched.
+#[derive(Debug, Clone, Default)]
+pub struct BucketSpec {
+    pub versioning: Option<bool>,
+    pub lifecycle: Vec<LifecycleRule>,
+    pub cors: Vec<CorsRule>,
+    /// Bucket policy as JSON document.
+    pub policy: Option<String>,
+}
+
+impl BucketSpec {
+    pub fn with_versioning(mut self, enabled: bool) -> Self {
+        self.versioning = Some(enabled);
+        self
+    }
+
+    pub fn with_lifecycle_rule(mut self, rule: LifecycleRule) -> Self {
+        self.lifecycle.push(rule);
+        self
+    }
+
+    pub fn with_cors_rule(mut self, rule: CorsRule) -> Self {
+        self.cors.push(rule);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: impl Into<String>) -> Self {
+        self.policy = Some(policy.into());
+        self
+    }
+}
+
+fn escape(v: &str) -> String {
+    v.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn element(name: &str, value: &str) -> String {
+    format!("<{name}>{}</{name}>", escape(value))
+}
+
+fn versioning_xml(enabled: bool) -> String {
+    format!(
+        "<VersioningConfiguration>{}</VersioningConfiguration>",
+        element("Status", if enabled { "Enabled" } else { "Suspended" })
+    )
+}
+
+fn lifecycle_xml(rules: &[LifecycleRule]) -> String {
+    let mut xml = String::from("<LifecycleConfiguration>");
+    for rule in rules {
+        xml.push_str(&format!(
+            "<Rule>{}<Filter>{}</Filter>{}<Expiration>{}</Expiration></Rule>",
+            element("ID", &rule.id),
+            element("Prefix", &rule.prefix),
+            element("Status", "Enabled"),
+            element("Days", &rule.expiration_days.to_string()),
+        ));
+    }
+    xml.push_str("</LifecycleConfiguration>");
+    xml
+}
+
+fn cors_xml(rules: &[CorsRule]) -> String {
+    let mut xml = String::from("<CORSConfiguration>");
+    for rule in rules {
+        xml.push_str("<CORSRule>");
+        for (name, values) in [
+            ("AllowedOrigin", &rule.allowed_origins),
+            ("AllowedMethod", &rule.allowed_methods),
+            ("AllowedHeader", &rule.allowed_headers),
+            ("ExposeHeader", &rule.expose_headers),
+        ] {
+            for value in values {
+                xml.push_str(&element(name, value));
+            }
+        }
+        if let Some(max_age_seconds) = rule.max_age_seconds {
+            xml.push_str(&element("MaxAgeSeconds", &max_age_seconds.to_string()));
+        }
+        xml.push_str("</CORSRule>");
+    }
+    xml.push_str("</CORSConfiguration>");
+    xml
+}
+
+impl S3 {
+    fn bucket_url(&self, name: &str, subresource: Option<&str>) -> Result<Url> {
+        let mut url = Url::parse(&format!(
+            "{}/{name}",
+            self.config().endpoint().trim_end_matches('/')
+        ))?;
+        if let Some(subresource) = subresource {
+            url.query_pairs_mut().append_key_only(subresource);
+        }
+        Ok(url)
+    }
+
+    async fn put_bucket(
+        &self,
+        name: &str,
+        subresource: Option<&str>,
+        body: String,
+        content_type: &str,
+    ) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+        headers.insert(
+            CONTENT_MD5,
+            HeaderValue::from_str(&content_md5(body.as_bytes()))?,
+        );
+        let payload_hash = sha256_hex(body.as_bytes());
+        let response = self
+            .send(
+                Method::PUT,
+                self.bucket_url(name, subresource)?,
+                headers,
+                &payload_hash,
+                Some(body.into()),
+            )
+            .await?;
+        error_for_status(response, name).await?;
+        Ok(())
+    }
+
+    pub async fn bucket_exists(&self, name: &str) -> Result<bool> {
+        let response = self
+            .send(
+                Method::HEAD,
+                self.bucket_url(name, None)?,
+                HeaderMap::new(),
+                EMPTY_PAYLOAD,
+                None,
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        error_for_status(response, name).await?;
+        Ok(true)
+    }
+
+    /// Creates the bucket `name` if it does not exist and applies `spec`.
+    pub async fn ensure_bucket(&self, name: &str, spec: &BucketSpec) -> Result<()> {
+        if !self.bucket_exists(name).await? {
+            let region = self.config().region();
+            let body = if region == "us-east-1" {
+                String::new()
+            } else {
+                format!(
+                    "<CreateBucketConfiguration>{}</CreateBucketConfiguration>",
+                    element("LocationConstraint", region)
+                )
+            };
+            self.put_bucket(name, None, body, "application/xml").await?;
+            tracing::info!("created bucket '{name}'");
+        }
+        if let Some(enabled) = spec.versioning {
+            self.put_bucket(
+                name,
+                Some("versioning"),
+                versioning_xml(enabled),
+                "application/xml",
+            )
+            .await?;
+        }
+        if !spec.lifecycle.is_empty() {
+            self.put_bucket(
+                name,
+                Some("lifecycle"),
+                lifecycle_xml(&spec.lifecycle),
+                "application/xml",
+            )
+            .await?;
+        }
+        if !spec.cors.is_empty() {
+            self.put_bucket(name, Some("cors"), cors_xml(&spec.cors), "application/xml")
+                .await?;
+        }
+        if let Some(policy) = &spec.policy {
+            self.put_bucket(name, Some("policy"), policy.clone(), "application/json")
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lifecycle_xml() {
+        assert_eq!(
+            lifecycle_xml(&[LifecycleRule::expire("tmp/", 1)]),
+            "<LifecycleConfiguration><Rule><ID>expire-tmp</ID><Filter><Prefix>tmp/</Prefix></Filter><Status>Enabled</Status><Expiration><Days>1</Days></Expiration></Rule></LifecycleConfiguration>"
+        );
+    }
+
+    #[test]
+    fn test_cors_xml() {
+        assert_eq!(
+            cors_xml(&[CorsRule {
+                allowed_origins: vec!["https://a.com?x&y".into()],
+                allowed_methods: vec!["GET".into()],
+                ..Default::default()
+            }]),
+            "<CORSConfiguration><CORSRule><AllowedOrigin>https://a.com?x&amp;y</AllowedOrigin><AllowedMethod>GET</AllowedMethod></CORSRule></CORSConfiguration>"
+        );
+    }
+}
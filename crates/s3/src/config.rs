@@ -0,0 +1,108 @@
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct Config {
+    endpoint: Option<Arc<str>>,
+    region: Option<Arc<str>>,
+    bucket: Option<Arc<str>>,
+    access_key_id: Option<Arc<str>>,
+    secret_access_key: Option<Arc<str>>,
+    public_endpoint: Option<Arc<str>>,
+    max_retries: Option<u32>,
+}
+
+impl Config {
+    pub fn new() -> envy::Result<Self> {
+        ConfigBuilder::default().build()
+    }
+
+    pub fn builder<'a>() -> ConfigBuilder<'a> {
+        ConfigBuilder::default()
+    }
+
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap()
+    }
+
+    pub fn region(&self) -> &str {
+        self.region.as_deref().unwrap()
+    }
+
+    pub fn bucket(&self) -> &str {
+        self.bucket.as_deref().unwrap()
+    }
+
+    pub fn access_key_id(&self) -> Option<&str> {
+        self.access_key_id.as_deref()
+    }
+
+    pub fn secret_access_key(&self) -> Option<&str> {
+        self.secret_access_key.as_deref()
+    }
+
+    /// Endpoint reachable by clients, used for presigned URLs.
+    pub fn public_endpoint(&self) -> &str {
+        self.public_endpoint
+            .as_deref()
+            .unwrap_or_else(|| self.endpoint())
+    }
+
+    /// Number of retries for a failed part of a multipart upload.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(3)
+    }
+}
+
+#[derive(Default)]
+pub struct ConfigBuilder<'a> {
+    prefix: Option<&'a str>,
+}
+
+impl<'a> ConfigBuilder<'a> {
+    pub fn with_prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = Some(prefix);
+        self
+    }
+
+    pub fn build(self) -> envy::Result<Config> {
+        let mut cfg: Config = envy::prefixed(self.prefix.unwrap_or("S3_")).from_env()?;
+        if cfg.endpoint.is_none() {
+            cfg.endpoint = Some(Arc::from("http://127.0.0.1:3900"));
+        }
+        if cfg.region.is_none() {
+            cfg.region = Some(Arc::from("garage"));
+        }
+        if cfg.bucket.is_none() {
+            cfg.bucket = Some(Arc::from("default"));
+        }
+        Ok(cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_builtin_config_test() -> envy::Result<()> {
+        let cfg = super::Config::builder()
+            .with_prefix("DEFAULT_S3_NOT_SET_IN_SHELL_")
+            .build()?;
+        assert_eq!(cfg.endpoint(), "http://127.0.0.1:3900");
+        assert_eq!(cfg.region(), "garage");
+        assert_eq!(cfg.bucket(), "default");
+        assert!(cfg.access_key_id().is_none());
+        assert_eq!(cfg.public_endpoint(), "http://127.0.0.1:3900");
+        assert_eq!(cfg.max_retries(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_prefixed_config_test() -> envy::Result<()> {
+        std::env::set_var("S3_CUSTOM_ENDPOINT", "http://localhost:3900");
+        std::env::set_var("S3_CUSTOM_BUCKET", "documents");
+        let cfg = super::Config::builder().with_prefix("S3_CUSTOM_").build()?;
+        assert_eq!(cfg.endpoint(), "http://localhost:3900");
+        assert_eq!(cfg.bucket(), "documents");
+        Ok(())
+    }
+}
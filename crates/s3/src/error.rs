@@ -0,0 +1,81 @@
+use reqwest::StatusCode;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Invalid environment configuration.
+    #[error(transparent)]
+    Config(#[from] envy::Error),
+    #[error("S3 access key id and secret access key are required")]
+    MissingCredentials,
+    #[error(transparent)]
+    Url(#[from] url::ParseError),
+    #[error("invalid header value: {0}")]
+    Header(String),
+    #[error("invalid object key '{0}'")]
+    InvalidKey(String),
+    /// Access outside of the scope of the caller.
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    /// Presigned URLs are valid for at most 7 days.
+    #[error("invalid expiry of {0} seconds, the maximum is 604800")]
+    InvalidExpiry(u64),
+    /// Connection or transport failure.
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The S3 API answered with an error status.
+    #[error("S3 request for '{key}' failed with status {status}: {message}")]
+    Status {
+        key: String,
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+    },
+    /// The transferred content does not match the expected digest.
+    #[error("checksum mismatch for '{key}': expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        key: String,
+        expected: String,
+        actual: String,
+    },
+    /// The response could not be interpreted.
+    #[error("unexpected S3 response for '{0}': {1}")]
+    InvalidResponse(String, String),
+}
+
+impl Error {
+    /// The S3 error code, e.g. `NoSuchKey`, if the API answered with an error.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Status { code, .. } => code.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Whether repeating the request may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Request(err) => err.is_timeout() || err.is_connect() || err.is_request(),
+            Self::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+}
+
+impl From<reqwest::header::InvalidHeaderValue> for Error {
+    fn from(value: reqwest::header::InvalidHeaderValue) -> Self {
+        Self::Header(value.to_string())
+    }
+}
+
+impl From<reqwest::header::ToStrError> for Error {
+    fn from(value: reqwest::header::ToStrError) -> Self {
+        Self::Header(value.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,5 +1,248 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
+    Method, StatusCode,
+};
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+mod bucket;
+mod config;
+mod error;
+mod list;
+mod multipart;
+#[cfg(feature = "entity")]
+mod scoped;
+mod sign;
+mod stream;
+
+pub use bucket::{BucketSpec, CorsRule, LifecycleRule};
+pub use config::Config as S3Config;
+pub use error::{Error, Result};
+pub use list::ObjectInfo;
+#[cfg(feature = "entity")]
+pub use scoped::{scope_prefix, ScopedBucket};
+use sign::{encode_key, Signer, EMPTY_PAYLOAD, UNSIGNED_PAYLOAD};
+pub use stream::{Checksum, Transfer};
+
+const MAX_PRESIGN_EXPIRY: u64 = 7 * 24 * 60 * 60;
+
+struct Inner {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+/// Minimal S3 client for the objects of the configured bucket, requests are
+/// signed with AWS signature version 4 and use path style addressing.
+#[derive(Clone)]
+pub struct S3 {
+    inner: Arc<Inner>,
+}
+
+impl S3 {
+    pub fn new() -> Result<Self> {
+        Self::from_config(S3Config::new()?)
+    }
+
+    pub fn from_config(config: S3Config) -> Result<Self> {
+        if config.access_key_id().is_none() || config.secret_access_key().is_none() {
+            return Err(Error::MissingCredentials);
+        }
+        Ok(Self {
+            inner: Arc::new(Inner {
+                config,
+                client: reqwest::Client::new(),
+            }),
+        })
+    }
+
+    pub fn config(&self) -> &S3Config {
+        &self.inner.config
+    }
+
+    pub fn bucket(&self) -> &str {
+        self.inner.config.bucket()
+    }
+
+    fn signer(&self) -> Signer<'_> {
+        let config = &self.inner.config;
+        Signer {
+            access_key_id: config.access_key_id().unwrap_or_default(),
+            secret_access_key: config.secret_access_key().unwrap_or_default(),
+            region: config.region(),
+        }
+    }
+
+    fn url(&self, endpoint: &str, key: &str) -> Result<Url> {
+        Ok(Url::parse(&format!(
+            "{}/{}/{}",
+            endpoint.trim_end_matches('/'),
+            self.bucket(),
+            encode_key(key.trim_start_matches('/'))
+        ))?)
+    }
+
+    fn object_url(&self, key: &str) -> Result<Url> {
+        self.url(self.inner.config.endpoint(), key)
+    }
+
+    fn presign(&self, method: Method, key: &str, expiry: Duration) -> Result<Url> {
+        let expires_in = expiry.as_secs();
+        if expires_in == 0 || expires_in > MAX_PRESIGN_EXPIRY {
+            return Err(Error::InvalidExpiry(expires_in));
+        }
+        let url = self.url(self.inner.config.public_endpoint(), key)?;
+        self.signer()
+            .presign(method.as_str(), &url, expires_in, &chrono::Utc::now())
+    }
+
+    /// URL to download `key` without credentials until `expiry` has passed.
+    pub fn presign_get(&self, key: &str, expiry: Duration) -> Result<Url> {
+        self.presign(Method::GET, key, expiry)
+    }
+
+    /// URL to upload `key` with a plain `PUT` request without credentials
+    /// until `expiry` has passed.
+    pub fn presign_put(&self, key: &str, expiry: Duration) -> Result<Url> {
+        self.presign(Method::PUT, key, expiry)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        url: Url,
+        mut headers: HeaderMap,
+        payload_hash: &str,
+        body: Option<reqwest::Body>,
+    ) -> Result<reqwest::Response> {
+        self.signer().sign(
+            method.as_str(),
+            &url,
+            &mut headers,
+            payload_hash,
+            &chrono::Utc::now(),
+        )?;
+        let mut request = self.inner.client.request(method, url).headers(headers);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        Ok(request.send().await?)
+    }
+
+    /// Uploads `body` of `content_length` bytes to `key`, the payload is
+    /// streamed and not hashed.
+    pub async fn put_object(
+        &self,
+        key: &str,
+        body: impl Into<reqwest::Body>,
+        content_length: u64,
+        content_type: Option<&str>,
+    ) -> Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+        if let Some(content_type) = content_type {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+        }
+        let response = self
+            .send(
+                Method::PUT,
+                self.object_url(key)?,
+                headers,
+                UNSIGNED_PAYLOAD,
+                Some(body.into()),
+            )
+            .await?;
+        error_for_status(response, key).await?;
+        Ok(())
+    }
+
+    /// Streams the content of `file` to `key` and returns the number of
+    /// bytes written.
+    pub async fn put_file(
+        &self,
+        key: &str,
+        file: std::fs::File,
+        content_type: Option<&str>,
+    ) -> Result<u64> {
+        let size = file.metadata()?.len();
+        let stream = ReaderStream::new(tokio::fs::File::from_std(file));
+        self.put_object(key, reqwest::Body::wrap_stream(stream), size, content_type)
+            .await?;
+        Ok(size)
+    }
+
+    /// Returns the content of `key` or `None` if the object does not exist.
+    pub async fn get_object(&self, key: &str) -> Result<Option<bytes::Bytes>> {
+        let response = self
+            .send(
+                Method::GET,
+                self.object_url(key)?,
+                HeaderMap::new(),
+                EMPTY_PAYLOAD,
+                None,
+            )
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(error_for_status(response, key).await?.bytes().await?))
+    }
+
+    pub async fn delete_object(&self, key: &str) -> Result<()> {
+        let response = self
+            .send(
+                Method::DELETE,
+                self.object_url(key)?,
+                HeaderMap::new(),
+                EMPTY_PAYLOAD,
+                None,
+            )
+            .await?;
+        error_for_status(response, key).await?;
+        Ok(())
+    }
+}
+
+/// Text of the first `<tag>` element of an S3 XML document.
+fn xml_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{tag}>"))? + start;
+    Some(&xml[start..end])
+}
+
+/// Texts of all `<tag>` elements of an S3 XML document.
+fn xml_values<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut values = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        values.push(&rest[..end]);
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+fn status_error(key: &str, status: StatusCode, body: &str) -> Error {
+    Error::Status {
+        key: key.to_string(),
+        status,
+        code: xml_value(body, "Code").map(String::from),
+        message: xml_value(body, "Message").unwrap_or(body).to_string(),
+    }
+}
+
+async fn error_for_status(response: reqwest::Response, key: &str) -> Result<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(status_error(key, status, &body))
 }
 
 #[cfg(test)]
@@ -7,8 +250,33 @@ mod tests {
     use super::*;
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn test_status_error() {
+        let body = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>NoSuchBucket</Code><Message>Bucket not found</Message></Error>";
+        let err = status_error("a.txt", StatusCode::NOT_FOUND, body);
+        assert_eq!(err.code(), Some("NoSuchBucket"));
+        assert!(!err.is_retryable());
+        assert_eq!(
+            err.to_string(),
+            "S3 request for 'a.txt' failed with status 404 Not Found: Bucket not found"
+        );
+    }
+
+    #[test]
+    fn test_presign_expiry() -> Result<()> {
+        std::env::set_var("S3_PRESIGN_TEST_ACCESS_KEY_ID", "key");
+        std::env::set_var("S3_PRESIGN_TEST_SECRET_ACCESS_KEY", "secret");
+        let s3 = S3::from_config(
+            S3Config::builder()
+                .with_prefix("S3_PRESIGN_TEST_")
+                .build()?,
+        )?;
+        assert!(matches!(
+            s3.presign_get("a.txt", Duration::from_secs(MAX_PRESIGN_EXPIRY + 1)),
+            Err(Error::InvalidExpiry(_))
+        ));
+        let url = s3.presign_put("docs/a b.txt", Duration::from_secs(60))?;
+        assert_eq!(url.path(), "/default/docs/a%20b.txt");
+        assert!(url.query_pairs().any(|(k, _)| k == "X-Amz-Signature"));
+        Ok(())
     }
 }
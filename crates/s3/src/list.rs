@@ -0,0 +1,97 @@
+use reqwest::{header::HeaderMap, Method};
+use url::Url;
+
+use crate::{error_for_status, sign::EMPTY_PAYLOAD, xml_value, xml_values, Result, S3};
+
+/// Entry of [`S3::list_objects`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+fn unescape(v: &str) -> String {
+    v.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn parse_objects(xml: &str) -> Vec<ObjectInfo> {
+    xml_values(xml, "Contents")
+        .into_iter()
+        .filter_map(|contents| {
+            Some(ObjectInfo {
+                key: unescape(xml_value(contents, "Key")?),
+                size: xml_value(contents, "Size")?.parse().ok()?,
+                etag: xml_value(contents, "ETag").map(unescape),
+                last_modified: xml_value(contents, "LastModified").map(String::from),
+            })
+        })
+        .collect()
+}
+
+impl S3 {
+    /// Lists all objects of the bucket whose key starts with `prefix`.
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<ObjectInfo>> {
+        let mut objects = vec![];
+        let mut continuation_token: Option<String> = None;
+        loop {
+            let mut url = Url::parse(&format!(
+                "{}/{}",
+                self.config().endpoint().trim_end_matches('/'),
+                self.bucket()
+            ))?;
+            {
+                let mut query = url.query_pairs_mut();
+                query
+                    .append_pair("list-type", "2")
+                    .append_pair("prefix", prefix);
+                if let Some(token) = continuation_token.as_deref() {
+                    query.append_pair("continuation-token", token);
+                }
+            }
+            let response = self
+                .send(Method::GET, url, HeaderMap::new(), EMPTY_PAYLOAD, None)
+                .await?;
+            let body = error_for_status(response, prefix).await?.text().await?;
+            objects.extend(parse_objects(&body));
+            continuation_token = xml_value(&body, "NextContinuationToken")
+                .filter(|_| xml_value(&body, "IsTruncated") == Some("true"))
+                .map(unescape);
+            if continuation_token.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_objects() {
+        let xml = r#"<ListBucketResult><IsTruncated>false</IsTruncated><Contents><Key>a&amp;b.txt</Key><LastModified>2024-01-01T00:00:00.000Z</LastModified><ETag>&quot;abc&quot;</ETag><Size>3</Size></Contents><Contents><Key>c.txt</Key><Size>0</Size></Contents></ListBucketResult>"#;
+        assert_eq!(
+            parse_objects(xml),
+            vec![
+                ObjectInfo {
+                    key: "a&b.txt".into(),
+                    size: 3,
+                    etag: Some("\"abc\"".into()),
+                    last_modified: Some("2024-01-01T00:00:00.000Z".into()),
+                },
+                ObjectInfo {
+                    key: "c.txt".into(),
+                    size: 0,
+                    etag: None,
+                    last_modified: None,
+                }
+            ]
+        );
+    }
+}
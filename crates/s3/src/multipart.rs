@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG},
+    Method,
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use url::Url;
+
+use crate::{
+    error_for_status,
+    sign::{content_md5, sha256_hex, CONTENT_MD5, EMPTY_PAYLOAD, UNSIGNED_PAYLOAD},
+    status_error, xml_value, Error, Result, S3,
+};
+
+const MIB: u64 = 1024 * 1024;
+const MIN_PART_SIZE: u64 = 8 * MIB;
+const MAX_PARTS: u64 = 10_000;
+
+/// Part size for an object of `content_length` bytes, a multiple of 1 MiB,
+/// at least 8 MiB and large enough to stay within 10000 parts.
+pub(crate) fn part_size(content_length: Option<u64>) -> u64 {
+    match content_length {
+        Some(len) => (len.div_ceil(MAX_PARTS).div_ceil(MIB) * MIB).max(MIN_PART_SIZE),
+        None => MIN_PART_SIZE,
+    }
+}
+
+async fn read_part<R>(reader: &mut R, size: u64) -> Result<Bytes>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::with_capacity(size as usize);
+    reader.take(size).read_to_end(&mut buf).await?;
+    Ok(buf.into())
+}
+
+impl S3 {
+    fn upload_url(&self, key: &str, upload_id: &str) -> Result<Url> {
+        let mut url = self.object_url(key)?;
+        url.query_pairs_mut().append_pair("uploadId", upload_id);
+        Ok(url)
+    }
+
+    /// Uploads the content of `reader` in parts and returns the number of
+    /// bytes written, `content_length` is only used to size the parts.
+    ///
+    /// Failed parts are retried up to [`crate::S3Config::max_retries`] times,
+    /// on error the upload is aborted.
+    pub async fn put_multipart<R>(
+        &self,
+        key: &str,
+        mut reader: R,
+        content_length: Option<u64>,
+        content_type: Option<&str>,
+    ) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.multipart(key, &mut reader, content_length, content_type, |_| Ok(()))
+            .await
+    }
+
+    /// Uploads `reader` in parts, `verify` is called after all parts are
+    /// uploaded and aborts the upload on error.
+    pub(crate) async fn multipart<R, V>(
+        &self,
+        key: &str,
+        reader: &mut R,
+        content_length: Option<u64>,
+        content_type: Option<&str>,
+        verify: V,
+    ) -> Result<u64>
+    where
+        R: AsyncRead + Unpin,
+        V: FnOnce(&R) -> Result<()>,
+    {
+        let upload_id = self.create_multipart_upload(key, content_type).await?;
+        let result = async {
+            let (etags, size) = self
+                .upload_parts(key, &upload_id, reader, part_size(content_length))
+                .await?;
+            verify(reader)?;
+            self.complete_multipart_upload(key, &upload_id, &etags)
+                .await?;
+            Ok(size)
+        }
+        .await;
+        if result.is_err() {
+            if let Err(err) = self.abort_multipart_upload(key, &upload_id).await {
+                tracing::warn!("unable to abort multipart upload of '{key}': {err}");
+            }
+        }
+        result
+    }
+
+    async fn create_multipart_upload(
+        &self,
+        key: &str,
+        content_type: Option<&str>,
+    ) -> Result<String> {
+        let mut url = self.object_url(key)?;
+        url.query_pairs_mut().append_key_only("uploads");
+        let mut headers = HeaderMap::new();
+        if let Some(content_type) = content_type {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+        }
+        let response = self
+            .send(Method::POST, url, headers, EMPTY_PAYLOAD, None)
+            .await?;
+        let body = error_for_status(response, key).await?.text().await?;
+        xml_value(&body, "UploadId")
+            .map(String::from)
+            .ok_or_else(|| Error::InvalidResponse(key.to_string(), "missing UploadId".into()))
+    }
+
+    async fn upload_parts<R>(
+        &self,
+        key: &str,
+        upload_id: &str,
+        reader: &mut R,
+        part_size: u64,
+    ) -> Result<(Vec<String>, u64)>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut etags = vec![];
+        let mut size = 0;
+        loop {
+            let part = read_part(reader, part_size).await?;
+            if part.is_empty() && !etags.is_empty() {
+                break;
+            }
+            let len = part.len() as u64;
+            let part_number = etags.len() + 1;
+            etags.push(self.upload_part(key, upload_id, part_number, part).await?);
+            size += len;
+            if len < part_size {
+                break;
+            }
+        }
+        Ok((etags, size))
+    }
+
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: usize,
+        body: Bytes,
+    ) -> Result<String> {
+        let mut url = self.upload_url(key, upload_id)?;
+        url.query_pairs_mut()
+            .append_pair("partNumber", &part_number.to_string());
+        let mut attempt = 0;
+        loop {
+            let mut headers = HeaderMap::new();
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+            headers.insert(CONTENT_MD5, HeaderValue::from_str(&content_md5(&body))?);
+            let result = match self
+                .send(
+                    Method::PUT,
+                    url.clone(),
+                    headers,
+                    UNSIGNED_PAYLOAD,
+                    Some(body.clone().into()),
+                )
+                .await
+            {
+                Ok(response) => error_for_status(response, key).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(response) => {
+                    let etag = response.headers().get(ETAG).ok_or_else(|| {
+                        Error::InvalidResponse(
+                            key.to_string(),
+                            format!("missing ETag of part {part_number}"),
+                        )
+                    })?;
+                    return Ok(etag.to_str()?.to_string());
+                }
+                Err(err) if err.is_retryable() && attempt < self.config().max_retries() => {
+                    attempt += 1;
+                    let delay = Duration::from_millis(100 << attempt);
+                    tracing::warn!(
+                        "upload of part {part_number} of '{key}' failed, retrying in {delay:?}: {err}"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        etags: &[String],
+    ) -> Result<()> {
+        let mut body = String::from("<CompleteMultipartUpload>");
+        for (i, etag) in etags.iter().enumerate() {
+            body.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{etag}</ETag></Part>",
+                i + 1
+            ));
+        }
+        body.push_str("</CompleteMultipartUpload>");
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        let payload_hash = sha256_hex(body.as_bytes());
+        let response = self
+            .send(
+                Method::POST,
+                self.upload_url(key, upload_id)?,
+                headers,
+                &payload_hash,
+                Some(body.into()),
+            )
+            .await?;
+        let response = error_for_status(response, key).await?;
+        let status = response.status();
+        // errors during completion are reported with status 200
+        let body = response.text().await?;
+        if body.contains("<Error>") {
+            return Err(status_error(key, status, &body));
+        }
+        Ok(())
+    }
+
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        let response = self
+            .send(
+                Method::DELETE,
+                self.upload_url(key, upload_id)?,
+                HeaderMap::new(),
+                EMPTY_PAYLOAD,
+                None,
+            )
+            .await?;
+        error_for_status(response, key).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_size() {
+        assert_eq!(part_size(None), 8 * MIB);
+        assert_eq!(part_size(Some(0)), 8 * MIB);
+        assert_eq!(part_size(Some(80_000 * MIB)), 8 * MIB);
+        assert_eq!(part_size(Some(80_000 * MIB + 1)), 9 * MIB);
+        assert!(part_size(Some(5 * 1024 * 1024 * MIB)) * MAX_PARTS >= 5 * 1024 * 1024 * MIB);
+    }
+
+    #[tokio::test]
+    async fn test_read_part() -> Result<()> {
+        let mut reader: &[u8] = b"0123456789";
+        assert_eq!(read_part(&mut reader, 4).await?, "0123");
+        assert_eq!(read_part(&mut reader, 4).await?, "4567");
+        assert_eq!(read_part(&mut reader, 4).await?, "89");
+        assert!(read_part(&mut reader, 4).await?.is_empty());
+        Ok(())
+    }
+}
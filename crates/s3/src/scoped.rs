@@ -0,0 +1,188 @@
+use std::time::Duration;
+
+use qm_entity::{ids::InfraContext, IsAdmin, IsSupport, SessionAccess};
+use url::Url;
+
+use crate::{Error, ObjectInfo, Result, S3};
+
+/// Key prefix of the objects owned by `context`, nested by the hierarchy
+/// customer, organization, institution / organization unit.
+pub fn scope_prefix(context: &InfraContext) -> String {
+    match context {
+        InfraContext::Customer(v) => format!("customer/{}/", v.unzip()),
+        InfraContext::Organization(v) => {
+            let (cid, oid) = v.unzip();
+            format!("customer/{cid}/organization/{oid}/")
+        }
+        InfraContext::Institution(v) => {
+            let (cid, oid, iid) = v.unzip();
+            format!("customer/{cid}/organization/{oid}/institution/{iid}/")
+        }
+        InfraContext::OrganizationUnit(v) => match v.unzip() {
+            (cid, 0, uid) => format!("customer/{cid}/organization_unit/{uid}/"),
+            (cid, oid, uid) => {
+                format!("customer/{cid}/organization/{oid}/organization_unit/{uid}/")
+            }
+        },
+    }
+}
+
+/// Wraps [`S3`] and places objects below the prefix of their
+/// [`InfraContext`], access outside of the callers context is rejected.
+#[derive(Clone)]
+pub struct ScopedBucket {
+    s3: S3,
+    context: Option<InfraContext>,
+}
+
+impl ScopedBucket {
+    /// Creates a scoped bucket, `None` means unrestricted access and should
+    /// only be used for admin or support users.
+    pub fn new(s3: S3, context: Option<InfraContext>) -> Self {
+        Self { s3, context }
+    }
+
+    pub fn from_session<A>(s3: S3, auth: &A) -> Result<Self>
+    where
+        A: SessionAccess + IsAdmin + IsSupport,
+    {
+        if auth.is_admin() || auth.is_support() {
+            return Ok(Self::new(s3, None));
+        }
+        let context = auth
+            .session_access()
+            .and_then(|access| access.id())
+            .and_then(|id| InfraContext::parse(id).ok())
+            .ok_or_else(|| Error::Forbidden("missing session context".into()))?;
+        Ok(Self::new(s3, Some(context)))
+    }
+
+    pub fn context(&self) -> Option<&InfraContext> {
+        self.context.as_ref()
+    }
+
+    pub fn s3(&self) -> &S3 {
+        &self.s3
+    }
+
+    fn check(&self, context: &InfraContext) -> Result<String> {
+        let prefix = scope_prefix(context);
+        match &self.context {
+            Some(own) if !prefix.starts_with(&scope_prefix(own)) => {
+                Err(Error::Forbidden(format!("objects of {context}")))
+            }
+            _ => Ok(prefix),
+        }
+    }
+
+    /// Full object key of `name` in `context`.
+    pub fn key(&self, context: &InfraContext, name: &str) -> Result<String> {
+        let prefix = self.check(context)?;
+        if name.is_empty()
+            || name.starts_with('/')
+            || name.split('/').any(|segment| segment == "..")
+        {
+            return Err(Error::InvalidKey(name.to_string()));
+        }
+        Ok(format!("{prefix}{name}"))
+    }
+
+    pub async fn get(&self, context: &InfraContext, name: &str) -> Result<Option<bytes::Bytes>> {
+        self.s3.get_object(&self.key(context, name)?).await
+    }
+
+    pub async fn put(
+        &self,
+        context: &InfraContext,
+        name: &str,
+        body: impl Into<reqwest::Body>,
+        content_length: u64,
+        content_type: Option<&str>,
+    ) -> Result<String> {
+        let key = self.key(context, name)?;
+        self.s3
+            .put_object(&key, body, content_length, content_type)
+            .await?;
+        Ok(key)
+    }
+
+    pub async fn delete(&self, context: &InfraContext, name: &str) -> Result<()> {
+        self.s3.delete_object(&self.key(context, name)?).await
+    }
+
+    pub fn presign_get(&self, context: &InfraContext, name: &str, expiry: Duration) -> Result<Url> {
+        self.s3.presign_get(&self.key(context, name)?, expiry)
+    }
+
+    pub fn presign_put(&self, context: &InfraContext, name: &str, expiry: Duration) -> Result<Url> {
+        self.s3.presign_put(&self.key(context, name)?, expiry)
+    }
+
+    /// Lists all objects of `context` including those of nested contexts.
+    pub async fn list_scope(&self, context: &InfraContext) -> Result<Vec<ObjectInfo>> {
+        self.s3.list_objects(&self.check(context)?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qm_entity::ids::{CustomerId, InstitutionId, OrganizationId, OrganizationUnitId};
+
+    fn bucket(context: Option<InfraContext>) -> Result<ScopedBucket> {
+        std::env::set_var("S3_SCOPED_TEST_ACCESS_KEY_ID", "key");
+        std::env::set_var("S3_SCOPED_TEST_SECRET_ACCESS_KEY", "secret");
+        let s3 = S3::from_config(
+            crate::S3Config::builder()
+                .with_prefix("S3_SCOPED_TEST_")
+                .build()?,
+        )?;
+        Ok(ScopedBucket::new(s3, context))
+    }
+
+    #[test]
+    fn test_scope_prefix() {
+        let uid: OrganizationUnitId = (1_i64, 0_i64, 4_i64).into();
+        assert_eq!(
+            scope_prefix(&InfraContext::OrganizationUnit(uid)),
+            "customer/1/organization_unit/4/"
+        );
+        let iid: InstitutionId = (1_i64, 2_i64, 3_i64).into();
+        assert_eq!(
+            scope_prefix(&InfraContext::Institution(iid)),
+            "customer/1/organization/2/institution/3/"
+        );
+    }
+
+    #[test]
+    fn test_key_access() -> Result<()> {
+        let cid: CustomerId = 1_i64.into();
+        let oid: OrganizationId = (1_i64, 2_i64).into();
+        let iid: InstitutionId = (1_i64, 2_i64, 3_i64).into();
+        let other: InstitutionId = (1_i64, 2_i64, 33_i64).into();
+        let bucket = bucket(Some(InfraContext::Organization(oid)))?;
+        assert_eq!(
+            bucket.key(&InfraContext::Institution(iid), "a/b.pdf")?,
+            "customer/1/organization/2/institution/3/a/b.pdf"
+        );
+        assert!(bucket
+            .key(&InfraContext::Institution(other), "b.pdf")
+            .is_ok());
+        assert!(matches!(
+            bucket.key(&InfraContext::Customer(cid), "b.pdf"),
+            Err(Error::Forbidden(_))
+        ));
+        assert!(matches!(
+            bucket.key(&InfraContext::Institution(iid), "../33/b.pdf"),
+            Err(Error::InvalidKey(_))
+        ));
+        let bucket = self::bucket(Some(InfraContext::Institution(iid)))?;
+        assert!(bucket
+            .key(&InfraContext::Institution(other), "b.pdf")
+            .is_err());
+        assert!(self::bucket(None)?
+            .key(&InfraContext::Customer(cid), "b.pdf")
+            .is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use md5::Md5;
+use reqwest::{header::HeaderMap, Method};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+use crate::{error_for_status, sign::EMPTY_PAYLOAD, Error, Result, S3};
+
+/// Expected digest of an object as lowercase hex string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    Sha256(String),
+    Md5(String),
+}
+
+/// Size and digests of a streamed object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+    pub size: u64,
+    pub sha256: String,
+    pub md5: String,
+}
+
+struct Digests {
+    sha256: Sha256,
+    md5: Md5,
+    size: u64,
+}
+
+impl Digests {
+    fn new() -> Self {
+        Self {
+            sha256: Sha256::new(),
+            md5: Md5::new(),
+            size: 0,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        self.md5.update(data);
+        self.size += data.len() as u64;
+    }
+
+    fn transfer(&self) -> Transfer {
+        Transfer {
+            size: self.size,
+            sha256: hex::encode(self.sha256.clone().finalize()),
+            md5: hex::encode(self.md5.clone().finalize()),
+        }
+    }
+}
+
+fn verify(key: &str, transfer: &Transfer, expected: Option<&Checksum>) -> Result<()> {
+    let (expected, actual) = match expected {
+        Some(Checksum::Sha256(expected)) => (expected, &transfer.sha256),
+        Some(Checksum::Md5(expected)) => (expected, &transfer.md5),
+        None => return Ok(()),
+    };
+    if !expected.eq_ignore_ascii_case(actual) {
+        return Err(Error::ChecksumMismatch {
+            key: key.to_string(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        });
+    }
+    Ok(())
+}
+
+struct DigestReader<R, F> {
+    inner: R,
+    digests: Digests,
+    total: Option<u64>,
+    progress: F,
+}
+
+impl<R, F> AsyncRead for DigestReader<R, F>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(u64, Option<u64>) + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        let data = &buf.filled()[filled..];
+        if !data.is_empty() {
+            this.digests.update(data);
+            (this.progress)(this.digests.size, this.total);
+        }
+        result
+    }
+}
+
+impl S3 {
+    /// Writes the content of `key` to `writer` without buffering the object.
+    ///
+    /// `progress` receives the bytes written so far and the object size. The
+    /// content is verified against `checksum`, or the ETag if it is a plain
+    /// MD5 digest, after it has been written, on a mismatch the caller has to
+    /// discard the written data.
+    pub async fn stream_object<W, F>(
+        &self,
+        key: &str,
+        writer: &mut W,
+        checksum: Option<&Checksum>,
+        mut progress: F,
+    ) -> Result<Transfer>
+    where
+        W: AsyncWrite + Unpin,
+        F: FnMut(u64, Option<u64>),
+    {
+        let response = self
+            .send(
+                Method::GET,
+                self.object_url(key)?,
+                HeaderMap::new(),
+                EMPTY_PAYLOAD,
+                None,
+            )
+            .await?;
+        let mut response = error_for_status(response, key).await?;
+        let total = response.content_length();
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim_matches('"').to_string())
+            .filter(|v| v.len() == 32 && v.bytes().all(|b| b.is_ascii_hexdigit()))
+            .map(Checksum::Md5);
+        let mut digests = Digests::new();
+        while let Some(chunk) = response.chunk().await? {
+            writer.write_all(&chunk).await?;
+            digests.update(&chunk);
+            progress(digests.size, total);
+        }
+        writer.flush().await?;
+        let transfer = digests.transfer();
+        verify(key, &transfer, checksum.or(etag.as_ref()))?;
+        Ok(transfer)
+    }
+
+    /// Uploads the content of `reader` as multipart upload, see
+    /// [`S3::put_multipart`].
+    ///
+    /// `progress` receives the bytes read so far and `content_length`. If the
+    /// content does not match `checksum` the upload is aborted.
+    pub async fn put_stream<R, F>(
+        &self,
+        key: &str,
+        reader: R,
+        content_length: Option<u64>,
+        content_type: Option<&str>,
+        checksum: Option<&Checksum>,
+        progress: F,
+    ) -> Result<Transfer>
+    where
+        R: AsyncRead + Unpin,
+        F: FnMut(u64, Option<u64>) + Unpin,
+    {
+        let mut reader = DigestReader {
+            inner: reader,
+            digests: Digests::new(),
+            total: content_length,
+            progress,
+        };
+        let mut transfer = None;
+        self.multipart(key, &mut reader, content_length, content_type, |reader| {
+            let result = reader.digests.transfer();
+            verify(key, &result, checksum)?;
+            transfer = Some(result);
+            Ok(())
+        })
+        .await?;
+        Ok(transfer.unwrap_or_else(|| reader.digests.transfer()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_digest_reader() -> Result<()> {
+        let mut calls = vec![];
+        let mut reader = DigestReader {
+            inner: &b"hello world"[..],
+            digests: Digests::new(),
+            total: Some(11),
+            progress: |size, total| calls.push((size, total)),
+        };
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).await?;
+        let transfer = reader.digests.transfer();
+        assert_eq!(transfer.size, 11);
+        assert_eq!(
+            transfer.sha256,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+        assert_eq!(transfer.md5, "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert!(verify(
+            "a",
+            &transfer,
+            Some(&Checksum::Md5("5EB63BBBE01EEED093CB22BB8F5ACDC3".into()))
+        )
+        .is_ok());
+        assert!(matches!(
+            verify("a", &transfer, Some(&Checksum::Sha256("00".into()))),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert_eq!(calls.last(), Some(&(11, Some(11))));
+        Ok(())
+    }
+}
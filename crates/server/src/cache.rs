@@ -0,0 +1,447 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextPrepareRequest},
+    Request, ServerError, ServerResult,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+const PERSISTED_QUERY: &str = "persistedQuery";
+const PERSISTED_QUERY_NOT_FOUND: &str = "PersistedQueryNotFound";
+
+/// Queries by hash and the hashes in insertion order.
+type Queries = (HashMap<String, String>, VecDeque<String>);
+
+pub(crate) fn sha256_hex(v: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(v))
+}
+
+/// Storage of the queries registered by automatic persisted queries.
+#[async_trait::async_trait]
+pub trait QueryStore: Send + Sync + Clone + 'static {
+    async fn get(&self, hash: &str) -> Option<String>;
+    async fn set(&self, hash: &str, query: &str);
+}
+
+/// In-memory [`QueryStore`] holding at most `capacity` queries, the oldest
+/// query is evicted first.
+#[derive(Clone)]
+pub struct MemoryQueryStore {
+    capacity: usize,
+    inner: Arc<Mutex<Queries>>,
+}
+
+impl MemoryQueryStore {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Arc::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl QueryStore for MemoryQueryStore {
+    async fn get(&self, hash: &str) -> Option<String> {
+        self.inner.lock().ok()?.0.get(hash).cloned()
+    }
+
+    async fn set(&self, hash: &str, query: &str) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+        let (queries, order) = &mut *inner;
+        if queries
+            .insert(hash.to_string(), query.to_string())
+            .is_none()
+        {
+            order.push_back(hash.to_string());
+        }
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                queries.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PersistedQuery {
+    version: i32,
+    #[serde(rename = "sha256Hash")]
+    sha256_hash: String,
+}
+
+/// Automatic persisted queries, compatible with the Apollo client link.
+///
+/// Clients send the sha256 hash of the query in the `persistedQuery`
+/// extension and only send the full query if the server responds with
+/// `PersistedQueryNotFound`.
+pub struct PersistedQueries<S>(S);
+
+impl<S: QueryStore> PersistedQueries<S> {
+    pub fn new(store: S) -> Self {
+        Self(store)
+    }
+}
+
+impl<S: QueryStore> ExtensionFactory for PersistedQueries<S> {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(PersistedQueriesExtension {
+            store: self.0.clone(),
+        })
+    }
+}
+
+struct PersistedQueriesExtension<S> {
+    store: S,
+}
+
+#[async_trait::async_trait]
+impl<S: QueryStore> Extension for PersistedQueriesExtension<S> {
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        mut request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if let Some(value) = request.extensions.remove(PERSISTED_QUERY) {
+            let persisted_query: PersistedQuery = async_graphql::from_value(value)
+                .map_err(|_| ServerError::new("invalid persistedQuery extension", None))?;
+            if persisted_query.version != 1 {
+                return Err(ServerError::new(
+                    format!(
+                        "unsupported persistedQuery version {}",
+                        persisted_query.version
+                    ),
+                    None,
+                ));
+            }
+            if request.query.is_empty() {
+                match self.store.get(&persisted_query.sha256_hash).await {
+                    Some(query) => request.query = query,
+                    None => return Err(ServerError::new(PERSISTED_QUERY_NOT_FOUND, None)),
+                }
+            } else if sha256_hex(request.query.as_bytes()) != persisted_query.sha256_hash {
+                return Err(ServerError::new("provided sha does not match query", None));
+            } else {
+                self.store
+                    .set(&persisted_query.sha256_hash, &request.query)
+                    .await;
+            }
+        }
+        next.run(ctx, request).await
+    }
+}
+
+#[cfg(feature = "redis")]
+mod redis {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use async_graphql::{
+        extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery},
+        parser::types::{ExecutableDocument, OperationType},
+        Response, ServerResult, Value, Variables,
+    };
+    use qm_redis::{redis::AsyncCommands, Redis};
+    use qm_role::AuthContainer;
+
+    use super::{sha256_hex, QueryStore};
+
+    const QUERY_PREFIX: &str = "qm:apq";
+    const RESPONSE_PREFIX: &str = "qm:response";
+
+    /// [`QueryStore`] shared by all instances of a service.
+    #[derive(Clone)]
+    pub struct RedisQueryStore {
+        redis: Redis,
+        ttl: Option<Duration>,
+    }
+
+    impl RedisQueryStore {
+        pub fn new(redis: Redis) -> Self {
+            Self { redis, ttl: None }
+        }
+
+        pub fn with_ttl(mut self, ttl: Duration) -> Self {
+            self.ttl = Some(ttl);
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl QueryStore for RedisQueryStore {
+        async fn get(&self, hash: &str) -> Option<String> {
+            let mut con = self.redis.connect().await.ok()?;
+            con.get(format!("{QUERY_PREFIX}:{hash}")).await.ok()?
+        }
+
+        async fn set(&self, hash: &str, query: &str) {
+            let Ok(mut con) = self.redis.connect().await else {
+                return;
+            };
+            let key = format!("{QUERY_PREFIX}:{hash}");
+            let result = match self.ttl {
+                Some(ttl) => con.set_ex::<_, _, ()>(key, query, ttl.as_secs()).await,
+                None => con.set::<_, _, ()>(key, query).await,
+            };
+            if let Err(err) = result {
+                tracing::warn!("unable to persist query {hash}: {err:#?}");
+            }
+        }
+    }
+
+    type ContextKey = Arc<dyn Fn(&ExtensionContext<'_>) -> String + Send + Sync>;
+
+    /// Redis backed cache of query responses.
+    ///
+    /// Only query operations with a TTL hint are cached, mutations and
+    /// subscriptions never are. The cache key consists of the hash of the
+    /// parsed document, the variables, the operation name and the access
+    /// context, which defaults to the access token of the request.
+    pub struct ResponseCache {
+        redis: Redis,
+        ttls: Arc<HashMap<String, Duration>>,
+        context_key: ContextKey,
+    }
+
+    impl ResponseCache {
+        pub fn new<A>(redis: Redis) -> Self
+        where
+            A: Send + Sync + 'static,
+        {
+            Self {
+                redis,
+                ttls: Arc::default(),
+                context_key: Arc::new(|ctx| {
+                    ctx.data_opt::<AuthContainer<A>>()
+                        .and_then(|auth| auth.encoded())
+                        .map(|token| sha256_hex(token.as_bytes()))
+                        .unwrap_or_default()
+                }),
+            }
+        }
+
+        /// Caches the responses of the query `operation_name` for `ttl`.
+        pub fn with_ttl(mut self, operation_name: &str, ttl: Duration) -> Self {
+            Arc::make_mut(&mut self.ttls).insert(operation_name.to_string(), ttl);
+            self
+        }
+
+        /// Overrides the access context part of the cache key.
+        pub fn with_context_key<F>(mut self, f: F) -> Self
+        where
+            F: Fn(&ExtensionContext<'_>) -> String + Send + Sync + 'static,
+        {
+            self.context_key = Arc::new(f);
+            self
+        }
+    }
+
+    impl ExtensionFactory for ResponseCache {
+        fn create(&self) -> Arc<dyn Extension> {
+            Arc::new(ResponseCacheExtension {
+                redis: self.redis.clone(),
+                ttls: self.ttls.clone(),
+                context_key: self.context_key.clone(),
+                document: std::sync::Mutex::new(None),
+            })
+        }
+    }
+
+    /// Hash of a parsed request and the named queries of its document.
+    struct ParsedDocument {
+        hash: String,
+        queries: Vec<Option<String>>,
+        operations: usize,
+    }
+
+    impl ParsedDocument {
+        fn new(document: &ExecutableDocument, hash: String) -> Self {
+            let queries = document
+                .operations
+                .iter()
+                .filter(|(_, op)| op.node.ty == OperationType::Query)
+                .map(|(name, _)| name.map(|v| v.to_string()))
+                .collect();
+            Self {
+                hash,
+                queries,
+                operations: document.operations.iter().count(),
+            }
+        }
+
+        /// Name of the executed operation if it is a named query.
+        fn query_name(&self, operation_name: Option<&str>) -> Option<&str> {
+            match operation_name {
+                Some(name) => self
+                    .queries
+                    .iter()
+                    .flatten()
+                    .find(|v| v.as_str() == name)
+                    .map(String::as_str),
+                None if self.operations == 1 => self.queries.first()?.as_deref(),
+                None => None,
+            }
+        }
+    }
+
+    struct ResponseCacheExtension {
+        redis: Redis,
+        ttls: Arc<HashMap<String, Duration>>,
+        context_key: ContextKey,
+        document: std::sync::Mutex<Option<ParsedDocument>>,
+    }
+
+    fn document_hash(query: &str, variables: &Variables, context: &str) -> String {
+        let variables = serde_json::to_string(variables).unwrap_or_default();
+        sha256_hex(format!("{query}\n{variables}\n{context}").as_bytes())
+    }
+
+    impl ResponseCacheExtension {
+        async fn load(&self, key: &str) -> Option<Value> {
+            let mut con = self.redis.connect().await.ok()?;
+            let cached: Option<String> = con.get(key).await.ok()?;
+            serde_json::from_str(&cached?).ok()
+        }
+
+        async fn store(&self, key: &str, ttl: Duration, data: &Value) {
+            let Ok(data) = serde_json::to_string(data) else {
+                return;
+            };
+            let Ok(mut con) = self.redis.connect().await else {
+                return;
+            };
+            if let Err(err) = con
+                .set_ex::<_, _, ()>(key, data, ttl.as_secs().max(1))
+                .await
+            {
+                tracing::warn!("unable to cache response: {err:#?}");
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Extension for ResponseCacheExtension {
+        async fn parse_query(
+            &self,
+            ctx: &ExtensionContext<'_>,
+            query: &str,
+            variables: &Variables,
+            next: NextParseQuery<'_>,
+        ) -> ServerResult<ExecutableDocument> {
+            let document = next.run(ctx, query, variables).await?;
+            let hash = document_hash(query, variables, &(self.context_key)(ctx));
+            if let Ok(mut parsed) = self.document.lock() {
+                parsed.replace(ParsedDocument::new(&document, hash));
+            }
+            Ok(document)
+        }
+
+        async fn execute(
+            &self,
+            ctx: &ExtensionContext<'_>,
+            operation_name: Option<&str>,
+            next: NextExecute<'_>,
+        ) -> Response {
+            let parsed = self.document.lock().ok().and_then(|mut v| v.take());
+            let entry = parsed.as_ref().and_then(|parsed| {
+                let name = parsed.query_name(operation_name)?;
+                let ttl = self.ttls.get(name)?;
+                let key = sha256_hex(format!("{}\n{name}", parsed.hash).as_bytes());
+                Some((format!("{RESPONSE_PREFIX}:{key}"), *ttl))
+            });
+            let Some((key, ttl)) = entry else {
+                return next.run(ctx, operation_name).await;
+            };
+            if let Some(data) = self.load(&key).await {
+                return Response::new(data);
+            }
+            let response = next.run(ctx, operation_name).await;
+            if response.is_ok() {
+                self.store(&key, ttl, &response.data).await;
+            }
+            response
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_query_name() {
+            let parse = |query: &str| {
+                let document = async_graphql::parser::parse_query(query).unwrap();
+                ParsedDocument::new(&document, String::new())
+            };
+            let parsed = parse("query Dashboard { a } mutation Dashboard2 { b }");
+            assert_eq!(parsed.query_name(Some("Dashboard")), Some("Dashboard"));
+            assert_eq!(parsed.query_name(Some("Dashboard2")), None);
+            assert_eq!(parsed.query_name(None), None);
+            let parsed = parse("mutation Dashboard { a }");
+            assert_eq!(parsed.query_name(Some("Dashboard")), None);
+            assert_eq!(parsed.query_name(None), None);
+            let parsed = parse("query Dashboard { a }");
+            assert_eq!(parsed.query_name(None), Some("Dashboard"));
+            assert_eq!(parse("{ a }").query_name(None), None);
+        }
+    }
+}
+
+#[cfg(feature = "redis")]
+pub use redis::{RedisQueryStore, ResponseCache};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_query_store() {
+        let store = MemoryQueryStore::new(2);
+        store.set("a", "{ a }").await;
+        store.set("b", "{ b }").await;
+        store.set("c", "{ c }").await;
+        assert_eq!(store.get("a").await, None);
+        assert_eq!(store.get("b").await.as_deref(), Some("{ b }"));
+        assert_eq!(store.get("c").await.as_deref(), Some("{ c }"));
+    }
+
+    #[tokio::test]
+    async fn test_persisted_queries() {
+        use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+
+        struct Query;
+
+        #[Object]
+        impl Query {
+            async fn value(&self) -> i32 {
+                42
+            }
+        }
+
+        let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+            .extension(PersistedQueries::new(MemoryQueryStore::new(8)))
+            .finish();
+        let query = "{ value }";
+        let hash = sha256_hex(query.as_bytes());
+        let persisted = |query: &str| {
+            let mut request = Request::new(query);
+            request.extensions.insert(
+                PERSISTED_QUERY.to_string(),
+                async_graphql::value!({ "version": 1, "sha256Hash": hash.clone() }),
+            );
+            request
+        };
+        let response = schema.execute(persisted("")).await;
+        assert_eq!(response.errors[0].message, PERSISTED_QUERY_NOT_FOUND);
+        let response = schema.execute(persisted(query)).await;
+        assert_eq!(response.data, async_graphql::value!({ "value": 42 }));
+        let response = schema.execute(persisted("")).await;
+        assert_eq!(response.data, async_graphql::value!({ "value": 42 }));
+    }
+}
@@ -1,11 +1,25 @@
 use serde::Deserialize;
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+
+use crate::context::TrustedProxies;
 
 #[derive(Deserialize)]
 pub struct Config {
     app_name: Option<Arc<str>>,
     host: Option<Arc<str>>,
     port: Option<u16>,
+    upload_max_file_size: Option<usize>,
+    upload_max_files: Option<usize>,
+    tls_cert_path: Option<Arc<str>>,
+    tls_key_path: Option<Arc<str>>,
+    tls_handshake_timeout_secs: Option<u64>,
+    keep_alive_secs: Option<u64>,
+    request_timeout_secs: Option<u64>,
+    shutdown_timeout_secs: Option<u64>,
+    #[serde(rename = "trusted_proxies")]
+    trusted_proxies_list: Option<Arc<str>>,
+    #[serde(skip)]
+    trusted_proxies: TrustedProxies,
     #[serde(skip)]
     address: Option<Arc<str>>,
 }
@@ -30,6 +44,65 @@ impl Config {
     pub fn port(&self) -> u16 {
         self.port.unwrap_or(3000)
     }
+
+    /// Maximum size of a single uploaded file in bytes, defaults to 10 MiB.
+    pub fn upload_max_file_size(&self) -> usize {
+        self.upload_max_file_size.unwrap_or(10 * 1024 * 1024)
+    }
+
+    pub fn upload_max_files(&self) -> usize {
+        self.upload_max_files.unwrap_or(10)
+    }
+
+    /// PEM encoded certificate chain, TLS is enabled if set together with
+    /// the private key.
+    pub fn tls_cert_path(&self) -> Option<&str> {
+        self.tls_cert_path.as_deref()
+    }
+
+    pub fn tls_key_path(&self) -> Option<&str> {
+        self.tls_key_path.as_deref()
+    }
+
+    /// Time a client gets to complete the TLS handshake, defaults to 10
+    /// seconds.
+    pub fn tls_handshake_timeout(&self) -> Duration {
+        Duration::from_secs(self.tls_handshake_timeout_secs.unwrap_or(10))
+    }
+
+    /// Interval of HTTP/2 keep-alive pings, `0` disables keep-alive for
+    /// HTTP/1 and HTTP/2 connections. Defaults to 75 seconds.
+    pub fn keep_alive(&self) -> Option<Duration> {
+        match self.keep_alive_secs.unwrap_or(75) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Maximum duration of a request, disabled by default.
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+    }
+
+    /// Time open connections get to finish after a shutdown signal,
+    /// defaults to 30 seconds.
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs.unwrap_or(30))
+    }
+
+    /// Reverse proxies allowed to set the client address, comma separated
+    /// addresses or CIDR ranges. None by default.
+    pub fn trusted_proxies(&self) -> &TrustedProxies {
+        &self.trusted_proxies
+    }
+
+    pub fn multipart_options(&self) -> async_graphql::http::MultipartOptions {
+        async_graphql::http::MultipartOptions::default()
+            .max_file_size(self.upload_max_file_size())
+            .max_num_files(self.upload_max_files())
+    }
 }
 
 #[derive(Default)]
@@ -52,6 +125,11 @@ impl<'a> ConfigBuilder<'a> {
         let host = cfg.host.as_deref().unwrap_or("127.0.0.1");
         let port = cfg.port.unwrap_or(3000);
         cfg.address = Some(Arc::from(format!("{}:{}", host, port)));
+        if let Some(list) = cfg.trusted_proxies_list.as_deref() {
+            cfg.trusted_proxies = list
+                .parse()
+                .map_err(|err: anyhow::Error| envy::Error::Custom(err.to_string()))?;
+        }
         Ok(cfg)
     }
 }
@@ -64,6 +142,14 @@ mod tests {
             .with_prefix("DEFAULT_SERVER_NOT_SET_IN_SHELL_")
             .build()?;
         assert_eq!(cfg.address(), "127.0.0.1:3000");
+        assert_eq!(cfg.upload_max_file_size(), 10 * 1024 * 1024);
+        assert_eq!(cfg.keep_alive(), Some(std::time::Duration::from_secs(75)));
+        assert_eq!(cfg.request_timeout(), None);
+        assert_eq!(cfg.trusted_proxies(), &Default::default());
+        assert_eq!(
+            cfg.tls_handshake_timeout(),
+            std::time::Duration::from_secs(10)
+        );
         Ok(())
     }
 
@@ -76,6 +162,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_trusted_proxies_test() -> envy::Result<()> {
+        std::env::set_var("SERVER_PROXIES_TRUSTED_PROXIES", "10.0.0.0/8, ::1");
+        let cfg = super::Config::builder()
+            .with_prefix("SERVER_PROXIES_")
+            .build()?;
+        assert!(cfg.trusted_proxies().contains("10.2.3.4".parse().unwrap()));
+        std::env::set_var("SERVER_INVALID_PROXIES_TRUSTED_PROXIES", "10.0.0.0/40");
+        assert!(super::Config::builder()
+            .with_prefix("SERVER_INVALID_PROXIES_")
+            .build()
+            .is_err());
+        Ok(())
+    }
+
     #[test]
     fn parse_prefixed_config_test() -> envy::Result<()> {
         std::env::set_var("SERVER_CUSTOM_HOST", "localhost");
@@ -0,0 +1,307 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, HeaderName},
+};
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+pub const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+pub const X_REAL_IP: HeaderName = HeaderName::from_static("x-real-ip");
+
+/// Addresses and CIDR ranges of reverse proxies allowed to set
+/// `x-forwarded-for` and `x-real-ip`, the headers of other peers are
+/// ignored. [`crate::serve`] adds the ones of
+/// [`crate::ServerConfig::trusted_proxies`] as request extension.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Arc<[(IpAddr, u8)]>);
+
+impl TrustedProxies {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.0.iter().any(|(net, prefix)| match (net, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - *prefix as u32).unwrap_or(0);
+                u32::from(*net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - *prefix as u32).unwrap_or(0);
+                u128::from(*net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+    }
+
+    /// Client address of a request from `peer`. If `peer` is trusted,
+    /// `x-forwarded-for` is walked from the right and the first untrusted
+    /// hop is used, otherwise `x-real-ip`.
+    pub fn client_ip(
+        &self,
+        peer: IpAddr,
+        forwarded_for: Option<&str>,
+        real_ip: Option<&str>,
+    ) -> IpAddr {
+        if !self.contains(peer) {
+            return peer;
+        }
+        if let Some(forwarded_for) = forwarded_for {
+            let mut client = peer;
+            for hop in forwarded_for.rsplit(',') {
+                let Ok(ip) = hop.trim().parse::<IpAddr>() else {
+                    break;
+                };
+                client = ip;
+                if !self.contains(ip) {
+                    break;
+                }
+            }
+            return client;
+        }
+        real_ip.and_then(|v| v.trim().parse().ok()).unwrap_or(peer)
+    }
+}
+
+impl std::str::FromStr for TrustedProxies {
+    type Err = anyhow::Error;
+
+    /// Comma separated addresses or CIDR ranges, e.g. `10.0.0.0/8, ::1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                let (ip, prefix) = v.split_once('/').unwrap_or((v, ""));
+                let ip: IpAddr = ip
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("invalid trusted proxy '{v}'"))?;
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                let prefix = match prefix {
+                    "" => max,
+                    prefix => prefix
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|p| *p <= max)
+                        .ok_or_else(|| anyhow::anyhow!("invalid trusted proxy '{v}'"))?,
+                };
+                Ok((ip, prefix))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map(|v| Self(Arc::from(v)))
+    }
+}
+
+/// Request metadata available as GraphQL data, used to correlate logs and
+/// to localize messages.
+#[derive(Debug, Clone)]
+pub struct RequestContext {
+    request_id: Arc<str>,
+    trace_id: Option<Arc<str>>,
+    traceparent: Option<Arc<str>>,
+    client_ip: Option<IpAddr>,
+    locale: Option<Arc<str>>,
+}
+
+impl Default for RequestContext {
+    fn default() -> Self {
+        Self {
+            request_id: Arc::from(uuid::Uuid::new_v4().to_string()),
+            trace_id: None,
+            traceparent: None,
+            client_ip: None,
+            locale: None,
+        }
+    }
+}
+
+impl RequestContext {
+    /// The `x-request-id` header, falls back to the trace id of the
+    /// `traceparent` header or a random id.
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    pub fn trace_id(&self) -> Option<&str> {
+        self.trace_id.as_deref()
+    }
+
+    /// The `traceparent` header if it carries a valid trace id.
+    pub fn traceparent(&self) -> Option<&str> {
+        self.traceparent.as_deref()
+    }
+
+    /// Address of the client, see [`TrustedProxies::client_ip`]. `None` if
+    /// the router wasn't served with the peer address as [`ConnectInfo`].
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+
+    /// Preferred language of the `Accept-Language` header.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn from_headers(
+        headers: &HeaderMap,
+        peer: Option<SocketAddr>,
+        trusted_proxies: &TrustedProxies,
+    ) -> Self {
+        let header = |name: &HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+        };
+        let traceparent = header(&TRACEPARENT);
+        let trace_id = traceparent.and_then(parse_traceparent);
+        let request_id = header(&X_REQUEST_ID)
+            .map(Arc::from)
+            .or_else(|| trace_id.map(Arc::from))
+            .unwrap_or_else(|| Arc::from(uuid::Uuid::new_v4().to_string()));
+        let client_ip = peer.map(|peer| {
+            trusted_proxies.client_ip(peer.ip(), header(&X_FORWARDED_FOR), header(&X_REAL_IP))
+        });
+        let locale = header(&axum::http::header::ACCEPT_LANGUAGE)
+            .and_then(parse_accept_language)
+            .map(Arc::from);
+        Self {
+            request_id,
+            trace_id: trace_id.map(Arc::from),
+            traceparent: traceparent.filter(|_| trace_id.is_some()).map(Arc::from),
+            client_ip,
+            locale,
+        }
+    }
+}
+
+/// Trace id of a W3C `traceparent` header, `00-<trace-id>-<parent-id>-<flags>`.
+fn parse_traceparent(v: &str) -> Option<&str> {
+    let mut parts = v.split('-');
+    let _version = parts.next()?;
+    let trace_id = parts.next()?;
+    (trace_id.len() == 32
+        && trace_id.chars().all(|c| c.is_ascii_hexdigit())
+        && trace_id.chars().any(|c| c != '0'))
+    .then_some(trace_id)
+}
+
+/// Language with the highest quality of an `Accept-Language` header.
+fn parse_accept_language(v: &str) -> Option<&str> {
+    v.split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let lang = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (!lang.is_empty() && lang != "*" && q > 0.0).then_some((lang, q))
+        })
+        .fold(None, |best: Option<(&str, f32)>, (lang, q)| match best {
+            Some((_, best_q)) if best_q >= q => best,
+            _ => Some((lang, q)),
+        })
+        .map(|(lang, _)| lang)
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for RequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|v| v.0);
+        let trusted_proxies = parts
+            .extensions
+            .get::<TrustedProxies>()
+            .cloned()
+            .unwrap_or_default();
+        Ok(Self::from_headers(&parts.headers, peer, &trusted_proxies))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_request_context_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+        );
+        headers.insert(
+            X_FORWARDED_FOR,
+            HeaderValue::from_static("203.0.113.7, 10.0.0.1"),
+        );
+        headers.insert(
+            axum::http::header::ACCEPT_LANGUAGE,
+            HeaderValue::from_static("en;q=0.8, de-DE, *;q=0.1"),
+        );
+        let peer: SocketAddr = "10.0.0.2:4000".parse().unwrap();
+        let ctx = RequestContext::from_headers(&headers, Some(peer), &TrustedProxies::default());
+        assert_eq!(ctx.trace_id(), Some("4bf92f3577b34da6a3ce929d0e0e4736"));
+        assert_eq!(ctx.request_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(ctx.client_ip(), Some(peer.ip()));
+        assert_eq!(ctx.locale(), Some("de-DE"));
+
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        let ctx = RequestContext::from_headers(&headers, Some(peer), &trusted);
+        assert_eq!(ctx.client_ip(), Some("203.0.113.7".parse().unwrap()));
+        assert_eq!(
+            RequestContext::from_headers(&headers, None, &trusted).client_ip(),
+            None
+        );
+
+        headers.insert(X_REQUEST_ID, HeaderValue::from_static("req-1"));
+        headers.remove(X_FORWARDED_FOR);
+        let peer: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let ctx = RequestContext::from_headers(&headers, Some(peer), &trusted);
+        assert_eq!(ctx.request_id(), "req-1");
+        assert_eq!(ctx.client_ip(), Some(peer.ip()));
+    }
+
+    #[test]
+    fn test_trusted_proxies() {
+        let trusted: TrustedProxies = "10.0.0.0/8, 192.168.1.1, fd00::/8".parse().unwrap();
+        let ip = |v: &str| v.parse::<IpAddr>().unwrap();
+        assert!(trusted.contains(ip("10.1.2.3")));
+        assert!(trusted.contains(ip("::ffff:10.1.2.3")));
+        assert!(trusted.contains(ip("fd12::1")));
+        assert!(!trusted.contains(ip("192.168.1.2")));
+        // spoofed left-most entries are skipped
+        assert_eq!(
+            trusted.client_ip(
+                ip("10.0.0.1"),
+                Some("1.1.1.1, 203.0.113.7, 192.168.1.1"),
+                None
+            ),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            trusted.client_ip(ip("203.0.113.9"), Some("1.1.1.1"), Some("1.1.1.1")),
+            ip("203.0.113.9")
+        );
+        assert_eq!(
+            trusted.client_ip(ip("10.0.0.1"), None, Some("198.51.100.1")),
+            ip("198.51.100.1")
+        );
+        assert_eq!(
+            trusted.client_ip(ip("10.0.0.1"), Some("10.0.0.5"), None),
+            ip("10.0.0.5")
+        );
+        assert!("10.0.0.0/33".parse::<TrustedProxies>().is_err());
+        assert!("proxy".parse::<TrustedProxies>().is_err());
+    }
+}
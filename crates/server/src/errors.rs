@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextRequest},
+    ErrorExtensionValues, Response, ServerError, Value,
+};
+
+use crate::RequestContext;
+
+const INTERNAL: &str = "INTERNAL";
+const BAD_REQUEST: &str = "BAD_REQUEST";
+const INTERNAL_MESSAGE: &str = "internal server error";
+
+/// GraphQL extension giving all errors of a response the same extensions,
+/// so clients of every service can handle them generically:
+///
+/// - `code`: category like `NOT_FOUND` or `INTERNAL`
+/// - `reason`: identifies the error, e.g. `NAME_CONFLICT`
+/// - `retryable`: whether repeating the request may succeed
+/// - `requestId`: id of the request to correlate it with the logs
+///
+/// The HTTP like status of entity errors is kept as `status`, `field`,
+/// `params` and `details` are kept as well. Errors without a category from
+/// resolvers, e.g. database or Keycloak failures, are internal, their
+/// message is replaced and only logged.
+#[derive(Clone, Copy)]
+pub struct ErrorMapper {
+    mask_internal: bool,
+}
+
+impl Default for ErrorMapper {
+    fn default() -> Self {
+        Self {
+            mask_internal: true,
+        }
+    }
+}
+
+impl ErrorMapper {
+    /// Keeps the messages of internal errors, e.g. in development.
+    pub fn with_mask_internal(mut self, mask_internal: bool) -> Self {
+        self.mask_internal = mask_internal;
+        self
+    }
+
+    /// Maps `err` to the uniform shape.
+    pub fn map(&self, mut err: ServerError, request_id: Option<&str>) -> ServerError {
+        let extensions = err.extensions.take().unwrap_or_default();
+        let code = match extensions.get("error") {
+            Some(Value::String(code)) => code.clone(),
+            // errors of the request itself, e.g. parsing or validation
+            _ if err.path.is_empty() => BAD_REQUEST.to_string(),
+            _ => INTERNAL.to_string(),
+        };
+        let reason = match extensions.get("key") {
+            Some(Value::String(key)) => key.clone(),
+            _ => code.clone(),
+        };
+        let retryable = matches!(extensions.get("retryable"), Some(Value::Boolean(true)));
+        let internal = code == INTERNAL;
+        if internal {
+            tracing::error!(
+                request_id,
+                path = ?err.path,
+                reason = reason.as_str(),
+                "{}",
+                err.message
+            );
+        }
+        let mut mapped = ErrorExtensionValues::default();
+        if !(internal && self.mask_internal) {
+            for key in ["field", "params", "details", "type"] {
+                if let Some(value) = extensions.get(key) {
+                    mapped.set(key, value.clone());
+                }
+            }
+        } else {
+            err.message = INTERNAL_MESSAGE.to_string();
+        }
+        if let Some(status) = extensions
+            .get("code")
+            .filter(|v| matches!(v, Value::Number(_)))
+        {
+            mapped.set("status", status.clone());
+        }
+        mapped.set("code", code);
+        mapped.set("reason", reason);
+        mapped.set("retryable", retryable);
+        if let Some(request_id) = request_id {
+            mapped.set("requestId", request_id);
+        }
+        err.extensions = Some(mapped);
+        err
+    }
+}
+
+impl ExtensionFactory for ErrorMapper {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for ErrorMapper {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+        if response.errors.is_empty() {
+            return response;
+        }
+        let request_id = ctx
+            .data_opt::<RequestContext>()
+            .map(|ctx| ctx.request_id().to_string());
+        response.errors = std::mem::take(&mut response.errors)
+            .into_iter()
+            .map(|err| self.map(err, request_id.as_deref()))
+            .collect();
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{ErrorExtensions, PathSegment};
+
+    use super::*;
+
+    fn resolver_error(err: async_graphql::Error) -> ServerError {
+        let mut err = err.into_server_error(Default::default());
+        err.path = vec![PathSegment::Field("customer".to_string())];
+        err
+    }
+
+    #[test]
+    fn test_map() {
+        let mapper = ErrorMapper::default();
+        let err =
+            async_graphql::Error::new("the resource Customer with name 'acme' already exists")
+                .extend_with(|_, e| {
+                    e.set("code", 409);
+                    e.set("error", "CONFLICT");
+                    e.set("key", "NAME_CONFLICT");
+                    e.set("field", "name");
+                    e.set("retryable", false);
+                });
+        let err = mapper.map(resolver_error(err), Some("r1"));
+        let ext = err.extensions.unwrap();
+        assert_eq!(ext.get("code"), Some(&"CONFLICT".into()));
+        assert_eq!(ext.get("reason"), Some(&"NAME_CONFLICT".into()));
+        assert_eq!(ext.get("status"), Some(&Value::from(409)));
+        assert_eq!(ext.get("field"), Some(&"name".into()));
+        assert_eq!(ext.get("retryable"), Some(&false.into()));
+        assert_eq!(ext.get("requestId"), Some(&"r1".into()));
+
+        let err = async_graphql::Error::new("pool timed out").extend_with(|_, e| {
+            e.set("code", 500);
+            e.set("error", "INTERNAL");
+            e.set("key", "LOCKED");
+            e.set("retryable", true);
+        });
+        let err = mapper.map(resolver_error(err), None);
+        assert_eq!(err.message, INTERNAL_MESSAGE);
+        let ext = err.extensions.unwrap();
+        assert_eq!(ext.get("reason"), Some(&"LOCKED".into()));
+        assert_eq!(ext.get("retryable"), Some(&true.into()));
+
+        let err = mapper.map(resolver_error("connection refused".into()), None);
+        assert_eq!(err.message, INTERNAL_MESSAGE);
+        assert_eq!(
+            err.extensions.unwrap().get("code"),
+            Some(&"INTERNAL".into())
+        );
+
+        let err = mapper.map(ServerError::new("unknown field", None), None);
+        assert_eq!(err.message, "unknown field");
+        assert_eq!(
+            err.extensions.unwrap().get("code"),
+            Some(&BAD_REQUEST.into())
+        );
+    }
+}
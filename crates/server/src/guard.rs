@@ -0,0 +1,368 @@
+use std::{collections::HashSet, marker::PhantomData, sync::Arc, sync::Mutex};
+
+use async_graphql::{
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+    },
+    parser::types::{
+        DocumentOperations, ExecutableDocument, OperationDefinition, OperationType as GqlType,
+        Selection, SelectionSet,
+    },
+    Name, Positioned, Request, ServerError, ServerResult, Variables,
+};
+use qm_role::{
+    operations::{Guard, OperationRegistry, OperationType},
+    Role,
+};
+
+/// Authorization of a request checked by [`OperationGuard`].
+#[async_trait::async_trait]
+pub trait OperationAuth<R, P>: Send + Sync + Sized + 'static
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    /// Decodes the authorization of the request, `None` for anonymous
+    /// requests.
+    async fn from_extension_context(ctx: &ExtensionContext<'_>) -> ServerResult<Option<Self>>;
+
+    fn satisfies(&self, role: &Role<R, P>) -> bool;
+}
+
+/// GraphQL extension denying operations with top-level fields which are not
+/// in the [`OperationRegistry`] or whose roles the user doesn't hold.
+///
+/// The check runs before any resolver, resolvers may still check the
+/// context of the accessed entities. Introspection fields are always
+/// allowed, use [`crate::SchemaHardening`] to disable introspection.
+pub struct OperationGuard<A, R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    registry: Arc<OperationRegistry<R, P>>,
+    _auth: PhantomData<fn() -> A>,
+}
+
+impl<A, R, P> OperationGuard<A, R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    pub fn new(registry: OperationRegistry<R, P>) -> Self {
+        Self {
+            registry: Arc::new(registry),
+            _auth: PhantomData,
+        }
+    }
+}
+
+impl<A, R, P> ExtensionFactory for OperationGuard<A, R, P>
+where
+    A: OperationAuth<R, P>,
+    R: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+    P: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+{
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(OperationGuardExtension::<A, R, P> {
+            registry: self.registry.clone(),
+            operation_name: Mutex::default(),
+            _auth: PhantomData,
+        })
+    }
+}
+
+struct OperationGuardExtension<A, R, P>
+where
+    R: std::fmt::Debug + std::marker::Copy + Clone,
+    P: std::fmt::Debug + std::marker::Copy + Clone,
+{
+    registry: Arc<OperationRegistry<R, P>>,
+    operation_name: Mutex<Option<String>>,
+    _auth: PhantomData<fn() -> A>,
+}
+
+fn forbidden(key: &str, message: String) -> ServerError {
+    let mut err = ServerError::new(message, None);
+    let extensions = err.extensions.get_or_insert_with(Default::default);
+    extensions.set("code", 403);
+    extensions.set("error", "FORBIDDEN");
+    extensions.set("key", key);
+    err
+}
+
+fn unauthorized(message: String) -> ServerError {
+    let mut err = ServerError::new(message, None);
+    let extensions = err.extensions.get_or_insert_with(Default::default);
+    extensions.set("code", 401);
+    extensions.set("error", "UNAUTHORIZED");
+    extensions.set("key", "UNAUTHORIZED");
+    err
+}
+
+/// Operation of the request, `None` if it doesn't exist, which is reported
+/// by the execution.
+fn operation<'a>(
+    document: &'a ExecutableDocument,
+    operation_name: Option<&str>,
+) -> Option<&'a Positioned<OperationDefinition>> {
+    match (&document.operations, operation_name) {
+        (DocumentOperations::Single(operation), _) => Some(operation),
+        (DocumentOperations::Multiple(operations), Some(name)) => operations.get(name),
+        (DocumentOperations::Multiple(operations), None) if operations.len() == 1 => {
+            operations.values().next()
+        }
+        _ => None,
+    }
+}
+
+/// Names of the top-level fields, including those of fragments.
+fn top_level_fields<'a>(
+    document: &'a ExecutableDocument,
+    selection_set: &'a SelectionSet,
+    visited: &mut HashSet<&'a Name>,
+    fields: &mut Vec<&'a Positioned<Name>>,
+) {
+    for selection in selection_set.items.iter() {
+        match &selection.node {
+            Selection::Field(field) => fields.push(&field.node.name),
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.node.fragment_name.node;
+                if let Some(fragment) = document.fragments.get(name) {
+                    if visited.insert(name) {
+                        top_level_fields(
+                            document,
+                            &fragment.node.selection_set.node,
+                            visited,
+                            fields,
+                        );
+                    }
+                }
+            }
+            Selection::InlineFragment(fragment) => {
+                top_level_fields(document, &fragment.node.selection_set.node, visited, fields)
+            }
+        }
+    }
+}
+
+impl<A, R, P> OperationGuardExtension<A, R, P>
+where
+    A: OperationAuth<R, P>,
+    R: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+    P: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+{
+    async fn check(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        document: &ExecutableDocument,
+    ) -> ServerResult<()> {
+        let operation_name = self.operation_name.lock().unwrap().take();
+        let Some(operation) = operation(document, operation_name.as_deref()) else {
+            return Ok(());
+        };
+        let ty = match operation.node.ty {
+            GqlType::Query => OperationType::Query,
+            GqlType::Mutation => OperationType::Mutation,
+            GqlType::Subscription => OperationType::Subscription,
+        };
+        let mut fields = vec![];
+        top_level_fields(
+            document,
+            &operation.node.selection_set.node,
+            &mut HashSet::new(),
+            &mut fields,
+        );
+        let mut auth: Option<Option<A>> = None;
+        for field in fields {
+            let name = field.node.as_str();
+            if name.starts_with("__") {
+                continue;
+            }
+            let Some(guard) = self.registry.get(ty, name) else {
+                return Err(forbidden(
+                    "OPERATION_NOT_REGISTERED",
+                    format!("the {ty} field '{name}' is not allowed"),
+                )
+                .with_pos(field.pos));
+            };
+            let roles = match guard {
+                Guard::Public => continue,
+                Guard::Authenticated => None,
+                Guard::AnyOf(roles) => Some(roles),
+            };
+            if auth.is_none() {
+                auth = Some(A::from_extension_context(ctx).await?);
+            }
+            let Some(Some(auth)) = auth.as_ref() else {
+                return Err(unauthorized(format!(
+                    "the {ty} field '{name}' requires authentication"
+                ))
+                .with_pos(field.pos));
+            };
+            if roles.is_some_and(|roles| !roles.iter().any(|role| auth.satisfies(role))) {
+                return Err(forbidden(
+                    "MISSING_ROLE",
+                    format!("the {ty} field '{name}' is forbidden"),
+                )
+                .with_pos(field.pos));
+            }
+        }
+        Ok(())
+    }
+}
+
+trait WithPos {
+    fn with_pos(self, pos: async_graphql::Pos) -> Self;
+}
+
+impl WithPos for ServerError {
+    fn with_pos(mut self, pos: async_graphql::Pos) -> Self {
+        self.locations = vec![pos];
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<A, R, P> Extension for OperationGuardExtension<A, R, P>
+where
+    A: OperationAuth<R, P>,
+    R: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+    P: std::fmt::Debug + std::marker::Copy + Clone + Send + Sync + 'static,
+{
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        *self.operation_name.lock().unwrap() = request.operation_name.clone();
+        next.run(ctx, request).await
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        let document = next.run(ctx, query, variables).await?;
+        self.check(ctx, &document).await?;
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::{EmptySubscription, Object, Schema};
+
+    use super::*;
+
+    #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+    enum Resource {
+        Entity,
+    }
+
+    #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+    enum Permission {
+        List,
+        Create,
+    }
+
+    struct User(Vec<Role<Resource, Permission>>);
+
+    #[async_trait::async_trait]
+    impl OperationAuth<Resource, Permission> for User {
+        async fn from_extension_context(ctx: &ExtensionContext<'_>) -> ServerResult<Option<Self>> {
+            Ok(ctx
+                .data_opt::<Vec<Role<Resource, Permission>>>()
+                .map(|roles| User(roles.clone())))
+        }
+
+        fn satisfies(&self, role: &Role<Resource, Permission>) -> bool {
+            self.0.contains(role)
+        }
+    }
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn health(&self) -> bool {
+            true
+        }
+
+        async fn entities(&self) -> Vec<String> {
+            vec![]
+        }
+
+        async fn secret(&self) -> String {
+            "secret".to_string()
+        }
+    }
+
+    struct Mutation;
+
+    #[Object]
+    impl Mutation {
+        async fn create_entity(&self) -> bool {
+            true
+        }
+    }
+
+    fn key(response: &async_graphql::Response) -> Option<String> {
+        response
+            .errors
+            .first()
+            .and_then(|err| match err.extensions.as_ref()?.get("key")? {
+                async_graphql::Value::String(key) => Some(key.clone()),
+                _ => None,
+            })
+    }
+
+    #[tokio::test]
+    async fn test_guard() {
+        let registry = OperationRegistry::new()
+            .with(OperationType::Query, "health", Guard::Public)
+            .with(
+                OperationType::Query,
+                "entities",
+                Guard::AnyOf(vec![Role::new(Resource::Entity, Some(Permission::List))]),
+            )
+            .with(
+                OperationType::Mutation,
+                "createEntity",
+                Guard::AnyOf(vec![Role::new(Resource::Entity, Some(Permission::Create))]),
+            );
+        let schema = Schema::build(Query, Mutation, EmptySubscription)
+            .extension(OperationGuard::<User, _, _>::new(registry))
+            .finish();
+        let reader = vec![Role::new(Resource::Entity, Some(Permission::List))];
+
+        let res = schema.execute("{ health __typename }").await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let res = schema.execute("{ entities }").await;
+        assert_eq!(key(&res).as_deref(), Some("UNAUTHORIZED"));
+        let res = schema
+            .execute(Request::new("{ ...F } fragment F on Query { entities }").data(reader.clone()))
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        let res = schema
+            .execute(Request::new("{ health secret }").data(reader.clone()))
+            .await;
+        assert_eq!(key(&res).as_deref(), Some("OPERATION_NOT_REGISTERED"));
+        let res = schema
+            .execute(Request::new("mutation { createEntity }").data(reader.clone()))
+            .await;
+        assert_eq!(key(&res).as_deref(), Some("MISSING_ROLE"));
+        let res = schema
+            .execute(
+                Request::new("query A { secret } mutation B { createEntity }")
+                    .operation_name("A")
+                    .data(reader),
+            )
+            .await;
+        assert_eq!(key(&res).as_deref(), Some("OPERATION_NOT_REGISTERED"));
+    }
+}
@@ -0,0 +1,181 @@
+use serde::Deserialize;
+
+/// Limits applied to a schema by [`build_schema`], loaded from the
+/// environment with the prefix `SERVER_GRAPHQL_` by default, e.g.
+/// `SERVER_GRAPHQL_MAX_DEPTH=16`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SchemaHardening {
+    max_depth: Option<usize>,
+    max_complexity: Option<usize>,
+    #[serde(default)]
+    disable_introspection: bool,
+    #[serde(default)]
+    disable_suggestions: bool,
+}
+
+impl SchemaHardening {
+    pub fn new() -> envy::Result<Self> {
+        Self::with_prefix("SERVER_GRAPHQL_")
+    }
+
+    pub fn with_prefix(prefix: &str) -> envy::Result<Self> {
+        envy::prefixed(prefix).from_env()
+    }
+
+    /// Defaults for production, introspection and field suggestions are
+    /// disabled.
+    pub fn production() -> Self {
+        Self {
+            max_depth: Some(16),
+            max_complexity: Some(1000),
+            disable_introspection: true,
+            disable_suggestions: true,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn with_max_complexity(mut self, max_complexity: usize) -> Self {
+        self.max_complexity = Some(max_complexity);
+        self
+    }
+
+    pub fn with_disable_introspection(mut self, disable_introspection: bool) -> Self {
+        self.disable_introspection = disable_introspection;
+        self
+    }
+
+    pub fn with_disable_suggestions(mut self, disable_suggestions: bool) -> Self {
+        self.disable_suggestions = disable_suggestions;
+        self
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    pub fn max_complexity(&self) -> Option<usize> {
+        self.max_complexity
+    }
+
+    pub fn introspection_disabled(&self) -> bool {
+        self.disable_introspection
+    }
+
+    pub fn suggestions_disabled(&self) -> bool {
+        self.disable_suggestions
+    }
+
+    pub fn apply<Q, M, S>(
+        &self,
+        mut builder: async_graphql::SchemaBuilder<Q, M, S>,
+    ) -> async_graphql::SchemaBuilder<Q, M, S> {
+        if let Some(max_depth) = self.max_depth {
+            builder = builder.limit_depth(max_depth);
+        }
+        if let Some(max_complexity) = self.max_complexity {
+            builder = builder.limit_complexity(max_complexity);
+        }
+        if self.disable_introspection {
+            builder = builder.disable_introspection();
+        }
+        if self.disable_suggestions {
+            builder = builder.disable_suggestions();
+        }
+        builder
+    }
+}
+
+/// Finishes `builder` with the limits of `hardening` applied.
+pub fn build_schema<Q, M, S>(
+    builder: async_graphql::SchemaBuilder<Q, M, S>,
+    hardening: &SchemaHardening,
+) -> async_graphql::Schema<Q, M, S>
+where
+    Q: async_graphql::ObjectType + 'static,
+    M: async_graphql::ObjectType + 'static,
+    S: async_graphql::SubscriptionType + 'static,
+{
+    hardening.apply(builder).finish()
+}
+
+/// Like [`build_schema`] with Apollo Federation v2 enabled, the schema
+/// exposes `_service` and `_entities` to be composed into a gateway.
+#[cfg(feature = "federation")]
+pub fn build_federated_schema<Q, M, S>(
+    builder: async_graphql::SchemaBuilder<Q, M, S>,
+    hardening: &SchemaHardening,
+) -> async_graphql::Schema<Q, M, S>
+where
+    Q: async_graphql::ObjectType + 'static,
+    M: async_graphql::ObjectType + 'static,
+    S: async_graphql::SubscriptionType + 'static,
+{
+    hardening.apply(builder.enable_federation()).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+
+    #[derive(SimpleObject)]
+    struct Node {
+        value: i32,
+    }
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn node(&self) -> Node {
+            Node { value: 1 }
+        }
+    }
+
+    #[test]
+    fn parse_hardening_config_test() -> envy::Result<()> {
+        std::env::set_var("SERVER_GRAPHQL_TEST_MAX_DEPTH", "4");
+        std::env::set_var("SERVER_GRAPHQL_TEST_DISABLE_INTROSPECTION", "true");
+        let cfg = SchemaHardening::with_prefix("SERVER_GRAPHQL_TEST_")?;
+        assert_eq!(cfg.max_depth(), Some(4));
+        assert_eq!(cfg.max_complexity(), None);
+        assert!(cfg.introspection_disabled());
+        assert!(!cfg.suggestions_disabled());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_schema() {
+        let schema = build_schema(
+            Schema::build(Query, EmptyMutation, EmptySubscription),
+            &SchemaHardening::production().with_max_depth(1),
+        );
+        let response = schema.execute("{ node { value } }").await;
+        assert!(response.errors[0].message.contains("too deep"));
+        let schema = build_schema(
+            Schema::build(Query, EmptyMutation, EmptySubscription),
+            &SchemaHardening::default()
+                .with_disable_introspection(true)
+                .with_disable_suggestions(true),
+        );
+        let response = schema.execute("{ __schema { queryType { name } } }").await;
+        assert_eq!(response.data, async_graphql::value!({ "__schema": null }));
+        let response = schema.execute("{ nod }").await;
+        assert!(!response.errors[0].message.contains("Did you mean"));
+    }
+
+    #[cfg(feature = "federation")]
+    #[tokio::test]
+    async fn test_build_federated_schema() {
+        let schema = build_federated_schema(
+            Schema::build(Query, EmptyMutation, EmptySubscription),
+            &SchemaHardening::production(),
+        );
+        let response = schema.execute("{ _service { sdl } }").await;
+        assert!(response.errors.is_empty());
+    }
+}
@@ -1,15 +1,42 @@
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::extract::Extension;
-use axum::http::header::HeaderMap;
-use axum::http::header::AUTHORIZATION;
 use qm_role::AuthContainer;
 
+mod cache;
 mod config;
+mod context;
+mod errors;
+mod guard;
+mod hardening;
+mod logging;
+mod router;
+mod schema;
+mod serve;
+mod subscription;
+mod upload;
+pub use cache::{MemoryQueryStore, PersistedQueries, QueryStore};
+#[cfg(feature = "redis")]
+pub use cache::{RedisQueryStore, ResponseCache};
 pub use config::Config as ServerConfig;
+pub use context::{RequestContext, TrustedProxies, X_REQUEST_ID};
+pub use errors::ErrorMapper;
+pub use guard::{OperationAuth, OperationGuard};
+#[cfg(feature = "federation")]
+pub use hardening::build_federated_schema;
+pub use hardening::{build_schema, SchemaHardening};
+pub use logging::{log_request, RequestLogger};
+pub use router::{router, RouterBuilder};
+pub use schema::SchemaBuilder;
+pub use serve::{serve, serve_with_coordinator, serve_with_shutdown, shutdown_signal};
+pub use subscription::{graphql_ws_handler, token_from_init_payload, SubscriptionRouterExt};
+pub use upload::graphql_upload_handler;
+#[cfg(feature = "s3")]
+pub use upload::{store_upload, StoredUpload};
 
 pub async fn graphql_handler<A, Q, M, S>(
     schema: Extension<async_graphql::Schema<Q, M, S>>,
-    headers: HeaderMap,
+    auth: AuthContainer<A>,
+    ctx: RequestContext,
     req: GraphQLRequest,
 ) -> GraphQLResponse
 where
@@ -18,11 +45,43 @@ where
     M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
     S: async_graphql::SubscriptionType + Send + Sync + 'static,
 {
-    let mut req = req.into_inner();
-    if let Some(auth_header) = headers.get(AUTHORIZATION).map(AuthContainer::<A>::from) {
-        req = req.data(auth_header);
-    } else {
-        req = req.data(AuthContainer::<A>::default());
+    execute(&schema, auth, ctx, req.into_inner()).await
+}
+
+/// Runs `req` with the auth and request context as data, shared by the
+/// JSON and multipart handlers.
+pub(crate) async fn execute<A, Q, M, S>(
+    schema: &async_graphql::Schema<Q, M, S>,
+    auth: AuthContainer<A>,
+    ctx: RequestContext,
+    req: async_graphql::Request,
+) -> GraphQLResponse
+where
+    A: Send + Sync + 'static,
+    Q: async_graphql::ObjectType + Send + Sync + 'static,
+    M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+    S: async_graphql::SubscriptionType + Send + Sync + 'static,
+{
+    let request_id = axum::http::HeaderValue::from_str(ctx.request_id()).ok();
+    #[cfg(feature = "otel")]
+    let span = {
+        let span = tracing::info_span!(
+            "graphql",
+            request_id = ctx.request_id(),
+            trace_id = ctx.trace_id()
+        );
+        if let Some(traceparent) = ctx.traceparent() {
+            qm_utils::telemetry::set_parent(&span, traceparent);
+        }
+        span
+    };
+    let req = req.data(auth).data(ctx);
+    #[cfg(feature = "otel")]
+    let mut res = tracing::Instrument::instrument(schema.execute(req), span).await;
+    #[cfg(not(feature = "otel"))]
+    let mut res = schema.execute(req).await;
+    if let Some(request_id) = request_id {
+        res.http_headers.insert(X_REQUEST_ID, request_id);
     }
-    schema.execute(req).await.into()
+    res.into()
 }
@@ -0,0 +1,274 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use async_graphql::{
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextExecute, NextPrepareRequest, NextRequest,
+    },
+    Request, Response, ServerResult, Value, Variables,
+};
+use axum::{extract::Request as HttpRequest, middleware::Next, response::Response as HttpResponse};
+use qm_role::AuthContainer;
+
+use crate::RequestContext;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Logs method, path, status and duration of every HTTP request, use with
+/// `axum::middleware::from_fn(qm_server::log_request)`.
+pub async fn log_request(request: HttpRequest, next: Next) -> HttpResponse {
+    let start = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+    let size = response
+        .headers()
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    tracing::info!(
+        http.method = %method,
+        http.path = %path,
+        http.status = response.status().as_u16(),
+        http.response_size = size,
+        duration_ms = start.elapsed().as_millis() as u64,
+        "http request"
+    );
+    response
+}
+
+type UserIdFn<A> = Arc<dyn Fn(&A) -> Option<String> + Send + Sync>;
+
+/// GraphQL extension logging operation name, duration, user id and error
+/// codes as structured tracing events, the response size is logged if
+/// enabled with [`RequestLogger::response_size`].
+///
+/// Variables are logged on debug level, values of variables and input
+/// fields whose name contains one of the [`RequestLogger::redact`] patterns,
+/// ignoring case, are replaced.
+pub struct RequestLogger<A> {
+    user_id: UserIdFn<A>,
+    sensitive: Arc<Vec<String>>,
+    response_size: bool,
+}
+
+impl<A> RequestLogger<A>
+where
+    A: Send + Sync + 'static,
+{
+    pub fn new<F>(user_id: F) -> Self
+    where
+        F: Fn(&A) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            user_id: Arc::new(user_id),
+            sensitive: Arc::new(
+                ["password", "secret", "token", "apikey", "authorization"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            response_size: false,
+        }
+    }
+
+    /// Adds patterns of variable and input field names whose values must
+    /// not be logged, e.g. `key` also redacts `apiKey` and `KEY_ID`.
+    pub fn redact<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Arc::make_mut(&mut self.sensitive)
+            .extend(patterns.into_iter().map(|v| v.into().to_lowercase()));
+        self
+    }
+
+    /// Logs the size of the serialized response, which requires serializing
+    /// every response once more.
+    pub fn response_size(mut self) -> Self {
+        self.response_size = true;
+        self
+    }
+}
+
+impl<A> ExtensionFactory for RequestLogger<A>
+where
+    A: Send + Sync + 'static,
+{
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RequestLoggerExtension {
+            user_id: self.user_id.clone(),
+            sensitive: self.sensitive.clone(),
+            response_size: self.response_size,
+            state: Mutex::default(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct State {
+    operation_name: Option<String>,
+    variables: Option<Value>,
+}
+
+struct RequestLoggerExtension<A> {
+    user_id: UserIdFn<A>,
+    sensitive: Arc<Vec<String>>,
+    response_size: bool,
+    state: Mutex<State>,
+}
+
+fn is_sensitive(name: &str, sensitive: &[String]) -> bool {
+    let name = name.to_lowercase();
+    sensitive
+        .iter()
+        .any(|pattern| name.contains(pattern.as_str()))
+}
+
+fn redact(value: Value, sensitive: &[String]) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .into_iter()
+                .map(|(k, v)| {
+                    if is_sensitive(k.as_str(), sensitive) {
+                        (k, Value::String(REDACTED.to_string()))
+                    } else {
+                        (k, redact(v, sensitive))
+                    }
+                })
+                .collect(),
+        ),
+        Value::List(list) => Value::List(list.into_iter().map(|v| redact(v, sensitive)).collect()),
+        value => value,
+    }
+}
+
+fn redact_variables(variables: &Variables, sensitive: &[String]) -> Value {
+    redact(variables.clone().into_value(), sensitive)
+}
+
+fn error_codes(response: &Response) -> Vec<String> {
+    response
+        .errors
+        .iter()
+        .filter_map(|err| err.extensions.as_ref()?.get("code"))
+        .map(|code| match code {
+            Value::String(code) => code.clone(),
+            code => code.to_string(),
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl<A> Extension for RequestLoggerExtension<A>
+where
+    A: Send + Sync + 'static,
+{
+    async fn prepare_request(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        request: Request,
+        next: NextPrepareRequest<'_>,
+    ) -> ServerResult<Request> {
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            let variables = redact_variables(&request.variables, &self.sensitive);
+            if let Ok(mut state) = self.state.lock() {
+                state.variables = Some(variables);
+            }
+        }
+        next.run(ctx, request).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        if let Ok(mut state) = self.state.lock() {
+            state.operation_name = operation_name.map(String::from);
+        }
+        next.run(ctx, operation_name).await
+    }
+
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let start = Instant::now();
+        let response = next.run(ctx).await;
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let State {
+            operation_name,
+            variables,
+        } = self
+            .state
+            .lock()
+            .map(|mut state| std::mem::take(&mut *state))
+            .unwrap_or_default();
+        let user_id = match ctx.data_opt::<AuthContainer<A>>() {
+            Some(auth) => auth.read().await.as_ref().and_then(|a| (self.user_id)(a)),
+            None => None,
+        };
+        let request_id = ctx
+            .data_opt::<RequestContext>()
+            .map(|ctx| ctx.request_id().to_string());
+        let response_size = self
+            .response_size
+            .then(|| serde_json::to_vec(&response).map(|v| v.len()).ok())
+            .flatten();
+        let error_codes = error_codes(&response);
+        if response.is_ok() {
+            tracing::info!(
+                graphql.operation = operation_name.as_deref(),
+                user_id = user_id.as_deref(),
+                request_id = request_id.as_deref(),
+                response_size,
+                duration_ms,
+                "graphql request"
+            );
+        } else {
+            tracing::warn!(
+                graphql.operation = operation_name.as_deref(),
+                user_id = user_id.as_deref(),
+                request_id = request_id.as_deref(),
+                error_codes = ?error_codes,
+                response_size,
+                duration_ms,
+                "graphql request failed"
+            );
+        }
+        if let Some(variables) = variables {
+            tracing::debug!(
+                graphql.operation = operation_name.as_deref(),
+                request_id = request_id.as_deref(),
+                %variables,
+                "graphql variables"
+            );
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_variables() {
+        let variables = Variables::from_json(serde_json::json!({
+            "username": "admin",
+            "newPassword": "1234",
+            "input": [{ "accessToken": "abc", "PASSWORD": "x", "name": "x" }],
+        }));
+        let sensitive = ["password", "token"].map(String::from);
+        assert_eq!(
+            redact_variables(&variables, &sensitive),
+            async_graphql::value!({
+                "username": "admin",
+                "newPassword": REDACTED,
+                "input": [{ "accessToken": REDACTED, "PASSWORD": REDACTED, "name": "x" }],
+            })
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::{future::Future, marker::PhantomData, sync::Arc};
+
+use async_graphql::http::{GraphiQLSource, MultipartOptions};
+use axum::{
+    extract::Extension,
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Router,
+};
+use futures::future::BoxFuture;
+use prometheus_client::registry::Registry;
+use qm_role::{TokenSource, TokenSources};
+
+use crate::{graphql_handler, graphql_upload_handler, SubscriptionRouterExt};
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+type Check = Arc<dyn Fn() -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+#[derive(Clone, Default)]
+struct Checks(Arc<[(Arc<str>, Check)]>);
+
+/// Creates a [`RouterBuilder`] mounting the GraphQL handler of `schema`
+/// together with `/healthz`, `/readyz` and optionally `/metrics`.
+///
+/// ```ignore
+/// let router = qm::server::router::<Authorization, _, _, _>(schema)
+///     .graphql_path("/api/graphql")
+///     .graphiql()
+///     .readiness_check("mongodb", move || {
+///         let db = db.clone();
+///         async move { db.ping().await.map(|_| ()) }
+///     })
+///     .metrics(registry)
+///     .build();
+/// ```
+pub fn router<A, Q, M, S>(schema: async_graphql::Schema<Q, M, S>) -> RouterBuilder<A, Q, M, S>
+where
+    A: Send + Sync + 'static,
+    Q: async_graphql::ObjectType + Send + Sync + 'static,
+    M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+    S: async_graphql::SubscriptionType + Send + Sync + 'static,
+{
+    RouterBuilder {
+        schema,
+        graphql_path: "/graphql".to_string(),
+        subscription_path: None,
+        graphiql: false,
+        uploads: None,
+        cookie: None,
+        checks: vec![],
+        registry: None,
+        _auth: PhantomData,
+    }
+}
+
+pub struct RouterBuilder<A, Q, M, S> {
+    schema: async_graphql::Schema<Q, M, S>,
+    graphql_path: String,
+    subscription_path: Option<String>,
+    graphiql: bool,
+    uploads: Option<MultipartOptions>,
+    cookie: Option<&'static str>,
+    checks: Vec<(Arc<str>, Check)>,
+    registry: Option<Arc<Registry>>,
+    _auth: PhantomData<A>,
+}
+
+impl<A, Q, M, S> RouterBuilder<A, Q, M, S>
+where
+    A: Send + Sync + 'static,
+    Q: async_graphql::ObjectType + Send + Sync + 'static,
+    M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+    S: async_graphql::SubscriptionType + Send + Sync + 'static,
+{
+    pub fn graphql_path(mut self, path: &str) -> Self {
+        self.graphql_path = path.to_string();
+        self
+    }
+
+    /// Mounts the websocket handler for subscriptions at `path`.
+    pub fn subscription_path(mut self, path: &str) -> Self {
+        self.subscription_path = Some(path.to_string());
+        self
+    }
+
+    /// Serves the GraphiQL IDE on `GET` of the GraphQL path.
+    pub fn graphiql(mut self) -> Self {
+        self.graphiql = true;
+        self
+    }
+
+    /// Accepts file uploads following the GraphQL multipart request spec on
+    /// the GraphQL path, see [`graphql_upload_handler`] for the required
+    /// headers.
+    pub fn uploads(mut self, options: MultipartOptions) -> Self {
+        self.uploads = Some(options);
+        self
+    }
+
+    /// Accepts the access token from the cookie `name` in addition to the
+    /// `Authorization` header, only for same-origin requests, see
+    /// [`qm_role::TokenSource::Cookie`].
+    pub fn cookie_auth(mut self, name: &'static str) -> Self {
+        self.cookie = Some(name);
+        self
+    }
+
+    /// Adds a check run by `/readyz`, the service is ready if all checks
+    /// succeed.
+    pub fn readiness_check<F, Fut>(mut self, name: &str, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.checks
+            .push((Arc::from(name), Arc::new(move || Box::pin(check()))));
+        self
+    }
+
+    /// Exposes `registry` in the OpenMetrics text format at `/metrics`.
+    pub fn metrics(mut self, registry: Arc<Registry>) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    pub fn build(self) -> Router {
+        let graphql = match self.uploads {
+            Some(_) => post(graphql_upload_handler::<A, Q, M, S>),
+            None => post(graphql_handler::<A, Q, M, S>),
+        };
+        let graphql = if self.graphiql {
+            let endpoint = self.graphql_path.clone();
+            let subscription_endpoint = self.subscription_path.clone();
+            graphql.get(move || graphiql(endpoint, subscription_endpoint))
+        } else {
+            graphql
+        };
+        let graphql = match self.cookie {
+            Some(name) => graphql.layer(Extension(TokenSources::new([
+                TokenSource::Header,
+                TokenSource::Cookie(name),
+            ]))),
+            None => graphql,
+        };
+        let mut router = Router::new()
+            .route(&self.graphql_path, graphql)
+            .route("/healthz", get(healthz))
+            .route("/readyz", get(readyz))
+            .layer(Extension(Checks(Arc::from(self.checks))));
+        if let Some(path) = self.subscription_path.as_deref() {
+            router = router.graphql_subscription::<A, Q, M, S>(path);
+        }
+        if let Some(options) = self.uploads {
+            router = router.layer(Extension(options));
+        }
+        if let Some(registry) = self.registry {
+            router = router.route("/metrics", get(move || metrics(registry)));
+        }
+        router.layer(Extension(self.schema))
+    }
+}
+
+async fn graphiql(endpoint: String, subscription_endpoint: Option<String>) -> impl IntoResponse {
+    let source = GraphiQLSource::build().endpoint(&endpoint);
+    let source = match subscription_endpoint.as_deref() {
+        Some(subscription_endpoint) => source.subscription_endpoint(subscription_endpoint),
+        None => source,
+    };
+    Html(source.finish())
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn run_checks(checks: &Checks) -> (bool, serde_json::Map<String, serde_json::Value>) {
+    let results = futures::future::join_all(checks.0.iter().map(|(name, check)| async move {
+        let result = check().await;
+        if let Err(err) = result.as_ref() {
+            tracing::warn!("readiness check {name} failed: {err:#}");
+        }
+        (name.clone(), result)
+    }))
+    .await;
+    let ready = results.iter().all(|(_, result)| result.is_ok());
+    let details = results
+        .into_iter()
+        .map(|(name, result)| {
+            let status = match result {
+                Ok(()) => "ok".to_string(),
+                Err(err) => format!("{err:#}"),
+            };
+            (name.to_string(), serde_json::Value::String(status))
+        })
+        .collect();
+    (ready, details)
+}
+
+async fn readyz(Extension(checks): Extension<Checks>) -> Response {
+    let (ready, details) = run_checks(&checks).await;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let body = serde_json::json!({
+        "status": if ready { "ok" } else { "error" },
+        "checks": details,
+    });
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+async fn metrics(registry: Arc<Registry>) -> Response {
+    let mut body = String::new();
+    match prometheus_client::encoding::text::encode(&mut body, &registry) {
+        Ok(()) => ([(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_checks() {
+        let ok: Check = Arc::new(|| Box::pin(async { Ok(()) }));
+        let failing: Check = Arc::new(|| Box::pin(async { anyhow::bail!("unreachable") }));
+        let checks = Checks(Arc::from(vec![
+            (Arc::from("mongodb"), ok.clone()),
+            (Arc::from("redis"), failing),
+        ]));
+        let (ready, details) = run_checks(&checks).await;
+        assert!(!ready);
+        assert_eq!(details["mongodb"], "ok");
+        assert_eq!(details["redis"], "unreachable");
+        let (ready, _) = run_checks(&Checks(Arc::from(vec![(Arc::from("mongodb"), ok)]))).await;
+        assert!(ready);
+    }
+}
@@ -0,0 +1,168 @@
+use async_graphql::{dataloader::DataLoader, extensions::ExtensionFactory};
+use qm_role::AuthContainer;
+
+use crate::errors::ErrorMapper;
+use crate::hardening::SchemaHardening;
+
+/// Builds the schema of a service with storage, authentication, extensions,
+/// limits and data loaders.
+///
+/// ```ignore
+/// let schema = SchemaBuilder::<QueryRoot, MutationRoot, EmptySubscription>::default()
+///     .with_storage(store)
+///     .with_access_token::<Authorization>(Some(token))
+///     .with_data_loader(UserLoader::new(db))
+///     .build();
+/// ```
+pub struct SchemaBuilder<Q, M, S> {
+    builder: async_graphql::SchemaBuilder<Q, M, S>,
+    hardening: Option<SchemaHardening>,
+    #[cfg(feature = "federation")]
+    federation: bool,
+}
+
+impl<Q, M, S> Default for SchemaBuilder<Q, M, S>
+where
+    Q: async_graphql::ObjectType + Default + 'static,
+    M: async_graphql::ObjectType + Default + 'static,
+    S: async_graphql::SubscriptionType + Default + 'static,
+{
+    fn default() -> Self {
+        Self::new(Q::default(), M::default(), S::default())
+    }
+}
+
+impl<Q, M, S> SchemaBuilder<Q, M, S>
+where
+    Q: async_graphql::ObjectType + 'static,
+    M: async_graphql::ObjectType + 'static,
+    S: async_graphql::SubscriptionType + 'static,
+{
+    pub fn new(query: Q, mutation: M, subscription: S) -> Self {
+        Self {
+            builder: async_graphql::Schema::build(query, mutation, subscription),
+            hardening: None,
+            #[cfg(feature = "federation")]
+            federation: false,
+        }
+    }
+
+    /// Storage of the service, available in resolvers with `ctx.data`.
+    pub fn with_storage<T: Send + Sync + 'static>(self, storage: T) -> Self {
+        self.with_data(storage)
+    }
+
+    pub fn with_data<T: Send + Sync + 'static>(mut self, data: T) -> Self {
+        self.builder = self.builder.data(data);
+        self
+    }
+
+    /// Authenticates every request with `access_token`, e.g. in tests which
+    /// execute the schema directly. Without a token requests are anonymous
+    /// unless the HTTP handler provides the [`AuthContainer`].
+    pub fn with_access_token<A: Send + Sync + 'static>(self, access_token: Option<&str>) -> Self {
+        match access_token {
+            Some(access_token) => self.with_data(AuthContainer::<A>::new(access_token)),
+            None => self.with_data(AuthContainer::<A>::default()),
+        }
+    }
+
+    /// Installs `loader` as [`DataLoader`] running on tokio.
+    pub fn with_data_loader<T: Send + Sync + 'static>(self, loader: T) -> Self {
+        self.with_data(DataLoader::new(loader, tokio::spawn))
+    }
+
+    pub fn with_extension(mut self, extension: impl ExtensionFactory) -> Self {
+        self.builder = self.builder.extension(extension);
+        self
+    }
+
+    /// Uniform error extensions, internal errors are masked.
+    pub fn with_error_mapper(self) -> Self {
+        self.with_extension(ErrorMapper::default())
+    }
+
+    /// Spans for requests and resolvers with `tracing`.
+    #[cfg(feature = "graphql-tracing")]
+    pub fn with_tracing(self) -> Self {
+        self.with_extension(async_graphql::extensions::Tracing)
+    }
+
+    /// Resolver timings in the `tracing` response extension.
+    #[cfg(feature = "apollo-tracing")]
+    pub fn with_apollo_tracing(self) -> Self {
+        self.with_extension(async_graphql::extensions::ApolloTracing)
+    }
+
+    /// Limits of the schema, loaded from the environment if not set.
+    pub fn with_hardening(mut self, hardening: SchemaHardening) -> Self {
+        self.hardening = Some(hardening);
+        self
+    }
+
+    #[cfg(feature = "federation")]
+    pub fn with_federation(mut self) -> Self {
+        self.federation = true;
+        self
+    }
+
+    /// Applies `f` to the underlying builder, for registration functions
+    /// like `qm_entity::loader::register`.
+    pub fn map(
+        mut self,
+        f: impl FnOnce(async_graphql::SchemaBuilder<Q, M, S>) -> async_graphql::SchemaBuilder<Q, M, S>,
+    ) -> Self {
+        self.builder = f(self.builder);
+        self
+    }
+
+    pub fn build(self) -> async_graphql::Schema<Q, M, S> {
+        let hardening = self.hardening.unwrap_or_else(|| {
+            SchemaHardening::new().unwrap_or_else(|err| {
+                tracing::warn!("invalid schema hardening config: {err}");
+                Default::default()
+            })
+        });
+        #[cfg(feature = "federation")]
+        if self.federation {
+            return crate::hardening::build_federated_schema(self.builder, &hardening);
+        }
+        crate::hardening::build_schema(self.builder, &hardening)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::{Context, EmptyMutation, EmptySubscription, Object};
+
+    struct Storage(&'static str);
+
+    #[derive(Default)]
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn storage(&self, ctx: &Context<'_>) -> &'static str {
+            ctx.data_unchecked::<Storage>().0
+        }
+
+        async fn authenticated(&self, ctx: &Context<'_>) -> bool {
+            ctx.data_unchecked::<AuthContainer<()>>().has_encoded()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schema_builder() {
+        let schema = SchemaBuilder::<Query, EmptyMutation, EmptySubscription>::default()
+            .with_storage(Storage("memory"))
+            .with_access_token::<()>(Some("a.b.c"))
+            .with_hardening(SchemaHardening::default().with_max_depth(1))
+            .build();
+        let response = schema.execute("{ storage authenticated }").await;
+        assert_eq!(
+            response.data,
+            async_graphql::value!({ "storage": "memory", "authenticated": true })
+        );
+    }
+}
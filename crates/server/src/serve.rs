@@ -0,0 +1,322 @@
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, Extension},
+    Router,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::conn::auto::Builder,
+};
+use qm_utils::shutdown::{Shutdown, ShutdownPhase};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{oneshot, watch},
+    task::JoinSet,
+};
+use tower::Service;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::ServerConfig;
+
+/// Delay before accepting again after an error other than a failed
+/// connection.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_secs(1);
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send + 'static {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + 'static> Io for T {}
+
+#[cfg(feature = "tls")]
+type TlsAcceptor = tokio_rustls::TlsAcceptor;
+#[cfg(not(feature = "tls"))]
+type TlsAcceptor = std::convert::Infallible;
+
+#[cfg(feature = "tls")]
+fn load_tls(cert_path: &str, key_path: &str) -> anyhow::Result<TlsAcceptor> {
+    use std::{fs::File, io::BufReader};
+    use tokio_rustls::rustls;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path)?))?
+        .ok_or_else(|| anyhow::anyhow!("no private key found in {key_path}"))?;
+    let mut config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+#[cfg(not(feature = "tls"))]
+fn load_tls(_: &str, _: &str) -> anyhow::Result<TlsAcceptor> {
+    anyhow::bail!("TLS is configured but qm-server was built without the `tls` feature")
+}
+
+fn tls_acceptor(config: &ServerConfig) -> anyhow::Result<Option<TlsAcceptor>> {
+    match (config.tls_cert_path(), config.tls_key_path()) {
+        (Some(cert_path), Some(key_path)) => load_tls(cert_path, key_path).map(Some),
+        (None, None) => Ok(None),
+        _ => anyhow::bail!("TLS requires both a certificate and a private key"),
+    }
+}
+
+#[cfg(feature = "tls")]
+async fn accept(
+    tls: &Option<TlsAcceptor>,
+    stream: tokio::net::TcpStream,
+    handshake_timeout: Duration,
+) -> std::io::Result<Box<dyn Io>> {
+    Ok(match tls {
+        Some(tls) => Box::new(
+            tokio::time::timeout(handshake_timeout, tls.accept(stream))
+                .await
+                .map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::TimedOut, "TLS handshake timed out")
+                })??,
+        ),
+        None => Box::new(stream),
+    })
+}
+
+#[cfg(not(feature = "tls"))]
+async fn accept(
+    _: &Option<TlsAcceptor>,
+    stream: tokio::net::TcpStream,
+    _: Duration,
+) -> std::io::Result<Box<dyn Io>> {
+    Ok(Box::new(stream))
+}
+
+/// Completes on `Ctrl+C` or `SIGTERM`.
+pub async fn shutdown_signal() {
+    qm_utils::shutdown::signal().await
+}
+
+/// Binds the address of `config` and serves `router` until
+/// [`shutdown_signal`] completes.
+pub async fn serve(router: Router, config: &ServerConfig) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(config.address()).await?;
+    serve_with_shutdown(listener, router, config, shutdown_signal()).await
+}
+
+/// Serves `router` until `shutdown` runs, the connection drain is part of
+/// its [`ShutdownPhase::Ingress`].
+pub async fn serve_with_coordinator(
+    listener: TcpListener,
+    router: Router,
+    config: &ServerConfig,
+    shutdown: &Shutdown,
+) -> anyhow::Result<()> {
+    let (done_tx, done_rx) = oneshot::channel::<()>();
+    shutdown.register(
+        "http server",
+        ShutdownPhase::Ingress,
+        config.shutdown_timeout() + Duration::from_secs(1),
+        || async move {
+            done_rx.await.ok();
+            Ok(())
+        },
+    );
+    let result = serve_with_shutdown(listener, router, config, shutdown.token().triggered()).await;
+    done_tx.send(()).ok();
+    result
+}
+
+/// Serves `router` with the TLS, keep-alive and timeout settings of
+/// `config`. After `signal` completes no new connections are accepted and
+/// open connections are drained for at most
+/// [`ServerConfig::shutdown_timeout`], connections still open afterwards are
+/// aborted.
+pub async fn serve_with_shutdown<F>(
+    listener: TcpListener,
+    router: Router,
+    config: &ServerConfig,
+    signal: F,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()> + Send,
+{
+    let tls = Arc::new(tls_acceptor(config)?);
+    let router = match config.request_timeout() {
+        Some(timeout) => router.layer(TimeoutLayer::new(timeout)),
+        None => router,
+    }
+    .layer(Extension(config.trusted_proxies().clone()));
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .keep_alive(config.keep_alive().is_some());
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(config.keep_alive());
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    let handshake_timeout = config.tls_handshake_timeout();
+    let mut connections = JoinSet::new();
+    tracing::info!(
+        "listening on {}{}",
+        listener.local_addr()?,
+        if tls.is_some() { " (tls)" } else { "" }
+    );
+    tokio::pin!(signal);
+    loop {
+        let (stream, peer) = tokio::select! {
+            result = listener.accept() => match result {
+                Ok(v) => v,
+                Err(err) if is_connection_error(&err) => continue,
+                Err(err) => {
+                    // e.g. out of file descriptors, retrying at once would spin
+                    tracing::warn!("unable to accept connection: {err:#}");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+            _ = &mut signal => break,
+        };
+        let builder = builder.clone();
+        let router = router.clone();
+        let tls = tls.clone();
+        let shutdown_rx = shutdown_rx.clone();
+        connections.spawn(async move {
+            match accept(&tls, stream, handshake_timeout).await {
+                Ok(io) => serve_connection(builder, io, router, peer, shutdown_rx).await,
+                Err(err) => tracing::debug!("TLS handshake with {peer} failed: {err:#}"),
+            }
+        });
+    }
+    drop(listener);
+    shutdown_tx.send(()).ok();
+    let drain = config.shutdown_timeout();
+    let drained = tokio::time::timeout(drain, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        tracing::warn!(
+            "{} connections still open after {drain:?}, closing them",
+            connections.len()
+        );
+        connections.shutdown().await;
+    }
+    Ok(())
+}
+
+/// Errors of a single connection, accepting again right away is fine.
+fn is_connection_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::ConnectionRefused
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::ConnectionReset
+    )
+}
+
+async fn serve_connection(
+    builder: Builder<TokioExecutor>,
+    io: Box<dyn Io>,
+    router: Router,
+    peer: SocketAddr,
+    mut shutdown: watch::Receiver<()>,
+) {
+    let service = hyper::service::service_fn(move |mut req: hyper::Request<Incoming>| {
+        req.extensions_mut().insert(ConnectInfo(peer));
+        router.clone().call(req)
+    });
+    let conn = builder.serve_connection_with_upgrades(TokioIo::new(io), service);
+    tokio::pin!(conn);
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = shutdown.changed() => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(err) = result {
+        tracing::debug!("connection to {peer} closed: {err:#}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_serve_with_shutdown() -> anyhow::Result<()> {
+        let config = ServerConfig::builder()
+            .with_prefix("DEFAULT_SERVE_NOT_SET_IN_SHELL_")
+            .build()?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new().route(
+            "/peer",
+            get(|ConnectInfo(peer): ConnectInfo<SocketAddr>| async move { peer.ip().to_string() }),
+        );
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_with_shutdown(listener, router, &config, async {
+                rx.await.ok();
+            })
+            .await
+        });
+        let mut stream = tokio::net::TcpStream::connect(address).await?;
+        stream
+            .write_all(b"GET /peer HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("127.0.0.1"));
+        tx.send(()).ok();
+        server.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_abort_after_drain() -> anyhow::Result<()> {
+        std::env::set_var("SERVE_DRAIN_TEST_SHUTDOWN_TIMEOUT_SECS", "1");
+        let config = ServerConfig::builder()
+            .with_prefix("SERVE_DRAIN_TEST_")
+            .build()?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let router = Router::new().route("/pending", get(std::future::pending::<()>));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            serve_with_shutdown(listener, router, &config, async {
+                rx.await.ok();
+            })
+            .await
+        });
+        let mut stream = tokio::net::TcpStream::connect(address).await?;
+        stream
+            .write_all(b"GET /pending HTTP/1.1\r\nhost: localhost\r\n\r\n")
+            .await?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        tx.send(()).ok();
+        tokio::time::timeout(Duration::from_secs(5), server).await???;
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await.ok();
+        assert!(response.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tls_requires_cert_and_key() -> anyhow::Result<()> {
+        std::env::set_var("SERVER_TLS_TEST_TLS_CERT_PATH", "cert.pem");
+        let config = ServerConfig::builder()
+            .with_prefix("SERVER_TLS_TEST_")
+            .build()?;
+        assert!(tls_acceptor(&config).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use async_graphql::{http::ALL_WEBSOCKET_PROTOCOLS, Data};
+use async_graphql_axum::{GraphQLProtocol, GraphQLWebSocket};
+use axum::{
+    extract::{Extension, WebSocketUpgrade},
+    http::{HeaderMap, Uri},
+    response::Response,
+    routing::get,
+    Router,
+};
+use qm_role::AuthContainer;
+
+use crate::RequestContext;
+
+const BEARER_PREFIX: &str = "Bearer ";
+/// Keys of the `connection_init` payload checked for the access token.
+const INIT_PAYLOAD_KEYS: [&str; 3] = ["Authorization", "authorization", qm_role::ACCESS_TOKEN];
+
+/// Access token of a `connection_init` payload, e.g.
+/// `{ "Authorization": "Bearer <token>" }` or `{ "access_token": "<token>" }`.
+pub fn token_from_init_payload(payload: &serde_json::Value) -> Option<&str> {
+    INIT_PAYLOAD_KEYS
+        .iter()
+        .filter_map(|key| payload.get(key)?.as_str())
+        .map(|v| v.strip_prefix(BEARER_PREFIX).unwrap_or(v))
+        .find(|v| !v.is_empty())
+}
+
+/// Serves GraphQL subscriptions over websockets, the token of the
+/// `connection_init` payload takes precedence over the one of the upgrade
+/// request, which is read from the [`qm_role::WEBSOCKET_TOKEN_SOURCES`].
+pub async fn graphql_ws_handler<A, Q, M, S>(
+    Extension(schema): Extension<async_graphql::Schema<Q, M, S>>,
+    headers: HeaderMap,
+    uri: Uri,
+    ctx: RequestContext,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response
+where
+    A: Send + Sync + 'static,
+    Q: async_graphql::ObjectType + Send + Sync + 'static,
+    M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+    S: async_graphql::SubscriptionType + Send + Sync + 'static,
+{
+    let auth = AuthContainer::<A>::from_sources(&headers, &uri, &qm_role::WEBSOCKET_TOKEN_SOURCES);
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |stream| {
+            GraphQLWebSocket::new(stream, schema, protocol)
+                .on_connection_init(move |payload| async move {
+                    let auth = token_from_init_payload(&payload)
+                        .map(AuthContainer::<A>::new)
+                        .unwrap_or(auth);
+                    let mut data = Data::default();
+                    data.insert(auth);
+                    data.insert(ctx);
+                    Ok(data)
+                })
+                .serve()
+        })
+}
+
+pub trait SubscriptionRouterExt {
+    /// Mounts [`graphql_ws_handler`] at `path`, the schema has to be
+    /// provided as [`Extension`] layer.
+    fn graphql_subscription<A, Q, M, S>(self, path: &str) -> Self
+    where
+        A: Send + Sync + 'static,
+        Q: async_graphql::ObjectType + Send + Sync + 'static,
+        M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+        S: async_graphql::SubscriptionType + Send + Sync + 'static;
+}
+
+impl<St> SubscriptionRouterExt for Router<St>
+where
+    St: Clone + Send + Sync + 'static,
+{
+    fn graphql_subscription<A, Q, M, S>(self, path: &str) -> Self
+    where
+        A: Send + Sync + 'static,
+        Q: async_graphql::ObjectType + Send + Sync + 'static,
+        M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+        S: async_graphql::SubscriptionType + Send + Sync + 'static,
+    {
+        self.route(path, get(graphql_ws_handler::<A, Q, M, S>))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_from_init_payload() {
+        let payload = serde_json::json!({ "Authorization": "Bearer abc" });
+        assert_eq!(token_from_init_payload(&payload), Some("abc"));
+        let payload = serde_json::json!({ "access_token": "def" });
+        assert_eq!(token_from_init_payload(&payload), Some("def"));
+        let payload = serde_json::json!({ "authorization": "" });
+        assert_eq!(token_from_init_payload(&payload), None);
+        assert_eq!(token_from_init_payload(&serde_json::Value::Null), None);
+    }
+}
@@ -0,0 +1,121 @@
+use async_graphql::http::MultipartOptions;
+use async_graphql_axum::{rejection::GraphQLRejection, GraphQLResponse};
+use axum::{
+    extract::{Extension, Request},
+    http::{header::CONTENT_TYPE, HeaderMap, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::TryStreamExt;
+use qm_role::AuthContainer;
+
+use crate::{execute, RequestContext};
+
+const APOLLO_REQUIRE_PREFLIGHT: HeaderName = HeaderName::from_static("apollo-require-preflight");
+const X_APOLLO_OPERATION_NAME: HeaderName = HeaderName::from_static("x-apollo-operation-name");
+
+/// Multipart requests are simple requests a browser sends cross-origin
+/// without a CORS preflight, one of these non-simple headers is required
+/// to rule out CSRF.
+fn has_preflight_header(headers: &HeaderMap) -> bool {
+    [APOLLO_REQUIRE_PREFLIGHT, X_APOLLO_OPERATION_NAME]
+        .iter()
+        .any(|name| headers.get(name).is_some_and(|v| !v.is_empty()))
+}
+
+/// GraphQL handler accepting JSON requests as well as the GraphQL multipart
+/// request spec, limits are taken from an `Extension<MultipartOptions>`,
+/// see [`crate::ServerConfig::multipart_options`]. Multipart requests
+/// must carry an `Apollo-Require-Preflight` or `X-Apollo-Operation-Name`
+/// header.
+pub async fn graphql_upload_handler<A, Q, M, S>(
+    schema: Extension<async_graphql::Schema<Q, M, S>>,
+    options: Option<Extension<MultipartOptions>>,
+    auth: AuthContainer<A>,
+    ctx: RequestContext,
+    request: Request,
+) -> Result<GraphQLResponse, Response>
+where
+    A: Send + Sync + 'static,
+    Q: async_graphql::ObjectType + Send + Sync + 'static,
+    M: async_graphql::ObjectType + async_graphql::ContainerType + Send + Sync + 'static,
+    S: async_graphql::SubscriptionType + Send + Sync + 'static,
+{
+    let options = options
+        .map(|Extension(options)| options)
+        .unwrap_or_default();
+    let content_type = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let multipart = content_type
+        .as_deref()
+        .is_some_and(|v| v.starts_with("multipart/"));
+    if multipart && !has_preflight_header(request.headers()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "multipart requests require an Apollo-Require-Preflight header",
+        )
+            .into_response());
+    }
+    let body = request
+        .into_body()
+        .into_data_stream()
+        .map_err(std::io::Error::other)
+        .into_async_read();
+    let req = async_graphql::http::receive_body(content_type, body, options)
+        .await
+        .map_err(|err| GraphQLRejection(err).into_response())?;
+    Ok(execute(&schema, auth, ctx, req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_has_preflight_header() {
+        let mut headers = HeaderMap::new();
+        assert!(!has_preflight_header(&headers));
+        headers.insert(X_APOLLO_OPERATION_NAME, HeaderValue::from_static(""));
+        assert!(!has_preflight_header(&headers));
+        headers.insert(APOLLO_REQUIRE_PREFLIGHT, HeaderValue::from_static("true"));
+        assert!(has_preflight_header(&headers));
+    }
+}
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use async_graphql::{SimpleObject, UploadValue};
+    use qm_s3::S3;
+
+    /// Object created from an upload by [`store_upload`].
+    #[derive(Debug, Clone, SimpleObject)]
+    pub struct StoredUpload {
+        pub key: String,
+        pub filename: String,
+        pub content_type: Option<String>,
+        pub size: u64,
+    }
+
+    /// Streams the spooled upload into the bucket of `s3` at `key`.
+    pub async fn store_upload(
+        s3: &S3,
+        key: &str,
+        upload: UploadValue,
+    ) -> anyhow::Result<StoredUpload> {
+        let size = s3
+            .put_file(key, upload.content, upload.content_type.as_deref())
+            .await?;
+        Ok(StoredUpload {
+            key: key.to_string(),
+            filename: upload.filename,
+            content_type: upload.content_type,
+            size,
+        })
+    }
+}
+
+#[cfg(feature = "s3")]
+pub use s3::{store_upload, StoredUpload};
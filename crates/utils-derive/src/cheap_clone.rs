@@ -1,8 +1,209 @@
+use proc_macro2::{TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, spanned::Spanned, Data, DeriveInput, Field, Fields, GenericParam, Ident, Path,
+    Type,
+};
+
 pub fn expand(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    // Parse the string representation
-    // let mut input = syn::parse_macro_input!(input as syn::DeriveInput);
-    // expand_derive_rcobj(&mut input)
-    //     .unwrap_or_else(syn::Error::into_compile_error)
-    //     .into()
-    input
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_derive(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a field is cloned.
+#[derive(Clone, Copy, PartialEq)]
+enum Strategy {
+    /// `Arc` and `Rc` fields, cloned without requiring anything from the
+    /// pointee, this covers `Arc<str>` and `Arc<[T]>`.
+    Pointer,
+    /// Fields marked with `#[cheap_clone(skip)]`, deep-cloned with `Clone`.
+    Clone,
+    Cheap,
+}
+
+struct Options {
+    krate: Path,
+}
+
+fn options(input: &DeriveInput) -> syn::Result<Options> {
+    let mut krate = parse_quote!(::qm_utils);
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("cheap_clone"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                krate = value.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported cheap_clone attribute, expected `crate`"))
+            }
+        })?;
+    }
+    Ok(Options { krate })
+}
+
+fn strategy(field: &Field) -> syn::Result<Strategy> {
+    let mut skip = false;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("cheap_clone"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported cheap_clone attribute, expected `skip`"))
+            }
+        })?;
+    }
+    if skip {
+        return Ok(Strategy::Clone);
+    }
+    if let Type::Path(ty) = &field.ty {
+        let pointer = ty.qself.is_none()
+            && ty
+                .path
+                .segments
+                .last()
+                .is_some_and(|s| s.ident == "Arc" || s.ident == "Rc");
+        if pointer {
+            return Ok(Strategy::Pointer);
+        }
+    }
+    Ok(Strategy::Cheap)
+}
+
+fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(i) => &i == ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+fn clone_expr(krate: &Path, strategy: Strategy, value: TokenStream) -> TokenStream {
+    match strategy {
+        Strategy::Pointer | Strategy::Clone => quote!(::std::clone::Clone::clone(#value)),
+        Strategy::Cheap => quote!(#krate::CheapClone::cheap_clone(#value)),
+    }
+}
+
+/// Clones the bindings `names` of `fields` and builds `path` from them.
+fn construct(
+    krate: &Path,
+    path: TokenStream,
+    fields: &Fields,
+    names: &[Ident],
+    strategies: &[Strategy],
+) -> TokenStream {
+    let values = names
+        .iter()
+        .zip(strategies)
+        .map(|(name, strategy)| clone_expr(krate, *strategy, quote!(#name)));
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#idents: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#path ( #(#values),* )),
+        Fields::Unit => path,
+    }
+}
+
+fn pattern(path: TokenStream, fields: &Fields, names: &[Ident]) -> TokenStream {
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#idents: #names),* })
+        }
+        Fields::Unnamed(_) => quote!(#path ( #(#names),* )),
+        Fields::Unit => path,
+    }
+}
+
+fn expand_derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let Options { krate } = options(input)?;
+    let variants: Vec<(TokenStream, &Fields)> = match &input.data {
+        Data::Struct(data) => vec![(quote!(Self), &data.fields)],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|v| {
+                let ident = &v.ident;
+                (quote!(Self::#ident), &v.fields)
+            })
+            .collect(),
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span(),
+                "CheapClone can not be derived for unions",
+            ))
+        }
+    };
+    let mut typed = Vec::new();
+    let mut arms = Vec::new();
+    for (path, fields) in variants {
+        let names: Vec<Ident> = (0..fields.len())
+            .map(|i| format_ident!("__field{i}"))
+            .collect();
+        let strategies = fields
+            .iter()
+            .map(strategy)
+            .collect::<syn::Result<Vec<_>>>()?;
+        let pattern = pattern(path.clone(), fields, &names);
+        let body = construct(&krate, path, fields, &names, &strategies);
+        arms.push(quote!(#pattern => #body));
+        typed.extend(fields.iter().map(|f| &f.ty).zip(strategies));
+    }
+
+    // Field types mentioning a type parameter are bound by how they are
+    // cloned, so `Arc<T>` and `&T` fields don't require anything from `T`.
+    let mut generics = input.generics.clone();
+    let params: Vec<Ident> = generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) => Some(t.ident.clone()),
+            _ => None,
+        })
+        .collect();
+    let where_clause = generics.make_where_clause();
+    let mut bound = Vec::new();
+    for (ty, strategy) in typed {
+        if !params.iter().any(|param| mentions(quote!(#ty), param)) {
+            continue;
+        }
+        let predicate: syn::WherePredicate = match strategy {
+            Strategy::Pointer => continue,
+            Strategy::Cheap => parse_quote!(#ty: #krate::CheapClone),
+            Strategy::Clone => parse_quote!(#ty: ::std::clone::Clone),
+        };
+        if !bound.contains(&predicate) {
+            bound.push(predicate.clone());
+            where_clause.predicates.push(predicate);
+        }
+    }
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let body = if arms.is_empty() {
+        quote!(match *self {})
+    } else {
+        quote!(match self { #(#arms,)* })
+    };
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #krate::CheapClone for #ident #ty_generics #where_clause {
+            #[inline]
+            fn cheap_clone(&self) -> Self {
+                #body
+            }
+        }
+    })
 }
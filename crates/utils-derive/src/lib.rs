@@ -2,7 +2,11 @@ use proc_macro::TokenStream;
 
 mod cheap_clone;
 
-#[proc_macro_derive(CheapClone)]
+/// Implements `CheapClone` by cheap-cloning every field. `Arc` and `Rc`
+/// fields are cloned directly, fields marked `#[cheap_clone(skip)]` are
+/// cloned with `Clone`. Use `#[cheap_clone(crate = "qm::utils")]` when
+/// `qm_utils` is only reachable through another crate.
+#[proc_macro_derive(CheapClone, attributes(cheap_clone))]
 pub fn cheap_clone(item: TokenStream) -> TokenStream {
     cheap_clone::expand(item)
 }
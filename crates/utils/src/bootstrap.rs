@@ -0,0 +1,227 @@
+//! Ordered initialization of the dependencies of a service.
+//!
+//! Every dependency is initialized by a named step which is retried with the
+//! [`RetryPolicy`] of the [`Bootstrap`]. Steps run in the order they are
+//! awaited, so a step can use the results of earlier ones. A failed step
+//! returns a [`BootstrapError`] naming the dependency instead of the bare
+//! error of the client library.
+//!
+//! ```ignore
+//! let mut bootstrap = Bootstrap::new();
+//! let config = bootstrap.load("config", ServerConfig::new)?;
+//! let db = bootstrap.init("mongodb", || DB::new(config.app_name(), &db_config)).await?;
+//! bootstrap.ready("redis", || redis.ping()).await?;
+//! tracing::info!("started in {:?}", bootstrap.elapsed());
+//! ```
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::retry::RetryPolicy;
+
+/// Initialization of a dependency failed after all attempts.
+#[derive(Debug)]
+pub struct BootstrapError {
+    dependency: String,
+    attempts: u32,
+    source: anyhow::Error,
+}
+
+impl BootstrapError {
+    /// Name of the step which failed.
+    pub fn dependency(&self) -> &str {
+        &self.dependency
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+}
+
+impl Display for BootstrapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unable to initialize {}", self.dependency)?;
+        if self.attempts > 1 {
+            write!(f, " after {} attempts", self.attempts)?;
+        }
+        write!(f, ": {:#}", self.source)
+    }
+}
+
+impl std::error::Error for BootstrapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Outcome of a step of the [`Bootstrap`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub name: String,
+    pub attempts: u32,
+    pub elapsed: Duration,
+    pub ready: bool,
+}
+
+pub struct Bootstrap {
+    policy: RetryPolicy,
+    start: Instant,
+    steps: Vec<Step>,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bootstrap {
+    /// Retries steps for up to a minute with exponential backoff.
+    pub fn new() -> Self {
+        Self {
+            policy: RetryPolicy::exponential(Duration::from_millis(500))
+                .with_max_delay(Duration::from_secs(10))
+                .with_max_attempts(u32::MAX)
+                .with_max_elapsed(Duration::from_secs(60)),
+            start: Instant::now(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn with_policy(mut self, policy: RetryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Runs `f` once, for steps which can't succeed on retry, e.g. reading
+    /// the configuration from the environment.
+    pub fn load<T, E, F>(&mut self, name: &str, f: F) -> Result<T, BootstrapError>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: Into<anyhow::Error>,
+    {
+        let start = Instant::now();
+        let result = f().map_err(Into::into);
+        self.finish(name, 1, start, result)
+    }
+
+    /// Runs `f` until it succeeds or the policy allows no further attempt.
+    pub async fn init<T, E, F, Fut>(&mut self, name: &str, mut f: F) -> Result<T, BootstrapError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        let start = Instant::now();
+        let mut backoff = self.policy.backoff();
+        loop {
+            let err = match f().await {
+                Ok(value) => return self.finish(name, backoff.attempt() + 1, start, Ok(value)),
+                Err(err) => err.into(),
+            };
+            let Some(delay) = backoff.next() else {
+                return self.finish(name, backoff.attempt(), start, Err(err));
+            };
+            tracing::warn!(
+                "{name} not ready after {} attempts, retrying in {delay:?}: {err:#}",
+                backoff.attempt()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Waits until the readiness probe `f` of an initialized dependency
+    /// succeeds.
+    pub async fn ready<E, F, Fut>(&mut self, name: &str, f: F) -> Result<(), BootstrapError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: Into<anyhow::Error>,
+    {
+        self.init(name, f).await
+    }
+
+    fn finish<T>(
+        &mut self,
+        name: &str,
+        attempts: u32,
+        start: Instant,
+        result: anyhow::Result<T>,
+    ) -> Result<T, BootstrapError> {
+        let step = Step {
+            name: name.to_string(),
+            attempts,
+            elapsed: start.elapsed(),
+            ready: result.is_ok(),
+        };
+        self.steps.push(step);
+        match result {
+            Ok(value) => {
+                tracing::info!("{name} ready in {:?}", start.elapsed());
+                Ok(value)
+            }
+            Err(source) => {
+                let err = BootstrapError {
+                    dependency: name.to_string(),
+                    attempts,
+                    source,
+                };
+                tracing::error!("{err}");
+                Err(err)
+            }
+        }
+    }
+
+    /// Steps run so far, in order.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Time since the bootstrap was created.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_bootstrap() {
+        let mut bootstrap = Bootstrap::new()
+            .with_policy(RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3));
+        let port: u16 = bootstrap.load("config", || "8080".parse()).unwrap();
+        let calls = AtomicU32::new(0);
+        let db = bootstrap
+            .init("db", || async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => anyhow::bail!("connection refused"),
+                    _ => Ok(port),
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(db, 8080);
+        let err = bootstrap
+            .ready("cache", || async { anyhow::bail!("connection refused") })
+            .await
+            .unwrap_err();
+        assert_eq!(err.dependency(), "cache");
+        assert_eq!(
+            err.to_string(),
+            "unable to initialize cache after 3 attempts: connection refused"
+        );
+        let steps: Vec<(&str, u32, bool)> = bootstrap
+            .steps()
+            .iter()
+            .map(|step| (step.name.as_str(), step.attempts, step.ready))
+            .collect();
+        assert_eq!(
+            steps,
+            [("config", 1, true), ("db", 2, true), ("cache", 3, false)]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Clones which only copy values or bump reference counts, so they can be
+/// done freely, e.g. once per request. Derive it with `#[derive(CheapClone)]`.
+pub trait CheapClone {
+    fn cheap_clone(&self) -> Self;
+}
+
+impl<T: ?Sized> CheapClone for Arc<T> {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        Arc::clone(self)
+    }
+}
+
+impl<T: ?Sized> CheapClone for Rc<T> {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        Rc::clone(self)
+    }
+}
+
+impl<T: ?Sized> CheapClone for &T {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        self
+    }
+}
+
+impl<T: CheapClone> CheapClone for Option<T> {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        self.as_ref().map(CheapClone::cheap_clone)
+    }
+}
+
+impl<T> CheapClone for std::marker::PhantomData<T> {
+    #[inline]
+    fn cheap_clone(&self) -> Self {
+        *self
+    }
+}
+
+macro_rules! impl_copy {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl CheapClone for $ty {
+                #[inline]
+                fn cheap_clone(&self) -> Self {
+                    *self
+                }
+            }
+        )*
+    };
+}
+
+impl_copy!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    Duration,
+    Instant,
+    SystemTime,
+);
+
+#[cfg(test)]
+mod tests {
+    use crate::CheapClone;
+    use std::sync::Arc;
+
+    #[derive(CheapClone)]
+    struct Config {
+        name: Arc<str>,
+        hosts: Arc<[String]>,
+        port: u16,
+        #[cheap_clone(skip)]
+        tags: Vec<String>,
+    }
+
+    /// `T` isn't `CheapClone`, it is only used behind an `Arc`.
+    #[derive(CheapClone)]
+    struct Shared<T, U> {
+        value: Arc<T>,
+        extra: Option<U>,
+    }
+
+    /// Bound by the field type `&'a T`, which is `CheapClone` for any `T`.
+    #[derive(CheapClone)]
+    struct Borrowed<'a, T> {
+        value: &'a T,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Deep(String);
+
+    #[derive(CheapClone)]
+    enum Source<T> {
+        Empty,
+        Inline(Arc<str>, u32),
+        Remote { url: Arc<str>, retries: Option<T> },
+    }
+
+    #[test]
+    fn test_derive() {
+        let config = Config {
+            name: Arc::from("qm"),
+            hosts: Arc::from(vec!["a".to_string()]),
+            port: 80,
+            tags: vec!["x".to_string()],
+        };
+        let clone = config.cheap_clone();
+        assert!(Arc::ptr_eq(&config.name, &clone.name));
+        assert!(Arc::ptr_eq(&config.hosts, &clone.hosts));
+        assert_eq!((clone.port, clone.tags), (80, config.tags));
+
+        let shared = Shared {
+            value: Arc::new(Deep("deep".to_string())),
+            extra: Some(1u8),
+        };
+        let clone = shared.cheap_clone();
+        assert!(Arc::ptr_eq(&shared.value, &clone.value));
+        assert_eq!(clone.extra, Some(1));
+
+        let deep = Deep("deep".to_string());
+        let borrowed = Borrowed { value: &deep };
+        assert!(std::ptr::eq(borrowed.cheap_clone().value, &deep));
+
+        let url: Arc<str> = Arc::from("http://localhost");
+        let source = Source::Remote {
+            url: url.clone(),
+            retries: Some(3u32),
+        };
+        match source.cheap_clone() {
+            Source::Remote { url: u, retries } => {
+                assert!(Arc::ptr_eq(&u, &url));
+                assert_eq!(retries, Some(3));
+            }
+            _ => unreachable!(),
+        }
+        assert!(matches!(
+            Source::<u32>::Inline(url, 2).cheap_clone(),
+            Source::Inline(_, 2)
+        ));
+        assert!(matches!(Source::<u32>::Empty.cheap_clone(), Source::Empty));
+    }
+}
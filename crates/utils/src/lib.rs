@@ -1 +1,12 @@
+extern crate self as qm_utils;
+
+pub mod bootstrap;
+mod cheap_clone;
+pub mod retry;
+pub mod secret;
+pub mod shutdown;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+
+pub use cheap_clone::CheapClone;
 pub use qm_utils_derive::CheapClone;
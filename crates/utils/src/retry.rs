@@ -0,0 +1,264 @@
+//! Retries of fallible async operations with exponential backoff and jitter.
+//!
+//! ```ignore
+//! let policy = RetryPolicy::exponential(Duration::from_millis(100)).with_max_attempts(5);
+//! let value = retry(&policy, || async { fetch().await }).await?;
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt::Display;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: u32,
+    jitter: f64,
+    max_attempts: u32,
+    max_elapsed: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2,
+            jitter: 0.5,
+            max_attempts: 5,
+            max_elapsed: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delays start at `initial_delay` and double with every retry.
+    pub fn exponential(initial_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            ..Default::default()
+        }
+    }
+
+    /// Waits `delay` before every retry, without jitter.
+    pub fn fixed(delay: Duration) -> Self {
+        Self {
+            initial_delay: delay,
+            max_delay: delay,
+            multiplier: 1,
+            jitter: 0.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: u32) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Fraction between 0 and 1 by which delays are randomly shortened, so
+    /// clients failing at the same time don't retry at the same time.
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Attempts including the first one, at least one attempt is made.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// No retry is started if it would begin after `max_elapsed` since the
+    /// first attempt.
+    pub fn with_max_elapsed(mut self, max_elapsed: Duration) -> Self {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Delay before the retry following the failed `attempt`, starting at 1,
+    /// without jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(self.multiplier.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+
+    fn jittered(&self, attempt: u32) -> Duration {
+        let delay = self.delay(attempt);
+        if self.jitter == 0.0 {
+            return delay;
+        }
+        let random = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        delay.mul_f64(1.0 - self.jitter * random)
+    }
+
+    /// Delays between the attempts of one operation, for loops which can't
+    /// use [`retry`].
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            policy: *self,
+            attempt: 0,
+            start: Instant::now(),
+            waited: Duration::ZERO,
+        }
+    }
+}
+
+/// Yields the delay before each retry, ends when no retry is left.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    policy: RetryPolicy,
+    attempt: u32,
+    start: Instant,
+    waited: Duration,
+}
+
+impl Backoff {
+    /// Number of failed attempts so far.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.attempt += 1;
+        if self.attempt >= self.policy.max_attempts {
+            return None;
+        }
+        let delay = self.policy.jittered(self.attempt);
+        // delays handed out count as elapsed even if the caller didn't sleep yet
+        let elapsed = self.start.elapsed().max(self.waited);
+        if self
+            .policy
+            .max_elapsed
+            .is_some_and(|max| elapsed + delay > max)
+        {
+            return None;
+        }
+        self.waited += delay;
+        Some(delay)
+    }
+}
+
+/// Runs `f` until it succeeds or `policy` allows no further attempt.
+pub async fn retry<T, E, F, Fut>(policy: &RetryPolicy, f: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    retry_if(policy, |_| true, f).await
+}
+
+/// Like [`retry`], errors for which `retryable` returns false are returned
+/// immediately.
+pub async fn retry_if<T, E, F, Fut, P>(
+    policy: &RetryPolicy,
+    mut retryable: P,
+    mut f: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: FnMut(&E) -> bool,
+    E: Display,
+{
+    let mut backoff = policy.backoff();
+    loop {
+        let err = match f().await {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        if !retryable(&err) {
+            return Err(err);
+        }
+        let Some(delay) = backoff.next() else {
+            return Err(err);
+        };
+        tracing::warn!(
+            "attempt {} failed, retrying in {delay:?}: {err:#}",
+            backoff.attempt()
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::exponential(Duration::from_millis(100))
+            .with_max_delay(Duration::from_secs(1))
+            .with_max_attempts(10);
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(3), Duration::from_millis(400));
+        assert_eq!(policy.delay(8), Duration::from_secs(1));
+        for (attempt, delay) in (1..).zip(policy.backoff()) {
+            assert!(delay <= policy.delay(attempt));
+            assert!(delay >= policy.delay(attempt) / 2);
+        }
+        assert_eq!(policy.backoff().count(), 9);
+        let fixed: Vec<Duration> = RetryPolicy::fixed(Duration::from_millis(5))
+            .with_max_attempts(3)
+            .backoff()
+            .collect();
+        assert_eq!(fixed, [Duration::from_millis(5); 2]);
+        let elapsed = RetryPolicy::fixed(Duration::from_secs(1))
+            .with_max_attempts(10)
+            .with_max_elapsed(Duration::from_millis(1500));
+        assert_eq!(elapsed.backoff().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let policy = RetryPolicy::fixed(Duration::from_millis(1)).with_max_attempts(3);
+        let calls = AtomicU32::new(0);
+        let result: Result<u32, String> = retry(&policy, || async {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("busy".to_string()),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(1));
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), String> = retry(&policy, || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err("busy".to_string())
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), String> = retry_if(
+            &policy,
+            |err| err != "fatal",
+            || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err("fatal".to_string())
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
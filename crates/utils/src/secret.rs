@@ -0,0 +1,235 @@
+//! Secrets of the configuration which don't have to be in the environment.
+//!
+//! A [`SecretSource`] resolves the secrets of a config, e.g.
+//! `MONGODB_PASSWORD`, from the first of
+//!
+//! 1. the environment variable itself,
+//! 2. the file named by `<KEY>_FILE`, e.g. a Docker or Kubernetes secret,
+//! 3. the [`SecretProvider`]s of the source, in order.
+//!
+//! With the `vault` feature secrets can be loaded from HashiCorp Vault with
+//! `VaultKv`.
+//!
+//! [`SecretSource::vars`] returns the environment with the resolved secrets,
+//! to be deserialized by the `ConfigBuilder`s with `envy::from_iter`.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Environment variable with the directory of [`SecretSource::from_env`].
+pub const SECRETS_DIR: &str = "QM_SECRETS_DIR";
+const FILE_SUFFIX: &str = "_FILE";
+
+/// Additional store of secrets, e.g. for secrets written by an agent of a
+/// secret manager.
+pub trait SecretProvider: Send + Sync {
+    /// Value of the secret named like its environment variable.
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// Secrets stored as files named by their key, like mounted Kubernetes
+/// secrets, Docker secrets in `/run/secrets` or files rendered by the Vault
+/// agent. Lowercase file names are accepted as well.
+#[derive(Debug, Clone)]
+pub struct SecretDir {
+    path: PathBuf,
+}
+
+impl SecretDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SecretProvider for SecretDir {
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        for name in [key.to_string(), key.to_lowercase()] {
+            let path = self.path.join(name);
+            if path.is_file() {
+                return read_secret(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Secrets of a Vault KV version 2 secret, loaded once so the config
+/// builders can read them synchronously. Keys are named like the environment
+/// variables, lowercase keys are accepted as well.
+#[cfg(feature = "vault")]
+#[derive(Debug, Clone, Default)]
+pub struct VaultKv {
+    values: std::collections::HashMap<String, String>,
+}
+
+#[cfg(feature = "vault")]
+impl VaultKv {
+    /// Reads the secret at `path` of the KV engine mounted at `mount`.
+    pub async fn load(address: &str, token: &str, mount: &str, path: &str) -> anyhow::Result<Self> {
+        let url = format!("{}/v1/{mount}/data/{path}", address.trim_end_matches('/'));
+        let response: serde_json::Value = reqwest::Client::new()
+            .get(&url)
+            .header("X-Vault-Token", token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Self::from_response(&response)
+    }
+
+    /// Like [`VaultKv::load`] with the address and token of `VAULT_ADDR` and
+    /// `VAULT_TOKEN`, the token may be read from `VAULT_TOKEN_FILE`.
+    pub async fn from_env(mount: &str, path: &str) -> anyhow::Result<Self> {
+        let address =
+            std::env::var("VAULT_ADDR").map_err(|_| anyhow::anyhow!("VAULT_ADDR is not set"))?;
+        let token = SecretSource::new()
+            .get("VAULT_TOKEN")?
+            .ok_or_else(|| anyhow::anyhow!("VAULT_TOKEN is not set"))?;
+        Self::load(&address, &token, mount, path).await
+    }
+
+    fn from_response(response: &serde_json::Value) -> anyhow::Result<Self> {
+        let data = response
+            .pointer("/data/data")
+            .and_then(serde_json::Value::as_object)
+            .ok_or_else(|| anyhow::anyhow!("invalid response of the vault kv engine"))?;
+        let values = data
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+            .collect();
+        Ok(Self { values })
+    }
+}
+
+#[cfg(feature = "vault")]
+impl SecretProvider for VaultKv {
+    fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .values
+            .get(key)
+            .or_else(|| self.values.get(&key.to_lowercase()))
+            .cloned())
+    }
+}
+
+fn read_secret(path: &Path) -> anyhow::Result<String> {
+    let value = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("unable to read secret '{}': {err}", path.display()))?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[derive(Clone, Default)]
+pub struct SecretSource {
+    providers: Vec<Arc<dyn SecretProvider>>,
+}
+
+impl SecretSource {
+    /// Resolves secrets from environment variables and `*_FILE` only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Like [`SecretSource::new`], with a [`SecretDir`] if [`SECRETS_DIR`]
+    /// is set.
+    pub fn from_env() -> Self {
+        match std::env::var(SECRETS_DIR) {
+            Ok(path) if !path.is_empty() => Self::new().with_provider(SecretDir::new(path)),
+            _ => Self::new(),
+        }
+    }
+
+    pub fn with_provider(mut self, provider: impl SecretProvider + 'static) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Value of the secret `key`, e.g. `MONGODB_PASSWORD`.
+    pub fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let file = format!("{key}{FILE_SUFFIX}");
+        match (std::env::var(key), std::env::var(&file)) {
+            (Ok(_), Ok(_)) => anyhow::bail!("{key} and {file} must not both be set"),
+            (Ok(value), _) => return Ok(Some(value)),
+            (_, Ok(path)) => return read_secret(Path::new(&path)).map(Some),
+            _ => {}
+        }
+        for provider in self.providers.iter() {
+            if let Some(value) = provider.get(key)? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Environment with the `secrets` of the config with `prefix` resolved,
+    /// e.g. `vars("MONGODB_", &["PASSWORD"])`.
+    pub fn vars(&self, prefix: &str, secrets: &[&str]) -> anyhow::Result<Vec<(String, String)>> {
+        let keys: Vec<String> = secrets.iter().map(|s| format!("{prefix}{s}")).collect();
+        let files: Vec<String> = keys.iter().map(|k| format!("{k}{FILE_SUFFIX}")).collect();
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .filter(|(k, _)| !keys.contains(k) && !files.contains(k))
+            .collect();
+        for key in keys {
+            if let Some(value) = self.get(&key)? {
+                vars.push((key, value));
+            }
+        }
+        Ok(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_source() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join("qm-utils-secret-test");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("SECRET_TEST_PASSWORD"), "file\n")?;
+        std::fs::write(dir.join("secret_test_token"), "dir")?;
+        std::env::set_var("SECRET_TEST_USERNAME", "env");
+        std::env::set_var(
+            "SECRET_TEST_PASSWORD_FILE",
+            dir.join("SECRET_TEST_PASSWORD"),
+        );
+
+        let source = SecretSource::new().with_provider(SecretDir::new(&dir));
+        let vars = source.vars("SECRET_TEST_", &["USERNAME", "PASSWORD", "TOKEN", "KEY"])?;
+        let var = |key: &str| vars.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+        assert_eq!(var("SECRET_TEST_USERNAME"), Some("env"));
+        assert_eq!(var("SECRET_TEST_PASSWORD"), Some("file"));
+        assert_eq!(var("SECRET_TEST_TOKEN"), Some("dir"));
+        assert_eq!(var("SECRET_TEST_KEY"), None);
+        assert_eq!(var("SECRET_TEST_PASSWORD_FILE"), None);
+
+        std::env::set_var("SECRET_TEST_MISSING_FILE", dir.join("missing"));
+        assert!(source.get("SECRET_TEST_MISSING").is_err());
+        std::env::set_var(
+            "SECRET_TEST_USERNAME_FILE",
+            dir.join("SECRET_TEST_PASSWORD"),
+        );
+        assert!(source.get("SECRET_TEST_USERNAME").is_err());
+        std::fs::remove_dir_all(&dir).ok();
+        Ok(())
+    }
+
+    #[cfg(feature = "vault")]
+    #[test]
+    fn test_vault_kv() -> anyhow::Result<()> {
+        let response = serde_json::json!({
+            "data": {
+                "data": { "VAULT_TEST_PASSWORD": "vault", "vault_test_token": "lower", "n": 1 },
+                "metadata": { "version": 3 }
+            }
+        });
+        let source = SecretSource::new().with_provider(VaultKv::from_response(&response)?);
+        assert_eq!(source.get("VAULT_TEST_PASSWORD")?.as_deref(), Some("vault"));
+        assert_eq!(source.get("VAULT_TEST_TOKEN")?.as_deref(), Some("lower"));
+        assert_eq!(source.get("n")?, None);
+        assert!(VaultKv::from_response(&serde_json::json!({ "errors": [] })).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+//! Coordinated graceful shutdown.
+//!
+//! Components register a hook in a [`ShutdownPhase`] and watch a
+//! [`ShutdownToken`]. [`Shutdown::run`] first broadcasts the token, then runs
+//! the hooks phase by phase, hooks of the same phase concurrently, each with
+//! its own deadline.
+//!
+//! ```ignore
+//! let shutdown = Shutdown::new();
+//! workers.register_shutdown(&shutdown, Duration::from_secs(30));
+//! bridge
+//!     .kafka_to_jetstream("events", "bridge", "events")?
+//!     .register_shutdown(&shutdown, "kafka bridge", Duration::from_secs(10));
+//! tokio::spawn(shutdown.clone().run_on_signal());
+//! qm::server::serve_with_coordinator(listener, router, &config, &shutdown).await?;
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+type HookFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send + 'static>>;
+type HookFn = Box<dyn FnOnce() -> HookFuture + Send + 'static>;
+
+/// Order in which hooks run, earlier phases complete before later ones start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShutdownPhase {
+    /// Servers stop accepting requests and drain open connections.
+    Ingress,
+    /// Message consumers stop receiving and commit what they handled.
+    Consumers,
+    /// Background workers finish their current items.
+    Workers,
+    /// Connections and pools which the previous phases still needed.
+    Resources,
+}
+
+struct Hook {
+    name: String,
+    phase: ShutdownPhase,
+    deadline: Duration,
+    run: HookFn,
+}
+
+struct Inner {
+    token: watch::Sender<bool>,
+    hooks: Mutex<Option<Vec<Hook>>>,
+}
+
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                token: watch::channel(false).0,
+                hooks: Mutex::new(Some(Vec::new())),
+            }),
+        }
+    }
+
+    pub fn token(&self) -> ShutdownToken {
+        ShutdownToken(self.inner.token.subscribe())
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.inner.token.borrow()
+    }
+
+    /// Registers `hook` to run in `phase`, it is abandoned after `deadline`.
+    /// Hooks registered after the shutdown started run immediately.
+    pub fn register<S, F, Fut>(&self, name: S, phase: ShutdownPhase, deadline: Duration, hook: F)
+    where
+        S: Into<String>,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let hook = Hook {
+            name: name.into(),
+            phase,
+            deadline,
+            run: Box::new(move || Box::pin(hook())),
+        };
+        let mut hooks = self.inner.hooks.lock().unwrap();
+        match hooks.as_mut() {
+            Some(hooks) => hooks.push(hook),
+            None => {
+                drop(hooks);
+                tokio::spawn(run_hook(hook));
+            }
+        }
+    }
+
+    /// Notifies all tokens without running hooks.
+    pub fn trigger(&self) {
+        self.inner.token.send_replace(true);
+    }
+
+    /// Triggers the shutdown and runs all registered hooks. Fails if a hook
+    /// failed or missed its deadline, the remaining hooks run regardless.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        self.trigger();
+        let Some(mut hooks) = self.inner.hooks.lock().unwrap().take() else {
+            anyhow::bail!("shutdown already running");
+        };
+        hooks.sort_by_key(|hook| hook.phase);
+        let mut failed = Vec::new();
+        let mut hooks = hooks.into_iter().peekable();
+        while let Some(phase) = hooks.peek().map(|hook| hook.phase) {
+            tracing::info!("shutdown phase {phase:?}");
+            let mut running = Vec::new();
+            while let Some(hook) = hooks.next_if(|hook| hook.phase == phase) {
+                let name = hook.name.clone();
+                running.push((name, tokio::spawn(run_hook(hook))));
+            }
+            for (name, handle) in running {
+                if !handle.await.unwrap_or(false) {
+                    failed.push(name);
+                }
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            anyhow::bail!("shutdown of {} failed", failed.join(", "))
+        }
+    }
+
+    /// Waits for `Ctrl+C` or `SIGTERM` and runs the shutdown.
+    pub async fn run_on_signal(self) -> anyhow::Result<()> {
+        signal().await;
+        tracing::info!("shutdown requested");
+        self.run().await
+    }
+}
+
+async fn run_hook(hook: Hook) -> bool {
+    let Hook {
+        name,
+        deadline,
+        run,
+        ..
+    } = hook;
+    match tokio::time::timeout(deadline, run()).await {
+        Ok(Ok(())) => {
+            tracing::info!("stopped {name}");
+            true
+        }
+        Ok(Err(err)) => {
+            tracing::error!("stopping {name} failed: {err:#}");
+            false
+        }
+        Err(_) => {
+            tracing::error!("stopping {name} exceeded {deadline:?}");
+            false
+        }
+    }
+}
+
+/// Receiving side of [`Shutdown`].
+#[derive(Clone)]
+pub struct ShutdownToken(watch::Receiver<bool>);
+
+impl ShutdownToken {
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once the shutdown is triggered, or when the [`Shutdown`] is
+    /// dropped.
+    pub async fn triggered(mut self) {
+        self.0.wait_for(|triggered| *triggered).await.ok();
+    }
+}
+
+/// Completes on `Ctrl+C` or `SIGTERM`.
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            tracing::error!("unable to listen for Ctrl+C: {err:#}");
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                tracing::error!("unable to listen for SIGTERM: {err:#}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run() {
+        let shutdown = Shutdown::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let token = shutdown.token();
+        for (name, phase) in [
+            ("pool", ShutdownPhase::Resources),
+            ("server", ShutdownPhase::Ingress),
+            ("workers", ShutdownPhase::Workers),
+        ] {
+            let order = order.clone();
+            let token = token.clone();
+            shutdown.register(name, phase, Duration::from_secs(1), move || async move {
+                assert!(token.is_triggered());
+                order.lock().unwrap().push(name);
+                Ok(())
+            });
+        }
+        shutdown.register(
+            "stuck",
+            ShutdownPhase::Consumers,
+            Duration::from_millis(10),
+            std::future::pending,
+        );
+        assert!(!token.is_triggered());
+        let err = shutdown.run().await.unwrap_err();
+        assert_eq!(err.to_string(), "shutdown of stuck failed");
+        assert_eq!(*order.lock().unwrap(), ["server", "workers", "pool"]);
+        token.triggered().await;
+        assert!(shutdown.run().await.is_err());
+    }
+}
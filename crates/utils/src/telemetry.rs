@@ -0,0 +1,140 @@
+//! OpenTelemetry bootstrap, spans of all `qm` crates built with their `otel`
+//! feature are exported with OTLP over HTTP.
+//!
+//! The exporter is configured with the standard environment variables, e.g.
+//! `OTEL_EXPORTER_OTLP_ENDPOINT=http://collector:4318`. [`init`] installs the
+//! global `tracing` subscriber, so it replaces loggers like `env_logger`.
+//!
+//! ```ignore
+//! let _telemetry = qm_utils::telemetry::init()?;
+//! ```
+
+use std::collections::HashMap;
+
+use opentelemetry::{global, trace::TracerProvider as _, KeyValue};
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, TracerProvider},
+    Resource,
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// Loaded from the environment with the prefix `OTEL_`, e.g.
+/// `OTEL_SERVICE_NAME=customer`.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct Config {
+    service_name: Option<String>,
+    traces_sampler_arg: Option<f64>,
+}
+
+impl Config {
+    pub fn new() -> envy::Result<Self> {
+        envy::prefixed("OTEL_").from_env()
+    }
+
+    pub fn with_service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.service_name = Some(service_name.into());
+        self
+    }
+
+    /// Ratio of sampled traces, traces continued from a sampled parent are
+    /// always sampled.
+    pub fn with_sample_ratio(mut self, ratio: f64) -> Self {
+        self.traces_sampler_arg = Some(ratio);
+        self
+    }
+
+    pub fn service_name(&self) -> &str {
+        self.service_name.as_deref().unwrap_or("qm")
+    }
+
+    pub fn sample_ratio(&self) -> f64 {
+        self.traces_sampler_arg.unwrap_or(1.0).clamp(0.0, 1.0)
+    }
+}
+
+/// Flushes pending spans when dropped.
+pub struct TelemetryGuard {
+    provider: TracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            eprintln!("unable to shutdown tracer provider: {err}");
+        }
+    }
+}
+
+/// Initializes telemetry with [`Config::new`].
+pub fn init() -> anyhow::Result<TelemetryGuard> {
+    init_with_config(&Config::new()?)
+}
+
+/// Installs the OTLP exporter, the W3C trace context propagator and the
+/// global `tracing` subscriber, filtered by `RUST_LOG`.
+pub fn init_with_config(config: &Config) -> anyhow::Result<TelemetryGuard> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio(),
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name().to_string(),
+        )]))
+        .build();
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer = provider.tracer(config.service_name().to_string());
+    global::set_tracer_provider(provider.clone());
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+    Ok(TelemetryGuard { provider })
+}
+
+/// Continues the trace of a W3C `traceparent` header in `span`.
+pub fn set_parent(span: &tracing::Span, traceparent: &str) {
+    let carrier = HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+    span.set_parent(global::get_text_map_propagator(|p| p.extract(&carrier)));
+}
+
+/// `traceparent` of the current span, to continue the trace in messages or
+/// requests to other services.
+pub fn traceparent() -> Option<String> {
+    let mut carrier = HashMap::new();
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|p| p.inject_context(&context, &mut carrier));
+    carrier.remove("traceparent")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_propagation() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer = TracerProvider::builder().build().tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            set_parent(
+                &span,
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            );
+            let _entered = span.enter();
+            let traceparent = traceparent().unwrap();
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+        });
+    }
+}
@@ -5,13 +5,14 @@ use qm::{
     customer::{
         context::{
             AdminContext, CustomerResource, InstitutionResource, OrganizationResource,
-            OrganizationUnitResource, RelatedAuth, RelatedPermission, RelatedResource, UserContext,
-            UserResource,
+            OrganizationUnitResource, RedisClient, RelatedAuth, RelatedPermission, RelatedResource,
+            UserContext, UserResource,
         },
         groups::{
             CustomerOwnerGroup, CustomerUnitOwnerGroup, InstitutionOwnerGroup,
             InstitutionUnitOwnerGroup, OrganizationOwnerGroup, RelatedBuiltInGroup, RelatedGroups,
         },
+        schema::api_client,
     },
     entity::{
         err, AsNumber, FromGraphQLContext, HasAccess, HasRole, IsAdmin, IsSupport,
@@ -25,17 +26,22 @@ use roles::BuiltInGroup;
 use sqlx::types::Uuid;
 
 pub mod roles;
-use crate::roles::{Permission, Resource, BUILT_IN_GROUPS};
+use crate::roles::{role_table, Permission, Resource, BUILT_IN_GROUPS};
+
+/// Username prefix of the service accounts of API clients.
+const SERVICE_ACCOUNT_PREFIX: &str = "service-account-";
 
 pub type AuthContainer = qm::role::AuthContainer<Authorization>;
 pub type Role = qm::role::Role<Resource, Permission>;
 pub type Group = qm::role::Group<Resource, Permission>;
+pub type RoleSet = qm::role::RoleSet<Resource, Permission>;
 
 #[derive(Default)]
 struct Inner {
     _claims: Option<Claims>,
     access: Option<Access>,
     roles: HashSet<Role>,
+    role_set: RoleSet,
     is_admin: bool,
     is_support: bool,
     user_id: Option<Uuid>,
@@ -59,12 +65,26 @@ impl FromGraphQLContext for Authorization {
             let mut v = auth_container.write().await;
             let storage = ctx.data_unchecked::<Storage>();
             let claims: Claims = storage.jwt_store().decode(encoded).await?;
+            if claims
+                .preferred_username
+                .starts_with(SERVICE_ACCOUNT_PREFIX)
+            {
+                if let Err(err) = api_client::track_usage(storage.redis(), &claims.azp).await {
+                    tracing::warn!(
+                        "unable to track usage of api client {}: {err:#}",
+                        claims.azp
+                    );
+                }
+            }
             let user_id = Uuid::parse_str(&claims.sub)?;
-            let mut parsed = qm::role::parse(&claims.realm_access.roles);
+            let role_table = role_table();
+            let mut parsed = role_table.parse(&claims.realm_access.roles);
             let is_admin = parsed
                 .roles
                 .contains(&qm::role::role!(Resource::Administration));
             let is_support = parsed.roles.contains(&qm::role::role!(Resource::Support));
+            let role_set =
+                role_table.role_set(&claims.realm_access.roles, Some(Resource::Administration));
 
             let access = if is_admin {
                 Access::new(AccessLevel::Admin)
@@ -79,6 +99,7 @@ impl FromGraphQLContext for Authorization {
                     _claims: Some(claims),
                     access: Some(access),
                     roles: parsed.roles,
+                    role_set,
                     is_admin,
                     is_support,
                     user_id: Some(user_id),
@@ -129,6 +150,9 @@ impl HasRole<Resource, Permission> for Authorization {
     fn has_role_object(&self, role: &qm::role::Role<Resource, Permission>) -> bool {
         self.inner.roles.contains(role)
     }
+    fn satisfies(&self, role: &qm::role::Role<Resource, Permission>) -> bool {
+        self.inner.role_set.satisfies(role)
+    }
 }
 
 impl AsNumber for Authorization {
@@ -239,6 +263,10 @@ impl RelatedGroups<Resource, Permission> for Authorization {
     fn built_in_groups() -> &'static [&'static str] {
         &BUILT_IN_GROUPS
     }
+
+    fn group_definitions() -> Vec<Group> {
+        roles::groups()
+    }
 }
 impl SessionAccess for Authorization {
     fn session_access(&self) -> Option<&qm::role::Access> {
@@ -2,8 +2,10 @@
 //!
 //! This command configures MongoDB, Keycloak and S3.
 //!
-use crate::commands::ConfigureCommand;
+use crate::commands::{ConfigureCommand, KeycloakRealmOptions};
+use qm::keycloak::validation::model::RealmDriftReport;
 use std::collections::BTreeSet;
+use std::io::IsTerminal;
 
 async fn configure_keycloak() -> anyhow::Result<()> {
     let keycloak = qm::keycloak::Keycloak::builder()
@@ -33,15 +35,98 @@ async fn configure_keycloak() -> anyhow::Result<()> {
     Ok(())
 }
 
+fn paint(color: u8, text: &str) -> String {
+    if std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+        format!("\x1b[{color}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+fn print_report(report: &RealmDriftReport, dry_run: bool) {
+    const RED: u8 = 31;
+    const GREEN: u8 = 32;
+    const YELLOW: u8 = 33;
+    println!("realm '{}'", report.realm);
+    for error in report.drift.iter() {
+        let (color, label) = if report.is_repaired(error) {
+            (GREEN, "repaired")
+        } else if dry_run {
+            (YELLOW, "would repair")
+        } else {
+            (RED, "drift")
+        };
+        println!(
+            "  {} {} ({})",
+            paint(color, &format!("{label:>12}")),
+            error.id,
+            error.key
+        );
+    }
+    let summary = match (&report.remaining, report.drift.len()) {
+        (_, 0) => paint(GREEN, "no drift found"),
+        (Some(remaining), found) if remaining.is_empty() => {
+            paint(GREEN, &format!("repaired {found} settings"))
+        }
+        (Some(remaining), found) => paint(
+            RED,
+            &format!(
+                "{} of {found} settings could not be repaired",
+                remaining.len()
+            ),
+        ),
+        (None, found) => paint(
+            if dry_run { YELLOW } else { RED },
+            &format!("{found} settings differ"),
+        ),
+    };
+    println!("{summary}");
+}
+
+/// Validates or repairs the realm, fails if drift remains so it can be used
+/// in scripts.
+async fn check_keycloak_realm(options: &KeycloakRealmOptions) -> anyhow::Result<()> {
+    let keycloak = qm::keycloak::Keycloak::builder()
+        .with_no_refresh()
+        .build()
+        .await?;
+    let repair = options.repair && !options.dry_run;
+    let report = qm::keycloak::realm::check_realm(&keycloak, repair).await?;
+    print_report(&report, options.dry_run);
+    if !report.is_clean() {
+        anyhow::bail!(
+            "realm '{}' differs from the expected configuration",
+            report.realm
+        );
+    }
+    Ok(())
+}
+
+async fn configure_s3() -> anyhow::Result<()> {
+    let s3 = qm::s3::S3::new()?;
+    let spec = qm::s3::BucketSpec::default()
+        .with_lifecycle_rule(qm::s3::LifecycleRule::expire("tmp/", 1))
+        .with_cors_rule(qm::s3::CorsRule::presigned(["*"]));
+    s3.ensure_bucket(s3.bucket(), &spec).await?;
+    Ok(())
+}
+
 impl ConfigureCommand {
     pub async fn run(self) -> anyhow::Result<()> {
         match self.resource {
             super::Resource::All => {
                 configure_keycloak().await?;
+                configure_s3().await?;
             }
-            super::Resource::KeycloakRealm => {
+            super::Resource::KeycloakRealm(options) if options.validate || options.repair => {
+                check_keycloak_realm(&options).await?;
+            }
+            super::Resource::KeycloakRealm(_) => {
                 configure_keycloak().await?;
             }
+            super::Resource::S3 => {
+                configure_s3().await?;
+            }
             _ => {
                 unimplemented!()
             }
@@ -1,12 +1,26 @@
-use clap::Parser;
+use clap::{Args, Parser};
 
 mod configure;
 mod remove;
+mod seed;
+
+#[derive(Clone, Args)]
+pub struct KeycloakRealmOptions {
+    /// only report differences to the expected realm configuration
+    #[clap(long, conflicts_with = "repair")]
+    pub validate: bool,
+    /// fix differences to the expected realm configuration
+    #[clap(long)]
+    pub repair: bool,
+    /// report what `--repair` would change without changing it
+    #[clap(long, requires = "repair")]
+    pub dry_run: bool,
+}
 
 #[derive(Clone, Parser)]
 pub enum Resource {
     All,
-    KeycloakRealm,
+    KeycloakRealm(KeycloakRealmOptions),
     Mongodb,
     S3,
 }
@@ -27,12 +41,38 @@ pub struct ConfigureCommand {
     pub resource: Resource,
 }
 
+#[derive(Parser)]
+pub struct SeedCommand {
+    /// remove the data of a previous seed instead of creating it
+    #[clap(long)]
+    pub cleanup: bool,
+    /// prefix of all names, seeds with different prefixes don't interfere
+    #[clap(long, default_value = "Demo")]
+    pub prefix: String,
+    #[clap(long, default_value_t = 2)]
+    pub customers: usize,
+    /// organizations per customer
+    #[clap(long, default_value_t = 2)]
+    pub organizations: usize,
+    /// institutions per organization
+    #[clap(long, default_value_t = 2)]
+    pub institutions: usize,
+    /// users per institution
+    #[clap(long, default_value_t = 2)]
+    pub users: usize,
+    /// password of all created users
+    #[clap(long, default_value = "Demo-Passw0rd!")]
+    pub password: String,
+}
+
 #[derive(Parser)]
 pub enum SubCommand {
     /// remove
     Remove(RemoveCommand),
     /// configure
     Configure(ConfigureCommand),
+    /// seed demo tenants
+    Seed(SeedCommand),
 }
 
 #[derive(Parser)]
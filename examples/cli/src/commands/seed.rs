@@ -0,0 +1,224 @@
+//! # seed command
+//!
+//! This command creates demo customers, organizations, institutions and users
+//! through the GraphQL mutations, so the same checks and side effects apply
+//! as for requests of the realm admin.
+//!
+
+use async_graphql::{Request, Variables};
+use qm::keycloak::session::{KeycloakSession, KeycloakSessionClient};
+use qm_example_ctx::Storage;
+use qm_example_server::schema::{Schema, SchemaBuilder};
+use serde_json::{json, Value};
+
+use crate::commands::SeedCommand;
+
+const PAGE_LIMIT: u64 = 100;
+
+const CUSTOMERS: &str = "query($filter: ListFilter!) {
+    qmCustomers(filter: $filter) { items { id name } total }
+}";
+const CREATE_CUSTOMER: &str = "mutation($input: QmCreateCustomerInput!) {
+    qmCreateCustomer(input: $input) { id }
+}";
+const CREATE_ORGANIZATION: &str =
+    "mutation($context: CustomerId!, $input: CreateOrganizationInput!) {
+    qmCreateOrganization(context: $context, input: $input) { id }
+}";
+const CREATE_INSTITUTION: &str =
+    "mutation($context: OrganizationId!, $input: CreateInstitutionInput!) {
+    qmCreateInstitution(context: $context, input: $input) { id }
+}";
+const CREATE_USER: &str = "mutation($context: ContextFilter!, $input: QmCreateUserInput!) {
+    createUser(accessLevel: INSTITUTION, context: $context, input: $input) { id }
+}";
+const USERS: &str = "query($context: ContextFilter!, $filter: ListFilter!) {
+    users(context: $context, filter: $filter) { items { id username } total }
+}";
+const REMOVE_USERS: &str = "mutation($ids: [UUID!]!) { removeUsers(ids: $ids) }";
+const REMOVE_CUSTOMERS: &str = "mutation($ids: [CustomerId!]!) { qmRemoveCustomers(ids: $ids) }";
+
+struct Client {
+    schema: Schema,
+}
+
+impl Client {
+    async fn new() -> anyhow::Result<Self> {
+        let store = Storage::new().await?;
+        let config = store.keycloak().config();
+        let session = KeycloakSession::new(
+            KeycloakSessionClient::new(config.address(), config.realm(), "spa"),
+            config.realm_admin_username(),
+            config.realm_admin_password(),
+            false,
+        )
+        .await?;
+        let schema = SchemaBuilder::default()
+            .with_access_token(session.access_token().await.as_ref())
+            .build(store);
+        Ok(Self { schema })
+    }
+
+    async fn execute(&self, query: &str, variables: Value) -> anyhow::Result<Value> {
+        let request = Request::new(query).variables(Variables::from_json(variables));
+        let response = self.schema.execute(request).await;
+        if let Some(err) = response.errors.first() {
+            anyhow::bail!("{}", err.message);
+        }
+        Ok(response.data.into_json()?)
+    }
+
+    async fn create(&self, query: &str, field: &str, variables: Value) -> anyhow::Result<Value> {
+        let data = self.execute(query, variables).await?;
+        Ok(data[field]["id"].clone())
+    }
+
+    /// Returns the items of the list `field` from all pages.
+    async fn list_all(
+        &self,
+        query: &str,
+        field: &str,
+        mut variables: Value,
+    ) -> anyhow::Result<Vec<Value>> {
+        let mut items = vec![];
+        for page in 0.. {
+            variables["filter"] = json!({ "page": page, "limit": PAGE_LIMIT });
+            let mut data = self.execute(query, variables.clone()).await?;
+            let total = data[field]["total"].as_u64().unwrap_or(0);
+            let Value::Array(page_items) = data[field]["items"].take() else {
+                break;
+            };
+            let exhausted = (page_items.len() as u64) < PAGE_LIMIT;
+            items.extend(page_items);
+            if exhausted || items.len() as u64 >= total {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Returns the ids of the customers created by a previous seed.
+    async fn seeded_customers(&self, prefix: &str) -> anyhow::Result<Vec<Value>> {
+        let prefix = format!("{prefix} Customer ");
+        Ok(self
+            .list_all(CUSTOMERS, "qmCustomers", json!({}))
+            .await?
+            .into_iter()
+            .filter(|c| c["name"].as_str().is_some_and(|n| n.starts_with(&prefix)))
+            .map(|c| c["id"].clone())
+            .collect())
+    }
+}
+
+impl SeedCommand {
+    fn username(&self, path: &str) -> String {
+        format!("{}-user-{path}", self.prefix.to_lowercase())
+    }
+
+    async fn seed(&self, client: &Client) -> anyhow::Result<()> {
+        if !client.seeded_customers(&self.prefix).await?.is_empty() {
+            anyhow::bail!(
+                "'{}' demo data exists already, remove it with `seed --cleanup`",
+                self.prefix
+            );
+        }
+        let prefix = &self.prefix;
+        let mut users = 0;
+        for c in 1..=self.customers {
+            let name = format!("{prefix} Customer {c}");
+            let input = json!({ "input": { "name": name } });
+            let customer = client
+                .create(CREATE_CUSTOMER, "qmCreateCustomer", input)
+                .await?;
+            println!("{name}");
+            for o in 1..=self.organizations {
+                let name = format!("{prefix} Organization {c}.{o}");
+                let input = json!({ "context": customer, "input": { "name": name } });
+                let organization = client
+                    .create(CREATE_ORGANIZATION, "qmCreateOrganization", input)
+                    .await?;
+                println!("  {name}");
+                for i in 1..=self.institutions {
+                    let name = format!("{prefix} Institution {c}.{o}.{i}");
+                    let input = json!({ "context": organization, "input": { "name": name } });
+                    let institution = client
+                        .create(CREATE_INSTITUTION, "qmCreateInstitution", input)
+                        .await?;
+                    println!("    {name}");
+                    for u in 1..=self.users {
+                        let username = self.username(&format!("{c}-{o}-{i}-{u}"));
+                        let input = json!({
+                            "context": { "institution": institution },
+                            "input": {
+                                "username": username,
+                                "email": format!("{username}@example.com"),
+                                "firstname": "Demo",
+                                "lastname": format!("User {c}.{o}.{i}.{u}"),
+                                "password": self.password,
+                            },
+                        });
+                        client.create(CREATE_USER, "createUser", input).await?;
+                        users += 1;
+                    }
+                }
+            }
+        }
+        println!(
+            "created {} customers, {} organizations, {} institutions and {users} users",
+            self.customers,
+            self.customers * self.organizations,
+            self.customers * self.organizations * self.institutions,
+        );
+        Ok(())
+    }
+
+    async fn cleanup(&self, client: &Client) -> anyhow::Result<()> {
+        let customers = client.seeded_customers(&self.prefix).await?;
+        let username_prefix = self.username("");
+        let mut users = vec![];
+        for customer in customers.iter() {
+            let items = client
+                .list_all(
+                    USERS,
+                    "users",
+                    json!({ "context": { "customer": customer } }),
+                )
+                .await?;
+            users.extend(
+                items
+                    .into_iter()
+                    .filter(|u| {
+                        u["username"]
+                            .as_str()
+                            .is_some_and(|n| n.starts_with(&username_prefix))
+                    })
+                    .map(|u| u["id"].clone()),
+            );
+        }
+        if !users.is_empty() {
+            client
+                .execute(REMOVE_USERS, json!({ "ids": users }))
+                .await?;
+        }
+        if !customers.is_empty() {
+            client
+                .execute(REMOVE_CUSTOMERS, json!({ "ids": customers }))
+                .await?;
+        }
+        println!(
+            "removed {} customers and {} users, organizations and institutions are removed by the cleanup worker",
+            customers.len(),
+            users.len()
+        );
+        Ok(())
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        let client = Client::new().await?;
+        if self.cleanup {
+            self.cleanup(&client).await
+        } else {
+            self.seed(&client).await
+        }
+    }
+}
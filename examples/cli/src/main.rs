@@ -15,6 +15,7 @@ async fn main() -> anyhow::Result<()> {
     match opts.subcmd {
         SubCommand::Configure(cmd) => cmd.run().await?,
         SubCommand::Remove(cmd) => cmd.run().await?,
+        SubCommand::Seed(cmd) => cmd.run().await?,
     }
     Ok(())
 }
@@ -8,12 +8,14 @@ use qm::{
     keycloak::{JwtStore, Keycloak},
     mongodb::DB,
     redis::Redis,
-    server::ServerConfig,
+    server::{SchemaHardening, ServerConfig},
+    utils::bootstrap::Bootstrap,
 };
 use std::sync::Arc;
 
 struct Inner {
     server_config: ServerConfig,
+    schema_hardening: SchemaHardening,
     keycloak: Keycloak,
     jwt_store: JwtStore,
     keycloak_db: qm::pg::DB,
@@ -53,39 +55,56 @@ qm::customer::cache!(Storage);
 
 impl Storage {
     pub async fn new() -> anyhow::Result<Self> {
-        let server_config = ServerConfig::new()?;
-        let db =
-            qm::mongodb::DB::new(server_config.app_name(), &qm::mongodb::DbConfig::new()?).await?;
-        let keycloak_db = qm::pg::DB::new(
-            server_config.app_name(),
-            &qm::pg::DbConfig::builder()
+        let mut bootstrap = Bootstrap::new();
+        let server_config = bootstrap.load("server config", ServerConfig::new)?;
+        let schema_hardening = bootstrap.load("schema hardening", SchemaHardening::new)?;
+        let customer_config =
+            bootstrap.load("customer config", qm::customer::config::Config::new)?;
+        let db_config = bootstrap.load("mongodb config", qm::mongodb::DbConfig::new)?;
+        let keycloak_db_config = bootstrap.load("keycloak db config", || {
+            qm::pg::DbConfig::builder()
                 .with_prefix("KEYCLOAK_DB_")
-                .build()?,
-        )
-        .await?;
-        let customer_db = qm::pg::DB::new(
-            server_config.app_name(),
-            &qm::pg::DbConfig::builder()
+                .build()
+        })?;
+        let customer_db_config = bootstrap.load("customer db config", || {
+            qm::pg::DbConfig::builder()
                 .with_prefix("CUSTOMER_DB_")
-                .build()?,
-        )
-        .await?;
-        let keycloak = qm::keycloak::Keycloak::new().await?;
-        let cache_db = CacheDB::new(
-            &customer_db,
-            &keycloak_db,
-            keycloak.config().realm(),
-            keycloak.config().realm_admin_username(),
-        )
-        .await?;
+                .build()
+        })?;
+        let db = bootstrap
+            .init("mongodb", || DB::new(server_config.app_name(), &db_config))
+            .await?;
+        let keycloak_db = bootstrap
+            .init("keycloak db", || {
+                qm::pg::DB::new(server_config.app_name(), &keycloak_db_config)
+            })
+            .await?;
+        let customer_db = bootstrap
+            .init("customer db", || {
+                qm::pg::DB::new(server_config.app_name(), &customer_db_config)
+            })
+            .await?;
+        let keycloak = bootstrap.init("keycloak", Keycloak::new).await?;
+        let cache_db = bootstrap
+            .init("customer cache", || {
+                CacheDB::with_config(
+                    &customer_db,
+                    &keycloak_db,
+                    keycloak.config().realm(),
+                    keycloak.config().realm_admin_username(),
+                    &customer_config,
+                )
+            })
+            .await?;
         let jwt_store = JwtStore::new(keycloak.config());
-        let redis = Redis::new()?;
+        let redis = bootstrap.load("redis", Redis::new)?;
         // let cache = Cache::new("qm-example", keycloak.config().realm()).await?;
-        let mutation_event_producer = Producer::new()?;
+        let mutation_event_producer = bootstrap.load("kafka producer", Producer::new)?;
         let cleanup_task_producer = CleanupProducer::new(redis.pool());
         let result = Self {
             inner: Arc::new(Inner {
                 server_config,
+                schema_hardening,
                 keycloak,
                 jwt_store,
                 keycloak_db,
@@ -108,6 +127,9 @@ impl Storage {
     pub fn server_config(&self) -> &ServerConfig {
         &self.inner.server_config
     }
+    pub fn schema_hardening(&self) -> &SchemaHardening {
+        &self.inner.schema_hardening
+    }
     pub fn keycloak(&self) -> &Keycloak {
         &self.inner.keycloak
     }
@@ -0,0 +1,44 @@
+use chrono::NaiveDateTime;
+use qm::entity::{entity, member};
+
+#[member]
+pub struct Person {
+    firstname: String,
+    middlename: Option<String>,
+    lastname: String,
+}
+
+#[member]
+pub struct Address {
+    street: Option<String>,
+    zip_code: Option<String>,
+    place: Option<String>,
+}
+
+#[entity(relations(EmployeeAppointments, EmployeeWorkTimes, EmployeeOffice))]
+pub struct Employee {
+    person: Person,
+    address: Option<Address>,
+}
+
+#[entity(relations(WorkTimeEmployee))]
+pub struct WorkTime {
+    from: NaiveDateTime,
+    to: Option<NaiveDateTime>,
+}
+
+#[entity(relations(OfficeAppointments, OfficeEmployee))]
+pub struct Office {
+    name: String,
+    address: Option<Address>,
+}
+
+#[entity(relations(AppointmentEmployees, AppointmentOffices))]
+pub struct Appointment {
+    name: String,
+}
+
+qm::entity::m2m!(Appointment, Employee);
+qm::entity::m2m!(Appointment, Office);
+qm::entity::o2m!(Employee, WorkTime);
+qm::entity::o2o!(Employee, Office);
@@ -1,37 +1,55 @@
 use async_graphql::{ComplexObject, InputObject, MaybeUndefined, SimpleObject};
 use qm::customer::model::{QmCustomer, QmInstitution, QmOrganization};
 use qm::entity::ids::{InstitutionResourceId, OrganizationResourceId, Owner, ID};
+use qm::entity::patch::Patch;
+use qm::entity::validate::Validate;
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Clone, SimpleObject, InputObject, Serialize, Deserialize)]
+pub mod entities;
+
+#[derive(Default, Debug, Clone, SimpleObject, InputObject, Serialize, Deserialize, Validate)]
 #[graphql(input_name = "PersonInput")]
 #[serde(rename_all = "camelCase")]
 pub struct Person {
+    #[validate(length(max = 50))]
     pub salutation: Option<String>,
+    #[validate(length(max = 50))]
     pub title: Option<String>,
     #[serde(default)]
+    #[validate(length(min = 1, max = 100))]
     pub firstname: String,
+    #[validate(length(max = 100))]
     pub middlename: Option<String>,
     #[serde(default)]
+    #[validate(length(min = 1, max = 100))]
     pub lastname: String,
     #[serde(default)]
+    #[validate(length(max = 20))]
     pub gender: String,
-    #[serde(default)]
+    #[serde(default, with = "qm::entity::encrypted")]
     pub citizenships: Option<Vec<String>>,
+    #[serde(default, with = "qm::entity::encrypted")]
     pub confession: Option<String>,
     pub denomination: Option<String>,
     pub languages: Option<Vec<String>>,
     pub is_german_first_language: Option<bool>,
 }
 
-#[derive(Default, Debug, Clone, SimpleObject, InputObject, Serialize, Deserialize)]
+#[derive(
+    Default, Debug, Clone, PartialEq, SimpleObject, InputObject, Serialize, Deserialize, Validate,
+)]
 #[graphql(input_name = "SimpleAddressInput")]
 #[serde(rename_all = "camelCase")]
 pub struct SimpleAddress {
+    #[validate(length(max = 200))]
     pub street: Option<String>,
+    #[validate(regex = "^[0-9A-Za-z -]{3,10}$")]
     pub zip_code: Option<String>,
+    #[validate(length(max = 100))]
     pub city: Option<String>,
+    #[validate(length(max = 100))]
     pub district: Option<String>,
+    #[validate(length(max = 100))]
     pub country: Option<String>,
 }
 
@@ -68,13 +86,20 @@ pub struct EmployeeList {
     pub page: Option<i64>,
 }
 
-#[derive(Debug, InputObject)]
+#[derive(Debug, InputObject, Validate, Patch)]
+#[patch(target = "Person")]
 pub struct UpdatePersonInput {
+    #[validate(length(max = 50))]
     pub salutation: MaybeUndefined<String>,
+    #[validate(length(max = 50))]
     pub title: MaybeUndefined<String>,
+    #[validate(length(min = 1, max = 100))]
     pub firstname: Option<String>,
+    #[validate(length(max = 100))]
     pub middlename: MaybeUndefined<String>,
+    #[validate(length(min = 1, max = 100))]
     pub lastname: Option<String>,
+    #[validate(length(max = 20))]
     pub gender: Option<String>,
     pub citizenships: MaybeUndefined<Vec<String>>,
     pub confession: MaybeUndefined<String>,
@@ -83,69 +108,23 @@ pub struct UpdatePersonInput {
     pub is_german_first_language: MaybeUndefined<bool>,
 }
 
-#[derive(Debug, InputObject)]
+#[derive(Debug, InputObject, Validate)]
 pub struct CreateEmployeeInput {
     institution: OrganizationResourceId,
+    #[validate(nested)]
     person: Person,
+    #[validate(nested)]
     address: Option<SimpleAddress>,
 }
 
-#[derive(Debug, InputObject)]
+#[derive(Debug, InputObject, Validate, Patch)]
+#[patch(target = "Employee")]
 pub struct UpdateEmployeeInput {
+    #[patch(skip)]
     id: InstitutionResourceId,
+    #[validate(nested)]
+    #[patch(nested)]
     person: Option<UpdatePersonInput>,
+    #[validate(nested)]
     address: MaybeUndefined<SimpleAddress>,
 }
-
-// target structure, implemented with proc macros
-
-// use chrono::NaiveDateTime;
-// use qm::entity::{entity, member};
-
-// #[member]
-// pub struct Person {
-//     firstname: String,
-//     middlename: Option<String>,
-//     lastname: String,
-// }
-
-// #[member]
-// pub struct Address {
-//     street: Option<String>,
-//     zip_code: Option<String>,
-//     place: Option<String>,
-// }
-
-// #[entity]
-// pub struct Employee {
-//     person: Person,
-//     address: Option<Address>,
-// }
-
-// #[entity]
-// pub struct WorkTime {
-//    from: NaiveDateTime,
-//    to: Option<NaiveDateTime>,
-// }
-
-// #[entity]
-// pub struct Office {
-//     name: String,
-//     address: Option<Address>,
-// }
-
-// #[entity]
-// pub struct Employee {
-//     person: Person,
-//     address: Option<Address>,
-// }
-
-// #[entity]
-// pub struct Appointment {
-//     name: String,
-// }
-
-// qm::entity::m2m!(Appointment, Employee);
-// qm::entity::m2m!(Appointment, Office);
-// qm::entity::o2m!(Employee, WorkTime);
-// qm::entity::o2o!(Employee, Office);
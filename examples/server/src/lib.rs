@@ -1,17 +1,28 @@
-use async_graphql::http::GraphiQLSource;
+use anyhow::Context as _;
 use axum::{
-    extract::Extension,
     http::Method,
     response::{Html, IntoResponse},
     routing::get,
     Router,
 };
+use qm::{
+    customer::{context::RedisClient, worker},
+    redis::Workers,
+};
+use qm_example_auth::{
+    roles::{Permission, Resource},
+    Authorization,
+};
+use std::time::Duration;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
 pub mod schema;
 
 use qm_example_ctx::Storage;
 
+/// Interval in which expired API clients are disabled.
+const API_CLIENT_EXPIRY_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
 const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
 const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 const INDEX: &str = constcat::concat!(
@@ -22,10 +33,6 @@ const INDEX: &str = constcat::concat!(
     "</h1><div>visit <a href=\"/api/graphql\">GraphQL Playground</a></div></html>"
 );
 
-pub async fn graphiql() -> impl IntoResponse {
-    Html(GraphiQLSource::build().endpoint("/api/graphql").finish())
-}
-
 pub async fn index() -> impl IntoResponse {
     Html(INDEX)
 }
@@ -34,32 +41,43 @@ async fn router(store: Storage) -> Router {
     let port = store.server_config().port();
     let schema = schema::SchemaBuilder::default().build(store);
     println!("GraphiQL IDE: http://localhost:{port}");
-    Router::new()
-        .route("/", get(index))
-        .route(
-            "/api/graphql",
-            get(graphiql).post(
-                qm::server::graphql_handler::<
-                    qm_example_auth::Authorization,
-                    schema::QueryRoot,
-                    schema::MutationRoot,
-                    async_graphql::EmptySubscription,
-                >,
-            ),
-        )
-        .layer(Extension(schema))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(AllowOrigin::predicate(|_, _| true))
-                .allow_methods([Method::GET, Method::POST]),
-        )
+    qm::server::router::<
+        Authorization,
+        schema::QueryRoot,
+        schema::MutationRoot,
+        async_graphql::EmptySubscription,
+    >(schema)
+    .graphql_path("/api/graphql")
+    .graphiql()
+    .build()
+    .route("/", get(index))
+    .layer(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(|_, _| true))
+            .allow_methods([Method::GET, Method::POST]),
+    )
 }
 
 pub async fn start() -> anyhow::Result<()> {
+    // `Person` encrypts fields, the server doesn't start without keys
+    let encryption = qm::entity::encrypted::Config::new()?;
+    let keyring = qm::entity::encrypted::Keyring::new(&encryption)
+        .context("ENCRYPTION_KEYS must be configured")?;
+    qm::entity::encrypted::set_keyring(keyring);
     let store = Storage::new().await?;
-    let address = store.server_config().address().to_string();
-    let router = router(store).await;
-    let listener = tokio::net::TcpListener::bind(address).await.unwrap();
-    axum::serve(listener, router).await.unwrap();
-    Ok(())
+    let workers = Workers::new(store.redis().config())?;
+    worker::run(
+        &workers,
+        worker::CleanupWorkerCtx::<Authorization, Storage, Resource, Permission>::new(
+            store.clone(),
+        ),
+        1,
+    )
+    .await?;
+    let expiry = worker::schedule_api_client_expiry(store.clone(), API_CLIENT_EXPIRY_INTERVAL);
+    let router = router(store.clone()).await;
+    let result = qm::server::serve(router, store.server_config()).await;
+    expiry.abort();
+    workers.terminate().await?;
+    result
 }
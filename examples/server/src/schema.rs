@@ -2,7 +2,6 @@ use async_graphql::{EmptySubscription, MergedObject, Object};
 use qm::{
     customer::schema::{QmCustomerMutationRoot, QmCustomerQueryRoot},
     entity::ids::InstitutionResourceId,
-    role::AuthContainer,
 };
 
 use qm_example_auth::{
@@ -81,17 +80,10 @@ impl SchemaBuilder {
     }
 
     pub fn build(self, store: Storage) -> Schema {
-        let mut s = async_graphql::Schema::build(
-            QueryRoot::default(),
-            MutationRoot::default(),
-            EmptySubscription,
-        )
-        .data(store);
-        if let Some(access_token) = self.access_token {
-            s = s.data(AuthContainer::<Authorization>::new(&access_token));
-        } else {
-            s = s.data(AuthContainer::<Authorization>::default());
-        }
-        s.finish()
+        qm::server::SchemaBuilder::default()
+            .with_hardening(store.schema_hardening().clone())
+            .with_storage(store)
+            .with_access_token::<Authorization>(self.access_token.as_deref())
+            .build()
     }
 }